@@ -3,24 +3,35 @@ use std::cmp;
 use std::ops;
 use std::str;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, BTreeSet};
 use take_mut::take;
 
 use kailua_env::{Span, Spanned, WithLoc};
-use kailua_diag::{self, Result, Report, Reporter};
+use kailua_diag::{self, Result, Localize, Report, Reporter};
 use kailua_syntax::{Str, Name};
+use kailua_syntax::lang::Lua;
 use kailua_syntax::ast::{self, NameRef, Var, TypeSpec, Kind, Sig, Ex, Exp, UnOp, BinOp, Table};
 use kailua_syntax::ast::{SelfParam, TypeScope, Args, St, Stmt, Block, K, Attr, M, MM, Varargs};
 use kailua_types::diag::{TypeReport, TypeReportHint, TypeReportMore};
 use kailua_types::ty::{Displayed, Display, TypeContext, TypeResolver};
-use kailua_types::ty::{Dyn, Nil, T, Ty, TySeq, SpannedTySeq, Lattice, Union, Dummy};
-use kailua_types::ty::{Key, Tables, Function, Functions};
-use kailua_types::ty::{F, Slot, SlotSeq, SpannedSlotSeq, Tag, Class, ClassId};
+use kailua_types::ty::{Dyn, Nil, T, Ty, TySeq, SpannedTySeq, Lattice, Union, Unioned, Dummy};
+use kailua_types::ty::{Key, Tables, Function, Functions, Coroutine, TParam, Substitution};
+use kailua_types::ty::ComplexityLimit;
+use kailua_types::ty::{F, Slot, SlotSeq, SpannedSlotSeq, Tag, TagStrId, Class, ClassId, Strings};
 use kailua_types::ty::flags::*;
 use kailua_types::env::Types;
-use env::{Env, Returns, Frame, Scope, Module, Context, SlotSpec};
+use options::DynamicRequire;
+use env::{Env, Returns, Frame, Scope, Module, Context, SlotSpec, Interface};
+use env::{Assumption, AssumptionKind, SavedVar};
 use class_system::make_predefined_class_system;
+use stats::StatsScope;
 use message as m;
+use unused::{find_unused, UnusedKind};
+use shadow::{find_shadowing, Shadowed};
+use lint::{find_lint, is_diagnostic_code};
+use lint::rules::{self as lint_rules, Violation};
+use ignore::find_suppressions;
+use ops::{OpRules, Coercion, NumericString, parse_numeric_string};
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 enum Exit {
@@ -156,6 +167,29 @@ enum Cond {
     Not(Box<Cond>),
 }
 
+// a flow-sensitive narrowing in effect, undone by `Checker::restore_narrowed`
+#[derive(Clone, Debug)]
+enum Narrowing {
+    // the type of the slot has been replaced, and the original type is kept
+    Slot(Slot, Ty),
+    // a field of the local variable has been narrowed to given slot
+    Field(NameRef, Key, Slot),
+    // the narrowing has been invalidated by an assignment and should not be undone
+    Invalidated,
+}
+
+// a syntactic condition usable for the narrowing, collected by `Checker::narrow_by_cond`
+enum NarrowCond<'a> {
+    // `x` or `x.key` is truthy (or falsy if negated)
+    Truthy(&'a NameRef, Option<Key>),
+    // `x ~= nil` or `x.key ~= nil` (or `== nil` if negated)
+    NonNil(&'a NameRef, Option<Key>),
+    // `x == "lit"` or `x.key == "lit"` (or `~=` if negated)
+    Literal(&'a NameRef, Option<Key>, &'a Str),
+    // `f(x) == "lit"` or `f(x.key) == "lit"` (or `~=` if negated) where `f` may be `type`
+    TypeOf(&'a NameRef, &'a NameRef, Option<Key>, &'a Str),
+}
+
 #[derive(Copy, Clone, Debug)]
 enum Bool {
     Unknown,
@@ -163,6 +197,14 @@ enum Bool {
     Falsy,
 }
 
+// a single literal value known at the checking time, used to fold comparisons
+#[derive(Clone, Debug, PartialEq)]
+enum Literal {
+    Boolean(bool),
+    Integer(i32),
+    String(Str),
+}
+
 #[derive(Clone, Debug)]
 enum Index {
     Missing, // field not found, creation not requested
@@ -176,6 +218,59 @@ impl Index {
     }
 }
 
+// true if the expression is `package.seeall`, which makes globals visible in the module mode
+fn is_package_seeall(exp: &Spanned<Exp>) -> bool {
+    if let Ex::IndexName(ref e, ref key) = *exp.base {
+        if let Ex::Var(Spanned { base: NameRef::Global(ref name), .. }) = *e.base {
+            return &name[..] == b"package" && &key.base[..] == b"seeall";
+        }
+    }
+    false
+}
+
+// returns a variable `x` or a field `x.key` of the variable, which can be narrowed
+fn narrow_path_of(exp: &Spanned<Exp>) -> Option<(&NameRef, Option<Key>)> {
+    let (var, key) = match *exp.base {
+        Ex::Var(ref name) => return Some((&name.base, None)),
+        Ex::IndexName(ref e, ref key) => {
+            (e, Key::from(Str::from(key.base[..].to_owned())))
+        }
+        Ex::Index(ref e, Spanned { base: ref key, .. }) => match **key {
+            Ex::Str(ref key) => (e, Key::from(key.clone())),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    if let Ex::Var(ref name) = *var.base {
+        Some((&name.base, Some(key)))
+    } else {
+        None
+    }
+}
+
+// converts a type name returned by `type` to flags. `ext` allows names only accepted by
+// the `[assert_type]` function: `integer` (or `int`) and the `?` suffix for an optional type.
+fn type_name_to_flags(tyname: &[u8], ext: bool) -> Option<Flags> {
+    let (tyname, nilflags) = if ext && tyname.ends_with(b"?") {
+        (&tyname[..tyname.len()-1], T_NOISY_NIL)
+    } else {
+        (tyname, T_NONE)
+    };
+    let flags = match tyname {
+        b"nil" => T_NOISY_NIL,
+        b"int" | b"integer" if ext => T_INTEGER, // XXX the real impl should follow
+        b"number" => T_NUMBER,
+        b"string" => T_STRING,
+        b"boolean" => T_BOOLEAN,
+        b"table" => T_TABLE,
+        b"function" => T_FUNCTION,
+        b"thread" => T_THREAD,
+        b"userdata" => T_USERDATA,
+        _ => return None,
+    };
+    Some(nilflags | flags)
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 enum NoCheck {
     User, // user-requested
@@ -224,12 +319,81 @@ impl<'inp> PendingModules<'inp> {
 pub struct Checker<'inp, 'envr, 'env: 'envr, R: 'env> {
     env: &'envr mut Env<'env, R>,
     pending_modules: Vec<PendingModules<'inp>>,
+
+    // a stack of narrowings in effect, innermost last
+    narrowed: Vec<Narrowing>,
+
+    // local variables initialized from a field of other local variable (`local t = x.key`),
+    // as long as neither has been assigned since then
+    aliases: Vec<(NameRef, NameRef, Key)>,
+
+    // a stack of types yielded (via `[coroutine_yield]`) by function bodies being checked
+    yields: Vec<Option<Ty>>,
+
+    // types yielded by checked function bodies, keyed by the span of their declarations
+    body_yields: HashMap<Span, Option<Ty>>,
+
+    // spans of function declarations bound to variables, as long as they are not assigned
+    func_decls: HashMap<NameRef, Span>,
+
+    // the span of the last `[pcall]` or `[xpcall]` call with return types on success
+    // and the type of the error value on failure, to be bound to local variables
+    pcall_results: Option<(Span, TySeq, Ty)>,
+
+    // local variables holding results of protected calls, narrowed by the boolean variable.
+    // each result has a type on success and optionally a type on failure
+    pcalls: Vec<(NameRef, Vec<(NameRef, Ty, Option<Ty>)>)>,
+
+    // spans of index expressions reading from weak tables
+    weak_reads: HashSet<Span>,
+
+    // spans of calls to `[nodiscard]` functions
+    nodiscard_calls: HashSet<Span>,
+
+    // true while visiting the top-level `return` of the file with a variable as the first value;
+    // returning a `[deprecated]` variable there deprecates the module and is not a use
+    returning_module: bool,
+
+    // the span of the last `[geval]` call and whether it has been reported for global accesses.
+    // after that call global variables are dynamic, except for those in `reasserted_globals`
+    global_eval: Option<(Span, bool)>,
+
+    // global variables assigned after the last `[geval]` call
+    reasserted_globals: HashSet<Name>,
+
+    // the module table made by the `[become_module]` call and whether `package.seeall` is given.
+    // global variables are read from and written to that table instead
+    module_table: Option<(Spanned<Slot>, bool)>,
+
+    // the number of table constructors being visited, see `Options::table_depth_limit`
+    table_depth: usize,
+
+    // a stack of variables assumed with `--# assume scoped`, to be restored at the block end
+    scoped_assumptions: Vec<SavedVar>,
 }
 
 impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
     /// Creates a new checker from the per-file context.
     pub fn new(env: &'envr mut Env<'env, R>) -> Checker<'inp, 'envr, 'env, R> {
-        Checker { env: env, pending_modules: Vec::new() }
+        Checker {
+            env: env,
+            pending_modules: Vec::new(),
+            narrowed: Vec::new(),
+            aliases: Vec::new(),
+            yields: Vec::new(),
+            body_yields: HashMap::new(),
+            func_decls: HashMap::new(),
+            pcall_results: None,
+            pcalls: Vec::new(),
+            weak_reads: HashSet::new(),
+            nodiscard_calls: HashSet::new(),
+            returning_module: false,
+            global_eval: None,
+            reasserted_globals: HashSet::new(),
+            module_table: None,
+            table_depth: 0,
+            scoped_assumptions: Vec::new(),
+        }
     }
 
     fn types(&mut self) -> &mut Types {
@@ -271,6 +435,21 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
         }
     }
 
+    fn literal_of(&self, info: &Slot) -> Option<Literal> {
+        let ty = info.unlift();
+        if let Some(s) = ty.as_string() {
+            return Some(Literal::String(s.clone()));
+        }
+        if let Some(v) = ty.as_integer() {
+            return Some(Literal::Integer(v));
+        }
+        match self.env.get_type_bounds(&ty) {
+            (T_TRUE, T_TRUE) => Some(Literal::Boolean(true)),
+            (T_FALSE, T_FALSE) => Some(Literal::Boolean(false)),
+            _ => None,
+        }
+    }
+
     fn check_un_op(&mut self, op: UnOp, info: &Spanned<Slot>, expspan: Span) -> Result<Slot> {
         let finalize = |r: TypeReport, checker: &mut Checker<R>| {
             checker.env.error(expspan,
@@ -289,6 +468,15 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             }
         }
 
+        let rules = self.op_rules();
+        if let Some(name) = rules.unary_metamethod(op) {
+            if let Some(ty) = self.check_metamethod_op(name, &[info], false, expspan)? {
+                return Ok(Slot::just(ty));
+            }
+        }
+
+        let info = &self.coerce_string_operand(op.symbol(), rules.unary_coercion(op), info)?;
+
         match op {
             UnOp::Neg => {
                 assert_sub!(&info, &T::Number);
@@ -312,11 +500,548 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 assert_sub!(&info, &(T::table() | T::String));
                 Ok(Slot::just(Ty::new(T::Integer)))
             }
+
+            UnOp::BNot => {
+                // floats with an exact integer representation are also accepted by Lua,
+                // but they can't be distinguished from other floats
+                assert_sub!(&info, &T::Integer);
+                Ok(Slot::just(Ty::new(T::Integer)))
+            }
+        }
+    }
+
+    // returns string literals of the type if it is a union of string literals (an "enum"),
+    // e.g. declared with `--# type Direction = "n" | "s" | "e" | "w"`
+    fn enum_strings(&self, ty: &Ty) -> Option<BTreeSet<Str>> {
+        let ty = self.env.resolve_exact_type(ty)?;
+        if let T::Union(ref u) = *ty {
+            if (u.flags() & !T_STRING).is_empty() {
+                if let Some(Strings::Some(ref strs)) = u.strings {
+                    return Some(strs.clone());
+                }
+            }
+        }
+        None
+    }
+
+    // warns when an enum type is compared to a string literal outside of the enum
+    fn check_enum_comparison(&mut self, lhs: &Spanned<Slot>, op: BinOp, rhs: &Spanned<Slot>,
+                             expspan: Span) -> Result<()> {
+        for &(enumslot, litslot) in &[(lhs, rhs), (rhs, lhs)] {
+            let lit = if let Some(lit) = litslot.unlift().as_string() {
+                lit.clone()
+            } else {
+                continue;
+            };
+            let enumty = enumslot.unlift().clone();
+            if self.enum_strings(&enumty).map_or(false, |strs| !strs.contains(&lit)) {
+                let msg = m::ImpossibleEnumComparison {
+                    ty: self.display(&enumty), lit: &lit, result: op == BinOp::Ne,
+                };
+                self.env.warn(expspan, msg).done()?;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // reports `==` or `~=` between different primitive types if enabled (ignoring nils)
+    fn check_mixed_type_equality(&mut self, lhs: &Spanned<Slot>, op: BinOp, rhs: &Spanned<Slot>,
+                                 expspan: Span) -> Result<()> {
+        fn primitive(flags: Flags) -> Option<Flags> {
+            let flags = flags & !T_NOISY_NIL;
+            if flags.is_dynamic() || flags.is_empty() {
+                return None;
+            }
+            [T_BOOLEAN, T_NUMBER, T_STRING].iter().cloned().find(|&t| !flags.intersects(!t))
+        }
+
+        let kind = match self.env.opts().borrow().lint_severity("mixed-type-equality").to_kind() {
+            Some(kind) => kind,
+            None => return Ok(()),
+        };
+
+        let lprim = primitive(self.env.get_type_bounds(&lhs.unlift()).1);
+        let rprim = primitive(self.env.get_type_bounds(&rhs.unlift()).1);
+        if let (Some(l), Some(r)) = (lprim, rprim) {
+            if l != r {
+                let msg = m::MixedTypeEquality {
+                    op: op.symbol(), lhs: self.display(lhs), rhs: self.display(rhs),
+                };
+                self.env.add_lint_span("mixed-type-equality", kind, expspan, &msg)?;
+            }
+        }
+        Ok(())
+    }
+
+    // warns when the `if` statement without `else` dispatches over an enum-typed variable
+    // (`if v == "a" then ... elseif v == "b" or v == "c" then ... end`) but misses some cases
+    fn check_enum_dispatch(&mut self,
+                           conds: &'inp [Spanned<(Spanned<Exp>, Spanned<Block>)>]) -> Result<()> {
+        fn collect_cases<'a>(exp: &'a Spanned<Exp>, var: &mut Option<&'a NameRef>,
+                             cases: &mut Vec<&'a Str>) -> bool {
+            match *exp.base {
+                Ex::Exp(ref e) => collect_cases(e, var, cases),
+                Ex::Bin(ref l, Spanned { base: BinOp::Or, .. }, ref r) => {
+                    collect_cases(l, var, cases) && collect_cases(r, var, cases)
+                }
+                Ex::Bin(ref l, Spanned { base: BinOp::Eq, .. }, ref r) => {
+                    let (name, lit) = match (&*l.base, &*r.base) {
+                        (&Ex::Var(ref name), &Ex::Str(ref lit)) |
+                        (&Ex::Str(ref lit), &Ex::Var(ref name)) => (&name.base, lit),
+                        _ => return false,
+                    };
+                    if var.map_or(false, |var| var != name) {
+                        return false;
+                    }
+                    *var = Some(name);
+                    cases.push(lit);
+                    true
+                }
+                _ => false,
+            }
+        }
+
+        let mut var = None;
+        let mut cases = Vec::new();
+        for cond in conds {
+            if !collect_cases(&cond.base.0, &mut var, &mut cases) {
+                return Ok(());
+            }
+        }
+
+        let slot = var.and_then(|var| self.env.get_var(var))
+                      .and_then(|def| def.slot.slot().cloned());
+        let ty = if let Some(slot) = slot { slot.unlift().clone() } else { return Ok(()) };
+        let strs = if let Some(strs) = self.enum_strings(&ty) { strs } else { return Ok(()) };
+        let missing: Vec<_> = strs.iter().filter(|s| !cases.contains(s))
+                                  .map(|s| s.to_string()).collect();
+        if !missing.is_empty() {
+            let msg = m::NonExhaustiveEnumDispatch {
+                ty: self.display(&ty), missing: &missing.join(", "),
+            };
+            // the statement can be very long, so the first condition is reported instead
+            self.env.warn(&conds[0].base.0, msg).done()?;
+        }
+        Ok(())
+    }
+
+    // narrows local variables of discriminated union types in given condition,
+    // e.g. `x.kind == "circle"` narrows `x` to records with `kind: "circle"`.
+    // the condition is assumed to be true, or false if `negated` is set.
+    // returns slots and their previous types so that they can be restored later.
+    // narrows variables and fields of local variables by a syntactic condition,
+    // which may be negated. the caller should remember the length of `self.narrowed`
+    // and restore it with `restore_narrowed` when the condition no longer holds.
+    //
+    // handles `x`, `x ~= nil` (and `== nil` when negated), `x == "lit"`, `x ~= "lit"`,
+    // `type(x) == "lit"` and `type(x) ~= "lit"` where `x` is a variable or `var.key`,
+    // possibly combined with `not`, `and` and `or`.
+    fn narrow_by_cond(&mut self, exp: &Spanned<Exp>, negated: bool) {
+        fn call(exp: &Spanned<Exp>) -> Option<(&NameRef, &NameRef, Option<Key>)> {
+            if let Ex::FuncCall(ref func, ref args) = *exp.base {
+                if let (&Ex::Var(ref func), &Args::List(ref args)) = (&*func.base, &args.base) {
+                    if args.len() == 1 {
+                        let (name, key) = narrow_path_of(&args[0])?;
+                        return Some((&func.base, name, key));
+                    }
+                }
+            }
+            None
+        }
+
+        fn collect<'a>(exp: &'a Spanned<Exp>, negated: bool,
+                       out: &mut Vec<(NarrowCond<'a>, bool)>) {
+            match *exp.base {
+                Ex::Exp(ref e) => collect(e, negated, out),
+                Ex::Un(Spanned { base: UnOp::Not, .. }, ref e) => collect(e, !negated, out),
+                Ex::Bin(ref l, Spanned { base: BinOp::And, .. }, ref r) if !negated => {
+                    collect(l, negated, out);
+                    collect(r, negated, out);
+                }
+                Ex::Bin(ref l, Spanned { base: BinOp::Or, .. }, ref r) if negated => {
+                    collect(l, negated, out);
+                    collect(r, negated, out);
+                }
+                Ex::Bin(ref l, Spanned { base: op @ BinOp::Eq, .. }, ref r) |
+                Ex::Bin(ref l, Spanned { base: op @ BinOp::Ne, .. }, ref r) => {
+                    let negated = negated != (op == BinOp::Ne);
+                    match (&*l.base, &*r.base) {
+                        (_, &Ex::Str(ref lit)) | (&Ex::Str(ref lit), _) => {
+                            let other = if let Ex::Str(_) = *l.base { r } else { l };
+                            if let Some((name, key)) = narrow_path_of(other) {
+                                out.push((NarrowCond::Literal(name, key, lit), negated));
+                            } else if let Some((func, name, key)) = call(other) {
+                                out.push((NarrowCond::TypeOf(func, name, key, lit), negated));
+                            }
+                        }
+                        (_, &Ex::Nil) | (&Ex::Nil, _) => {
+                            let other = if let Ex::Nil = *l.base { r } else { l };
+                            if let Some((name, key)) = narrow_path_of(other) {
+                                out.push((NarrowCond::NonNil(name, key), !negated));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {
+                    if let Some((name, key)) = narrow_path_of(exp) {
+                        out.push((NarrowCond::Truthy(name, key), negated));
+                    }
+                }
+            }
+        }
+
+        let mut conds = Vec::new();
+        collect(exp, negated, &mut conds);
+
+        for (cond, negated) in conds {
+            match cond {
+                NarrowCond::Truthy(name, None) if self.pcall_of(name).is_some() => {
+                    self.narrow_pcall_results(name, negated);
+                    if !negated {
+                        self.narrow_path(name, None, true);
+                    }
+                }
+
+                NarrowCond::Literal(name, Some(key), lit) => {
+                    self.narrow_var_by_discriminant(name, &key, lit, negated);
+                }
+                NarrowCond::Literal(name, None, lit) => {
+                    // `local k = x.kind; if k == "lit" then ... end` narrows `x`
+                    if let Some((base, key)) = self.alias_of(name) {
+                        self.narrow_var_by_discriminant(&base, &key, lit, negated);
+                    }
+                }
+
+                NarrowCond::TypeOf(func, name, key, lit) => {
+                    let functy = self.env.get_var(func).and_then(|def| def.slot.slot().cloned());
+                    if functy.map_or(true, |f| f.unlift().tag() != Some(Tag::Type)) {
+                        continue;
+                    }
+                    if let Some(flags) = type_name_to_flags(lit, false) {
+                        let flags = if negated { !flags } else { flags };
+                        self.narrow_path_with(name, key, &|ty, ctx| {
+                            ty.filter_by_flags(flags, ctx).ok()
+                        });
+                    }
+                }
+
+                // only the positive conditions are useful for the narrowing
+                NarrowCond::Truthy(name, key) if !negated => self.narrow_path(name, key, true),
+                NarrowCond::NonNil(name, key) if !negated => self.narrow_path(name, key, false),
+                NarrowCond::Truthy(..) | NarrowCond::NonNil(..) => {}
+            }
+        }
+    }
+
+    // narrows a variable `name` or its field `name.key` to truthy or non-nil types
+    fn narrow_path(&mut self, name: &NameRef, key: Option<Key>, truthy: bool) {
+        self.narrow_path_with(name, key, &|ty, ctx| {
+            if truthy {
+                ty.filter_by_flags(T_TRUTHY, ctx).ok()
+            } else {
+                Some(ty.without_nil())
+            }
+        });
+    }
+
+    // narrows a variable `name` or its field `name.key` with given function,
+    // which returns a narrowed type or `None` if the type cannot be narrowed
+    fn narrow_path_with(&mut self, name: &NameRef, key: Option<Key>,
+                        narrow: &Fn(Ty, &mut TypeContext) -> Option<Ty>) {
+        let (base, key) = if let Some(key) = key {
+            (name.clone(), key)
+        } else {
+            let slot = self.env.get_var(name).and_then(|def| def.slot.slot().cloned());
+            if let Some(slot) = slot {
+                let ty = slot.unlift().clone();
+                if let Some(newty) = narrow(ty, self.types()) {
+                    trace!("narrowing {:?} to {:?}", slot, newty);
+                    let ty = slot.replace_ty(newty);
+                    self.narrowed.push(Narrowing::Slot(slot, ty));
+                }
+            }
+
+            // `local t = x.key; if t then ... end` also narrows `x.key`
+            if let Some(alias) = self.alias_of(name) {
+                alias
+            } else {
+                return;
+            }
+        };
+
+        if let Some(field) = self.field_of_local(&base, &key) {
+            let ty = field.unlift().clone();
+            if let Some(newty) = narrow(ty, self.types()) {
+                trace!("narrowing the field {:?} of {:?} to {:?}", key, base, newty);
+                let newslot = Slot::new(field.flex(), newty);
+                self.narrowed.push(Narrowing::Field(base, key, newslot));
+            }
+        }
+    }
+
+    // returns the results of a protected call narrowed by a boolean variable `name`
+    fn pcall_of(&self, name: &NameRef) -> Option<&[(NameRef, Ty, Option<Ty>)]> {
+        self.pcalls.iter().rev().find(|&&(ref ok, _)| *ok == *name)
+                   .map(|&(_, ref results)| &results[..])
+    }
+
+    // narrows results of a protected call to the types on success (or failure if `negated`)
+    fn narrow_pcall_results(&mut self, name: &NameRef, negated: bool) {
+        let results = self.pcall_of(name).map_or(Vec::new(), |results| results.to_owned());
+        for (result, success, failure) in results {
+            let newty = if negated {
+                if let Some(failure) = failure { failure } else { continue; }
+            } else {
+                success
+            };
+            let slot = self.env.get_var(&result).and_then(|def| def.slot.slot().cloned());
+            if let Some(slot) = slot {
+                trace!("narrowing {:?} to {:?}", slot, newty);
+                let ty = slot.replace_ty(newty);
+                self.narrowed.push(Narrowing::Slot(slot, ty));
+            }
+        }
+    }
+
+    fn narrow_var_by_discriminant(&mut self, name: &NameRef, key: &Key, lit: &Str,
+                                  negated: bool) {
+        let slot = self.env.get_var(name).and_then(|def| def.slot.slot().cloned());
+        if let Some(slot) = slot {
+            let ty = slot.unlift().clone();
+            if let Some(newty) = self.narrow_discriminated_union(&ty, key, lit, negated) {
+                trace!("narrowing {:?} to {:?}", ty, newty);
+                let ty = slot.replace_ty(newty);
+                self.narrowed.push(Narrowing::Slot(slot, ty));
+            }
+        }
+    }
+
+    // undoes all narrowings made after `self.narrowed` had given length
+    fn restore_narrowed(&mut self, len: usize) {
+        while self.narrowed.len() > len {
+            match self.narrowed.pop() {
+                Some(Narrowing::Slot(slot, ty)) => { slot.replace_ty(ty); }
+                Some(Narrowing::Field(..)) | Some(Narrowing::Invalidated) | None => {}
+            }
+        }
+    }
+
+    // returns the variable and key that given local variable has been initialized from
+    fn alias_of(&self, name: &NameRef) -> Option<(NameRef, Key)> {
+        self.aliases.iter().rev().find(|&&(ref alias, _, _)| *alias == *name)
+                    .map(|&(_, ref base, ref key)| (base.clone(), key.clone()))
+    }
+
+    // returns the current slot of a record field of the local variable, possibly narrowed
+    fn field_of_local(&mut self, name: &NameRef, key: &Key) -> Option<Slot> {
+        if let NameRef::Global(_) = *name {
+            return None; // globals can be updated by any function call
+        }
+        if let Some(slot) = self.narrowed_field(name, key) {
+            return Some(slot);
+        }
+
+        let slot = self.env.get_var(name).and_then(|def| def.slot.slot().cloned())?;
+        let ty = slot.unlift().clone();
+        if let T::Tables(ref tab) = *ty {
+            if let Tables::Fields(ref rvar) = **tab {
+                return self.types().get_rvar_fields(rvar.clone()).into_iter()
+                                   .find(|&(ref k, _)| *k == *key).map(|(_, v)| v);
+            }
+        }
+        None
+    }
+
+    // returns the narrowed slot of `name.key` if any
+    fn narrowed_field(&self, name: &NameRef, key: &Key) -> Option<Slot> {
+        for narrowing in self.narrowed.iter().rev() {
+            if let Narrowing::Field(ref n, ref k, ref slot) = *narrowing {
+                if *n == *name && *k == *key {
+                    return Some(slot.clone());
+                }
+            }
+        }
+        None
+    }
+
+    // called before the assignment to the variable `name`, or to its field `key`
+    // (`None` for all fields); no longer narrows them and restores the original type,
+    // so that the assignment is checked against the declared type.
+    fn invalidate_narrowed(&mut self, name: &NameRef, key: Option<&Key>) {
+        let slot = if key.is_none() {
+            self.env.get_var(name).and_then(|def| def.slot.slot().cloned())
+        } else {
+            None
+        };
+
+        let mut restored = false;
+        for narrowing in &mut self.narrowed {
+            let invalidate = match *narrowing {
+                Narrowing::Slot(ref s, ref ty) => {
+                    let matches = slot.as_ref().map_or(false, |slot| {
+                        &*s.unlift() as *const _ == &*slot.unlift() as *const _
+                    });
+                    if matches && !restored {
+                        s.replace_ty(ty.clone());
+                        restored = true;
+                    }
+                    matches
+                }
+                Narrowing::Field(ref n, ref k, _) => {
+                    *n == *name && key.map_or(true, |key| *k == *key)
+                }
+                Narrowing::Invalidated => false,
+            };
+            if invalidate {
+                *narrowing = Narrowing::Invalidated;
+            }
         }
+
+        self.aliases.retain(|&(ref alias, ref base, ref k)| {
+            if key.is_none() && *alias == *name {
+                return false;
+            }
+            !(*base == *name && key.map_or(true, |key| *k == *key))
+        });
+    }
+
+    // returns a resolved function type if it has a known signature
+    fn simple_func_of(&mut self, func: Spanned<Ty>) -> Option<Spanned<Ty>> {
+        let functy = self.env.resolve_exact_type(&func)?;
+        match functy.get_functions() {
+            Some(&Functions::Simple(_)) if !functy.is_dynamic() => {}
+            _ => return None,
+        }
+        Some(functy.with_loc(&func))
+    }
+
+    // returns a coroutine type for a function expression to be used as a coroutine body.
+    // the body should have been checked, so that yielded types are known
+    fn coroutine_from_func(&mut self, exp: &Spanned<Exp>, functy: &Ty) -> Option<Coroutine> {
+        let declspan = match *exp.base {
+            Ex::Func(..) => exp.span,
+            Ex::Var(ref name) => *self.func_decls.get(&name.base)?,
+            _ => return None,
+        };
+        let yields = self.body_yields.get(&declspan)?.clone();
+
+        let functy = self.env.resolve_exact_type(functy)?;
+        let func = match functy.get_functions() {
+            Some(&Functions::Simple(ref func)) if !func.is_generic() => func,
+            _ => return None,
+        };
+
+        // the first resumption passes values as arguments, and subsequent ones via `yield`
+        // (variadic values can be always omitted)
+        let first = |seq: &TySeq| {
+            let tail = seq.tail.as_ref().map(|t| t.clone().union_nil(Nil::Noisy));
+            seq.head.first().cloned().or(tail)
+        };
+        let resumes = first(&func.args).unwrap_or(Ty::new(T::All).union_nil(Nil::Noisy));
+
+        // the final return is also passed to the resuming code
+        let returns = func.returns.as_ref().map(|seq| first(seq).unwrap_or(Ty::silent_nil()));
+        let yields = match (yields, returns) {
+            (Some(yields), Some(returns)) => yields.union(&returns, false, self.types()).ok()?,
+            (Some(ty), None) | (None, Some(ty)) => ty,
+            (None, None) => Ty::silent_nil(),
+        };
+        Some(Coroutine::new(yields.coerce(), resumes))
+    }
+
+    // returns a discriminated union type restricted to records where the discriminant `key`
+    // has (or does not have, if `negated`) a string literal type `lit`
+    fn narrow_discriminated_union(&mut self, ty: &Ty, key: &Key, lit: &Str,
+                                  negated: bool) -> Option<Ty> {
+        let u = if let T::Union(ref u) = **ty { u } else { return None };
+        if Unioned::discriminant(&u.records, self.types()).as_ref() != Some(key) {
+            return None;
+        }
+
+        let records: Vec<_> = u.records.iter().filter(|r| {
+            let value = Unioned::discriminant_value(r, key, self.types());
+            (value.as_ref() == Some(lit)) != negated
+        }).cloned().collect();
+        if records.is_empty() || records.len() == u.records.len() {
+            return None;
+        }
+
+        // the positive condition implies that the value is a table with that discriminant
+        let mut newu = if negated { u.clone().into_owned() } else { Unioned::empty() };
+        if records.len() == 1 {
+            newu.tables = Some(Tables::Fields(records[0].clone()));
+            newu.records = Vec::new();
+        } else {
+            newu.records = records;
+        }
+        newu.filter_display_hints(T_TABLE);
+        Some(Ty::new(newu.simplify()).or_nil(ty.nil()))
+    }
+
+    // returns a metamethod of given name if the operand is a class instance
+    // or a record with a tracked metatable
+    fn find_metamethod(&mut self, info: &Spanned<Slot>, name: &[u8]) -> Result<Option<Ty>> {
+        let ty = if let Some(ty) = self.env.resolve_exact_type(&info.unlift()) {
+            ty
+        } else {
+            return Ok(None);
+        };
+
+        let key = Key::from(Str::from(name.to_owned()));
+        let slot = if let T::Class(Class::Instance(cid)) = *ty {
+            self.context().index_class_rval(Class::Instance(cid), (&key).with_loc(info),
+                                            info.span)?
+        } else {
+            self.env.context().get_metamethod(&info, &key)
+        };
+        Ok(slot.map(|slot| slot.unlift().clone()))
+    }
+
+    // checks the call to the metamethod found from given operands (in order) and
+    // returns the result, or returns `None` if the operator should be checked as usual
+    fn check_metamethod_op(&mut self, name: &[u8], operands: &[&Spanned<Slot>], both: bool,
+                           expspan: Span) -> Result<Option<Ty>> {
+        let mut meta = None;
+        for operand in operands {
+            match self.find_metamethod(operand, name)? {
+                Some(ty) => { meta = meta.or(Some(ty)); }
+                // comparison metamethods are only used when both operands have them
+                None if both => return Ok(None),
+                None => {}
+            }
+        }
+        let meta = if let Some(meta) = meta { meta } else { return Ok(None); };
+
+        let args = SpannedTySeq {
+            head: operands.iter().map(|info| info.unlift().clone().with_loc(*info)).collect(),
+            tail: None,
+            span: expspan,
+        };
+        let Exitable(_, returns) = self.check_callable(&meta.with_loc(expspan), &args, false)?;
+        Ok(Some(returns.into_first()))
     }
 
     fn check_bin_op(&mut self, lhs: &Spanned<Slot>, op: BinOp, rhs: &Spanned<Slot>,
                     expspan: Span) -> Result<Slot> {
+        // operands with metamethods (e.g. `__add` for `+`) are checked against them.
+        // `a > b` and `a >= b` are same to `b < a` and `b <= a` respectively.
+        let rules = self.op_rules();
+        if let Some(meta) = rules.binary_metamethod(op) {
+            let operands = if meta.swapped { [rhs, lhs] } else { [lhs, rhs] };
+            if let Some(ty) = self.check_metamethod_op(meta.name, &operands, meta.both,
+                                                       expspan)? {
+                // comparisons always result in a boolean
+                let ty = match op {
+                    BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge |
+                    BinOp::Eq | BinOp::Ne => Ty::new(T::Boolean),
+                    _ => ty,
+                };
+                return Ok(Slot::just(ty));
+            }
+        }
+
         let finalize = |r: TypeReport, checker: &mut Checker<R>| {
             checker.env.error(expspan,
                               m::WrongBinaryOperands { op: op.symbol(),
@@ -359,8 +1084,16 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             }
         }
 
+        // string literals may be converted to numbers from now on
+        // (`finalize` and `finalize2` still display the original operands)
+        let coercion = rules.binary_coercion(op);
+        let lhs = &self.coerce_string_operand(op.symbol(), coercion, lhs)?;
+        let rhs = &self.coerce_string_operand(op.symbol(), coercion, rhs)?;
+
         match op {
-            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Mod => {
+            // `//` (Lua 5.3+) results in an integer only when both operands are integers,
+            // while `/` always results in a float there
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Mod | BinOp::IDiv => {
                 // ? + integer = integer, ? + number = ? + ? = number, number + integer = number
                 // see UnOp::Neg comment for the rationale
                 let lflags = self.env.get_type_bounds(&lhs.unlift()).1;
@@ -384,6 +1117,12 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 Ok(Slot::just(Ty::new(T::Number)))
             }
 
+            BinOp::BAnd | BinOp::BOr | BinOp::BXor | BinOp::Shl | BinOp::Shr => {
+                // see UnOp::BNot comment for floats
+                assert_sub_both!(lhs, rhs, &T::Integer);
+                Ok(Slot::just(Ty::new(T::Integer)))
+            }
+
             BinOp::Cat => {
                 assert_sub_both!(lhs, rhs, &(T::Number | T::String));
 
@@ -466,6 +1205,17 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             }
 
             BinOp::Eq | BinOp::Ne => { // works for any types
+                self.check_enum_comparison(lhs, op, rhs, expspan)?;
+                self.check_mixed_type_equality(lhs, op, rhs, expspan)?;
+
+                // a constant compared against a literal is folded (e.g. `PLATFORM == "win"`),
+                // so that the dead branch elimination also applies to such conditions
+                if lhs.flex() == F::Const || rhs.flex() == F::Const {
+                    if let (Some(l), Some(r)) = (self.literal_of(lhs), self.literal_of(rhs)) {
+                        let equal = (l == r) == (op == BinOp::Eq);
+                        return Ok(Slot::just(Ty::new(if equal { T::True } else { T::False })));
+                    }
+                }
                 Ok(Slot::just(Ty::new(T::Boolean)))
             }
 
@@ -525,6 +1275,17 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
         // check if generalize(func.args) :> args and gather generalize(func.returns)
         let mut returns = match *functy.get_functions().unwrap() {
             Functions::Simple(ref f) => {
+                // generic functions are instantiated with type parameters inferred from args.
+                // parameters not inferred are made dynamic; this is common for constructors
+                // of generic classes, e.g. `Queue.new()` can be assigned to `Queue<integer>`.
+                let f = if f.is_generic() {
+                    let args = args.clone().unspan();
+                    let unbound = Ty::new(T::Dynamic(Dyn::User));
+                    Cow::Owned(f.instantiate(&args, &unbound, self.types()))
+                } else {
+                    Cow::Borrowed(f)
+                };
+
                 let generalize_tyseq = |seq: &TySeq, ctx: &mut TypeContext| {
                     let head = seq.head.iter().map(|t| t.clone().generalize(ctx)).collect();
                     let tail = seq.tail.as_ref().map(|t| t.clone().generalize(ctx));
@@ -614,6 +1375,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 let knil = k.clone().with_nil();
                 let v = v.unlift().clone().without_nil();
                 *returns.ensure_at_mut(0) = Ty::new(T::func(Function {
+                    type_params: Vec::new(),
                     args: TySeq { head: vec![tab.clone(), k.clone()], tail: None },
                     argnames: Vec::new(),
                     returns: Some(TySeq { head: vec![knil, v], tail: None }),
@@ -723,6 +1485,49 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 }
             }
 
+            // a discriminated union can be only read with fields common to all records
+            T::Union(ref u) if !u.records.is_empty() => {
+                let litkey = if let Some(key) = kty.as_integer() {
+                    Key::from(key)
+                } else if let Some(key) = kty.as_string() {
+                    Key::from(key)
+                } else {
+                    self.env.error(expspan,
+                                   m::IndexToRecWithUnknownStr { tab: self.display(&*ety0),
+                                                                 key: self.display(&kty) })
+                            .done()?;
+                    return Ok(Index::dummy());
+                };
+
+                if lval {
+                    self.env.error(&*ety0,
+                                   m::UpdateToDiscriminatedUnion { tab: self.display(&*ety0) })
+                            .done()?;
+                    return Ok(Index::dummy());
+                }
+
+                let mut fieldty: Option<Ty> = None;
+                for rvar in &u.records {
+                    let fields = self.types().get_rvar_fields(rvar.clone());
+                    let vslot = match fields.into_iter().find(|&(ref k, _)| *k == litkey) {
+                        Some((_, vslot)) => vslot,
+                        None => return Ok(Index::Missing),
+                    };
+                    let ty = vslot.unlift().clone();
+                    fieldty = Some(match fieldty {
+                        Some(prevty) => match prevty.union(&ty, true, self.types()) {
+                            Ok(ty) => ty,
+                            Err(_) => {
+                                self.cannot_index(expspan, &ety0, kty0)?;
+                                return Ok(Index::dummy());
+                            }
+                        },
+                        None => ty,
+                    });
+                }
+                return Ok(Index::Found(Slot::just(fieldty.unwrap())));
+            }
+
             T::Union(ref u) if !u.classes.is_empty() => {
                 // the union is assumed to be simplified, so even if `u.classes` has one type
                 // it is mixed with other types so it cannot be indexed.
@@ -821,14 +1626,36 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                         // should *not* extend the terminal rvar (from `list_rvar_fields`),
                         // since it has to be instantiated which we can't do without a ref
                         (None, true) => {
+                            // records from table constructors cannot be extended,
+                            // but recover by extending anyway
+                            if self.env.context().is_sealed(rvar) {
+                                self.env.error(expspan,
+                                               m::CannotAddFieldToSealed {
+                                                   tab: self.display(&ety0),
+                                                   key: self.display(kty0),
+                                               })
+                                        .note(expspan, m::SealedRecordHint {})
+                                        .done()?;
+                            }
                             let vslot = new_slot(F::Unknown, self.types());
                             check!(self.types().assert_rvar_includes(rvar.clone(),
                                                                      &[(litkey, vslot.clone())]));
                             (vslot, true)
                         },
 
-                        // the field does not exist and is used as an r-value, return nothing
-                        (None, false) => return Ok(Index::Missing),
+                        // the field does not exist and is used as an r-value;
+                        // it may be still available via metatables (not affected by the flex)
+                        (None, false) => {
+                            if let Some(vslot) = self.env.context().index_via_metatable(rvar,
+                                                                                        &litkey) {
+                                return Ok(Index::Found(vslot));
+                            }
+                            if let Some(func) = self.env.context().get_index_function(rvar) {
+                                let vslot = self.index_via_function(&func, &ety0, kty0, expspan)?;
+                                return Ok(Index::Found(vslot));
+                            }
+                            return Ok(Index::Missing);
+                        }
                     };
 
                     vslot.adapt(ety0.flex(), self.types());
@@ -864,8 +1691,14 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 Ok(Index::Found(value))
             },
 
-            Some(&Tables::Fields(..)) => {
+            Some(&Tables::Fields(ref rvar)) => {
                 assert!(!had_litkey);
+                if !lval {
+                    if let Some(func) = self.env.context().get_index_function(rvar) {
+                        let vslot = self.index_via_function(&func, &ety0, kty0, expspan)?;
+                        return Ok(Index::Found(vslot));
+                    }
+                }
                 self.env.error(expspan,
                                m::IndexToRecWithUnknownStr { tab: self.display(&*ety0),
                                                              key: self.display(&kty) })
@@ -875,7 +1708,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
 
             Some(&Tables::Array(ref value)) | Some(&Tables::ArrayN(ref value)) if intkey => {
                 if lval { value.adapt(ety0.flex(), self.types()); }
-                Ok(Index::Found((*value).clone().with_nil()))
+                Ok(Index::Found(self.missing_nil(value, lval)))
             },
 
             Some(&Tables::Array(..)) | Some(&Tables::ArrayN(..)) => {
@@ -889,7 +1722,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             Some(&Tables::Map(ref key, ref value)) => {
                 check!(kty.assert_sub(&**key, self.types()));
                 if lval { value.adapt(ety0.flex(), self.types()); }
-                Ok(Index::Found((*value).clone().with_nil()))
+                Ok(Index::Found(self.missing_nil(value, lval)))
             },
 
             Some(&Tables::All) => {
@@ -900,6 +1733,31 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
         }
     }
 
+    // reads a field missing from a record by calling the `__index` function of its metatable
+    // with the record and the key, so that the key is checked against the function signature
+    // (e.g. an enumeration of string literals) and the read results in the return type
+    fn index_via_function(&mut self, func: &Slot, ety: &Spanned<Slot>, kty: &Spanned<Slot>,
+                          expspan: Span) -> Result<Slot> {
+        let args = SpannedTySeq {
+            head: vec![ety.unlift().clone().with_loc(ety), kty.unlift().clone().with_loc(kty)],
+            tail: None,
+            span: expspan,
+        };
+        let func = func.unlift().clone().with_loc(expspan);
+        let Exitable(_, returns) = self.check_callable(&func, &args, false)?;
+        Ok(Slot::just(returns.into_first()))
+    }
+
+    // an array or map value as returned from indexing, which may be missing.
+    // the strict nil-safety mode requires a check for r-values
+    fn missing_nil(&self, value: &Slot, lval: bool) -> Slot {
+        if !lval && self.strict_nil() {
+            Slot::just(value.unlift().clone().or_nil(Nil::Noisy))
+        } else {
+            value.clone().with_nil()
+        }
+    }
+
     fn check_rval_index(&mut self, ety: &Spanned<Slot>, kty: &Spanned<Slot>,
                         expspan: Span) -> Result<Slot> {
         match self.check_index_common(ety, kty, expspan, false)? {
@@ -908,10 +1766,26 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 Ok(Slot::dummy())
             },
             Index::Created(..) => unreachable!(),
+            // entries of weak tables may have been collected at any time
+            Index::Found(ref slot) if ety.unlift().tag() == Some(Tag::Weak) => {
+                self.weak_reads.insert(expspan);
+                Ok(Slot::just(slot.unlift().clone().or_nil(Nil::Noisy)))
+            },
             Index::Found(slot) => Ok(slot),
         }
     }
 
+    // marks a table variable as weak, i.e. its entries can be collected at any time
+    fn mark_weak_var(&mut self, name: &NameRef) {
+        let slot = self.env.get_var(name).and_then(|def| def.slot.slot().cloned());
+        if let Some(slot) = slot {
+            if slot.unlift().tag() != Some(Tag::Weak) {
+                let ty = slot.unlift().clone().with_tag(Tag::Weak);
+                slot.replace_ty(ty);
+            }
+        }
+    }
+
     // this should be followed by assign_to_lval_index
     fn check_lval_index(&mut self, ety: &Spanned<Slot>, kty: &Spanned<Slot>,
                         expspan: Span) -> Result<Lvalue> {
@@ -938,6 +1812,16 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                                    specrhs: self.display(specrhs),
                                })
                         .done()?;
+            } else if let Some(T::Class(cls)) = self.env.resolve_exact_type(&ety.unlift())
+                                                        .map(|ty| ty.unwrap()) {
+                // the class system may have deferred some checks until the field gets a type
+                let kty_ = kty.unlift();
+                let key = kty_.as_integer().map(|key| Key::from(key))
+                              .or_else(|| kty_.as_string().map(|key| Key::from(key)));
+                if let Some(key) = key {
+                    self.env.context().complete_class_field(cls, (&key).with_loc(kty),
+                                                             lvalue.slot.span)?;
+                }
             }
         }
 
@@ -945,6 +1829,20 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
         Ok(())
     }
 
+    // records an `--# assume` directive for `root.fields...` for the later audit
+    fn add_assumption(&mut self, span: Span, kind: AssumptionKind, root: &NameRef,
+                      fields: &[Spanned<Name>], ty: Slot) {
+        let mut path = vec![self.env.get_name(root).clone()];
+        path.extend(fields.iter().map(|name| name.base.clone()));
+        let global = match *root {
+            NameRef::Global(_) => true,
+            NameRef::Local(_) => false,
+        };
+        self.context().add_assumption(Assumption {
+            span: span, kind: kind, global: global, path: path, ty: ty,
+        });
+    }
+
     fn assume_field_slot(&mut self, static_: bool, rootslot: Spanned<Slot>,
                          names: &[Spanned<Name>], namespan: Span, slot: Slot) -> Result<Slot> {
         assert!(!names.is_empty());
@@ -1120,8 +2018,9 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     exps: Option<&'inp Spanned<Vec<Spanned<Exp>>>>,
                     stmtspan: Span) -> Result<Exit> {
         #[derive(Debug)]
-        enum VarRef<'a> {
-            Name(&'a Spanned<NameRef>),
+        enum VarRef {
+            // also used for fields of the `[genv]` table
+            Name(Spanned<NameRef>),
             // table slot, key slot, indexed lvalue
             Slot(Spanned<Slot>, Spanned<Slot>, Lvalue),
         }
@@ -1129,7 +2028,12 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
         let mut exprexit = ExprExit::None;
         let varrefspecs = vars.iter().map(|varspec| {
             let varref = match varspec.base.base {
-                Var::Name(ref nameref) => VarRef::Name(nameref),
+                Var::Name(ref nameref) => if let Some((table, kty)) = self.module_field(nameref) {
+                    let lvalue = self.check_lval_index(&table, &kty, nameref.span)?;
+                    VarRef::Slot(table, kty, lvalue)
+                } else {
+                    VarRef::Name(nameref.clone())
+                },
 
                 Var::Index(ref e, ref key) => {
                     let Exitable(exit1, ty) = self.visit_exp(e, None)?;
@@ -1137,18 +2041,31 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     exprexit = exprexit.collide(exit1).collide(exit2);
                     let ty = ty.into_first();
                     let kty = kty.into_first();
-                    let lvalue = self.check_lval_index(&ty, &kty, varspec.base.span)?;
-                    VarRef::Slot(ty, kty, lvalue)
+                    let global = if let Ex::Str(ref keystr) = *key.base {
+                        self.genv_global(&ty, keystr, key.span)
+                    } else {
+                        None
+                    };
+                    if let Some(nameref) = global {
+                        VarRef::Name(nameref)
+                    } else {
+                        let lvalue = self.check_lval_index(&ty, &kty, varspec.base.span)?;
+                        VarRef::Slot(ty, kty, lvalue)
+                    }
                 },
 
                 Var::IndexName(ref e, ref key) => {
                     let Exitable(exit, ty) = self.visit_exp(e, None)?;
                     exprexit = exprexit.collide(exit);
                     let ty = ty.into_first();
-                    let keystr = Str::from(key.base[..].to_owned());
-                    let kty = Slot::just(Ty::new(T::Str(Cow::Owned(keystr)))).with_loc(key);
-                    let lvalue = self.check_lval_index(&ty, &kty, varspec.base.span)?;
-                    VarRef::Slot(ty, kty, lvalue)
+                    if let Some(nameref) = self.genv_global(&ty, &key.base, key.span) {
+                        VarRef::Name(nameref)
+                    } else {
+                        let keystr = Str::from(key.base[..].to_owned());
+                        let kty = Slot::just(Ty::new(T::Str(Cow::Owned(keystr)))).with_loc(key);
+                        let lvalue = self.check_lval_index(&ty, &kty, varspec.base.span)?;
+                        VarRef::Slot(ty, kty, lvalue)
+                    }
                 },
             };
 
@@ -1166,7 +2083,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 } else {
                     // no type spec, use the existing slot type as a substitute
                     match *varref {
-                        VarRef::Name(nameref) => {
+                        VarRef::Name(ref nameref) => {
                             let def = self.env.get_var(nameref);
                             let defslot = def.and_then(|def| def.slot.slot().cloned());
                             // the slot may haven't been assigned;
@@ -1187,6 +2104,32 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             None
         };
 
+        // assigned variables and fields are no longer narrowed
+        for (var, &(ref varref, _)) in vars.base.iter().zip(&varrefspecs) {
+            if let VarRef::Name(ref nameref) = *varref {
+                self.invalidate_narrowed(nameref, None);
+                self.func_decls.remove(&nameref.base);
+                self.pcalls.retain(|&(ref ok, ref results)| {
+                    *ok != nameref.base && results.iter().all(|r| r.0 != nameref.base)
+                });
+                continue;
+            }
+            match var.base.base {
+                Var::Name(_) => {},
+                Var::Index(ref e, ref key) => if let Ex::Var(ref name) = *e.base {
+                    if let Ex::Str(ref key) = *key.base {
+                        self.invalidate_narrowed(name, Some(&Key::from(key.clone())));
+                    } else {
+                        self.invalidate_narrowed(name, None);
+                    }
+                },
+                Var::IndexName(ref e, ref key) => if let Ex::Var(ref name) = *e.base {
+                    let key = Key::from(Str::from(key.base[..].to_owned()));
+                    self.invalidate_narrowed(name, Some(&key));
+                },
+            }
+        }
+
         // unlike St::Local, do not tolerate the uninitialized variables
         for (var, (varref, specinfo)) in vars.iter().zip(varrefspecs.into_iter()) {
             // ideally should be done via zip, but then concrete types will collide
@@ -1199,16 +2142,18 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 VarRef::Name(nameref) => {
                     let varslot = if let Some(specinfo) = specinfo {
                         // variable declaration
-                        self.env.add_var(nameref, Some(specinfo), info)?
+                        self.env.add_var(&nameref, Some(specinfo), info)?
                     } else {
                         // variable assignment
                         if let Some(info) = info {
-                            Some(self.env.assign_to_var(nameref, info)?)
+                            Some(self.env.assign_to_var(&nameref, info)?)
                         } else {
                             None
                         }
                     };
 
+                    self.reassert_global(&nameref.base);
+
                     // map the name span to the resulting slot
                     if let Some(varslot) = varslot {
                         self.register_module_if_needed(&varslot);
@@ -1233,39 +2178,207 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 }
             }
         }
-
-        exprexit.to_stmt(stmtspan, self.env)
+
+        exprexit.to_stmt(stmtspan, self.env)
+    }
+
+    #[cfg(feature = "no_implicit_func_sig")]
+    fn error_on_implicit_sig(&mut self, sig: &Sig) -> Result<()> {
+        if sig.args.head.iter().any(|spec| spec.kind.is_none()) {
+            self.env.error(&sig.args, m::ImplicitSigOnNamedFunc {}).done()?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_implicit_func_sig"))]
+    fn error_on_implicit_sig(&mut self, _sig: &Sig) -> Result<()> {
+        Ok(())
+    }
+
+    /// Type-checks a given chunk (here is same to the top-level block).
+    pub fn visit(&mut self, chunk: &'inp Spanned<Block>) -> Result<()> {
+        self.visit_module(chunk)?;
+        Ok(())
+    }
+
+    /// Same to `visit` but returns true if the chunk is known to diverge,
+    /// which should be passed to `Env::return_from_module` when the chunk is a module.
+    pub fn visit_module(&mut self, chunk: &'inp Spanned<Block>) -> Result<bool> {
+        self.add_suppressions(chunk)?;
+        self.context().enter_stats_scope(StatsScope::Module(chunk.span.unit()));
+        let ret = self.visit_block(chunk);
+        self.context().leave_stats_scope();
+        let exit = ret?;
+        self.check_unused(chunk)?;
+        self.check_shadowing(chunk)?;
+        self.check_lint_rules(chunk)?;
+        self.check_unused_suppressions(chunk)?;
+        Ok(exit >= Exit::Stop)
+    }
+
+    // registers `--# ignore` directives in the chunk to the context
+    fn add_suppressions(&mut self, chunk: &Spanned<Block>) -> Result<()> {
+        for suppression in find_suppressions(chunk) {
+            let mut codes = Vec::new();
+            for code in suppression.codes {
+                let known = str::from_utf8(&code.base).ok().map_or(false, |code| {
+                    find_lint(code).is_some() || is_diagnostic_code(code)
+                });
+                if !known {
+                    self.env.warn(&code, m::UnknownLintInIgnore { code: &code.base }).done()?;
+                }
+                codes.push(code.base);
+            }
+            self.context().add_suppression(suppression.directive, suppression.range, codes);
+        }
+        Ok(())
+    }
+
+    // reports `--# ignore` directives in the chunk which did not suppress anything, if enabled
+    fn check_unused_suppressions(&mut self, chunk: &Spanned<Block>) -> Result<()> {
+        let unused = self.context().take_unused_suppressions(chunk.span.unit());
+        let kind = self.env.opts().borrow().lint_severity("unused-ignore").to_kind();
+        if let Some(kind) = kind {
+            for span in unused {
+                self.env.add_lint_span("unused-ignore", kind, span, &m::UnusedIgnore {})?;
+            }
+        }
+        Ok(())
+    }
+
+    // reports unused local variables and assignments in the chunk, if enabled
+    fn check_unused(&mut self, chunk: &Spanned<Block>) -> Result<()> {
+        let (var_kind, assign_kind) = {
+            let opts = self.env.opts().borrow();
+            (opts.lint_severity("unused-variable").to_kind(),
+             opts.lint_severity("unused-assignment").to_kind())
+        };
+        if var_kind.is_none() && assign_kind.is_none() {
+            return Ok(());
+        }
+
+        for (kind, id) in find_unused(chunk, self.env.scope_map()) {
+            let name = id.base.name(self.env.scope_map()).clone();
+            match kind {
+                UnusedKind::Variable => if let Some(k) = var_kind {
+                    let msg = m::UnusedVariable { name: &name };
+                    self.env.add_lint_span("unused-variable", k, id.span, &msg)?;
+                },
+                UnusedKind::Assignment => if let Some(k) = assign_kind {
+                    let msg = m::UnusedAssignment { name: &name };
+                    self.env.add_lint_span("unused-assignment", k, id.span, &msg)?;
+                },
+            }
+        }
+        Ok(())
     }
 
-    #[cfg(feature = "no_implicit_func_sig")]
-    fn error_on_implicit_sig(&mut self, sig: &Sig) -> Result<()> {
-        if sig.args.head.iter().any(|spec| spec.kind.is_none()) {
-            self.env.error(&sig.args, m::ImplicitSigOnNamedFunc {}).done()?;
+    // reports the syntactic lint rules in the chunk, if enabled
+    fn check_lint_rules(&mut self, chunk: &Spanned<Block>) -> Result<()> {
+        use kailua_diag::Kind::Note;
+
+        let (lua, api_kind, block_kind) = {
+            let opts = self.env.opts().borrow();
+            (opts.lua_version(),
+             opts.lint_severity("deprecated-api").to_kind(),
+             opts.lint_severity("empty-block").to_kind())
+        };
+        if api_kind.is_none() && block_kind.is_none() {
+            return Ok(());
+        }
+
+        for (span, violation) in lint_rules::find_violations(chunk, lua) {
+            match violation {
+                Violation::DeprecatedApi(api) => if let Some(kind) = api_kind {
+                    let name = api.name();
+                    let msg = m::DeprecatedApi { name: &name, lua: api.since.name() };
+                    self.env.add_lint_span("deprecated-api", kind, span, &msg)?;
+                    if let Some(replacement) = api.replacement {
+                        let msg = m::DeprecatedApiReplacement { replacement: replacement };
+                        self.env.add_span(Note, span, &msg)?;
+                    }
+                },
+                Violation::EmptyBlock => if let Some(kind) = block_kind {
+                    self.env.add_lint_span("empty-block", kind, span, &m::EmptyBlock {})?;
+                },
+            }
         }
         Ok(())
     }
 
-    #[cfg(not(feature = "no_implicit_func_sig"))]
-    fn error_on_implicit_sig(&mut self, _sig: &Sig) -> Result<()> {
+    // reports a numeric `for` loop step which is not an integer, if enabled
+    fn check_float_for_step(&mut self, step: &Spanned<Ty>) -> Result<()> {
+        let kind = match self.env.opts().borrow().lint_severity("float-for-step").to_kind() {
+            Some(kind) => kind,
+            None => return Ok(()),
+        };
+
+        let flags = self.env.get_type_bounds(step).1;
+        if !flags.is_dynamic() && flags.intersects(T_NONINTEGER) && !flags.intersects(!T_NUMBER) {
+            let msg = m::FloatForStep { step: self.display(step) };
+            self.env.add_lint_span("float-for-step", kind, step.span, &msg)?;
+        }
         Ok(())
     }
 
-    /// Type-checks a given chunk (here is same to the top-level block).
-    pub fn visit(&mut self, chunk: &'inp Spanned<Block>) -> Result<()> {
-        self.visit_block(chunk)?;
+    // reports local variables shadowing other variables in the chunk, if enabled
+    fn check_shadowing(&mut self, chunk: &Spanned<Block>) -> Result<()> {
+        use kailua_diag::Kind::Note;
+
+        let kind = match self.env.opts().borrow().lint_severity("shadowing").to_kind() {
+            Some(kind) => kind,
+            None => return Ok(()),
+        };
+
+        for (id, shadowed) in find_shadowing(chunk, self.env.scope_map()) {
+            let name = id.base.name(self.env.scope_map()).clone();
+            let prevspan = match shadowed {
+                Shadowed::Local(prev) => {
+                    let msg = m::ShadowedLocal { name: &name };
+                    self.env.add_lint_span("shadowing", kind, id.span, &msg)?;
+                    prev.span
+                }
+                Shadowed::Upvalue(prev) => {
+                    let msg = m::ShadowedUpvalue { name: &name };
+                    self.env.add_lint_span("shadowing", kind, id.span, &msg)?;
+                    prev.span
+                }
+                Shadowed::Global(name) => {
+                    let prevspan = match self.env.get_var(&NameRef::Global(name.clone())) {
+                        Some(def) => def.span,
+                        None => continue,
+                    };
+                    let msg = m::ShadowedGlobal { name: &name };
+                    self.env.add_lint_span("shadowing", kind, id.span, &msg)?;
+                    prevspan
+                }
+            };
+            if prevspan.is_source_dependent() {
+                self.env.add_span(Note, prevspan, &m::ShadowedVarDefinition {})?;
+            }
+        }
         Ok(())
     }
 
     fn visit_block(&mut self, block: &'inp Spanned<Block>) -> Result<Exit> {
         // `self.pending_modules` should be kept in sync, even when the checking fails
         self.pending_modules.push(PendingModules::new());
+        let narrowed = self.narrowed.len();
+        let assumed = self.scoped_assumptions.len();
         let exit;
         let ret;
         {
             let mut scope = self.scoped(Scope::new());
             exit = scope.visit_block_(block);
+            // narrowings made in this block do not apply to pending function bodies
+            scope.restore_narrowed(narrowed);
             ret = scope.check_pending_modules();
         }
+        // while scoped assumptions do apply to them
+        while self.scoped_assumptions.len() > assumed {
+            let saved = self.scoped_assumptions.pop().unwrap();
+            self.env.restore_var(saved);
+        }
         self.pending_modules.pop().expect("no matching pending module list");
         let exit = exit?;
         ret?;
@@ -1318,6 +2431,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             } else {
                 exit = self.visit_stmt(stmt)?;
             }
+            self.report_exceeded_limits(stmt.span)?;
         }
         #[cfg(feature = "warn_on_dead_code")] {
             if let Some(span) = ignored_stmts {
@@ -1327,8 +2441,34 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
         Ok(exit)
     }
 
+    // reports a note for each complexity limit newly exceeded by given statement
+    fn report_exceeded_limits(&mut self, span: Span) -> Result<()> {
+        use kailua_diag::Kind::Note;
+
+        for limit in self.context().take_exceeded_limits() {
+            let opts = self.env.opts().clone();
+            let opts = opts.borrow();
+            match limit {
+                ComplexityLimit::UnionSize => {
+                    let msg = m::UnionLimitExceeded { limit: opts.literal_union_limit() };
+                    self.env.add_span(Note, span, &msg)?;
+                }
+                ComplexityLimit::TableDepth => {
+                    let msg = m::TableDepthLimitExceeded { limit: opts.table_depth_limit() };
+                    self.env.add_span(Note, span, &msg)?;
+                }
+                ComplexityLimit::Constraints => {
+                    let msg = m::ConstraintLimitExceeded { limit: opts.constraint_limit() };
+                    self.env.add_span(Note, span, &msg)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn visit_stmt(&mut self, stmt: &'inp Spanned<Stmt>) -> Result<Exit> {
         debug!("visiting stmt {:?}", *stmt);
+        self.context().keep_going()?;
 
         match *stmt.base {
             // it should not happen, but for the purpose of checker, the error nodes are ignored
@@ -1336,6 +2476,13 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
 
             St::Void(ref exp) => {
                 let (exit, _) = self.visit_exp_from_stmt(exp, None)?;
+                if self.nodiscard_calls.contains(&exp.span) {
+                    let kind = self.env.opts().borrow().lint_severity("discarded-result").to_kind();
+                    if let Some(kind) = kind {
+                        let msg = m::DiscardedResult {};
+                        self.env.add_lint_span("discarded-result", kind, exp.span, &msg)?;
+                    }
+                }
                 Ok(exit)
             },
 
@@ -1364,8 +2511,11 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 }
 
                 if let Some(normal_exit) = normal_exit {
-                    exit &= self.visit_block(block)?;
-                    exit = exit.loop_boundary(normal_exit);
+                    let mut blockexit = self.visit_block(block)?.loop_boundary(normal_exit);
+                    if let Bool::Unknown = boolean {
+                        blockexit |= Exit::None; // the block may not run at all
+                    }
+                    exit &= blockexit;
                 }
                 Ok(exit)
             }
@@ -1386,6 +2536,10 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             }
 
             St::If(ref conds, ref lastblock) => {
+                if lastblock.is_none() && conds.len() >= 2 {
+                    self.check_enum_dispatch(conds)?;
+                }
+
                 // conditions *can* diverge, which complicates the exit computation.
                 // if we have something like `if C1 then B1 elseif C2 then B2 ... else E end`,
                 // the final exit should be `(C1 & B1) | (C1 & C2 & B2) | ... | (C1 & C2 & ... & E)`
@@ -1393,6 +2547,13 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 let mut exit = Exit::Stop; // (C1 & B1) | ... | (C1 & ... & Ck & Bk)
                 let mut condexit = Exit::None; // C1 & ... & Ck
 
+                // conditions narrow variables in the corresponding block,
+                // and negated conditions apply to all subsequent conditions and blocks.
+                // if every block diverges and there is no `else`, negated conditions also apply
+                // to the remainder of the enclosing block (`if not x then return end`).
+                let narrowed = self.narrowed.len();
+                let mut diverging = true;
+
                 let mut ignored_blocks = None; // or Some((first truthy cond span, blocks span))
                 for &Spanned { base: (ref cond, ref block), span } in conds {
                     // do not update the exit, as blocks after a truthy condition will be ignored
@@ -1404,10 +2565,14 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     let (condexit_, ty) = self.visit_exp_from_stmt(cond, None)?;
                     condexit &= condexit_;
                     let boolean = self.check_bool(ty.unspan().unlift());
+                    let narrowed_in_block = self.narrowed.len();
+                    self.narrow_by_cond(cond, false);
                     match boolean {
                         Bool::Truthy => {
                             ignored_blocks = Some((cond.span, Span::dummy()));
-                            exit |= condexit & self.visit_block(block)?;
+                            let blockexit = self.visit_block(block)?;
+                            diverging = false;
+                            exit |= condexit & blockexit;
                         }
                         Bool::Falsy => {
                             #[cfg(feature = "warn_on_useless_conds")] {
@@ -1418,9 +2583,13 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                             exit |= condexit;
                         }
                         Bool::Unknown => {
-                            exit |= condexit & self.visit_block(block)?;
+                            let blockexit = self.visit_block(block)?;
+                            diverging &= blockexit >= Exit::Break;
+                            exit |= condexit & blockexit;
                         }
                     }
+                    self.restore_narrowed(narrowed_in_block);
+                    self.narrow_by_cond(cond, true);
                 }
 
                 if let &Some(ref block) = lastblock {
@@ -1429,10 +2598,14 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     } else {
                         exit |= condexit & self.visit_block(block)?;
                     }
+                    self.restore_narrowed(narrowed);
                 } else {
                     if ignored_blocks.is_none() {
                         exit |= condexit;
                     }
+                    if !diverging {
+                        self.restore_narrowed(narrowed);
+                    }
                 }
 
                 #[cfg(feature = "warn_on_useless_conds")] {
@@ -1459,7 +2632,9 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 let Exitable(exit3, step) = if let &Some(ref step) = step {
                     expspan |= step.span;
                     let step = self.visit_exp(step, None)?;
-                    step.map(|slot| slot.into_first().map(|s| s.unlift().clone()))
+                    let step = step.map(|slot| slot.into_first().map(|s| s.unlift().clone()));
+                    self.check_float_for_step(&step.1)?;
+                    step
                 } else {
                     Exitable::new(Ty::new(T::Integer).without_loc()) // to simplify the matter
                 };
@@ -1514,8 +2689,9 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     scope.register_module_if_needed(&varslot);
                 }
 
-                exit &= scope.visit_block(block)?;
-                Ok(exit.loop_boundary(Exit::None))
+                // the block may not run at all
+                exit &= scope.visit_block(block)?.loop_boundary(Exit::None) | Exit::None;
+                Ok(exit)
             }
 
             St::ForIn(ref names, ref exps, _blockscope, ref block) => {
@@ -1593,13 +2769,25 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     }
                 }
 
-                exit &= scope.visit_block(block)?;
-                Ok(exit.loop_boundary(Exit::None))
+                // the block may not run at all
+                exit &= scope.visit_block(block)?.loop_boundary(Exit::None) | Exit::None;
+                Ok(exit)
             }
 
             St::FuncDecl(ref name, ref sig, _blockscope, ref block, nextscope) => {
                 self.error_on_implicit_sig(sig)?;
 
+                if let Some((table, kty)) = self.module_field(name) {
+                    // `function foo()` in the module mode declares a field of the module table
+                    let lvalue = self.check_lval_index(&table, &kty, name.span)?;
+                    let (tag, no_check) = self.visit_sig_attrs(&sig.attrs)?;
+                    let functy = self.visit_func_body(tag, no_check, None, sig, block,
+                                                      stmt.span, None)?;
+                    self.assign_to_lval_index(&table, &kty, &lvalue, &functy.with_loc(stmt),
+                                              None)?;
+                    return Ok(Exit::None);
+                }
+
                 // `name` itself is available to the inner scope
                 let funcv = self.types().gen_tvar();
                 let info = Slot::just(Ty::new(T::TVar(funcv))).with_loc(stmt);
@@ -1619,6 +2807,8 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 let (tag, no_check) = self.visit_sig_attrs(&sig.attrs)?;
                 let functy = self.visit_func_body(tag, no_check, None, sig, block,
                                                   stmt.span, None)?;
+                self.func_decls.insert(name.base.clone(), stmt.span);
+                self.reassert_global(&name.base);
                 if let Err(r) = Ty::new(T::TVar(funcv)).assert_eq(&*functy.unlift(), self.types()) {
                     self.env.error(stmt, m::BadRecursiveCall {})
                         .report_types(r, TypeReportHint::None)
@@ -1716,12 +2906,71 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 };
                 let (exit, infos) = self.visit_explist_from_stmt(exps, Some(hint))?;
 
+                // `local t = x.key` makes `t` an alias to `x.key` for the narrowing
+                let alias = match (&names[..], &exps[..]) {
+                    (&[ref name], &[ref exp]) if name.kind.is_none() => match *exp.base {
+                        Ex::IndexName(ref e, ref key) => {
+                            Some((e, Key::from(Str::from(key.base[..].to_owned()))))
+                        }
+                        Ex::Index(ref e, ref key) => match *key.base {
+                            Ex::Str(ref key) => Some((e, Key::from(key.clone()))),
+                            _ => None,
+                        },
+                        _ => None,
+                    },
+                    _ => None,
+                };
+
                 for ((localname, specinfo), info) in nameinfos.into_iter()
                                                               .zip(infos.into_iter_with_none()) {
                     let nameref = NameRef::Local(localname.base.clone()).with_loc(localname);
+                    let weak = info.as_ref().map_or(false, |info| {
+                        info.unlift().tag() == Some(Tag::Weak)
+                    });
                     if let Some(varslot) = self.env.add_var(&nameref, specinfo, info)? {
                         self.register_module_if_needed(&varslot);
                     }
+                    // the weak table is still weak even when the variable is explicitly typed
+                    if weak {
+                        self.mark_weak_var(&nameref);
+                    }
+                }
+
+                if let Some((base, key)) = alias {
+                    if let Ex::Var(Spanned { base: NameRef::Local(ref id), .. }) = *base.base {
+                        let alias = NameRef::Local(names[0].base.base.clone());
+                        self.aliases.push((alias, NameRef::Local(id.clone()), key));
+                    }
+                }
+
+                // `local ok, res = pcall(f)` makes `res` narrowed by `ok`
+                if let Some((span, success, failure)) = self.pcall_results.take() {
+                    if exps.len() == 1 && exps[0].span == span && names.len() >= 2 {
+                        let ok = NameRef::Local(names[0].base.base.clone());
+                        let mut failure = Some(failure);
+                        let mut results = Vec::new();
+                        for (i, name) in names[1..].iter().enumerate() {
+                            let failure = failure.take(); // only the first is an error value
+                            if name.kind.is_some() {
+                                continue;
+                            }
+                            let success = match success.head.get(i) {
+                                Some(ty) => ty.clone(),
+                                None => success.tail.clone().unwrap_or(Ty::silent_nil()),
+                            };
+                            let name = NameRef::Local(name.base.base.clone());
+                            results.push((name, success, failure));
+                        }
+                        self.pcalls.push((ok, results));
+                    }
+                }
+
+                // `local f = function() ... end` can be later used as a coroutine body
+                if let (&[ref name], &[ref exp]) = (&names[..], &exps[..]) {
+                    if let Ex::Func(..) = *exp.base {
+                        let name = NameRef::Local(name.base.base.clone());
+                        self.func_decls.insert(name, exp.span);
+                    }
                 }
                 Ok(exit)
             }
@@ -1732,7 +2981,25 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     Returns::Implicit(ref returns) | Returns::Explicit(ref returns) =>
                         Some(SpannedSlotSeq::from_seq(returns.clone().all_with_loc(stmt))),
                 };
-                let (exit, seq) = self.visit_explist_from_stmt(exps, hint)?;
+                self.returning_module = self.env.is_top_level() && match exps.get(0) {
+                    Some(exp) => if let Ex::Var(_) = *exp.base { true } else { false },
+                    None => false,
+                };
+                let ret = self.visit_explist_from_stmt(exps, hint);
+                self.returning_module = false;
+                let (exit, seq) = ret?;
+
+                // returning a value from a weak table requires a nil check if not nilable
+                if let Returns::Explicit(ref returns) = self.env.get_frame().returns.clone() {
+                    for (i, exp) in exps.iter().enumerate() {
+                        let nilable = returns.head.get(i).or(returns.tail.as_ref())
+                                             .map_or(true, |ty| ty.nil() == Nil::Noisy);
+                        if !nilable && self.weak_reads.contains(&exp.span) {
+                            self.env.warn(exp, m::ReturnFromWeakTable {}).done()?;
+                        }
+                    }
+                }
+
                 self.visit_return(seq, stmt.span)?;
                 Ok(exit & Exit::Return)
             }
@@ -1764,7 +3031,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     }
                 }
 
-                let ty = Ty::from_kind(kind, &mut self.env)?;
+                let ty = self.resolve_kind(kind)?;
                 match scope {
                     TypeScope::Local => self.env.define_local_type(name, ty)?,
                     TypeScope::Global => self.env.define_global_type(name, ty)?,
@@ -1773,20 +3040,39 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 Ok(Exit::None)
             }
 
-            St::KailuaAssume(ref name, kindm, ref kind, _nextscope) => {
+            St::KailuaAssume(scoped, ref name, kindm, ref kind, _nextscope) => {
                 let slot = self.visit_kind(kindm, kind)?;
+                if scoped {
+                    let saved = self.env.save_var(&name.after);
+                    self.scoped_assumptions.push(saved);
+                }
+                self.add_assumption(stmt.span, AssumptionKind::Variable, &name.after, &[],
+                                    slot.base.clone());
                 let varslot = self.env.assume_var(&name.after.clone().with_loc(name), slot)?;
                 self.register_module_if_needed(&varslot);
                 Ok(Exit::None)
             }
 
-            St::KailuaAssumeField(static_, Spanned { base: (ref rootname, ref names), span },
+            St::KailuaAssumeField(scoped, static_,
+                                  Spanned { base: (ref rootname, ref names), span },
                                   kindm, ref kind) => {
                 if self.env.get_var(rootname).is_some() {
-                    let slot = self.visit_kind(kindm, kind)?;
                     let rootslot = self.env.ensure_var(rootname)?.with_loc(rootname);
+                    let slot = {
+                        // fields of generic classes can refer to type parameters of the class
+                        let class_params = self.class_type_params(&rootslot.unlift());
+                        let mut scope = self.scoped(Scope::new());
+                        scope.define_class_type_params(class_params, stmt.span)?;
+                        scope.visit_kind(kindm, kind)?
+                    };
+                    self.add_assumption(stmt.span, AssumptionKind::Field, rootname, names,
+                                        slot.base.clone());
                     let newslot = self.assume_field_slot(static_, rootslot, names, span,
                                                          slot.base)?;
+                    if scoped {
+                        let saved = self.env.save_var(rootname);
+                        self.scoped_assumptions.push(saved);
+                    }
                     let varslot = self.env.assume_var(rootname, newslot.with_loc(rootname))?;
                     self.register_module_if_needed(&varslot);
                 } else {
@@ -1796,15 +3082,20 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 Ok(Exit::None)
             }
 
-            St::KailuaAssumeMethod(Spanned { base: (ref rootname, ref names), span },
+            St::KailuaAssumeMethod(scoped, Spanned { base: (ref rootname, ref names), span },
                                    kindm, ref funckind) => {
                 assert!(!names.is_empty());
 
                 if self.env.get_var(rootname).is_some() {
                     let flex = F::from(kindm);
-                    let mut func = Function::from_kind(funckind, &mut self.env)?;
-
                     let rootslot = self.env.ensure_var(rootname)?.with_loc(rootname);
+                    let mut func = {
+                        // methods of generic classes can refer to type parameters of the class
+                        let class_params = self.class_type_params(&rootslot.unlift());
+                        let mut scope = self.scoped(Scope::new());
+                        scope.define_class_type_params(class_params, stmt.span)?;
+                        Function::from_kind(funckind, &mut scope.env)?
+                    };
 
                     // convert `method(...) --> ...` to `function(self: Self, ...) --> ...`
                     // where `Self` is an inferred type from `rootslot`
@@ -1815,9 +3106,15 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     let slot = Slot::new(
                         flex, Ty::new(T::Functions(Cow::Owned(Functions::Simple(func)))),
                     );
+                    self.add_assumption(stmt.span, AssumptionKind::Method, rootname, names,
+                                        slot.clone());
 
                     // the final slot should be static
                     let newslot = self.assume_field_slot(true, rootslot, names, span, slot)?;
+                    if scoped {
+                        let saved = self.env.save_var(rootname);
+                        self.scoped_assumptions.push(saved);
+                    }
                     let varslot = self.env.assume_var(rootname, newslot.with_loc(rootname))?;
                     self.register_module_if_needed(&varslot);
                 } else {
@@ -1839,7 +3136,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 Ok(Exit::None)
             }
 
-            St::KailuaAssumeClass(ref system, ref name, ref parent, _scope) => {
+            St::KailuaAssumeClass(ref system, ref name, ref type_params, ref parent, _scope) => {
                 let csid = if let Some(ref system) = *system {
                     self.env.class_system_from_name(system)?
                 } else {
@@ -1872,6 +3169,13 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 // defaults to the (publicly invisible) "dumb" class system
                 let csid = csid.unwrap_or_else(|| self.env.dumb_class_system());
                 if let Some(cid) = self.context().assume_class(csid, parent, stmt.span)? {
+                    if !type_params.is_empty() {
+                        let params = type_params.iter().map(|name| {
+                            self.types().gen_tparam(name.base.clone())
+                        }).collect();
+                        self.context().make_generic_class(cid, params, stmt.span)?;
+                    }
+
                     let name = name.after.clone().with_loc(name);
 
                     // immediately name the class.
@@ -1882,13 +3186,134 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     // the variable has been also renamed so it should be reflected.
                     // classes are implicitly delay-checked, just like the `[make_class]` attribute
                     let slot = Slot::new(F::Module, Ty::new(T::Class(Class::Prototype(cid))));
+                    self.add_assumption(stmt.span, AssumptionKind::Class, &name, &[],
+                                        slot.clone());
                     self.register_module_if_needed(&slot);
                     self.env.assume_var(&name, slot.with_loc(&name))?;
                 }
 
                 Ok(Exit::None)
             }
+
+            St::KailuaInterface(ref name, ref fields) => {
+                let mut ifacefields = Vec::new();
+                for &(ref fieldname, ref field) in fields {
+                    let key = Key::from(Str::from(fieldname.base.clone())).with_loc(fieldname);
+                    let (method, slot) = match *field {
+                        ast::InterfaceField::Field(ref slotkind) => {
+                            (false, self.visit_kind(slotkind.modf, &slotkind.kind)?.base)
+                        }
+                        ast::InterfaceField::Method(ref funckind) => {
+                            let func = Function::from_kind(funckind, &mut self.env)?;
+                            let ty = Ty::new(T::Functions(Cow::Owned(Functions::Simple(func))));
+                            (true, Slot::just(ty))
+                        }
+                    };
+                    ifacefields.push((key, method, slot));
+                }
+
+                let iface = Interface { span: stmt.span, fields: ifacefields };
+                self.env.define_interface(name, iface)?;
+                Ok(Exit::None)
+            }
+
+            St::KailuaAssertImplements(ref name, ref iface) => {
+                self.check_implements(name, iface, stmt.span)?;
+                Ok(Exit::None)
+            }
+
+            // suppressions are collected before checking the chunk
+            St::KailuaIgnore(..) => Ok(Exit::None),
+        }
+    }
+
+    // checks if a variable implements an interface and reports every missing
+    // or mismatching field at once. class prototypes are checked with their instances
+    fn check_implements(&mut self, name: &Spanned<NameRef>, iface: &Spanned<Name>,
+                        span: Span) -> Result<()> {
+        let def = match self.env.context().get_interface(iface) {
+            Some(def) => def.clone(),
+            None => {
+                self.env.error(iface, m::NoInterface { name: &iface.base }).done()?;
+                return Ok(());
+            }
+        };
+
+        if self.env.get_var(name).is_none() {
+            self.env.error(name, m::NoVar { name: self.env.get_name(name) }).done()?;
+            return Ok(());
+        }
+        let slot = self.env.ensure_var(name)?;
+
+        let ty = self.env.resolve_exact_type(&slot.unlift()).map(|ty| {
+            if let T::Class(Class::Prototype(cid)) = *ty {
+                Ty::new(T::Class(Class::Instance(cid)))
+            } else {
+                ty
+            }
+        });
+        let fields = ty.as_ref().and_then(|ty| self.env.context().get_available_fields(ty));
+        let (ty, fields) = match (ty, fields) {
+            (Some(ty), Some(fields)) => (ty, fields),
+            _ => {
+                self.env.error(name, m::ImplementsNonTable { name: self.env.get_name(name),
+                                                             ty: self.display(&slot) })
+                        .done()?;
+                return Ok(());
+            }
+        };
+
+        let mut missing = Vec::new();
+        let mut mismatching = Vec::new();
+        for &(ref key, method, ref expected) in &def.fields {
+            let actual = match fields.get(&key.base) {
+                Some(actual) => actual.clone(),
+                None => {
+                    missing.push((key, method, expected));
+                    continue;
+                }
+            };
+
+            // fields are only required to be readable, so their types are compared covariantly.
+            // the receiver of methods is always the implementing type itself
+            let expected = if method {
+                let mut func = match expected.unlift().get_functions() {
+                    Some(&Functions::Simple(ref func)) => func.clone(),
+                    _ => panic!("an interface method should have a function type"),
+                };
+                func.args.head.insert(0, ty.clone());
+                func.argnames.insert(0, Some(Name::from(&b"self"[..]).without_loc()));
+                Slot::just(Ty::new(T::Functions(Cow::Owned(Functions::Simple(func)))))
+            } else {
+                expected.clone()
+            };
+
+            if actual.unlift().assert_sub(&*expected.unlift(), self.types()).is_err() {
+                mismatching.push((key, expected, actual));
+            }
+        }
+
+        if missing.is_empty() && mismatching.is_empty() {
+            return Ok(());
+        }
+
+        let mut more = self.env.error(span, m::NotImplemented { name: self.env.get_name(name),
+                                                                iface: &iface.base });
+        for &(key, method, expected) in &missing {
+            more = if method {
+                more.note(key, m::InterfaceMethodMissing { key: &key.base,
+                                                           ty: self.display(expected) })
+            } else {
+                more.note(key, m::InterfaceFieldMissing { key: &key.base,
+                                                          ty: self.display(expected) })
+            };
+        }
+        for &(key, ref expected, ref actual) in &mismatching {
+            more = more.note(key, m::InterfaceFieldMismatch { key: &key.base,
+                                                              expected: self.display(expected),
+                                                              actual: self.display(actual) });
         }
+        more.done()
     }
 
     fn visit_return(&mut self, seq: SpannedSlotSeq, stmtspan: Span) -> Result<()> {
@@ -1938,6 +3363,22 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
         Ok(())
     }
 
+    // same to `visit_return` with no values, but the declared returns that cannot be omitted
+    // are reported as a missing return at the end of the function (`endspan`)
+    fn visit_implicit_return(&mut self, span: Span, endspan: Span) -> Result<()> {
+        if let Returns::Explicit(returns) = self.env.get_frame().returns.clone() {
+            let returns = returns.all_with_loc(endspan);
+            let seq = SpannedSlotSeq::new(span).unlift();
+            if seq.assert_sub(&returns, self.types()).is_err() {
+                self.env.error(endspan, m::MissingReturn { returns: self.display(&returns) })
+                        .done()?;
+            }
+            Ok(())
+        } else {
+            self.visit_return(SpannedSlotSeq::new(span), span)
+        }
+    }
+
     fn visit_sig_attrs(&mut self,
                        attrs: &[Spanned<Attr>]) -> Result<(Option<Tag>, Option<NoCheck>)> {
         let mut tag = None;
@@ -2009,6 +3450,113 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                        selfparam: Option<(&Spanned<SelfParam>, Slot)>, sig: &Sig,
                        block: &'inp Spanned<Vec<Spanned<Stmt>>>, declspan: Span,
                        hint: Option<Spanned<Slot>>) -> Result<Slot> {
+        self.context().enter_stats_scope(StatsScope::Function(declspan));
+        self.yields.push(None);
+        let ret = self.visit_func_body_(tag, no_check, selfparam, sig, block, declspan, hint);
+        let yields = self.yields.pop().expect("yields stack underflow");
+        self.body_yields.insert(declspan, yields);
+        self.context().leave_stats_scope();
+        ret
+    }
+
+    fn visit_func_body_(&mut self, tag: Option<Tag>, no_check: Option<NoCheck>,
+                        selfparam: Option<(&Spanned<SelfParam>, Slot)>, sig: &Sig,
+                        block: &'inp Spanned<Vec<Spanned<Stmt>>>, declspan: Span,
+                        hint: Option<Spanned<Slot>>) -> Result<Slot> {
+        // methods of generic classes can refer to type parameters of the class
+        let class_params = if let Some((_, ref selfslot)) = selfparam {
+            self.class_type_params(&selfslot.unlift())
+        } else {
+            Vec::new()
+        };
+
+        // methods of classes can refer to the class instance type as `self`,
+        // which is replaced with the receiver type at the call site
+        let self_type = if let Some((_, ref selfslot)) = selfparam {
+            self.self_type(&selfslot.unlift())
+        } else {
+            None
+        };
+
+        if sig.type_params.is_empty() && class_params.is_empty() && self_type.is_none() {
+            // an anonymous function takes type parameters from the generic hint if any
+            let type_params = hint.as_ref().and_then(|hint| {
+                self.env.resolve_exact_type(&hint.unlift()).and_then(|ty| {
+                    if let Some(&Functions::Simple(ref f)) = ty.get_functions() {
+                        Some(f.type_params.clone())
+                    } else {
+                        None
+                    }
+                })
+            }).unwrap_or(Vec::new());
+            return self.visit_generic_func_body(tag, no_check, selfparam, sig, block,
+                                                declspan, hint, type_params);
+        }
+
+        // type parameters should be visible to both the signature and the body
+        let mut scope = self.scoped(Scope::new());
+        scope.define_class_type_params(class_params, declspan)?;
+        if let Some(ty) = self_type {
+            scope.env.define_local_type(&Name::from(b"self".to_vec()).with_loc(declspan), ty)?;
+        }
+        let mut type_params = Vec::new();
+        for name in &sig.type_params {
+            let param = scope.types().gen_tparam(name.base.clone());
+            scope.env.define_local_type(name, Ty::new(T::Param(param.clone())))?;
+            type_params.push(param);
+        }
+        scope.visit_generic_func_body(tag, no_check, selfparam, sig, block,
+                                      declspan, hint, type_params)
+    }
+
+    // returns a type for `self` if given type is a class instance
+    // and no other type named `self` is visible
+    fn self_type(&mut self, ty: &Ty) -> Option<Ty> {
+        let inst = match self.env.resolve_exact_type(ty) {
+            Some(inst) => inst,
+            None => return None,
+        };
+        if let T::Class(Class::Instance(_)) = *inst {
+            let name = Name::from(b"self".to_vec()).with_loc(Span::dummy());
+            if self.env.get_named_type(&name).is_none() {
+                return Some(inst.without_nil().with_tag(Tag::SelfType));
+            }
+        }
+        None
+    }
+
+    // returns type parameters of the generic class if given type is its prototype or instance
+    fn class_type_params(&mut self, ty: &Ty) -> Vec<TParam> {
+        let cid = match self.env.resolve_exact_type(ty).as_ref().map(|ty| &**ty) {
+            Some(&T::Class(Class::Prototype(cid))) | Some(&T::Class(Class::Instance(cid))) => cid,
+            _ => return Vec::new(),
+        };
+        match self.types().class_type_args(cid) {
+            Some((generic, args)) => {
+                if generic != cid {
+                    return Vec::new(); // specialized, parameters are not visible
+                }
+                args.into_iter().filter_map(|arg| {
+                    if let T::Param(ref param) = *arg { Some(param.clone()) } else { None }
+                }).collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn define_class_type_params(&mut self, params: Vec<TParam>, span: Span) -> Result<()> {
+        for param in params {
+            let name = param.name.clone().with_loc(span);
+            self.env.define_local_type(&name, Ty::new(T::Param(param)))?;
+        }
+        Ok(())
+    }
+
+    fn visit_generic_func_body(&mut self, tag: Option<Tag>, no_check: Option<NoCheck>,
+                               selfparam: Option<(&Spanned<SelfParam>, Slot)>, sig: &Sig,
+                               block: &'inp Spanned<Vec<Spanned<Stmt>>>, declspan: Span,
+                               hint: Option<Spanned<Slot>>,
+                               type_params: Vec<TParam>) -> Result<Slot> {
         // if the hint exists and has a functional portion,
         // collect first `sig.args.head.len()` types for missing argument types,
         // and a repeating part of remaining type sequence for a missing variadic argument type.
@@ -2073,7 +3621,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             },
 
             Some(Varargs { kind: Some(ref k), .. }) => {
-                Some(Ty::from_kind(k, &mut self.env)?)
+                Some(self.resolve_kind(k)?)
             },
         };
 
@@ -2087,8 +3635,11 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
         let returns = if let Some(ref returns) = sig.returns {
             match *returns {
                 ast::Returns::Never(_) => Returns::Never,
-                ast::Returns::Seq(ref seq) =>
-                    Returns::Explicit(TySeq::from_kind_seq(seq, |kind| kind, &mut self.env)?),
+                ast::Returns::Seq(ref seq) => {
+                    let mut seq = TySeq::from_kind_seq(seq, |kind| kind, &mut self.env)?;
+                    seq.head = seq.head.into_iter().map(|t| self.strict_nil_ty(t)).collect();
+                    Returns::Explicit(seq)
+                },
             }
         } else if let Some(hint) = hintreturns {
             // use a hint if possible ([NO_CHECK] can rely on this hint as well)
@@ -2190,7 +3741,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             if let Exit::None = scope.visit_block(block)? {
                 // the last statement is an implicit return
                 let span = Span::from(block.span.end()); // conceptually at the end of block
-                scope.visit_return(SpannedSlotSeq::new(span), span)?;
+                scope.visit_implicit_return(span, Span::from(declspan.end()))?;
             }
         }
 
@@ -2198,10 +3749,37 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             Returns::Implicit(ref ret) | Returns::Explicit(ref ret) => Some(ret.clone()),
             Returns::Never | Returns::None => None,
         };
-        let func = Function { args: args, argnames: argnames, returns: returns };
+        let func = Function {
+            type_params: type_params,
+            args: args,
+            argnames: argnames,
+            returns: returns,
+        };
         Ok(Slot::just(Ty::new(T::func(func)).with_tag(tag)))
     }
 
+    // type parameters in generic functions are not yet known before visiting arguments,
+    // so only function arguments get hints where type parameters are made dynamic
+    // (they are later inferred from other arguments).
+    // other arguments get a neutral hint as they are used for inferring type parameters.
+    fn generic_arg_hints(&mut self, f: &Function) -> TySeq {
+        let mut subst = Substitution::new();
+        for param in &f.type_params {
+            subst.bind(param.clone(), Ty::new(T::Dynamic(Dyn::User)));
+        }
+
+        let mut hint = |ty: &Ty, types: &mut TypeContext| {
+            if let Some(&Functions::Simple(_)) = ty.get_functions() {
+                ty.clone().substitute(&mut subst, types)
+            } else {
+                Ty::new(T::All)
+            }
+        };
+        let head = f.args.head.iter().map(|ty| hint(ty, self.types())).collect();
+        let tail = f.args.tail.as_ref().map(|ty| hint(ty, self.types()));
+        TySeq { head: head, tail: tail }
+    }
+
     fn visit_func_call(&mut self, functy: &Spanned<Ty>, selfinfo: Option<Spanned<Slot>>,
                        args: &'inp Spanned<Args>, expspan: Span) -> Result<Exitable<SlotSeq>> {
         let functy = if let Some(func) = self.env.resolve_exact_type(functy) {
@@ -2211,12 +3789,40 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             return Ok(Exitable::dummy());
         };
 
-        // construct hints; they are given at the best effort basis
-        let hint = if let Some(&Functions::Simple(ref f)) = functy.get_functions() {
-            let mut args = f.args.clone();
+        // a table with the `__call` metamethod is called with itself prepended to arguments
+        let callee = Slot::just(functy.base.clone()).with_loc(&functy);
+        let (functy, callee) = if let Some(call) = self.find_metamethod(&callee, b"__call")? {
+            if let Some(call) = self.env.resolve_exact_type(&call) {
+                (call.with_loc(&functy), Some(callee))
+            } else {
+                let call = call.with_loc(&functy);
+                self.env.error(&call, m::CallToInexactType { func: self.display(&call) }).done()?;
+                return Ok(Exitable::dummy());
+            }
+        } else {
+            (functy, None)
+        };
+
+        if functy.tag() == Some(Tag::NoDiscard) {
+            self.nodiscard_calls.insert(expspan);
+        }
+
+        // construct hints; they are given at the best effort basis.
+        // `setmetatable` should not turn table constructors into `table` via hints.
+        let hint = if functy.tag() == Some(Tag::SetMetatable) {
+            None
+        } else if let Some(&Functions::Simple(ref f)) = functy.get_functions() {
+            let mut args = if f.is_generic() {
+                self.generic_arg_hints(f)
+            } else {
+                f.args.clone()
+            };
             if selfinfo.is_some() && !args.head.is_empty() {
                 args.head.remove(0); // args do not contain self, so do hints
             }
+            if callee.is_some() && !args.head.is_empty() {
+                args.head.remove(0); // same for the callable table
+            }
             Some(SlotSeq::from_seq(args).all_with_loc(&functy))
         } else {
             None
@@ -2241,6 +3847,18 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             },
         };
 
+        if self.context().has_listeners() {
+            // arguments are ordered as they will be checked below
+            let mut allargtys = argtys.clone();
+            if let Some(ref selfinfo) = selfinfo {
+                allargtys.head.insert(0, selfinfo.clone());
+            }
+            if let Some(ref callee) = callee {
+                allargtys.head.insert(0, callee.clone());
+            }
+            self.context().notify_call(&functy, &allargtys, expspan)?;
+        }
+
         if !self.env.get_type_bounds(&functy).1.is_callable() {
             self.env.error(&functy, m::CallToNonFunc { func: self.display(&functy) }).done()?;
             return Ok(exit.with_dummy());
@@ -2265,72 +3883,299 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     if let Some(module) = self.require(modname, expspan)? {
                         self.env.import_types(module.exported_types.with_loc(expspan))?;
                         if let Some(ref returns) = module.returns {
+                            let name = Str::from(modname.to_vec());
+                            let msg = m::DeprecatedModule { name: &name };
+                            self.check_deprecated(returns, expspan, &msg)?;
                             return Ok(exit.with(SlotSeq::from(returns.clone())));
                         } else {
                             // the module never returns, subsequent statements won't execute
                             return Ok(exit.with_diverging())
                         }
-                    } else {
-                        return Ok(exit.with_dummy());
+                    } else {
+                        return Ok(exit.with_dummy());
+                    }
+                } else if argtys.ensure_at(0).get_dynamic() == Some(Dyn::Oops) {
+                    // the argument has already been reported
+                    return Ok(exit.with_dummy());
+                } else {
+                    let argspan = argtys.head.first().map_or(expspan, |arg| arg.span);
+                    let slot = self.require_dynamic(argspan, expspan)?;
+                    return Ok(exit.with(SlotSeq::from(slot)));
+                }
+            }
+
+            // assert(expr)
+            Some(Tag::Assert) => {
+                if nargs < 1 {
+                    // TODO should display the true name
+                    self.env.error(expspan, m::BuiltinGivenLessArgs { name: "assert", nargs: 1 })
+                            .done()?;
+                    return Ok(exit.with_dummy());
+                }
+
+                // non-list arguments have no usable conditions (always evaluate to true)
+                if let Args::List(ref args) = args.base {
+                    if let (Some(cond), _seq) = self.collect_conds_from_exp(&args[0])? {
+                        self.assert_cond(cond, false)?;
+                    }
+                    // the narrowing persists until the end of the current block
+                    self.narrow_by_cond(&args[0], false);
+                }
+            }
+
+            // assert_not(expr)
+            Some(Tag::AssertNot) => {
+                if nargs < 1 {
+                    // TODO should display the true name
+                    self.env.error(expspan,
+                                   m::BuiltinGivenLessArgs { name: "assert-not", nargs: 1 })
+                            .done()?;
+                    return Ok(exit.with_dummy());
+                }
+
+                if let Args::List(ref args) = args.base {
+                    if let (Some(cond), _seq) = self.collect_conds_from_exp(&args[0])? {
+                        self.assert_cond(cond, true)?;
+                    }
+                    self.narrow_by_cond(&args[0], true);
+                }
+            }
+
+            // assert_type(expr)
+            Some(Tag::AssertType) => {
+                if nargs < 2 {
+                    // TODO should display the true name
+                    self.env.error(expspan,
+                                   m::BuiltinGivenLessArgs { name: "assert-type", nargs: 2 })
+                            .done()?;
+                    return Ok(exit.with_dummy());
+                }
+
+                if let Some(flags) = self.ext_literal_ty_to_flags(argtys.ensure_at(1))? {
+                    let cond = Cond::Flags(argtys.ensure_at(0).clone(), flags);
+                    self.assert_cond(cond, false)?;
+
+                    // the narrowing persists until the end of the current block
+                    if let Args::List(ref args) = args.base {
+                        if let Some((name, key)) = narrow_path_of(&args[0]) {
+                            self.narrow_path_with(name, key, &|ty, ctx| {
+                                ty.filter_by_flags(flags, ctx).ok()
+                            });
+                        }
+                    }
+                }
+            }
+
+            // setmetatable(table, metatable)
+            Some(Tag::SetMetatable) => {
+                if nargs < 2 {
+                    self.env.error(expspan,
+                                   m::BuiltinGivenLessArgs { name: "setmetatable", nargs: 2 })
+                            .done()?;
+                    return Ok(exit.with_dummy());
+                }
+
+                // a metatable with `__mode` makes the table (and the variable, if given) weak
+                let weak = self.env.context().is_weak_metatable(&argtys.ensure_at(1));
+                let tabslot = if weak {
+                    if let Args::List(ref ee) = args.base {
+                        if let Ex::Var(ref name) = *ee[0].base {
+                            self.mark_weak_var(name);
+                        }
                     }
+                    let tabslot = &argtys.head[0];
+                    Slot::new(tabslot.flex(), tabslot.unlift().clone().with_tag(Tag::Weak))
                 } else {
-                    return Ok(exit.with(SlotSeq::from(T::All)));
+                    argtys.head[0].base.clone()
+                };
+
+                // only records can have tracked metatables, others are checked as usual
+                let tab = self.env.resolve_exact_type(&argtys.ensure_at(0).unlift());
+                if let Some(&Tables::Fields(ref rvar)) = tab.as_ref().and_then(|t| t.get_tables()) {
+                    let meta = argtys.ensure_at(1).clone();
+                    let metaty = self.env.resolve_exact_type(&meta.unlift());
+                    let (_, metaflags) = self.env.get_type_bounds(&meta.unlift());
+                    if metaty.as_ref().map_or(false, |t| t.get_tables().is_some()) {
+                        self.context().set_metatable(rvar.clone(), Some(meta.base));
+                        return Ok(exit.with(SlotSeq::from(tabslot)));
+                    } else if (metaflags - T_NOISY_NIL).is_empty() {
+                        self.context().set_metatable(rvar.clone(), None);
+                        return Ok(exit.with(SlotSeq::from(tabslot)));
+                    }
+                }
+                if weak && tab.as_ref().map_or(false, |t| t.get_tables().is_some()) {
+                    return Ok(exit.with(SlotSeq::from(tabslot)));
                 }
             }
 
-            // assert(expr)
-            Some(Tag::Assert) => {
+            // getmetatable(table)
+            Some(Tag::GetMetatable) => {
                 if nargs < 1 {
-                    // TODO should display the true name
-                    self.env.error(expspan, m::BuiltinGivenLessArgs { name: "assert", nargs: 1 })
+                    self.env.error(expspan,
+                                   m::BuiltinGivenLessArgs { name: "getmetatable", nargs: 1 })
                             .done()?;
                     return Ok(exit.with_dummy());
                 }
 
-                // non-list arguments have no usable conditions (always evaluate to true)
-                if let Args::List(ref args) = args.base {
-                    if let (Some(cond), _seq) = self.collect_conds_from_exp(&args[0])? {
-                        self.assert_cond(cond, false)?;
+                let tab = self.env.resolve_exact_type(&argtys.ensure_at(0).unlift());
+                if let Some(&Tables::Fields(ref rvar)) = tab.as_ref().and_then(|t| t.get_tables()) {
+                    if let Some(meta) = self.env.context().get_metatable(rvar) {
+                        return Ok(exit.with(SlotSeq::from(meta)));
                     }
                 }
             }
 
-            // assert_not(expr)
-            Some(Tag::AssertNot) => {
+            // select(index, ...)
+            Some(Tag::Select) => {
                 if nargs < 1 {
-                    // TODO should display the true name
-                    self.env.error(expspan,
-                                   m::BuiltinGivenLessArgs { name: "assert-not", nargs: 1 })
+                    self.env.error(expspan, m::BuiltinGivenLessArgs { name: "select", nargs: 1 })
                             .done()?;
                     return Ok(exit.with_dummy());
                 }
 
-                if let Args::List(ref args) = args.base {
-                    if let (Some(cond), _seq) = self.collect_conds_from_exp(&args[0])? {
-                        self.assert_cond(cond, true)?;
+                let index = self.env.resolve_exact_type(&argtys.ensure_at(0).unlift());
+                if index.as_ref().and_then(|t| t.as_string()).map_or(false, |s| &s[..] == b"#") {
+                    return Ok(exit.with(SlotSeq::from(T::Integer)));
+                }
+
+                // the `n`-th variadic argument is at `argtys.head[n]`.
+                // any other index (including negative ones) is checked as usual
+                if let Some(n) = index.as_ref().and_then(|t| t.as_integer()) {
+                    if n >= 1 {
+                        let n = n as usize;
+                        let head = if n < argtys.head.len() {
+                            argtys.head.split_off(n).into_iter().map(|s| s.base).collect()
+                        } else {
+                            Vec::new()
+                        };
+                        let tail = argtys.tail.take().map(|s| s.base);
+                        return Ok(exit.with(SlotSeq { head: head, tail: tail }));
                     }
                 }
             }
 
-            // assert_type(expr)
-            Some(Tag::AssertType) => {
-                if nargs < 2 {
-                    // TODO should display the true name
-                    self.env.error(expspan,
-                                   m::BuiltinGivenLessArgs { name: "assert-type", nargs: 2 })
+            // pcall(f, ...) and xpcall(f, handler, ...)
+            Some(tag @ Tag::Pcall) | Some(tag @ Tag::Xpcall) => {
+                let (name, minargs) = if tag == Tag::Pcall { ("pcall", 1) } else { ("xpcall", 2) };
+                if nargs < minargs {
+                    self.env.error(expspan, m::BuiltinGivenLessArgs { name: name,
+                                                                      nargs: minargs })
                             .done()?;
                     return Ok(exit.with_dummy());
                 }
 
-                if let Some(flags) = self.ext_literal_ty_to_flags(argtys.ensure_at(1))? {
-                    let cond = Cond::Flags(argtys.ensure_at(0).clone(), flags);
-                    self.assert_cond(cond, false)?;
+                // only known function signatures are handled; `function` is checked as usual
+                let mut fargs = argtys.clone().unlift();
+                let func = self.simple_func_of(fargs.head.remove(0));
+                let handler = if tag == Tag::Xpcall {
+                    Some(self.simple_func_of(fargs.head.remove(0)))
+                } else {
+                    None
+                };
+                if let Some(func) = func {
+                    // errors from `f` are caught, so the exit from `f` is not propagated
+                    let Exitable(_, success) = self.check_callable(&func, &fargs, false)?;
+
+                    let failure = match handler {
+                        None => Ty::new(T::String),
+                        Some(None) => Ty::new(T::All),
+                        Some(Some(handler)) => {
+                            let msg = SpannedTySeq {
+                                head: vec![Ty::new(T::String).with_loc(&handler)],
+                                tail: None,
+                                span: handler.span,
+                            };
+                            let Exitable(_, returns) = self.check_callable(&handler, &msg, false)?;
+                            returns.head.first().cloned().unwrap_or(Ty::new(T::All))
+                        }
+                    };
+
+                    // (boolean, <first return type or the error value>, <remaining types>...)
+                    let mut results = success.clone();
+                    let first = match results.head.first() {
+                        Some(ty) => ty.clone(),
+                        None => results.tail.clone().unwrap_or(Ty::silent_nil()),
+                    };
+                    let first = first.union(&failure, false, self.types())
+                                     .map(|ty| ty.coerce()).unwrap_or(Ty::new(T::All));
+                    if results.head.is_empty() {
+                        results.head.push(first);
+                    } else {
+                        results.head[0] = first;
+                    }
+                    results.head.insert(0, Ty::new(T::Boolean));
+
+                    self.pcall_results = Some((expspan, success, failure));
+                    return Ok(exit.with(SlotSeq::from_seq(results)));
+                }
+            }
+
+            // coroutine.yield(value)
+            Some(Tag::CoroutineYield) => {
+                // only the first value is tracked; the top-level chunk cannot yield
+                if let Some(prev) = self.yields.pop() {
+                    let value = match argtys.head.first() {
+                        Some(arg) => arg.unlift().clone(),
+                        None => argtys.tail.as_ref().map_or(Ty::silent_nil(),
+                                                            |tail| tail.unlift().clone()),
+                    };
+                    let value = value.coerce();
+                    let yields = match prev {
+                        Some(prev) => {
+                            prev.union(&value, false, self.types()).unwrap_or(Ty::new(T::All))
+                        }
+                        None => value,
+                    };
+                    self.yields.push(Some(yields));
+                }
+            }
+
+            // coroutine.resume(co, value)
+            Some(Tag::CoroutineResume) => {
+                let co = argtys.head.first().and_then(|co| {
+                    self.env.resolve_exact_type(&co.unlift())
+                });
+                if let Some(&T::Coroutine(ref co)) = co.as_ref().map(|co| &**co) {
+                    // checked against `function(thread<Y, R>, R, any...) --> (boolean, Y)`
+                    let resume = Function {
+                        type_params: Vec::new(),
+                        args: TySeq {
+                            head: vec![Ty::new(T::Coroutine(Cow::Owned(co.clone().into_owned()))),
+                                       co.resumes.clone()],
+                            tail: Some(Ty::new(T::All)),
+                        },
+                        argnames: Vec::new(),
+                        returns: Some(TySeq {
+                            head: vec![Ty::new(T::Boolean), co.yields.clone()],
+                            tail: None,
+                        }),
+                    };
+                    let resume = Ty::new(T::func(resume)).with_loc(&functy);
+                    let Exitable(retexit, returns) =
+                        self.check_callable(&resume, &argtys.unlift(), false)?;
+                    return Ok(Exitable(cmp::max(exit, retexit), SlotSeq::from_seq(returns)));
                 }
             }
 
             // class([parent])
             Some(Tag::MakeClass(system)) => {
+                // a generic `[make_class]` function makes a generic class with the same
+                // (but fresh) type parameters for each call
+                let param_names: Vec<_> = match functy.get_functions() {
+                    Some(&Functions::Simple(ref f)) => {
+                        f.type_params.iter().map(|p| p.name.clone()).collect()
+                    }
+                    _ => Vec::new(),
+                };
+
                 if let Some(cid) = self.context().make_class(system, argtys, expspan)? {
+                    if !param_names.is_empty() {
+                        let params = param_names.into_iter().map(|name| {
+                            self.types().gen_tparam(name)
+                        }).collect();
+                        self.context().make_generic_class(cid, params, expspan)?;
+                    }
+
                     // classes are implicitly delay-checked
                     let slot = Slot::new(F::Module, Ty::new(T::Class(Class::Prototype(cid))));
                     self.register_module_if_needed(&slot);
@@ -2340,6 +4185,23 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 }
             }
 
+            // module(name, ...)
+            Some(Tag::BecomeModule) => {
+                if !self.env.is_top_level() {
+                    self.env.warn(expspan, m::BecomeModuleNotAtTopLevel {}).done()?;
+                } else {
+                    let seeall = match args.base {
+                        Args::List(ref ee) => ee.iter().skip(1).any(is_package_seeall),
+                        _ => false,
+                    };
+                    let rvar = self.types().gen_rvar();
+                    let table = T::Tables(Cow::Owned(Tables::Fields(rvar)));
+                    let table = Slot::new(F::Var, Ty::new(table));
+                    self.env.become_module(table.clone());
+                    self.module_table = Some((table.with_loc(expspan), seeall));
+                }
+            }
+
             // kailua_test.gen_tvar()
             Some(Tag::KailuaGenTvar) => {
                 return Ok(exit.with(SlotSeq::from(T::TVar(self.types().gen_tvar()))));
@@ -2374,9 +4236,73 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             false
         };
 
-        let Exitable(retexit, returns) =
+        // the callable table is an implicit first argument, so it is reported like `self`
+        let methodcall = if let Some(callee) = callee {
+            argtys.head.insert(0, callee);
+            true
+        } else {
+            methodcall
+        };
+
+        // `self` in the return type refers to the actual receiver (the first argument)
+        let recv = argtys.head.first().and_then(|recv| {
+            self.env.resolve_exact_type(&recv.unlift())
+        });
+
+        // coroutine.create(f) and coroutine.wrap(f) know types from the body of `f` if any
+        let coroutine = match (functy.tag(), &args.base) {
+            (Some(Tag::CoroutineCreate), &Args::List(ref ee)) |
+            (Some(Tag::CoroutineWrap), &Args::List(ref ee)) if !methodcall && !ee.is_empty() => {
+                let bodyty = argtys.ensure_at(0).unlift().clone();
+                self.coroutine_from_func(&ee[0], &bodyty)
+            }
+            _ => None,
+        };
+
+        // custom tags are invoked with checked arguments, which are consumed by the check
+        let custom = match functy.tag() {
+            Some(Tag::Custom(id)) => Some((id, argtys.clone())),
+            _ => None,
+        };
+
+        let Exitable(retexit, mut returns) =
             self.check_callable(&functy, &argtys.unlift(), methodcall)?;
 
+        if let Some(co) = coroutine {
+            let ty = if functy.tag() == Some(Tag::CoroutineCreate) {
+                T::Coroutine(Cow::Owned(co))
+            } else {
+                T::func(Function {
+                    type_params: Vec::new(),
+                    args: TySeq { head: vec![co.resumes], tail: None },
+                    argnames: Vec::new(),
+                    returns: Some(TySeq { head: vec![co.yields], tail: None }),
+                })
+            };
+            returns = TySeq::from(Ty::new(ty));
+        }
+
+        if let Some((id, argtys)) = custom {
+            returns = self.context().custom_tag_call(id, &argtys, returns, expspan)?;
+        }
+
+        if functy.tag() == Some(Tag::GlobalEval) {
+            self.global_eval = Some((expspan, false));
+            self.reasserted_globals.clear();
+        }
+
+        if let Some(recv) = recv {
+            if let T::Class(Class::Instance(_)) = *recv {
+                let recv = recv.without_nil();
+                for ty in returns.head.iter_mut().chain(returns.tail.as_mut()) {
+                    if ty.tag() == Some(Tag::SelfType) {
+                        let nil = ty.nil();
+                        *ty = recv.clone().union_nil(nil);
+                    }
+                }
+            }
+        }
+
         // merge exits; do not use `ExprExit::then` as this is the only way to generate Stop.
         // TODO this should be Var instead of Just!!!!!
         Ok(Exitable(cmp::max(exit, retexit), SlotSeq::from_seq(returns)))
@@ -2384,6 +4310,41 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
 
     fn visit_table(&mut self, tab: &'inp Table, tabspan: Span,
                    hint: Option<Spanned<Slot>>) -> Result<Exitable<T<'static>>> {
+        let (depth_limit, constraint_limit) = {
+            let opts = self.env.opts().borrow();
+            (opts.table_depth_limit(), opts.constraint_limit())
+        };
+        let limit = if self.table_depth >= depth_limit {
+            Some(ComplexityLimit::TableDepth)
+        } else if self.types().constraint_count() > constraint_limit {
+            Some(ComplexityLimit::Constraints)
+        } else {
+            None
+        };
+
+        if let Some(limit) = limit {
+            // the table is widened to `WHATEVER`, but items should be still checked
+            self.types().record_limit_exceeded(limit);
+            let mut exprexit = ExprExit::None;
+            for &(ref key, ref value) in &tab.items {
+                if let Some(ref key) = *key {
+                    let Exitable(exit, _) = self.visit_exp(key, None)?;
+                    exprexit = exprexit.collide(exit);
+                }
+                let Exitable(exit, _) = self.visit_exp(value, None)?;
+                exprexit = exprexit.collide(exit);
+            }
+            return Ok(exprexit.with(T::Dynamic(Dyn::User)));
+        }
+
+        self.table_depth += 1;
+        let ret = self.visit_table_(tab, tabspan, hint);
+        self.table_depth -= 1;
+        ret
+    }
+
+    fn visit_table_(&mut self, tab: &'inp Table, tabspan: Span,
+                    hint: Option<Spanned<Slot>>) -> Result<Exitable<T<'static>>> {
         // the finally resolved type depends on the hint type
         #[derive(Debug)]
         enum Target {
@@ -2391,7 +4352,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             Fields(bool /*explicitly typed*/, Vec<(Key, Slot)>),
             // need to ensure that there is no hole and everything is 1-based
             Array(Spanned<Slot>, i32 /*min*/, i32 /*max*/, i32 /*count*/),
-            Map(Spanned<Ty>, Spanned<Slot>),
+            Map(bool /*explicitly typed*/, Spanned<Ty>, Spanned<Slot>),
         }
 
         // if the hint exists and has vectors or maps in a tabular portion,
@@ -2407,7 +4368,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     Some(&Tables::Map(ref k, ref v)) => {
                         let k = k.clone().with_loc(&hint);
                         let v = v.clone().with_nil().with_loc(&hint);
-                        Some(Target::Map(k, v))
+                        Some(Target::Map(true, k, v))
                     },
                     Some(&Tables::Fields(_)) => Some(Target::Fields(true, Vec::new())),
                     None => None,
@@ -2444,6 +4405,33 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 }
             }
 
+            // a table without the hint is assumed to be a map if its first key is not literal
+            // (e.g. `{[obj] = data}`), keys and values are then widened as they are added
+            let implicit_map = match *target {
+                Target::Fields(false, ref fields) => {
+                    litkey.is_none() && fields.is_empty() && !varargs
+                },
+                _ => false,
+            };
+            // keys of an implicit map are widened; records are only compared by identity
+            // so they become `table` (otherwise their rows would be unified with each other)
+            let widen_key = |k: &Spanned<Ty>, env: &mut Env<R>| -> Ty {
+                let ty = k.base.clone().coerce();
+                match env.resolve_exact_type(&ty).as_ref().and_then(|t| t.get_tables()) {
+                    Some(&Tables::Fields(_)) => {
+                        Ty::new(T::Tables(Cow::Owned(Tables::All))).union_nil(ty.nil())
+                    },
+                    _ => ty,
+                }
+            };
+
+            if implicit_map {
+                let k = widen_key(&k, env).with_loc(&k);
+                let v = v.map(|v| Slot::just(v.unlift().clone().coerce()).with_nil());
+                *target = Target::Map(false, k, v);
+                return Ok(());
+            }
+
             match *target {
                 Target::Any => {}
 
@@ -2501,7 +4489,40 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     }
                 }
 
-                Target::Map(ref mut kty, ref mut vty) => {
+                Target::Map(false, ref mut kty, ref mut vty) => {
+                    let kwidened = widen_key(&k, env);
+                    match kty.base.union(&kwidened, false, env.types()) {
+                        Ok(ty) => { kty.base = ty; }
+                        Err(r) => {
+                            env.error(&k,
+                                      m::TableLitWithInvalidMapKey {
+                                          given: env.display(&k),
+                                          key: env.display(kty), value: env.display(vty),
+                                      })
+                               .note(&*kty, m::TableLitIsImplicitlyMap {})
+                               .report_types(r, TypeReportHint::None)
+                               .done()?;
+                        }
+                    }
+
+                    let vcoerced = v.unlift().clone().coerce();
+                    let vunion = vty.unlift().union(&vcoerced, false, env.types());
+                    match vunion {
+                        Ok(ty) => { vty.base = Slot::just(ty).with_nil(); }
+                        Err(r) => {
+                            env.error(&v,
+                                      m::TableLitWithInvalidMapValue {
+                                          given: env.display(&v),
+                                          key: env.display(kty), value: env.display(vty),
+                                      })
+                               .note(&*kty, m::TableLitIsImplicitlyMap {})
+                               .report_types(r, TypeReportHint::None)
+                               .done()?;
+                        }
+                    }
+                }
+
+                Target::Map(true, ref mut kty, ref mut vty) => {
                     if let Err(r) = k.assert_sub(kty, env.types()) {
                         env.error(&k,
                                   m::TableLitWithInvalidMapKey {
@@ -2542,8 +4563,29 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     add_field(&mut target, &mut self.env, kty, ty, false)?;
                 }
                 if let Some(ty) = vty.tail {
-                    let kty = Ty::new(T::Integer).with_loc(vty.span.end());
-                    add_field(&mut target, &mut self.env, kty, ty, true)?;
+                    // a table without the hint and with only positional values would be
+                    // a record, which cannot have an unknown number of values.
+                    // make it a vector of all values instead if possible.
+                    let positional = tab.items.iter().all(|&(ref key, _)| key.is_none());
+                    let elem = match target {
+                        Target::Fields(false, ref fields) if positional => {
+                            let mut elem = Some(ty.unlift().clone());
+                            for &(_, ref slot) in fields {
+                                elem = elem.and_then(|elem| {
+                                    slot.unlift().union(&elem, false, self.types()).ok()
+                                });
+                            }
+                            elem
+                        }
+                        _ => None,
+                    };
+
+                    if let Some(elem) = elem {
+                        target = Target::Array(Slot::just(elem).with_loc(tabspan), 1, len, len);
+                    } else {
+                        let kty = Ty::new(T::Integer).with_loc(vty.span.end());
+                        add_field(&mut target, &mut self.env, kty, ty, true)?;
+                    }
                 }
             } else {
                 let kty = if let Some(ref key) = *key {
@@ -2572,6 +4614,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     self.types().assert_rvar_includes(rvar.clone(), &fields).expect(
                         "cannot insert disjoint fields into a fresh row variable"
                     );
+                    self.env.context().seal(rvar.clone());
                 }
                 Tables::Fields(rvar)
             },
@@ -2588,7 +4631,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 Tables::Array(vty.base)
             },
 
-            Target::Map(kty, vty) => Tables::Map(kty.base, vty.base),
+            Target::Map(_, kty, vty) => Tables::Map(kty.base, vty.base),
         };
 
         Ok(exprexit.with(T::Tables(Cow::Owned(table))))
@@ -2608,6 +4651,11 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
     {
         let Exitable(exit, slotseq) = self.visit_exp_(exp, hint)?;
 
+        if self.context().has_listeners() {
+            let types = slotseq.clone().all_with_loc(exp);
+            self.context().notify_exp(exp, &types)?;
+        }
+
         // mark the resulting slot (sequence) to the span
         let slot = if let Some(slot) = slotseq.head.first() {
             slot.clone()
@@ -2621,6 +4669,103 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
         Ok(exit.with(slotseq.all_with_loc(exp)))
     }
 
+    // returns the module table and the key for given global variable in the module mode
+    fn module_field(&self, name: &Spanned<NameRef>) -> Option<(Spanned<Slot>, Spanned<Slot>)> {
+        if let (&NameRef::Global(ref global), Some((ref table, _))) =
+                (&name.base, self.module_table.as_ref()) {
+            let keystr = Str::from(global[..].to_owned());
+            let kty = Slot::just(Ty::new(T::Str(Cow::Owned(keystr)))).with_loc(name);
+            Some((table.clone(), kty))
+        } else {
+            None
+        }
+    }
+
+    // returns `None` if the variable is not defined, after reporting that.
+    // global variables are read from the module table first in the module mode
+    fn read_var(&mut self, name: &Spanned<NameRef>, expspan: Span) -> Result<Option<Slot>> {
+        if let Some((table, kty)) = self.module_field(name) {
+            match self.check_index_common(&table, &kty, expspan, false)? {
+                Index::Found(slot) => return Ok(Some(slot)),
+                Index::Missing | Index::Created(_) => {
+                    let seeall = self.module_table.as_ref().map_or(false, |&(_, seeall)| seeall);
+                    if !seeall {
+                        self.env.error(expspan, m::NoVar { name: self.env.get_name(name) })
+                                .done()?;
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+        self.read_env_var(name, expspan)
+    }
+
+    // same to `read_var` but does not consider the module mode
+    fn read_env_var(&mut self, name: &Spanned<NameRef>, expspan: Span) -> Result<Option<Slot>> {
+        if self.env.get_var(name).is_some() {
+            let mut slot = self.env.ensure_var(name)?;
+            if let NameRef::Global(ref global) = name.base {
+                if self.is_evaled_global(global) {
+                    slot = self.evaled_global(global, expspan)?;
+                }
+            }
+            // only the first variable visited in the module `return` is exempted
+            let returning_module = self.returning_module;
+            self.returning_module = false;
+            if !returning_module && self.deprecation(&slot).is_some() {
+                let name = self.env.get_name(name).clone();
+                self.check_deprecated(&slot, expspan, &m::DeprecatedVar { name: &name })?;
+            }
+            Ok(Some(slot))
+        } else {
+            self.env.error(expspan, m::NoVar { name: self.env.get_name(name) }).done()?;
+            Ok(None)
+        }
+    }
+
+    // true if the global variable is no longer known due to the prior `[geval]` call.
+    // explicitly assumed globals (including built-in libraries) are trusted
+    fn is_evaled_global(&mut self, name: &Name) -> bool {
+        self.global_eval.is_some() && !self.reasserted_globals.contains(name) &&
+            !self.env.context().is_assumed_global(name)
+    }
+
+    // returns a dynamic slot for the global variable altered by the prior `[geval]` call,
+    // reporting the reason on the first such access after the call
+    fn evaled_global(&mut self, name: &Name, expspan: Span) -> Result<Slot> {
+        use kailua_diag::Kind::Note;
+
+        if let Some((evalspan, false)) = self.global_eval {
+            self.global_eval = Some((evalspan, true));
+            self.env.add_span(Note, expspan, &m::UntypedGlobalAfterEval { name: name })?;
+            self.env.add_span(Note, evalspan, &m::GlobalEvalCall {})?;
+        }
+        Ok(Slot::just(Ty::new(T::Dynamic(Dyn::User))))
+    }
+
+    fn reassert_global(&mut self, name: &NameRef) {
+        if let NameRef::Global(ref name) = *name {
+            if self.global_eval.is_some() {
+                self.reasserted_globals.insert(name.clone());
+            }
+        }
+    }
+
+    // returns a global variable for `<table>.<key>` if the table has the `[genv]` tag
+    fn genv_global(&self, ety: &Slot, key: &[u8], keyspan: Span) -> Option<Spanned<NameRef>> {
+        let ty = ety.unlift();
+        let tag = if let T::TVar(_) = **ty {
+            self.env.resolve_exact_type(&ty).and_then(|t| t.tag())
+        } else {
+            ty.tag()
+        };
+        if tag == Some(Tag::GlobalEnv) {
+            Some(NameRef::Global(Name::from(key)).with_loc(keyspan))
+        } else {
+            None
+        }
+    }
+
     fn visit_exp_(&mut self, exp: &'inp Spanned<Exp>, hint: Option<SpannedSlotSeq>)
         -> Result<Exitable<SlotSeq>>
     {
@@ -2630,10 +4775,18 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             // it should not happen, but for the purpose of checker, the error nodes are dummies
             Ex::Oops => Exitable::dummy(),
 
-            // the explicit type `nil` is different from `nil` from an implicit expression
+            // the explicit type `nil` is different from `nil` from an implicit expression.
+            // the strict nil-safety mode makes it noisy so that it cannot go into `T!`
+            Ex::Nil if self.strict_nil() => Exitable::new(SlotSeq::from(Ty::noisy_nil())),
             Ex::Nil => Exitable::new(SlotSeq::from(Ty::silent_nil())),
             Ex::False => Exitable::new(SlotSeq::from(T::False)),
             Ex::True => Exitable::new(SlotSeq::from(T::True)),
+            // Lua 5.3+ distinguishes floats from (64-bit) integers, even when the value is same
+            Ex::Num(_) if self.lua_version().has_integers() =>
+                Exitable::new(SlotSeq::from(T::Number)),
+            Ex::Int(v) if i32::MIN as i64 <= v && v <= i32::MAX as i64 =>
+                Exitable::new(SlotSeq::from(T::Int(v as i32))),
+            Ex::Int(_) => Exitable::new(SlotSeq::from(T::Integer)),
             Ex::Num(v) if v.floor() == v =>
                 if i32::MIN as f64 <= v && v <= i32::MAX as f64 {
                     Exitable::new(SlotSeq::from(T::Int(v as i32)))
@@ -2655,10 +4808,9 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 }
             },
             Ex::Var(ref name) => {
-                if self.env.get_var(name).is_some() {
-                    Exitable::new(SlotSeq::from(self.env.ensure_var(name)?))
+                if let Some(slot) = self.read_var(name, exp.span)? {
+                    Exitable::new(SlotSeq::from(slot))
                 } else {
-                    self.env.error(exp, m::NoVar { name: self.env.get_name(name) }).done()?;
                     Exitable::dummy()
                 }
             },
@@ -2692,6 +4844,8 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 let ty = ty.into_first();
                 let kty = Slot::just(Ty::new(T::Str(Cow::Owned(keystr)))).with_loc(method.span);
                 let methinfo = self.check_rval_index(&ty, &kty, exp.span)?;
+                let key = Key::from(Str::from(method.base[..].to_owned()));
+                self.check_deprecated(&methinfo, method.span, &m::DeprecatedField { key: &key })?;
                 self.context().spanned_slots_mut().insert(methinfo.clone().with_loc(span));
                 let methinfo = methinfo.unlift().clone().with_loc(span);
                 exit.then(self.visit_func_call(&methinfo, Some(ty), args, exp.span)?)
@@ -2703,14 +4857,44 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 let ty = ty.into_first();
                 let kty = kty.into_first();
                 let exit = exit1.collide(exit2);
-                exit.with(SlotSeq::from(self.check_rval_index(&ty, &kty, exp.span)?))
+                if let Ex::Str(ref keystr) = *key.base {
+                    if let Some(name) = self.genv_global(&ty, keystr, key.span) {
+                        let slot = self.read_env_var(&name, exp.span)?;
+                        return Ok(exit.with(slot.map_or_else(SlotSeq::dummy, SlotSeq::from)));
+                    }
+                }
+                let mut slot = self.check_rval_index(&ty, &kty, exp.span)?;
+                if let Ex::Str(ref key) = *key.base {
+                    let key = Key::from(key.clone());
+                    self.check_deprecated(&slot, exp.span, &m::DeprecatedField { key: &key })?;
+                } else {
+                    self.check_deprecated(&slot, exp.span, &m::DeprecatedValue {})?;
+                }
+                if let (&Ex::Var(ref name), &Ex::Str(ref key)) = (&*e.base, &*key.base) {
+                    if let Some(narrowed) = self.narrowed_field(name, &Key::from(key.clone())) {
+                        slot = narrowed;
+                    }
+                }
+                exit.with(SlotSeq::from(slot))
             },
             Ex::IndexName(ref e, ref key) => {
                 let keystr = Str::from(key.base[..].to_owned());
                 let Exitable(exit, ty) = self.visit_exp(e, None)?;
                 let ty = ty.into_first();
-                let kty = Slot::just(Ty::new(T::Str(Cow::Owned(keystr)))).with_loc(key);
-                exit.with(SlotSeq::from(self.check_rval_index(&ty, &kty, exp.span)?))
+                if let Some(name) = self.genv_global(&ty, &key.base, key.span) {
+                    let slot = self.read_env_var(&name, exp.span)?;
+                    return Ok(exit.with(slot.map_or_else(SlotSeq::dummy, SlotSeq::from)));
+                }
+                let kty = Slot::just(Ty::new(T::Str(Cow::Owned(keystr.clone())))).with_loc(key);
+                let mut slot = self.check_rval_index(&ty, &kty, exp.span)?;
+                let msg = m::DeprecatedField { key: &Key::from(keystr.clone()) };
+                self.check_deprecated(&slot, key.span, &msg)?;
+                if let Ex::Var(ref name) = *e.base {
+                    if let Some(narrowed) = self.narrowed_field(name, &Key::from(keystr)) {
+                        slot = narrowed;
+                    }
+                }
+                exit.with(SlotSeq::from(slot))
             },
 
             Ex::Un(op, ref e) => {
@@ -2731,7 +4915,17 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
 
             Ex::Bin(ref l, op, ref r) => {
                 let Exitable(exit1, lhs) = self.visit_exp(l, None)?;
+
+                // `x.kind == "a" and ...` narrows `x` in the right operand, and so does `or`
+                let narrowed = self.narrowed.len();
+                match op.base {
+                    BinOp::And => self.narrow_by_cond(l, false),
+                    BinOp::Or => self.narrow_by_cond(l, true),
+                    _ => {}
+                }
                 let Exitable(exit2, rhs) = self.visit_exp(r, None)?;
+                self.restore_narrowed(narrowed);
+
                 let lhs = lhs.into_first();
                 let rhs = rhs.into_first();
                 let info = self.check_bin_op(&lhs, op.base, &rhs, exp.span)?;
@@ -2803,8 +4997,66 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
         Ok(exprexit.with(SpannedSlotSeq { head: head, tail: last.tail, span: expspan }))
     }
 
-    fn visit_kind(&mut self, modf: M, kind: &Spanned<Kind>) -> Result<Spanned<Slot>> {
+    fn strict_nil(&self) -> bool {
+        self.env.opts().borrow().strict_nil()
+    }
+
+    fn lua_version(&self) -> Lua {
+        self.env.opts().borrow().lua_version()
+    }
+
+    // returns `Some(message)` if the value has the `[deprecated]` tag.
+    // a declared function has a type variable bound to the tagged type, so it is resolved first
+    fn deprecation(&self, slot: &Slot) -> Option<Option<TagStrId>> {
+        let ty = slot.unlift();
+        let tag = if let T::TVar(_) = **ty {
+            self.env.resolve_exact_type(&ty).and_then(|t| t.tag())
+        } else {
+            ty.tag()
+        };
+        if let Some(Tag::Deprecated(message)) = tag { Some(message) } else { None }
+    }
+
+    // reports a use of the value with the `[deprecated]` tag, described by `msg`
+    fn check_deprecated(&mut self, slot: &Slot, span: Span, msg: &Localize) -> Result<()> {
+        use kailua_diag::Kind::Note;
+
+        if let Some(message) = self.deprecation(slot) {
+            let kind = self.env.opts().borrow().lint_severity("deprecated").to_kind();
+            if let Some(kind) = kind {
+                self.env.add_lint_span("deprecated", kind, span, msg)?;
+                if let Some(msg) = message.and_then(|id| self.types().get_tag_str(id).cloned()) {
+                    let msg = String::from_utf8_lossy(&msg);
+                    self.env.add_span(Note, span, &m::DeprecationMessage { msg: &msg })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // in the strict nil-safety mode, explicit types without `?` do not accept `nil`.
+    // `nil` itself and dynamic types are left as is.
+    fn strict_nil_ty(&self, ty: Ty) -> Ty {
+        if self.strict_nil() && ty.nil() == Nil::Silent && !ty.is_dynamic() {
+            if let T::None = *ty {
+                return ty;
+            }
+            ty.or_nil(Nil::Absent)
+        } else {
+            ty
+        }
+    }
+
+    fn resolve_kind(&mut self, kind: &Spanned<Kind>) -> Result<Ty> {
         let ty = Ty::from_kind(kind, &mut self.env)?;
+        if self.context().has_listeners() {
+            self.context().notify_type(kind, &ty)?;
+        }
+        Ok(ty)
+    }
+
+    fn visit_kind(&mut self, modf: M, kind: &Spanned<Kind>) -> Result<Spanned<Slot>> {
+        let ty = self.resolve_kind(kind)?;
         Ok(Slot::new(F::from(modf), ty).with_loc(kind))
     }
 
@@ -2816,7 +5068,8 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
         }
 
         let (explicit, ty) = if let Some(ref kind) = spec.kind {
-            (true, Ty::from_kind(kind, &mut self.env)?.with_loc(kind))
+            let ty = self.resolve_kind(kind)?;
+            (true, self.strict_nil_ty(ty).with_loc(kind))
         } else {
             (false, Ty::new(T::TVar(self.types().gen_tvar())).with_loc(&spec.base))
         };
@@ -2843,6 +5096,7 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
     /// Returns the resulting module (that may have diverged, in which case `returns` is `None`)
     /// or `None` when the error occurred and has been recovered.
     pub fn require(&mut self, modname: Spanned<&[u8]>, expspan: Span) -> Result<Option<Module>> {
+        self.env.opts().borrow_mut().on_require(modname);
         let mut module = self.context().get_loaded_module(&modname, expspan)?;
 
         if module.is_none() {
@@ -2852,29 +5106,100 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
             let opts = self.env.opts().clone();
             let chunk = match opts.borrow_mut().require_chunk(modname, self.env) {
                 Ok(chunk) => chunk,
-                Err(_) => {
-                    self.env.warn(modname, m::CannotResolveModName {}).done()?;
+                Err(e) => {
+                    // `Some(Stop)` means that the options have reported the error by themselves
+                    if e.is_none() {
+                        self.env.warn(modname, m::CannotResolveModName {}).done()?;
+                    }
 
                     // since the failure to resolve the module name is a mere warning,
                     // we don't want to return the dummy type here
-                    return Ok(Some(Module {
+                    let module = Module {
                         returns: Some(Slot::just(Ty::new(T::All))),
                         exported_types: HashMap::new(),
-                    }));
+                    };
+                    self.context().mark_module_as_loaded(&modname, module.clone());
+                    return Ok(Some(module));
                 }
             };
 
             let mut env = Env::new(self.env.context(), opts, chunk.map);
-            let exit = {
-                let mut sub = Checker::new(&mut env);
-                sub.visit_block(&chunk.block)?
-            };
-            module = env.return_from_module(&modname, exit >= Exit::Stop, expspan)?;
+            let diverging = Checker::new(&mut env).visit_module(&chunk.block)?;
+            module = env.return_from_module(&modname, diverging, expspan)?;
+
+            // the failed module is not checked again
+            let loaded = module.clone().unwrap_or_else(Module::dummy);
+            self.context().mark_module_as_loaded(&modname, loaded);
         }
 
         Ok(module)
     }
 
+    // `require` with a non-literal module name, handled as `Options::dynamic_require` says
+    fn require_dynamic(&mut self, argspan: Span, expspan: Span) -> Result<Slot> {
+        let dynamic_require = self.env.opts().borrow().dynamic_require();
+        let candidates = match dynamic_require {
+            DynamicRequire::Error => {
+                self.env.error(argspan, m::NonLiteralRequire {}).done()?;
+                return Ok(Slot::dummy());
+            }
+            DynamicRequire::Warn => {
+                self.env.warn(argspan, m::NonLiteralRequire {}).done()?;
+                return Ok(Slot::just(Ty::new(T::Dynamic(Dyn::User))));
+            }
+            DynamicRequire::OneOf(candidates) => candidates,
+        };
+
+        // diverging modules do not contribute to the result
+        let mut returns: Option<Ty> = None;
+        for modname in &candidates {
+            let module = self.require((&modname[..]).with_loc(argspan), expspan)?;
+            if let Some(ty) = module.and_then(|m| m.returns).map(|r| r.unlift().clone()) {
+                returns = match returns {
+                    Some(prev) => match prev.union(&ty, false, self.types()) {
+                        Ok(ty) => Some(ty),
+                        Err(_) => {
+                            self.env.warn(argspan, m::DynamicRequireIncompatible {}).done()?;
+                            return Ok(Slot::just(Ty::new(T::Dynamic(Dyn::User))));
+                        }
+                    },
+                    None => Some(ty),
+                };
+            }
+        }
+        Ok(Slot::just(returns.unwrap_or_else(|| Ty::new(T::Dynamic(Dyn::User)))))
+    }
+
+    fn op_rules(&self) -> OpRules {
+        OpRules::new(self.lua_version())
+    }
+
+    // returns a numeric type if the operand is a string literal that the operator
+    // implicitly converts to a number, and the operand itself otherwise
+    fn coerce_string_operand(&mut self, op: &str, coercion: Coercion,
+                             info: &Spanned<Slot>) -> Result<Spanned<Slot>> {
+        if coercion == Coercion::None {
+            return Ok(info.clone());
+        }
+        let s = match self.env.resolve_exact_type(&info.unlift())
+                              .and_then(|t| t.as_string().map(|s| s.to_owned())) {
+            Some(s) => s,
+            None => return Ok(info.clone()),
+        };
+        let ty = match (parse_numeric_string(&s), coercion) {
+            (Some(NumericString::Integer), _) => T::Integer,
+            (Some(NumericString::Float), Coercion::Number) => T::Number,
+            _ => return Ok(info.clone()), // will be reported as a wrong operand
+        };
+
+        let kind = self.env.opts().borrow().lint_severity("string-coercion").to_kind();
+        if let Some(kind) = kind {
+            let msg = m::StringCoercedToNumber { op: op, value: &s };
+            self.env.add_lint_span("string-coercion", kind, info.span, &msg)?;
+        }
+        Ok(Slot::just(Ty::new(ty)).with_loc(info))
+    }
+
     fn register_module_if_needed(&mut self, slot: &Slot) {
         if slot.flex() == F::Module {
             debug!("registering {:?} to the current scope", slot);
@@ -2946,14 +5271,16 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                 Ok((cond, SpannedSlotSeq::from(info.with_loc(exp))))
             }
 
-            Ex::Bin(ref l, Spanned { base: BinOp::Eq, .. }, ref r) => {
+            Ex::Bin(ref l, Spanned { base: op @ BinOp::Eq, .. }, ref r) |
+            Ex::Bin(ref l, Spanned { base: op @ BinOp::Ne, .. }, ref r) => {
                 let (lty, linfo) = self.collect_type_from_exp(l)?;
                 let (rty, rinfo) = self.collect_type_from_exp(r)?;
 
                 let linfo = linfo.into_first();
                 let rinfo = rinfo.into_first();
+                self.check_enum_comparison(&linfo, op, &rinfo, exp.span)?;
 
-                // detect an expression of the form `type(x) == y`.
+                // detect an expression of the form `type(x) == y` or `type(x) ~= y`.
                 // it is technically possible to detect `type(x) == type(y)` as well,
                 // but it is not common and results in a very subtle semi-equivalence condition
                 // that we cannot readily handle.
@@ -2974,6 +5301,13 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
                     },
                     (_, _) => None,
                 };
+                let cond = match cond {
+                    // `type(x) ~= y` excludes the type from x, as in `T - U`
+                    Some(Cond::Flags(ty, flags)) if op == BinOp::Ne => {
+                        Some(Cond::Flags(ty, !flags))
+                    }
+                    cond => cond,
+                };
 
                 // TODO when cond is None try to assert the type equivalence;
                 // it is currently not implemented due to bad interaction with sub-literal types
@@ -3076,53 +5410,22 @@ impl<'inp, 'envr, 'env, R: Report> Checker<'inp, 'envr, 'env, R> {
     }
 
     fn literal_ty_to_flags(&self, info: &Spanned<Slot>) -> Result<Option<Flags>> {
-        if let Some(s) = info.unlift().as_string() {
-            let tyname = &s[..];
-            let flags = match tyname {
-                b"nil" => T_NOISY_NIL,
-                b"number" => T_NUMBER,
-                b"string" => T_STRING,
-                b"boolean" => T_BOOLEAN,
-                b"table" => T_TABLE,
-                b"function" => T_FUNCTION,
-                b"thread" => T_THREAD,
-                b"userdata" => T_USERDATA,
-                _ => {
-                    self.env.error(info, m::UnknownLiteralTypeName {}).done()?;
-                    return Ok(None);
-                }
-            };
-            Ok(Some(flags))
-        } else {
-            Ok(None)
-        }
+        self.literal_ty_to_flags_(info, false)
     }
 
     // AssertType tag accepts more strings than Type
     fn ext_literal_ty_to_flags(&self, info: &Spanned<Slot>) -> Result<Option<Flags>> {
+        self.literal_ty_to_flags_(info, true)
+    }
+
+    fn literal_ty_to_flags_(&self, info: &Spanned<Slot>, ext: bool) -> Result<Option<Flags>> {
         if let Some(s) = info.unlift().as_string() {
-            let tyname = &s[..];
-            let (tyname, nilflags) = if tyname.ends_with(b"?") {
-                (&tyname[..tyname.len()-1], T_NOISY_NIL)
+            if let Some(flags) = type_name_to_flags(s, ext) {
+                Ok(Some(flags))
             } else {
-                (tyname, T_NONE)
-            };
-            let flags = match tyname {
-                b"nil" => T_NOISY_NIL,
-                b"int" | b"integer" => T_INTEGER, // XXX the real impl should follow
-                b"number" => T_NUMBER,
-                b"string" => T_STRING,
-                b"boolean" => T_BOOLEAN,
-                b"table" => T_TABLE,
-                b"function" => T_FUNCTION,
-                b"thread" => T_THREAD,
-                b"userdata" => T_USERDATA,
-                _ => {
-                    self.env.error(info, m::UnknownLiteralTypeName {}).done()?;
-                    return Ok(None);
-                }
-            };
-            Ok(Some(nilflags | flags))
+                self.env.error(info, m::UnknownLiteralTypeName {}).done()?;
+                Ok(None)
+            }
         } else {
             Ok(None)
         }
@@ -130,7 +130,7 @@ impl ClassSystem for DumbClassSystem {
 
     fn fmt_class(&self, cid: ClassId, f: &mut fmt::Formatter, st: &DisplayState) -> fmt::Result {
         let names = self.class_names.read();
-        match (&st.locale[..], &names.get(cid.1 as usize)) {
+        match (&st.locale.resolve(&["ko"])[..], &names.get(cid.1 as usize)) {
             (_,    &Some(ref name)) => write!(f, "{:+}", name),
             ("ko", &None) => write!(f, "이름 없는 클래스 #{}.{}", (cid.0).0, cid.1),
             (_,    &None) => write!(f, "unnamed class #{}.{}", (cid.0).0, cid.1),
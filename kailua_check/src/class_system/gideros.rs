@@ -18,8 +18,17 @@
 // it may be possible to force the use of `x:f()` to equate the self type and first argument,
 // but this may be a serious drawback, so for now we only handle the constructors specificially:
 // they cannot be accessed via instances, and in turn can be overriden as long as
-// non-self arguments match. for other cases overriding requires strict subtyping
-// and methods couldn't be overriden in a usual way.
+// non-self arguments match. methods (functions in the prototype with the instance `self`)
+// are similarly overriden by ignoring `self`, but otherwise arguments should be contravariant
+// and returns should be covariant. for other cases overriding requires strict subtyping.
+//
+// a class can be made generic over type parameters (`Queue<T>`). each distinct set of
+// type arguments is represented as a separate "specialized" class whose parent is
+// the generic class itself; it has no fields on its own and accessing the fields substitutes
+// type parameters with type arguments. consequently a specialized instance can be used
+// where the generic class is expected (this is required for passing it as `self`),
+// and specializations with different type arguments are unrelated, except that
+// dynamic type arguments are compatible to anything. generic classes cannot be inherited.
 //
 // Gideros class system also features the topmost genesis class (normally `Object`) which is
 // a parent of all other classes (and `class()` is equivalent to `class(Object)`).
@@ -36,6 +45,7 @@ use kailua_diag::{self, Report, Reporter};
 use kailua_syntax::{Str, Name};
 use kailua_types::ty::{TypeContext, ClassSystemId, ClassId, Class, Display, DisplayState, Lattice};
 use kailua_types::ty::{Slot, SpannedSlotSeq, Key, T, Ty, TySeq, F, Functions, Function, Nil};
+use kailua_types::ty::{TParam, Substitution, Tag};
 use kailua_types::diag::{TypeReportMore, TypeReportHint};
 use message as m;
 use super::ClassSystem;
@@ -59,6 +69,9 @@ struct ClassDef {
     new_ty: Option<Slot>, // a dummy slot after the first error on the constructor typing
     class_fields: HashMap<Key, Field>,
     instance_fields: HashMap<Key, Field>,
+    type_params: Vec<TParam>, // non-empty for generic classes
+    type_args: Option<Vec<Ty>>, // present for specialized classes, the parent is generic
+    overrides: HashMap<Key, Spanned<Slot>>, // parent methods overridden by yet unassigned fields
 }
 
 impl ClassDef {
@@ -144,6 +157,113 @@ impl GiderosClassSystem {
         None
     }
 
+    // returns the generic class and type arguments if the class is specialized
+    fn specialization(&self, cid: ClassId) -> Option<(ClassId, Vec<Ty>)> {
+        let classes = self.classes.read();
+        let cls = &classes[cid.1 as usize];
+        cls.type_args.as_ref().map(|args| {
+            (ClassId(cid.0, cls.parent.expect("specialized class without a generic class")),
+             args.clone())
+        })
+    }
+
+    // substitutes type parameters of the generic class in the field slot.
+    // the class lock should not be held as the substitution can specialize other classes.
+    fn specialize_slot(&self, generic: ClassId, args: &[Ty], slot: Slot,
+                       ctx: &mut TypeContext) -> Slot {
+        let params = self.classes.read()[generic.1 as usize].type_params.clone();
+        let mut subst = Substitution::new();
+        for (param, arg) in params.into_iter().zip(args) {
+            subst.bind(param, arg.clone());
+        }
+        let ty = ctx.resolve_exact_type(&slot.unlift()).unwrap_or_else(|| slot.unlift().clone());
+        Slot::new(slot.flex(), ty.substitute(&mut subst, ctx))
+    }
+
+    // returns a function type if the slot is a method, i.e. has an instance as the first argument
+    fn method_type(slot: &Slot, ctx: &mut TypeContext) -> Option<Function> {
+        let ty = ctx.resolve_exact_type(&slot.unlift())?;
+        let func = match *ty {
+            T::Functions(ref func) => match **func {
+                Functions::Simple(ref f) => f.to_owned(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let selfarg = ctx.resolve_exact_type(func.args.head.first()?)?;
+        if let T::Class(Class::Instance(_)) = *selfarg { Some(func) } else { None }
+    }
+
+    // checks if the method `slot` can override the parent method.
+    // `self` is excluded from the check (it is always a subtype of the parent's `self`);
+    // remaining arguments are contravariant and returns are covariant.
+    fn check_override(key: Spanned<&Key>, slot: &Slot, parent_slot: &Spanned<Slot>,
+                      expspan: Span, ctx: &mut TypeContext,
+                      report: &Report) -> kailua_diag::Result<()> {
+        let parent = Self::method_type(parent_slot, ctx).expect("parent field is not a method");
+        let mut child = match Self::method_type(slot, ctx) {
+            Some(ref child) if !child.is_generic() && !parent.is_generic() => child.clone(),
+            _ => {
+                // not a (simple) method, fall back to the strict subtyping
+                if let Err(r) = slot.assert_sub(parent_slot, ctx) {
+                    report.error(expspan,
+                                 m::NotSubtypeOfParentField {
+                                     key: &key, sub: slot.display(ctx),
+                                     sup: parent_slot.base.display(ctx),
+                                 })
+                          .note_if(parent_slot, m::PreviousParentFieldType {})
+                          .report_types(r, TypeReportHint::None)
+                          .done()?;
+                }
+                return Ok(());
+            }
+        };
+        child.args.head[0] = parent.args.head[0].clone();
+
+        if let Err(r) = parent.args.assert_sub(&child.args, ctx) {
+            report.error(expspan, m::BadOverridingMethodArgs { key: &key })
+                  .note_if(parent_slot, m::PreviousParentFieldType {})
+                  .report_types(r, TypeReportHint::MethodArgs)
+                  .done()?;
+        }
+
+        let returns = match (&child.returns, &parent.returns) {
+            (&Some(ref lhs), &Some(ref rhs)) => lhs.assert_sub(rhs, ctx),
+            (&Some(_), &None) => Err(ctx.gen_report()),
+            (&None, _) => Ok(()),
+        };
+        if let Err(r) = returns {
+            report.error(expspan, m::BadOverridingMethodReturns { key: &key })
+                  .note_if(parent_slot, m::PreviousParentFieldType {})
+                  .report_types(r, TypeReportHint::Returns)
+                  .done()?;
+        }
+
+        Ok(())
+    }
+
+    // returns abstract prototype fields that are not yet overridden in the class or its parents,
+    // in the order of keys. the nearest definition of each field decides if it is implemented.
+    fn unimplemented_abstracts(classes: &[ClassDef], cid: ClassId,
+                               ctx: &mut TypeContext) -> Vec<Spanned<Key>> {
+        let mut keys = Vec::new();
+        for (_, cls) in Ancestors::new(classes, cid.1) {
+            for key in cls.class_fields.keys() {
+                if keys.iter().any(|k: &Spanned<Key>| k.base == *key) {
+                    continue;
+                }
+                if let Some(slot) = Self::lookup(classes, cid, true, key) {
+                    let ty = ctx.resolve_exact_type(&slot.unlift());
+                    if ty.map_or(false, |ty| ty.tag() == Some(Tag::Abstract)) {
+                        keys.push(key.clone().with_loc(slot));
+                    }
+                }
+            }
+        }
+        keys.sort_by(|a, b| a.base.cmp(&b.base));
+        keys
+    }
+
     fn new_method_from_init(classes: &[ClassDef], cid: ClassId, init: &Spanned<Slot>,
                             ctx: &mut TypeContext, report: &Report) -> kailua_diag::Result<Slot> {
         // ensure that the type can be resolved...
@@ -195,10 +315,13 @@ impl GiderosClassSystem {
         }
 
         // now `init` is: function(/* removed self */, ...) -> any
-        // fix the return type to make a signature for the `new` method
+        // fix the return type to make a signature for the `new` method.
+        // type parameters of the generic class are inferred from arguments like functions.
         let returns = T::Class(Class::Instance(cid));
-        let ctor = Function { args: func.args, argnames: func.argnames,
-                              returns: Some(TySeq::from(returns)) };
+        let mut type_params = classes[cid.1 as usize].type_params.clone();
+        type_params.extend(func.type_params);
+        let ctor = Function { type_params: type_params, args: func.args,
+                              argnames: func.argnames, returns: Some(TySeq::from(returns)) };
         let ctor = Slot::new(F::Const, Ty::new(T::func(ctor)));
 
         Ok(ctor)
@@ -235,6 +358,9 @@ impl ClassSystem for GiderosClassSystem {
 
         let (parent, generation) = if let Some(parent) = parent {
             assert!((parent as usize) < classes.len(), "invalid ClassId");
+            if !classes[parent as usize].type_params.is_empty() {
+                report.error(outerspan, m::ClassInheritFromGenericClass {}).done()?;
+            }
             (Some(parent), classes[parent as usize].generation + 1)
         } else if classes.is_empty() {
             // only the genesis class can have no parent
@@ -252,6 +378,9 @@ impl ClassSystem for GiderosClassSystem {
             new_ty: None,
             class_fields: HashMap::new(),
             instance_fields: HashMap::new(),
+            type_params: Vec::new(),
+            type_args: None,
+            overrides: HashMap::new(),
         });
         Ok(Some(cid))
     }
@@ -285,6 +414,18 @@ impl ClassSystem for GiderosClassSystem {
             return true;
         }
 
+        // specializations of the same generic class are compatible when type arguments are
+        // same, except that dynamic type arguments are compatible to anything
+        {
+            let (lcls, rcls) = (&classes[lhs as usize], &classes[rhs as usize]);
+            if let (&Some(ref largs), &Some(ref rargs)) = (&lcls.type_args, &rcls.type_args) {
+                return lcls.parent == rcls.parent &&
+                       largs.iter().zip(rargs).all(|(l, r)| {
+                           l == r || l.is_dynamic() || r.is_dynamic()
+                       });
+            }
+        }
+
         // as we keep the "generation" (depth of inheritance tree) for each class,
         // class A is a subtype of B if and only if gen(A) >= gen(B) and
         // parent^{gen(A) - gen(B)}(B) = A. (equality only holds for A = B.)
@@ -308,6 +449,30 @@ impl ClassSystem for GiderosClassSystem {
             return Ok(None);
         }
 
+        // specialized classes forward to the generic class and substitute the result
+        if let Some((generic, args)) = self.specialization(cid) {
+            let slot = self.index_rval(Class::Instance(generic), key, expspan, ctx, report)?;
+            return Ok(slot.map(|slot| self.specialize_slot(generic, &args, slot, ctx)));
+        }
+
+        if is_new_key(&key) {
+            // abstract fields should be defined before any instantiation.
+            // the class lock should not be held while displaying the class.
+            let abstracts = Self::unimplemented_abstracts(&self.classes.read(), cid, ctx);
+            if !abstracts.is_empty() {
+                let names = abstracts.iter().map(|k| format!("`{}`", k.base))
+                                     .collect::<Vec<_>>().join(", ");
+                let inst = T::Class(Class::Instance(cid));
+                let mut more = report.error(expspan, m::UnimplementedAbstractFields {
+                    cls: inst.display(ctx), keys: &names,
+                });
+                for k in &abstracts {
+                    more = more.note(k, m::AbstractFieldDeclared { key: &k.base });
+                }
+                more.done()?;
+            }
+        }
+
         let mut classes = self.classes.write();
         if is_new_key(&key) {
             if let Some(new) = classes[cid.1 as usize].new_ty.as_ref().map(|s| s.clone()) {
@@ -384,6 +549,14 @@ impl ClassSystem for GiderosClassSystem {
             return Ok(None);
         }
 
+        // specialized classes forward to the generic class and substitute the result
+        if let Some((generic, args)) = self.specialization(cid) {
+            let res = self.index_lval(Class::Instance(generic), key, expspan, hint, ctx, report)?;
+            return Ok(res.map(|(created, slot)| {
+                (created, self.specialize_slot(generic, &args, slot, ctx))
+            }));
+        }
+
         let mut classes = self.classes.write();
         match classes[cid.1 as usize].fields(proto).get(&key) {
             Some(&Field::Slot(ref slot)) => {
@@ -418,6 +591,10 @@ impl ClassSystem for GiderosClassSystem {
             Slot::new(F::Unknown, Ty::new(tvar))
         };
 
+        // the nearest parent method overridden by this prototype field, if any
+        let mut overridden = None;
+        let is_override = proto && !is_init;
+
         // mark missing fields of the same name in parents
         // so that any further assignments to those marks are subject to subtyping constraints.
         let mut mark_missing = |cls: &mut ClassDef, proto: bool, or_insert: Field, slot: &Slot| {
@@ -431,6 +608,15 @@ impl ClassSystem for GiderosClassSystem {
                     Ok(Some((false, parent_slot.base.clone())))
                 },
 
+                // overriding methods are checked separately (see above)
+                // once the field type is known, only against the nearest parent method
+                Field::Slot(_) if is_override && overridden.is_some() => Ok(None),
+                Field::Slot(ref parent_slot)
+                        if is_override && Self::method_type(parent_slot, ctx).is_some() => {
+                    overridden = Some(parent_slot.clone());
+                    Ok(None)
+                },
+
                 // if there is a field with the same name in the parents
                 // a new field created should be a subtype of that field in order to be compatible.
                 // (note that the subtyping will be actually useful only for const slots.)
@@ -478,15 +664,54 @@ impl ClassSystem for GiderosClassSystem {
         }
 
         // finally update the current class
-        let fields = classes[cid.1 as usize].fields_mut(proto);
-        fields.insert(key.base.clone(), Field::Slot(slot.clone().with_loc(&key)));
+        let cls = &mut classes[cid.1 as usize];
+        cls.fields_mut(proto).insert(key.base.clone(), Field::Slot(slot.clone().with_loc(&key)));
+
+        // the type of the method is only known after the assignment unless hinted
+        if let Some(parent_slot) = overridden {
+            if hint.is_some() {
+                drop(classes);
+                Self::check_override(key, &slot, &parent_slot, expspan, ctx, report)?;
+            } else {
+                cls.overrides.insert(key.base.clone(), parent_slot);
+            }
+        }
 
         Ok(Some((true, slot)))
     }
 
+    fn complete_field(&self, cls: Class, key: Spanned<&Key>, expspan: Span,
+                      ctx: &mut TypeContext, report: &Report) -> kailua_diag::Result<()> {
+        let cid = match cls {
+            Class::Prototype(cid) => cid,
+            Class::Instance(_) => return Ok(()),
+        };
+
+        let overridden = self.classes.write()[cid.1 as usize].overrides.remove(&key.base);
+        if let Some(parent_slot) = overridden {
+            let slot = match Self::lookup(&self.classes.read(), cid, true, &key) {
+                Some(slot) => slot.base.clone(),
+                None => return Ok(()),
+            };
+            Self::check_override(key, &slot, &parent_slot, expspan, ctx, report)?;
+        }
+        Ok(())
+    }
+
     fn fmt_class(&self, cid: ClassId, f: &mut fmt::Formatter, st: &DisplayState) -> fmt::Result {
+        // type arguments may refer to other classes, so locks should be released before
+        if let Some((generic, args)) = self.specialization(cid) {
+            self.fmt_class(generic, f, st)?;
+            write!(f, "<")?;
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 { write!(f, ", ")?; }
+                arg.fmt_displayed(f, st)?;
+            }
+            return write!(f, ">");
+        }
+
         let names = self.class_names.read();
-        match (&st.locale[..], &names.get(cid.1 as usize)) {
+        match (&st.locale.resolve(&["ko"])[..], &names.get(cid.1 as usize)) {
             (_,    &Some(ref name)) => write!(f, "{:+}", name),
             ("ko", &None) => write!(f, "<이름 없는 클래스 #{}.{}>", (cid.0).0, cid.1),
             (_,    &None) => write!(f, "<unnamed class #{}.{}>", (cid.0).0, cid.1),
@@ -529,5 +754,71 @@ impl ClassSystem for GiderosClassSystem {
         }
         Ok(())
     }
+
+    fn make_generic(&self, cid: ClassId, params: Vec<TParam>, _outerspan: Span,
+                    _report: &Report) -> kailua_diag::Result<()> {
+        let mut classes = self.classes.write();
+        let cls = &mut classes[cid.1 as usize];
+        assert!(cls.type_params.is_empty() && cls.type_args.is_none(),
+                "make_generic called multiple times");
+        cls.type_params = params;
+        Ok(())
+    }
+
+    fn class_type_args(&self, cid: ClassId) -> Option<(ClassId, Vec<Ty>)> {
+        if let Some(spec) = self.specialization(cid) {
+            return Some(spec);
+        }
+
+        let classes = self.classes.read();
+        let params = &classes[cid.1 as usize].type_params;
+        if params.is_empty() {
+            None
+        } else {
+            Some((cid, params.iter().map(|p| Ty::new(T::Param(p.clone()))).collect()))
+        }
+    }
+
+    fn specialize_class(&self, cid: ClassId, args: Vec<Ty>) -> Option<ClassId> {
+        let mut classes = self.classes.write();
+
+        let generation = {
+            let cls = &classes[cid.1 as usize];
+            if cls.type_params.is_empty() || cls.type_params.len() != args.len() {
+                return None;
+            }
+
+            // the generic class itself is same to the class specialized with its own parameters
+            let is_own_param = |(param, arg): (&TParam, &Ty)| {
+                if let T::Param(ref p) = **arg { p == param } else { false }
+            };
+            if cls.type_params.iter().zip(&args).all(is_own_param) {
+                return Some(cid);
+            }
+
+            cls.generation + 1
+        };
+
+        let existing = classes.iter().position(|cls| {
+            cls.parent == Some(cid.1) && cls.type_args.as_ref() == Some(&args)
+        });
+        if let Some(specialized) = existing {
+            return Some(ClassId(cid.0, specialized as u32));
+        }
+
+        let specialized = ClassId(cid.0, classes.len() as u32);
+        trace!("specialized {:?} with {:?} as {:?}", cid, args, specialized);
+        classes.push(ClassDef {
+            generation: generation,
+            parent: Some(cid.1),
+            new_ty: None,
+            class_fields: HashMap::new(),
+            instance_fields: HashMap::new(),
+            type_params: Vec::new(),
+            type_args: Some(args),
+            overrides: HashMap::new(),
+        });
+        Some(specialized)
+    }
 }
 
@@ -3,7 +3,7 @@ use kailua_env::{Span, Spanned, WithLoc};
 use kailua_diag::{self, Report, Reporter};
 use kailua_syntax::Name;
 use kailua_types::ty::{TypeContext, ClassSystemId, ClassId, Class, Display, DisplayState};
-use kailua_types::ty::{Slot, SpannedSlotSeq, Key, T, Nil};
+use kailua_types::ty::{Slot, SpannedSlotSeq, Key, T, Ty, Nil, TParam};
 use message as m;
 
 /// Defines the various characteristics of class systems.
@@ -38,6 +38,32 @@ pub trait ClassSystem: Send + Sync + fmt::Debug {
                     outerspan: Span, ctx: &mut TypeContext,
                     report: &Report) -> kailua_diag::Result<Option<ClassId>>;
 
+    /// Makes a newly defined class generic over given type parameters.
+    ///
+    /// Invoked right after `make_class` or `assume_class` when type parameters are present,
+    /// so the class has no fields at this point.
+    /// The default implementation reports that the class system has no generic classes.
+    fn make_generic(&self, cid: ClassId, params: Vec<TParam>, outerspan: Span,
+                    report: &Report) -> kailua_diag::Result<()> {
+        let _ = (cid, params);
+        report.error(outerspan, m::NoGenericClassInClassSystem {}).done()
+    }
+
+    /// Returns the generic class and type arguments for given class if any.
+    ///
+    /// For the generic class itself type arguments are its own type parameters.
+    fn class_type_args(&self, _cid: ClassId) -> Option<(ClassId, Vec<Ty>)> {
+        None
+    }
+
+    /// Returns a class for given generic class specialized with type arguments.
+    ///
+    /// Should return `None` if the class is not generic or the number of arguments is incorrect,
+    /// and the same class for the same type arguments otherwise.
+    fn specialize_class(&self, _cid: ClassId, _args: Vec<Ty>) -> Option<ClassId> {
+        None
+    }
+
     /// Names a defined class. Fails with the previous name if already named.
     ///
     /// This is distinct from `make_class` and `assume_class` to allow unnamed classes,
@@ -76,6 +102,17 @@ pub trait ClassSystem: Send + Sync + fmt::Debug {
                   hint: Option<&Slot>, ctx: &mut TypeContext,
                   report: &Report) -> kailua_diag::Result<Option<(bool, Slot)>>;
 
+    /// Invoked after a field newly created by `index_lval` has been assigned.
+    ///
+    /// The class system can check constraints that had to be deferred until
+    /// the type of the field is known. Does nothing by default.
+    ///
+    /// The caller guarantees that the `cls` refers to a class defined from given class system.
+    fn complete_field(&self, _cls: Class, _key: Spanned<&Key>, _expspan: Span,
+                      _ctx: &mut TypeContext, _report: &Report) -> kailua_diag::Result<()> {
+        Ok(())
+    }
+
     /// Prints the nominal type name (or an appropriate placeholder if unnamed) to the formatter.
     fn fmt_class(&self, cid: ClassId, f: &mut fmt::Formatter, st: &DisplayState) -> fmt::Result;
 
@@ -0,0 +1,37 @@
+//! Custom type tags defined by the embedder.
+
+use kailua_env::{Span, Spanned};
+use kailua_diag::{self, Report};
+use kailua_types::ty::{TypeContext, CustomTagId, Slot, SpannedSlotSeq, TySeq};
+
+/// Defines the behavior of a custom type tag (`Tag::Custom`).
+///
+/// Custom tags are registered to the context with `Context::add_custom_tag`
+/// and can be used as an attribute in the Kailua type syntax (e.g. `[signal] function(...)`)
+/// just like built-in tags. This allows engine-specific functions to receive
+/// a special treatment without changing the checker.
+///
+/// As with `ClassSystem` the tag itself has no permanent knowledge about its identifier,
+/// so every method receives the current `CustomTagId`.
+pub trait CustomTag: Send + Sync {
+    /// Invoked when a function with this tag gets called.
+    /// Should return the return types of the call, which are `returns` by default.
+    ///
+    /// This happens after the argument type checking, so the `argtys` are guaranteed to be
+    /// correctly typed according to the original function. For method calls the receiver is
+    /// included as the first argument. `returns` are the return types of the original function.
+    fn on_call(&self, _self_id: CustomTagId, _argtys: &SpannedSlotSeq, returns: TySeq,
+               _outerspan: Span, _ctx: &mut TypeContext,
+               _report: &Report) -> kailua_diag::Result<TySeq> {
+        Ok(returns)
+    }
+
+    /// Invoked when a variable or field with this tag gets assigned.
+    ///
+    /// This happens before the actual assignment, which is done only when this returns true.
+    /// The implementation should report an error in that case. Returns true by default.
+    fn on_assign(&self, _self_id: CustomTagId, _lhs: &Spanned<Slot>, _rhs: &Spanned<Slot>,
+                 _ctx: &mut TypeContext, _report: &Report) -> kailua_diag::Result<bool> {
+        Ok(true)
+    }
+}
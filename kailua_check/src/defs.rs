@@ -1,22 +1,42 @@
-use kailua_env::{Span, SourceData, WithLoc};
-use kailua_diag::NoReport;
-use kailua_syntax::{Chunk, Lexer, Nest, Parser};
+use kailua_env::{Span, Spanned, SourceData, WithLoc};
+use kailua_diag::{self, Report, NoReport};
+use kailua_syntax::{Chunk, Kind, Lexer, Nest, Parser};
+use kailua_syntax::lang::Lua;
+
+/// Parses a code not from the `Source`, for example built-in definitions.
+pub fn parse_builtin_chunk(code: &[u8], report: &Report) -> kailua_diag::Result<Chunk> {
+    let span = Span::builtin(); // a special span independent of Source
+    let mut iter = code.iter().map(|&c| SourceData::U8(c).with_loc(span))
+                              .chain(Some(SourceData::EOF.with_loc(span)));
+    let mut lexer = Lexer::new(&mut iter, report);
+    let mut nest = Nest::new(&mut lexer);
+    let parser = Parser::new(&mut nest, report);
+    parser.into_chunk()
+}
+
+/// Parses a type not from the `Source`, for example given in the configuration.
+pub fn parse_builtin_kind(code: &[u8], report: &Report) -> kailua_diag::Result<Spanned<Kind>> {
+    let span = Span::builtin();
+    let mut iter = code.iter().map(|&c| SourceData::U8(c).with_loc(span))
+                              .chain(Some(SourceData::EOF.with_loc(span)));
+    let mut lexer = Lexer::new_in_meta(&mut iter, report);
+    let mut nest = Nest::new(&mut lexer);
+    let parser = Parser::new(&mut nest, report);
+    parser.into_kind()
+}
 
 pub struct Def {
     pub name: &'static str,
     pub code: &'static [u8],
+
+    /// The module name if the definition is a built-in module to be `require`d
+    /// instead of a set of global names.
+    pub module: Option<&'static str>,
 }
 
 impl Def {
     pub fn to_chunk(&self) -> Chunk {
-        let span = Span::builtin(); // a special span independent of Source
-        let mut iter = self.code.iter().map(|&c| SourceData::U8(c).with_loc(span))
-                                       .chain(Some(SourceData::EOF.with_loc(span)));
-        let no_report = NoReport;
-        let mut lexer = Lexer::new(&mut iter, &no_report);
-        let mut nest = Nest::new(&mut lexer);
-        let parser = Parser::new(&mut nest, &no_report);
-        match parser.into_chunk() {
+        match parse_builtin_chunk(self.code, &NoReport) {
             Ok(chunk) => chunk,
             Err(e) => panic!("failed to parse a built-in definition {:?}: {:?}", self.name, e),
         }
@@ -25,7 +45,7 @@ impl Def {
 
 macro_rules! defs {
     ($($defs:ident, $def:ident = $name:expr, $path:expr;)*) => ($(
-        const $def: Def = Def { name: $name, code: include_bytes!($path) };
+        const $def: Def = Def { name: $name, code: include_bytes!($path), module: None };
         const $defs: &'static [Def] = &[$def];
     )*);
 }
@@ -39,9 +59,36 @@ defs! {
     LUA51_IO_DEFS,      LUA51_IO_DEF      = "lua51_io",      "defs/lua51_io.lua";
     LUA51_OS_DEFS,      LUA51_OS_DEF      = "lua51_os",      "defs/lua51_os.lua";
     LUA51_DEBUG_DEFS,   LUA51_DEBUG_DEF   = "lua51_debug",   "defs/lua51_debug.lua";
+    LUA52_BASE_DEFS,    LUA52_BASE_DEF    = "lua52_base",    "defs/lua52_base.lua";
+    LUA52_PACKAGE_DEFS, LUA52_PACKAGE_DEF = "lua52_package", "defs/lua52_package.lua";
+    LUA52_STRING_DEFS,  LUA52_STRING_DEF  = "lua52_string",  "defs/lua52_string.lua";
+    LUA52_TABLE_DEFS,   LUA52_TABLE_DEF   = "lua52_table",   "defs/lua52_table.lua";
+    LUA52_MATH_DEFS,    LUA52_MATH_DEF    = "lua52_math",    "defs/lua52_math.lua";
+    LUA52_BIT32_DEFS,   LUA52_BIT32_DEF   = "lua52_bit32",   "defs/lua52_bit32.lua";
+    LUA52_IO_DEFS,      LUA52_IO_DEF      = "lua52_io",      "defs/lua52_io.lua";
+    LUA52_OS_DEFS,      LUA52_OS_DEF      = "lua52_os",      "defs/lua52_os.lua";
+    LUA52_DEBUG_DEFS,   LUA52_DEBUG_DEF   = "lua52_debug",   "defs/lua52_debug.lua";
+    LUA53_BASE_DEFS,    LUA53_BASE_DEF    = "lua53_base",    "defs/lua53_base.lua";
+    LUA53_STRING_DEFS,  LUA53_STRING_DEF  = "lua53_string",  "defs/lua53_string.lua";
+    LUA53_TABLE_DEFS,   LUA53_TABLE_DEF   = "lua53_table",   "defs/lua53_table.lua";
+    LUA53_MATH_DEFS,    LUA53_MATH_DEF    = "lua53_math",    "defs/lua53_math.lua";
+    LUA53_UTF8_DEFS,    LUA53_UTF8_DEF    = "lua53_utf8",    "defs/lua53_utf8.lua";
+    LUA53_IO_DEFS,      LUA53_IO_DEF      = "lua53_io",      "defs/lua53_io.lua";
+    LUA54_BASE_DEFS,    LUA54_BASE_DEF    = "lua54_base",    "defs/lua54_base.lua";
+    LUA54_MATH_DEFS,    LUA54_MATH_DEF    = "lua54_math",    "defs/lua54_math.lua";
+    LUA54_UTF8_DEFS,    LUA54_UTF8_DEF    = "lua54_utf8",    "defs/lua54_utf8.lua";
+    LUA54_DEBUG_DEFS,   LUA54_DEBUG_DEF   = "lua54_debug",   "defs/lua54_debug.lua";
+    LUAJIT_BIT_DEFS,    LUAJIT_BIT_DEF    = "luajit_bit",    "defs/luajit_bit.lua";
+    LUAJIT_JIT_DEFS,    LUAJIT_JIT_DEF    = "luajit_jit",    "defs/luajit_jit.lua";
     KAILUA_TEST_DEFS,   KAILUA_TEST_DEF   = "kailua_test",   "defs/kailua_test.lua";
 }
 
+// LuaJIT `ffi` is not a global and should be `require`d
+const LUAJIT_FFI_DEF: Def = Def {
+    name: "luajit_ffi", code: include_bytes!("defs/luajit_ffi.lua"), module: Some("ffi"),
+};
+const LUAJIT_FFI_DEFS: &'static [Def] = &[LUAJIT_FFI_DEF];
+
 const LUA51_DEFS: &'static [Def] = &[
     LUA51_BASE_DEF,
     LUA51_PACKAGE_DEF,
@@ -53,8 +100,84 @@ const LUA51_DEFS: &'static [Def] = &[
     LUA51_DEBUG_DEF,
 ];
 
-pub fn get_defs(name: &str) -> Option<&'static [Def]> {
+const LUA52_DEFS: &'static [Def] = &[
+    LUA52_BASE_DEF,
+    LUA52_PACKAGE_DEF,
+    LUA52_STRING_DEF,
+    LUA52_TABLE_DEF,
+    LUA52_MATH_DEF,
+    LUA52_BIT32_DEF,
+    LUA52_IO_DEF,
+    LUA52_OS_DEF,
+    LUA52_DEBUG_DEF,
+];
+
+// later versions share unchanged components with the earlier versions
+const LUA53_DEFS: &'static [Def] = &[
+    LUA53_BASE_DEF,
+    LUA52_PACKAGE_DEF,
+    LUA53_STRING_DEF,
+    LUA53_TABLE_DEF,
+    LUA53_MATH_DEF,
+    LUA53_UTF8_DEF,
+    LUA53_IO_DEF,
+    LUA52_OS_DEF,
+    LUA52_DEBUG_DEF,
+];
+
+const LUA54_DEFS: &'static [Def] = &[
+    LUA54_BASE_DEF,
+    LUA52_PACKAGE_DEF,
+    LUA53_STRING_DEF,
+    LUA53_TABLE_DEF,
+    LUA54_MATH_DEF,
+    LUA54_UTF8_DEF,
+    LUA53_IO_DEF,
+    LUA52_OS_DEF,
+    LUA54_DEBUG_DEF,
+];
+
+const LUAJIT_DEFS: &'static [Def] = &[
+    LUA51_BASE_DEF,
+    LUA51_PACKAGE_DEF,
+    LUA51_STRING_DEF,
+    LUA51_TABLE_DEF,
+    LUA51_MATH_DEF,
+    LUA51_IO_DEF,
+    LUA51_OS_DEF,
+    LUA51_DEBUG_DEF,
+    LUAJIT_BIT_DEF,
+    LUAJIT_JIT_DEF,
+    LUAJIT_FFI_DEF,
+];
+
+/// Names accepted by `get_defs`, except for internal ones.
+pub const LIBRARY_NAMES: &'static [&'static str] = &[
+    "lua51", "lua51_base", "lua51_package", "lua51_string", "lua51_table",
+    "lua51_math", "lua51_io", "lua51_os", "lua51_debug",
+    "lua52", "lua52_base", "lua52_package", "lua52_string", "lua52_table",
+    "lua52_math", "lua52_bit32", "lua52_io", "lua52_os", "lua52_debug",
+    "lua53", "lua53_base", "lua53_package", "lua53_string", "lua53_table",
+    "lua53_math", "lua53_utf8", "lua53_io", "lua53_os", "lua53_debug",
+    "lua54", "lua54_base", "lua54_package", "lua54_string", "lua54_table",
+    "lua54_math", "lua54_utf8", "lua54_io", "lua54_os", "lua54_debug",
+    "luajit", "luajit_bit", "luajit_jit", "luajit_ffi",
+    "lua",
+];
+
+/// Returns the definitions for given library name.
+///
+/// `lua` is a version-neutral name which resolves to the standard library
+/// of given Lua version.
+pub fn get_defs(name: &str, version: Lua) -> Option<&'static [Def]> {
     match name {
+        "lua" => match version {
+            Lua::Lua51 => Some(LUA51_DEFS),
+            Lua::Lua52 => Some(LUA52_DEFS),
+            Lua::Lua53 => Some(LUA53_DEFS),
+            Lua::Lua54 => Some(LUA54_DEFS),
+        },
+
         "lua51"         => Some(LUA51_DEFS),
         "lua51_base"    => Some(LUA51_BASE_DEFS),
         "lua51_package" => Some(LUA51_PACKAGE_DEFS),
@@ -65,6 +188,44 @@ pub fn get_defs(name: &str) -> Option<&'static [Def]> {
         "lua51_os"      => Some(LUA51_OS_DEFS),
         "lua51_debug"   => Some(LUA51_DEBUG_DEFS),
 
+        "lua52"         => Some(LUA52_DEFS),
+        "lua52_base"    => Some(LUA52_BASE_DEFS),
+        "lua52_package" => Some(LUA52_PACKAGE_DEFS),
+        "lua52_string"  => Some(LUA52_STRING_DEFS),
+        "lua52_table"   => Some(LUA52_TABLE_DEFS),
+        "lua52_math"    => Some(LUA52_MATH_DEFS),
+        "lua52_bit32"   => Some(LUA52_BIT32_DEFS),
+        "lua52_io"      => Some(LUA52_IO_DEFS),
+        "lua52_os"      => Some(LUA52_OS_DEFS),
+        "lua52_debug"   => Some(LUA52_DEBUG_DEFS),
+
+        "lua53"         => Some(LUA53_DEFS),
+        "lua53_base"    => Some(LUA53_BASE_DEFS),
+        "lua53_package" => Some(LUA52_PACKAGE_DEFS),
+        "lua53_string"  => Some(LUA53_STRING_DEFS),
+        "lua53_table"   => Some(LUA53_TABLE_DEFS),
+        "lua53_math"    => Some(LUA53_MATH_DEFS),
+        "lua53_utf8"    => Some(LUA53_UTF8_DEFS),
+        "lua53_io"      => Some(LUA53_IO_DEFS),
+        "lua53_os"      => Some(LUA52_OS_DEFS),
+        "lua53_debug"   => Some(LUA52_DEBUG_DEFS),
+
+        "lua54"         => Some(LUA54_DEFS),
+        "lua54_base"    => Some(LUA54_BASE_DEFS),
+        "lua54_package" => Some(LUA52_PACKAGE_DEFS),
+        "lua54_string"  => Some(LUA53_STRING_DEFS),
+        "lua54_table"   => Some(LUA53_TABLE_DEFS),
+        "lua54_math"    => Some(LUA54_MATH_DEFS),
+        "lua54_utf8"    => Some(LUA54_UTF8_DEFS),
+        "lua54_io"      => Some(LUA53_IO_DEFS),
+        "lua54_os"      => Some(LUA52_OS_DEFS),
+        "lua54_debug"   => Some(LUA54_DEBUG_DEFS),
+
+        "luajit"        => Some(LUAJIT_DEFS),
+        "luajit_bit"    => Some(LUAJIT_BIT_DEFS),
+        "luajit_jit"    => Some(LUAJIT_JIT_DEFS),
+        "luajit_ffi"    => Some(LUAJIT_FFI_DEFS),
+
         // only internally used
         "internal kailua_test" => Some(KAILUA_TEST_DEFS),
 
@@ -72,3 +233,19 @@ pub fn get_defs(name: &str) -> Option<&'static [Def]> {
     }
 }
 
+
+#[test]
+fn test_defs_parse_without_reports() {
+    use kailua_diag::{CollectedReport, Locale};
+
+    let versions = [Lua::Lua51, Lua::Lua52, Lua::Lua53, Lua::Lua54];
+    for name in LIBRARY_NAMES {
+        for &version in &versions {
+            for def in get_defs(name, version).expect("unknown library name") {
+                let report = CollectedReport::new(Locale::dummy());
+                assert!(parse_builtin_chunk(def.code, &report).is_ok(), "{:?}", def.name);
+                assert_eq!(report.into_reports(), vec![], "{:?}", def.name);
+            }
+        }
+    }
+}
@@ -20,6 +20,7 @@
 --#     function(f: function|integer?) --> table
 --#
 --# assume global `getmetatable`:
+--#     [getmetatable]
 --#     function(object: any) --> table
 --#
 --# assume global `ipairs`:
@@ -48,9 +49,8 @@
 --#     [generic_pairs] function(t: table) --> (function(table, any) --> (any?, any), table, any)
 --#
 --# -- TODO `f` should be once function
---# -- TODO genericity
 --# assume global `pcall`:
---#     function(f: function, any...) --> (boolean, any...)
+--#     [pcall] function(f: function, any...) --> (boolean, any...)
 --#
 --# assume global `print`:
 --#     function(any...)
@@ -64,14 +64,15 @@
 --# assume global `rawset`:
 --#     function(table: table, index: any, value: any) --> table
 --#
---# -- TODO genericity
 --# assume global `select`:
+--#     [select]
 --#     function(index: number|'#', any...) --> (any...)
 --#
 --# assume global `setfenv`:
 --#     function(f: function|integer?, table: table) --> function
 --#
 --# assume global `setmetatable`:
+--#     [setmetatable]
 --#     function(table: table, metatable: any?) --> table
 --#
 --# assume global `tonumber`:
@@ -92,20 +93,17 @@
 --#     string
 --#
 --# -- TODO `f` and `err` should be once function
---# -- TODO genericity
 --# assume global `xpcall`:
---#     function(f: function, err: function) --> (boolean, any...)
+--#     [xpcall] function(f: function, err: function) --> (boolean, any...)
 --#
 --# assume global `coroutine`:
 --#     {
---#         -- TODO genericity
---#         `create`: function(f: function) --> thread;
---#         `resume`: function(co: thread, any...) --> (boolean, any...);
+--#         `create`: [coroutine_create] function(f: function) --> thread;
+--#         `resume`: [coroutine_resume] function(co: thread, any...) --> (boolean, any...);
 --#         `running`: function() --> thread;
 --#         `status`: function(co: thread) --> string;
---#         -- TODO genericity
---#         `wrap`: function(f: function) --> thread;
---#         `yield`: function(any...) --> (any...);
+--#         `wrap`: [coroutine_wrap] function(f: function) --> function(any...) --> (any...);
+--#         `yield`: [coroutine_yield] function(any...) --> (any...);
 --#         ...
 --#     }
 
@@ -24,9 +24,9 @@
 --#         `input`: function(file: string|file?) --> file;
 --#         `lines`: function(filename: string?) --> function() --> string?;
 --#         -- TODO sequence conditional union: (file) | (nil, string)
---#         `open`: function(filename: string, mode: string?) --> (file, string);
+--#         `open`: [nodiscard] function(filename: string, mode: string?) --> (file, string);
 --#         `output`: function(file: string|file?) --> file;
---#         `popen`: function(prog: string, mode: string?) --> file;
+--#         `popen`: [nodiscard] function(prog: string, mode: string?) --> file;
 --#         `read`: function(format: '*a'|'*l'|integer?) --> string;
 --#         `tmpfile`: function() --> file;
 --#         `type`: function(obj: any) --> 'file'|'closed file';
@@ -14,8 +14,7 @@
 --#         `loadlib`: [geval] function(libname: string, funcname: string);
 --#         `path`: [package_path] string;
 --#         `preload`: vector<function(string) --> (function|string)?>;
---#         -- TODO error type not yet supported (should it be a slot?)
---#         --`seeall`: error "package.seeall is discouraged, use _G instead";
+--#         `seeall`: function(table);
 --#         ...
 --#     }
 
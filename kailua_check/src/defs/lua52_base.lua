@@ -0,0 +1,100 @@
+-- definitions for Lua 5.2 basic library
+
+--# -- TODO return generics
+--# assume global `assert`:
+--#     --[[ [assert] ]] function(v: any, message: string?)
+--#
+--# assume global `collectgarbage`:
+--#     function(opt: string?, arg: any?) --> any
+--#
+--# assume global `dofile`:
+--#     [geval] function(filename: string?) --> any
+--#
+--# assume global `error`:
+--#     function(message: string, level: integer?) --> !
+--#
+--# assume global `_G`:
+--#     [genv] table
+--#
+--# assume global `getmetatable`:
+--#     [getmetatable]
+--#     function(object: any) --> table
+--#
+--# assume global `ipairs`:
+--#     [generic_pairs]
+--#     function(t: vector<const WHATEVER>) -->
+--#         (function(vector<const WHATEVER>, integer) --> (integer?, any),
+--#          vector<const WHATEVER>, integer)
+--#
+--# -- TODO sequence conditional union: (function) | (nil, string)
+--# assume global `load`:
+--#     [geval] function(ld: string | (function() --> string?), source: string?,
+--#                      mode: 'b'|'t'|'bt'?, env: table?) --> (function, string)
+--#
+--# -- TODO sequence conditional union: (function) | (nil, string)
+--# assume global `loadfile`:
+--#     [geval] function(filename: string?, mode: 'b'|'t'|'bt'?,
+--#                      env: table?) --> (function, string)
+--#
+--# -- TODO genericity
+--# assume global `next`:
+--#     function(table: table, index: any?) --> (integer, any)
+--#
+--# assume global `pairs`:
+--#     [generic_pairs] function(t: table) --> (function(table, any) --> (any?, any), table, any)
+--#
+--# -- TODO `f` should be once function
+--# assume global `pcall`:
+--#     [pcall] function(f: function, any...) --> (boolean, any...)
+--#
+--# assume global `print`:
+--#     function(any...)
+--#
+--# assume global `rawequal`:
+--#     function(v1: any, v2: any) --> boolean
+--#
+--# assume global `rawget`:
+--#     function(table: table, index: any) --> any
+--#
+--# assume global `rawlen`:
+--#     function(v: table|string) --> integer
+--#
+--# assume global `rawset`:
+--#     function(table: table, index: any, value: any) --> table
+--#
+--# assume global `select`:
+--#     [select]
+--#     function(index: number|'#', any...) --> (any...)
+--#
+--# assume global `setmetatable`:
+--#     [setmetatable]
+--#     function(table: table, metatable: any?) --> table
+--#
+--# assume global `tonumber`:
+--#     function(e: any, base: integer?) --> number
+--#
+--# assume global `tostring`:
+--#     function(e: any) --> string
+--#
+--# -- TODO enumerate all the possibility?
+--# assume global `type`:
+--#     [type] function(v: any) --> string
+--#
+--# assume global `_VERSION`:
+--#     string
+--#
+--# -- TODO `f` and `err` should be once function
+--# assume global `xpcall`:
+--#     [xpcall] function(f: function, msgh: function, any...) --> (boolean, any...)
+--#
+--# assume global `coroutine`:
+--#     {
+--#         `create`: [coroutine_create] function(f: function) --> thread;
+--#         `resume`: [coroutine_resume] function(co: thread, any...) --> (boolean, any...);
+--#         `running`: function() --> (thread, boolean);
+--#         `status`: function(co: thread) --> string;
+--#         `wrap`: [coroutine_wrap] function(f: function) --> function(any...) --> (any...);
+--#         `yield`: [coroutine_yield] function(any...) --> (any...);
+--#         ...
+--#     }
+
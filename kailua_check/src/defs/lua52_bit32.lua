@@ -0,0 +1,19 @@
+-- definitions for Lua 5.2 bit32 library
+
+--# assume global `bit32`:
+--#     {
+--#         `arshift`: function(x: integer, disp: integer) --> integer;
+--#         `band`: function(integer...) --> integer;
+--#         `bnot`: function(x: integer) --> integer;
+--#         `bor`: function(integer...) --> integer;
+--#         `btest`: function(integer...) --> boolean;
+--#         `bxor`: function(integer...) --> integer;
+--#         `extract`: function(n: integer, field: integer, width: integer?) --> integer;
+--#         `lrotate`: function(x: integer, disp: integer) --> integer;
+--#         `lshift`: function(x: integer, disp: integer) --> integer;
+--#         `replace`: function(n: integer, v: integer, field: integer,
+--#                             width: integer?) --> integer;
+--#         `rrotate`: function(x: integer, disp: integer) --> integer;
+--#         `rshift`: function(x: integer, disp: integer) --> integer;
+--#         ...
+--#     }
@@ -0,0 +1,37 @@
+-- definitions for Lua 5.2 debug library
+
+--# -- TODO many of them will interfere with Kailua's own reasoning, so the support is minimal
+--# assume global `debug`:
+--#     {
+--#         `debug`: function();
+--#         `gethook`: function(thread: thread?) --> (WHATEVER, string, integer);
+--#         -- XXX overloading required
+--#         `getinfo`: function(`thread/function`: WHATEVER,
+--#                             `function/what`: WHATEVER, what: WHATEVER?) --> table;
+--#         -- XXX overloading required
+--#         `getlocal`: function(`thread/level`: WHATEVER,
+--#                              `level/local`: WHATEVER,
+--#                              `local`: WHATEVER?) --> (string, WHATEVER);
+--#         `getmetatable`: function(object: any) --> table;
+--#         `getregistry`: function() --> table;
+--#         `getupvalue`: function(func: function, up: integer) --> (string, WHATEVER);
+--#         `getuservalue`: function(u: WHATEVER) --> table?;
+--#         -- XXX overloading required
+--#         `sethook`: function(`thread/hook`: WHATEVER,
+--#                             `hook/mask`: WHATEVER,
+--#                             `mask/count`: WHATEVER, count: integer?);
+--#         -- XXX overloading required
+--#         `setlocal`: function(`thread/level`: WHATEVER,
+--#                              `level/local`: WHATEVER,
+--#                              `local/value`: WHATEVER, value: WHATEVER?) --> string;
+--#         `setmetatable`: function(object: any, table: table);
+--#         `setupvalue`: function(func: function, up: integer, value: WHATEVER) --> string;
+--#         `setuservalue`: function(udata: WHATEVER, value: table?) --> WHATEVER;
+--#         -- XXX overloading required
+--#         `traceback`: function(`thread/message`: WHATEVER?,
+--#                               `message/level`: WHATEVER?, level: integer?) --> string;
+--#         `upvalueid`: function(f: function, n: integer) --> WHATEVER;
+--#         `upvaluejoin`: function(f1: function, n1: integer, f2: function, n2: integer);
+--#         ...
+--#     }
+
@@ -0,0 +1,43 @@
+-- definitions for Lua 5.2 io library
+
+--# type local file = {
+--#     -- TODO method receivers and recursive types are generally not implemented yet
+--#     `close`: function(self: WHATEVER);
+--#     `flush`: function(self: WHATEVER);
+--#     `lines`: function(self: WHATEVER,
+--#                       '*a'|'*l'|'*L'|integer...) --> function() --> string?;
+--#     -- TODO should be: function(self, '*n') --> number &
+--#     --                 function(self, '*a') --> string &
+--#     --                 function(self, '*l'|integer?) --> string
+--#     -- for now, let's ignore *n (which is most useless)
+--#     `read`: function(self: WHATEVER, format: '*a'|'*l'|'*L'|integer?) --> string;
+--#     `seek`: function(self: WHATEVER, whence: 'set'|'cur'|'end'?, offset: integer?);
+--#     -- TODO again, 'no' does not accept an integer
+--#     `setvbuf`: function(self: WHATEVER, mode: 'no'|'full'|'line', size: integer?);
+--#     -- TODO sequence conditional union: (file) | (nil, string)
+--#     `write`: function(self: WHATEVER, string|number...) --> (WHATEVER, string);
+--# }
+--#
+--# assume global `io`:
+--#     {
+--#         `close`: function(file: file?);
+--#         `flush`: function();
+--#         -- TODO should be separated
+--#         `input`: function(file: string|file?) --> file;
+--#         `lines`: function(filename: string?,
+--#                           '*a'|'*l'|'*L'|integer...) --> function() --> string?;
+--#         -- TODO sequence conditional union: (file) | (nil, string)
+--#         `open`: [nodiscard] function(filename: string, mode: string?) --> (file, string);
+--#         `output`: function(file: string|file?) --> file;
+--#         `popen`: [nodiscard] function(prog: string, mode: string?) --> file;
+--#         `read`: function(format: '*a'|'*l'|'*L'|integer?) --> string;
+--#         `stderr`: file;
+--#         `stdin`: file;
+--#         `stdout`: file;
+--#         `tmpfile`: function() --> file;
+--#         `type`: function(obj: any) --> 'file'|'closed file';
+--#         -- TODO sequence conditional union: (file) | (nil, string)
+--#         `write`: function(string|number...) --> (file, string);
+--#         ...
+--#     }
+
@@ -0,0 +1,33 @@
+-- definitions for Lua 5.2 os library
+
+--# type local tm = {
+--#     year: integer, month: integer, day: integer,
+--#     hour: integer?, min: integer?, sec: integer?, isdst: boolean?
+--# }
+--#
+--# assume global `os`:
+--#     {
+--#         `clock`: function() --> number;
+--#         -- TODO it is very hard to recognize '*t'|!'*t' from other string types, ugh
+--#         `date`: function(format: string?, time: tm?) --> string | {
+--#             year: integer, month: integer, day: integer,
+--#             hour: integer, min: integer, sec: integer,
+--#             wday: integer, yday: integer, isdst: boolean
+--#         };
+--#         `difftime`: function(t2: number, t1: number) --> number;
+--#         -- TODO sequence conditional union: (true, 'exit'|'signal', integer) | (nil, ...)
+--#         `execute`: function(command: string?) --> (boolean, string, integer);
+--#         -- TODO diverging function signature
+--#         `exit`: function(code: boolean|integer?, close: boolean?);
+--#         `getenv`: function(varname: string) --> string;
+--#         -- TODO sequence conditional union: (true) | (nil, string)
+--#         -- there is also an undocumented 3rd return type (integer) for errno, omitted here
+--#         `remove`: function(filename: string) --> (boolean, string);
+--#         -- TODO sequence conditional union: (true) | (nil, string)
+--#         `rename`: function(oldname: string, newname: string) --> (boolean, string);
+--#         `setlocale`: function(locale: string?, category: string?) --> string;
+--#         `time`: function(table: tm?) --> integer;
+--#         `tmpname`: function() --> string;
+--#         ...
+--#     }
+
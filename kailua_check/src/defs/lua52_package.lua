@@ -0,0 +1,19 @@
+-- definitions for Lua 5.2 package library
+
+--# assume global `require`:
+--#     [require] function(modname: string) --> any
+--#
+--# assume global `package`:
+--#     {
+--#         `config`: string;
+--#         `cpath`: [package_cpath] string;
+--#         `loaded`: map<string, table>;
+--#         `loadlib`: [geval] function(libname: string, funcname: string);
+--#         `path`: [package_path] string;
+--#         `preload`: map<string, function(string) --> any>;
+--#         `searchers`: vector<function(string) --> (function|string)?>;
+--#         -- TODO sequence conditional union: (string) | (nil, string)
+--#         `searchpath`: function(name: string, path: string, sep: string?,
+--#                                rep: string?) --> (string, string);
+--#         ...
+--#     }
@@ -0,0 +1,19 @@
+-- definitions for Lua 5.3 table library
+
+--# -- TODO lacks genericity (yet)
+--# assume global `table`:
+--#     {
+--#         `concat`: function(table: vector<const string|number>, sep: string?,
+--#                            i: integer?, j: integer?) --> string;
+--#         -- TODO needs overloading with
+--#         --      function(table: vector<WHATEVER>, pos: integer, value: any)
+--#         `insert`: function(table: vector<WHATEVER>, value: any);
+--#         `move`: function(a1: table, f: integer, e: integer, t: integer,
+--#                          a2: table?) --> table;
+--#         `pack`: function(any...) --> table;
+--#         `remove`: function(table: vector<WHATEVER>, pos: integer?) --> any;
+--#         `sort`: function(table: vector<WHATEVER>,
+--#                          comp: (function(WHATEVER, WHATEVER) --> boolean)?);
+--#         `unpack`: function(list: table, i: integer?, j: integer?) --> (any...);
+--#         ...
+--#     }
@@ -0,0 +1,14 @@
+-- definitions for Lua 5.3 utf8 library
+
+--# assume global `utf8`:
+--#     {
+--#         `char`: function(integer...) --> string;
+--#         `charpattern`: string;
+--#         `codepoint`: function(s: string, i: integer?, j: integer?) --> (integer...);
+--#         `codes`: function(s: string) -->
+--#                      (function(string, integer) --> (integer?, integer), string, integer);
+--#         -- TODO sequence conditional union: (integer) | (nil, integer)
+--#         `len`: function(s: string, i: integer?, j: integer?) --> (integer?, integer);
+--#         `offset`: function(s: string, n: integer, i: integer?) --> integer?;
+--#         ...
+--#     }
@@ -0,0 +1,41 @@
+-- definitions for Lua 5.4 math library
+
+--# assume global `math`:
+--#     {
+--#         `abs`: function(x: number) --> number;
+--#         `acos`: function(x: number) --> number;
+--#         `asin`: function(x: number) --> number;
+--#         `atan`: function(y: number, x: number?) --> number;
+--#         `ceil`: function(x: number) --> integer;
+--#         `cos`: function(x: number) --> number;
+--#         `deg`: function(x: number) --> number;
+--#         `exp`: function(x: number) --> number;
+--#         `floor`: function(x: number) --> integer;
+--#         `fmod`: function(x: number, y: number) --> number;
+--#         `huge`: number;
+--#         `log`: function(x: number, base: number?) --> number;
+--#         -- TODO should really be
+--#         --      `function(x: integer, integer...) --> integer &
+--#         --       function(x: number, number...) --> number`
+--#         `max`: function(x: number, number...) --> number;
+--#         `maxinteger`: integer;
+--#         -- TODO should really be
+--#         --      `function(x: integer, integer...) --> integer &
+--#         --       function(x: number, number...) --> number`
+--#         `min`: function(x: number, number...) --> number;
+--#         `mininteger`: integer;
+--#         `modf`: function(x: number) --> (number, number);
+--#         `pi`: number;
+--#         `rad`: function(x: number) --> number;
+--#         -- TODO should really be
+--#         --      `function() --> number & function(m: integer, n: integer?) --> integer`
+--#         `random`: function(m: integer?, n: integer?) --> number;
+--#         `randomseed`: function(x: integer?, y: integer?) --> (integer, integer);
+--#         `sin`: function(x: number) --> number;
+--#         `sqrt`: function(x: number) --> number;
+--#         `tan`: function(x: number) --> number;
+--#         `tointeger`: function(x: any) --> integer?;
+--#         `type`: function(x: any) --> ('float'|'integer')?;
+--#         `ult`: function(m: integer, n: integer) --> boolean;
+--#         ...
+--#     }
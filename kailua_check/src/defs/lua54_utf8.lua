@@ -0,0 +1,16 @@
+-- definitions for Lua 5.4 utf8 library
+
+--# assume global `utf8`:
+--#     {
+--#         `char`: function(integer...) --> string;
+--#         `charpattern`: string;
+--#         `codepoint`: function(s: string, i: integer?, j: integer?,
+--#                               lax: boolean?) --> (integer...);
+--#         `codes`: function(s: string, lax: boolean?) -->
+--#                      (function(string, integer) --> (integer?, integer), string, integer);
+--#         -- TODO sequence conditional union: (integer) | (nil, integer)
+--#         `len`: function(s: string, i: integer?, j: integer?,
+--#                         lax: boolean?) --> (integer?, integer);
+--#         `offset`: function(s: string, n: integer, i: integer?) --> integer?;
+--#         ...
+--#     }
@@ -0,0 +1,33 @@
+-- definitions for LuaJIT ffi library, available via `require "ffi"`
+
+--# -- C data objects are opaque to Kailua
+--# type global `cdata` = userdata
+--#
+--# -- C types are either C declarations or ctype objects
+--# type local ct = string | cdata
+
+local ffi
+--# -- TODO the namespaces should be indexable with any C symbol
+--# assume ffi: {
+--#     `C`: WHATEVER;
+--#     `abi`: function(param: string) --> boolean;
+--#     `alignof`: function(ct: ct) --> integer;
+--#     `arch`: string;
+--#     `cast`: function(ct: ct, init: any) --> cdata;
+--#     `cdef`: function(def: string);
+--#     `copy`: function(dst: cdata, src: cdata|string, len: integer?);
+--#     `errno`: function(newerr: integer?) --> integer;
+--#     `fill`: function(dst: cdata, len: integer, c: integer?);
+--#     `gc`: function(cdata: cdata, finalizer: function?) --> cdata;
+--#     `istype`: function(ct: ct, obj: any) --> boolean;
+--#     `load`: function(name: string, `global`: boolean?) --> WHATEVER;
+--#     `metatype`: function(ct: ct, metatable: table) --> cdata;
+--#     `new`: function(ct: ct, any...) --> cdata;
+--#     `offsetof`: function(ct: ct, field: string) --> (integer?, integer?, integer?);
+--#     `os`: string;
+--#     `sizeof`: function(ct: ct, nelem: integer?) --> integer?;
+--#     `string`: function(ptr: cdata, len: integer?) --> string;
+--#     `typeof`: function(ct: ct, any...) --> cdata;
+--#     ...
+--# }
+return ffi
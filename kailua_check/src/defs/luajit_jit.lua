@@ -0,0 +1,18 @@
+-- definitions for LuaJIT jit library
+
+--# assume global `jit`:
+--#     {
+--#         `arch`: string;
+--#         `flush`: function(func: function|boolean?, recursive: boolean?);
+--#         `off`: function(func: function|boolean?, recursive: boolean?);
+--#         `on`: function(func: function|boolean?, recursive: boolean?);
+--#         `opt`: {
+--#             `start`: function(string...);
+--#             ...
+--#         };
+--#         `os`: string;
+--#         `status`: function() --> (boolean, string...);
+--#         `version`: string;
+--#         `version_num`: integer;
+--#         ...
+--#     }
@@ -8,24 +8,28 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use std::collections::{hash_map, HashMap, HashSet};
 use std::sync::Arc;
-use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
-use kailua_env::{self, Span, Spanned, WithLoc, ScopedId, ScopeMap, SpanMap};
-use kailua_diag::{Result, Kind, Report, Reporter, Locale, Localize};
+use kailua_env::{self, Unit, Span, Spanned, WithLoc, ScopedId, ScopeMap, SpanMap};
+use kailua_diag::{Result, Kind, Report, Reporter, Locale, Localize, CancellationToken};
 use kailua_syntax::{Str, Name};
-use kailua_syntax::ast::NameRef;
+use kailua_syntax::ast::{self, NameRef, Exp};
 use kailua_types::diag::{TypeReportHint, TypeReportMore};
 use kailua_types::ty::{Displayed, Display, DisplayState, DisplayName};
 use kailua_types::ty::{Ty, TySeq, Nil, T, Slot, SpannedSlotSeq, F, TVar, Lattice, Union, Tag};
-use kailua_types::ty::{TypeContext, TypeResolver, ClassId, ClassSystemId, Class};
-use kailua_types::ty::{Tables, Key};
+use kailua_types::ty::{TypeContext, TypeResolver, ClassId, ClassSystemId, Class, CustomTagId};
+use kailua_types::ty::{Tables, Key, TParam, RVar, ComplexityLimit};
 use kailua_types::ty::flags::*;
 use kailua_types::env::{Types, ClassProvider};
-use defs::get_defs;
+use defs::{get_defs, parse_builtin_chunk, parse_builtin_kind};
 use class_system::ClassSystem;
 use class_system::dumb::DumbClassSystem;
+use custom_tag::CustomTag;
+use listener::CheckListener;
 use options::Options;
+use stats::{StatsScope, StatsCollector};
 use check::Checker;
+use {Assertion, Constant};
 use message as m;
 
 /// A globally unique name reference.
@@ -212,6 +216,65 @@ pub struct TypeDef {
     pub ty: Ty,
 }
 
+/// An interface declared with `--# interface`.
+#[derive(Clone, Debug)]
+pub struct Interface {
+    /// The definition span.
+    pub span: Span,
+
+    /// Required fields in the order of declaration.
+    ///
+    /// The `bool` is true for methods, whose types do not include the receiver argument.
+    pub fields: Vec<(Spanned<Key>, bool /*method*/, Slot)>,
+}
+
+/// The kind of an `--# assume` directive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AssumptionKind {
+    /// `--# assume [global] NAME: TYPE`.
+    Variable,
+
+    /// `--# assume [static] NAME.FIELD...: TYPE`.
+    Field,
+
+    /// `--# assume NAME.FIELD...: method(...) --> ...`.
+    Method,
+
+    /// `--# assume [global] class NAME`.
+    Class,
+}
+
+/// An `--# assume` directive which has been used during the checking.
+///
+/// The checker trusts these directives without any verification,
+/// so they form the unchecked surface of the code base.
+#[derive(Clone, Debug)]
+pub struct Assumption {
+    /// The directive span.
+    pub span: Span,
+
+    pub kind: AssumptionKind,
+
+    /// True if the variable is global.
+    pub global: bool,
+
+    /// The variable name followed by field names, if any.
+    pub path: Vec<Name>,
+
+    /// The declared type.
+    ///
+    /// Methods include the `self` argument, and classes are given as the class prototype.
+    pub ty: Slot,
+}
+
+/// A state of a variable saved by `Env::save_var`, to be restored by `Env::restore_var`.
+#[derive(Clone, Debug)]
+pub struct SavedVar {
+    id: Id,
+    def: Option<NameDef>,
+    assumed_global: bool,
+}
+
 /// A scope.
 ///
 /// This is currently used to track the function frame and type names.
@@ -271,7 +334,16 @@ impl Module {
 #[derive(Clone, Debug)]
 enum LoadStatus {
     Done(Module),
-    Ongoing(Span), // span for who to blame
+    Ongoing, // see `Context::loading` for details
+}
+
+// a module being loaded by `require`
+#[derive(Clone, Debug)]
+struct LoadingModule {
+    name: Vec<u8>,
+    span: Span, // span for who to blame
+    // the module table made by `module(...)`, which is visible to `require` in advance
+    table: Option<Slot>,
 }
 
 /// A slot type "specification".
@@ -357,7 +429,7 @@ impl ClassProvider for ClassContext {
         if let Some(&(_, ref system)) = inner.class_systems.get((cid.0).0 as usize) {
             system.fmt_class(cid, f, st)
         } else {
-            match &st.locale[..] {
+            match &st.locale.resolve(&["ko"])[..] {
                 "ko" => write!(f, "<잘못된 클래스 {:?}>", cid),
                 _ =>    write!(f, "<Bad class {:?}>", cid),
             }
@@ -370,7 +442,7 @@ impl ClassProvider for ClassContext {
         if let Some(&(ref name, _)) = inner.class_systems.get(csid.0 as usize) {
             fmt::Debug::fmt(name, f)
         } else {
-            match &st.locale[..] {
+            match &st.locale.resolve(&["ko"])[..] {
                 "ko" => write!(f, "<잘못된 클래스 시스템 {:?}>", csid),
                 _ =>    write!(f, "<Bad class system {:?}>", csid),
             }
@@ -385,6 +457,16 @@ impl ClassProvider for ClassContext {
             false
         }
     }
+
+    fn class_type_args(&self, cid: ClassId) -> Option<(ClassId, Vec<Ty>)> {
+        let inner = self.inner.read();
+        inner.get(cid.0).and_then(|system| system.class_type_args(cid))
+    }
+
+    fn specialize_class(&self, cid: ClassId, args: Vec<Ty>) -> Option<ClassId> {
+        let inner = self.inner.read();
+        inner.get(cid.0).and_then(|system| system.specialize_class(cid, args))
+    }
 }
 
 /// The global context, which also contains the type context.
@@ -395,6 +477,36 @@ impl ClassProvider for ClassContext {
 pub struct Context<R> {
     report: R,
     output: Output,
+
+    // checked for each statement
+    cancel_token: Option<CancellationToken>,
+
+    // modules being loaded, from the outermost `require`
+    loading: Vec<LoadingModule>,
+
+    // notified in the order of registration
+    listeners: Vec<Box<CheckListener>>,
+
+    // the number of exceeded complexity limits already reported
+    reported_limits: usize,
+
+    // `--# ignore` directives in effect, updated while reporting (hence the lock)
+    suppressions: Mutex<Suppressions>,
+}
+
+struct Suppression {
+    directive: Span,
+    range: Span,
+    codes: Vec<Name>,
+    used: bool,
+}
+
+struct Suppressions {
+    list: Vec<Suppression>,
+
+    // true if the last report other than notes and causes has been suppressed,
+    // in which case following notes and causes are also suppressed
+    last_suppressed: bool,
 }
 
 /// A report-free version of `Context`. Suitable for analysis.
@@ -417,8 +529,23 @@ pub struct Output {
     // runtime information
     string_meta: Option<Spanned<Slot>>,
 
+    // global variables declared with `--# assume`, which survive `[geval]` calls
+    assumed_globals: HashSet<Name>,
+
+    // interfaces declared with `--# interface`, which are always global
+    interfaces: HashMap<Name, Interface>,
+
+    // `--# assume` directives in the order of checking, excluding built-in definitions
+    assumptions: Vec<Assumption>,
+
     // class and class system (shared with Types)
     classes: ClassContext,
+
+    // callbacks for custom tags, indexed by `CustomTagId` (names are kept in Types)
+    custom_tags: Vec<Box<CustomTag>>,
+
+    // inference statistics per module and function
+    stats: StatsCollector,
 }
 
 impl<R: Report> Context<R> {
@@ -436,8 +563,18 @@ impl<R: Report> Context<R> {
                 opened: HashSet::new(),
                 loaded: HashMap::new(),
                 string_meta: None,
+                assumed_globals: HashSet::new(),
+                interfaces: HashMap::new(),
+                assumptions: Vec::new(),
                 classes: classes,
-            }
+                custom_tags: Vec::new(),
+                stats: StatsCollector::new(),
+            },
+            cancel_token: None,
+            loading: Vec::new(),
+            listeners: Vec::new(),
+            reported_limits: 0,
+            suppressions: Mutex::new(Suppressions { list: Vec::new(), last_suppressed: false }),
         };
 
         // it is fine to return from the top-level, so we treat it as like a function frame
@@ -450,16 +587,38 @@ impl<R: Report> Context<R> {
         &self.report
     }
 
+    /// Makes the checker stop with `Stop` as soon as given token gets cancelled.
+    ///
+    /// The checker stops without reporting anything in that case,
+    /// and the context should be discarded as it may be in an inconsistent state.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancel_token = Some(token);
+    }
+
+    /// Returns `Err(Stop)` if the cancellation token has been cancelled.
+    pub fn keep_going(&self) -> Result<()> {
+        self.cancel_token.as_ref().map_or(Ok(()), |token| token.keep_going())
+    }
+
     pub fn open_library(&mut self, name: Spanned<&[u8]>, opts: Rc<RefCell<Options>>) -> Result<()> {
-        if let Some(defs) = str::from_utf8(&name.base).ok().and_then(get_defs) {
+        let version = opts.borrow().lua_version();
+        let defs = str::from_utf8(&name.base).ok().and_then(|name| get_defs(name, version));
+        if let Some(defs) = defs {
             // one library may consist of multiple files, so we defer duplicate check
             for def in defs {
                 if self.opened.insert(def.name.to_owned()) {
                     // the built-in code is parsed independently and has no usable span
                     let chunk = def.to_chunk();
                     let mut env = Env::new(self, opts.clone(), chunk.map);
-                    let mut checker = Checker::new(&mut env);
-                    checker.visit(&chunk.block)?
+                    if let Some(modname) = def.module {
+                        // built-in modules are loaded in advance, as if `require`d
+                        let modname = modname.as_bytes();
+                        let diverging = Checker::new(&mut env).visit_module(&chunk.block)?;
+                        env.return_from_module(modname, diverging, Span::dummy())?;
+                    } else {
+                        let mut checker = Checker::new(&mut env);
+                        checker.visit(&chunk.block)?
+                    }
                 }
             }
         } else {
@@ -468,23 +627,189 @@ impl<R: Report> Context<R> {
         Ok(())
     }
 
+    /// Declares global variables as if `--# assume global NAME: TYPE` is used for each pair.
+    /// The type is given in the Kailua syntax, and defaults to `WHATEVER` when missing.
+    pub fn assume_globals(&mut self, globals: &[(Vec<u8>, Option<Vec<u8>>)],
+                          opts: Rc<RefCell<Options>>) -> Result<()> {
+        if globals.is_empty() {
+            return Ok(());
+        }
+
+        let mut code = Vec::new();
+        for &(ref name, ref kind) in globals {
+            code.extend_from_slice(b"--# assume global ");
+            code.extend_from_slice(name);
+            code.extend_from_slice(b": ");
+            code.extend_from_slice(kind.as_ref().map_or(&b"WHATEVER"[..], |kind| &kind[..]));
+            code.push(b'\n');
+        }
+
+        // the declarations do not come from any file and have no usable span
+        let chunk = parse_builtin_chunk(&code, &self.report)?;
+        let mut env = Env::new(self, opts, chunk.map);
+        let mut checker = Checker::new(&mut env);
+        checker.visit(&chunk.block)
+    }
+
+    /// Declares constants as if `--# assume global NAME: const LITERAL` is used for each pair.
+    pub fn assume_constants(&mut self, constants: &[(Vec<u8>, Constant)],
+                            opts: Rc<RefCell<Options>>) -> Result<()> {
+        let globals: Vec<_> = constants.iter().map(|&(ref name, ref value)| {
+            let mut kind = b"const ".to_vec();
+            match *value {
+                Constant::Boolean(v) => kind.extend_from_slice(if v { b"true" } else { b"false" }),
+                Constant::Integer(v) => kind.extend_from_slice(v.to_string().as_bytes()),
+                Constant::String(ref s) => {
+                    // always use three-digit escapes so that the next digit is not absorbed
+                    kind.push(b'"');
+                    for &c in s {
+                        match c {
+                            b'"' | b'\\' => { kind.push(b'\\'); kind.push(c); }
+                            b'\x20'...b'\x7e' => kind.push(c),
+                            _ => kind.extend_from_slice(format!("\\{:03}", c).as_bytes()),
+                        }
+                    }
+                    kind.push(b'"');
+                }
+            }
+            (name.clone(), Some(kind))
+        }).collect();
+        self.assume_globals(&globals, opts)
+    }
+
+    /// Verifies an assertion against the checked program.
+    /// Returns a localized message for the failed assertion.
+    pub fn verify_assertion(&mut self, assertion: &Assertion,
+                            opts: Rc<RefCell<Options>>) -> Result<Option<String>> {
+        use kailua_diag::Localized;
+
+        let kind = match *assertion {
+            Assertion::Global(_, ref kind) | Assertion::Export(_, _, ref kind) => kind,
+        };
+
+        // the type does not come from any file and has no usable span
+        let kind = parse_builtin_kind(kind, &self.report)?;
+        let expected = {
+            let mut env = Env::new(self, opts, ScopeMap::new());
+            Ty::from_kind(&kind, &mut env)?
+        };
+
+        let locale = self.report.message_locale();
+        let message = match *assertion {
+            Assertion::Global(ref name, _) => {
+                let name = Name::from(name.clone());
+                let slot = self.ids.get(&Id::Global(name.clone()))
+                                   .and_then(|def| def.slot.slot().cloned());
+                if let Some(slot) = slot {
+                    let actual = slot.unlift().clone();
+                    if actual.assert_sub(&expected, &mut self.types).is_ok() {
+                        return Ok(None);
+                    }
+                    let msg = m::AssertionGlobalType {
+                        name: &name,
+                        expected: expected.display(&self.types as &TypeContext),
+                        actual: actual.display(&self.types as &TypeContext),
+                    };
+                    Localized::new(&msg, locale).to_string()
+                } else {
+                    Localized::new(&m::AssertionNoGlobal { name: &name }, locale).to_string()
+                }
+            }
+
+            Assertion::Export(ref module, ref field, _) => {
+                let returns = match self.loaded.get(module) {
+                    Some(&LoadStatus::Done(ref module)) => module.returns.clone(),
+                    _ => None,
+                };
+                let module = Str::from(module.clone());
+                let field = Str::from(field.clone());
+                let returns = if let Some(returns) = returns {
+                    returns
+                } else {
+                    let msg = m::AssertionNoModule { name: &module };
+                    return Ok(Some(Localized::new(&msg, locale).to_string()));
+                };
+
+                let fields = self.get_available_fields(&returns.unlift());
+                let slot = fields.and_then(|mut fields| fields.remove(&Key::from(field.clone())));
+                if let Some(slot) = slot {
+                    let actual = slot.unlift().clone();
+                    if actual.assert_sub(&expected, &mut self.types).is_ok() {
+                        return Ok(None);
+                    }
+                    let msg = m::AssertionExportType {
+                        module: &module,
+                        field: &field,
+                        expected: expected.display(&self.types as &TypeContext),
+                        actual: actual.display(&self.types as &TypeContext),
+                    };
+                    Localized::new(&msg, locale).to_string()
+                } else {
+                    let msg = m::AssertionNoExport { module: &module, field: &field };
+                    Localized::new(&msg, locale).to_string()
+                }
+            }
+        };
+        Ok(Some(message))
+    }
+
     pub fn get_loaded_module(&self, name: &[u8], span: Span) -> Result<Option<Module>> {
         match self.loaded.get(name) {
             Some(&LoadStatus::Done(ref module)) => Ok(Some(module.clone())),
             None => Ok(None),
+            Some(&LoadStatus::Ongoing) => self.report_require_cycle(name, span).map(Some),
+        }
+    }
 
-            // this is allowed in Lua 5.2 and later, but will result in a loop anyway.
-            Some(&LoadStatus::Ongoing(oldspan)) => {
-                self.error(span, m::RecursiveRequire {})
-                    .note(oldspan, m::PreviousRequire {})
-                    .done()?;
-                Ok(Some(Module::dummy()))
-            }
+    // reports a `require` cycle ending at `span` and returns the module seen from there.
+    //
+    // the cycle is allowed in Lua 5.2 and later, but will result in a loop anyway,
+    // unless the module has become a module table with `module(...)` (which is fine in 5.1).
+    // in the latter case the partially initialized module table is returned.
+    fn report_require_cycle(&self, name: &[u8], span: Span) -> Result<Module> {
+        let start = self.loading.iter().position(|m| m.name == name)
+                                .expect("ongoing module is not being loaded");
+        let cycle = &self.loading[start..];
+
+        let names: Vec<_> = cycle.iter().map(|m| String::from_utf8_lossy(&m.name)).collect();
+        let mut chain: Vec<String> = names.iter().map(|name| format!("`{}`", name)).collect();
+        chain.push(chain[0].clone());
+        let chain = chain.join(" -> ");
+
+        let table = cycle[0].table.clone();
+        let mut more = if table.is_some() {
+            self.warn(span, m::RecursiveRequireToModuleTable { cycle: &chain })
+        } else {
+            self.error(span, m::RecursiveRequire { cycle: &chain })
+        };
+        for (module, name) in cycle.iter().zip(&names) {
+            more = more.note(module.span, m::RequiredInCycle { name: name });
         }
+        more.done()?;
+
+        Ok(match table {
+            Some(table) => Module {
+                returns: Some(Slot::new(F::Var, table.unlift().clone())),
+                exported_types: HashMap::new(),
+            },
+            None => Module::dummy(),
+        })
     }
 
     pub fn mark_module_as_loading(&mut self, name: &[u8], span: Span) {
-        self.loaded.entry(name.to_owned()).or_insert(LoadStatus::Ongoing(span));
+        self.loaded.entry(name.to_owned()).or_insert(LoadStatus::Ongoing);
+        self.loading.push(LoadingModule { name: name.to_owned(), span: span, table: None });
+    }
+
+    /// Should be called after the module marked by `mark_module_as_loading` has been
+    /// loaded (by `Env::return_from_module`) or failed to load.
+    ///
+    /// `module` is what `require` returns for the subsequent calls.
+    pub fn mark_module_as_loaded(&mut self, name: &[u8], module: Module) {
+        if self.loading.last().map_or(false, |m| m.name == name) {
+            self.loading.pop();
+        }
+        self.loaded.insert(name.to_owned(), LoadStatus::Done(module));
     }
 
     pub fn make_class(&mut self, csid: ClassSystemId, argtys: SpannedSlotSeq,
@@ -494,6 +819,134 @@ impl<R: Report> Context<R> {
         cls.make_class(csid, argtys, outerspan, &mut self.output.types, &self.report)
     }
 
+    /// Registers a custom tag with given attribute name and returns its identifier.
+    ///
+    /// Returns `None` if the name has been already registered as a custom tag.
+    /// Built-in tags take precedence, so registering their names has no effect.
+    pub fn add_custom_tag(&mut self, name: Name, tag: Box<CustomTag>) -> Option<CustomTagId> {
+        let id = self.output.types.add_custom_tag(name)?;
+        assert_eq!(id.0 as usize, self.output.custom_tags.len());
+        self.output.custom_tags.push(tag);
+        Some(id)
+    }
+
+    pub fn custom_tag_call(&mut self, id: CustomTagId, argtys: &SpannedSlotSeq, returns: TySeq,
+                           outerspan: Span) -> Result<TySeq> {
+        let tag = self.output.custom_tags.get(id.0 as usize).expect("bad custom tag id");
+        tag.on_call(id, argtys, returns, outerspan, &mut self.output.types, &self.report)
+    }
+
+    pub fn custom_tag_assign(&mut self, id: CustomTagId, lhs: &Spanned<Slot>,
+                             rhs: &Spanned<Slot>) -> Result<bool> {
+        let tag = self.output.custom_tags.get(id.0 as usize).expect("bad custom tag id");
+        tag.on_assign(id, lhs, rhs, &mut self.output.types, &self.report)
+    }
+
+    /// Registers a listener to be notified of checked expressions, calls,
+    /// assignments and resolved types.
+    pub fn add_listener(&mut self, listener: Box<CheckListener>) {
+        self.listeners.push(listener);
+    }
+
+    pub fn has_listeners(&self) -> bool {
+        !self.listeners.is_empty()
+    }
+
+    pub fn notify_exp(&mut self, exp: &Spanned<Exp>, types: &SpannedSlotSeq) -> Result<()> {
+        for listener in &self.listeners {
+            listener.on_exp(exp, types, &mut self.output.types, &self.report)?;
+        }
+        Ok(())
+    }
+
+    pub fn notify_call(&mut self, func: &Spanned<Ty>, argtys: &SpannedSlotSeq,
+                       outerspan: Span) -> Result<()> {
+        for listener in &self.listeners {
+            listener.on_call(func, argtys, outerspan, &mut self.output.types, &self.report)?;
+        }
+        Ok(())
+    }
+
+    pub fn notify_assign(&mut self, lhs: &Spanned<Slot>, rhs: &Spanned<Slot>,
+                         init: bool) -> Result<()> {
+        for listener in &self.listeners {
+            listener.on_assign(lhs, rhs, init, &mut self.output.types, &self.report)?;
+        }
+        Ok(())
+    }
+
+    pub fn notify_type(&mut self, kind: &Spanned<ast::Kind>, ty: &Ty) -> Result<()> {
+        for listener in &self.listeners {
+            listener.on_type(kind, ty, &mut self.output.types, &self.report)?;
+        }
+        Ok(())
+    }
+
+    /// Suppresses reports within given range, as if `--# ignore` is given at `directive`.
+    ///
+    /// Every report is suppressed if `codes` is empty.
+    /// Otherwise only reports from given lints or with given diagnostic codes are suppressed.
+    pub fn add_suppression(&mut self, directive: Span, range: Span, codes: Vec<Name>) {
+        self.suppressions.lock().list.push(Suppression {
+            directive: directive, range: range, codes: codes, used: false,
+        });
+    }
+
+    /// Removes all suppressions for given unit and returns spans of unused directives.
+    pub fn take_unused_suppressions(&mut self, unit: Unit) -> Vec<Span> {
+        let mut unused = Vec::new();
+        self.suppressions.lock().list.retain(|s| {
+            if s.directive.unit() != unit {
+                return true;
+            }
+            if !s.used {
+                unused.push(s.directive);
+            }
+            false
+        });
+        unused
+    }
+
+    // returns true if the report from given lint (if any) with given diagnostic code (if any)
+    // should be suppressed. notes and causes follow the last report,
+    // and fatal errors are never suppressed
+    fn is_suppressed(&self, kind: Kind, span: Span,
+                     lint: Option<&str>, code: Option<&str>) -> bool {
+        let mut suppressions = self.suppressions.lock();
+        let suppressed = match kind {
+            Kind::Note | Kind::Cause => return suppressions.last_suppressed,
+            Kind::Fatal => false,
+            Kind::Info | Kind::Warning | Kind::Error => {
+                let found = suppressions.list.iter_mut().find(|s| {
+                    let matches = |code: &str| s.codes.iter().any(|c| **c == *code.as_bytes());
+                    s.range.contains(span.begin()) && s.range.contains_or_end(span.end()) &&
+                        (s.codes.is_empty() || lint.map_or(false, &matches) ||
+                                               code.map_or(false, &matches))
+                });
+                if let Some(s) = found {
+                    s.used = true;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+        suppressions.last_suppressed = suppressed;
+        suppressed
+    }
+
+    // makes following notes and causes suppressed, as if the last report has been suppressed
+    fn suppress_following(&self) {
+        self.suppressions.lock().last_suppressed = true;
+    }
+
+    /// Returns complexity limits exceeded since the last call, so that each is reported once.
+    pub fn take_exceeded_limits(&mut self) -> Vec<ComplexityLimit> {
+        let limits = self.output.types.exceeded_limits()[self.reported_limits..].to_owned();
+        self.reported_limits += limits.len();
+        limits
+    }
+
     pub fn assume_class(&mut self, csid: ClassSystemId, parent: Option<Spanned<ClassId>>,
                         outerspan: Span) -> Result<Option<ClassId>> {
         let classes = self.output.classes.inner.read();
@@ -501,6 +954,13 @@ impl<R: Report> Context<R> {
         cls.assume_class(csid, parent, outerspan, &mut self.output.types, &self.report)
     }
 
+    pub fn make_generic_class(&mut self, cid: ClassId, params: Vec<TParam>,
+                              outerspan: Span) -> Result<()> {
+        let classes = self.output.classes.inner.read();
+        let cls = classes.get(cid.0).expect("bad class system id");
+        cls.make_generic(cid, params, outerspan, &self.report)
+    }
+
     pub fn name_class(&mut self, cid: ClassId, name: Spanned<Name>) -> Result<()> {
         let classes = self.classes.inner.read();
         let cls = classes.get(cid.0).expect("bad class system id");
@@ -527,6 +987,25 @@ impl<R: Report> Context<R> {
         c.index_lval(cls, key, expspan, hint, &mut self.output.types, &self.report)
     }
 
+    pub fn complete_class_field(&mut self, cls: Class, key: Spanned<&Key>,
+                                expspan: Span) -> Result<()> {
+        let classes = self.output.classes.inner.read();
+        let c = classes.get(cls.system()).expect("bad class system id");
+        c.complete_field(cls, key, expspan, &mut self.output.types, &self.report)
+    }
+
+    /// Starts attributing the inference statistics to given module or function.
+    pub fn enter_stats_scope(&mut self, scope: StatsScope) {
+        let now = self.output.types.stats();
+        self.output.stats.enter(scope, now);
+    }
+
+    /// Stops attributing the inference statistics to the current module or function.
+    pub fn leave_stats_scope(&mut self) {
+        let now = self.output.types.stats();
+        self.output.stats.leave(now);
+    }
+
     pub fn into_output(self) -> Output {
         self.output
     }
@@ -537,6 +1016,43 @@ impl Output {
         &self.types
     }
 
+    /// Returns the inference statistics per module and function.
+    pub fn stats(&self) -> &StatsCollector {
+        &self.stats
+    }
+
+    /// Returns true if the global variable has been declared with `--# assume`.
+    pub fn is_assumed_global(&self, name: &Name) -> bool {
+        self.assumed_globals.contains(name)
+    }
+
+    /// Returns the interface with given name, if any.
+    pub fn get_interface(&self, name: &Name) -> Option<&Interface> {
+        self.interfaces.get(name)
+    }
+
+    /// Returns all `--# assume` directives used so far, in the order of checking.
+    ///
+    /// Directives from built-in library definitions (`--# open`) are not included.
+    pub fn assumptions(&self) -> &[Assumption] {
+        &self.assumptions
+    }
+
+    /// Records an `--# assume` directive. Ignored for built-in library definitions.
+    pub fn add_assumption(&mut self, assumption: Assumption) {
+        if assumption.span.unit() != Unit::builtin() {
+            self.assumptions.push(assumption);
+        }
+    }
+
+    /// Returns the module loaded by `require` with given name, if it has been fully loaded.
+    pub fn get_module(&self, name: &[u8]) -> Option<&Module> {
+        match self.loaded.get(name) {
+            Some(&LoadStatus::Done(ref module)) => Some(module),
+            _ => None,
+        }
+    }
+
     pub fn types_mut(&mut self) -> &mut Types {
         &mut self.types
     }
@@ -573,6 +1089,95 @@ impl Output {
         self.string_meta.clone()
     }
 
+    fn get_record_rvar(&self, slot: &Slot) -> Option<RVar> {
+        let ty = self.resolve_exact_type(&slot.unlift())?;
+        if let Some(&Tables::Fields(ref rvar)) = ty.get_tables() {
+            Some(rvar.clone())
+        } else {
+            None
+        }
+    }
+
+    fn get_rvar_field(&self, rvar: &RVar, key: &Key) -> Option<Slot> {
+        let mut value = None;
+        let _ = self.list_rvar_fields(rvar.clone(), &mut |k, v| {
+            if k == key {
+                value = Some(v.clone());
+                Err(())
+            } else {
+                Ok(())
+            }
+        });
+        value
+    }
+
+    /// Returns row variables of records which would be consulted, in this order,
+    /// when a field is missing from a record with given row variable.
+    ///
+    /// They are `__index` fields of the metatable, `__index` fields of their metatables
+    /// and so on, as long as they are records. A cycle ends the chain.
+    pub fn get_index_chain(&self, rvar: &RVar) -> Vec<RVar> {
+        let index = Key::from(Str::from(b"__index"[..].to_owned()));
+
+        let mut chain = Vec::new();
+        let mut rvar = rvar.clone();
+        loop {
+            let next = self.get_metatable(&rvar)
+                           .and_then(|meta| self.get_record_rvar(&meta))
+                           .and_then(|meta| self.get_rvar_field(&meta, &index))
+                           .and_then(|index| self.get_record_rvar(&index));
+            match next {
+                Some(next) => {
+                    if next == rvar || chain.contains(&next) {
+                        break;
+                    }
+                    chain.push(next.clone());
+                    rvar = next;
+                }
+                None => break,
+            }
+        }
+        chain
+    }
+
+    /// Returns true if given metatable has a `__mode` field of a string literal type
+    /// containing `k` or `v`, i.e. keys or values of the table are weak.
+    pub fn is_weak_metatable(&self, meta: &Slot) -> bool {
+        let mode = Key::from(Str::from(b"__mode"[..].to_owned()));
+        self.get_record_rvar(meta)
+            .and_then(|meta| self.get_rvar_field(&meta, &mode))
+            .and_then(|mode| self.resolve_exact_type(&mode.unlift()))
+            .map_or(false, |mode| {
+                mode.as_string().map_or(false, |s| s.iter().any(|&c| c == b'k' || c == b'v'))
+            })
+    }
+
+    /// Looks a field missing from a record up through its metatables (see `get_index_chain`).
+    pub fn index_via_metatable(&self, rvar: &RVar, key: &Key) -> Option<Slot> {
+        self.get_index_chain(rvar).iter().filter_map(|index| self.get_rvar_field(index, key)).next()
+    }
+
+    /// Returns the `__index` function which would be called when a field is missing from
+    /// a record with given row variable and all records in its index chain (`get_index_chain`).
+    pub fn get_index_function(&self, rvar: &RVar) -> Option<Slot> {
+        let index = Key::from(Str::from(b"__index"[..].to_owned()));
+        let last = self.get_index_chain(rvar).pop().unwrap_or_else(|| rvar.clone());
+        let func = self.get_metatable(&last)
+                       .and_then(|meta| self.get_record_rvar(&meta))
+                       .and_then(|meta| self.get_rvar_field(&meta, &index))?;
+        let is_func = self.resolve_exact_type(&func.unlift())
+                          .map_or(false, |ty| ty.get_functions().is_some());
+        if is_func { Some(func) } else { None }
+    }
+
+    /// Returns a metamethod (e.g. `__add`) from the tracked metatable of a record, if any.
+    pub fn get_metamethod(&self, slot: &Slot, key: &Key) -> Option<Slot> {
+        self.get_record_rvar(slot)
+            .and_then(|rvar| self.get_metatable(&rvar))
+            .and_then(|meta| self.get_record_rvar(&meta))
+            .and_then(|meta| self.get_rvar_field(&meta, key))
+    }
+
     // TODO if we've got a common crate for IDE support, this will be there
     pub fn get_available_fields<'a>(&'a self, ty: &Ty) -> Option<HashMap<Key, Slot>> {
         if let Some(mut ty) = self.resolve_exact_type(ty) {
@@ -613,6 +1218,14 @@ impl Output {
                         fields.insert(k.clone(), v.clone());
                         Ok(())
                     }).expect("list_rvar_fields exited early while we haven't break");
+
+                    // fields available via metatables are shadowed by the record's own fields
+                    for index in self.get_index_chain(rvar) {
+                        self.list_rvar_fields(index, &mut |k, v| -> result::Result<(), ()> {
+                            fields.entry(k.clone()).or_insert_with(|| v.clone());
+                            Ok(())
+                        }).expect("list_rvar_fields exited early while we haven't break");
+                    }
                     return Some(fields);
                 }
 
@@ -656,6 +1269,9 @@ impl<R: Report> Report for Context<R> {
     }
 
     fn add_span(&self, k: Kind, s: Span, m: &Localize) -> Result<()> {
+        if self.is_suppressed(k, s, None, m.code()) {
+            return Ok(());
+        }
         self.report.add_span(k, s, m)
     }
 }
@@ -668,6 +1284,10 @@ pub struct Env<'ctx, R: 'ctx> {
     scopes: Vec<Scope>,
     // separate from scoped types, `--# type` will set both
     exported_types: HashMap<Name, TypeDef>,
+    // the module table made by the `[become_module]` call (i.e. `module(...)`) if any
+    module_table: Option<Slot>,
+    // true if this is the start file, where the top level can implicitly create globals
+    start_file: bool,
 }
 
 impl<'ctx, R: Report> Env<'ctx, R> {
@@ -675,6 +1295,7 @@ impl<'ctx, R: Report> Env<'ctx, R> {
                map: ScopeMap<Name>) -> Env<'ctx, R> {
         let map_index = context.scope_maps.len();
         context.scope_maps.push(map);
+        context.types.set_literal_union_limit(opts.borrow().literal_union_limit());
         let global_frame = Frame { vararg: None, returns: Returns::None };
         Env {
             context: context,
@@ -683,9 +1304,19 @@ impl<'ctx, R: Report> Env<'ctx, R> {
             // we have local variables even at the global position, so we need at least one Scope
             scopes: vec![Scope::new_function(global_frame)],
             exported_types: HashMap::new(),
+            module_table: None,
+            start_file: false,
         }
     }
 
+    /// Marks this environment as the start file.
+    ///
+    /// In the strict globals mode (`Options::strict_globals`),
+    /// undeclared global variables can be assigned only at the top level of the start file.
+    pub fn set_start_file(&mut self) {
+        self.start_file = true;
+    }
+
     pub fn types(&mut self) -> &mut Types {
         &mut self.context.types
     }
@@ -699,6 +1330,36 @@ impl<'ctx, R: Report> Env<'ctx, R> {
         &self.opts
     }
 
+    /// Same to `Report::add_span` but for reports from given lint,
+    /// which can be also suppressed by `--# ignore: CODE`.
+    pub fn add_lint_span(&self, code: &str, kind: Kind, span: Span, msg: &Localize) -> Result<()> {
+        let kind = match self.override_kind(kind, msg) {
+            Some(kind) => kind,
+            None => { self.context.suppress_following(); return Ok(()); }
+        };
+        if self.context.is_suppressed(kind, span, Some(code), msg.code()) {
+            return Ok(());
+        }
+        self.context.report.add_span(kind, span, msg)
+    }
+
+    // applies `Options::code_severity` to the warning or error, `None` if it should be dropped.
+    // options are left intact while they are mutably borrowed (e.g. reports from `require`)
+    fn override_kind(&self, kind: Kind, msg: &Localize) -> Option<Kind> {
+        match kind {
+            Kind::Warning | Kind::Error => {}
+            _ => return Some(kind),
+        }
+        let severity = match (msg.code(), self.opts.try_borrow()) {
+            (Some(code), Ok(opts)) => opts.code_severity(code),
+            (_, _) => None,
+        };
+        match severity {
+            Some(severity) => severity.to_kind(),
+            None => Some(kind),
+        }
+    }
+
     pub fn scope_map(&self) -> &ScopeMap<Name> {
         &self.context.scope_maps[self.map_index]
     }
@@ -738,12 +1399,27 @@ impl<'ctx, R: Report> Env<'ctx, R> {
         self.context.resolve_exact_type(ty)
     }
 
+    /// Makes the current chunk a module with given table, as Lua 5.1 `module(...)` does.
+    ///
+    /// The table will be returned from the chunk unless it explicitly returns something.
+    pub fn become_module(&mut self, table: Slot) {
+        // the innermost module being loaded is always the current chunk, if any
+        if let Some(module) = self.context.loading.last_mut() {
+            module.table = Some(table.clone());
+        }
+        self.module_table = Some(table);
+    }
+
     pub fn return_from_module(mut self, modname: &[u8], diverging: bool,
                               span: Span) -> Result<Option<Module>> {
         // note that this scope is distinct from the global scope
         let top_scope = self.scopes.drain(..).next().unwrap();
         let returns = match top_scope.frame.unwrap().returns {
             Returns::Implicit(returns) | Returns::Explicit(returns) => Some(returns.into_first()),
+            // `module(...)` stores the module table to `package.loaded`, which `require` returns
+            Returns::None if !diverging && self.module_table.is_some() => {
+                self.module_table.take().map(|table| table.unlift().clone())
+            },
             // chunk implicitly returns nil at the end (unless it's diverging)
             Returns::None if !diverging => Some(Ty::noisy_nil()),
             Returns::None | Returns::Never => None,
@@ -838,6 +1514,11 @@ impl<'ctx, R: Report> Env<'ctx, R> {
         self.context.global_scope_mut().get_frame_mut().expect("global scope lacks a frame")
     }
 
+    /// Returns true if the current position is not inside any function in this file.
+    pub fn is_top_level(&self) -> bool {
+        self.scopes[1..].iter().all(|scope| scope.get_frame().is_none())
+    }
+
     pub fn get_vararg<'a>(&'a self) -> Option<&'a TySeq> {
         self.get_frame().vararg.as_ref()
     }
@@ -875,6 +1556,10 @@ impl<'ctx, R: Report> Env<'ctx, R> {
                 }
             }
 
+            Some(Tag::Custom(id)) => {
+                return self.context.custom_tag_assign(id, lhs, rhs);
+            }
+
             _ => {}
         }
 
@@ -888,6 +1573,7 @@ impl<'ctx, R: Report> Env<'ctx, R> {
                     .note_if(rhs, m::OtherTypeOrigin {})
                     .done()?;
             }
+            self.context.notify_assign(lhs, rhs, init)?;
         }
         Ok(())
     }
@@ -1071,6 +1757,7 @@ impl<'ctx, R: Report> Env<'ctx, R> {
             self.error(nameref, m::CannotRedefineVar { name: name }).done()?;
             return Ok(None);
         }
+        self.check_implicit_global(&id)?;
 
         let slot = if let Some(initinfo) = initinfo {
             let specinfo = self.assign_from_spec(&initinfo, specinfo.as_ref())?;
@@ -1097,6 +1784,18 @@ impl<'ctx, R: Report> Env<'ctx, R> {
         }
     }
 
+    // in the strict globals mode, a new global variable can be only created
+    // at the top level of the start file unless declared beforehand
+    fn check_implicit_global(&self, id: &Spanned<Id>) -> Result<()> {
+        if let Id::Global(ref name) = id.base {
+            if !(self.start_file && self.is_top_level()) && id.span.is_source_dependent() &&
+               self.opts.borrow().strict_globals() {
+                self.error(id, m::ImplicitGlobal { name: name }).done()?;
+            }
+        }
+        Ok(())
+    }
+
     /// Adds a local variable with that has been already initialized with the type `info`.
     ///
     /// This is necessary for non-assignment statements that introduce a new variable
@@ -1143,6 +1842,8 @@ impl<'ctx, R: Report> Env<'ctx, R> {
             def.slot = NameSlot::Set(previnfo.clone());
             (previnfo, prevset, needslotassign)
         } else {
+            self.check_implicit_global(&id)?;
+
             let varname = id.name(self.context).clone().with_loc(nameref);
             let info = info.base.clone().set_display(DisplayName::Var(varname));
             self.context.ids.insert(id.base.clone(),
@@ -1187,6 +1888,9 @@ impl<'ctx, R: Report> Env<'ctx, R> {
         debug!("(force) adding a variable {} as {:?}", id.display(&self.context), info);
 
         self.assume_special(&info)?;
+        if let NameRef::Global(ref name) = name.base {
+            self.context.assumed_globals.insert(name.clone());
+        }
 
         let varname = id.name(self.context).clone().with_loc(name);
         let info = info.base.set_display(DisplayName::Var(varname));
@@ -1199,6 +1903,39 @@ impl<'ctx, R: Report> Env<'ctx, R> {
         Ok(info)
     }
 
+    /// Saves the current definition of given variable, so that later changes
+    /// (most importantly by `assume_var`) can be undone with `restore_var`.
+    pub fn save_var(&self, name: &NameRef) -> SavedVar {
+        let id = Id::from(self.map_index, name.clone());
+        let def = self.context.ids.get(&id).cloned();
+        let assumed_global = match *name {
+            NameRef::Global(ref name) => self.context.assumed_globals.contains(name),
+            NameRef::Local(_) => false,
+        };
+        SavedVar { id: id, def: def, assumed_global: assumed_global }
+    }
+
+    /// Restores the definition of the variable saved by `save_var`.
+    ///
+    /// Only the binding is restored; any change to the type itself
+    /// (e.g. new fields in a class) is retained.
+    pub fn restore_var(&mut self, saved: SavedVar) {
+        debug!("restoring a variable {} to {:?}", saved.id.display(&self.context), saved.def);
+
+        if let Id::Global(ref name) = saved.id {
+            if saved.assumed_global {
+                self.context.assumed_globals.insert(name.clone());
+            } else {
+                self.context.assumed_globals.remove(name);
+            }
+        }
+        if let Some(def) = saved.def {
+            self.context.ids.insert(saved.id, def);
+        } else {
+            self.context.ids.remove(&saved.id);
+        }
+    }
+
     pub fn get_tvar_bounds(&self, tvar: TVar) -> (Flags /*lb*/, Flags /*ub*/) {
         self.context.get_tvar_bounds(tvar)
     }
@@ -1260,6 +1997,18 @@ impl<'ctx, R: Report> Env<'ctx, R> {
         Ok(())
     }
 
+    pub fn define_interface(&mut self, name: &Spanned<Name>, iface: Interface) -> Result<()> {
+        if let Some(def) = self.context.interfaces.get(&name.base) {
+            self.error(name, m::CannotRedefineInterface { name: &name.base })
+                .note(def.span, m::AlreadyDefinedInterface {})
+                .done()?;
+            return Ok(());
+        }
+
+        self.context.interfaces.insert(name.base.clone(), iface);
+        Ok(())
+    }
+
     pub fn define_and_export_type(&mut self, name: &Spanned<Name>, ty: Ty) -> Result<()> {
         if let Some(def) = self.get_named_type(name) {
             self.error(name, m::CannotRedefineAndReexportType { name: &name.base })
@@ -1394,7 +2143,11 @@ impl<'ctx, R: Report> Report for Env<'ctx, R> {
     }
 
     fn add_span(&self, k: Kind, s: Span, m: &Localize) -> Result<()> {
-        self.context.report.add_span(k, s, m)
+        let k = match self.override_kind(k, m) {
+            Some(k) => k,
+            None => { self.context.suppress_following(); return Ok(()); }
+        };
+        self.context.add_span(k, s, m)
     }
 }
 
@@ -1437,3 +2190,38 @@ fn test_context_is_send_and_sync() {
     _assert_sync(Context::new(NoReport));
 }
 
+
+#[test]
+fn test_cancellation() {
+    use kailua_env::{Source, SourceFile};
+    use kailua_diag::{CollectedReport, Interrupted};
+    use kailua_syntax::parse_chunk_with_cancellation;
+    use kailua_syntax::lang::Language;
+    use options::Options;
+    use check_from_chunk;
+
+    struct NoOptions;
+    impl Options for NoOptions {}
+
+    let mut source = Source::new();
+    let code = b"local x = 1 + 'a'\nlocal y = x".to_vec();
+    let span = source.add(SourceFile::from_u8("cancel.lua".to_owned(), code));
+    let token = CancellationToken::new();
+    let report = CollectedReport::new(Locale::dummy());
+    let chunk = parse_chunk_with_cancellation(&source, span, Language::default(), &report, &token);
+    let chunk = chunk.expect("parsing failed");
+
+    // the checker stops right before the first statement without any report
+    let mut context = Context::new(report);
+    context.set_cancellation_token(token.clone());
+    token.cancel();
+    let opts = Rc::new(RefCell::new(NoOptions));
+    let ret = check_from_chunk(&mut context, chunk, opts);
+    assert_eq!(token.interrupted(ret), Err(Interrupted::Cancelled));
+    assert!(context.report.into_reports().is_empty());
+
+    // the parser also stops when cancelled
+    let report = CollectedReport::new(Locale::dummy());
+    let chunk = parse_chunk_with_cancellation(&source, span, Language::default(), &report, &token);
+    assert_eq!(chunk.err(), Some(Interrupted::Cancelled));
+}
@@ -0,0 +1,155 @@
+//! Collection of the `--# ignore` directives.
+//!
+//! This is a purely syntactic pass run before checking each chunk.
+//! `--# ignore` affects the next statement in the same block (consecutive directives
+//! affect the same statement), and `--# ignore function` affects the innermost function
+//! enclosing the directive, or the whole chunk at the top level.
+
+use kailua_env::{Span, Spanned};
+use kailua_syntax::Name;
+use kailua_syntax::ast::{Var, Exp, Ex, Args, Table, St, Stmt, Block};
+
+/// A single `--# ignore` directive.
+#[derive(Clone, Debug)]
+pub struct Suppression {
+    /// The span of the directive itself.
+    pub directive: Span,
+
+    /// The span where reports are suppressed; dummy if there is no next statement.
+    pub range: Span,
+
+    /// Lint codes to be suppressed. Every report is suppressed if empty.
+    pub codes: Vec<Spanned<Name>>,
+}
+
+/// Returns a list of `--# ignore` directives in the chunk, sorted by the span.
+pub fn find_suppressions(chunk: &Spanned<Block>) -> Vec<Suppression> {
+    let mut collector = Collector { func_spans: vec![chunk.span], suppressions: Vec::new() };
+    collector.visit_block(chunk);
+    collector.suppressions.sort_by_key(|s| s.directive.begin());
+    collector.suppressions
+}
+
+struct Collector {
+    // spans of enclosing functions, innermost last
+    func_spans: Vec<Span>,
+    suppressions: Vec<Suppression>,
+}
+
+impl Collector {
+    fn visit_func_body(&mut self, span: Span, block: &Spanned<Block>) {
+        self.func_spans.push(span);
+        self.visit_block(block);
+        self.func_spans.pop();
+    }
+
+    fn visit_block(&mut self, block: &Spanned<Block>) {
+        for (i, stmt) in block.base.iter().enumerate() {
+            if let St::KailuaIgnore(func, ref codes) = *stmt.base {
+                let range = if func {
+                    *self.func_spans.last().unwrap()
+                } else {
+                    block.base[i+1..].iter()
+                        .find(|next| match *next.base { St::KailuaIgnore(..) => false, _ => true })
+                        .map_or(Span::dummy(), |next| next.span)
+                };
+                self.suppressions.push(Suppression {
+                    directive: stmt.span,
+                    range: range,
+                    codes: codes.clone(),
+                });
+            } else {
+                self.visit_stmt(stmt);
+            }
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Spanned<Stmt>) {
+        match *stmt.base {
+            St::Oops | St::Break | St::KailuaOpen(..) | St::KailuaType(..) |
+            St::KailuaClassSystem(..) | St::KailuaInterface(..) |
+            St::KailuaAssumeField(..) | St::KailuaAssumeMethod(..) |
+            St::KailuaAssertImplements(..) | St::KailuaAssume(..) |
+            St::KailuaAssumeClass(..) | St::KailuaIgnore(..) => {}
+
+            St::Void(ref exp) => self.visit_exp(exp),
+            St::Assign(ref vars, ref exps) => {
+                for var in &vars.base {
+                    match var.base.base {
+                        Var::Name(_) => {}
+                        Var::Index(ref e1, ref e2) => { self.visit_exp(e1); self.visit_exp(e2); }
+                        Var::IndexName(ref e, _) => self.visit_exp(e),
+                    }
+                }
+                if let Some(ref exps) = *exps {
+                    for exp in &exps.base { self.visit_exp(exp); }
+                }
+            }
+            St::Do(ref block) => self.visit_block(block),
+            St::While(ref cond, ref block) | St::Repeat(ref block, ref cond) => {
+                self.visit_exp(cond);
+                self.visit_block(block);
+            }
+            St::If(ref conds, ref lastblock) => {
+                for cond in conds {
+                    let (ref exp, ref block) = cond.base;
+                    self.visit_exp(exp);
+                    self.visit_block(block);
+                }
+                if let Some(ref block) = *lastblock {
+                    self.visit_block(block);
+                }
+            }
+            St::For(_, ref start, ref end, ref step, _, ref block) => {
+                self.visit_exp(start);
+                self.visit_exp(end);
+                if let Some(ref step) = *step { self.visit_exp(step); }
+                self.visit_block(block);
+            }
+            St::ForIn(_, ref exps, _, ref block) => {
+                for exp in &exps.base { self.visit_exp(exp); }
+                self.visit_block(block);
+            }
+            St::FuncDecl(_, _, _, ref block, _) |
+            St::MethodDecl(_, _, _, _, ref block) => self.visit_func_body(stmt.span, block),
+            St::Local(_, ref exps, _) | St::Return(ref exps) => {
+                for exp in &exps.base { self.visit_exp(exp); }
+            }
+        }
+    }
+
+    fn visit_args(&mut self, args: &Args) {
+        match *args {
+            Args::List(ref exps) => for exp in exps { self.visit_exp(exp); },
+            Args::Str(_) => {}
+            Args::Table(ref table) => self.visit_table(table),
+        }
+    }
+
+    fn visit_table(&mut self, table: &Table) {
+        for &(ref key, ref value) in &table.items {
+            if let Some(ref key) = *key { self.visit_exp(key); }
+            self.visit_exp(value);
+        }
+    }
+
+    fn visit_exp(&mut self, exp: &Spanned<Exp>) {
+        match *exp.base {
+            Ex::Oops | Ex::Nil | Ex::False | Ex::True | Ex::Num(_) | Ex::Int(_) |
+            Ex::Str(_) | Ex::Varargs | Ex::Var(_) => {}
+
+            Ex::Func(_, _, ref block) => self.visit_func_body(exp.span, block),
+            Ex::Table(ref table) => self.visit_table(table),
+            Ex::Exp(ref e) | Ex::Un(_, ref e) | Ex::IndexName(ref e, _) => self.visit_exp(e),
+            Ex::FuncCall(ref e, ref args) => { self.visit_exp(e); self.visit_args(args); }
+            Ex::MethodCall(ref e, ref args) => {
+                self.visit_exp(&e.base.0);
+                self.visit_args(args);
+            }
+            Ex::Index(ref e1, ref e2) | Ex::Bin(ref e1, _, ref e2) => {
+                self.visit_exp(e1);
+                self.visit_exp(e2);
+            }
+        }
+    }
+}
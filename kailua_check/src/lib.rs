@@ -10,12 +10,16 @@
 //!   but the first `Env` should be given explicitly.
 //!
 //! * `kailua_check::options::Options` is a configurable portion of the type checker.
-//!   Currently it allows you to configure the `require` path and the actual loading process.
+//!   Currently it allows you to configure the `require` path, the actual loading process
+//!   and the strict nil-safety mode.
 //!
 //! * `kailua_check::Checker` is the actual checker.
 //!   Due to the internal architecture, it also holds some side information
 //!   depending on the input chunk (and cannot be put to `Env` due to the lifetime mismatch).
 //!
+//! Additional rules can be implemented by registering a `kailua_check::listener::CheckListener`
+//! to the `Context`, which gets notified while the checker visits the code.
+//!
 //! After the type checking, `Context` can be extracted into the `Output` for later analysis.
 
 #[macro_use] extern crate parse_generics_shim;
@@ -37,30 +41,74 @@ pub use check::Checker;
 
 mod message;
 pub mod options;
+pub mod lint;
 pub mod env;
+pub mod stats;
+pub mod custom_tag;
+pub mod listener;
 mod defs;
 mod class_system;
 mod check;
+mod unused;
+mod shadow;
+mod ignore;
+mod ops;
+
+/// Returns the names of built-in libraries that can be opened with `--# open`
+/// or `Preload::open`.
+pub fn available_libraries() -> &'static [&'static str] {
+    defs::LIBRARY_NAMES
+}
 
 /// Options to populate the execution environment before checking.
 ///
-/// They are essentially identical to a series of `--# open` statements and
-/// `require` function calls in the order of arguments.
+/// They are essentially identical to a series of `--# open` statements,
+/// `--# assume global` statements and `require` function calls in the order of arguments.
 #[derive(Clone, Debug)]
 pub struct Preload {
     /// A list of preloaded built-in libraries.
     pub open: Vec<Spanned<Vec<u8>>>,
 
+    /// A list of preloaded global variables and their types in the Kailua syntax.
+    /// A global without a type is declared to have a type `WHATEVER`.
+    pub globals: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+
+    /// A list of preloaded constants and their values.
+    /// Each constant is declared as a global variable of the `const` literal type,
+    /// so that conditions depending on it are known at the checking time.
+    pub constants: Vec<(Vec<u8>, Constant)>,
+
     /// A list of preloaded `require` targets.
     pub require: Vec<Spanned<Vec<u8>>>,
 }
 
 impl Default for Preload {
     fn default() -> Preload {
-        Preload { open: Vec::new(), require: Vec::new() }
+        Preload { open: Vec::new(), globals: Vec::new(), constants: Vec::new(),
+                  require: Vec::new() }
     }
 }
 
+/// A value of the constant given to `Preload::constants`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Constant {
+    Boolean(bool),
+    Integer(i32),
+    String(Vec<u8>),
+}
+
+/// A workspace-level invariant verified after checking.
+///
+/// Types are given in the Kailua syntax, and the actual type should be their subtype.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Assertion {
+    /// A global variable should be defined with given type.
+    Global(Vec<u8>, Vec<u8>),
+
+    /// A module (as given to `require`) should be loaded and export a field with given type.
+    Export(Vec<u8>, Vec<u8>, Vec<u8>),
+}
+
 /// An one-off function to check a chunk with given `Options`.
 pub fn check_from_chunk<R: Report>(
     context: &mut env::Context<R>,
@@ -68,6 +116,7 @@ pub fn check_from_chunk<R: Report>(
     opts: Rc<RefCell<options::Options>>
 ) -> kailua_diag::Result<()> {
     let mut env = env::Env::new(context, opts, chunk.map);
+    env.set_start_file();
     let mut checker = Checker::new(&mut env);
     checker.visit(&chunk.block)
 }
@@ -84,7 +133,12 @@ pub fn check_from_chunk_with_preloading<R: Report>(
         context.open_library(name.as_ref().map(|n| &n[..]), opts.clone())?;
     }
 
+    // preload `--# assume global`s into the context
+    context.assume_globals(&preload.globals, opts.clone())?;
+    context.assume_constants(&preload.constants, opts.clone())?;
+
     let mut env = env::Env::new(context, opts, chunk.map);
+    env.set_start_file();
     let mut checker = Checker::new(&mut env);
 
     // preload `require`s into the checker
@@ -95,3 +149,19 @@ pub fn check_from_chunk_with_preloading<R: Report>(
     checker.visit(&chunk.block)
 }
 
+/// Verifies workspace-level assertions after checking.
+///
+/// Returns localized messages for failed assertions, which are not associated to any file.
+pub fn verify_assertions<R: Report>(
+    context: &mut env::Context<R>,
+    assertions: &[Assertion],
+    opts: Rc<RefCell<options::Options>>
+) -> kailua_diag::Result<Vec<String>> {
+    let mut failures = Vec::new();
+    for assertion in assertions {
+        if let Some(msg) = context.verify_assertion(assertion, opts.clone())? {
+            failures.push(msg);
+        }
+    }
+    Ok(failures)
+}
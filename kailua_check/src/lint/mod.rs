@@ -0,0 +1,271 @@
+//! The list of lints, i.e. warnings that the checker may report for valid code.
+//!
+//! Unlike errors, lints indicate possible problems and can be toggled by users.
+//! `available_lints` lists every lint known to this version of the checker,
+//! so that editors and configuration generators need not hardcode them.
+//!
+//! Lints that are allowed by default are opt-in rules for stricter code bases,
+//! some of which are implemented in the `rules` module.
+
+use kailua_diag::Kind;
+
+pub mod rules;
+
+/// The severity of reports from a lint.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Severity {
+    /// The lint is disabled.
+    Allow,
+
+    /// The lint is reported as a warning.
+    Warn,
+
+    /// The lint is reported as an error.
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Severity::Allow => "allow",
+            Severity::Warn => "warn",
+            Severity::Error => "error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Severity> {
+        match name {
+            "allow" => Some(Severity::Allow),
+            "warn" => Some(Severity::Warn),
+            "error" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+
+    /// Returns the report kind for this severity, or `None` if disabled.
+    pub fn to_kind(&self) -> Option<Kind> {
+        match *self {
+            Severity::Allow => None,
+            Severity::Warn => Some(Kind::Warning),
+            Severity::Error => Some(Kind::Error),
+        }
+    }
+}
+
+/// The type of a lint option.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LintOptionType {
+    Boolean,
+    Integer,
+    String,
+
+    /// One of given strings.
+    Enum(&'static [&'static str]),
+}
+
+/// A configurable option of a lint, in addition to its severity.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LintOption {
+    pub name: &'static str,
+    pub type_: LintOptionType,
+
+    /// The default value in the JSON syntax.
+    pub default: &'static str,
+
+    pub description: &'static str,
+}
+
+/// The description of a lint.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Lint {
+    /// A unique identifier of the lint, in lowercase words separated by hyphens.
+    pub code: &'static str,
+
+    pub default_severity: Severity,
+
+    /// A single-sentence English description of what the lint reports.
+    pub description: &'static str,
+
+    /// The configuration schema of the lint; empty if only the severity can be configured.
+    pub options: &'static [LintOption],
+}
+
+const LINTS: &'static [Lint] = &[
+    Lint {
+        code: "dead-code",
+        default_severity: Severity::Warn,
+        description: "Reports statements that will never be executed.",
+        options: &[],
+    },
+    Lint {
+        code: "deprecated",
+        default_severity: Severity::Warn,
+        description: "Reports uses of functions, fields and modules \
+                      with the `[deprecated]` attribute.",
+        options: &[],
+    },
+    Lint {
+        code: "discarded-result",
+        default_severity: Severity::Warn,
+        description: "Reports calls to functions with the `[nodiscard]` attribute \
+                      whose return values are ignored.",
+        options: &[],
+    },
+    Lint {
+        code: "diverging-in-expr",
+        default_severity: Severity::Warn,
+        description: "Reports expressions of which some portion won't be evaluated \
+                      because a preceding sub-expression never returns.",
+        options: &[],
+    },
+    Lint {
+        code: "ignored-if-case",
+        default_severity: Severity::Warn,
+        description: "Reports `if` cases that are never executed \
+                      because a prior condition is always truthy.",
+        options: &[],
+    },
+    Lint {
+        code: "duplicate-attr-in-sig",
+        default_severity: Severity::Warn,
+        description: "Reports attributes added to a function specification \
+                      that already has an attribute.",
+        options: &[],
+    },
+    Lint {
+        code: "unresolved-module-name",
+        default_severity: Severity::Warn,
+        description: "Reports `require` calls with a module name that cannot be resolved \
+                      at the check time.",
+        options: &[],
+    },
+    Lint {
+        code: "native-module-without-stub",
+        default_severity: Severity::Warn,
+        description: "Reports `require` calls resolving to a native module \
+                      without a type stub.",
+        options: &[],
+    },
+    Lint {
+        code: "redefined-class-name",
+        default_severity: Severity::Warn,
+        description: "Reports a new name given to a class that has been already named.",
+        options: &[],
+    },
+    Lint {
+        code: "unused-variable",
+        default_severity: Severity::Warn,
+        description: "Reports local variables, local functions and loop variables \
+                      that are never read, unless their names start with `_`.",
+        options: &[],
+    },
+    Lint {
+        code: "unused-assignment",
+        default_severity: Severity::Warn,
+        description: "Reports assignments to local variables whose values are never read, \
+                      unless their names start with `_`.",
+        options: &[],
+    },
+    Lint {
+        code: "shadowing",
+        default_severity: Severity::Warn,
+        description: "Reports local variables shadowing other local or global variables \
+                      of the same name, unless their names start with `_` or \
+                      they are initialized from the shadowed variables.",
+        options: &[],
+    },
+    Lint {
+        code: "string-coercion",
+        default_severity: Severity::Warn,
+        description: "Reports string literals implicitly converted to numbers \
+                      by arithmetic or bitwise operators.",
+        options: &[],
+    },
+    Lint {
+        code: "deprecated-api",
+        default_severity: Severity::Allow,
+        description: "Reports uses of standard library functions that are deprecated \
+                      or removed in the Lua version being checked.",
+        options: &[],
+    },
+    Lint {
+        code: "mixed-type-equality",
+        default_severity: Severity::Allow,
+        description: "Reports `==` and `~=` comparing a boolean, a number or a string \
+                      with a value of another of these types.",
+        options: &[],
+    },
+    Lint {
+        code: "empty-block",
+        default_severity: Severity::Allow,
+        description: "Reports empty blocks in `do`, `if`, `while`, `repeat` and `for` statements.",
+        options: &[],
+    },
+    Lint {
+        code: "float-for-step",
+        default_severity: Severity::Allow,
+        description: "Reports numeric `for` loops whose step is not an integer.",
+        options: &[],
+    },
+    Lint {
+        code: "unused-ignore",
+        default_severity: Severity::Warn,
+        description: "Reports `--# ignore` directives that did not suppress any report.",
+        options: &[],
+    },
+    Lint {
+        code: "package-path-assign",
+        default_severity: Severity::Warn,
+        description: "Reports assignments to `package.path` or `package.cpath` \
+                      which the checker cannot follow.",
+        options: &[],
+    },
+];
+
+/// Returns all lints known to the checker, sorted by the code.
+pub fn available_lints() -> Vec<Lint> {
+    let mut lints = LINTS.to_vec();
+    lints.sort_by_key(|lint| lint.code);
+    lints
+}
+
+/// Returns a lint with given code if any.
+pub fn find_lint(code: &str) -> Option<&'static Lint> {
+    LINTS.iter().find(|lint| lint.code == code)
+}
+
+/// Returns true if given string looks like a diagnostic code (e.g. `KC0123`, `KT0042`).
+///
+/// Diagnostic codes are assigned to messages from the checker (`KC`) and types (`KT`),
+/// and can be used in place of lint codes for `--# ignore` and severity overrides.
+/// This does not check if the code is actually assigned.
+pub fn is_diagnostic_code(code: &str) -> bool {
+    let code = code.as_bytes();
+    code.len() == 6 && (code.starts_with(b"KC") || code.starts_with(b"KT")) &&
+        code[2..].iter().all(|&c| b'0' <= c && c <= b'9')
+}
+
+#[test]
+fn test_available_lints() {
+    let lints = available_lints();
+    assert!(!lints.is_empty());
+    for (prev, next) in lints.iter().zip(lints.iter().skip(1)) {
+        assert!(prev.code < next.code, "duplicate or unsorted lint codes");
+    }
+    for lint in &lints {
+        assert!(lint.code.bytes().all(|c| c == b'-' || (b'a' <= c && c <= b'z')));
+        assert!(lint.description.ends_with('.'));
+        assert_eq!(find_lint(lint.code), Some(lint));
+    }
+    assert_eq!(find_lint("no-such-lint"), None);
+}
+
+#[test]
+fn test_is_diagnostic_code() {
+    assert!(is_diagnostic_code("KC0001"));
+    assert!(is_diagnostic_code("KT0123"));
+    assert!(!is_diagnostic_code("KC123"));
+    assert!(!is_diagnostic_code("KX0001"));
+    assert!(!is_diagnostic_code("kc0001"));
+    assert!(!is_diagnostic_code("shadowing"));
+}
@@ -0,0 +1,239 @@
+//! Syntactic lint rules, which do not need the type information.
+//!
+//! These rules are run after checking each chunk, only when the corresponding lints are
+//! enabled (see `Options::lint_severity`). Rules depending on types are implemented
+//! in the checker itself.
+
+use kailua_env::{Span, Spanned};
+use kailua_syntax::lang::Lua;
+use kailua_syntax::ast::{NameRef, Var, Exp, Ex, Args, Table, St, Stmt, Block};
+
+/// A standard library function (or a library) deprecated in some Lua version.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct DeprecatedApi {
+    /// The global variable holding the function or the library.
+    pub global: &'static str,
+
+    /// The name of the function in the library, if any.
+    pub field: Option<&'static str>,
+
+    /// The first Lua version where the function has been deprecated or removed.
+    pub since: Lua,
+
+    /// A replacement in the Lua syntax, if any.
+    pub replacement: Option<&'static str>,
+}
+
+impl DeprecatedApi {
+    /// Returns a full name like `table.getn`.
+    pub fn name(&self) -> String {
+        match self.field {
+            Some(field) => format!("{}.{}", self.global, field),
+            None => self.global.to_owned(),
+        }
+    }
+}
+
+macro_rules! deprecated_apis {
+    ($($global:ident $(. $field:ident)* => $since:ident, $replacement:expr;)*) => (
+        &[$(DeprecatedApi {
+            global: stringify!($global),
+            field: deprecated_apis!(@field $($field)*),
+            since: Lua::$since,
+            replacement: $replacement,
+        },)*]
+    );
+    (@field) => (None);
+    (@field $field:ident) => (Some(stringify!($field)));
+}
+
+const DEPRECATED_APIS: &'static [DeprecatedApi] = deprecated_apis! {
+    table.getn => Lua51, Some("#t");
+    table.setn => Lua51, None;
+    table.foreach => Lua51, Some("for k, v in pairs(t) do ... end");
+    table.foreachi => Lua51, Some("for i, v in ipairs(t) do ... end");
+    string.gfind => Lua51, Some("string.gmatch");
+    math.mod => Lua51, Some("math.fmod");
+    unpack => Lua52, Some("table.unpack");
+    loadstring => Lua52, Some("load");
+    setfenv => Lua52, Some("_ENV");
+    getfenv => Lua52, Some("_ENV");
+    module => Lua52, None;
+    table.maxn => Lua52, None;
+    math.log10 => Lua52, Some("math.log(x, 10)");
+    math.pow => Lua53, Some("x ^ y");
+    math.ldexp => Lua53, Some("m * 2.0 ^ e");
+    math.frexp => Lua53, None;
+    math.cosh => Lua53, None;
+    math.sinh => Lua53, None;
+    math.tanh => Lua53, None;
+    bit32 => Lua53, None;
+};
+
+/// A violation of the syntactic lint rules.
+#[derive(Clone, Debug)]
+pub enum Violation {
+    /// A use of the deprecated API (the `deprecated-api` lint).
+    DeprecatedApi(&'static DeprecatedApi),
+
+    /// An empty block in a compound statement (the `empty-block` lint).
+    EmptyBlock,
+}
+
+/// Returns a list of violations of the syntactic lint rules, sorted by the span.
+///
+/// The Lua version determines which standard library functions are deprecated.
+pub fn find_violations(block: &Spanned<Block>, lua: Lua) -> Vec<(Span, Violation)> {
+    let mut collector = Collector { lua: lua, violations: Vec::new() };
+    collector.visit_block(block);
+    collector.violations.sort_by_key(|&(span, _)| span.begin());
+    collector.violations
+}
+
+struct Collector {
+    lua: Lua,
+    violations: Vec<(Span, Violation)>,
+}
+
+impl Collector {
+    // returns true if reported
+    fn check_api(&mut self, span: Span, global: &[u8], field: Option<&[u8]>) -> bool {
+        let lua = self.lua;
+        let found = DEPRECATED_APIS.iter().find(|api| {
+            api.since <= lua && api.global.as_bytes() == global &&
+                api.field.map(|f| f.as_bytes()) == field
+        });
+        if let Some(api) = found {
+            self.violations.push((span, Violation::DeprecatedApi(api)));
+            true
+        } else {
+            false
+        }
+    }
+
+    // `span` is used in place of the block span, which is empty for empty blocks
+    fn check_block(&mut self, span: Span, block: &Spanned<Block>) {
+        if block.base.is_empty() && span.is_source_dependent() {
+            self.violations.push((span, Violation::EmptyBlock));
+        }
+    }
+
+    fn visit_block(&mut self, block: &Spanned<Block>) {
+        for stmt in &block.base {
+            self.visit_stmt(stmt);
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Spanned<Stmt>) {
+        match *stmt.base {
+            St::Oops | St::Break | St::KailuaOpen(..) | St::KailuaType(..) |
+            St::KailuaClassSystem(..) | St::KailuaInterface(..) |
+            St::KailuaAssumeField(..) | St::KailuaAssumeMethod(..) |
+            St::KailuaAssertImplements(..) | St::KailuaAssume(..) |
+            St::KailuaAssumeClass(..) | St::KailuaIgnore(..) => {}
+
+            St::Void(ref exp) => self.visit_exp(exp),
+            St::Assign(ref vars, ref exps) => {
+                for var in &vars.base {
+                    match var.base.base {
+                        Var::Name(_) => {}
+                        Var::Index(ref e1, ref e2) => { self.visit_exp(e1); self.visit_exp(e2); }
+                        Var::IndexName(ref e, _) => self.visit_exp(e),
+                    }
+                }
+                if let Some(ref exps) = *exps {
+                    for exp in &exps.base { self.visit_exp(exp); }
+                }
+            }
+            St::Do(ref block) => {
+                self.check_block(stmt.span, block);
+                self.visit_block(block);
+            }
+            St::While(ref cond, ref block) | St::Repeat(ref block, ref cond) => {
+                self.visit_exp(cond);
+                self.check_block(stmt.span, block);
+                self.visit_block(block);
+            }
+            St::If(ref conds, ref lastblock) => {
+                for cond in conds {
+                    let (ref exp, ref block) = cond.base;
+                    self.visit_exp(exp);
+                    self.check_block(cond.span, block);
+                    self.visit_block(block);
+                }
+                if let Some(ref block) = *lastblock {
+                    // there is no span for `else`, but the block span still covers blanks
+                    self.check_block(block.span, block);
+                    self.visit_block(block);
+                }
+            }
+            St::For(_, ref start, ref end, ref step, _, ref block) => {
+                self.visit_exp(start);
+                self.visit_exp(end);
+                if let Some(ref step) = *step { self.visit_exp(step); }
+                self.check_block(stmt.span, block);
+                self.visit_block(block);
+            }
+            St::ForIn(_, ref exps, _, ref block) => {
+                for exp in &exps.base { self.visit_exp(exp); }
+                self.check_block(stmt.span, block);
+                self.visit_block(block);
+            }
+            St::FuncDecl(_, _, _, ref block, _) |
+            St::MethodDecl(_, _, _, _, ref block) => self.visit_block(block),
+            St::Local(_, ref exps, _) | St::Return(ref exps) => {
+                for exp in &exps.base { self.visit_exp(exp); }
+            }
+        }
+    }
+
+    fn visit_args(&mut self, args: &Args) {
+        match *args {
+            Args::List(ref exps) => for exp in exps { self.visit_exp(exp); },
+            Args::Str(_) => {}
+            Args::Table(ref table) => self.visit_table(table),
+        }
+    }
+
+    fn visit_table(&mut self, table: &Table) {
+        for &(ref key, ref value) in &table.items {
+            if let Some(ref key) = *key { self.visit_exp(key); }
+            self.visit_exp(value);
+        }
+    }
+
+    fn visit_exp(&mut self, exp: &Spanned<Exp>) {
+        match *exp.base {
+            Ex::Oops | Ex::Nil | Ex::False | Ex::True | Ex::Num(_) | Ex::Int(_) |
+            Ex::Str(_) | Ex::Varargs => {}
+
+            Ex::Var(ref name) => {
+                if let NameRef::Global(ref name) = name.base {
+                    self.check_api(exp.span, name, None);
+                }
+            }
+            Ex::IndexName(ref e, ref field) => {
+                if let Ex::Var(Spanned { base: NameRef::Global(ref name), .. }) = *e.base {
+                    // `bit32.band` is reported as a use of `bit32` instead
+                    if self.check_api(exp.span, name, Some(field)) {
+                        return;
+                    }
+                }
+                self.visit_exp(e);
+            }
+
+            Ex::Func(_, _, ref block) => self.visit_block(block),
+            Ex::Table(ref table) => self.visit_table(table),
+            Ex::Exp(ref e) | Ex::Un(_, ref e) => self.visit_exp(e),
+            Ex::FuncCall(ref e, ref args) => { self.visit_exp(e); self.visit_args(args); }
+            Ex::MethodCall(ref e, ref args) => {
+                self.visit_exp(&e.base.0);
+                self.visit_args(args);
+            }
+            Ex::Index(ref e1, ref e2) | Ex::Bin(ref e1, _, ref e2) => {
+                self.visit_exp(e1);
+                self.visit_exp(e2);
+            }
+        }
+    }
+}
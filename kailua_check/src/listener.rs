@@ -0,0 +1,56 @@
+//! Hooks for the embedder to observe the type checking.
+
+use kailua_env::{Span, Spanned};
+use kailua_diag::{self, Report};
+use kailua_syntax::ast::{Exp, Kind};
+use kailua_types::ty::{TypeContext, Ty, Slot, SpannedSlotSeq};
+
+/// Receives events from the checker, so that project-specific rules can be implemented
+/// without changing the checker.
+///
+/// Listeners are registered to the context with `Context::add_listener`
+/// and notified in the order of registration. They cannot alter the checking,
+/// but can report additional diagnostics. Every method does nothing by default.
+///
+/// As with `CustomTag` the listener is shared, so any state should be kept behind a lock.
+pub trait CheckListener: Send + Sync {
+    /// Invoked after an expression has been checked, with its resulting types.
+    ///
+    /// Sub-expressions are notified before the enclosing expression.
+    /// Most expressions result in a single value; function calls and `...` may result in
+    /// zero or more values.
+    fn on_exp(&self, _exp: &Spanned<Exp>, _types: &SpannedSlotSeq,
+              _ctx: &mut TypeContext, _report: &Report) -> kailua_diag::Result<()> {
+        Ok(())
+    }
+
+    /// Invoked when a function gets called, after the arguments have been checked
+    /// but before the call itself is checked.
+    ///
+    /// `func` is the resolved type of the callee, which is the `__call` metamethod
+    /// for a callable table. For method calls the receiver is included as the first argument,
+    /// and a callable table is included before that. The return types are given to `on_exp`.
+    fn on_call(&self, _func: &Spanned<Ty>, _argtys: &SpannedSlotSeq, _outerspan: Span,
+               _ctx: &mut TypeContext, _report: &Report) -> kailua_diag::Result<()> {
+        Ok(())
+    }
+
+    /// Invoked when a value gets assigned to a variable or a field, after the type checking.
+    ///
+    /// `init` is true for the initialization of local variables (which may be separated from
+    /// the declaration) and false for the reassignment. The assignment may have failed,
+    /// in which case the error has been already reported.
+    fn on_assign(&self, _lhs: &Spanned<Slot>, _rhs: &Spanned<Slot>, _init: bool,
+                 _ctx: &mut TypeContext, _report: &Report) -> kailua_diag::Result<()> {
+        Ok(())
+    }
+
+    /// Invoked when a type in the Kailua syntax gets resolved,
+    /// e.g. from a type specification (`--: T`) or a type alias (`--# type A = T`).
+    ///
+    /// Only the outermost type is notified; nested types can be found from `kind`.
+    fn on_type(&self, _kind: &Spanned<Kind>, _ty: &Ty,
+               _ctx: &mut TypeContext, _report: &Report) -> kailua_diag::Result<()> {
+        Ok(())
+    }
+}
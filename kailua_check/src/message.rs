@@ -6,500 +6,601 @@ pub type Ty<'a> = Displayed<'a, ty::Ty, &'a TypeContext>;
 pub type SpannedTySeq<'a> = Displayed<'a, ty::SpannedTySeq, &'a TypeContext>;
 pub type Slot<'a> = Displayed<'a, ty::Slot, &'a TypeContext>;
 
-define_msg! { pub NoVar<'a> { name: &'a Name }:
+// every message has a stable diagnostic code (`KC0123`) which users can refer to;
+// codes are never reused or renumbered, so a new message should get the next unused code
+
+define_msg! { pub NoVar<'a> { name: &'a Name } = "KC0001":
     "ko" => "전역 또는 지역 변수 {name}가(이) 선언되지 않았습니다",
     _    => "Global or local variable {name} is not defined",
 }
 
-define_msg! { pub NoVarargs:
+define_msg! { pub NoVarargs = "KC0002":
     "ko" => "맨 안쪽 함수에 가변 인자가 존재하지 않습니다",
     _    => "Variadic arguments do not exist in the innermost function",
 }
 
-define_msg! { pub NoType<'a> { name: &'a Name }:
+define_msg! { pub NoType<'a> { name: &'a Name } = "KC0003":
     "ko" => "{name} 타입이 선언되지 않았습니다",
     _    => "Type {name} is not defined",
 }
 
-define_msg! { pub OtherTypeOrigin:
+define_msg! { pub OtherTypeOrigin = "KC0004":
     "ko" => "다른 타입은 여기에서 만들어졌습니다",
     _    => "The other type originates here",
 }
 
-define_msg! { pub CannotRedefineVar<'a> { name: &'a Name }:
+define_msg! { pub CannotRedefineVar<'a> { name: &'a Name } = "KC0005":
     "ko" => "{name} 변수의 타입을 재지정할 수 없습니다",
     _    => "Cannot redefine the type of a variable {name}",
 }
 
-define_msg! { pub TypeSpecToIndex:
+define_msg! { pub TypeSpecToIndex = "KC0006":
     "ko" => "인덱싱 수식에 타입을 지정할 수 없습니다",
     _    => "Cannot specify the type of indexing expression",
 }
 
-define_msg! { pub UseOfUnassignedVar:
+define_msg! { pub UseOfUnassignedVar = "KC0007":
     "ko" => "초기화되지 않은 변수를 사용하려고 했습니다",
     _    => "The variable is not yet initialized",
 }
 
-define_msg! { pub UnassignedVarOrigin<'a> { var: Slot<'a> }:
+define_msg! { pub UnassignedVarOrigin<'a> { var: Slot<'a> } = "KC0008":
     "ko" => "변수가 `{var}` 타입이기 때문에 `nil`로 자동으로 초기화되지 않습니다",
     _    => "The variable was not implicitly initialized to `nil` as its type is `{var}`",
 }
 
 // can be used for exported types, so avoid using a "locally defined" qualification
-define_msg! { pub CannotRedefineLocalType<'a> { name: &'a Name }:
+define_msg! { pub CannotRedefineLocalType<'a> { name: &'a Name } = "KC0009":
     "ko" => "{name} 타입은 이미 선언되어 있습니다",
     _    => "A type {name} is already defined",
 }
 
-define_msg! { pub CannotRedefineGlobalType<'a> { name: &'a Name }:
+define_msg! { pub CannotRedefineGlobalType<'a> { name: &'a Name } = "KC0010":
     "ko" => "{name} 타입은 이미 전역에 선언되어 있습니다",
     _    => "A type {name} is already defined globally",
 }
 
-define_msg! { pub CannotImportAlreadyDefinedType<'a> { name: &'a Name }:
+define_msg! { pub CannotImportAlreadyDefinedType<'a> { name: &'a Name } = "KC0011":
     "ko" => "{name} 타입을 들여 오려 했으나 이미 선언되어 있습니다",
     _    => "A type {name} to be imported is already defined",
 }
 
-define_msg! { pub CannotReexportType<'a> { name: &'a Name }:
+define_msg! { pub CannotReexportType<'a> { name: &'a Name } = "KC0012":
     "ko" => "모듈에서 {name} 타입을 다시 내보낼 수 없습니다",
     _    => "A type {name} cannot be exported again",
 }
 
-define_msg! { pub CannotRedefineLocalTypeAsGlobal<'a> { name: &'a Name }:
+define_msg! { pub CannotRedefineLocalTypeAsGlobal<'a> { name: &'a Name } = "KC0013":
     "ko" => "지역적으로 선언된 {name} 타입은 전역에서 자기 자신으로만 다시 선언할 수 있습니다",
     _    => "A locally defined type {name} can only be redefined as itself in the global scope",
 }
 
-define_msg! { pub CannotRedefineAndReexportType<'a> { name: &'a Name }:
+define_msg! { pub CannotRedefineAndReexportType<'a> { name: &'a Name } = "KC0014":
     "ko" => "모듈에서 내보내지 않은 {name} 타입은 자기 자신으로만 선언해서 내보낼 수 있습니다",
     _    => "A non-exported type {name} can only be redefined and exported as itself",
 }
 
-define_msg! { pub CannotRedefineTypeAsClass<'a> { name: &'a Name }:
+define_msg! { pub CannotRedefineTypeAsClass<'a> { name: &'a Name } = "KC0015":
     "ko" => "{name} 타입은 이미 선언되어 있습니다",
     _    => "A type {name} is already defined",
 }
 
-define_msg! { pub AlreadyDefinedType:
+define_msg! { pub AlreadyDefinedType = "KC0016":
     "ko" => "이전 타입 선언은 여기에 있습니다",
     _    => "The type was originally defined here",
 }
 
-define_msg! { pub WrongUnaryOperand<'a> { op: &'static str, ty: Slot<'a> }:
+define_msg! { pub WrongUnaryOperand<'a> { op: &'static str, ty: Slot<'a> } = "KC0017":
     "ko" => "{op} 연산자를 `{ty}`에 적용할 수 없습니다",
     _    => "Cannot apply {op} operator to `{ty}`",
 }
 
-define_msg! { pub WrongBinaryOperands<'a> { op: &'static str, lhs: Slot<'a>, rhs: Slot<'a> }:
+define_msg! { pub StringCoercedToNumber<'a> { op: &'a str, value: &'a Str } = "KC0018":
+    "ko" => "문자열 {value}이(가) `{op}` 연산자에 의해 암묵적으로 숫자로 변환됩니다",
+    _    => "The string {value} is implicitly converted to a number by the `{op}` operator",
+}
+
+define_msg! { pub WrongBinaryOperands<'a> { op: &'static str, lhs: Slot<'a>,
+                                            rhs: Slot<'a> } = "KC0019":
     "ko" => "{op} 연산자를 `{lhs}`와(과) `{rhs}`에 적용할 수 없습니다",
     _    => "Cannot apply {op} operator to `{lhs}` and `{rhs}`",
 }
 
-define_msg! { pub OperandIsBothNumOrStr<'a> { op: &'static str, operand: Slot<'a> }:
+define_msg! { pub OperandIsBothNumOrStr<'a> { op: &'static str, operand: Slot<'a> } = "KC0020":
     "ko" => "{op}의 피연산자 `{operand}`가(이) 문자열인지 숫자인지가 불분명합니다",
     _    => "Operand `{operand}` to {op} operator should be \
              either numbers or strings but not both",
 }
 
-define_msg! { pub OperandsAreNotBothNumOrStr<'a> { op: &'static str, lhs: Slot<'a>, rhs: Slot<'a> }:
+define_msg! { pub OperandsAreNotBothNumOrStr<'a> { op: &'static str, lhs: Slot<'a>,
+                                                   rhs: Slot<'a> } = "KC0021":
     "ko" => "{op}의 피연산자 `{lhs}`와(과) `{rhs}`가(이) \
              둘 다 문자열이거나 둘 다 숫자여야 하는데 아닙니다",
     _    => "Operands `{lhs}` and `{rhs}` to {op} operator \
              should be both numbers or both strings",
 }
 
-define_msg! { pub CannotDeduceBothNumOrStr<'a> { op: &'static str, lhs: Slot<'a>, rhs: Slot<'a> }:
+define_msg! { pub CannotDeduceBothNumOrStr<'a> { op: &'static str, lhs: Slot<'a>,
+                                                 rhs: Slot<'a> } = "KC0022":
     "ko" => "{op}의 피연산자 `{lhs}`와(과) `{rhs}`가(이) \
              둘 다 문자열이거나 숫자인지 알 수 없습니다",
     _    => "Cannot deduce if operands `{lhs}` and `{rhs}` \
              to {op} operator are either numbers or strings",
 }
 
-define_msg! { pub CallToNonFunc<'a> { func: Ty<'a> }:
+define_msg! { pub CallToNonFunc<'a> { func: Ty<'a> } = "KC0023":
     "ko" => "함수가 아닌 타입 `{func}`을(를) 호출하려고 했습니다",
     _    => "Tried to call a non-function `{func}`",
 }
 
-define_msg! { pub CallToInexactType<'a> { func: Ty<'a> }:
+define_msg! { pub CallToInexactType<'a> { func: Ty<'a> } = "KC0024":
     "ko" => "`{func}` 타입은 호출 가능하지만 아직 덜 추론되었습니다",
     _    => "The type `{func}` is callable but not known enough to call",
 }
 
-define_msg! { pub CallToWrongType<'a> { func: Ty<'a> }:
+define_msg! { pub CallToWrongType<'a> { func: Ty<'a> } = "KC0025":
     "ko" => "`{func}` 타입을 호출할 수 없습니다",
     _    => "The type `{func}` cannot be called",
 }
 
-define_msg! { pub CallToAnyFunc<'a> { func: Ty<'a> }:
+define_msg! { pub CallToAnyFunc<'a> { func: Ty<'a> } = "KC0026":
     "ko" => "타입이 `{func}`(이)라고만 알려져 있어서 호출할 수 없습니다. \
              타입을 더 구체적으로 명시하거나, 여의치 않으면 `--# assume`을 사용하십시오",
     _    => "Cannot call `{func}` without further type information; \
              specify more detailed type, or use `--# assume` as a last resort",
 }
 
-define_msg! { pub TableLitWithInvalidRecKey<'a> { key: Ty<'a> }:
+define_msg! { pub TableLitWithInvalidRecKey<'a> { key: Ty<'a> } = "KC0027":
     "ko" => "레코드 타입을 가지는 테이블 생성자에서 `{key}` 타입을 키로 쓸 수 없습니다",
     _    => "The type `{key}` cannot be used as a key in the table constructor for records",
 }
 
-define_msg! { pub TableLitWithInvalidArrayKey<'a> { key: Ty<'a> }:
+define_msg! { pub TableLitWithInvalidArrayKey<'a> { key: Ty<'a> } = "KC0028":
     "ko" => "배열 타입을 가지는 테이블 생성자에서 `{key}` 타입을 키로 쓸 수 없습니다",
     _    => "The type `{key}` cannot be used as a key in the table constructor for arrays",
 }
 
-define_msg! { pub TableLitWithInvalidArrayValue<'a> { given: Slot<'a>, value: Slot<'a> }:
+define_msg! { pub TableLitWithInvalidArrayValue<'a> { given: Slot<'a>, value: Slot<'a> } = "KC0029":
     "ko" => "`vector<{value}>` 타입을 가지는 테이블 생성자에서 \
              `{given}` 타입을 값으로 쓸 수 없습니다",
     _    => "The type `{given}` cannot be used as a value \
              in the table constructor for the type `vector<{value}>`",
 }
 
-define_msg! { pub TableLitWithInvalidMapKey<'a> { given: Ty<'a>, key: Ty<'a>, value: Slot<'a> }:
+define_msg! { pub TableLitWithInvalidMapKey<'a> { given: Ty<'a>, key: Ty<'a>,
+                                                  value: Slot<'a> } = "KC0030":
     "ko" => "`map<{key}, {value}>` 타입을 가지는 테이블 생성자에서 \
              `{given}` 타입을 키로 쓸 수 없습니다",
     _    => "The type `{given}` cannot be used as a key \
              in the table constructor for the type `map<{key}, {value}>`",
 }
 
-define_msg! { pub TableLitWithInvalidMapValue<'a> { given: Slot<'a>, key: Ty<'a>, value: Slot<'a> }:
+define_msg! { pub TableLitWithInvalidMapValue<'a> { given: Slot<'a>, key: Ty<'a>,
+                                                    value: Slot<'a> } = "KC0031":
     "ko" => "`map<{key}, {value}>` 타입을 가지는 테이블 생성자에서 \
              `{given}` 타입을 값으로 쓸 수 없습니다",
     _    => "The type `{given}` cannot be used as a value \
              in the table constructor for the type `map<{key}, {value}>`",
 }
 
-define_msg! { pub TableLitWithMissingArrayKey:
+define_msg! { pub TableLitWithMissingArrayKey = "KC0032":
     "ko" => "배열 타입을 가지는 테이블 생성자에서 빠진 키가 있습니다",
     _    => "Keys in the table constructor for arrays have a missing key",
 }
 
-define_msg! { pub TableLitWithNonOneMinArrayKey:
+define_msg! { pub TableLitWithNonOneMinArrayKey = "KC0033":
     "ko" => "배열 타입을 가지는 테이블 생성자에서 가장 작은 키가 1이 아닙니다",
     _    => "The minimum key in the table constructor for arrays is not 1",
 }
 
-define_msg! { pub TableLitWithUnboundSeq:
+define_msg! { pub TableLitWithUnboundSeq = "KC0034":
     "ko" => "레코드 타입을 가지는 테이블 생성자에서 \
              반환값 갯수가 정해지지 않은 수식을 마지막 수식으로 쓸 수 없습니다",
     _    => "This expression has an unknown number of return values, \
              so cannot be used as the last value in the table constructor for records",
 }
 
-define_msg! { pub TableLitWithDuplicateKey<'a> { key: &'a Key }:
+define_msg! { pub TableLitWithDuplicateKey<'a> { key: &'a Key } = "KC0035":
     "ko" => "테이블 생성자에서 `{key}` 키가 중복되었습니다",
     _    => "The key `{key}` is duplicated in the table constructor",
 }
 
-define_msg! { pub PreviousKeyInTableLit:
+define_msg! { pub PreviousKeyInTableLit = "KC0036":
     "ko" => "같은 키가 여기에서 이미 할당되었습니다",
     _    => "The key was previously assigned here",
 }
 
-define_msg! { pub TableLitIsImplicitlyRec:
+define_msg! { pub TableLitIsImplicitlyRec = "KC0037":
     "ko" => "이 테이블의 타입을 알 수 없어서 레코드로 간주했습니다. 타입을 명시해 주십시오",
     _    => "The type of this table was unknown so is assumed to be a record; \
              please specify its type"
 }
 
-define_msg! { pub IndexToNonTable<'a> { tab: Slot<'a> }:
+define_msg! { pub TableLitIsImplicitlyMap = "KC0038":
+    "ko" => "이 테이블의 타입을 알 수 없어서 첫 키로부터 맵으로 간주했습니다. \
+             타입을 명시해 주십시오",
+    _    => "The type of this table was unknown so is assumed to be a map from its first key; \
+             please specify its type"
+}
+
+define_msg! { pub IndexToNonTable<'a> { tab: Slot<'a> } = "KC0039":
     "ko" => "테이블이 아닌 타입 `{tab}`을(를) 인덱싱하려고 했습니다",
     _    => "Tried to index a non-table type `{tab}`",
 }
 
-define_msg! { pub IndexToInexactType<'a> { tab: Slot<'a> }:
+define_msg! { pub IndexToInexactType<'a> { tab: Slot<'a> } = "KC0040":
     "ko" => "`{tab}` 타입은 테이블이긴 하지만 아직 덜 추론되었습니다",
     _    => "The type `{tab}` is tabular but not known enough to index",
 }
 
-define_msg! { pub IndexToUnknownClass<'a> { cls: Slot<'a> }:
+define_msg! { pub IndexToUnknownClass<'a> { cls: Slot<'a> } = "KC0041":
     "ko" => "`{cls}` 타입이 정확히 하나의 클래스로 추론되지 않아 인덱싱할 수 없습니다",
     _    => "Cannot index `{cls}` that cannot be inferred to a single class",
 }
 
-define_msg! { pub IndexToRecWithUnknownStr<'a> { tab: Slot<'a>, key: Ty<'a> }:
+define_msg! { pub UpdateToDiscriminatedUnion<'a> { tab: Slot<'a> } = "KC0042":
+    "ko" => "`{tab}` 타입은 판별 가능한 유니언 타입이라 판별자를 비교하여 좁히기 전에는 \
+             필드를 갱신할 수 없습니다",
+    _    => "Cannot update a field of the discriminated union type `{tab}` \
+             before narrowing it by comparing its discriminant",
+}
+
+define_msg! { pub IndexToRecWithUnknownStr<'a> { tab: Slot<'a>, key: Ty<'a> } = "KC0043":
     "ko" => "`{tab}`에 `{key}`을(를) 키로 써서 인덱싱할 수 없습니다",
     _    => "Cannot index `{tab}` with `{key}`",
 }
 
-define_msg! { pub IndexToClassWithUnknown<'a> { cls: Slot<'a>, key: Ty<'a> }:
+define_msg! { pub IndexToClassWithUnknown<'a> { cls: Slot<'a>, key: Ty<'a> } = "KC0044":
     "ko" => "`{cls}`에 `{key}`을(를) 키로 써서 인덱싱할 수 없습니다",
     _    => "Cannot index `{cls}` with `{key}`",
 }
 
-define_msg! { pub IndexToArrayWithNonInt<'a> { tab: Slot<'a>, key: Ty<'a> }:
+define_msg! { pub IndexToArrayWithNonInt<'a> { tab: Slot<'a>, key: Ty<'a> } = "KC0045":
     "ko" => "`{tab}`에 정수가 아닌 `{key}`을(를) 키로 써서 인덱싱할 수 없습니다",
     _    => "Cannot index an array `{tab}` with a non-integral key `{key}`",
 }
 
-define_msg! { pub IndexToAnyTable<'a> { tab: Slot<'a> }:
+define_msg! { pub ReturnFromWeakTable = "KC0046":
+    "ko" => "약한 테이블의 값은 언제든 수집될 수 있으므로, 반환하기 전에 `nil`인지 검사해야 합니다",
+    _    => "A value from a weak table can be collected at any time \
+             and should be checked for `nil` before being returned",
+}
+
+define_msg! { pub IndexToAnyTable<'a> { tab: Slot<'a> } = "KC0047":
     "ko" => "타입이 `{tab}`(이)라고만 알려져 있어서 인덱싱할 수 없습니다. \
              타입을 더 구체적으로 명시하거나, 여의치 않으면 `--# assume`을 사용하십시오",
     _    => "Cannot index `{tab}` without further type information; \
              specify more detailed type, or use `--# assume` as a last resort",
 }
 
-define_msg! { pub CannotUpdate<'a> { tab: Slot<'a> }:
+define_msg! { pub CannotUpdate<'a> { tab: Slot<'a> } = "KC0048":
     "ko" => "변경할 수 없는 `{tab}` 타입을 인덱싱해서 갱신할 수 없습니다",
     _    => "Cannot update the immutable type `{tab}` by indexing",
 }
 
-define_msg! { pub CannotIndex<'a> { tab: Slot<'a>, key: Slot<'a> }:
+define_msg! { pub CannotIndex<'a> { tab: Slot<'a>, key: Slot<'a> } = "KC0049":
     "ko" => "`{tab}`에 `{key}`을(를) 키로 써서 인덱싱할 수 없습니다",
     _    => "Cannot index `{tab}` with `{key}`",
 }
 
 // a special case of CannotIndex when `key` is a string literal
-define_msg! { pub CannotIndexWithStr<'a> { tab: Slot<'a>, key: &'a Str }:
+define_msg! { pub CannotIndexWithStr<'a> { tab: Slot<'a>, key: &'a Str } = "KC0050":
     "ko" => "`{tab}`에 {key}이(가) 없습니다",
     _    => "Missing key {key} in `{tab}`",
 }
 
-define_msg! { pub CannotCreateIndex<'a> { tab: Slot<'a>, key: Slot<'a>, specrhs: Slot<'a> }:
+define_msg! { pub CannotCreateIndex<'a> { tab: Slot<'a>, key: Slot<'a>,
+                                          specrhs: Slot<'a> } = "KC0051":
     "ko" => "`{tab}`에 `{key}`을(를) 키로 써서 `{specrhs}` 타입의 필드를 새로 만들 수 없습니다",
     _    => "Cannot index `{tab}` with `{key}` and create a new field of the type `{specrhs}`",
 }
 
-define_msg! { pub CannotAssign<'a> { lhs: Slot<'a>, rhs: Slot<'a> }:
+define_msg! { pub CannotAddFieldToSealed<'a> { tab: Slot<'a>, key: Slot<'a> } = "KC0052":
+    "ko" => "테이블 생성자로 만든 `{tab}`에 `{key}` 키를 새로 추가할 수 없습니다",
+    _    => "Cannot add a new key `{key}` to `{tab}` constructed from a table constructor",
+}
+
+define_msg! { pub SealedRecordHint = "KC0053":
+    "ko" => "필드를 나중에 추가하려면 `{{..., ...}}`처럼 열린 레코드 타입을 명시해야 합니다",
+    _    => "Annotate an open record type like `{{..., ...}}` to add fields later",
+}
+
+define_msg! { pub CannotAssign<'a> { lhs: Slot<'a>, rhs: Slot<'a> } = "KC0054":
     "ko" => "`{lhs}` 타입에 `{rhs}` 타입을 대입할 수 없습니다",
     _    => "Cannot assign `{rhs}` into `{lhs}`",
 }
 
-define_msg! { pub NonNumericFor:
+define_msg! { pub NonNumericFor = "KC0055":
     "ko" => "`for` 문의 인자로 숫자가 아닌 타입(들)이 쓰였습니다",
     _    => "`for` statement was given non-numeric type(s)",
 }
 
-define_msg! { pub NonFuncIterator<'a> { iter: Ty<'a> }:
+define_msg! { pub NonFuncIterator<'a> { iter: Ty<'a> } = "KC0056":
     "ko" => "`for`-`in` 문에 주어진 반복자가 함수가 아닌 `{iter}` 타입을 반환했습니다",
     _    => "The iterator given to `for`-`in` statement returned a non-function type `{iter}`",
 }
 
-define_msg! { pub BadFuncIterator<'a> { iter: Ty<'a> }:
+define_msg! { pub BadFuncIterator<'a> { iter: Ty<'a> } = "KC0057":
     "ko" => "`for`-`in` 문에 주어진 반복자가 예상치 못한 `{iter}` 타입을 반환했습니다",
     _    => "The iterator given to `for`-`in` statement returned an unexpected type `{iter}`",
 }
 
-define_msg! { pub CannotExtendImplicitReturnType:
+define_msg! { pub CannotExtendImplicitReturnType = "KC0058":
     "ko" => "이 함수의 반환 타입을 암묵적으로 확장할 수 없습니다",
     _    => "Cannot extend the implicit return type of this function",
 }
 
-define_msg! { pub CannotReturn<'a> { returns: SpannedTySeq<'a>, ty: SpannedTySeq<'a> }:
+define_msg! { pub MissingReturn<'a> { returns: SpannedTySeq<'a> } = "KC0059":
+    "ko" => "함수가 값을 반환하지 않고 끝에 도달할 수 있지만 \
+             반환 타입 `{returns}`은(는) 생략할 수 없습니다",
+    _    => "The function can reach its end without returning a value, \
+             but its return type `{returns}` cannot be omitted",
+}
+
+define_msg! { pub CannotReturn<'a> { returns: SpannedTySeq<'a>, ty: SpannedTySeq<'a> } = "KC0060":
     "ko" => "지정된 `{returns}` 타입과 호환되지 않는 `{ty}`을(를) 반환하려 했습니다",
     _    => "Attempted to return a type `{ty}` which is incompatible to \
              given return type `{returns}`",
 }
 
-define_msg! { pub BadRecursiveCall:
+define_msg! { pub BadRecursiveCall = "KC0061":
     "ko" => "재귀호출되는 함수가 필요로 하는 타입과 실제 타입이 호환되지 않습니다",
     _    => "A required type and the actual type of the recursive function is not compatible",
 }
 
-define_msg! { pub BuiltinGivenLessArgs<'a> { name: &'a str, nargs: usize }:
+define_msg! { pub BuiltinGivenLessArgs<'a> { name: &'a str, nargs: usize } = "KC0062":
     "ko" => "`{name}` 내장 함수는 인자가 적어도 {nargs}개 필요합니다",
     _    => "`{name}` needs at least {nargs} argument(s)",
 }
 
-define_msg! { pub CannotOpenLibrary:
+define_msg! { pub CannotOpenLibrary = "KC0063":
     "ko" => "`--# open` 명령에 주어진 내장 라이브러리 이름을 찾을 수 없습니다",
     _    => "Cannot find the built-in library name given to `--# open` directive",
 }
 
-define_msg! { pub CannotResolveModName:
+define_msg! { pub CannotResolveModName = "KC0064":
     "ko" => "`require`에 주어진 모듈 이름을 찾을 수 없습니다",
     _    => "Cannot resolve the module name given to `require`",
 }
 
-define_msg! { pub RecursiveRequire:
-    "ko" => "모듈을 재귀적으로 `require`하려고 했습니다",
-    _    => "Recursive `require` was requested",
+define_msg! { pub NativeModuleWithoutStub<'a> { name: &'a Str, path: &'a str } = "KC0065":
+    "ko" => "`require`에 주어진 모듈 {name}은(는) 타입 스텁이 없는 네이티브 모듈({path})입니다; \
+             모듈의 내용을 설명하는 `.kailua` 파일을 만드십시오",
+    _    => "The module {name} given to `require` is a native module ({path}) \
+             without a type stub; create a `.kailua` file describing its contents",
 }
 
-define_msg! { pub PreviousRequire:
-    "ko" => "이전에 이미 여기에서 이 모듈을 `require` 했습니다",
-    _    => "The module was previously `require`d here",
+define_msg! { pub NativeModuleStubCandidates<'a> { paths: &'a str } = "KC0066":
+    "ko" => "타입 스텁은 다음 경로 중 하나에 둘 수 있습니다: {paths}",
+    _    => "A type stub can be placed at one of the following paths: {paths}",
 }
 
-define_msg! { pub ModCannotReturnFalse:
+define_msg! { pub NonLiteralRequire = "KC0067":
+    "ko" => "`require`에 주어진 모듈 이름이 문자열 리터럴이 아니므로 결과는 동적 타입이 됩니다",
+    _    => "The module name given to `require` is not a string literal, \
+             so the result is dynamic",
+}
+
+define_msg! { pub DynamicRequireIncompatible = "KC0068":
+    "ko" => "`require`가 반환할 수 있는 모듈들의 타입이 호환되지 않으므로 \
+             결과는 동적 타입이 됩니다",
+    _    => "The modules that `require` may return have incompatible types, \
+             so the result is dynamic",
+}
+
+define_msg! { pub RecursiveRequire<'a> { cycle: &'a str } = "KC0069":
+    "ko" => "모듈을 재귀적으로 `require`하려고 했습니다 ({cycle})",
+    _    => "Recursive `require` was requested ({cycle})",
+}
+
+define_msg! { pub RecursiveRequireToModuleTable<'a> { cycle: &'a str } = "KC0070":
+    "ko" => "모듈을 재귀적으로 `require`하려고 했으며 ({cycle}), \
+             `module(...)`로 만들어진 모듈 테이블은 아직 일부만 초기화되었습니다",
+    _    => "Recursive `require` was requested ({cycle}) and \
+             the module table made by `module(...)` is only partially initialized",
+}
+
+define_msg! { pub RequiredInCycle<'a> { name: &'a str } = "KC0071":
+    "ko" => "모듈 `{name}`을(를) 여기에서 `require` 했습니다",
+    _    => "The module `{name}` was `require`d here",
+}
+
+define_msg! { pub ModCannotReturnFalse = "KC0072":
     "ko" => "모듈에서 `false`를 반환하면 루아가 `require`를 재귀적으로 \
              요청하는 것을 막을 수 없으므로 사용하면 안 됩니다",
     _    => "Returning `false` from the module disables Lua's protection \
              against recursive `require` calls and is heavily discouraged",
 }
 
-define_msg! { pub ModCannotReturnInexactType<'a> { returns: Ty<'a> }:
+define_msg! { pub ModCannotReturnInexactType<'a> { returns: Ty<'a> } = "KC0073":
     "ko" => "모듈이 아직 덜 추론된 타입 `{returns}`을(를) 반환하려고 합니다",
     _    => "The module has returned a type `{returns}` that is not yet fully resolved",
 }
 
-define_msg! { pub UnknownLiteralTypeName:
+define_msg! { pub UnknownLiteralTypeName = "KC0074":
     "ko" => "리터럴이 `type`의 반환값으로 나올 수 있는 타입이 아닙니다",
     _    => "The literal cannot appear as a return type name for `type`",
 }
 
-define_msg! { pub DuplicateAttrInSig:
+define_msg! { pub DuplicateAttrInSig = "KC0075":
     "ko" => "이미 속성이 붙어 있는 함수 명세에 속성을 더 붙일 수 없습니다",
     _    => "Cannot add an attribute to a function specification with an existing attribute",
 }
 
-define_msg! { pub CannotAssignToPackagePath<'a> { name: &'a str }:
+define_msg! { pub CannotAssignToPackagePath<'a> { name: &'a str } = "KC0076":
     "ko" => "`{name}` 내장 변수에 값을 저장하다 문제가 생겨서 \
              `require` 경로를 찾는데 문제가 있을 수 있습니다",
     _    => "Cannot assign to the `{name}` built-in variable; \
              subsequent `require` may be unable to find the module path",
 }
 
-define_msg! { pub UnknownAssignToPackagePath<'a> { name: &'a str }:
+define_msg! { pub UnknownAssignToPackagePath<'a> { name: &'a str } = "KC0077":
     "ko" => "`{name}` 내장 변수에 저장되는 값을 알 수 없어서 \
              `require` 경로를 찾는데 문제가 있을 수 있습니다",
     _    => "Cannot infer the values assigned to the `{name}` built-in variable; \
              subsequent `require` may be unable to find the module path",
 }
 
-define_msg! { pub IndexedTypeIsBothTableOrStr<'a> { indexed: Slot<'a> }:
+define_msg! { pub IndexedTypeIsBothTableOrStr<'a> { indexed: Slot<'a> } = "KC0078":
     "ko" => "인덱싱이 되는 `{indexed}` 타입이 테이블인지 문자열인지가 불분명합니다",
     _    => "`{indexed}` type that is being indexed should be \
              either a table or a string but not both",
 }
 
-define_msg! { pub UndefinedStringMeta:
+define_msg! { pub UndefinedStringMeta = "KC0079":
     "ko" => "`string` 타입의 메타테이블이 아직 설정되지 않아서 문자열 메소드들을 쓸 수 없습니다",
     _    => "Cannot use string methods as a metatable for `string` type is not yet defined",
 }
 
-define_msg! { pub CannotRedefineStringMeta:
+define_msg! { pub CannotRedefineStringMeta = "KC0080":
     "ko" => "`string` 타입의 메타테이블은 한 번 이상 설정될 수 없으며 \
              기본적으로 `--# open` 명령을 통해서만 설정되어야 합니다",
     _    => "A metatable for `string` type cannot be defined more than once \
              and in general should only be set via `--# open` directive",
 }
 
-define_msg! { pub NonTableStringMeta:
+define_msg! { pub NonTableStringMeta = "KC0081":
     "ko" => "`string` 타입의 메타테이블이 설정되긴 했지만 테이블이 아닙니다",
     _    => "A metatable for `string` type has been defined but is not a table",
 }
 
-define_msg! { pub PreviousStringMeta:
+define_msg! { pub PreviousStringMeta = "KC0082":
     "ko" => "`string` 타입의 메타테이블이 이전에 여기서 설정되었습니다",
     _    => "A metatable for `string` type has been previously defined here",
 }
 
 #[cfg(feature = "warn_on_useless_conds")]
-define_msg! { pub IgnoredIfCase:
+define_msg! { pub IgnoredIfCase = "KC0083":
     "ko" => "`if` 문의 이 조건(들)은 실행되지 않습니다",
     _    => "These `if` case(s) are never executed",
 }
 
 #[cfg(feature = "warn_on_useless_conds")]
-define_msg! { pub IfCaseWithTruthyCond:
+define_msg! { pub IfCaseWithTruthyCond = "KC0084":
     "ko" => "이 조건이 항상 참인 값으로 평가됩니다",
     _    => "This condition always evaluates to a truthy value",
 }
 
 #[cfg(feature = "warn_on_useless_conds")]
-define_msg! { pub IfCaseWithFalsyCond:
+define_msg! { pub IfCaseWithFalsyCond = "KC0085":
     "ko" => "이 조건이 항상 거짓인 값으로 평가됩니다",
     _    => "This condition always evaluates to a falsy value",
 }
 
+define_msg! { pub ImpossibleEnumComparison<'a> { ty: Ty<'a>, lit: &'a Str,
+                                                 result: bool } = "KC0086":
+    "ko" => "`{ty}` 타입의 값은 {lit}일 수 없으므로 이 비교는 항상 `{result}`입니다",
+    _    => "This comparison is always `{result}` because `{ty}` cannot be {lit}",
+}
+
+define_msg! { pub NonExhaustiveEnumDispatch<'a> { ty: Ty<'a>, missing: &'a str } = "KC0087":
+    "ko" => "이 `if` 문은 `{ty}` 타입의 {missing}을(를) 처리하지 않습니다; \
+             빠진 경우나 `else` 블록을 추가하십시오",
+    _    => "This `if` statement does not handle {missing} of `{ty}`; \
+             add missing cases or an `else` block",
+}
+
 #[cfg(feature = "warn_on_dead_code")]
-define_msg! { pub DeadCode:
+define_msg! { pub DeadCode = "KC0088":
     "ko" => "이 코드는 실행되지 않을 것입니다",
     _    => "This code will never execute",
 }
 
-define_msg! { pub RedefinedClassName:
+define_msg! { pub RedefinedClassName = "KC0089":
     "ko" => "클래스 이름이 이미 설정되어 있어서 이 이름은 무시됩니다",
     _    => "A new name for the previously named class is ignored",
 }
 
-define_msg! { pub PreviousClassName:
+define_msg! { pub PreviousClassName = "KC0090":
     "ko" => "클래스 이름이 여기서 설정되었습니다",
     _    => "The class was previously named here",
 }
 
-define_msg! { pub CannotNameUnknownClass<'a> { cls: Slot<'a> }:
+define_msg! { pub CannotNameUnknownClass<'a> { cls: Slot<'a> } = "KC0091":
     "ko" => "`{cls}` 타입이 하나의 클래스로 정해지지 않았기 때문에 이름을 설정할 수 없습니다",
     _    => "The type `{cls}` cannot be resolved to a single class so cannot be named",
 }
 
-define_msg! { pub NoCtor:
+define_msg! { pub NoCtor = "KC0092":
     "ko" => "생성자(`init` 메소드)가 없이 `new` 메소드를 호출할 수 없습니다",
     _    => "The `new` method cannot be called with no constructor (`init` method) defined",
 }
 
-define_msg! { pub CannotAccessCtorThruInstance:
+define_msg! { pub CannotAccessCtorThruInstance = "KC0093":
     "ko" => "생성자(`init` 메소드)는 클래스 인스턴스를 통해 접근할 수 없습니다",
     _    => "The constructor (`init` method) should not be accessed through instances",
 }
 
-define_msg! { pub InexactInitMethod<'a> { init: Slot<'a> }:
+define_msg! { pub InexactInitMethod<'a> { init: Slot<'a> } = "KC0094":
     "ko" => "생성자(`init` 메소드)의 타입 `{init}`이(가) 덜 추론되었습니다",
     _    => "The type `{init}` of the constructor (`init` method) is not known enough to call",
 }
 
-define_msg! { pub NonFuncInitMethod<'a> { init: Slot<'a> }:
+define_msg! { pub NonFuncInitMethod<'a> { init: Slot<'a> } = "KC0095":
     "ko" => "생성자(`init` 메소드)의 타입 `{init}`이(가) 함수가 아닙니다",
     _    => "The type `{init}` of the constructor (`init` method) is not a function",
 }
 
-define_msg! { pub OverloadedFuncInitMethod<'a> { init: Slot<'a> }:
+define_msg! { pub OverloadedFuncInitMethod<'a> { init: Slot<'a> } = "KC0096":
     "ko" => "생성자(`init` 메소드)의 타입 `{init}`이(가) 오버로딩되어 있습니다",
     _    => "The type `{init}` of the constructor (`init` method) is overloaded",
 }
 
-define_msg! { pub BadSelfInInitMethod<'a> { init: Slot<'a> }:
+define_msg! { pub BadSelfInInitMethod<'a> { init: Slot<'a> } = "KC0097":
     "ko" => "생성자(`init` 메소드)의 타입 `{init}`이(가) \
              첫번째 인자로 올바른 타입을 가지지 않습니다",
     _    => "The type `{init}` of the constructor (`init` method) \
              doesn't have a correct type for the first argument",
 }
 
-define_msg! { pub ReservedNewMethod:
+define_msg! { pub ReservedNewMethod = "KC0098":
     "ko" => "`new` 메소드는 예약되어 있으며 선언될 수 없습니다",
     _    => "`new` method is reserved and cannot be defined",
 }
 
-define_msg! { pub NoInheritanceInDumbClassSystem:
+define_msg! { pub NoInheritanceInDumbClassSystem = "KC0099":
     "ko" => "클래스 시스템에 속하지 않은 클래스는 상속이 지원되지 않습니다",
     _    => "No inheritance is supported for classes without a class system",
 }
 
-define_msg! { pub CannotCreateFieldDefinedInInstance<'a> { key: &'a Key }:
+define_msg! { pub NoGenericClassInClassSystem = "KC0100":
+    "ko" => "이 클래스 시스템에서는 타입 인자를 받는 클래스가 지원되지 않습니다",
+    _    => "Classes with type parameters are not supported in this class system",
+}
+
+define_msg! { pub CannotCreateFieldDefinedInInstance<'a> { key: &'a Key } = "KC0101":
     "ko" => "인스턴스에 `{key}` 키가 이미 선언되어 있어 클래스에 같은 키를 선언할 수 없습니다",
     _    => "Cannot create a class field with the key `{key}` already defined in instances",
 }
 
-define_msg! { pub CannotCreateFieldDefinedInChildren<'a> { key: &'a Key }:
+define_msg! { pub CannotCreateFieldDefinedInChildren<'a> { key: &'a Key } = "KC0102":
     "ko" => "하위 클래스에 `{key}` 키가 이미 선언되어 있어 같은 키를 선언할 수 없습니다",
     _    => "Cannot create a field with the key `{key}` already defined in ancestor classes",
 }
 
-define_msg! { pub NoCheckRequiresTypedSelf:
+define_msg! { pub NoCheckRequiresTypedSelf = "KC0103":
     "ko" => "[NO_CHECK] 속성이 주어졌을 경우 `self` 인자의 타입이 명백해야 합니다. \
              대신 함수 선언 문법으로 타입을 직접 지정하십시오",
     _    => "[NO_CHECK] attribute requires that the type for `self` argument is clear; \
              directly specify the type for `self` with the function declaration instead",
 }
 
-define_msg! { pub NoCheckRequiresTypedArgs:
+define_msg! { pub NoCheckRequiresTypedArgs = "KC0104":
     "ko" => "[NO_CHECK] 속성이 주어졌을 경우 인자에 타입이 주어져야 합니다",
     _    => "[NO_CHECK] attribute requires the arguments to be typed",
 }
 
-define_msg! { pub NoCheckRequiresTypedVarargs:
+define_msg! { pub NoCheckRequiresTypedVarargs = "KC0105":
     "ko" => "[NO_CHECK] 속성이 주어졌을 경우 가변 인자에 타입이 주어져야 합니다",
     _    => "[NO_CHECK] attribute requires the variadic arguments to be typed",
 }
 
-define_msg! { pub NoCheckRequiresTypedReturns:
+define_msg! { pub NoCheckRequiresTypedReturns = "KC0106":
     "ko" => "[NO_CHECK] 속성이 주어졌을 경우 함수의 반환 타입이 주어져야 합니다",
     _    => "[NO_CHECK] attribute requires the return type to be present",
 }
 
-define_msg! { pub ModuleRequiresTypedSelf:
+define_msg! { pub ModuleRequiresTypedSelf = "KC0107":
     "ko" => "`module`로 타입 체크를 지연하려면 `self` 인자의 타입이 명백해야 합니다. \
              대신 함수 선언 문법으로 타입을 직접 지정하십시오",
     _    => "Delayed type checking via `module` requires that \
@@ -507,40 +608,40 @@ define_msg! { pub ModuleRequiresTypedSelf:
              directly specify the type for `self` with the function declaration instead",
 }
 
-define_msg! { pub ModuleRequiresTypedArgs:
+define_msg! { pub ModuleRequiresTypedArgs = "KC0108":
     "ko" => "`module`로 타입 체크를 지연하려면 인자에 타입이 주어져야 합니다",
     _    => "Delayed type checking via `module` requires the arguments to be typed",
 }
 
-define_msg! { pub ModuleRequiresTypedVarargs:
+define_msg! { pub ModuleRequiresTypedVarargs = "KC0109":
     "ko" => "`module`로 타입 체크를 지연하려면 가변 인자에 타입이 주어져야 합니다",
     _    => "Delayed type checking via `module` requires the variadic arguments to be typed",
 }
 
-define_msg! { pub ModuleRequiresTypedReturns:
+define_msg! { pub ModuleRequiresTypedReturns = "KC0110":
     "ko" => "`module`로 타입 체크를 지연하려면 함수의 반환 타입이 주어져야 합니다",
     _    => "Delayed type checking via `module` requires the return type to be present",
 }
 
-define_msg! { pub AssumeFieldToUnknownType:
+define_msg! { pub AssumeFieldToUnknownType = "KC0111":
     "ko" => "`--# assume` 명령이 아직 완전히 추론되지 않은 타입에서 필드를 접근하려 했습니다",
     _    => "`--# assume` directive tried to access a field from a type not yet known enough",
 }
 
-define_msg! { pub AssumeFieldToInstance<'a> { slot: Slot<'a> }:
+define_msg! { pub AssumeFieldToInstance<'a> { slot: Slot<'a> } = "KC0112":
     "ko" => "`--# assume` 명령을 클래스 프로토타입이 아닌 인스턴스 `{slot}`에 적용할 수 없습니다",
     _    => "`--# assume` directive cannot be applied to a class instance `{slot}` \
              instead of its prototype",
 }
 
-define_msg! { pub AssumeFieldToUnknownClass<'a> { cls: Slot<'a> }:
+define_msg! { pub AssumeFieldToUnknownClass<'a> { cls: Slot<'a> } = "KC0113":
     "ko" => "`{cls}` 타입이 정확히 하나의 클래스로 추론되지 않아 \
              `--# assume` 명령을 적용할 수 없습니다",
     _    => "Cannot apply `--# assume` directive to `{cls}` \
              that cannot be inferred to a single class",
 }
 
-define_msg! { pub AssumeFieldNestedToClass<'a> { cls: T<'a> }:
+define_msg! { pub AssumeFieldNestedToClass<'a> { cls: T<'a> } = "KC0114":
     "ko" => "`{cls}` 클래스 프로토타입의 필드 내부에 `--# assume` 명령을 적용할 수 없습니다",
     _    => "Cannot apply `--# assume` directive to the inside of fields \
              in the class prototype of `{cls}`",
@@ -548,117 +649,375 @@ define_msg! { pub AssumeFieldNestedToClass<'a> { cls: T<'a> }:
 
 // this error can also occur for `--# assume C.x: method()`,
 // so we have to avoid a mention to `--# assume static`
-define_msg! { pub AssumeFieldStaticToNonClass<'a> { slot: Slot<'a> }:
+define_msg! { pub AssumeFieldStaticToNonClass<'a> { slot: Slot<'a> } = "KC0115":
     "ko" => "`--# assume`으로 클래스가 아닌 `{slot}` 타입에 정적 필드를 추가할 수 없습니다",
     _    => "`--# assume` cannot be used to add a static field to a non-class type `{slot}`",
 }
 
-define_msg! { pub AssumeFieldToNonRecord<'a> { slot: Slot<'a> }:
+define_msg! { pub AssumeFieldToNonRecord<'a> { slot: Slot<'a> } = "KC0116":
     "ko" => "`--# assume` 명령이 레코드가 아닌 `{slot}` 타입에서 필드를 접근하려 했습니다",
     _    => "`--# assume` directive tried to access a field from a non-record type `{slot}`",
 }
 
-define_msg! { pub AssumeFieldToMissing:
+define_msg! { pub AssumeFieldToMissing = "KC0117":
     "ko" => "`--# assume` 명령이 존재하지 않는 필드를 접근하려 했습니다",
     _    => "`--# assume` directive tried to access a missing field",
 }
 
-define_msg! { pub AssumeExistingField:
+define_msg! { pub AssumeExistingField = "KC0118":
     "ko" => "`--# assume` 명령이 이미 있는 필드를 덮어 씌우려 합니다",
     _    => "`--# assume` directive tried to overwrite an existing field",
 }
 
 // this error should be avoided as much as possible, it doesn't give the exact reason
-define_msg! { pub AssumeCannotCreateNewField:
+define_msg! { pub AssumeCannotCreateNewField = "KC0119":
     "ko" => "`--# assume` 명령이 새 필드를 생성할 수 없습니다",
     _    => "`--# assume` directive cannot create a new field",
 }
 
-define_msg! { pub NoSuchClassSystem<'a> { name: &'a Name }:
+define_msg! { pub NoSuchClassSystem<'a> { name: &'a Name } = "KC0120":
     "ko" => "{name} 클래스 시스템이 정의되지 않았습니다",
     _    => "{name} class system hasn't been defined",
 }
 
-define_msg! { pub ClassSystemAlreadyExists<'a> { name: &'a Name }:
+define_msg! { pub ClassSystemAlreadyExists<'a> { name: &'a Name } = "KC0121":
     "ko" => "{name} 클래스 시스템이 이미 존재합니다",
     _    => "{name} class system already exists",
 }
 
-define_msg! { pub PreviousClassSystem:
+define_msg! { pub PreviousClassSystem = "KC0122":
     "ko" => "클래스 시스템이 여기에서 이미 선언되었습니다",
     _    => "Previous definition of the class system here",
 }
 
-define_msg! { pub NoSuchPredefinedClassSystem<'a> { name: &'a Name }:
+define_msg! { pub NoSuchPredefinedClassSystem<'a> { name: &'a Name } = "KC0123":
     "ko" => "{name} 클래스 시스템은 아직 지원되지 않습니다",
     _    => "{name} class system is not yet supported",
 }
 
-define_msg! { pub TooManyClassSystems:
+define_msg! { pub TooManyClassSystems = "KC0124":
     "ko" => "클래스 시스템은 최대 256개까지 선언할 수 있습니다",
     _    => "There may be at most 256 class systems defined",
 }
 
-define_msg! { pub BadClassParent<'a> { ty: Ty<'a> }:
+define_msg! { pub BadClassParent<'a> { ty: Ty<'a> } = "KC0125":
     "ko" => "클래스가 아닌 `{ty}` 타입은 부모 클래스가 될 수 없습니다",
     _    => "The non-class type `{ty}` cannot be a parent class",
 }
 
-define_msg! { pub NotSubtypeOfParentField<'a> { key: &'a Key, sub: Slot<'a>, sup: Slot<'a> }:
+define_msg! { pub NotSubtypeOfParentField<'a> { key: &'a Key, sub: Slot<'a>,
+                                                sup: Slot<'a> } = "KC0126":
     "ko" => "부모 클래스의 `{key}` 필드를 오버라이드하려 했으나 \
              변경 가능한 클래스 안에서는 `{sub}`이(가) 기존 타입 `{sup}`의 서브타입이 아닙니다",
     _    => "Tried to override a field `{key}` in a parent class \
              but `{sub}` is not a subtype of `{sup}` when being inside the mutable class",
 }
 
-define_msg! { pub PreviousParentFieldType:
+define_msg! { pub BadOverridingMethodArgs<'a> { key: &'a Key } = "KC0127":
+    "ko" => "메소드 `{key}`를 오버라이드하려 했으나 부모 클래스의 메소드가 받는 인자를 모두 받을 수 없습니다",
+    _    => "Tried to override a method `{key}` in a parent class \
+             but it cannot accept all arguments to the parent method",
+}
+
+define_msg! { pub BadOverridingMethodReturns<'a> { key: &'a Key } = "KC0128":
+    "ko" => "메소드 `{key}`를 오버라이드하려 했으나 \
+             부모 클래스의 메소드가 반환하는 타입의 서브타입을 반환하지 않습니다",
+    _    => "Tried to override a method `{key}` in a parent class \
+             but it does not return subtypes of what the parent method returns",
+}
+
+define_msg! { pub DeprecatedVar<'a> { name: &'a Name } = "KC0129":
+    "ko" => "{name}은(는) 더 이상 사용되지 않습니다",
+    _    => "{name} is deprecated",
+}
+
+define_msg! { pub DeprecatedField<'a> { key: &'a Key } = "KC0130":
+    "ko" => "필드 `{key}`는 더 이상 사용되지 않습니다",
+    _    => "The field `{key}` is deprecated",
+}
+
+define_msg! { pub DeprecatedValue = "KC0131":
+    "ko" => "이 값은 더 이상 사용되지 않습니다",
+    _    => "This value is deprecated",
+}
+
+define_msg! { pub DeprecatedModule<'a> { name: &'a Str } = "KC0132":
+    "ko" => "모듈 {name}은(는) 더 이상 사용되지 않습니다",
+    _    => "The module {name} is deprecated",
+}
+
+define_msg! { pub DeprecationMessage<'a> { msg: &'a str } = "KC0133":
+    "ko" => "{msg}",
+    _    => "{msg}",
+}
+
+define_msg! { pub DiscardedResult = "KC0134":
+    "ko" => "이 함수의 반환값은 무시하면 안 됩니다",
+    _    => "The return value of this function should not be discarded",
+}
+
+define_msg! { pub UnusedVariable<'a> { name: &'a Name } = "KC0135":
+    "ko" => "지역 변수 {name}이(가) 한 번도 읽히지 않습니다",
+    _    => "Local variable {name} is never read",
+}
+
+define_msg! { pub UnusedAssignment<'a> { name: &'a Name } = "KC0136":
+    "ko" => "{name}에 대입된 값이 한 번도 읽히지 않습니다",
+    _    => "The value assigned to {name} is never read",
+}
+
+define_msg! { pub ImplicitGlobal<'a> { name: &'a Name } = "KC0137":
+    "ko" => "전역 변수 {name}이(가) 선언되지 않았습니다. 시작 파일의 최상위가 아닌 곳에서는 \
+             `--# assume global`이나 설정으로 먼저 선언해야 합니다",
+    _    => "Global variable {name} is not declared; it should be declared with \
+             `--# assume global` or the configuration unless assigned at the top level \
+             of the start file",
+}
+
+define_msg! { pub ShadowedLocal<'a> { name: &'a Name } = "KC0138":
+    "ko" => "지역 변수 {name}이(가) 같은 이름의 지역 변수를 가립니다",
+    _    => "Local variable {name} shadows a local variable of the same name",
+}
+
+define_msg! { pub ShadowedUpvalue<'a> { name: &'a Name } = "KC0139":
+    "ko" => "지역 변수 {name}이(가) 바깥 함수에 있는 같은 이름의 지역 변수를 가립니다",
+    _    => "Local variable {name} shadows a local variable of the same name \
+             in an outer function",
+}
+
+define_msg! { pub ShadowedGlobal<'a> { name: &'a Name } = "KC0140":
+    "ko" => "지역 변수 {name}이(가) 같은 이름의 전역 변수를 가립니다",
+    _    => "Local variable {name} shadows a global variable of the same name",
+}
+
+define_msg! { pub ShadowedVarDefinition = "KC0141":
+    "ko" => "가려진 변수는 여기에서 정의되었습니다",
+    _    => "The shadowed variable was defined here",
+}
+
+define_msg! { pub DeprecatedApi<'a> { name: &'a str, lua: &'static str } = "KC0142":
+    "ko" => "`{name}`은(는) {lua}부터 더 이상 사용되지 않습니다",
+    _    => "`{name}` is deprecated since {lua}",
+}
+
+define_msg! { pub DeprecatedApiReplacement<'a> { replacement: &'a str } = "KC0143":
+    "ko" => "`{replacement}`을(를) 대신 사용하십시오",
+    _    => "Use `{replacement}` instead",
+}
+
+define_msg! { pub MixedTypeEquality<'a> { op: &'static str, lhs: Slot<'a>,
+                                          rhs: Slot<'a> } = "KC0144":
+    "ko" => "{op} 연산자가 서로 다른 타입 `{lhs}`와(과) `{rhs}`의 값을 비교합니다",
+    _    => "The {op} operator compares values of different types `{lhs}` and `{rhs}`",
+}
+
+define_msg! { pub EmptyBlock = "KC0145":
+    "ko" => "블록이 비어 있습니다",
+    _    => "This block is empty",
+}
+
+define_msg! { pub FloatForStep<'a> { step: Ty<'a> } = "KC0146":
+    "ko" => "숫자 `for` 루프의 증분이 정수가 아닌 `{step}` 타입이므로 \
+             반복 횟수가 반올림 오차에 따라 달라질 수 있습니다",
+    _    => "The step of this numeric `for` loop has a non-integral type `{step}`, \
+             so the number of iterations may depend on rounding errors",
+}
+
+define_msg! { pub UnknownLintInIgnore<'a> { code: &'a Name } = "KC0147":
+    "ko" => "`--# ignore`에 알 수 없는 린트 {code}이(가) 있습니다",
+    _    => "Unknown lint {code} in the `--# ignore` directive",
+}
+
+define_msg! { pub UnusedIgnore = "KC0148":
+    "ko" => "이 `--# ignore`로 숨겨진 보고가 없습니다",
+    _    => "This `--# ignore` directive did not suppress any report",
+}
+
+define_msg! { pub UnionLimitExceeded { limit: usize } = "KC0149":
+    "ko" => "리터럴이 {limit}개보다 많은 합 타입이 있어서 `integer`나 `string`으로 넓혀졌습니다",
+    _    => "Some unions had more than {limit} literals and have been widened \
+             to `integer` or `string`",
+}
+
+define_msg! { pub TableDepthLimitExceeded { limit: usize } = "KC0150":
+    "ko" => "테이블이 {limit}단계보다 깊게 중첩되어 있어서 \
+             더 안쪽의 테이블은 타입 검사가 되지 않습니다",
+    _    => "Tables are nested deeper than {limit} levels, \
+             so inner tables are no longer type checked",
+}
+
+define_msg! { pub ConstraintLimitExceeded { limit: usize } = "KC0151":
+    "ko" => "타입 제약 조건이 {limit}개를 넘어서 \
+             이후의 테이블은 타입 검사가 되지 않습니다",
+    _    => "There are more than {limit} type constraints, \
+             so subsequent tables are no longer type checked",
+}
+
+define_msg! { pub UntypedGlobalAfterEval<'a> { name: &'a Name } = "KC0152":
+    "ko" => "전역 환경이 바뀌었을 수 있으므로 전역 변수 {name}와(과) 이후의 전역 변수들의 \
+             타입을 더 이상 알 수 없습니다",
+    _    => "Global variable {name} and subsequent global variables are no longer typed \
+             as the global environment may have been altered",
+}
+
+define_msg! { pub GlobalEvalCall = "KC0153":
+    "ko" => "이 호출이 전역 환경을 바꿀 수 있습니다",
+    _    => "This call may alter the global environment",
+}
+
+define_msg! { pub BecomeModuleNotAtTopLevel = "KC0154":
+    "ko" => "`module` 함수는 파일의 최상위에서만 지원되며, 이 호출은 무시됩니다",
+    _    => "`module` is only supported at the top level of the file and this call is ignored",
+}
+
+define_msg! { pub CannotRedefineInterface<'a> { name: &'a Name } = "KC0155":
+    "ko" => "{name} 인터페이스는 이미 선언되어 있습니다",
+    _    => "An interface {name} is already defined",
+}
+
+define_msg! { pub AlreadyDefinedInterface = "KC0156":
+    "ko" => "이전 인터페이스 선언은 여기에 있습니다",
+    _    => "The interface was originally defined here",
+}
+
+define_msg! { pub NoInterface<'a> { name: &'a Name } = "KC0157":
+    "ko" => "{name} 인터페이스가 선언되지 않았습니다",
+    _    => "Interface {name} is not defined",
+}
+
+define_msg! { pub ImplementsNonTable<'a> { name: &'a Name, ty: Slot<'a> } = "KC0158":
+    "ko" => "{name} 변수의 타입 `{ty}`은(는) 필드를 알 수 없어 인터페이스를 구현하는지 검사할 수 없습니다",
+    _    => "Cannot check if {name} implements the interface, \
+             as its type `{ty}` has no known fields",
+}
+
+define_msg! { pub NotImplemented<'a> { name: &'a Name, iface: &'a Name } = "KC0159":
+    "ko" => "{name} 변수가 {iface} 인터페이스를 구현하지 않습니다",
+    _    => "{name} does not implement the interface {iface}",
+}
+
+define_msg! { pub InterfaceFieldMissing<'a> { key: &'a Key, ty: Slot<'a> } = "KC0160":
+    "ko" => "`{key}` 필드(`{ty}`)가 없습니다",
+    _    => "A field `{key}` (`{ty}`) is missing",
+}
+
+define_msg! { pub InterfaceMethodMissing<'a> { key: &'a Key, ty: Slot<'a> } = "KC0161":
+    "ko" => "`{key}` 메소드(`{ty}`에 `self` 인자를 더한 것)가 없습니다",
+    _    => "A method `{key}` (`{ty}` with the `self` argument) is missing",
+}
+
+define_msg! { pub InterfaceFieldMismatch<'a> { key: &'a Key, expected: Slot<'a>,
+                                               actual: Slot<'a> } = "KC0162":
+    "ko" => "`{key}` 필드의 타입 `{actual}`이(가) `{expected}`의 서브타입이 아닙니다",
+    _    => "A field `{key}` has a type `{actual}` which is not a subtype of `{expected}`",
+}
+
+define_msg! { pub UnimplementedAbstractFields<'a> { cls: T<'a>, keys: &'a str } = "KC0163":
+    "ko" => "`{cls}`에 구현되지 않은 추상 필드가 있어 인스턴스를 만들 수 없습니다: {keys}",
+    _    => "Cannot instantiate `{cls}` with unimplemented abstract fields: {keys}",
+}
+
+define_msg! { pub AbstractFieldDeclared<'a> { key: &'a Key } = "KC0164":
+    "ko" => "추상 필드 `{key}`는 여기에서 선언되었습니다",
+    _    => "The abstract field `{key}` is declared here",
+}
+
+define_msg! { pub PreviousParentFieldType = "KC0165":
     "ko" => "기존 타입은 여기에서 선언되었습니다",
     _    => "Previous definition of the field type here",
 }
 
-define_msg! { pub MissingParentClassForGideros:
+define_msg! { pub MissingParentClassForGideros = "KC0166":
     "ko" => "`gideros` 클래스 시스템에서 부모가 없는 클래스는 하나만 존재할 수 있습니다",
     _    => "There should be a single class without a parent in the `gideros` class system",
 }
 
-define_msg! { pub NotTVar<'a> { slot: Slot<'a> }:
+define_msg! { pub NotTVar<'a> { slot: Slot<'a> } = "KC0167":
     "ko" => "내부 오류: `{slot}` 타입이 타입 변수가 아닙니다",
     _    => "Internal Error: A type `{slot}` is not a type variable",
 }
 
 #[cfg(feature = "no_implicit_func_sig")]
-define_msg! { pub ImplicitSigOnNamedFunc:
+define_msg! { pub ImplicitSigOnNamedFunc = "KC0168":
     "ko" => "이름이 붙은 함수의 모든 인자에는 타입이 붙어야 합니다",
     _    => "Every argument in the named function should have a type specified",
 }
 
 #[cfg(feature = "no_implicit_func_sig")]
-define_msg! { pub ImplicitArgTypeOnAnonymousFunc:
+define_msg! { pub ImplicitArgTypeOnAnonymousFunc = "KC0169":
     "ko" => "익명 함수의 인자에 타입이 없고 호출로부터 추론할 수도 없습니다",
     _    => "The type for this argument in the anonymous function is missing \
              but couldn't be inferred from the calls",
 }
 
 #[cfg(feature = "no_implicit_func_sig")]
-define_msg! { pub ImplicitVarargsTypeOnAnonymousFunc:
+define_msg! { pub ImplicitVarargsTypeOnAnonymousFunc = "KC0170":
     "ko" => "익명 함수의 가변 인자에 타입이 없고 호출로부터 추론할 수도 없습니다",
     _    => "The type for variadic arguments in the anonymous function is missing \
              but couldn't be inferred from the calls",
 }
 
-define_msg! { pub DivergingInExpr:
+define_msg! { pub DivergingInExpr = "KC0171":
     "ko" => "중간에 반환하지 않는 함수 호출이 있어서 일부 수식은 영원히 평가되지 않습니다",
     _    => "A portion of this expression won't be evaluated \
              because it contains a call to a function that never returns",
 }
 
-define_msg! { pub ReturnInDivergingFunc:
+define_msg! { pub ReturnInDivergingFunc = "KC0172":
     "ko" => "반환하지 않도록 지정된 함수 안에서 반환하려고 했습니다",
     _    => "Tried to return from a function that is marked that it never returns",
 }
 
-define_msg! { pub ClassInheritFromDifferentClassSystem:
+define_msg! { pub ClassInheritFromDifferentClassSystem = "KC0173":
     "ko" => "이 클래스는 다른 클래스 시스템을 쓰는 클래스에서 상속받을 수 없습니다",
     _    => "The class cannot inherit from a class using a different class system",
 }
 
+define_msg! { pub ClassInheritFromGenericClass = "KC0174":
+    "ko" => "타입 인자를 받는 클래스는 상속할 수 없습니다",
+    _    => "The class cannot inherit from a class with type parameters",
+}
+
+
+define_msg! { pub AssertionNoGlobal<'a> { name: &'a Name } = "KC0175":
+    "ko" => "워크스페이스 검증 실패: 전역 변수 {name}가(이) 선언되지 않았습니다",
+    _    => "Workspace assertion failed: global variable {name} is not defined",
+}
+
+define_msg! { pub AssertionNoModule<'a> { name: &'a Str } = "KC0176":
+    "ko" => "워크스페이스 검증 실패: 모듈 {name}을(를) 읽어들인 적이 없습니다",
+    _    => "Workspace assertion failed: module {name} has never been loaded",
+}
+
+define_msg! { pub AssertionNoExport<'a> { module: &'a Str, field: &'a Str } = "KC0177":
+    "ko" => "워크스페이스 검증 실패: 모듈 {module}이(가) {field} 필드를 내보내지 않습니다",
+    _    => "Workspace assertion failed: module {module} does not export a field {field}",
+}
+
+define_msg! { pub AssertionGlobalType<'a> { name: &'a Name, expected: Ty<'a>,
+                                            actual: Ty<'a> } = "KC0178":
+    "ko" => "워크스페이스 검증 실패: 전역 변수 {name}의 타입 {actual}이(가) \
+             {expected}의 서브타입이 아닙니다",
+    _    => "Workspace assertion failed: global variable {name} has a type {actual} \
+             which is not a subtype of {expected}",
+}
+
+define_msg! { pub AssertionExportType<'a> { module: &'a Str, field: &'a Str,
+                                            expected: Ty<'a>, actual: Ty<'a> } = "KC0179":
+    "ko" => "워크스페이스 검증 실패: 모듈 {module}이(가) 내보낸 {field} 필드의 타입 {actual}이(가) \
+             {expected}의 서브타입이 아닙니다",
+    _    => "Workspace assertion failed: a field {field} exported by module {module} \
+             has a type {actual} which is not a subtype of {expected}",
+}
+
+#[test]
+fn test_codes() {
+    let source = include_str!("message.rs");
+    let mut codes: Vec<&str> = source.split("= \"KC").skip(1).map(|s| &s[..4]).collect();
+    assert_eq!(codes.len(), source.matches("define_msg! {").count() - 1);
+    assert!(codes.iter().all(|code| code.bytes().all(|c| b'0' <= c && c <= b'9')));
+    codes.sort();
+    let ncodes = codes.len();
+    codes.dedup();
+    assert_eq!(codes.len(), ncodes, "duplicate diagnostic codes");
+}
@@ -0,0 +1,207 @@
+//! Operator rules depending on the Lua version.
+//!
+//! Lua has changed the semantics of operators over versions: integer division and
+//! bitwise operators (and their metamethods) appeared in 5.3, `__lt` and `__le` are
+//! looked up from either operand since 5.2, `__len` applies to tables since 5.2,
+//! and strings are no longer converted to integers by bitwise operators since 5.4.
+//! The checker consults `OpRules` instead of testing the version by itself.
+
+use kailua_syntax::ast::{UnOp, BinOp};
+use kailua_syntax::lang::Lua;
+
+/// A metamethod consulted by an operator.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Metamethod {
+    /// The metamethod name like `__add`.
+    pub name: &'static [u8],
+
+    /// True if the operands are given in the reverse order (`a > b` is `b < a`).
+    pub swapped: bool,
+
+    /// True if the metamethod is only used when both operands have it.
+    pub both: bool,
+}
+
+/// How a string operand is converted by an arithmetic or bitwise operator.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Coercion {
+    /// Strings are not converted.
+    None,
+
+    /// Strings representing a number are converted to the number.
+    Number,
+
+    /// Strings representing an integer are converted to the integer.
+    Integer,
+}
+
+/// Operator rules for given Lua version.
+#[derive(Copy, Clone, Debug)]
+pub struct OpRules {
+    lua: Lua,
+}
+
+impl OpRules {
+    pub fn new(lua: Lua) -> OpRules {
+        OpRules { lua: lua }
+    }
+
+    /// Returns a metamethod that the unary operator consults, if any.
+    pub fn unary_metamethod(&self, op: UnOp) -> Option<&'static [u8]> {
+        match op {
+            UnOp::Neg => Some(b"__unm"),
+            UnOp::Not => None,
+            UnOp::Len if self.lua >= Lua::Lua52 => Some(b"__len"),
+            UnOp::Len => None, // only applies to userdata, which has no metatable in Kailua
+            UnOp::BNot => Some(b"__bnot"),
+        }
+    }
+
+    /// Returns a metamethod that the binary operator consults, if any.
+    pub fn binary_metamethod(&self, op: BinOp) -> Option<Metamethod> {
+        let meta = |name: &'static [u8], swapped, both| {
+            Some(Metamethod { name: name, swapped: swapped, both: both })
+        };
+
+        // Lua 5.1 requires both operands to have the same comparison metamethod
+        let ordered_both = self.lua < Lua::Lua52;
+
+        match op {
+            BinOp::Add => meta(b"__add", false, false),
+            BinOp::Sub => meta(b"__sub", false, false),
+            BinOp::Mul => meta(b"__mul", false, false),
+            BinOp::Div => meta(b"__div", false, false),
+            BinOp::IDiv => meta(b"__idiv", false, false),
+            BinOp::Pow => meta(b"__pow", false, false),
+            BinOp::Mod => meta(b"__mod", false, false),
+            BinOp::Cat => meta(b"__concat", false, false),
+            BinOp::BAnd => meta(b"__band", false, false),
+            BinOp::BOr => meta(b"__bor", false, false),
+            BinOp::BXor => meta(b"__bxor", false, false),
+            BinOp::Shl => meta(b"__shl", false, false),
+            BinOp::Shr => meta(b"__shr", false, false),
+            BinOp::Lt => meta(b"__lt", false, ordered_both),
+            BinOp::Le => meta(b"__le", false, ordered_both),
+            BinOp::Gt => meta(b"__lt", true, ordered_both),
+            BinOp::Ge => meta(b"__le", true, ordered_both),
+            // `__eq` is never called for non-table operands, so we always require both
+            BinOp::Eq | BinOp::Ne => meta(b"__eq", false, true),
+            BinOp::And | BinOp::Or => None,
+        }
+    }
+
+    /// Returns how the unary operator converts a string operand.
+    pub fn unary_coercion(&self, op: UnOp) -> Coercion {
+        match op {
+            UnOp::Neg => Coercion::Number,
+            UnOp::BNot => self.bitwise_coercion(),
+            UnOp::Not | UnOp::Len => Coercion::None,
+        }
+    }
+
+    /// Returns how the binary operator converts a string operand.
+    ///
+    /// Numbers are also converted to strings by `..`, which is not described here.
+    pub fn binary_coercion(&self, op: BinOp) -> Coercion {
+        match op {
+            // Lua 5.4 does this via string metamethods, but the result is same
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div |
+            BinOp::IDiv | BinOp::Pow | BinOp::Mod => Coercion::Number,
+
+            BinOp::BAnd | BinOp::BOr | BinOp::BXor |
+            BinOp::Shl | BinOp::Shr => self.bitwise_coercion(),
+
+            _ => Coercion::None,
+        }
+    }
+
+    fn bitwise_coercion(&self) -> Coercion {
+        if self.lua < Lua::Lua54 { Coercion::Integer } else { Coercion::None }
+    }
+}
+
+/// The kind of number that a string represents.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NumericString {
+    Integer,
+    Float,
+}
+
+/// Returns the kind of number if the string would be converted to a number by Lua.
+///
+/// This recognizes decimal and hexadecimal numerals surrounded by optional whitespaces,
+/// which is common to all supported versions. An integer overflow is not detected.
+pub fn parse_numeric_string(s: &[u8]) -> Option<NumericString> {
+    fn is_space(c: u8) -> bool {
+        c == b' ' || (b'\t' <= c && c <= b'\r')
+    }
+
+    fn digits(s: &[u8], radix: u32) -> usize {
+        s.iter().take_while(|&&c| (c as char).is_digit(radix)).count()
+    }
+
+    let begin = s.iter().position(|&c| !is_space(c)).unwrap_or(s.len());
+    let end = s.iter().rposition(|&c| !is_space(c)).map_or(begin, |i| i + 1);
+    let mut s = &s[begin..end];
+    if s.starts_with(b"-") || s.starts_with(b"+") {
+        s = &s[1..];
+    }
+
+    if s.starts_with(b"0x") || s.starts_with(b"0X") {
+        let n = digits(&s[2..], 16);
+        return if n > 0 && n + 2 == s.len() { Some(NumericString::Integer) } else { None };
+    }
+
+    let int = digits(s, 10);
+    s = &s[int..];
+    let mut frac = 0;
+    let mut float = false;
+    if s.starts_with(b".") {
+        frac = digits(&s[1..], 10);
+        s = &s[frac + 1..];
+        float = true;
+    }
+    if int + frac == 0 {
+        return None;
+    }
+    if s.starts_with(b"e") || s.starts_with(b"E") {
+        s = &s[1..];
+        if s.starts_with(b"-") || s.starts_with(b"+") {
+            s = &s[1..];
+        }
+        let exp = digits(s, 10);
+        if exp == 0 {
+            return None;
+        }
+        s = &s[exp..];
+        float = true;
+    }
+
+    if !s.is_empty() {
+        None
+    } else if float {
+        Some(NumericString::Float)
+    } else {
+        Some(NumericString::Integer)
+    }
+}
+
+#[test]
+fn test_parse_numeric_string() {
+    use self::NumericString::*;
+
+    assert_eq!(parse_numeric_string(b"42"), Some(Integer));
+    assert_eq!(parse_numeric_string(b" -42\n"), Some(Integer));
+    assert_eq!(parse_numeric_string(b"0x1F"), Some(Integer));
+    assert_eq!(parse_numeric_string(b"4.5"), Some(Float));
+    assert_eq!(parse_numeric_string(b".5"), Some(Float));
+    assert_eq!(parse_numeric_string(b"5."), Some(Float));
+    assert_eq!(parse_numeric_string(b"1e10"), Some(Float));
+    assert_eq!(parse_numeric_string(b"1E-3"), Some(Float));
+    assert_eq!(parse_numeric_string(b""), None);
+    assert_eq!(parse_numeric_string(b"."), None);
+    assert_eq!(parse_numeric_string(b"0x"), None);
+    assert_eq!(parse_numeric_string(b"1e"), None);
+    assert_eq!(parse_numeric_string(b"4 2"), None);
+    assert_eq!(parse_numeric_string(b"forty-two"), None);
+}
@@ -5,8 +5,33 @@ use std::ascii::AsciiExt;
 use std::path::{Path, PathBuf, MAIN_SEPARATOR};
 
 use kailua_env::{Spanned, WithLoc};
-use kailua_diag::{Report, Stop};
-use kailua_syntax::Chunk;
+use kailua_diag::{Report, Reporter, Stop};
+use kailua_syntax::{Str, Chunk};
+use kailua_syntax::lang::Lua;
+use message as m;
+use lint::{Severity, find_lint};
+
+pub use kailua_types::ty::DEFAULT_LITERAL_UNION_LIMIT;
+
+/// The default value of `Options::table_depth_limit`.
+pub const DEFAULT_TABLE_DEPTH_LIMIT: usize = 32;
+
+/// The default value of `Options::constraint_limit`.
+pub const DEFAULT_CONSTRAINT_LIMIT: usize = 1_000_000;
+
+/// How `require` with a non-literal module name is checked (see `Options::dynamic_require`).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DynamicRequire {
+    /// Reports an error. The result is dynamic.
+    Error,
+
+    /// Reports a warning. The result is dynamic.
+    Warn,
+
+    /// The module name is assumed to be one of given names, which are all `require`d.
+    /// The result is a union of their types.
+    OneOf(Vec<Vec<u8>>),
+}
 
 /// Options for customizing the type checker.
 ///
@@ -31,13 +56,110 @@ pub trait Options {
         Ok(())
     }
 
+    /// Called whenever `require` is called with a string literal type,
+    /// even when the module has been already loaded and `require_chunk` won't be called.
+    ///
+    /// The span of `path` points to the argument in the requiring file.
+    /// Does nothing by default.
+    fn on_require(&mut self, _path: Spanned<&[u8]>) {}
+
     /// Called when `require` is called with a string literal type.
     ///
-    /// Errors by default; the checker will use its own error message.
+    /// Errors by default; the checker will use its own error message
+    /// unless `Err(Some(Stop))` is returned, which means that the error has been reported.
     fn require_chunk(&mut self, _path: Spanned<&[u8]>,
                      _report: &Report) -> Result<Chunk, Option<Stop>> {
         Err(None)
     }
+
+    /// Should return true if the strict nil-safety mode is enabled.
+    ///
+    /// In this mode explicitly typed slots without `?` do not accept `nil`,
+    /// the `nil` literal itself is checked, and indexing arrays and maps returns `T?`
+    /// (which cannot be used without a check) instead of `T`.
+    /// Returns false by default.
+    fn strict_nil(&self) -> bool {
+        false
+    }
+
+    /// Should return true if the strict globals mode is enabled.
+    ///
+    /// In this mode assigning to an undeclared global variable is an error,
+    /// except at the top level of the start file (see `Env::set_start_file`).
+    /// Global variables should be declared with `--# assume global` or `Preload::globals`.
+    /// Returns false by default.
+    fn strict_globals(&self) -> bool {
+        false
+    }
+
+    /// Should return the version of Lua being checked.
+    ///
+    /// Since Lua 5.3 float literals are typed as `number` even when they are integral,
+    /// and integer literals and operators are typed as 64-bit integers.
+    /// Chunks should be parsed with the same version (`kailua_syntax::parse_chunk_with_language`)
+    /// in order to recognize integer literals and operators.
+    /// Returns Lua 5.1 by default.
+    fn lua_version(&self) -> Lua {
+        Lua::Lua51
+    }
+
+    /// Should return how `require` with a non-literal module name is checked.
+    ///
+    /// Returns `DynamicRequire::Warn` by default.
+    fn dynamic_require(&self) -> DynamicRequire {
+        DynamicRequire::Warn
+    }
+
+    /// Should return the maximum number of literals in a union of numbers or strings.
+    ///
+    /// Larger unions are widened to `integer` or `string` respectively;
+    /// see `TypeContext::literal_union_limit` for details.
+    /// Returns `DEFAULT_LITERAL_UNION_LIMIT` by default.
+    fn literal_union_limit(&self) -> usize {
+        DEFAULT_LITERAL_UNION_LIMIT
+    }
+
+    /// Should return the maximum nesting depth of table constructors.
+    ///
+    /// Tables nested deeper than this are given the dynamic type `WHATEVER`
+    /// and a note is reported once.
+    /// Returns `DEFAULT_TABLE_DEPTH_LIMIT` by default.
+    fn table_depth_limit(&self) -> usize {
+        DEFAULT_TABLE_DEPTH_LIMIT
+    }
+
+    /// Should return the maximum number of type constraints to be asserted,
+    /// see `Types::constraint_count` for what is counted.
+    ///
+    /// Once exceeded, table constructors are given the dynamic type `WHATEVER`
+    /// so that no further fields are inferred, and a note is reported once.
+    /// Returns `DEFAULT_CONSTRAINT_LIMIT` by default.
+    fn constraint_limit(&self) -> usize {
+        DEFAULT_CONSTRAINT_LIMIT
+    }
+
+    /// Should return the severity of reports from given lint (see `kailua_check::lint`).
+    ///
+    /// Currently only consulted for the `deprecated`, `discarded-result`,
+    /// `unused-variable`, `unused-assignment`, `shadowing`, `string-coercion`,
+    /// `deprecated-api`, `mixed-type-equality`, `empty-block`, `float-for-step`
+    /// and `unused-ignore` lints. Reports from these lints can be also suppressed
+    /// by `--# ignore: CODE`.
+    /// Returns the default severity of the lint by default.
+    fn lint_severity(&self, code: &str) -> Severity {
+        find_lint(code).map_or(Severity::Warn, |lint| lint.default_severity)
+    }
+
+    /// Should return the overridden severity of reports with given diagnostic code
+    /// (e.g. `KC0123`, see `kailua_diag::Localize::code`), or `None` to keep the original.
+    ///
+    /// Only consulted for warnings and errors (but not fatal errors);
+    /// `Severity::Allow` drops the report and its following notes altogether.
+    /// Reports with codes can be also suppressed by `--# ignore: CODE`.
+    /// Returns `None` by default.
+    fn code_severity(&self, _code: &str) -> Option<Severity> {
+        None
+    }
 }
 
 /// Checker options that are tailored to loading from the file system.
@@ -51,6 +173,13 @@ pub trait FsSource {
     fn chunk_from_path(&self, resolved_path: Spanned<&Path>,
                        report: &Report) -> Result<Option<Chunk>, Option<Stop>>;
 
+    /// Should return true if a given fully resolved path exists, even when it cannot be loaded.
+    ///
+    /// Used to detect native libraries without type stubs. Checks the file system by default.
+    fn path_exists(&self, resolved_path: Spanned<&Path>) -> bool {
+        resolved_path.is_file()
+    }
+
     /// Should try to parse a byte string containing a relative path to a path buffer.
     ///
     /// Used to delegate the encoding decision to the user.
@@ -64,12 +193,28 @@ pub trait FsSource {
     }
 }
 
+impl<S: FsSource + ?Sized> FsSource for Box<S> {
+    fn chunk_from_path(&self, resolved_path: Spanned<&Path>,
+                       report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+        (**self).chunk_from_path(resolved_path, report)
+    }
+
+    fn path_exists(&self, resolved_path: Spanned<&Path>) -> bool {
+        (**self).path_exists(resolved_path)
+    }
+
+    fn to_path_buf(&self, path: Spanned<&[u8]>, report: &Report) -> Result<PathBuf, Option<Stop>> {
+        (**self).to_path_buf(path, report)
+    }
+}
+
 /// An implementation of `Options` that loads from the file system.
 ///
 /// The user should provide `FsSource`, which provides a simpler interface for this use case.
 pub struct FsOptions<S> {
     source: S,
     root: PathBuf,
+    definition_paths: Vec<Vec<u8>>,
     package_path: Vec<Vec<u8>>,
     package_cpath: Vec<Vec<u8>>,
 }
@@ -79,6 +224,7 @@ impl<S: FsSource> FsOptions<S> {
         FsOptions {
             source: source,
             root: root,
+            definition_paths: vec![],
 
             // by default, local files only
             package_path: vec![b"?.lua".to_vec()],
@@ -86,8 +232,19 @@ impl<S: FsSource> FsOptions<S> {
         }
     }
 
-    fn search_file(&self, path: Spanned<&[u8]>, search_paths: &[Vec<u8>], suffix: &[u8],
-                   report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+    /// Sets templates (paths with a hole `?`, as in `package.path`) for definition files.
+    ///
+    /// They are searched before `package.path` and `package.cpath` but only for `.kailua` files,
+    /// so that type stubs shared across projects take precedence over the real source.
+    /// Unlike `package.path` they are never changed by the code being checked.
+    pub fn set_definition_paths(&mut self, paths: Vec<Vec<u8>>) {
+        self.definition_paths = paths;
+    }
+
+    // returns fully resolved paths for each template, with and without dots replaced
+    fn resolve_paths(&self, path: Spanned<&[u8]>, search_paths: &[Vec<u8>], suffix: &[u8],
+                     report: &Report) -> Result<Vec<PathBuf>, Option<Stop>> {
+        let mut resolved_paths = Vec::new();
         for template in search_paths {
             let mut newpath = Vec::new();
             let mut newpathdot = Vec::new();
@@ -105,16 +262,18 @@ impl<S: FsSource> FsOptions<S> {
             newpathdot.extend_from_slice(suffix);
 
             let newpath = (&newpath[..]).with_loc(path);
-            let resolved_path = self.root.join(self.source.to_path_buf(newpath, report)?);
-            let resolved_path = (&*resolved_path).with_loc(path);
-            trace!("trying to load {:?}", resolved_path);
-            if let Some(chunk) = self.source.chunk_from_path(resolved_path, report)? {
-                return Ok(Some(chunk));
-            }
+            resolved_paths.push(self.root.join(self.source.to_path_buf(newpath, report)?));
 
             // also try to load a dotted path
             let newpathdot = (&newpathdot[..]).with_loc(path);
-            let resolved_path = self.root.join(self.source.to_path_buf(newpathdot, report)?);
+            resolved_paths.push(self.root.join(self.source.to_path_buf(newpathdot, report)?));
+        }
+        Ok(resolved_paths)
+    }
+
+    fn search_file(&self, path: Spanned<&[u8]>, search_paths: &[Vec<u8>], suffix: &[u8],
+                   report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+        for resolved_path in self.resolve_paths(path, search_paths, suffix, report)? {
             let resolved_path = (&*resolved_path).with_loc(path);
             trace!("trying to load {:?}", resolved_path);
             if let Some(chunk) = self.source.chunk_from_path(resolved_path, report)? {
@@ -124,6 +283,92 @@ impl<S: FsSource> FsOptions<S> {
 
         Ok(None)
     }
+
+    // search paths and suffixes for chunks in the order of preference;
+    // native libraries are not included, as they cannot be loaded as is
+    fn chunk_search_order(&self) -> [(&[Vec<u8>], &'static [u8]); 4] {
+        [
+            (&self.definition_paths, b".kailua"),
+            (&self.package_path, b".kailua"),
+            (&self.package_path, b""),
+            (&self.package_cpath, b".kailua"),
+        ]
+    }
+
+    /// Searches for a chunk to be loaded by `require`, without reporting a missing module.
+    pub fn search_chunk(&self, path: Spanned<&[u8]>,
+                        report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+        for &(search_paths, suffix) in &self.chunk_search_order() {
+            if let Some(chunk) = self.search_file(path, search_paths, suffix, report)? {
+                return Ok(Some(chunk));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns a path to the chunk that `search_chunk` would load, without loading it.
+    ///
+    /// Only the existence of each candidate path is checked (with `FsSource::path_exists`),
+    /// so the returned chunk may still fail to load.
+    pub fn resolve_chunk_path(&self, path: Spanned<&[u8]>,
+                              report: &Report) -> Result<Option<PathBuf>, Option<Stop>> {
+        for &(search_paths, suffix) in &self.chunk_search_order() {
+            for resolved_path in self.resolve_paths(path, search_paths, suffix, report)? {
+                if self.source.path_exists((&*resolved_path).with_loc(path)) {
+                    return Ok(Some(resolved_path));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Searches for a native library in `package.cpath` that Lua would load for `require`
+    /// but the checker cannot load without a type stub.
+    pub fn search_native_library(&self, path: Spanned<&[u8]>,
+                                 report: &Report) -> Result<Option<PathBuf>, Option<Stop>> {
+        for resolved_path in self.resolve_paths(path, &self.package_cpath, b"", report)? {
+            if self.source.path_exists((&*resolved_path).with_loc(path)) {
+                return Ok(Some(resolved_path));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reports that a native library has been found for `require` but it has no type stub.
+    ///
+    /// Candidate paths for type stubs are listed as a note.
+    /// Returns an error to be returned from `Options::require_chunk`.
+    pub fn report_native_library(&self, path: Spanned<&[u8]>, native_path: &Path,
+                                 report: &Report) -> Option<Stop> {
+        let display = |p: &Path| {
+            format!("`{}`", p.strip_prefix(&self.root).unwrap_or(p).display())
+        };
+
+        // dotted paths are also searched, but they are rarely used and omitted here
+        let mut candidates = Vec::new();
+        for search_paths in &[&self.package_path, &self.package_cpath] {
+            if let Ok(paths) = self.resolve_paths(path, search_paths, b".kailua", report) {
+                for p in paths.iter().step_by(2) {
+                    let p = display(p);
+                    if !candidates.contains(&p) {
+                        candidates.push(p);
+                    }
+                }
+            }
+        }
+
+        let name = Str::from(path.to_vec());
+        let native_path = display(native_path);
+        let mut more = report.warn(path, m::NativeModuleWithoutStub { name: &name,
+                                                                      path: &native_path });
+        if !candidates.is_empty() {
+            more = more.note(path, m::NativeModuleStubCandidates { paths: &candidates.join(", ") });
+        }
+
+        // the checker should not report this again, so `Stop` is returned even on recovery
+        let _ = more.done();
+        Some(Stop)
+    }
 }
 
 impl<S: FsSource> Options for FsOptions<S> {
@@ -141,18 +386,12 @@ impl<S: FsSource> Options for FsOptions<S> {
 
     fn require_chunk(&mut self, path: Spanned<&[u8]>,
                      report: &Report) -> Result<Chunk, Option<Stop>> {
-        if let Some(chunk) = self.search_file(path, &self.package_path, b".kailua", report)? {
-            return Ok(chunk);
-        }
-        if let Some(chunk) = self.search_file(path, &self.package_path, b"", report)? {
+        if let Some(chunk) = self.search_chunk(path, report)? {
             return Ok(chunk);
         }
-        if let Some(chunk) = self.search_file(path, &self.package_cpath, b".kailua", report)? {
-            return Ok(chunk);
+        if let Some(native_path) = self.search_native_library(path, report)? {
+            return Err(self.report_native_library(path, &native_path, report));
         }
-        // avoid loading the native libraries as is
-
         Err(None)
     }
 }
-
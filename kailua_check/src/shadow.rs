@@ -0,0 +1,232 @@
+//! Detection of local variables shadowing other variables.
+//!
+//! This is a purely syntactic pass run after checking each chunk,
+//! except that the global variables are looked up by the checker afterwards.
+//! Declarations are resolved with the scope map, so the shadowed variable is
+//! the one that the same name would have referred to right before the declaration.
+//!
+//! Variables whose names start with `_` are never reported,
+//! and neither are declarations initialized from the shadowed variable (`local x = x`).
+
+use std::collections::HashMap;
+
+use kailua_env::{Span, Spanned, WithLoc, ScopedId, ScopeMap};
+use kailua_syntax::Name;
+use kailua_syntax::ast::{NameRef, Var, Exp, Ex, Args, Table, St, Block, Sig, SelfParam};
+
+/// The variable shadowed by a local declaration.
+#[derive(Clone, Debug)]
+pub enum Shadowed {
+    /// A local variable declared in the same function.
+    Local(Spanned<ScopedId>),
+
+    /// A local variable declared in an enclosing function.
+    Upvalue(Spanned<ScopedId>),
+
+    /// A global variable (if any), as there is no local variable of the same name.
+    Global(Name),
+}
+
+/// Returns a list of local declarations that may shadow other variables,
+/// sorted by the span.
+pub fn find_shadowing(block: &Spanned<Block>,
+                      scope_map: &ScopeMap<Name>) -> Vec<(Spanned<ScopedId>, Shadowed)> {
+    let mut collector = Collector {
+        decls: Vec::new(),
+        decl_sites: HashMap::new(),
+        func_stack: vec![0],
+        next_func: 1,
+    };
+    collector.visit_block(block);
+
+    let mut shadowing = Vec::new();
+    for &(ref id, reportable, init) in &collector.decls {
+        let name = id.base.name(scope_map);
+        if !reportable || !id.span.is_source_dependent() || name.starts_with(b"_") {
+            continue;
+        }
+
+        // names declared together share the same scope, so look up from its parent
+        let scope = scope_map.parent_scope(id.base.scope(scope_map));
+        let prev = scope.and_then(|scope| scope_map.find_name_in_scope(scope, name));
+
+        // `local x = x` is a deliberate shadowing
+        let initialized_from = |nameref: &NameRef| {
+            init.map_or(false, |e| match *e.base {
+                Ex::Var(ref var) => var.base == *nameref,
+                _ => false,
+            })
+        };
+
+        let shadowed = if let Some((_, prev)) = prev {
+            if initialized_from(&NameRef::Local(prev.clone())) {
+                continue;
+            }
+            let (span, func) = match collector.decl_sites.get(&prev) {
+                Some(&site) => site,
+                None => continue,
+            };
+            let prev = prev.with_loc(span);
+            if func == collector.decl_sites[&id.base].1 {
+                Shadowed::Local(prev)
+            } else {
+                Shadowed::Upvalue(prev)
+            }
+        } else {
+            if initialized_from(&NameRef::Global(name.clone())) {
+                continue;
+            }
+            Shadowed::Global(name.clone())
+        };
+        shadowing.push((id.clone(), shadowed));
+    }
+
+    shadowing.sort_by_key(|&(ref id, _)| id.span.begin());
+    shadowing
+}
+
+struct Collector<'a> {
+    // declared variables, whether they can be reported and their initializers if any
+    decls: Vec<(Spanned<ScopedId>, bool, Option<&'a Spanned<Exp>>)>,
+    // the declaration span and function of each variable
+    decl_sites: HashMap<ScopedId, (Span, usize)>,
+    func_stack: Vec<usize>,
+    next_func: usize,
+}
+
+impl<'a> Collector<'a> {
+    fn declare(&mut self, id: &Spanned<ScopedId>, reportable: bool,
+               init: Option<&'a Spanned<Exp>>) {
+        let func = *self.func_stack.last().unwrap();
+        self.decl_sites.insert(id.base.clone(), (id.span, func));
+        self.decls.push((id.clone(), reportable, init));
+    }
+
+    fn visit_func(&mut self, selfparam: Option<&Spanned<SelfParam>>,
+                  sig: &Sig, body: &'a Spanned<Block>) {
+        self.func_stack.push(self.next_func);
+        self.next_func += 1;
+        if let Some(selfparam) = selfparam {
+            self.declare(&selfparam.base.0.clone().with_loc(selfparam.span), true, None);
+        }
+        for arg in &sig.args.head {
+            self.declare(&arg.base, true, None);
+        }
+        self.visit_block(body);
+        self.func_stack.pop();
+    }
+
+    fn visit_block(&mut self, block: &'a Spanned<Block>) {
+        for stmt in &block.base {
+            self.visit_stmt(&stmt.base);
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &'a St) {
+        match *stmt {
+            St::Oops | St::Break | St::KailuaOpen(..) | St::KailuaType(..) |
+            St::KailuaClassSystem(..) | St::KailuaInterface(..) |
+            St::KailuaAssumeField(..) | St::KailuaAssumeMethod(..) |
+            St::KailuaAssertImplements(..) | St::KailuaIgnore(..) => {}
+
+            St::Void(ref exp) => self.visit_exp(exp),
+            St::Assign(ref vars, ref exps) => {
+                for var in &vars.base {
+                    match var.base.base {
+                        Var::Name(_) => {}
+                        Var::Index(ref e1, ref e2) => { self.visit_exp(e1); self.visit_exp(e2); }
+                        Var::IndexName(ref e, _) => self.visit_exp(e),
+                    }
+                }
+                if let Some(ref exps) = *exps {
+                    for exp in &exps.base { self.visit_exp(exp); }
+                }
+            }
+            St::Do(ref block) => self.visit_block(block),
+            St::While(ref cond, ref block) | St::Repeat(ref block, ref cond) => {
+                self.visit_exp(cond);
+                self.visit_block(block);
+            }
+            St::If(ref conds, ref lastblock) => {
+                for cond in conds {
+                    let (ref cond, ref block) = cond.base;
+                    self.visit_exp(cond);
+                    self.visit_block(block);
+                }
+                if let Some(ref block) = *lastblock { self.visit_block(block); }
+            }
+            St::For(ref var, ref start, ref end, ref step, _, ref block) => {
+                self.visit_exp(start);
+                self.visit_exp(end);
+                if let Some(ref step) = *step { self.visit_exp(step); }
+                self.declare(var, true, None);
+                self.visit_block(block);
+            }
+            St::ForIn(ref vars, ref exps, _, ref block) => {
+                for exp in &exps.base { self.visit_exp(exp); }
+                for var in &vars.base { self.declare(var, true, None); }
+                self.visit_block(block);
+            }
+            St::FuncDecl(ref name, ref sig, _, ref block, sibling_scope) => {
+                if let (Some(_), &NameRef::Local(ref id)) = (sibling_scope, &name.base) {
+                    self.declare(&id.clone().with_loc(name.span), true, None);
+                }
+                self.visit_func(None, sig, block);
+            }
+            St::MethodDecl(_, ref selfparam, ref sig, _, ref block) => {
+                self.visit_func(selfparam.as_ref(), sig, block);
+            }
+            St::Local(ref names, ref exps, _) => {
+                for exp in &exps.base { self.visit_exp(exp); }
+                for (i, name) in names.base.iter().enumerate() {
+                    self.declare(&name.base, true, exps.base.get(i));
+                }
+            }
+            St::Return(ref exps) => {
+                for exp in &exps.base { self.visit_exp(exp); }
+            }
+            St::KailuaAssume(_, ref rename, _, _, _) |
+            St::KailuaAssumeClass(_, ref rename, _, _, _) => {
+                // the renamed variable is a continuation of the original variable
+                if let NameRef::Local(ref id) = rename.base.after {
+                    self.declare(&id.clone().with_loc(rename.span), false, None);
+                }
+            }
+        }
+    }
+
+    fn visit_args(&mut self, args: &'a Args) {
+        match *args {
+            Args::List(ref exps) => for exp in exps { self.visit_exp(exp); },
+            Args::Str(_) => {}
+            Args::Table(ref table) => self.visit_table(table),
+        }
+    }
+
+    fn visit_table(&mut self, table: &'a Table) {
+        for &(ref key, ref value) in &table.items {
+            if let Some(ref key) = *key { self.visit_exp(key); }
+            self.visit_exp(value);
+        }
+    }
+
+    fn visit_exp(&mut self, exp: &'a Spanned<Exp>) {
+        match *exp.base {
+            Ex::Oops | Ex::Nil | Ex::False | Ex::True | Ex::Num(_) | Ex::Int(_) |
+            Ex::Str(_) | Ex::Varargs | Ex::Var(_) => {}
+
+            Ex::Func(ref sig, _, ref block) => self.visit_func(None, sig, block),
+            Ex::Table(ref table) => self.visit_table(table),
+            Ex::Exp(ref e) | Ex::IndexName(ref e, _) | Ex::Un(_, ref e) => self.visit_exp(e),
+            Ex::FuncCall(ref e, ref args) => { self.visit_exp(e); self.visit_args(args); }
+            Ex::MethodCall(ref e, ref args) => {
+                self.visit_exp(&e.base.0);
+                self.visit_args(args);
+            }
+            Ex::Index(ref e1, ref e2) | Ex::Bin(ref e1, _, ref e2) => {
+                self.visit_exp(e1);
+                self.visit_exp(e2);
+            }
+        }
+    }
+}
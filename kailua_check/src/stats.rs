@@ -0,0 +1,164 @@
+//! Type inference statistics per module and per function.
+//!
+//! The checker attributes the growth of `kailua_types::env::TypeStats` counters
+//! to the innermost module or function being checked, so that users can find which code
+//! dominates the inference cost (and probably deserves explicit type annotations).
+//! The elapsed time is attributed to the innermost module in the same way.
+
+use std::time::{Duration, Instant};
+use std::collections::HashMap;
+use kailua_env::{Unit, Pos, Span};
+pub use kailua_types::env::TypeStats;
+
+/// A portion of the checked program to which the inference statistics are attributed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StatsScope {
+    /// The top-level code of a module, excluding functions declared in it.
+    ///
+    /// Built-in libraries and preloaded declarations have a dummy unit.
+    Module(Unit),
+
+    /// The body of a function declared at given span, excluding nested functions.
+    Function(Span),
+}
+
+impl StatsScope {
+    /// Returns a unit (i.e. file) the scope belongs to.
+    pub fn unit(&self) -> Unit {
+        match *self {
+            StatsScope::Module(unit) => unit,
+            StatsScope::Function(span) => span.unit(),
+        }
+    }
+
+    fn sort_key(&self) -> (Unit, Option<Pos>) {
+        match *self {
+            StatsScope::Module(unit) => (unit, None),
+            StatsScope::Function(span) => (span.unit(), Some(span.begin())),
+        }
+    }
+}
+
+/// Inference statistics attributed to a single scope.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StatsEntry {
+    pub scope: StatsScope,
+    pub stats: TypeStats,
+}
+
+/// Collects inference statistics while checking.
+#[derive(Clone, Debug)]
+pub struct StatsCollector {
+    stack: Vec<StatsScope>,
+    last: TypeStats,
+    last_time: Instant,
+    stats: HashMap<StatsScope, TypeStats>,
+    durations: HashMap<Unit, Duration>,
+}
+
+impl StatsCollector {
+    pub fn new() -> StatsCollector {
+        StatsCollector {
+            stack: Vec::new(),
+            last: TypeStats::default(),
+            last_time: Instant::now(),
+            stats: HashMap::new(),
+            durations: HashMap::new(),
+        }
+    }
+
+    // attributes the counters grown and the time elapsed since the last flush
+    // to the current scope. the growth outside of any scope is discarded.
+    fn flush(&mut self, now: TypeStats) {
+        let now_time = Instant::now();
+        if let Some(&scope) = self.stack.last() {
+            let delta = now - self.last;
+            if delta != TypeStats::default() {
+                let stats = self.stats.entry(scope).or_insert_with(TypeStats::default);
+                *stats = *stats + delta;
+            }
+            let duration = self.durations.entry(scope.unit()).or_insert_with(Duration::default);
+            *duration += now_time - self.last_time;
+        }
+        self.last = now;
+        self.last_time = now_time;
+    }
+
+    /// Starts attributing the statistics to given scope until the matching `leave` call.
+    ///
+    /// `now` should be the current counters from `Types::stats`.
+    pub fn enter(&mut self, scope: StatsScope, now: TypeStats) {
+        self.flush(now);
+
+        // functions without a usable span (e.g. built-in ones) cannot be told apart
+        let scope = match scope {
+            StatsScope::Function(span) if span.is_dummy() => StatsScope::Module(span.unit()),
+            scope => scope,
+        };
+        self.stack.push(scope);
+    }
+
+    /// Stops attributing the statistics to the current scope.
+    pub fn leave(&mut self, now: TypeStats) {
+        self.flush(now);
+        self.stack.pop().expect("no matching StatsCollector::enter");
+    }
+
+    /// Returns statistics for each module and function with non-zero counters,
+    /// most costly first. Each entry excludes the statistics of nested functions.
+    pub fn entries(&self) -> Vec<StatsEntry> {
+        let mut entries: Vec<_> = self.stats.iter().map(|(&scope, &stats)| {
+            StatsEntry { scope: scope, stats: stats }
+        }).collect();
+        entries.sort_by(|a, b| {
+            b.stats.total().cmp(&a.stats.total())
+                .then_with(|| a.scope.sort_key().cmp(&b.scope.sort_key()))
+        });
+        entries
+    }
+
+    /// Returns statistics for each module, including all functions declared in it,
+    /// most costly first.
+    pub fn module_totals(&self) -> Vec<(Unit, TypeStats)> {
+        let mut totals = HashMap::new();
+        for (scope, &stats) in &self.stats {
+            let total = totals.entry(scope.unit()).or_insert_with(TypeStats::default);
+            *total = *total + stats;
+        }
+        let mut totals: Vec<_> = totals.into_iter().collect();
+        totals.sort_by(|a, b| b.1.total().cmp(&a.1.total()).then_with(|| a.0.cmp(&b.0)));
+        totals
+    }
+
+    /// Returns the time spent checking each module, including all functions declared in it
+    /// but excluding other modules required by it, most costly first.
+    pub fn module_durations(&self) -> Vec<(Unit, Duration)> {
+        let mut durations: Vec<_> = self.durations.iter().map(|(&u, &d)| (u, d)).collect();
+        durations.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        durations
+    }
+}
+
+#[test]
+fn test_stats_collector() {
+    fn stats(tvars: u64, widenings: u64, failures: u64) -> TypeStats {
+        TypeStats { tvars: tvars, widenings: widenings, failures: failures }
+    }
+
+    let unit = Unit::builtin();
+    let func = Span::builtin();
+    let mut collector = StatsCollector::new();
+    collector.enter(StatsScope::Module(unit), stats(5, 0, 0)); // earlier growth is discarded
+    collector.enter(StatsScope::Function(func), stats(6, 0, 0));
+    collector.leave(stats(10, 1, 2));
+    collector.enter(StatsScope::Function(func), stats(11, 1, 2));
+    collector.leave(stats(12, 1, 2));
+    collector.leave(stats(13, 1, 2));
+
+    assert_eq!(collector.entries(), [
+        StatsEntry { scope: StatsScope::Function(func), stats: stats(5, 1, 2) },
+        StatsEntry { scope: StatsScope::Module(unit), stats: stats(3, 0, 0) },
+    ]);
+    assert_eq!(collector.module_totals(), [(unit, stats(8, 1, 2))]);
+    assert_eq!(collector.module_durations().iter().map(|&(u, _)| u).collect::<Vec<_>>(), [unit]);
+}
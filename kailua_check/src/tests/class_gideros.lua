@@ -800,6 +800,252 @@ local d = D.new('delta') --: D
 
 --! ok
 
+--8<-- gideros-class-overriding-method
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+A = class()
+B = class(A)
+C = class(B)
+
+--v method(x: integer) --> number
+function A:f(x) return x end
+
+-- arguments are contravariant and returns are covariant, `self` is not checked
+--v method(x: number, y: string?) --> integer
+function B:f(x, y) return 42 end
+
+-- grandparent methods are overridden by the nearest one
+--v method(x: number, y: string?, z: boolean?) --> integer
+function C:f(x, y, z) return 54 end
+
+--# assume a: A
+--# assume b: B
+local p = a:f(1) --: number
+local q = b:f(1.5, 'hello') --: integer
+--! ok
+
+--8<-- gideros-class-overriding-method-bad-args
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+A = class()
+B = class(A)
+
+--v method(x: integer, y: string)
+function A:f(x, y) end --@< Note: Previous definition of the field type here
+
+--v method(x: string, y: string)
+function B:f(x, y) end
+--@^ Error: Tried to override a method `f` in a parent class but it cannot accept all arguments to the parent method
+--@ Cause: First method argument `integer` is not a subtype of `string`
+
+--v method(x: integer)
+function A:g(x) end --@< Note: Previous definition of the field type here
+
+--v method(x: integer, y: string)
+function B:g(x, y) end
+--@^ Error: Tried to override a method `g` in a parent class but it cannot accept all arguments to the parent method
+--@ Cause: Third method argument cannot be omitted because its type is `string`
+--! error
+
+--8<-- gideros-class-overriding-method-bad-returns
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+A = class()
+B = class(A)
+
+--v method() --> integer
+function A:f() return 42 end --@< Note: Previous definition of the field type here
+
+--v method() --> number
+function B:f() return 4.2 end
+--@^ Error: Tried to override a method `f` in a parent class but it does not return subtypes of what the parent method returns
+--@ Cause: First return type `number` is not a subtype of `integer`
+--! error
+
+--8<-- gideros-class-overriding-method-by-assume
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+A = class()
+B = class(A)
+
+--# assume static A.f: function(self: A, x: integer) --> number
+--# assume static B.f: function(self: B, x: number) --> integer
+--# assume static A.g: function(self: A, x: integer) --@< Note: Previous definition of the field type here
+--# assume static B.g: function(self: B, x: string)
+--@^ Error: Tried to override a method `g` in a parent class but it cannot accept all arguments to the parent method
+--@ Cause: First method argument `integer` is not a subtype of `string`
+--! error
+
+--8<-- gideros-class-abstract-method
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+A = class()
+B = class(A)
+
+--v method()
+function A:init() end
+
+--v [abstract] method() --> string
+function A:name() return '' end
+
+--v method() --> string
+function A:greet() return 'hello, ' .. self:name() end
+
+--v method() --> string
+function B:name() return 'b' end
+
+local b = B.new() --: B
+local s = b:greet() --: string
+--! ok
+
+--8<-- gideros-class-abstract-method-unimplemented
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+A = class()
+B = class(A)
+C = class(B)
+
+--v method()
+function A:init() end
+
+--v [abstract] method() --> string
+function A:name() return '' end
+
+--v [abstract] method(x: number)
+function A:draw(x) end --@< Note: The abstract field `draw` is declared here
+
+--v method() --> string
+function B:name() return 'b' end
+
+--v method(x: number)
+function C:draw(x) end
+
+local c = C.new() --: C
+local b = B.new() --@< Error: Cannot instantiate `B` with unimplemented abstract fields: `draw`
+--! error
+
+--8<-- gideros-class-abstract-method-reabstracted
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+A = class()
+B = class(A)
+
+--v method()
+function A:init() end
+
+--v method() --> string
+function A:name() return 'a' end
+
+--v [abstract] method() --> string
+function B:name() return '' end --@< Note: The abstract field `name` is declared here
+
+local a = A.new() --: A
+local b = B.new() --@< Error: Cannot instantiate `B` with unimplemented abstract fields: `name`
+--! error
+
+--8<-- gideros-class-abstract-method-by-assume
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+A = class()
+B = class(A)
+
+--# assume static A.init: function(self: A)
+--# assume static A.f: [abstract] function(self: A) --> number
+--@^ Note: The abstract field `f` is declared here
+--# assume static B.f: function(self: B) --> integer
+
+local b = B.new() --: B
+local a = A.new() --@< Error: Cannot instantiate `A` with unimplemented abstract fields: `f`
+--! error
+
+--8<-- gideros-class-deprecated-method
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+A = class()
+
+--v method()
+function A:init() end
+
+--v [deprecated("use `A:draw` instead")] method()
+function A:paint() self:draw() end
+
+--v method()
+function A:draw() end
+
+local a = A.new() --: A
+a:draw()
+a:paint() --@< Warning: The field `paint` is deprecated
+          --@^ Note: use `A:draw` instead
+--! ok
+
+--8<-- gideros-class-metamethod
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+Vec = class()
+
+--v method(x: number)
+function Vec:init(x) self.x = x end
+
+--v method(o: Vec) --> Vec
+function Vec:__add(o) return Vec.new(self.x + o.x) end
+
+--v method() --> Vec
+function Vec:__unm() return Vec.new(-self.x) end
+
+--v method(o: Vec) --> boolean
+function Vec:__lt(o) return self.x < o.x end
+
+--v method(o: Vec) --> boolean
+function Vec:__eq(o) return self.x == o.x end
+
+local a = Vec.new(1)
+local b = a + a --: Vec
+local c = -a --: Vec
+local d = a < b --: boolean
+local e = a > b --: boolean
+local f = a == b --: boolean
+local g = a ~= nil --: boolean
+--! ok
+
+--8<-- gideros-class-metamethod-bad-operand
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+Vec = class()
+
+--v method(x: number)
+function Vec:init(x) self.x = x end
+
+--v method(o: Vec) --> Vec
+function Vec:__add(o) return Vec.new(self.x + o.x) end
+
+local a = Vec.new(1)
+local b = a + 1
+--@^ Error: The type `function(self: Vec, o: Vec) --> Vec` cannot be called
+--@^^ Cause: Second function argument `1` is not a subtype of `Vec`
+--@^^^ Note: The other type originates here
+local c = a * a --@< Error: Cannot apply * operator to `Vec` and `Vec`
+--@^ Cause: `Vec` is not a subtype of `number`
+--@^^ Cause: `Vec` is not a subtype of `number`
+--! error
+
+--8<-- gideros-class-metamethod-call
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+Counter = class()
+
+--v method()
+function Counter:init() self.n = 0 end
+
+--v method(by: integer) --> integer
+function Counter:__call(by)
+    self.n = self.n + by
+    return self.n
+end
+
+local c = Counter.new()
+local n = c(2) --: integer
+--! ok
+
 --8<-- gideros-class-overriding-parent-prototype
 --# class system gideros
 --# assume `class`: [make_class(gideros)] function() --> table
@@ -887,3 +1133,260 @@ a = c
 --# assume global class(gideros) C: A
 --! error
 
+
+--8<-- gideros-generic-make-class
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+--# assume `generic_class`: [make_class(gideros)] function<T>() --> table
+Object = class()
+Queue = generic_class()
+
+--v method(first: T)
+function Queue:init(first)
+    local items = {first} --: vector<T>
+    self.items = items
+end
+
+--v method() --> T
+function Queue:peek()
+    return self.items[1]
+end
+
+local q = Queue.new(42)
+local n = q:peek() + 1 --: integer
+local s = Queue.new('x') --: Queue<string>
+local o = q --: Object
+--! ok
+
+--8<-- gideros-generic-make-class-method-arg
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+--# assume `generic_class`: [make_class(gideros)] function<T>() --> table
+Object = class()
+Queue = generic_class()
+
+--v method(first: T)
+function Queue:init(first)
+end
+
+--v method(item: T)
+function Queue:push(item)
+end
+
+local q = Queue.new(42)
+q:push(54)
+q:push('string')
+--@^ Error: The type `function(self: Queue<integer>, item: integer) --> ()` cannot be called
+--@^^ Cause: First method argument `"string"` is not a subtype of `integer`
+--@^^^ Note: The other type originates here
+--! error
+
+--8<-- gideros-generic-make-class-incompatible
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function() --> table
+--# assume `generic_class`: [make_class(gideros)] function<T>() --> table
+Object = class()
+Queue = generic_class()
+--# assume s: Queue<string>
+local t = s --: Queue<integer>
+--@^ Error: Cannot assign `Queue<string>` into `Queue<integer>`
+--@^^ Note: The other type originates here
+local u = s --: Queue<WHATEVER>
+--! error
+
+--8<-- gideros-generic-assume-class
+--# class system gideros
+--# assume global class(gideros) Object
+--# assume global class(gideros) Box<T>: Object
+--# assume Box.value: T
+--# assume Box.get: method() --> T
+--# assume make_box: function<T>(T) --> Box<T>
+local b = make_box('hello')
+local s = b:get() --: string
+local t = b.value --: string
+local o = b --: Object
+--! ok
+
+--8<-- gideros-generic-assume-class-incompatible
+--# class system gideros
+--# assume global class(gideros) Object
+--# assume global class(gideros) Box<T>: Object
+--# assume b: Box<string>
+local c = b --: Box<integer>
+--@^ Error: Cannot assign `Box<string>` into `Box<integer>`
+--@^^ Note: The other type originates here
+--! error
+
+--8<-- gideros-generic-wrong-num-of-args
+--# class system gideros
+--# assume global class(gideros) Object
+--# assume global class(gideros) Box<T>: Object
+local d --: Box<integer, string> --@< Error: The type `Box` requires 1 type argument(s) but 2 were given
+--! error
+
+--8<-- gideros-generic-non-generic-with-args
+--# class system gideros
+--# assume global class(gideros) Object
+local e --: Object<integer> --@< Error: The type `Object` is not a generic class and cannot have type arguments
+--! error
+
+--8<-- gideros-generic-inherit
+--# class system gideros
+--# assume global class(gideros) Object
+--# assume global class(gideros) Box<T>: Object
+--# assume global class(gideros) Sub: Box --@< Error: The class cannot inherit from a class with type parameters
+--! error
+
+--8<-- gideros-self-type
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function(any) --> table
+A = class()
+
+--v method(x: integer)
+function A:init(x)
+    self.x = x --: integer
+end
+
+--v method(x: integer) --> self
+function A:set(x)
+    self.x = x
+    return self
+end
+
+B = class(A)
+
+--v method() --> integer
+function B:get()
+    return self.x
+end
+
+local a = A.new(1):set(2) --: A
+local b = B.new(1):set(2) --: B
+local x = B.new(1):set(2):set(3):get() --: integer
+local y = b:set(4) --: A
+--! ok
+
+--8<-- gideros-self-type-display
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function(any) --> table
+A = class()
+
+--v method() --> self
+function A:me()
+    return self
+end
+
+local f = A.me --: string
+--@^ Error: Cannot assign `function(self: A) --> self` into `string`
+--@^^ Note: The other type originates here
+--! error
+
+--8<-- gideros-self-type-nilable
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function(any) --> table
+A = class()
+
+--v method() --> self?
+function A:maybe()
+    return nil
+end
+
+B = class(A)
+
+--v method()
+function B:init()
+end
+
+local b = B.new():maybe() --: B
+local c = B.new():maybe() --: B!
+--@^ Error: Cannot assign `B?` into `B!`
+--@^^ Note: The other type originates here
+--! error
+
+--8<-- gideros-self-type-outside-method
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function(any) --> table
+A = class()
+local x --: self --@< Error: Type `self` is not defined
+--! error
+
+--8<-- gideros-self-type-user-defined
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function(any) --> table
+--# type self = string
+A = class()
+
+--v method() --> self
+function A:name()
+    return 'a'
+end
+
+local x = A.name(nil) --: string
+--! ok
+
+--8<-- gideros-implements
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function(any) --> table
+Square = class()
+--# interface Shape = {
+--#     area: method() --> number,
+--#     scale: method(factor: number) --> Square,
+--# }
+
+--v method(side: number)
+function Square:init(side)
+    self.side = side
+end
+
+--v method() --> number
+function Square:area()
+    return self.side * self.side
+end
+
+--v method(factor: number) --> Square
+function Square:scale(factor)
+    return Square.new(self.side * factor)
+end
+
+--# assert Square implements Shape
+--! ok
+
+--8<-- gideros-implements-missing
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function(any) --> table
+--# interface Shape = {
+--#     area: method() --> number,
+--@^ Note: A method `area` (`function() --> number` with the `self` argument) is missing
+--#     name: string, --@< Note: A field `name` (`string`) is missing
+--# }
+Square = class()
+
+--v method(side: number)
+function Square:init(side)
+end
+
+--# assert Square implements Shape --@< Error: `Square` does not implement the interface `Shape`
+--! error
+
+--8<-- gideros-implements-mismatch
+--# class system gideros
+--# assume `class`: [make_class(gideros)] function(any) --> table
+--# interface Shape = {
+--#     area: method() --> number,
+--@^ Note: A field `area` has a type `function(self: Square) --> string` which is not a subtype of `function(self: Square) --> number`
+--#     perimeter: method() --> number,
+--# }
+Square = class()
+
+--v method() --> string
+function Square:area()
+    return 'big'
+end
+
+--v method() --> integer
+function Square:perimeter()
+    return 4
+end
+
+--# assert Square implements Shape --@< Error: `Square` does not implement the interface `Shape`
+--! error
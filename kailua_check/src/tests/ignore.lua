@@ -0,0 +1,150 @@
+-- `--# ignore` directive tests for the Kailua type checker.
+
+--8<-- ignore-next-stmt
+--# ignore
+local x = 'foo' + 1
+local y = 'bar' + 1 --@< Error: Cannot apply + operator to `"bar"` and `1`
+                    --@^ Cause: `"bar"` is not a subtype of `number`
+--! error
+
+--8<-- ignore-notes
+local x --: integer
+--# ignore
+local y = x .. {}
+--! ok
+
+--8<-- ignore-consecutive -- flag:shadowing
+local x = 1
+--# ignore: shadowing
+--# ignore: string-coercion
+local x = '3' + 1
+--! ok
+
+--8<-- ignore-function-decl
+--# ignore
+local function f()
+    local x = 'foo' + 1
+end
+local y = 'bar' + 1 --@< Error: Cannot apply + operator to `"bar"` and `1`
+                    --@^ Cause: `"bar"` is not a subtype of `number`
+--! error
+
+--8<-- ignore-enclosing-function
+local function f()
+    local x = 'foo' + 1
+    --# ignore function
+    local y = 'bar' + 1
+end
+local z = 'baz' + 1 --@< Error: Cannot apply + operator to `"baz"` and `1`
+                    --@^ Cause: `"baz"` is not a subtype of `number`
+--! error
+
+--8<-- ignore-enclosing-function-expr
+local f = function()
+    --# ignore function
+    return 'foo' + 1
+end
+local z = 'baz' + 1 --@< Error: Cannot apply + operator to `"baz"` and `1`
+                    --@^ Cause: `"baz"` is not a subtype of `number`
+--! error
+
+--8<-- ignore-chunk
+--# ignore function
+local x = 'foo' + 1
+local y = 'bar' + 1
+--! ok
+
+--8<-- ignore-code -- flag:shadowing
+local x = 1
+--# ignore: shadowing
+local x = 2
+local x = 3 --@< Warning: Local variable `x` shadows a local variable of the same name
+            --@^^ Note: The shadowed variable was defined here
+--! ok
+
+--8<-- ignore-code-not-matching -- flag:shadowing
+local x = 1
+--# ignore: float-for-step
+local x = 2 --@< Warning: Local variable `x` shadows a local variable of the same name
+            --@^^^ Note: The shadowed variable was defined here
+--@^^^ Warning: This `--# ignore` directive did not suppress any report
+--! ok
+
+--8<-- ignore-code-other-reports
+--# ignore: shadowing
+local x = 'foo' + 1 --@< Error: Cannot apply + operator to `"foo"` and `1`
+                    --@^ Cause: `"foo"` is not a subtype of `number`
+--@^^^ Warning: This `--# ignore` directive did not suppress any report
+--! error
+
+--8<-- ignore-code-multiple -- flag:shadowing flag:float-for-step
+local i = 1
+--# ignore function: shadowing, float-for-step
+for i = 0, 1, 0.5 do
+end
+--! ok
+
+--8<-- ignore-code-lint-notes -- flag:deprecated-api
+--# assume global table: { getn: function(table) --> integer }
+--# ignore: deprecated-api
+local n = table.getn({})
+--! ok
+
+--8<-- ignore-unknown-code
+--# ignore: no-such-lint --@< Warning: Unknown lint `no-such-lint` in the `--# ignore` directive
+local x = 1
+--@^^ Warning: This `--# ignore` directive did not suppress any report
+--! ok
+
+--8<-- ignore-unused
+--# ignore
+local x = 1
+--@^^ Warning: This `--# ignore` directive did not suppress any report
+--! ok
+
+--8<-- ignore-unused-last
+local x = 1
+--# ignore --@< Warning: This `--# ignore` directive did not suppress any report
+--! ok
+
+--8<-- ignore-in-module
+--# ignore
+local x = require 'a' + 1
+local y = 'bar' + 1 --@< Error: Cannot apply + operator to `"bar"` and `1`
+                    --@^ Cause: `"bar"` is not a subtype of `number`
+
+--& a
+--# ignore
+local x = 'foo' + 1
+return 42
+--! error
+
+--8<-- ignore-diagnostic-code
+--# ignore: KC0019
+local x = 'foo' + 1
+--! ok
+
+--8<-- ignore-diagnostic-code-not-matching
+--# ignore: KC0001
+local x = 'foo' + 1 --@< Error: Cannot apply + operator to `"foo"` and `1`
+                    --@^ Cause: `"foo"` is not a subtype of `number`
+--@^^^ Warning: This `--# ignore` directive did not suppress any report
+--! error
+
+--8<-- ignore-unknown-diagnostic-code
+--# ignore: KC01 --@< Warning: Unknown lint `KC01` in the `--# ignore` directive
+local x = 1
+--@^^ Warning: This `--# ignore` directive did not suppress any report
+--! ok
+
+--8<-- code-severity-allow -- flag:KC0019=allow
+local x = 'foo' + 1
+--! ok
+
+--8<-- code-severity-warn -- flag:KC0001=warn
+local x = y --@< Warning: Global or local variable `y` is not defined
+--! ok
+
+--8<-- code-severity-error -- flag:KC0018=error
+local x = '3' + 1 --@< Error: The string "3" is implicitly converted to a number by the `+` operator
+--! error
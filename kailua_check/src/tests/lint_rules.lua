@@ -0,0 +1,118 @@
+-- Opt-in lint rule tests for the Kailua type checker.
+
+--8<-- deprecated-api-lua51 -- flag:deprecated-api
+--# assume global table: { getn: function(table) --> integer }
+--# assume global unpack: function(table) --> (any, any)
+local t = {1, 2, 3}
+local n = table.getn(t) --@< Warning: `table.getn` is deprecated since Lua 5.1
+                        --@^ Note: Use `#t` instead
+local a, b = unpack(t)
+--! ok
+
+--8<-- deprecated-api-lua52 -- flag:deprecated-api flag:lua52
+--# open lua51
+local t = {1, 2, 3}
+local a, b = unpack(t) --@< Warning: `unpack` is deprecated since Lua 5.2
+                       --@^ Note: Use `table.unpack` instead
+local f = loadstring --@< Warning: `loadstring` is deprecated since Lua 5.2
+                     --@^ Note: Use `load` instead
+--! ok
+
+--8<-- deprecated-api-library -- flag:deprecated-api flag:lua53
+--# assume global bit32: { band: function(integer, integer) --> integer }
+local x = bit32.band(3, 5) --@< Warning: `bit32` is deprecated since Lua 5.3
+--! ok
+
+--8<-- deprecated-api-local -- flag:deprecated-api flag:lua52
+--# assume unpack: function(table) --> any
+local x = unpack({})
+--! ok
+
+--8<-- deprecated-api-without-flag -- flag:lua52
+--# open lua51
+local a, b = unpack({1, 2})
+--! ok
+
+--8<-- mixed-type-equality -- flag:mixed-type-equality
+local x = 42
+local y = '42'
+local z = true
+local a = x == y --@< Warning: The == operator compares values of different types `integer` and `string`
+local b = z ~= x --@< Warning: The ~= operator compares values of different types `boolean` and `integer`
+local c = x == 54
+local d = y == 'x'
+local e = x == nil
+local f = 3.5 == x
+--! ok
+
+--8<-- mixed-type-equality-nil -- flag:mixed-type-equality
+local x --: integer?
+local y --: string?
+local a = x == y --@< Warning: The == operator compares values of different types `integer?` and `string?`
+--! ok
+
+--8<-- mixed-type-equality-union -- flag:mixed-type-equality
+local x --: integer|string
+local y --: string
+local t = {}
+local a = x == y
+local b = x == t
+local c = t == y
+--! ok
+
+--8<-- mixed-type-equality-without-flag
+local a = 42 == '42'
+--! ok
+
+--8<-- empty-block -- flag:empty-block
+local x = 1
+do end --@< Warning: This block is empty
+while x < 1 do end --@< Warning: This block is empty
+for i = 1, 3 do end --@< Warning: This block is empty
+repeat until x > 0 --@< Warning: This block is empty
+--! ok
+
+--8<-- empty-block-if -- flag:empty-block
+local x = 1
+if x > 1 then --@< Warning: This block is empty
+elseif x > 0 then
+    x = 2
+else end --@< Warning: This block is empty
+--! ok
+
+--8<-- empty-block-func -- flag:empty-block
+local function f() end
+local g = function() end
+do
+    local h = function() end
+end
+--! ok
+
+--8<-- empty-block-without-flag
+do end
+--! ok
+
+--8<-- float-for-step -- flag:float-for-step
+for i = 0, 1, 0.1 do --@< Warning: The step of this numeric `for` loop has a non-integral type `number`, so the number of iterations may depend on rounding errors
+end
+for i = 0, 1, 1 do
+end
+for i = 0, 1 do
+end
+for i = 0.5, 1.5 do
+end
+--! ok
+
+--8<-- float-for-step-var -- flag:float-for-step
+local step --: number
+local istep --: integer
+for i = 1, 10, step do --@< Warning: The step of this numeric `for` loop has a non-integral type `number`, so the number of iterations may depend on rounding errors
+end
+for i = 1, 10, istep do
+end
+--! ok
+
+--8<-- float-for-step-without-flag
+for i = 0, 1, 0.1 do
+end
+--! ok
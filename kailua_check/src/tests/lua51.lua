@@ -118,6 +118,87 @@ assert_type(p, 'integer')
 print(p + 5)
 --! ok
 
+--8<-- lua51-type-narrowing-function
+--# open lua51
+local x = 'a' --: string | (function() --> integer)
+if type(x) == 'function' then
+    local a = x() --: integer
+else
+    local b = x --: string
+end
+local c = x --: string | (function() --> integer)
+--! ok
+
+--8<-- lua51-type-narrowing-boolean-negated
+--# open lua51
+local x = true --: boolean | string
+if type(x) ~= 'boolean' then
+    local a = x --: string
+else
+    local b = x --: boolean
+end
+--! ok
+
+--8<-- lua51-type-narrowing-thread-userdata
+--# open lua51
+--# assume x: thread | userdata | string
+if type(x) == 'thread' then
+    local a = x --: thread
+elseif type(x) == 'userdata' then
+    local b = x --: userdata
+else
+    local c = x --: string
+end
+--! ok
+
+--8<-- lua51-type-narrowing-field
+--# open lua51
+local t = { v = 1 } --: {v: integer | string}
+if type(t.v) == 'string' then
+    local a = t.v --: string
+end
+--! ok
+
+--8<-- lua51-type-narrowing-not-type
+--# open lua51
+local function type(x) --: any
+    return 'string'
+end
+local x = 42 --: integer | string
+if type(x) == 'string' then
+    local a = x --: string
+    --@^ Error: Cannot assign `(integer|string)` into `string`
+    --@^^ Note: The other type originates here
+end
+--! error
+
+--8<-- lua51-assert-type-function
+--# open lua51
+--# assume assert_type: const [assert_type] function(any, string)
+local x = 'a' --: string | (function() --> integer)
+assert_type(x, 'function')
+local a = x() --: integer
+--! ok
+
+--8<-- lua51-assert-type-boolean-thread
+--# open lua51
+--# assume assert_type: const [assert_type] function(any, string)
+--# assume x: boolean | string
+--# assume y: thread | integer
+assert_type(x, 'boolean')
+assert_type(y, 'thread?')
+local a = x --: boolean
+local b = y --: thread?
+--! ok
+
+--8<-- lua51-assert-type-narrowing-by-type
+--# open lua51
+--# assume assert: const [assert] function(any)
+--# assume x: userdata | string
+assert(type(x) ~= 'string')
+local a = x --: userdata
+--! ok
+
 --8<-- lua51-ipairs-integer-array
 --# open lua51
 --# assume p: vector<integer>
@@ -414,9 +495,57 @@ local m = table.maxn(y) --: integer
 
 --8<-- lua51-index-genv
 --# open lua51
-local x = _G.x --@< Error: Cannot index `[genv] table` without further type information; specify more detailed type, or use `--# assume` as a last resort
+local x = _G.x --@< Error: Global or local variable `x` is not defined
+--! error
+
+--8<-- lua51-index-genv-dynamic
+--# open lua51
+local k = 'x'
+local x = _G[k] --@< Error: Cannot index `[genv] table` without further type information; specify more detailed type, or use `--# assume` as a last resort
 --! error
 
+--8<-- lua51-genv-read
+--# open lua51
+x = 42
+local a = _G.x --: integer
+local b = _G['x'] --: integer
+local c = _G.x --: string --@< Error: Cannot assign `42` into `string`
+                          --@^ Note: The other type originates here
+--! error
+
+--8<-- lua51-genv-write
+--# open lua51
+_G.x = 42
+_G['y'] = 'foo'
+local a = x --: integer
+local b = y --: string
+--! ok
+
+--8<-- lua51-genv-write-type-mismatch
+--# open lua51
+x = 42 --: integer
+_G.x = 'foo' --@< Error: Cannot assign `"foo"` into `integer`
+             --@^ Note: The other type originates here
+--! error
+
+--8<-- lua51-genv-write-narrowed
+--# open lua51
+--# assume global x: integer?
+if x then
+    _G.x = nil
+    local y = x + 1 --@< Error: Cannot apply + operator to `integer?` and `1`
+                    --@^ Cause: `integer?` is not a subtype of `number`
+end
+--! error
+
+--8<-- lua51-genv-local-shadowing
+--# open lua51
+x = 42
+local x = 'foo'
+local a = _G.x --: integer
+local b = x --: string
+--! ok
+
 --8<-- lua51-assert-class-instance
 --# open lua51
 --# assume global class Hello
@@ -444,3 +573,624 @@ print(42) --@< Warning: This code will never execute
 math.randomseed(os.time())
 --! ok
 
+
+--8<-- lua51-setmetatable-index
+--# open lua51
+local Base = { greeting = 'hello' }
+local obj = setmetatable({ x = 42 }, { __index = Base })
+local x = obj.x --: integer
+local g = obj.greeting --: string
+--! ok
+
+--8<-- lua51-setmetatable-index-missing
+--# open lua51
+local obj = setmetatable({ x = 42 }, { __index = { y = 'foo' } })
+local z = obj.z --@< Error: Missing key "z" in `{x: 42, ...}`
+--! error
+
+--8<-- lua51-setmetatable-index-later-fields
+--# open lua51
+local Point = {}
+Point.__index = Point
+
+function Point.new(x, --: number
+                   y) --: number
+    return setmetatable({ x = x, y = y }, Point)
+end
+
+--v function(self: {x: number, y: number}) --> number
+function Point.sum(self)
+    return self.x + self.y
+end
+
+local p = Point.new(3, 4)
+local s = p.sum(p) --: number
+--! ok
+
+--8<-- lua51-setmetatable-index-chain
+--# open lua51
+local A = { a = 1 }
+local B = setmetatable({ b = 'b' }, { __index = A })
+local obj = setmetatable({}, { __index = B })
+local a = obj.a --: integer
+local b = obj.b --: string
+--! ok
+
+--8<-- lua51-setmetatable-index-cycle
+--# open lua51
+local T = {}
+T.__index = T
+setmetatable(T, T)
+local x = T.x --@< Error: Missing key "x" in `{__index: <variable T>, ...}`
+--! error
+
+--8<-- lua51-setmetatable-own-fields-first
+--# open lua51
+local obj = setmetatable({ x = 42 }, { __index = { x = 'shadowed' } })
+local x = obj.x --: integer
+--! ok
+
+--8<-- lua51-setmetatable-clear
+--# open lua51
+local obj = setmetatable({}, { __index = { y = 'foo' } })
+setmetatable(obj, nil)
+local y = obj.y --@< Error: Missing key "y" in `{...}`
+--! error
+
+--8<-- lua51-setmetatable-non-record
+--# open lua51
+--# assume t: map<string, integer>
+local u = setmetatable(t, {}) --: table
+--! ok
+
+--8<-- lua51-setmetatable-less-args
+--# open lua51
+local obj = setmetatable({}) --@< Error: `setmetatable` needs at least 2 argument(s)
+--! error
+
+--8<-- lua51-setmetatable-index-function
+--# open lua51
+--v function(t: table, k: string) --> integer
+local function getter(t, k) return #k end
+
+local obj = setmetatable({ name = 'box' }, { __index = getter })
+local n = obj.name --: string
+local w = obj.width --: integer
+local key = 'x' --: string
+local v = obj[key] --: integer
+--! ok
+
+--8<-- lua51-setmetatable-index-function-chain
+--# open lua51
+--v function(t: table, k: string) --> string
+local function getter(t, k) return k end
+
+local Base = setmetatable({ x = 42 }, { __index = getter })
+local obj = setmetatable({}, { __index = Base })
+local x = obj.x --: integer
+local y = obj.y --: string
+--! ok
+
+--8<-- lua51-setmetatable-index-function-literal-keys
+--# open lua51
+--v function(t: table, k: 'width' | 'height') --> number
+local function getter(t, k) return 0 end
+
+local obj = setmetatable({}, { __index = getter })
+local w = obj.width --: number
+local h = obj.height --: number
+local d = obj.depth
+--@^ Error: The type `function(t: table, k: ("height"|"width")) --> number` cannot be called
+--@^^ Cause: Second function argument `"depth"` is not a subtype of `("height"|"width")`
+--@^^^ Note: The other type originates here
+--! error
+
+--8<-- lua51-setmetatable-index-function-lval
+--# open lua51
+--v function(t: table, k: string) --> integer
+local function getter(t, k) return 0 end
+
+local obj = setmetatable({}, { __index = getter })
+local key = 'x' --: string
+obj[key] = 42 --@< Error: Cannot index `{...}` with `string`
+--! error
+
+--8<-- lua51-setmetatable-call
+--# open lua51
+local mt = {}
+--v function(self: table, x: integer) --> string
+function mt.__call(self, x) return 'called' end
+
+local mod = setmetatable({ version = 1 }, mt)
+local s = mod(42) --: string
+local v = mod.version --: integer
+--! ok
+
+--8<-- lua51-setmetatable-call-bad-args
+--# open lua51
+local mt = {}
+--v function(self: table, x: integer) --> string
+function mt.__call(self, x) return 'called' end
+
+local mod = setmetatable({}, mt)
+local s = mod('x')
+--@^ Error: The type `function(self: table, x: integer) --> string` cannot be called
+--@^^ Cause: First method argument `"x"` is not a subtype of `integer`
+--@^^^ Note: The other type originates here
+--! error
+
+--8<-- lua51-setmetatable-call-missing
+--# open lua51
+local mod = setmetatable({}, {})
+mod() --@< Error: Tried to call a non-function `{...}`
+--! error
+
+--8<-- lua51-setmetatable-arith-metamethod
+--# open lua51
+local mt = {}
+--v function(a: {x: number}, b: {x: number}) --> {x: number}
+function mt.__add(a, b) return { x = a.x + b.x } end
+--v function(a: {x: number}, b: {x: number}) --> string
+function mt.__concat(a, b) return 'x' end
+
+local v = setmetatable({ x = 1 }, mt)
+local w = v + v --: {x: number}
+local s = v .. v --: string
+--! ok
+
+--8<-- lua51-setmetatable-arith-metamethod-bad-operand
+--# open lua51
+local mt = {}
+--v function(a: {x: number}, b: {x: number}) --> {x: number}
+function mt.__add(a, b) return { x = a.x + b.x } end
+
+local v = setmetatable({ x = 1 }, mt)
+local w = v + 'x'
+--@^ Error: The type `function(a: {x: number}, b: {x: number}) --> {x: number}` cannot be called
+--@^^ Cause: Second function argument `"x"` is not a subtype of `{x: number}`
+--@^^^ Note: The other type originates here
+--! error
+
+--8<-- lua51-setmetatable-len-metamethod
+--# open lua51
+local mt = {}
+--v function(t: {n: integer}) --> string
+function mt.__len(t) return 'length' end
+
+-- Lua 5.1 ignores `__len` for tables
+local v = setmetatable({ n = 1 }, mt)
+local n = #v --: integer
+--! ok
+
+--8<-- lua51-setmetatable-lt-metamethod-one-operand
+--# open lua51
+local mt = {}
+--v function(a: {x: number}, b: number) --> boolean
+function mt.__lt(a, b) return a.x < b end
+
+-- Lua 5.1 requires both operands to have the metamethod
+local v = setmetatable({ x = 1 }, mt)
+local b = v < 2 --@< Error: Cannot apply < operator to `{x: 1, ...}` and `2`
+--! error
+
+--8<-- lua51-setmetatable-arith-no-metamethod
+--# open lua51
+local v = setmetatable({ x = 1 }, {})
+local w = v * 2 --@< Error: Cannot apply * operator to `{x: 1, ...}` and `2`
+--@^ Cause: `{x: 1, ...}` is not a subtype of `number`
+--! error
+
+--8<-- lua51-getmetatable
+--# open lua51
+local mt = { __index = { y = 'foo' }, tag = 42 }
+local obj = setmetatable({}, mt)
+local tag = getmetatable(obj).tag --: integer
+--! ok
+
+--8<-- lua51-getmetatable-untracked
+--# open lua51
+local obj = {}
+local mt = getmetatable(obj) --: table
+--! ok
+
+--8<-- lua51-select-varargs
+--# open lua51
+--v function(fmt: string, ...: number) --> string
+local function f(fmt, ...)
+    local a, b = select(2, ...)
+    local c = a --: number
+    local d = b --: string
+    --@^ Error: Cannot assign `number` into `string`
+    --@^^ Note: The other type originates here
+    return fmt
+end
+--! error
+
+--8<-- lua51-select-count
+--# open lua51
+--v function(...: string)
+local function f(...)
+    local n = select('#', ...) --: integer
+    local m = select('#') --: integer
+end
+--! ok
+
+--8<-- lua51-select-args
+--# open lua51
+local a, b, c = select(2, 'x', 42, true)
+local p = a --: integer
+local q = b --: boolean
+local r = c --: nil
+--! ok
+
+--8<-- lua51-select-non-literal
+--# open lua51
+--# assume n: integer
+--v function(...: number)
+local function f(...)
+    local a = select(n, ...) --: any
+end
+--! ok
+
+--8<-- lua51-select-less-args
+--# open lua51
+local a = select() --@< Error: `select` needs at least 1 argument(s)
+--! error
+
+--8<-- lua51-coroutine-create
+--# open lua51
+--v function(n: integer) --> string
+local function producer(n)
+    for i = 1, n do
+        coroutine.yield(i)
+    end
+    return 'done'
+end
+local co = coroutine.create(producer) --: thread<integer|string, integer>
+local ok, v = coroutine.resume(co, 3)
+local b = ok --: boolean
+local w = v --: integer|string
+--! ok
+
+--8<-- lua51-coroutine-resume-bad-arg
+--# open lua51
+local co = coroutine.create(function(s) --: string
+    coroutine.yield(#s)
+end)
+local ok, v = coroutine.resume(co, 42)
+--@^ Error: The type `function(thread<integer, string>, string, any...) --> (boolean, integer)` cannot be called
+--@^^ Cause: Second function argument `42` is not a subtype of `string`
+--@^^^ Note: The other type originates here
+--! error
+
+--8<-- lua51-coroutine-resume-yields
+--# open lua51
+local co = coroutine.create(function()
+    coroutine.yield(42)
+end)
+local ok, v = coroutine.resume(co)
+local s = v --: string
+--@^ Error: Cannot assign `integer` into `string`
+--@^^ Note: The other type originates here
+--! error
+
+--8<-- lua51-coroutine-wrap
+--# open lua51
+local gen = coroutine.wrap(function(prefix) --: string
+    coroutine.yield(prefix .. 'a')
+    coroutine.yield(prefix .. 'b')
+end)
+local f = gen --: function(string) --> string
+--! ok
+
+--8<-- lua51-coroutine-thread-subtype
+--# open lua51
+local co = coroutine.create(function() coroutine.yield(1) end)
+local t = co --: thread
+local u = t --: thread<integer, any?>
+--@^ Error: Cannot assign `thread` into `thread<integer, any?>`
+--@^^ Note: The other type originates here
+--! error
+
+--8<-- lua51-coroutine-variance
+--# assume co: thread<integer, number>
+local a = co --: thread<number, integer>
+local b = co --: thread<integer, string>
+--@^ Error: Cannot assign `thread<integer, number>` into `thread<integer, string>`
+--@^^ Note: The other type originates here
+--! error
+
+--8<-- lua51-coroutine-unknown-body
+--# open lua51
+--# assume f: function(integer) --> string
+local co = coroutine.create(f) --: thread
+local ok, v = coroutine.resume(co, 'anything')
+--! ok
+
+--8<-- lua51-pcall-narrowing
+--# open lua51
+--v function(x: integer) --> {a: integer}
+local function f(x) return {a = x} end
+local ok, res = pcall(f, 3)
+if ok then
+    local a = res.a --: integer
+else
+    local msg = res --: string
+end
+--! ok
+
+--8<-- lua51-pcall-without-check
+--# open lua51
+--v function() --> {a: integer}
+local function f() return {a = 1} end
+local ok, res = pcall(f)
+local a = res.a --@< Error: `(string|{a: integer})` type that is being indexed should be either a table or a string but not both
+--! error
+
+--8<-- lua51-pcall-early-exit
+--# open lua51
+--v function() --> (integer, string)
+local function f() return 1, 'x' end
+--v function()
+local function g()
+    local ok, n, s = pcall(f)
+    if not ok then return end
+    local m = n + 1 --: integer
+    local t = s .. '!' --: string
+end
+--! ok
+
+--8<-- lua51-pcall-assert
+--# open lua51
+--# assume assert: const [assert] function(any)
+--v function() --> integer
+local function f() return 42 end
+local ok, n = pcall(f)
+assert(ok)
+local m = n --: integer
+--! ok
+
+--8<-- lua51-pcall-bad-args
+--# open lua51
+--v function(x: integer)
+local function f(x) end
+local ok = pcall(f, 'x')
+--@^ Error: The type `function(x: integer) --> ()` cannot be called
+--@^^ Cause: First function argument `"x"` is not a subtype of `integer`
+--@^^^ Note: The other type originates here
+--! error
+
+--8<-- lua51-pcall-reassigned
+--# open lua51
+--v function() --> integer
+local function f() return 42 end
+local ok, n = pcall(f)
+ok = true
+if ok then
+    local m = n --: integer
+    --@^ Error: Cannot assign `(integer|string)` into `integer`
+    --@^^ Note: The other type originates here
+end
+--! error
+
+--8<-- lua51-pcall-any-function
+--# open lua51
+--# assume f: function
+local ok, res = pcall(f)
+local x = res --: any
+--! ok
+
+--8<-- lua51-xpcall
+--# open lua51
+--v function() --> string
+local function f() return 'ok' end
+--v function(msg: string) --> integer
+local function handler(msg) return #msg end
+local ok, res = xpcall(f, handler)
+if ok then
+    local s = res --: string
+else
+    local n = res --: integer
+end
+--! ok
+
+--8<-- lua51-weak-table-index
+--# open lua51
+--# type Data = {n: integer}
+local cache = setmetatable({}, {__mode = 'k'}) --: map<table, Data>
+local obj = {}
+cache[obj] = {n = 1}
+local n = cache[obj].n
+--@^ Error: Tried to index a non-table type `Data?`
+--! error
+
+--8<-- lua51-weak-table-nil-check
+--# open lua51
+--# type Data = {n: integer}
+local cache = setmetatable({}, {__mode = 'k'}) --: map<table, Data>
+local obj = {}
+local data = cache[obj]
+if data then
+    local n = data.n + 1 --: integer
+end
+--! ok
+
+--8<-- lua51-weak-table-statement
+--# open lua51
+local t = {} --: map<string, integer>
+setmetatable(t, {__mode = 'v'})
+local x = t.a + 1
+--@^ Error: Cannot apply + operator to `integer?` and `1`
+--@^^ Cause: `integer?` is not a subtype of `number`
+--! error
+
+--8<-- lua51-strong-table
+--# open lua51
+local t = {} --: map<string, integer>
+setmetatable(t, {__mode = 'x'})
+local x = t.a + 1 --: integer
+--! ok
+
+--8<-- lua51-weak-table-attr
+--# assume t: [weak] map<string, integer>
+local x = t.a + 1
+--@^ Error: Cannot apply + operator to `integer?` and `1`
+--@^^ Cause: `integer?` is not a subtype of `number`
+--! error
+
+--8<-- lua51-weak-table-return
+--# open lua51
+--# type Data = {n: integer}
+local cache = setmetatable({}, {__mode = 'k'}) --: map<table, Data>
+--v function(obj: table) --> Data
+local function get(obj)
+    return cache[obj]
+    --@^ Warning: A value from a weak table can be collected at any time and should be checked for `nil` before being returned
+end
+--v function(obj: table) --> Data?
+local function find(obj)
+    return cache[obj]
+end
+--v function(obj: table) --> Data
+local function fetch(obj)
+    local data = cache[obj]
+    assert(data)
+    return data
+end
+--! ok
+
+--8<-- lua51-io-open-nodiscard
+--# open lua51
+local f = io.open('foo.txt')
+io.open('foo.txt') --@< Warning: The return value of this function should not be discarded
+io.popen('ls') --@< Warning: The return value of this function should not be discarded
+io.write('hello')
+--! ok
+
+--8<-- lua51-geval-dofile
+--# open lua51
+x = 42
+dofile('foo.lua')
+local a = x --: string --@< Note: Global variable `x` and subsequent global variables are no longer typed as the global environment may have been altered
+--@^^ Note: This call may alter the global environment
+local b = x --: integer
+local c = x + 1 --: integer
+--! ok
+
+--8<-- lua51-geval-loadstring
+--# open lua51
+x = 42
+local f = loadstring('x = "foo"')
+local a = x --: string --@< Note: Global variable `x` and subsequent global variables are no longer typed as the global environment may have been altered
+--@^^ Note: This call may alter the global environment
+--! ok
+
+--8<-- lua51-geval-locals
+--# open lua51
+local x = 42
+dofile('foo.lua')
+local a = x --: string --@< Error: Cannot assign `integer` into `string`
+                       --@^ Note: The other type originates here
+--! error
+
+--8<-- lua51-geval-assumed-globals
+--# open lua51
+--# assume global x: integer
+dofile('foo.lua')
+local a = x --: integer
+local b = x --: string --@< Error: Cannot assign `integer` into `string`
+                       --@^ Note: The other type originates here
+print(a)
+--! error
+
+--8<-- lua51-geval-reasserted
+--# open lua51
+x = 42 --: integer
+dofile('foo.lua')
+x = 54
+local a = x --: string --@< Error: Cannot assign `integer` into `string`
+                       --@^ Note: The other type originates here
+--! error
+
+--8<-- lua51-geval-genv
+--# open lua51
+x = 42
+dofile('foo.lua')
+local a = _G.x --: string --@< Note: Global variable `x` and subsequent global variables are no longer typed as the global environment may have been altered
+--@^^ Note: This call may alter the global environment
+--! ok
+
+--8<-- lua51-module
+--# open lua51
+local a = require 'a'
+local b = a.greet('world') --: string
+local c = a.count --: integer
+local d = a.missing --@< Error: Missing key "missing" in `{count: integer, greet: function(name: string) --> string, ...}`
+
+--& a
+--# open lua51
+module('a', package.seeall)
+count = 42
+--v function(name: string) --> string
+function greet(name)
+    return 'hello ' .. name .. tostring(count)
+end
+
+--! error
+
+--8<-- lua51-module-no-global-leak
+--# open lua51
+require 'a'
+local x = count --@< Error: Global or local variable `count` is not defined
+
+--& a
+--# open lua51
+module('a', package.seeall)
+count = 42
+
+--! error
+
+--8<-- lua51-module-no-seeall
+--# open lua51
+require 'a'
+
+--& a
+--# open lua51
+module('a')
+count = 42
+local x = count --: integer
+print(x) --@< Error: Global or local variable `print` is not defined
+
+--! error
+
+--8<-- lua51-module-genv
+--# open lua51
+require 'a'
+local y = foo --: string
+
+--& a
+--# open lua51
+module('a', package.seeall)
+_G.foo = 'bar'
+local x = _G.foo --: string
+
+--! ok
+
+--8<-- lua51-module-explicit-return
+--# open lua51
+local a = require 'a' --: integer
+
+--& a
+--# open lua51
+module('a', package.seeall)
+count = 42
+return count
+
+--! ok
+
+--8<-- lua51-module-not-top-level
+--# open lua51
+local function f()
+    module('a') --@< Warning: `module` is only supported at the top level of the file and this call is ignored
+end
+--! ok
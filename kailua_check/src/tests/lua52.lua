@@ -0,0 +1,128 @@
+-- Tests specific to Lua 5.2 library support in the Kailua type checker.
+
+--8<-- lua52-print -- flag:lua52
+--# open lua52
+print('hello')
+--! ok
+
+--8<-- lua52-table-pack-unpack -- flag:lua52
+--# open lua52
+local t = table.pack(1, 2, 3)
+local a, b = table.unpack({1, 2, 3}, 1, 2)
+print(#t, rawlen(t), a, b)
+--! ok
+
+--8<-- lua52-no-unpack -- flag:lua52
+--# open lua52
+local a = unpack({1, 2, 3}) --@< Error: Global or local variable `unpack` is not defined
+--! error
+
+--8<-- lua52-no-setfenv -- flag:lua52
+--# open lua52
+setfenv(1, {}) --@< Error: Global or local variable `setfenv` is not defined
+--! error
+
+--8<-- lua52-bit32 -- flag:lua52
+--# open lua52
+local x = bit32.band(0xff, bit32.lshift(1, 4)) --: integer
+local y = bit32.extract(x, 2, 3) --: integer
+local z = bit32.btest(x, y) --: boolean
+--! ok
+
+--8<-- lua52-bit32-number -- flag:lua52
+--# open lua52
+local x = bit32.bnot(1.5)
+--@^ Error: The type `function(x: integer) --> integer` cannot be called
+--@^^ Cause: First function argument `number` is not a subtype of `integer`
+--@^^^ Note: The other type originates here
+--! error
+
+--8<-- lua52-len-metamethod -- flag:lua52
+--# open lua52
+local mt = {}
+--v function(t: {n: integer}) --> string
+function mt.__len(t) return 'length' end
+
+local v = setmetatable({ n = 1 }, mt)
+local n = #v --: string
+--! ok
+
+--8<-- lua52-lt-metamethod-either-operand -- flag:lua52
+--# open lua52
+local mt = {}
+--v function(a: {x: number}, b: number) --> boolean
+function mt.__lt(a, b) return a.x < b end
+
+local v = setmetatable({ x = 1 }, mt)
+local b = v < 2 --: boolean
+local c = 2 > v --: boolean
+local d = 2 < v
+--@^ Error: The type `function(a: {x: number}, b: number) --> boolean` cannot be called
+--@^^ Cause: First function argument `2` is not a subtype of `{x: number}`
+--@^^^ Note: The other type originates here
+--! error
+
+--8<-- lua52-load-env -- flag:lua52
+--# open lua52
+local f = load('return 42', 'chunk', 't', {})
+local g = loadfile('a.lua', 'bt')
+--! ok
+
+--8<-- lua52-xpcall-args -- flag:lua52
+--# open lua52
+local ok, v = xpcall(function(x) --: integer
+    return x + 1
+end, function(e) --: any
+    return e
+end, 41)
+--! ok
+
+--8<-- lua52-coroutine-running -- flag:lua52
+--# open lua52
+local co, main = coroutine.running()
+local b = main --: boolean
+--! ok
+
+--8<-- lua52-string-rep-sep -- flag:lua52
+--# open lua52
+local s = string.rep('a', 3, ', ') --: string
+--! ok
+
+--8<-- lua52-math-log-base -- flag:lua52
+--# open lua52
+local x = math.log(8, 2) --: number
+--! ok
+
+--8<-- lua52-package-searchers -- flag:lua52
+--# open lua52
+local path = package.searchpath('foo', package.path)
+local n = #package.searchers
+--! ok
+
+--8<-- lua52-debug-uservalue -- flag:lua52
+--# open lua52
+local v = debug.getuservalue(io.stdout)
+--! ok
+
+--8<-- lua-version-neutral-52 -- flag:lua52
+--# open lua
+local t = table.pack(1, 2, 3)
+local x = bit32.bor(1, 2) --: integer
+--! ok
+
+--8<-- lua-version-neutral-53 -- flag:lua53
+--# open lua
+local t = table.pack(1, 2, 3)
+--! ok
+
+--8<-- lua-version-neutral-51
+--# open lua
+local a = unpack({1, 2, 3})
+local x = bit32.bor(1, 2) --@< Error: Global or local variable `bit32` is not defined
+--! error
+
+--8<-- lua52-duplicate-open -- flag:lua52
+--# open lua52
+--# open lua
+print('hello')
+--! ok
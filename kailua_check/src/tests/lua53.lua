@@ -0,0 +1,145 @@
+-- Tests specific to Lua 5.3 and later semantics in the Kailua type checker.
+
+--8<-- lua53-integer-literal -- flag:lua53
+local x = 42 --: integer
+local y = 9223372036854775807 --: integer
+--! ok
+
+--8<-- lua53-integer-literal-overflow -- flag:lua53
+local x = 9223372036854775808 --: integer
+--@^ Error: Cannot assign `number` into `integer`
+--@^^ Note: The other type originates here
+--! error
+
+--8<-- lua53-float-literal -- flag:lua53
+local x = 1.0 --: integer
+--@^ Error: Cannot assign `number` into `integer`
+--@^^ Note: The other type originates here
+--! error
+
+--8<-- lua51-float-literal
+local x = 1.0 --: integer
+--! ok
+
+--8<-- lua53-integer-division -- flag:lua53
+--# assume p: integer
+local x = p // 2 --: integer
+local y = p / 2 --: number
+--! ok
+
+--8<-- lua53-integer-division-float -- flag:lua53
+--# assume p: integer
+local x = p / 2 --: integer
+--@^ Error: Cannot assign `number` into `integer`
+--@^^ Note: The other type originates here
+--! error
+
+--8<-- lua53-bitwise -- flag:lua53
+--# assume p: integer
+local x = p & 0xff | p ~ 1 << 2 >> 3 --: integer
+local y = ~p --: integer
+--! ok
+
+--8<-- lua53-bitwise-number -- flag:lua53
+--# assume p: number
+local x = p & 1
+--@^ Error: Cannot apply & operator to `number` and `1`
+--@^^ Cause: `number` is not a subtype of `integer`
+--! error
+
+--8<-- lua53-bitwise-not-number -- flag:lua53
+--# assume p: number
+local x = ~p
+--@^ Error: Cannot apply ~ operator to `number`
+--@^^ Cause: `number` is not a subtype of `integer`
+--! error
+
+--8<-- lua54-bitwise -- flag:lua54
+--# assume p: integer
+local x = p << 1 --: integer
+--! ok
+
+--8<-- lua53-bitwise-numeric-string -- flag:lua53
+local x = '0xff' & 0x0f --: integer
+--@^ Warning: The string "0xff" is implicitly converted to a number by the `&` operator
+local y = '1.5' | 0
+--@^ Error: Cannot apply | operator to `"1.5"` and `0`
+--@^^ Cause: `"1.5"` is not a subtype of `integer`
+--! error
+
+--8<-- lua54-bitwise-numeric-string -- flag:lua54
+local x = '0xff' & 0x0f
+--@^ Error: Cannot apply & operator to `"0xff"` and `15`
+--@^^ Cause: `"0xff"` is not a subtype of `integer`
+local y = '10' // 3 --: integer
+--@^ Warning: The string "10" is implicitly converted to a number by the `//` operator
+--! error
+
+--8<-- lua53-bitwise-metamethod -- flag:lua53
+--# open lua53
+local mt = {}
+--v function(a: {bits: integer}, b: integer) --> {bits: integer}
+function mt.__band(a, b) return { bits = a.bits & b } end
+--v function(a: {bits: integer}) --> {bits: integer}
+function mt.__bnot(a) return { bits = ~a.bits } end
+
+local v = setmetatable({ bits = 1 }, mt)
+local w = v & 3 --: {bits: integer}
+local u = ~v --: {bits: integer}
+--! ok
+
+--8<-- lua53-library -- flag:lua53
+--# open lua53
+local t = table.move({1, 2, 3}, 1, 3, 2)
+local s = string.pack('i4', 42) --: string
+local n = string.packsize('i4') --: integer
+local c = utf8.char(72, 105) --: string
+local l = utf8.len(c)
+--! ok
+
+--8<-- lua53-math-type -- flag:lua53
+--# open lua53
+local x = math.type(1) --: 'float'|'integer'|nil
+local y = math.tointeger(3.0) --: integer?
+local z = math.maxinteger --: integer
+local w = math.ult(1, -1) --: boolean
+--! ok
+
+--8<-- lua53-no-bit32 -- flag:lua53
+--# open lua53
+local x = bit32.band(1, 2) --@< Error: Global or local variable `bit32` is not defined
+--! error
+
+--8<-- lua53-no-warn -- flag:lua53
+--# open lua53
+warn('oops') --@< Error: Global or local variable `warn` is not defined
+--! error
+
+--8<-- lua54-library -- flag:lua54
+--# open lua54
+warn('@on')
+local co = coroutine.create(function() end)
+local ok, err = coroutine.close(co)
+local b = ok --: boolean
+local n = utf8.len('abc', 1, -1, true)
+math.randomseed()
+--! ok
+
+--8<-- lua-version-neutral-54 -- flag:lua54
+--# open lua
+local x = math.type(1)
+warn('hello')
+--! ok
+
+--8<-- lua54-local-const -- flag:lua54
+local x <const> = 42
+x = 54 --@< Error: Cannot assign `54` into `const integer`
+       --@^ Note: The other type originates here
+--! error
+
+--8<-- lua54-local-close -- flag:lua54
+--# open lua54
+local f <close> = io.open('a.txt')
+f = nil --@< Error: Cannot assign `nil` into `const file`
+        --@^ Note: The other type originates here
+--! error
@@ -0,0 +1,51 @@
+-- Tests specific to LuaJIT library support in the Kailua type checker.
+
+--8<-- luajit-bit
+--# open luajit
+local x = bit.band(0xff, bit.lshift(1, 4)) --: integer
+local s = bit.tohex(x) --: string
+--! ok
+
+--8<-- luajit-jit
+--# open luajit
+if jit.status() then
+    print(jit.version, jit.arch)
+end
+jit.opt.start('hotloop=10')
+--! ok
+
+--8<-- luajit-ffi
+--# open luajit
+local ffi = require 'ffi'
+ffi.cdef [[ int printf(const char *fmt, ...); ]]
+ffi.C.printf('hello %d\n', 42)
+local buf = ffi.new('char[?]', 64) --: cdata
+local s = ffi.string(buf, 3) --: string
+--! ok
+
+--8<-- luajit-ffi-cdata-opaque
+--# open luajit
+local ffi = require 'ffi'
+local p = ffi.new('struct { int x; }')
+local x = p.x --@< Error: Tried to index a non-table type `cdata`
+--! error
+
+--8<-- luajit-ffi-not-global
+--# open luajit
+ffi.cdef [[ int x; ]] --@< Error: Global or local variable `ffi` is not defined
+--! error
+
+--8<-- luajit-ffi-only
+--# open luajit_ffi
+--# assume global require: [require] function(string) --> any
+local ffi = require 'ffi'
+local n = ffi.sizeof('int')
+--! ok
+
+--8<-- luajit-duplicate-open
+--# open luajit
+--# open luajit_ffi
+--# open lua51
+local ffi = require 'ffi'
+print(bit.bnot(0))
+--! ok
@@ -0,0 +1,136 @@
+-- Shadowing tests for the Kailua type checker.
+
+--8<-- shadowing-local -- flag:shadowing
+local a = 1
+local a = 2 --@< Warning: Local variable `a` shadows a local variable of the same name
+--@^^ Note: The shadowed variable was defined here
+--! ok
+
+--8<-- shadowing-local-without-flag
+local a = 1
+local a = 2
+--! ok
+
+--8<-- shadowing-local-block -- flag:shadowing
+local a = 1
+do
+    local a = 'string' --@< Warning: Local variable `a` shadows a local variable of the same name
+end
+--@^^^^ Note: The shadowed variable was defined here
+--! ok
+
+--8<-- shadowing-local-sibling -- flag:shadowing
+do
+    local a = 1
+end
+do
+    local a = 2
+end
+local a = 3
+--! ok
+
+--8<-- shadowing-local-same-statement -- flag:shadowing
+local a, b = 1, 2
+--! ok
+
+--8<-- shadowing-underscore -- flag:shadowing
+--# open lua51
+--# assume t: vector<integer>
+for _, v in ipairs(t) do
+    for _, w in ipairs(t) do
+    end
+end
+local _x = 1
+local _x = 2
+--! ok
+
+--8<-- shadowing-loop-variable -- flag:shadowing
+for i = 1, 3 do
+    for i = 1, 3 do --@< Warning: Local variable `i` shadows a local variable of the same name
+    end
+end
+--@^^^^ Note: The shadowed variable was defined here
+--! ok
+
+--8<-- shadowing-loop-variable-in -- flag:shadowing
+--# open lua51
+--# assume t: vector<integer>
+local v = 0
+for k, v in ipairs(t) do --@< Warning: Local variable `v` shadows a local variable of the same name
+end
+--@^^^ Note: The shadowed variable was defined here
+--! ok
+
+--8<-- shadowing-upvalue -- flag:shadowing
+local a = 1
+local function f()
+    local a = 2 --@< Warning: Local variable `a` shadows a local variable of the same name in an outer function
+end
+--@^^^^ Note: The shadowed variable was defined here
+--! ok
+
+--8<-- shadowing-parameter -- flag:shadowing
+local a = 1
+--v function(a: integer)
+local function f(a) --@< Warning: Local variable `a` shadows a local variable of the same name in an outer function
+end
+--@^^^^ Note: The shadowed variable was defined here
+--! ok
+
+--8<-- shadowing-parameter-local -- flag:shadowing
+--v function(a: integer)
+local function f(a)
+    local a = 2 --@< Warning: Local variable `a` shadows a local variable of the same name
+end
+--@^^^ Note: The shadowed variable was defined here
+--! ok
+
+--8<-- shadowing-self -- flag:shadowing
+local A = {}
+local B = {}
+function A:f()
+    function B:g() --@< Warning: Local variable `self` shadows a local variable of the same name in an outer function
+    end
+end
+--@^^^^ Note: The shadowed variable was defined here
+--! ok
+
+--8<-- shadowing-local-function -- flag:shadowing
+local f = 1
+local function f() --@< Warning: Local variable `f` shadows a local variable of the same name
+end
+--@^^^ Note: The shadowed variable was defined here
+--! ok
+
+--8<-- shadowing-global -- flag:shadowing
+x = 1
+local x = 2 --@< Warning: Local variable `x` shadows a global variable of the same name
+--@^^ Note: The shadowed variable was defined here
+--! ok
+
+--8<-- shadowing-global-builtin -- flag:shadowing
+--# open lua51
+local print = 1 --@< Warning: Local variable `print` shadows a global variable of the same name
+--! ok
+
+--8<-- shadowing-global-undefined -- flag:shadowing
+local x = 1
+--! ok
+
+--8<-- shadowing-initialized-from-global -- flag:shadowing
+--# open lua51
+local print, type = print, type
+--! ok
+
+--8<-- shadowing-initialized-from-local -- flag:shadowing
+local a = 1
+local function f()
+    local a = a
+    return a
+end
+--! ok
+
+--8<-- shadowing-assume -- flag:shadowing
+local a = 1
+--# assume a: integer
+--! ok
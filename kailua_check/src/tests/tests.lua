@@ -633,6 +633,20 @@ local q = p + 3 --@< Error: Cannot apply + operator to `(number|string)` and `3`
                 --@^ Cause: `(number|string)` is not a subtype of `number`
 --! error
 
+--8<-- add-numeric-string-lit
+local a = '10' + 1 --: integer
+--@^ Warning: The string "10" is implicitly converted to a number by the `+` operator
+local b = 2 * ' 0.5 ' --: number
+--@^ Warning: The string " 0.5 " is implicitly converted to a number by the `*` operator
+local c = -'0x10' --: integer
+--@^ Warning: The string "0x10" is implicitly converted to a number by the `-` operator
+--! ok
+
+--8<-- add-non-numeric-string-lit
+local a = 'ten' + 1 --@< Error: Cannot apply + operator to `"ten"` and `1`
+                    --@^ Cause: `"ten"` is not a subtype of `number`
+--! error
+
 --8<-- cat-string-or-boolean
 --# assume p: string | boolean
 local q = p .. 3 --@< Error: Cannot apply .. operator to `(boolean|string)` and `3`
@@ -677,6 +691,151 @@ x = 3 --@< Error: Cannot assign `3` into `(4|5)`
       --@^ Note: The other type originates here
 --! error
 
+--8<-- var-integer-range
+local x --: integer(1..10)
+x = 1
+x = 10
+x = 11 --@< Error: Cannot assign `11` into `integer(1..10)`
+       --@^ Note: The other type originates here
+--! error
+
+--8<-- var-integer-range-literals
+local x --: integer(1..10)
+local y --: 2 | 3 | 5
+local z --: 0 | 5
+x = y
+x = z --@< Error: Cannot assign `(0|5)` into `integer(1..10)`
+      --@^ Note: The other type originates here
+--! error
+
+--8<-- var-integer-range-to-literals
+local x --: 1 | 2 | 3
+local y --: integer(1..3)
+local z --: integer(1..4)
+x = y
+x = z --@< Error: Cannot assign `integer(1..4)` into `(1|2|3)`
+      --@^ Note: The other type originates here
+--! error
+
+--8<-- var-integer-range-sub-range
+local x --: integer(0..255)
+local y --: integer(1..10)
+local z --: integer(-1..10)
+x = y
+x = z --@< Error: Cannot assign `integer(-1..10)` into `integer(0..255)`
+      --@^ Note: The other type originates here
+--! error
+
+--8<-- var-integer-range-to-integer
+local x --: integer(1..10)
+local y = x --: integer
+local z = x --: number
+--! ok
+
+--8<-- var-integer-to-integer-range
+local x --: integer
+local y = x --: integer(1..10) --@< Error: Cannot assign `integer` into `integer(1..10)`
+                                --@^ Note: The other type originates here
+--! error
+
+--8<-- var-integer-range-union
+local x --: integer(1..3) | integer(5..8)
+x = 4
+x = 9 --@< Error: Cannot assign `9` into `integer(1..8)`
+      --@^ Note: The other type originates here
+--! error
+
+--8<-- var-integer-range-union-literal
+local x --: integer(1..3) | 2
+x = 3
+x = 4 --@< Error: Cannot assign `4` into `integer(1..3)`
+      --@^ Note: The other type originates here
+--! error
+
+--8<-- var-integer-range-single
+local x --: integer(7..7)
+x = 7
+x = 8 --@< Error: Cannot assign `8` into `7`
+      --@^ Note: The other type originates here
+--! error
+
+--8<-- var-integer-range-arith
+local x --: integer(1..10)
+local y = x + 1 --: integer
+local z = x + 1 --: integer(1..10) --@< Error: Cannot assign `integer` into `integer(1..10)`
+                                    --@^ Note: The other type originates here
+--! error
+
+--8<-- var-integer-range-index
+local t = {} --: vector<string>
+local i --: integer(1..10)
+local s = t[i] --: string
+--! ok
+
+--8<-- funccall-integer-range
+--v function(level: integer(1..3))
+local function f(level) end
+f(2)
+f(4) --@< Error: The type `function(level: integer(1..3)) --> ()` cannot be called
+     --@^ Cause: First function argument `4` is not a subtype of `integer(1..3)`
+     --@^^ Note: The other type originates here
+--! error
+
+--8<-- var-difference-any-nil
+local a --: string?
+local b = 42 --: any - nil
+b = 'string'
+b = {}
+local c = a --: string? - nil --@< Error: Cannot assign `string?` into `string!`
+                              --@^ Note: The other type originates here
+--! error
+
+--8<-- var-difference-union
+local x --: string | number | table - number
+x = 'string'
+x = 42 --@< Error: Cannot assign `42` into `(string|table)`
+       --@^ Note: The other type originates here
+--! error
+
+--8<-- var-difference-any-table
+local x --: any - table?
+x = 'string'
+x = function() end
+x = {} --@< Error: Cannot assign `{...}` into `(boolean|thread|userdata|number|string|function)!`
+       --@^ Note: The other type originates here
+--! error
+
+--8<-- var-difference-chain
+local x --: boolean | string? - false - nil
+x = true
+x = 'string'
+x = false --@< Error: Cannot assign `false` into `(true|string)!`
+          --@^ Note: The other type originates here
+--! error
+
+--8<-- var-difference-nested
+--v function(t: {any - nil}) --> any!
+local function f(t)
+    return t[1]
+end
+--! ok
+
+--8<-- var-difference-nilable-any
+--v function(x: any?)
+local function f(x)
+    local y = x --: any - table --@< Error: Cannot assign `any?` into `(boolean|thread|userdata|number|string|function)`
+                                --@^ Note: The other type originates here
+end
+--! error
+
+--8<-- var-difference-non-primitive
+local x --: any - {} --@< Error: The excluded type in `T - U` should be a primitive type or a union of them
+--! error
+
+--8<-- var-difference-literal
+local x --: integer - 42 --@< Error: The excluded type in `T - U` should be a primitive type or a union of them
+--! error
+
 --8<-- add-sub-mul-mod-integer-integer
 local x, y, z
 --# assume x: integer
@@ -1391,6 +1550,33 @@ local z = a[3] --: integer
 --@^^ Note: The other type originates here
 --! error
 
+--8<-- var-map-update-and-index-non-stringy-key
+local a = {} --: map<boolean, string>
+local o = {}
+local b = {} --: map<table, integer>
+a[true] = 'yes'
+a[false] = 'no'
+b[o] = 42
+b[a] = 54
+local z = b[o] --: integer?
+--! ok
+
+--8<-- var-map-update-and-index-class-key
+--# assume global class Hello
+--# assume h: Hello
+local a = {} --: map<table, integer>
+a[h] = 42
+local b = {} --: map<Hello, integer>
+b[h] = 54
+local c = b --: const map<table, integer>
+--! ok
+
+--8<-- var-map-update-and-index-wrong-non-stringy-key
+local a = {} --: map<boolean, string>
+local o = {}
+a[o] = 'table' --@< Error: Cannot index `map<boolean, string>` with `{...}`
+--! error
+
 --8<-- const-only-1
 local a = 3 --: const
 a = 3 --@< Error: Cannot assign `3` into `const integer`
@@ -1634,6 +1820,79 @@ local function f()
 end
 --! error
 
+--8<-- func-missing-return
+--v function(n: integer) --> integer
+local function p(n)
+    if n > 0 then
+        return n
+    end
+end --@< Error: The function can reach its end without returning a value, but its return type `(integer)` cannot be omitted
+--! error
+
+--8<-- func-missing-return-empty
+--v function() --> string
+local function p()
+end --@< Error: The function can reach its end without returning a value, but its return type `(string)` cannot be omitted
+--! error
+
+--8<-- func-missing-return-seq
+--v function(n: integer) --> (integer, string?)
+local function p(n)
+    while n > 0 do
+        return n
+    end
+end --@< Error: The function can reach its end without returning a value, but its return type `(integer, string?)` cannot be omitted
+--! error
+
+--8<-- func-missing-return-anonymous
+local p --: function(integer) --> integer
+p = function(n)
+    if n > 0 then return n end
+end --@< Error: The function can reach its end without returning a value, but its return type `(integer)` cannot be omitted
+--! error
+
+--8<-- func-missing-return-for
+--v function(t: vector<integer>) --> integer
+local function p(t)
+    for i = 1, #t do
+        return t[i]
+    end
+end --@< Error: The function can reach its end without returning a value, but its return type `(integer)` cannot be omitted
+--! error
+
+--8<-- func-missing-return-nilable
+--v function(n: integer) --> integer?
+local function p(n)
+    if n > 0 then
+        return n
+    end
+end
+--! ok
+
+--8<-- func-missing-return-all-paths
+--# open lua51
+--v function(n: integer) --> integer
+local function p(n)
+    if n > 0 then
+        return n
+    elseif n < 0 then
+        error('negative')
+    else
+        return 0
+    end
+end
+--! ok
+
+--8<-- func-missing-return-infinite-loop
+--v function(n: integer) --> integer
+local function p(n)
+    while true do
+        if n > 0 then return n end
+        n = n + 1
+    end
+end
+--! ok
+
 --8<-- assign-func-no-hint-1
 local x --: function(string)
 x = function(a) end
@@ -2131,16 +2390,84 @@ return true
 
 --8<-- require-recursive
 --# assume global `require`: [require] function(string) --> any
-require 'a' --@< Note: The module was previously `require`d here
+require 'a' --@< Note: The module `a` was `require`d here
 
 --& a
-require 'b'
+require 'b' --@< Note: The module `b` was `require`d here
+
+--& b
+require 'a' --@< Error: Recursive `require` was requested (`a` -> `b` -> `a`)
+
+--! error
+
+--8<-- require-recursive-self
+--# assume global `require`: [require] function(string) --> any
+require 'a'
+
+--& a
+require 'b' --@< Note: The module `b` was `require`d here
+
+--& b
+require 'b' --@< Error: Recursive `require` was requested (`b` -> `b`)
+
+--! error
+
+--8<-- require-recursive-long
+--# assume global `require`: [require] function(string) --> any
+require 'a' --@< Note: The module `a` was `require`d here
+
+--& a
+require 'b' --@< Note: The module `b` was `require`d here
+
+--& b
+require 'c' --@< Note: The module `c` was `require`d here
+
+--& c
+require 'a' --@< Error: Recursive `require` was requested (`a` -> `b` -> `c` -> `a`)
+
+--! error
+
+--8<-- require-recursive-dynamic
+--# assume global `require`: [require] function(string) --> any
+require 'a' --@< Note: The module `a` was `require`d here
+
+--& a
+local b = require 'b' --@< Note: The module `b` was `require`d here
+return { x = b.y + 1 }
 
 --& b
-require 'a' --@< Error: Recursive `require` was requested
+local a = require 'a' --@< Error: Recursive `require` was requested (`a` -> `b` -> `a`)
+-- `a` is not yet known here and should not cause more errors
+local x = a.x + a.y()
+return { y = 42 }
 
 --! error
 
+--8<-- require-recursive-module-table
+--# open lua51
+require 'a' --@< Note: The module `a` was `require`d here
+
+--& a
+module('a', package.seeall)
+function h() return 42 end
+local b = require 'b' --@< Note: The module `b` was `require`d here
+function f() return b.g() end
+
+--& b
+local a = require 'a'
+--@^ Warning: Recursive `require` was requested (`a` -> `b` -> `a`) and the module table made by `module(...)` is only partially initialized
+local M = {}
+function M.g() return a.h() end
+return M
+
+--! ok
+
+--8<-- require-unresolved-twice
+--# assume global `require`: [require] function(string) --> any
+require 'a' --@< Warning: Cannot resolve the module name given to `require`
+require 'a'
+--! ok
+
 --8<-- require-disconnected
 --# assume global `require`: [require] function(string) --> any
 
@@ -2162,6 +2489,67 @@ return 42
 
 --! ok
 
+--8<-- require-non-literal
+--# assume global `require`: [require] function(string) --> any
+--# assume name: string
+local x = require(name) --@< Warning: The module name given to `require` is not a string literal, so the result is dynamic
+local y = x.foo + x.bar() --: number
+
+--& a
+return 42
+
+--! ok
+
+--8<-- require-non-literal-error -- flag:dynamic_require_error
+--# assume global `require`: [require] function(string) --> any
+--# assume name: string
+local x = require(name) --@< Error: The module name given to `require` is not a string literal, so the result is dynamic
+local y = x.foo + x.bar() --: number
+
+--! error
+
+--8<-- require-non-literal-one-of -- flag:dynamic_require_one_of
+--# assume global `require`: [require] function(string) --> any
+--# assume name: string
+local x = require(name) --: integer
+local y = require('b') + 1 --: integer
+
+--& a
+return 42
+
+--& b
+return 54
+
+--! ok
+
+--8<-- require-non-literal-one-of-union -- flag:dynamic_require_one_of
+--# assume global `require`: [require] function(string) --> any
+--# assume name: string
+local x = require(name) --: integer
+--@^ Error: Cannot assign `(42|"string")` into `integer`
+--@^^ Note: The other type originates here
+
+--& a
+return 42
+
+--& b
+return 'string'
+
+--! error
+
+--8<-- require-non-literal-one-of-incompatible -- flag:dynamic_require_one_of
+--# assume global `require`: [require] function(string) --> any
+--# assume name: string
+local x = require(name) --@< Warning: The modules that `require` may return have incompatible types, so the result is dynamic
+
+--& a
+return { x = 1 }
+
+--& b
+return { x = 'string' }
+
+--! ok
+
 --8<-- require-type-local
 --# assume global `require`: [require] function(string) --> any
 local x = require('x')
@@ -2460,6 +2848,142 @@ local function p() end
 function foo(x) end
 --! ok
 
+--8<-- custom-attr-call
+--# assume echo: [test_echo] function(any...) --> any
+local a, b = echo(42, 'x')
+local c = a + 1
+local d = b .. 'y'
+local e = b + 1 --@< Error: Cannot apply + operator to `string` and `1`
+                --@^ Cause: `string` is not a subtype of `number`
+--! error
+
+--8<-- custom-attr-method-call
+--# assume t: { echo: [test_echo] function(any, any...) --> any }
+local a, b = t:echo('x')
+local c = b .. 'y'
+local d = a .. 'y' --@< Error: Cannot apply .. operator to `{echo: [test_echo] function(any, any...) --> any}` and `"y"`
+                   --@^ Cause: `{echo: [test_echo] function(any, any...) --> any}` is not a subtype of `(number|string)`
+--! error
+
+--8<-- custom-attr-assign
+--# assume global count: [test_positive] integer
+count = 3
+count = -1 --@< Error: Only positive integer literals can be assigned
+--! error
+
+--8<-- custom-attr-assign-field
+--# assume t: { count: [test_positive] integer }
+t.count = 3
+t.count = 0 --@< Error: Only positive integer literals can be assigned
+--! error
+
+--8<-- listener-call -- flag:listener
+--# assume f: function(integer, string)
+f(1, 'x') --@< Warning: Called with 2 argument(s)
+--# assume t: { m: function(table, integer) }
+t:m(42) --@< Warning: Called with 2 argument(s)
+--! ok
+
+--8<-- listener-exp -- flag:listener
+local x = 'forbidden' --@< Warning: The string `forbidden` is not allowed
+local y = 'allowed'
+--! ok
+
+--8<-- listener-assign -- flag:listener
+local x = 1
+x = 2 --@< Warning: Reassigned
+local y --: integer
+y = 3
+--! ok
+
+--8<-- listener-type -- flag:listener
+local x = 1 --: any --@< Warning: `any` is not allowed
+--# type T = any --@< Warning: `any` is not allowed
+--# type U = { any }
+--! ok
+
+--8<-- deprecated-func
+--v [deprecated]
+--v function()
+function old() end
+old() --@< Warning: `old` is deprecated
+--! ok
+
+--8<-- deprecated-func-message
+--# assume old: [deprecated("use `new` instead")] function()
+old() --@< Warning: `old` is deprecated
+      --@^ Note: use `new` instead
+local f = old --: function() --@< Warning: `old` is deprecated
+                             --@^ Note: use `new` instead
+f()
+--! ok
+
+--8<-- deprecated-func-display
+--# assume old: [deprecated("use new instead")] function()
+local x = old + 1 --@< Warning: `old` is deprecated
+                  --@^ Note: use new instead
+                  --@^^ Error: Cannot apply + operator to `[deprecated("use new instead")] function() --> ()` and `1`
+                  --@^^^ Cause: `[deprecated("use new instead")] function() --> ()` is not a subtype of `number`
+--! error
+
+--8<-- deprecated-field
+--# assume t: { old: [deprecated] integer, new: integer }
+local a = t.new + t.old --@< Warning: The field `old` is deprecated
+local b = t['old'] --@< Warning: The field `old` is deprecated
+t.old = 42
+--! ok
+
+--8<-- deprecated-field-computed-key
+--# assume t: { old: [deprecated] integer, new: integer }
+--# assume k: 'old'
+local a = t[k] --@< Warning: This value is deprecated
+--! ok
+
+--8<-- deprecated-method
+--# assume t: { old: [deprecated("use `t:new()`")] function(table), new: function(table) }
+t:new()
+t:old() --@< Warning: The field `old` is deprecated
+        --@^ Note: use `t:new()`
+--! ok
+
+--8<-- deprecated-module
+--# assume global `require`: [require] function(string) --> any
+local a = require 'a' --@< Warning: The module "a" is deprecated
+                      --@^ Note: use the module "b" instead
+
+--& a
+--# assume M: [deprecated("use the module \"b\" instead")] {}
+local x = M --@< Warning: `M` is deprecated
+            --@^ Note: use the module "b" instead
+return M, M --@< Warning: `M` is deprecated
+            --@^ Note: use the module "b" instead
+--! ok
+
+--8<-- deprecated-bad-values
+--# assume a: [deprecated()] integer
+--# assume b: [deprecated(foo)] integer --@< Error: `deprecated` type attribute can only have no values or a single string
+--# assume c: [deprecated('x', 'y')] integer --@< Error: `deprecated` type attribute can only have no values or a single string
+--! error
+
+--8<-- nodiscard
+--v [nodiscard]
+--v function() --> integer
+function f() return 42 end
+local x = f()
+f() --@< Warning: The return value of this function should not be discarded
+local y = f() + f()
+--! ok
+
+--8<-- nodiscard-method
+--# assume t: { f: [nodiscard] function(table, integer) --> integer }
+t:f(t:f(1)) --@< Warning: The return value of this function should not be discarded
+--! ok
+
+--8<-- nodiscard-assign-plain-function
+--# assume f: [nodiscard] function() --> integer
+f = function() return 1 end
+--! ok
+
 --8<-- duplicate-attr
 -- `[string_meta] [genv] WHATEVER` is syntactically forbidden
 --# assume foo: [string_meta] ([genv] WHATEVER)
@@ -2634,6 +3158,40 @@ if f() then --@< Warning: This condition always evaluates to a truthy value
 end
 --! ok
 
+--8<-- if-const-false
+--# assume global DEBUG: const false
+if DEBUG then
+    local a = 1 + 'a'
+end
+--! ok
+
+--8<-- if-const-eq-literal
+--# assume global PLATFORM: const "win"
+if PLATFORM == 'linux' then
+    local a = 1 + 'a'
+elseif PLATFORM ~= 'win' then
+    local b = 1 + 'b'
+else
+    local c = PLATFORM --: "win"
+end
+--! ok
+
+--8<-- if-const-eq-literal-taken
+--# assume global LEVEL: const 3
+if LEVEL == 3 then
+    local a = 1 + 'a' --@< Error: Cannot apply + operator to `1` and `"a"`
+                      --@^ Cause: `"a"` is not a subtype of `number`
+end
+--! error
+
+--8<-- if-non-const-eq-literal
+--# assume global PLATFORM: "win"
+if PLATFORM == 'linux' then
+    local a = 1 + 'a' --@< Error: Cannot apply + operator to `1` and `"a"`
+                      --@^ Cause: `"a"` is not a subtype of `number`
+end
+--! error
+
 --8<-- while-false-warning -- feature:warn_on_dead_code
 while false do
     local a --@<-vv Warning: This code will never execute
@@ -2993,14 +3551,43 @@ local a = {
 
 --8<-- table-lit-rec-arbitrary-key
 --# assume k: string
-local a = {[k] = 42} --@< Error: The type `string` cannot be used as a key in the table constructor for records
-                     --@^ Note: The type of this table was unknown so is assumed to be a record; please specify its type
+local a = {x = 1, [k] = 42} --@< Error: The type `string` cannot be used as a key in the table constructor for records
+                            --@^ Note: The type of this table was unknown so is assumed to be a record; please specify its type
 --! error
 
 --8<-- table-lit-rec-non-stringy-key
 --# assume k: table
-local a = {[k] = 42} --@< Error: The type `table` cannot be used as a key in the table constructor for records
-                     --@^ Note: The type of this table was unknown so is assumed to be a record; please specify its type
+local a = {x = 1, [k] = 42} --@< Error: The type `table` cannot be used as a key in the table constructor for records
+                            --@^ Note: The type of this table was unknown so is assumed to be a record; please specify its type
+--! error
+
+--8<-- table-lit-implicit-map
+--# assume k: string
+local a = {[k] = 42}
+local b = a --: map<string, integer>
+--! ok
+
+--8<-- table-lit-implicit-map-non-stringy-key
+--# assume k: table
+--# assume l: boolean
+local a = {[k] = 42, [l] = 54, [true] = 'x'}
+local b = a --: map<table|boolean, integer|string>
+--! ok
+
+--8<-- table-lit-implicit-map-record-key
+local k = {}
+local a = {[k] = 42}
+k.x = 'extended'
+a[{}] = 54
+local b = a --: map<table, integer>
+--! ok
+
+--8<-- table-lit-implicit-map-invalid-key
+--# assume k: vector<integer>
+--# assume v: map<string, integer>
+local a = {[k] = 42, [v] = 54} --@< Error: The type `map<string, integer>` cannot be used as a key in the table constructor for the type `map<vector<integer>, integer>`
+                               --@^ Note: The type of this table was unknown so is assumed to be a map from its first key; please specify its type
+                               --@ Cause: Cannot create a union type of `vector<integer>` and `map<string, integer>`
 --! error
 
 --8<-- table-lit-rec-bounded-seq
@@ -3012,11 +3599,89 @@ local aa = a --: {integer, integer, integer, integer}
 --8<-- table-lit-rec-unbounded-seq
 function f() --> (integer...)
 end
--- note the missing hint
-local a = {1, 2, f()} --@< Error: This expression has an unknown number of return values, so cannot be used as the last value in the table constructor for records
-                      --@^ Note: The type of this table was unknown so is assumed to be a record; please specify its type
+-- note the missing hint; only positional values, so this is a vector
+local a = {1, 2, f()}
+local aa = a --: string
+--@^ Error: Cannot assign `vector<integer>` into `string`
+--@^^ Note: The other type originates here
+--! error
+
+--8<-- table-lit-rec-unbounded-seq-union
+--v function() --> (integer, string...)
+function f()
+    return 42
+end
+local a = {false, f()}
+local aa = a --: vector<boolean|integer|string>
+--! ok
+
+--8<-- table-lit-rec-unbounded-seq-varargs
+--v function(...: string)
+function f(...)
+    local a = {...}
+    local aa = a --: vector<string>
+end
+--! ok
+
+--8<-- table-lit-rec-unbounded-seq-with-key
+function f() --> (integer...)
+end
+local a = {x = 1, f()} --@< Error: This expression has an unknown number of return values, so cannot be used as the last value in the table constructor for records
+                       --@^ Note: The type of this table was unknown so is assumed to be a record; please specify its type
+--! error
+
+--8<-- multi-return-assign
+--v function() --> (integer, string, boolean)
+function f()
+    return 42, 'foo', true
+end
+local a, b, c, d = f()
+local aa = a --: integer
+local bb = b --: string
+local cc = c --: boolean
+local dd = d --: nil
+--! ok
+
+--8<-- multi-return-truncated
+--v function() --> (integer, string)
+function f()
+    return 42, 'foo'
+end
+local a, b, c = f(), f()
+local bb = b --: integer
+local cc = c --: string
+local d, e = (f())
+local ee = e --: nil
+--! ok
+
+--8<-- multi-return-spread-args
+--v function() --> (integer, string)
+function f()
+    return 42, 'foo'
+end
+--v function(a: number, b: integer, c: string)
+function g(a, b, c)
+end
+g(3.14, f())
+g(f()) --@< Error: The type `function(a: number, b: integer, c: string) --> ()` cannot be called
+       --@^ Cause: Second function argument `string` is not a subtype of `integer`
+       --@^^ Note: The other type originates here
 --! error
 
+--8<-- multi-return-spread-returns
+--v function() --> (integer, string)
+function f()
+    return 42, 'foo'
+end
+function g()
+    return true, f()
+end
+local a, b, c = g()
+local aa = a --: boolean
+local bb = b --: integer
+local cc = c --: string
+--! ok
+
 --8<-- table-lit-subtyping-1
 -- avoid hinting
 local a = {1, 2, 3}
@@ -3292,6 +3957,40 @@ local y = x(true, false)
 
 --8<-- table-assign-just
 ({a=1, b=2}).c = 3
+--@^ Error: Cannot add a new key `"c"` to `{a: 1, b: 2, ...}` constructed from a table constructor
+--@^^ Note: Annotate an open record type like `{..., ...}` to add fields later
+--! error
+
+--8<-- table-assign-just-empty
+({}).c = 3
+--! ok
+
+--8<-- table-assign-sealed
+local t = {name = 'x'}
+t.nmae = 'y'
+--@^ Error: Cannot add a new key `"nmae"` to `{name: "x", ...}` constructed from a table constructor
+--@^^ Note: Annotate an open record type like `{..., ...}` to add fields later
+--! error
+
+--8<-- table-assign-sealed-copy
+local t = {name = 'x'}
+local u = t
+u.nmae = 'y'
+--@^ Error: Cannot add a new key `"nmae"` to `{name: "x", ...}` constructed from a table constructor
+--@^^ Note: Annotate an open record type like `{..., ...}` to add fields later
+--! error
+
+--8<-- table-assign-sealed-method
+local t = {name = 'x'}
+function t.greet() end
+--@^ Error: Cannot add a new key `"greet"` to `{name: "x", ...}` constructed from a table constructor
+--@^^ Note: Annotate an open record type like `{..., ...}` to add fields later
+--! error
+
+--8<-- table-assign-open-annotated
+local t = {name = 'x'} --: {name: string, ...}
+t.id = 42
+function t.greet() end
 --! ok
 
 --8<-- table-assign-const
@@ -3463,6 +4162,79 @@ end
 f(0, '', false)
 --! ok
 
+--8<-- string-enum-assign
+--# type Direction = "n" | "s" | "e" | "w"
+local d = 'n' --: Direction
+d = 'w'
+d = 'q' --@< Error: Cannot assign `"q"` into `Direction`
+        --@^ Note: The other type originates here
+--! error
+
+--8<-- string-enum-comparison
+--# type Direction = "n" | "s" | "e" | "w"
+--v function(d: Direction)
+local function f(d)
+    if d == 'n' then end
+    if 's' ~= d then end
+    if d == 'x' then end --@< Warning: This comparison is always `false` because `Direction` cannot be "x"
+    if 'x' ~= d then end --@< Warning: This comparison is always `true` because `Direction` cannot be "x"
+end
+--! ok
+
+--8<-- string-enum-comparison-non-literal
+--# type Direction = "n" | "s" | "e" | "w"
+--v function(d: Direction, s: string)
+local function f(d, s)
+    if d == s then end
+end
+--! ok
+
+--8<-- string-enum-dispatch-exhaustive
+--# type Direction = "n" | "s" | "e" | "w"
+--v function(d: Direction)
+local function f(d)
+    if d == 'n' then
+    elseif d == 's' or d == 'e' then
+    elseif (d == 'w') then
+    end
+end
+--! ok
+
+--8<-- string-enum-dispatch-non-exhaustive
+--# type Direction = "n" | "s" | "e" | "w"
+--v function(d: Direction)
+local function f(d)
+    if d == 'n' then --@< Warning: This `if` statement does not handle "e", "w" of `Direction`; add missing cases or an `else` block
+    elseif d == 's' then
+    end
+end
+--! ok
+
+--8<-- string-enum-dispatch-else
+--# type Direction = "n" | "s" | "e" | "w"
+--v function(d: Direction)
+local function f(d)
+    if d == 'n' then
+    elseif d == 's' then
+    else
+    end
+end
+--! ok
+
+--8<-- string-enum-dispatch-other-conditions
+--# type Direction = "n" | "s" | "e" | "w"
+--v function(d: Direction, e: Direction)
+local function f(d, e)
+    if d == 'n' then
+    elseif e == 's' then
+    end
+    if d == 'n' then
+    elseif d ~= 's' then
+    end
+    if d == 'n' then end
+end
+--! ok
+
 --8<-- kailua-test-gen-tvar
 --# open `internal kailua_test`
 
@@ -3596,6 +4368,63 @@ local a = {}
 --# assume a.f: method() --> string --@< Error: `--# assume` cannot be used to add a static field to a non-class type `{...}`
 --! error
 
+--8<-- assume-scoped-field
+local x = {y = 'string'}
+do
+    --# assume scoped x.y: integer
+    local z = x.y + 42 --: integer
+end
+local w = x.y .. 'foo' --: string
+--! ok
+
+--8<-- assume-scoped-global
+--# assume global f: function() --> string
+do
+    --# assume scoped global f: function() --> integer
+    local a = f() + 1 --: integer
+end
+local b = f() .. 'x' --: string
+--! ok
+
+--8<-- assume-scoped-global-new
+do
+    --# assume scoped global g: integer
+    local a = g + 1
+end
+local b = g --@< Error: Global or local variable `g` is not defined
+--! error
+
+--8<-- assume-scoped-nested
+local x = {y = 'string'}
+do
+    --# assume scoped x.y: integer
+    do
+        --# assume scoped x.y: boolean
+        local z = x.y --: boolean
+    end
+    local z = x.y + 42 --: integer
+end
+local w = x.y .. 'foo' --: string
+--! ok
+
+--8<-- assume-scoped-function
+local function f()
+    --# assume scoped global g: integer
+    return g + 1
+end
+local b = g --@< Error: Global or local variable `g` is not defined
+--! error
+
+--8<-- assume-scoped-module
+--# open lua51
+local a = require 'a'
+local b = h --@< Error: Global or local variable `h` is not defined
+
+--& a
+--# assume scoped global h: integer
+return h + 1
+--! error
+
 --8<-- dead-code -- feature:warn_on_dead_code
 function f()
     local a = 42
@@ -3825,3 +4654,414 @@ function f(...) --: string
 end
 --! error
 
+
+--8<-- generic-func-1
+--v function<T>(x: T) --> T
+local function id(x) return x end
+local a = id('hello') --: string
+local b = id(42) --: string --@< Error: Cannot assign `integer` into `string`
+                            --@^ Note: The other type originates here
+--! error
+
+--8<-- generic-func-2
+--v function<T>(array: vector<T>, f: function(T) --> boolean) --> vector<T>
+local function filter(array, f)
+    local r = {} --: vector<T>
+    for i = 1, #array do
+        if f(array[i]) then r[#r + 1] = array[i] end
+    end
+    return r
+end
+
+local nums = {1, 2, 3} --: vector<integer>
+local evens = filter(nums, function(x) return x % 2 == 0 end)
+local n = evens[1] + 1 --: integer
+--! ok
+
+--8<-- generic-func-rigid
+--v function<T>(x: T) --> T
+local function bad(x)
+    return x + 1 --@< Error: Cannot apply + operator to `T` and `1`
+                 --@^ Cause: `T` is not a subtype of `number`
+                 --@^^ Error: Attempted to return a type `(number)` which is incompatible to given return type `(T)`
+                 --@^^^ Cause: First return type `number` is not a subtype of `T`
+                 --@^^^^ Note: The other type originates here
+end
+--! error
+
+--8<-- generic-func-assume
+--# assume twice: function<T>(f: function(T) --> T, x: T) --> T
+local x = twice(function(v) return v end, 3) --: integer
+local y = twice(function(v) return v end, 'x') --: string
+--! ok
+
+--8<-- generic-func-subtype
+--# assume id: function<T>(T) --> T
+local f = id --: function<U>(U) --> U
+local g = id --: function(integer) --> integer
+local h = id --: function(integer) --> string --@< Error: Cannot assign `function<T>(T) --> T` into `function(integer) --> string`
+                                             --@^ Note: The other type originates here
+--! error
+
+--8<-- generic-func-not-generic
+--# assume f: function(integer) --> integer
+local g = f --: function<T>(T) --> T --@< Error: Cannot assign `function(integer) --> integer` into `function<T>(T) --> T`
+                                     --@^ Note: The other type originates here
+--! error
+
+--8<-- strict-nil-off
+local t = {} --: vector<integer>
+local v = t[1] + 1
+local x = 'a' --: string
+x = nil
+--! ok
+
+--8<-- strict-nil-index-array -- flag:strict_nil
+local t = {} --: vector<integer>
+local v = t[1] + 1 --@< Error: Cannot apply + operator to `integer?` and `1`
+                   --@^ Cause: `integer?` is not a subtype of `number`
+--! error
+
+--8<-- strict-nil-index-map -- flag:strict_nil
+local t = {} --: map<string, string>
+local v = t.key .. 'x' --@< Error: Cannot apply .. operator to `string?` and `"x"`
+                       --@^ Cause: `string?` is not a subtype of `(number|string)`
+--! error
+
+--8<-- strict-nil-index-check -- flag:strict_nil
+local t = {} --: vector<integer>
+local v = (t[1] or 0) + 1
+local w = t[1] --: integer?
+t[2] = 42
+--! ok
+
+--8<-- strict-nil-index-record -- flag:strict_nil
+local t = {x = 42}
+local v = t.x + 1
+--! ok
+
+--8<-- strict-nil-assign -- flag:strict_nil
+local x = 'a' --: string
+x = nil --@< Error: Cannot assign `nil` into `string!`
+        --@^ Note: The other type originates here
+--! error
+
+--8<-- strict-nil-assign-optional -- flag:strict_nil
+local x = 'a' --: string?
+x = nil
+local y = nil --: WHATEVER
+--! ok
+
+--8<-- strict-nil-func-args -- flag:strict_nil
+local function f(a) --: string
+end
+f(nil) --@< Error: The type `function(a: string!) --> ()` cannot be called
+       --@^ Cause: First function argument `nil` is not a subtype of `string!`
+       --@^^ Note: The other type originates here
+--! error
+
+--8<-- strict-nil-func-returns -- flag:strict_nil
+local function f() --> string
+    return nil --@< Error: Attempted to return a type `(nil)` which is incompatible to given return type `(string!)`
+               --@^ Cause: First return type `nil` is not a subtype of `string!`
+               --@^^ Note: The other type originates here
+end
+--! error
+
+--8<-- discriminated-union-narrowing
+--# type Circle = {kind: "circle", r: number}
+--# type Rect = {kind: "rect", w: number, h: number}
+--# type Tri = {kind: "tri", a: number}
+--# type Shape = Circle | Rect | Tri
+local s --: Shape
+if s.kind == "circle" then
+    local r = s.r --: number
+elseif s.kind == "rect" then
+    local w = s.w * s.h --: number
+else
+    local a = s.a --: number
+end
+if not (s.kind ~= "rect") then
+    local w = s.w --: number
+end
+if s.kind ~= "rect" then
+    local k = s.kind --: "circle" | "tri"
+end
+if "tri" == s["kind"] and s.a > 0 then
+    local a = s.a --: number
+end
+local ok = s.kind ~= "tri" or s.a > 0
+--! ok
+
+--8<-- discriminated-union-common-field
+--# type Circle = {kind: "circle", r: number}
+--# type Rect = {kind: "rect", w: number, h: number}
+local s --: Circle | Rect
+local k = s.kind --: "circle" | "rect"
+local r = s.r --@< Error: Cannot index `(Circle|Rect)` with `"r"`
+--! error
+
+--8<-- discriminated-union-update
+--# type Circle = {kind: "circle", r: number}
+--# type Rect = {kind: "rect", w: number, h: number}
+local s --: Circle | Rect
+s.kind = "rect" --@< Error: Cannot update a field of the discriminated union type `(Circle|Rect)` before narrowing it by comparing its discriminant
+--! error
+
+--8<-- discriminated-union-assign
+--# type Circle = {kind: "circle", r: number}
+--# type Rect = {kind: "rect", w: number, h: number}
+local s --: Circle | Rect
+s = {kind = "rect", w = 1, h = 2}
+s = {kind = "circle", r = 3}
+s = {kind = "tri", a = 4} --@< Error: Cannot assign `{a: 4, kind: "tri", ...}` into `(Circle|Rect)`
+                          --@^ Note: The other type originates here
+--! error
+
+--8<-- discriminated-union-assert
+--# type Circle = {kind: "circle", r: number}
+--# type Rect = {kind: "rect", w: number, h: number}
+--# assume assert: const [assert] function(any)
+local s --: Circle | Rect
+assert(s.kind == "circle")
+local r = s.r --: number
+--! ok
+
+--8<-- discriminated-union-without-discriminant
+--# type A = {kind: string, x: number}
+--# type B = {kind: string, y: number}
+--# type C = A | B --@< Error: This union type is not supported in the specification
+                  --@^ Cause: Cannot create a union type of `A` and `B`
+                  --@^^ Note: The other type originates here
+                  --@ Cause: No longer possible to add a new field to this record type
+--! error
+
+--8<-- narrowing-if-truthy
+local p = 3 --: integer?
+if p then
+    local a = p + 1
+end
+local b = p + 1 --@< Error: Cannot apply + operator to `integer?` and `1`
+                --@^ Cause: `integer?` is not a subtype of `number`
+--! error
+
+--8<-- narrowing-if-not-nil
+local x = {field = 3} --: {field: integer?}
+if x.field ~= nil then
+    local a = x.field + 1
+end
+if nil == x.field then
+else
+    local b = x.field + 1
+end
+--! ok
+
+--8<-- narrowing-alias
+local x = {field = 3} --: {field: integer?}
+local t = x.field
+if t then
+    local a = t + 1
+    local b = x.field + 1
+end
+local c = x.field + 1 --@< Error: Cannot apply + operator to `integer?` and `1`
+                      --@^ Cause: `integer?` is not a subtype of `number`
+--! error
+
+--8<-- narrowing-alias-discriminant
+--# type Circle = {kind: "circle", r: number}
+--# type Rect = {kind: "rect", w: number, h: number}
+local s --: Circle | Rect
+local k = s.kind
+if k == "circle" then
+    local r = s.r --: number
+end
+--! ok
+
+--8<-- narrowing-early-exit
+local x = {field = 3} --: {field: integer?}
+local function f()
+    local t = x.field
+    if not t then return end
+    local a = t + 1
+    local b = x.field + 1
+end
+for i = 1, 10 do
+    if x.field == nil then break end
+    local c = x.field + 1
+end
+local d = x.field + 1 --@< Error: Cannot apply + operator to `integer?` and `1`
+                      --@^ Cause: `integer?` is not a subtype of `number`
+--! error
+
+--8<-- narrowing-invalidated-by-assignment
+local x = {field = 3} --: {field: integer?}
+local t = x.field
+if t then
+    x.field = nil
+    local a = t + 1
+    local b = x.field + 1 --@< Error: Cannot apply + operator to `integer?` and `1`
+                          --@^ Cause: `integer?` is not a subtype of `number`
+    t = nil
+    local c = t + 1 --@< Error: Cannot apply + operator to `integer?` and `1`
+                    --@^ Cause: `integer?` is not a subtype of `number`
+end
+--! error
+
+--8<-- narrowing-discriminated-union-assign
+--# type Circle = {kind: "circle", r: number}
+--# type Rect = {kind: "rect", w: number, h: number}
+local s --: Circle | Rect
+if s.kind == "circle" then
+    s = {kind = "rect", w = 1, h = 2}
+end
+--! ok
+
+--8<-- interface-record
+--# interface Named = {
+--#     name: string,
+--#     describe: method(prefix: string) --> string,
+--# }
+local t = { name = 'foo' } --: {name: string, ...}
+--v method(prefix: string) --> string
+function t:describe(prefix)
+    return prefix .. 'foo'
+end
+--# assert t implements Named
+--! ok
+
+--8<-- interface-record-mismatch
+--# interface Named = {
+--#     name: string, --@< Note: A field `name` has a type `42` which is not a subtype of `string`
+--#     id: integer,
+--# }
+local t = { name = 42, id = 1 }
+--# assert t implements Named --@< Error: `t` does not implement the interface `Named`
+--! error
+
+--8<-- interface-no-interface
+local t = {}
+--# assert t implements Named --@< Error: Interface `Named` is not defined
+--! error
+
+--8<-- interface-no-var
+--# interface Named = { name: string }
+--# assert t implements Named --@< Error: Global or local variable `t` is not defined
+--! error
+
+--8<-- interface-non-table
+--# interface Named = { name: string }
+local t = 42
+--# assert t implements Named
+--@^ Error: Cannot check if `t` implements the interface, as its type `integer` has no known fields
+--! error
+
+--8<-- interface-redefine
+--# interface Named = { name: string } --@< Note: The interface was originally defined here
+--# interface Named = { id: integer } --@< Error: An interface `Named` is already defined
+--! error
+
+--8<-- strict-globals-top-level -- flag:strict_globals
+x = 42
+function f() end
+if x then y = 'string' end
+--! ok
+
+--8<-- strict-globals-in-function -- flag:strict_globals
+local function f()
+    x = 42 --@< Error: Global variable `x` is not declared; it should be declared with `--# assume global` or the configuration unless assigned at the top level of the start file
+end
+--! error
+
+--8<-- strict-globals-in-function-no-flag
+local function f()
+    x = 42
+end
+--! ok
+
+--8<-- strict-globals-in-function-declared -- flag:strict_globals
+--# assume global x: integer
+x = 1
+local function f()
+    x = 42
+end
+--! ok
+
+--8<-- strict-globals-in-function-assigned-before -- flag:strict_globals
+x = 1 --: integer
+local function f()
+    x = 42
+end
+--! ok
+
+--8<-- strict-globals-function-decl -- flag:strict_globals
+local function f()
+    function g() end --@< Error: Global variable `g` is not declared; it should be declared with `--# assume global` or the configuration unless assigned at the top level of the start file
+end
+--! error
+
+--8<-- strict-globals-genv -- flag:strict_globals
+--# open lua51
+local function f()
+    _G.x = 42 --@< Error: Global variable `x` is not declared; it should be declared with `--# assume global` or the configuration unless assigned at the top level of the start file
+end
+--! error
+
+--8<-- strict-globals-module -- flag:strict_globals
+--# open lua51
+require 'a'
+y = 54
+
+--& a
+x = 42 --@< Error: Global variable `x` is not declared; it should be declared with `--# assume global` or the configuration unless assigned at the top level of the start file
+
+--! error
+
+--8<-- strict-globals-module-declared -- flag:strict_globals
+--# open lua51
+--# assume global x: integer
+require 'a'
+
+--& a
+x = 42
+
+--! ok
+
+--8<-- limit-union-size -- flag:small_limits
+--# type T = 'a'|'b'|'c'|'d' --@< Note: Some unions had more than 3 literals and have been widened to `integer` or `string`
+local x --: T
+local y = x --: string
+local z = x --: 'a'|'b'|'c' --@< Error: Cannot assign `T` into `("a"|"b"|"c")`
+                            --@^ Note: The other type originates here
+--! error
+
+--8<-- limit-union-size-under -- flag:small_limits
+local x --: 'a'|'b'|'c'
+local y = x --: 'a'|'b'|'c'
+local z = x --: 'a'|'b' --@< Error: Cannot assign `("a"|"b"|"c")` into `("a"|"b")`
+                        --@^ Note: The other type originates here
+--! error
+
+--8<-- limit-table-depth -- flag:small_limits
+local t = {a = {b = {c = {d = 1}}}} --@< Note: Tables are nested deeper than 2 levels, so inner tables are no longer type checked
+local u = {a = {b = {c = 1}}}
+local x = t.a.b.c.d.e
+--! ok
+
+--8<-- limit-table-depth-shallow -- flag:small_limits
+local t = {a = {b = 1}}
+local x = t.a.c --@< Error: Missing key "c" in `{b: 1, ...}`
+--! error
+
+--8<-- limit-table-depth-default
+local t = {a = {b = {c = {d = 1}}}}
+local x = t.a.b.c.e --@< Error: Missing key "e" in `{d: 1, ...}`
+--! error
+
+--8<-- limit-constraints -- flag:small_limits
+local t = {a1 = 1, a2 = 2, a3 = 3, a4 = 4, a5 = 5, a6 = 6, a7 = 7, a8 = 8, a9 = 9, a10 = 10}
+local u = {b1 = 1, b2 = 2, b3 = 3, b4 = 4, b5 = 5, b6 = 6, b7 = 7, b8 = 8, b9 = 9, b10 = 10}
+local v = {c1 = 1, c2 = 2, c3 = 3, c4 = 4, c5 = 5, c6 = 6, c7 = 7, c8 = 8, c9 = 9, c10 = 10}
+local w = {d1 = 1} --@< Note: There are more than 20 type constraints, so subsequent tables are no longer type checked
+local x = w.d2
+local y = {e1 = 1}
+--! ok
@@ -0,0 +1,170 @@
+-- Unused local variable and assignment tests for the Kailua type checker.
+
+--8<-- unused-local -- flag:unused
+local a = 1 --@< Warning: Local variable `a` is never read
+local b = 2
+local _c = 3
+return b
+--! ok
+
+--8<-- unused-local-without-flag
+local a = 1
+--! ok
+
+--8<-- unused-local-multiple -- flag:unused
+--# assume f: function() --> (integer, integer)
+local a, b = f() --@< Warning: Local variable `b` is never read
+local _, d = f() --@< Warning: Local variable `d` is never read
+return a
+--! ok
+
+--8<-- unused-local-function -- flag:unused
+local function f() --@< Warning: Local variable `f` is never read
+end
+local function g()
+end
+g()
+--! ok
+
+--8<-- unused-loop-variable -- flag:unused
+--# open lua51
+--# assume t: vector<integer>
+for i = 1, 3 do --@< Warning: Local variable `i` is never read
+end
+for k, v in ipairs(t) do --@< Warning: Local variable `k` is never read
+    return v
+end
+for _, v in ipairs(t) do
+    return v
+end
+--! ok
+
+--8<-- unused-parameter -- flag:unused
+--v function(a: integer, b: integer) --> integer
+local function f(a, b)
+    return a
+end
+return f
+--! ok
+
+--8<-- unused-written-only -- flag:unused
+local a = 1 --@< Warning: Local variable `a` is never read
+a = 2
+--! ok
+
+--8<-- unused-assignment -- flag:unused
+local a = 1 --@< Warning: The value assigned to `a` is never read
+a = 2
+return a
+--! ok
+
+--8<-- unused-assignment-overwritten -- flag:unused
+--# open lua51
+local a = 1
+print(a)
+a = 2 --@< Warning: The value assigned to `a` is never read
+a = 3
+return a
+--! ok
+
+--8<-- unused-assignment-after-last-read -- flag:unused
+--# open lua51
+local a = 1
+print(a)
+a = 2 --@< Warning: The value assigned to `a` is never read
+--! ok
+
+--8<-- unused-assignment-nil -- flag:unused
+--# assume p: boolean
+local a = nil --: integer?
+if p then a = 1 else a = 2 end
+return a
+--! ok
+
+--8<-- unused-assignment-branch -- flag:unused
+--# assume p: boolean
+local a = 1
+if p then a = 2 end
+return a
+--! ok
+
+--8<-- unused-assignment-branch-both -- flag:unused
+--# assume p: boolean
+local a = 1 --@< Warning: The value assigned to `a` is never read
+if p then a = 2 else a = 3 end
+return a
+--! ok
+
+--8<-- unused-assignment-loop -- flag:unused
+local a = 0
+local n = 0
+while a < 10 do
+    a = a + 1
+    n = n + 1 -- read by the next iteration
+end
+--! ok
+
+--8<-- unused-assignment-loop-carried -- flag:unused
+local a, b = 0, 0
+for i = 1, 10 do
+    a = b
+    b = i
+end
+return a
+--! ok
+
+--8<-- unused-assignment-break -- flag:unused
+local a = 0
+while true do
+    a = 1
+    break
+end
+return a
+--! ok
+
+--8<-- unused-assignment-repeat -- flag:unused
+local a = 0
+repeat
+    a = a + 1
+until a > 10
+--! ok
+
+--8<-- unused-assignment-captured -- flag:unused
+local a = 1
+local function f() return a end
+a = 2
+f()
+--! ok
+
+--8<-- unused-assignment-captured-write -- flag:unused
+local a = 1
+local function f() a = 2 end
+f()
+return a
+--! ok
+
+--8<-- unused-assignment-in-function -- flag:unused
+local function f()
+    local a = 1 --@< Warning: The value assigned to `a` is never read
+    a = 2
+    return a
+end
+return f
+--! ok
+
+--8<-- unused-assignment-underscore -- flag:unused
+local _a = 1
+_a = 2
+return _a
+--! ok
+
+--8<-- unused-assume -- flag:unused
+local a = 1
+--# assume a: integer
+return a
+--! ok
+
+--8<-- unused-method -- flag:unused
+local M = {}
+function M:f() return self end
+--! ok
@@ -0,0 +1,471 @@
+//! Detection of unused local variables and unused assignments.
+//!
+//! This is a purely syntactic pass run after checking each chunk.
+//! A local variable is unused when it is never read anywhere,
+//! and an assignment is unused when no read can follow it before the next assignment
+//! (a backward liveness analysis over each function body).
+//!
+//! Variables whose names start with `_` are never reported.
+//! Variables captured by nested functions can be read at any time,
+//! so assignments to them are never reported either.
+
+use std::collections::{HashMap, HashSet};
+
+use kailua_env::{Spanned, WithLoc, ScopedId, ScopeMap};
+use kailua_syntax::Name;
+use kailua_syntax::ast::{NameRef, Var, Exp, Ex, Args, Table, St, Block, Sig, SelfParam};
+
+/// The kind of the unused thing found.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum UnusedKind {
+    /// A local variable (or a local function or a loop variable) that is never read.
+    Variable,
+
+    /// An assignment (or an initialization) whose value is never read.
+    Assignment,
+}
+
+/// Returns a list of unused variables and assignments in the chunk, sorted by the span.
+pub fn find_unused(block: &Spanned<Block>,
+                   scope_map: &ScopeMap<Name>) -> Vec<(UnusedKind, Spanned<ScopedId>)> {
+    let mut collector = Collector {
+        decls: Vec::new(),
+        decl_funcs: HashMap::new(),
+        reads: HashSet::new(),
+        captured: HashSet::new(),
+        funcs: Vec::new(),
+        func_stack: vec![0],
+        next_func: 1,
+    };
+    collector.visit_block(block);
+
+    let mut unused = Vec::new();
+    for &(ref id, reportable) in &collector.decls {
+        if reportable && !collector.reads.contains(&id.base) &&
+           id.span.is_source_dependent() && !id.base.name(scope_map).starts_with(b"_") {
+            unused.push((UnusedKind::Variable, id.clone()));
+        }
+    }
+
+    let mut liveness = Liveness {
+        // unread variables are already reported above, and
+        // assignments to the captured variables cannot be tracked
+        tracked: |id: &ScopedId| collector.reads.contains(id) && !collector.captured.contains(id),
+        scope_map: scope_map,
+        reporting: true,
+        break_targets: Vec::new(),
+        dead: Vec::new(),
+    };
+    // nested functions are analyzed separately
+    liveness.visit_block(block, Live::new());
+    for body in &collector.funcs {
+        liveness.visit_block(body, Live::new());
+    }
+    unused.extend(liveness.dead.into_iter().map(|id| (UnusedKind::Assignment, id)));
+
+    unused.sort_by_key(|&(_, ref id)| id.span.begin());
+    unused.dedup_by_key(|&mut (_, ref id)| id.span);
+    unused
+}
+
+// the first pass: collects declarations, reads, captures and function bodies
+struct Collector<'a> {
+    // declared variables and whether they can be reported when unread
+    decls: Vec<(Spanned<ScopedId>, bool)>,
+    // the function where each variable is declared
+    decl_funcs: HashMap<ScopedId, usize>,
+    reads: HashSet<ScopedId>,
+    // variables used by functions other than the declaring one
+    captured: HashSet<ScopedId>,
+    funcs: Vec<&'a Spanned<Block>>,
+    func_stack: Vec<usize>,
+    next_func: usize,
+}
+
+impl<'a> Collector<'a> {
+    fn declare(&mut self, id: &Spanned<ScopedId>, reportable: bool) {
+        let func = *self.func_stack.last().unwrap();
+        self.decl_funcs.insert(id.base.clone(), func);
+        self.decls.push((id.clone(), reportable));
+    }
+
+    fn touch(&mut self, name: &NameRef, read: bool) {
+        if let NameRef::Local(ref id) = *name {
+            if read {
+                self.reads.insert(id.clone());
+            }
+            let func = *self.func_stack.last().unwrap();
+            if self.decl_funcs.get(id).map_or(false, |&f| f != func) {
+                self.captured.insert(id.clone());
+            }
+        }
+    }
+
+    fn visit_func(&mut self, selfparam: Option<&Spanned<SelfParam>>,
+                  sig: &Sig, body: &'a Spanned<Block>) {
+        self.func_stack.push(self.next_func);
+        self.next_func += 1;
+        if let Some(selfparam) = selfparam {
+            self.declare(&selfparam.base.0.clone().with_loc(selfparam.span), false);
+        }
+        for arg in &sig.args.head {
+            self.declare(&arg.base, false);
+        }
+        self.funcs.push(body);
+        self.visit_block(body);
+        self.func_stack.pop();
+    }
+
+    fn visit_block(&mut self, block: &'a Spanned<Block>) {
+        for stmt in &block.base {
+            self.visit_stmt(&stmt.base);
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &'a St) {
+        match *stmt {
+            St::Oops | St::Break | St::KailuaOpen(..) | St::KailuaType(..) |
+            St::KailuaClassSystem(..) | St::KailuaInterface(..) | St::KailuaIgnore(..) => {}
+
+            St::Void(ref exp) => self.visit_exp(exp),
+            St::Assign(ref vars, ref exps) => {
+                for var in &vars.base {
+                    match var.base.base {
+                        Var::Name(ref name) => self.touch(&name.base, false),
+                        Var::Index(ref e1, ref e2) => { self.visit_exp(e1); self.visit_exp(e2); }
+                        Var::IndexName(ref e, _) => self.visit_exp(e),
+                    }
+                }
+                if let Some(ref exps) = *exps {
+                    for exp in &exps.base { self.visit_exp(exp); }
+                }
+            }
+            St::Do(ref block) => self.visit_block(block),
+            St::While(ref cond, ref block) | St::Repeat(ref block, ref cond) => {
+                self.visit_exp(cond);
+                self.visit_block(block);
+            }
+            St::If(ref conds, ref lastblock) => {
+                for cond in conds {
+                    let (ref cond, ref block) = cond.base;
+                    self.visit_exp(cond);
+                    self.visit_block(block);
+                }
+                if let Some(ref block) = *lastblock { self.visit_block(block); }
+            }
+            St::For(ref var, ref start, ref end, ref step, _, ref block) => {
+                self.visit_exp(start);
+                self.visit_exp(end);
+                if let Some(ref step) = *step { self.visit_exp(step); }
+                self.declare(var, true);
+                self.visit_block(block);
+            }
+            St::ForIn(ref vars, ref exps, _, ref block) => {
+                for exp in &exps.base { self.visit_exp(exp); }
+                for var in &vars.base { self.declare(var, true); }
+                self.visit_block(block);
+            }
+            St::FuncDecl(ref name, ref sig, _, ref block, sibling_scope) => {
+                match (sibling_scope, &name.base) {
+                    (Some(_), &NameRef::Local(ref id)) => {
+                        self.declare(&id.clone().with_loc(name.span), true);
+                    }
+                    (_, name) => self.touch(name, false),
+                }
+                self.visit_func(None, sig, block);
+            }
+            St::MethodDecl(ref names, ref selfparam, ref sig, _, ref block) => {
+                self.touch(&(names.base.0).base, true);
+                self.visit_func(selfparam.as_ref(), sig, block);
+            }
+            St::Local(ref names, ref exps, _) => {
+                for exp in &exps.base { self.visit_exp(exp); }
+                for name in &names.base { self.declare(&name.base, true); }
+            }
+            St::Return(ref exps) => {
+                for exp in &exps.base { self.visit_exp(exp); }
+            }
+            St::KailuaAssume(_, ref rename, _, _, _) |
+            St::KailuaAssumeClass(_, ref rename, _, _, _) => {
+                // the renamed variable is a continuation of the original variable
+                self.touch(&rename.base.before, true);
+                if let NameRef::Local(ref id) = rename.base.after {
+                    self.declare(&id.clone().with_loc(rename.span), false);
+                }
+            }
+            St::KailuaAssumeField(_, _, ref names, _, _) |
+            St::KailuaAssumeMethod(_, ref names, _, _) => {
+                self.touch(&(names.base.0).base, true);
+            }
+            St::KailuaAssertImplements(ref name, _) => self.touch(&name.base, true),
+        }
+    }
+
+    fn visit_args(&mut self, args: &'a Args) {
+        match *args {
+            Args::List(ref exps) => for exp in exps { self.visit_exp(exp); },
+            Args::Str(_) => {}
+            Args::Table(ref table) => self.visit_table(table),
+        }
+    }
+
+    fn visit_table(&mut self, table: &'a Table) {
+        for &(ref key, ref value) in &table.items {
+            if let Some(ref key) = *key { self.visit_exp(key); }
+            self.visit_exp(value);
+        }
+    }
+
+    fn visit_exp(&mut self, exp: &'a Spanned<Exp>) {
+        match *exp.base {
+            Ex::Oops | Ex::Nil | Ex::False | Ex::True | Ex::Num(_) | Ex::Int(_) |
+            Ex::Str(_) | Ex::Varargs => {}
+
+            Ex::Func(ref sig, _, ref block) => self.visit_func(None, sig, block),
+            Ex::Table(ref table) => self.visit_table(table),
+            Ex::Var(ref name) => self.touch(&name.base, true),
+            Ex::Exp(ref e) | Ex::IndexName(ref e, _) | Ex::Un(_, ref e) => self.visit_exp(e),
+            Ex::FuncCall(ref e, ref args) => { self.visit_exp(e); self.visit_args(args); }
+            Ex::MethodCall(ref e, ref args) => {
+                self.visit_exp(&e.base.0);
+                self.visit_args(args);
+            }
+            Ex::Index(ref e1, ref e2) | Ex::Bin(ref e1, _, ref e2) => {
+                self.visit_exp(e1);
+                self.visit_exp(e2);
+            }
+        }
+    }
+}
+
+type Live = HashSet<ScopedId>;
+
+// the second pass: backward liveness analysis for each function body
+struct Liveness<'a, F> {
+    tracked: F,
+    scope_map: &'a ScopeMap<Name>,
+    // false while computing the fixed point of loops
+    reporting: bool,
+    break_targets: Vec<Live>,
+    dead: Vec<Spanned<ScopedId>>,
+}
+
+impl<'a, F: Fn(&ScopedId) -> bool> Liveness<'a, F> {
+    // `live` is the set of variables live after the assignment
+    fn assign(&mut self, id: &Spanned<ScopedId>, live: &mut Live) {
+        if !live.remove(&id.base) && self.reporting && (self.tracked)(&id.base) &&
+           id.span.is_source_dependent() && !id.base.name(self.scope_map).starts_with(b"_") {
+            self.dead.push(id.clone());
+        }
+    }
+
+    fn visit_block(&mut self, block: &Spanned<Block>, mut live: Live) -> Live {
+        for stmt in block.base.iter().rev() {
+            live = self.visit_stmt(&stmt.base, live);
+        }
+        live
+    }
+
+    // computes the live set at the loop head, where `body` maps the live set
+    // at the end of the loop body to the live set at the loop head
+    fn fixpoint<B>(&mut self, after: &Live, mut body: B) -> Live
+        where B: FnMut(&mut Self, Live) -> Live
+    {
+        let reporting = self.reporting;
+        self.reporting = false;
+        self.break_targets.push(after.clone());
+        let mut head = after.clone();
+        loop {
+            let next = body(self, head.clone());
+            if next == head { break; }
+            head = next;
+        }
+        self.reporting = reporting;
+        if reporting {
+            body(self, head.clone());
+        }
+        self.break_targets.pop();
+        head
+    }
+
+    fn visit_stmt(&mut self, stmt: &St, mut live: Live) -> Live {
+        match *stmt {
+            St::Oops | St::KailuaOpen(..) | St::KailuaType(..) | St::KailuaClassSystem(..) |
+            St::KailuaInterface(..) | St::KailuaIgnore(..) => live,
+
+            St::Break => self.break_targets.last().cloned().unwrap_or(live),
+            St::Return(ref exps) => {
+                let mut live = Live::new();
+                for exp in &exps.base { uses(exp, &mut live); }
+                live
+            }
+            St::Void(ref exp) => { uses(exp, &mut live); live }
+            St::Assign(ref vars, ref exps) => {
+                let nexps = exps.as_ref().map_or(0, |exps| exps.base.len());
+                let multi = exps.as_ref().map_or(false, |exps| has_multiple_values(&exps.base));
+                for (i, var) in vars.base.iter().enumerate().rev() {
+                    if let Var::Name(Spanned { base: NameRef::Local(ref id), span }) =
+                        var.base.base
+                    {
+                        let id = id.clone().with_loc(span);
+                        if i < nexps || multi {
+                            self.assign(&id, &mut live);
+                        } else {
+                            live.remove(&id.base);
+                        }
+                    }
+                }
+                for var in &vars.base {
+                    match var.base.base {
+                        Var::Name(_) => {}
+                        Var::Index(ref e1, ref e2) => { uses(e1, &mut live); uses(e2, &mut live); }
+                        Var::IndexName(ref e, _) => uses(e, &mut live),
+                    }
+                }
+                if let Some(ref exps) = *exps {
+                    for exp in &exps.base { uses(exp, &mut live); }
+                }
+                live
+            }
+            St::Do(ref block) => self.visit_block(block, live),
+            St::While(ref cond, ref block) => {
+                self.fixpoint(&live, |this, head| {
+                    let mut head_ = this.visit_block(block, head);
+                    head_.extend(this.break_targets.last().unwrap().iter().cloned());
+                    uses(cond, &mut head_);
+                    head_
+                })
+            }
+            St::Repeat(ref block, ref cond) => {
+                self.fixpoint(&live, |this, head| {
+                    let mut end = head;
+                    end.extend(this.break_targets.last().unwrap().iter().cloned());
+                    uses(cond, &mut end);
+                    this.visit_block(block, end)
+                })
+            }
+            St::If(ref conds, ref lastblock) => {
+                let mut acc = match *lastblock {
+                    Some(ref block) => self.visit_block(block, live.clone()),
+                    None => live.clone(),
+                };
+                for cond in conds.iter().rev() {
+                    let (ref cond, ref block) = cond.base;
+                    acc.extend(self.visit_block(block, live.clone()));
+                    uses(cond, &mut acc);
+                }
+                acc
+            }
+            St::For(ref var, ref start, ref end, ref step, _, ref block) => {
+                let mut head = self.fixpoint(&live, |this, head| {
+                    let mut head_ = this.visit_block(block, head);
+                    head_.remove(&var.base);
+                    head_.extend(this.break_targets.last().unwrap().iter().cloned());
+                    head_
+                });
+                uses(start, &mut head);
+                uses(end, &mut head);
+                if let Some(ref step) = *step { uses(step, &mut head); }
+                head
+            }
+            St::ForIn(ref vars, ref exps, _, ref block) => {
+                let mut head = self.fixpoint(&live, |this, head| {
+                    let mut head_ = this.visit_block(block, head);
+                    for var in &vars.base { head_.remove(&var.base); }
+                    head_.extend(this.break_targets.last().unwrap().iter().cloned());
+                    head_
+                });
+                for exp in &exps.base { uses(exp, &mut head); }
+                head
+            }
+            St::FuncDecl(ref name, _, _, _, sibling_scope) => {
+                if let NameRef::Local(ref id) = name.base {
+                    if sibling_scope.is_some() {
+                        live.remove(id);
+                    } else {
+                        self.assign(&id.clone().with_loc(name.span), &mut live);
+                    }
+                }
+                live
+            }
+            St::MethodDecl(ref names, _, _, _, _) => {
+                if let NameRef::Local(ref id) = (names.base.0).base { live.insert(id.clone()); }
+                live
+            }
+            St::Local(ref names, ref exps, _) => {
+                let multi = has_multiple_values(&exps.base);
+                for (i, name) in names.base.iter().enumerate().rev() {
+                    let initialized = match exps.base.get(i) {
+                        // `local x = nil` is a common idiom for declaring a variable
+                        Some(exp) => if let Ex::Nil = *exp.base { false } else { true },
+                        None => multi,
+                    };
+                    if initialized {
+                        self.assign(&name.base, &mut live);
+                    } else {
+                        live.remove(&name.base.base);
+                    }
+                }
+                for exp in &exps.base { uses(exp, &mut live); }
+                live
+            }
+            St::KailuaAssume(_, ref rename, _, _, _) |
+            St::KailuaAssumeClass(_, ref rename, _, _, _) => {
+                if let NameRef::Local(ref id) = rename.base.before { live.insert(id.clone()); }
+                live
+            }
+            St::KailuaAssumeField(_, _, ref names, _, _) |
+            St::KailuaAssumeMethod(_, ref names, _, _) => {
+                if let NameRef::Local(ref id) = (names.base.0).base { live.insert(id.clone()); }
+                live
+            }
+            St::KailuaAssertImplements(ref name, _) => {
+                if let NameRef::Local(ref id) = name.base { live.insert(id.clone()); }
+                live
+            }
+        }
+    }
+}
+
+// true if the last expression can result in multiple values
+fn has_multiple_values(exps: &[Spanned<Exp>]) -> bool {
+    match exps.last().map(|exp| &*exp.base) {
+        Some(&Ex::FuncCall(..)) | Some(&Ex::MethodCall(..)) | Some(&Ex::Varargs) => true,
+        _ => false,
+    }
+}
+
+// adds local variables read by the expression, except for those in nested functions
+fn uses(exp: &Spanned<Exp>, live: &mut Live) {
+    fn args_uses(args: &Args, live: &mut Live) {
+        match *args {
+            Args::List(ref exps) => for exp in exps { uses(exp, live); },
+            Args::Str(_) => {}
+            Args::Table(ref table) => table_uses(table, live),
+        }
+    }
+
+    fn table_uses(table: &Table, live: &mut Live) {
+        for &(ref key, ref value) in &table.items {
+            if let Some(ref key) = *key { uses(key, live); }
+            uses(value, live);
+        }
+    }
+
+    match *exp.base {
+        Ex::Oops | Ex::Nil | Ex::False | Ex::True | Ex::Num(_) | Ex::Int(_) |
+        Ex::Str(_) | Ex::Varargs | Ex::Func(..) => {}
+
+        Ex::Table(ref table) => table_uses(table, live),
+        Ex::Var(ref name) => {
+            if let NameRef::Local(ref id) = name.base { live.insert(id.clone()); }
+        }
+        Ex::Exp(ref e) | Ex::IndexName(ref e, _) | Ex::Un(_, ref e) => uses(e, live),
+        Ex::FuncCall(ref e, ref args) => { uses(e, live); args_uses(args, live); }
+        Ex::MethodCall(ref e, ref args) => { uses(&e.base.0, live); args_uses(args, live); }
+        Ex::Index(ref e1, ref e2) | Ex::Bin(ref e1, _, ref e2) => {
+            uses(e1, live);
+            uses(e2, live);
+        }
+    }
+}
@@ -12,15 +12,89 @@ use std::str;
 use std::usize;
 use std::cell::RefCell;
 use std::rc::Rc;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use clap::{App, Arg, ArgMatches};
 use kailua_env::{Source, Span, Spanned};
 use kailua_diag::{Stop, Locale, Report, Reporter, TrackMaxKind};
-use kailua_syntax::{Chunk, parse_chunk};
-use kailua_types::ty::{TypeContext, Display};
+use kailua_syntax::{Name, Chunk, parse_chunk_with_language};
+use kailua_syntax::lang::{Language, Lua, Kailua};
+use kailua_syntax::ast::{Exp, Kind};
+use kailua_types::ty::{TypeContext, Display, CustomTagId, Slot, SpannedSlotSeq, TySeq, Ty, T};
 use kailua_check::check_from_chunk;
-use kailua_check::options::Options;
+use kailua_check::options::{Options, DynamicRequire};
+use kailua_check::options::{DEFAULT_LITERAL_UNION_LIMIT, DEFAULT_TABLE_DEPTH_LIMIT};
+use kailua_check::options::DEFAULT_CONSTRAINT_LIMIT;
+use kailua_check::lint::{Severity, find_lint};
 use kailua_check::env::Context;
+use kailua_check::custom_tag::CustomTag;
+use kailua_check::listener::CheckListener;
+
+// `[test_echo] function(...)` returns the types of its arguments
+struct EchoTag;
+
+impl CustomTag for EchoTag {
+    fn on_call(&self, _self_id: CustomTagId, argtys: &SpannedSlotSeq, _returns: TySeq,
+               _outerspan: Span, _ctx: &mut TypeContext,
+               _report: &Report) -> kailua_diag::Result<TySeq> {
+        Ok(argtys.clone().unlift().unspan())
+    }
+}
+
+// `[test_positive] number` only accepts positive integer literals
+struct PositiveTag;
+
+impl CustomTag for PositiveTag {
+    fn on_assign(&self, _self_id: CustomTagId, _lhs: &Spanned<Slot>, rhs: &Spanned<Slot>,
+                 _ctx: &mut TypeContext, report: &Report) -> kailua_diag::Result<bool> {
+        match rhs.unlift().as_integer() {
+            Some(v) if v > 0 => Ok(true),
+            _ => {
+                report.error(rhs, "Only positive integer literals can be assigned").done()?;
+                Ok(false)
+            }
+        }
+    }
+}
+
+// `flag:listener` reports string literals `"forbidden"`, `any` types,
+// the number of call arguments and reassignments
+struct TestListener;
+
+impl CheckListener for TestListener {
+    fn on_exp(&self, exp: &Spanned<Exp>, types: &SpannedSlotSeq,
+              _ctx: &mut TypeContext, report: &Report) -> kailua_diag::Result<()> {
+        if types.head.len() == 1 && types.tail.is_none() {
+            if let Some(s) = types.head[0].unlift().as_string() {
+                if &s[..] == b"forbidden" {
+                    report.warn(exp, "The string `forbidden` is not allowed").done()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn on_call(&self, _func: &Spanned<Ty>, argtys: &SpannedSlotSeq, outerspan: Span,
+               _ctx: &mut TypeContext, report: &Report) -> kailua_diag::Result<()> {
+        let msg = format!("Called with {} argument(s)", argtys.head.len());
+        report.warn(outerspan, &msg).done()
+    }
+
+    fn on_assign(&self, lhs: &Spanned<Slot>, _rhs: &Spanned<Slot>, init: bool,
+                 _ctx: &mut TypeContext, report: &Report) -> kailua_diag::Result<()> {
+        if !init {
+            report.warn(lhs, "Reassigned").done()?;
+        }
+        Ok(())
+    }
+
+    fn on_type(&self, kind: &Spanned<Kind>, ty: &Ty,
+               _ctx: &mut TypeContext, report: &Report) -> kailua_diag::Result<()> {
+        if let T::All = **ty {
+            report.warn(kind, "`any` is not allowed").done()?;
+        }
+        Ok(())
+    }
+}
 
 struct Testing {
     note_spanned_infos: bool,
@@ -48,7 +122,25 @@ impl kailua_test::Testing for Testing {
 
     fn run(&self, source: Rc<RefCell<Source>>, span: Span, filespans: &HashMap<String, Span>,
            report: Rc<Report>) -> String {
-        let chunk = match parse_chunk(&source.borrow(), span, &*report) {
+        self.run_with_flags(source, span, filespans, report, &HashSet::new())
+    }
+
+    fn run_with_flags(&self, source: Rc<RefCell<Source>>, span: Span,
+                      filespans: &HashMap<String, Span>, report: Rc<Report>,
+                      flags: &HashSet<String>) -> String {
+        // `flag:lua53` and so on change the Lua version for both parsing and checking
+        let lua = if flags.contains("lua54") {
+            Lua::Lua54
+        } else if flags.contains("lua53") {
+            Lua::Lua53
+        } else if flags.contains("lua52") {
+            Lua::Lua52
+        } else {
+            Lua::Lua51
+        };
+        let language = Language::new(lua, Kailua::Kailua10);
+
+        let chunk = match parse_chunk_with_language(&source.borrow(), span, language, &*report) {
             Ok(chunk) => chunk,
             Err(_) => return format!("parse error"),
         };
@@ -56,6 +148,14 @@ impl kailua_test::Testing for Testing {
         struct Opts {
             source: Rc<RefCell<Source>>,
             filespans: HashMap<String, Span>,
+            language: Language,
+            strict_nil: bool,
+            strict_globals: bool,
+            dynamic_require: DynamicRequire,
+            unused: bool,
+            shadowing: bool,
+            small_limits: bool,
+            flags: HashSet<String>,
         }
 
         impl Options for Opts {
@@ -63,13 +163,91 @@ impl kailua_test::Testing for Testing {
                              report: &Report) -> Result<Chunk, Option<Stop>> {
                 let path = str::from_utf8(&path).map_err(|_| None)?;
                 let span = *self.filespans.get(path).ok_or(None)?;
-                parse_chunk(&self.source.borrow(), span, report).map_err(|_| None)
+                parse_chunk_with_language(&self.source.borrow(), span, self.language, report)
+                    .map_err(|_| None)
+            }
+
+            fn lua_version(&self) -> Lua {
+                self.language.lua()
+            }
+
+            fn strict_nil(&self) -> bool {
+                self.strict_nil
+            }
+
+            fn strict_globals(&self) -> bool {
+                self.strict_globals
+            }
+
+            fn dynamic_require(&self) -> DynamicRequire {
+                self.dynamic_require.clone()
+            }
+
+            // `flag:small_limits` makes complexity limits small enough to be tested
+            fn literal_union_limit(&self) -> usize {
+                if self.small_limits { 3 } else { DEFAULT_LITERAL_UNION_LIMIT }
+            }
+
+            fn table_depth_limit(&self) -> usize {
+                if self.small_limits { 2 } else { DEFAULT_TABLE_DEPTH_LIMIT }
+            }
+
+            fn constraint_limit(&self) -> usize {
+                if self.small_limits { 20 } else { DEFAULT_CONSTRAINT_LIMIT }
+            }
+
+            // most tests declare locals only to inspect their types,
+            // so unused or shadowing variables are reported only with
+            // `flag:unused` and `flag:shadowing` respectively;
+            // any lint can be also enabled with a flag of the same name (`flag:empty-block`)
+            fn lint_severity(&self, code: &str) -> Severity {
+                match code {
+                    _ if self.flags.contains(code) => Severity::Warn,
+                    "unused-variable" | "unused-assignment" if !self.unused => Severity::Allow,
+                    "shadowing" if !self.shadowing => Severity::Allow,
+                    _ => find_lint(code).map_or(Severity::Warn, |lint| lint.default_severity),
+                }
+            }
+
+            // `flag:KC0123=error` overrides the severity of reports with given diagnostic code
+            fn code_severity(&self, code: &str) -> Option<Severity> {
+                self.flags.iter().filter_map(|flag| {
+                    let mut parts = flag.splitn(2, '=');
+                    match (parts.next(), parts.next()) {
+                        (Some(c), Some(severity)) if c == code => Severity::from_name(severity),
+                        _ => None,
+                    }
+                }).next()
             }
         }
 
+        // `flag:dynamic_require_one_of` allows every other file in the test
+        let dynamic_require = if flags.contains("dynamic_require_error") {
+            DynamicRequire::Error
+        } else if flags.contains("dynamic_require_one_of") {
+            let mut names: Vec<_> = filespans.keys().map(|k| k.as_bytes().to_owned()).collect();
+            names.sort();
+            DynamicRequire::OneOf(names)
+        } else {
+            DynamicRequire::Warn
+        };
+
         let report = Rc::new(TrackMaxKind::new(report));
-        let opts = Rc::new(RefCell::new(Opts { source: source, filespans: filespans.clone() }));
+        let opts = Rc::new(RefCell::new(Opts { source: source, filespans: filespans.clone(),
+                                               language: language,
+                                               strict_nil: flags.contains("strict_nil"),
+                                               strict_globals: flags.contains("strict_globals"),
+                                               dynamic_require: dynamic_require,
+                                               unused: flags.contains("unused"),
+                                               shadowing: flags.contains("shadowing"),
+                                               small_limits: flags.contains("small_limits"),
+                                               flags: flags.clone() }));
         let mut context = Context::new(report.clone());
+        context.add_custom_tag(Name::from(&b"test_echo"[..]), Box::new(EchoTag));
+        context.add_custom_tag(Name::from(&b"test_positive"[..]), Box::new(PositiveTag));
+        if flags.contains("listener") {
+            context.add_listener(Box::new(TestListener));
+        }
         let ret = check_from_chunk(&mut context, chunk, opts);
 
         // spanned information is available even on error
@@ -0,0 +1,86 @@
+//! Cooperative cancellation of long-running procedures.
+
+use std::fmt;
+use std::result;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use report::{Stop, Result};
+
+/// A shared flag to request an ongoing parsing or checking to stop as soon as possible.
+///
+/// The parser and the checker periodically look at the flag (`Parser::set_cancellation_token`
+/// and `Context::set_cancellation_token`) and stop with `Stop` once cancelled.
+/// Reports generated until then are partial and should be discarded;
+/// `CancellationToken::interrupted` distinguishes this case from the normal `Stop`.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken { cancelled: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Requests every procedure sharing this token to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns `Err(Stop)` if cancelled, so that it can be used with the `?` operator.
+    pub fn keep_going(&self) -> Result<()> {
+        if self.is_cancelled() { Err(Stop) } else { Ok(()) }
+    }
+
+    /// Converts the result of a procedure using this token,
+    /// so that the cancellation can be distinguished from the normal `Stop`.
+    ///
+    /// A successful result is kept even when the token has been cancelled afterwards.
+    pub fn interrupted<T>(&self, ret: Result<T>) -> result::Result<T, Interrupted> {
+        match ret {
+            Ok(v) => Ok(v),
+            Err(Stop) if self.is_cancelled() => Err(Interrupted::Cancelled),
+            Err(Stop) => Err(Interrupted::Stop),
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cancelled = self.is_cancelled();
+        write!(f, "<CancellationToken {}>", if cancelled { "on" } else { "off" })
+    }
+}
+
+/// The reason that a cancellable procedure has stopped.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Interrupted {
+    /// Stopped after the reporting, as with `Stop`. Reports are complete.
+    Stop,
+
+    /// Stopped due to `CancellationToken::cancel`. Reports are partial.
+    Cancelled,
+}
+
+impl From<Stop> for Interrupted {
+    fn from(_: Stop) -> Interrupted { Interrupted::Stop }
+}
+
+#[test]
+fn test_interrupted() {
+    let token = CancellationToken::new();
+    let other = token.clone();
+    assert_eq!(token.interrupted(Err::<(), _>(Stop)), Err(Interrupted::Stop));
+    assert_eq!(token.keep_going(), Ok(()));
+
+    other.cancel();
+    assert!(token.is_cancelled());
+    assert_eq!(token.keep_going(), Err(Stop));
+    assert_eq!(token.interrupted(Err::<(), _>(Stop)), Err(Interrupted::Cancelled));
+    assert_eq!(token.interrupted(Ok(42)), Ok(42));
+}
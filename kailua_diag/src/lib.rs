@@ -8,11 +8,13 @@ extern crate parse_generics_shim;
 #[cfg(windows)] extern crate kernel32;
 extern crate kailua_env;
 
-pub use message::{Locale, Localize, Localized};
+pub use message::{Locale, Localize, Localized, Coded};
 pub use report::{Kind, Stop, Result, Report, Reporter};
 pub use report::{ConsoleReport, CollectedReport, NoReport, TrackMaxKind};
+pub use cancel::{CancellationToken, Interrupted};
 
 pub mod message;
 pub mod report;
+pub mod cancel;
 mod dummy_term;
 
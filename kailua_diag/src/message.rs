@@ -23,11 +23,21 @@ use std::env;
 ///     _ => println!("*unintelligible gibberish*"),
 /// }
 /// ```
+///
+/// A locale may optionally have a chain of fallback locales (see `Locale::with_fallbacks`),
+/// which are consulted for each message that is not available in the primary locale.
+/// The dereferenced string is always the primary locale, so messages should be localized with
+/// `Locale::resolve` or `Locale::resolve_by` to take fallbacks into account.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Locale {
     lang: [u8; 8],
+    // empty (all-zero) entries are placed at the end
+    fallbacks: [[u8; 8]; MAX_FALLBACKS],
 }
 
+/// The maximum number of fallback locales per locale.
+pub const MAX_FALLBACKS: usize = 3;
+
 impl Locale {
     /// Parses and creates a new message locale.
     ///
@@ -62,12 +72,63 @@ impl Locale {
             return None;
         }
 
-        Some(Locale { lang: lang })
+        Some(Locale { lang: lang, fallbacks: [[0; 8]; MAX_FALLBACKS] })
     }
 
     /// A dummy locale (`xx`) used when no appropriate locale information is available.
     pub fn dummy() -> Locale {
-        Locale { lang: *b"xx\0\0\0\0\0\0" }
+        Locale { lang: *b"xx\0\0\0\0\0\0", fallbacks: [[0; 8]; MAX_FALLBACKS] }
+    }
+
+    /// Creates a locale from the chain of locales, the first being the primary locale.
+    ///
+    /// Fallbacks in given locales are ignored; only their primary locales are used.
+    /// Returns `None` if the chain is empty or has more than `MAX_FALLBACKS + 1` locales.
+    pub fn with_fallbacks(chain: &[Locale]) -> Option<Locale> {
+        if chain.is_empty() || chain.len() > MAX_FALLBACKS + 1 {
+            return None;
+        }
+
+        let mut locale = chain[0].primary();
+        for (fallback, l) in locale.fallbacks.iter_mut().zip(&chain[1..]) {
+            *fallback = l.lang;
+        }
+        Some(locale)
+    }
+
+    /// Returns the primary locale without any fallback.
+    pub fn primary(&self) -> Locale {
+        Locale { lang: self.lang, fallbacks: [[0; 8]; MAX_FALLBACKS] }
+    }
+
+    /// Returns the primary locale followed by fallback locales, if any.
+    pub fn chain(&self) -> Vec<Locale> {
+        let mut chain = vec![self.primary()];
+        for fallback in &self.fallbacks {
+            if fallback[0] == 0 { break; }
+            chain.push(Locale { lang: *fallback, fallbacks: [[0; 8]; MAX_FALLBACKS] });
+        }
+        chain
+    }
+
+    /// Returns the first locale in the chain for which `supported` returns true,
+    /// or the primary locale if there is no such locale.
+    ///
+    /// Default messages are written in English, so English locales are always supported.
+    /// The returned locale has no fallback.
+    pub fn resolve_by<F: Fn(&str) -> bool>(&self, supported: F) -> Locale {
+        for locale in self.chain() {
+            let is_english = &locale[..] == "en" || locale.starts_with("en-");
+            if is_english || supported(&locale) {
+                return locale;
+            }
+        }
+        self.primary()
+    }
+
+    /// Same to `Locale::resolve_by` but with a list of supported locales.
+    pub fn resolve(&self, supported: &[&str]) -> Locale {
+        self.resolve_by(|l| supported.contains(&l))
     }
 }
 
@@ -87,7 +148,11 @@ impl ops::Deref for Locale {
 
 impl fmt::Debug for Locale {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "<Locale {}>", &self[..])
+        write!(f, "<Locale {}", &self[..])?;
+        for fallback in &self.chain()[1..] {
+            write!(f, " > {}", &fallback[..])?;
+        }
+        write!(f, ">")
     }
 }
 
@@ -105,15 +170,67 @@ fn test_locale_names() {
     assert!(Locale::new("ko-KR-x-qqq").is_none());
 }
 
+#[test]
+fn test_locale_fallbacks() {
+    let en = Locale::from("en");
+    let ko = Locale::from("ko");
+    let ptbr = Locale::from("pt-br");
+
+    assert!(Locale::with_fallbacks(&[]).is_none());
+    assert!(Locale::with_fallbacks(&[en, en, en, en, en]).is_none());
+    assert_eq!(Locale::with_fallbacks(&[ko]), Some(ko));
+
+    let chain = Locale::with_fallbacks(&[ptbr, ko, en]).unwrap();
+    assert_eq!(&chain[..], "pt-br");
+    assert_ne!(chain, ptbr);
+    assert_eq!(chain.primary(), ptbr);
+    assert_eq!(chain.chain(), vec![ptbr, ko, en]);
+    assert_eq!(chain.resolve(&["ko"]), ko);
+    assert_eq!(chain.resolve(&["en", "ko"]), ko);
+    assert_eq!(chain.resolve(&["de"]), en);
+    assert_eq!(Locale::with_fallbacks(&[ptbr, ptbr]).unwrap().resolve(&["de"]), ptbr);
+    assert_eq!(format!("{:?}", chain), "<Locale pt-br > ko > en>");
+}
+
 /// Any type that can be formatted into a localized text.
 pub trait Localize: fmt::Debug {
     fn fmt_localized(&self, f: &mut fmt::Formatter, locale: Locale) -> fmt::Result;
+
+    /// Returns a stable diagnostic code like `KC0123` identifying this kind of messages,
+    /// independent to the locale. Defaults to `None`.
+    fn code(&self) -> Option<&'static str> {
+        None
+    }
 }
 
 impl<'a> Localize for &'a Localize {
     fn fmt_localized(&self, f: &mut fmt::Formatter, locale: Locale) -> fmt::Result {
         (**self).fmt_localized(f, locale)
     }
+
+    fn code(&self) -> Option<&'static str> {
+        (**self).code()
+    }
+}
+
+/// A message with an explicitly given diagnostic code.
+///
+/// This is mainly useful for relaying already localized messages (e.g. from `CollectedReport`)
+/// without losing their codes.
+#[derive(Clone, Debug)]
+pub struct Coded<T> {
+    pub code: Option<&'static str>,
+    pub msg: T,
+}
+
+impl<T: Localize> Localize for Coded<T> {
+    fn fmt_localized(&self, f: &mut fmt::Formatter, locale: Locale) -> fmt::Result {
+        self.msg.fmt_localized(f, locale)
+    }
+
+    fn code(&self) -> Option<&'static str> {
+        self.code
+    }
 }
 
 impl<T: fmt::Display + fmt::Debug> Localize for T {
@@ -157,6 +274,24 @@ macro_rules! define_msg_internal {
         write!($f, $format $($tail)*)
     );
 
+    (@gen_code) => (None);
+    (@gen_code $code:tt) => (Some($code));
+
+    // true if the locale string matches to any arm other than the last (catch-all) one
+    (@is_explicit $l:ident; $($locale:pat),*) => ({
+        let mut narms = 0usize;
+        let mut matched = None;
+        $(
+            #[allow(unreachable_patterns)]
+            match $l {
+                $locale if matched.is_none() => { matched = Some(narms); }
+                _ => {}
+            }
+            narms += 1;
+        )*
+        matched.map_or(false, |arm| arm + 1 < narms)
+    });
+
     (
         $name:ident
         ($($prefix:tt)*)
@@ -167,7 +302,8 @@ macro_rules! define_msg_internal {
         },
         $({
             $($fname:ident: $ftype:ty),* $(,)*
-        })*:
+        })*
+        $(= $code:tt)*:
         $($locale:pat => $format:tt),* $(,)*
     ) => (
         // since `$($constr)*` and `$($params)*` have to be expanded lazily,
@@ -184,11 +320,20 @@ macro_rules! define_msg_internal {
             impl<$($constr)*> $crate::Localize for $name<$($params)*> {
                 fn fmt_localized(&self, f: &mut ::std::fmt::Formatter,
                                  locale: ::kailua_diag::Locale) -> ::std::fmt::Result {
+                    // use the first locale in the chain for which this message is localized
+                    let resolved = locale.resolve_by(|l| {
+                        define_msg_internal!(@is_explicit l; $($locale),*)
+                    });
+
                     // "tt bundling" as in http://stackoverflow.com/a/37754096
-                    define_msg_internal!(@gen_match f, locale;
+                    define_msg_internal!(@gen_match f, resolved;
                         $($locale => $format),*;
                         ($($(, $fname = $crate::Localized::new(&self.$fname, locale))*)*))
                 }
+
+                fn code(&self) -> Option<&'static str> {
+                    define_msg_internal!(@gen_code $($code)*)
+                }
             }
         }
     );
@@ -206,6 +351,8 @@ macro_rules! define_msg_internal {
 ///
 /// Each parameter should be localizable.
 /// `pub` and the parameters can be omitted, and type or lifetime parameters can be given.
+/// A diagnostic code (see `Localize::code`) can be given before the colon,
+/// as in `define_msg! { pub StructName { ... } = "KC0123": ... }`.
 /// (But note that the constructor itself is a struct, so `StructName {}` is required
 /// even when there are no message parameters.)
 ///
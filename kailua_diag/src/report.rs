@@ -12,7 +12,7 @@ use kailua_env::{Source, SourceSlice, Span, Pos};
 
 use dummy_term::{stderr_or_dummy};
 use term::{color, StderrTerminal};
-use message::{Locale, Localize, Localized, get_message_locale};
+use message::{Locale, Localize, Localized, Coded, get_message_locale};
 
 /// The diagnostic category.
 ///
@@ -379,6 +379,11 @@ impl Report for ConsoleReport {
         let _ = write!(term, "[");
         let _ = term.fg(bright);
         let _ = write!(term, "{:?}", kind);
+        // codes are omitted for notes and causes, which always follow other reports
+        if let (Some(code), false) = (msg.code(), kind == Kind::Note || kind == Kind::Cause) {
+            let _ = term.fg(dim);
+            let _ = write!(term, " {}", code);
+        }
         let _ = term.fg(dim);
         let _ = write!(term, "] ");
         let _ = term.fg(color::BRIGHT_WHITE);
@@ -525,7 +530,7 @@ impl Report for ConsoleReport {
 ///
 /// Note that the message itself is localized at the report time, so the locale is still required.
 pub struct CollectedReport {
-    collected: RefCell<Vec<(Kind, Span, String)>>,
+    collected: RefCell<Vec<(Kind, Span, Coded<String>)>>,
     locale: Locale,
 }
 
@@ -535,6 +540,11 @@ impl CollectedReport {
     }
 
     pub fn into_reports(self) -> Vec<(Kind, Span, String)> {
+        self.collected.into_inner().into_iter().map(|(k, s, m)| (k, s, m.msg)).collect()
+    }
+
+    /// Same to `into_reports` but retains diagnostic codes, so that reports can be relayed.
+    pub fn into_coded_reports(self) -> Vec<(Kind, Span, Coded<String>)> {
         self.collected.into_inner()
     }
 }
@@ -545,8 +555,8 @@ impl Report for CollectedReport {
     }
 
     fn add_span(&self, kind: Kind, span: Span, msg: &Localize) -> Result<()> {
-        let msg = Localized::new(msg, self.locale).to_string();
-        self.collected.borrow_mut().push((kind, span, msg));
+        let text = Localized::new(msg, self.locale).to_string();
+        self.collected.borrow_mut().push((kind, span, Coded { code: msg.code(), msg: text }));
         if kind == Kind::Fatal { Err(Stop) } else { Ok(()) }
     }
 }
@@ -244,7 +244,11 @@ impl<Name: Clone + Hash + Eq + fmt::Debug> ScopeMap<Name> {
 
     pub fn set_span(&mut self, scope: Spanned<Scope>) {
         assert!((scope.base.scope as usize) < self.scopes.len());
-        assert!(scope.span.is_source_dependent());
+        if !scope.span.is_source_dependent() {
+            // scopes from the code without a source (e.g. built-in definitions)
+            // cannot be found by positions, but are otherwise usable
+            return;
+        }
 
         let scopespan = &mut self.scopes[scope.base.scope as usize].span;
         assert!(scopespan.is_dummy(), "scope {:?} has already set the span", scope.base);
@@ -4,6 +4,7 @@ use std::hash;
 use std::sync::Arc;
 use std::collections::HashSet;
 use parking_lot::{Mutex, MutexGuard};
+use serde_json::Value;
 
 use kailua_env::{Pos, Span, Source, SourceFile, SourceSlice};
 use kailua_diag::{self, Kind, Report, Locale, Localize, Localized};
@@ -162,6 +163,7 @@ impl<F> Report for ReportTreeReport<F>
     }
 
     fn add_span(&self, kind: Kind, span: Span, msg: &Localize) -> kailua_diag::Result<()> {
+        let code = msg.code().map(|code| Value::String(code.to_owned()));
         let msg = Localized::new(msg, self.inner.locale).to_string();
 
         // TODO span should be translated _after_ deciding whether to put a new diagnostic,
@@ -200,7 +202,7 @@ impl<F> Report for ReportTreeReport<F>
                 // otherwise report normally
                 collected.push((path, Diagnostic {
                     range: range, severity: Some(severity),
-                    code: None, source: None, message: msg,
+                    code: code, source: None, message: msg,
                 }));
             }
         }
@@ -4,6 +4,7 @@ use futures::{Future, Poll};
 use futures::future::Shared;
 use futures::sync::oneshot::{self, Receiver, Sender};
 use parking_lot::Mutex;
+use kailua_diag::CancellationToken;
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum CancelError<T> {
@@ -35,6 +36,7 @@ impl<T> From<T> for CancelError<T> {
 struct CancelTokenInner {
     sender: Mutex<Option<Sender<()>>>,
     receiver: Shared<Receiver<()>>,
+    flag: CancellationToken, // for the parser and the checker
 }
 
 #[derive(Clone)]
@@ -53,6 +55,7 @@ impl CancelToken {
         CancelToken(Arc::new(CancelTokenInner {
             sender: Mutex::new(Some(sender)),
             receiver: receiver.shared(),
+            flag: CancellationToken::new(),
         }))
     }
 
@@ -68,6 +71,11 @@ impl CancelToken {
         }
     }
 
+    /// Returns a token for the parser and the checker, cancelled along with this token.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.0.flag.clone()
+    }
+
     pub fn future(&self) -> CancelFuture {
         CancelFuture { receiver: self.0.receiver.clone() }
     }
@@ -75,6 +83,7 @@ impl CancelToken {
     pub fn cancel(&self) -> bool {
         if let Some(sender) = self.0.sender.lock().take() {
             // guaranteed to be entered by only one thread
+            self.0.flag.cancel();
             let _ = sender.send(());
             true
         } else {
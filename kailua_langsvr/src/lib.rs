@@ -33,6 +33,7 @@ mod workspace;
 mod futureutils;
 mod message;
 mod ops;
+mod uri;
 
 use std::io;
 use std::net::SocketAddr;
@@ -46,14 +47,19 @@ use futureutils::{CancelToken, CancelError};
 use diags::ReportTree;
 use workspace::{Workspace, WorkspaceFile};
 
+pub use uri::{UriMapper, FileUriMapper};
+
 // initialization options supposed to be sent from the extension
 struct InitOptions {
     default_locale: kailua_diag::Locale,
+
+    // same to `kailua check --cache`, writes caches to `BASE_DIR/.kailua-cache` if true
+    cache: bool,
 }
 
 impl Default for InitOptions {
     fn default() -> InitOptions {
-        InitOptions { default_locale: kailua_diag::Locale::from("en") }
+        InitOptions { default_locale: kailua_diag::Locale::from("en"), cache: false }
     }
 }
 
@@ -61,12 +67,14 @@ fn parse_init_options(opts: Option<serde_json::Value>) -> InitOptions {
     #[derive(Deserialize)]
     struct Options {
         default_locale: String,
+        #[serde(default)]
+        cache: bool,
     }
 
     if let Some(opts) = opts {
         if let Ok(opts) = serde_json::from_value::<Options>(opts) {
             if let Some(locale) = kailua_diag::Locale::new(&opts.default_locale) {
-                return InitOptions { default_locale: locale };
+                return InitOptions { default_locale: locale, cache: opts.cache };
             }
         }
     }
@@ -76,7 +84,6 @@ fn parse_init_options(opts: Option<serde_json::Value>) -> InitOptions {
 // both show the message (more visible but will be dismissed after a few seconds)
 // and the diagnostics (less visible but will last for this session)
 fn cannot_read_config(workspace: &Workspace, server: &Server, msg: &kailua_diag::Localize) {
-    use url::Url;
     use protocol::*;
 
     let _ = server.send_notify(
@@ -87,11 +94,12 @@ fn cannot_read_config(workspace: &Workspace, server: &Server, msg: &kailua_diag:
         },
     );
 
-    if let Ok(uri) = Url::from_file_path(&workspace.config_path_or_default()) {
+    let config_path = workspace.config_path_or_default().display().to_string();
+    if let Some(uri) = workspace.uri_mapper().path_to_uri(&config_path) {
         let _ = server.send_notify(
             Method::PublishDiagnostics,
             PublishDiagnosticsParams {
-                uri: uri.to_string(),
+                uri: uri,
                 diagnostics: vec![
                     protocol::Diagnostic {
                         range: protocol::Range {
@@ -109,7 +117,7 @@ fn cannot_read_config(workspace: &Workspace, server: &Server, msg: &kailua_diag:
     }
 }
 
-fn initialize_workspace(server: &Server) -> Workspace {
+fn initialize_workspace(server: &Server, uri_mapper: Arc<UriMapper>) -> Workspace {
     use std::path::PathBuf;
     use futures_cpupool::CpuPool;
     use protocol::*;
@@ -136,7 +144,9 @@ fn initialize_workspace(server: &Server) -> Workspace {
                     let pool = Arc::new(CpuPool::new(nworkers));
 
                     let mut workspace = Workspace::new(PathBuf::from(dir), pool.clone(),
-                                                       initopts.default_locale);
+                                                       initopts.default_locale,
+                                                       initopts.cache,
+                                                       uri_mapper.clone());
 
                     // try to read the config...
                     let config_read = workspace.read_config();
@@ -202,10 +212,9 @@ fn initialize_workspace(server: &Server) -> Workspace {
     }
 }
 
-fn send_diagnostics(server: Server, root: &ReportTree) -> io::Result<()> {
-    use std::path::Path;
+fn send_diagnostics(server: Server, uri_mapper: &UriMapper,
+                    root: &ReportTree) -> io::Result<()> {
     use std::collections::HashMap;
-    use url::Url;
     use protocol::*;
 
     // try to deduplicate diagnostics from different paths.
@@ -252,11 +261,13 @@ fn send_diagnostics(server: Server, root: &ReportTree) -> io::Result<()> {
     }
 
     for (path, diags) in diags.into_iter() {
-        let uri = Url::from_file_path(&Path::new(&path)).expect("no absolute path");
-        server.send_notify(
-            Method::PublishDiagnostics,
-            PublishDiagnosticsParams { uri: uri.to_string(), diagnostics: diags }
-        )?;
+        // the mapper may refuse to map some paths, which are silently ignored
+        if let Some(uri) = uri_mapper.path_to_uri(&path) {
+            server.send_notify(
+                Method::PublishDiagnostics,
+                PublishDiagnosticsParams { uri: uri, diagnostics: diags }
+            )?;
+        }
     }
 
     Ok(())
@@ -264,6 +275,7 @@ fn send_diagnostics(server: Server, root: &ReportTree) -> io::Result<()> {
 
 fn send_diagnostics_when_available<T, F>(server: Server,
                                          pool: &futures_cpupool::CpuPool,
+                                         uri_mapper: Arc<UriMapper>,
                                          fut: futures::future::Shared<F>)
     where T: Send + Sync + 'static,
           F: Send + 'static + Future<Item=(T, ReportTree), Error=CancelError<ReportTree>>
@@ -276,7 +288,7 @@ fn send_diagnostics_when_available<T, F>(server: Server,
                 CancelError::Error(ref diags) => diags,
             },
         };
-        send_diagnostics(server, diags)
+        send_diagnostics(server, &*uri_mapper, diags)
     });
 
     // this should be forgotten as we won't make use of its result
@@ -284,9 +296,11 @@ fn send_diagnostics_when_available<T, F>(server: Server,
     pool.spawn(fut).forget();
 }
 
-fn on_file_changed(file: &WorkspaceFile, server: Server, pool: &futures_cpupool::CpuPool) {
-    send_diagnostics_when_available(server.clone(), pool, file.ensure_tokens());
-    send_diagnostics_when_available(server, pool, file.ensure_chunk());
+fn on_file_changed(file: &WorkspaceFile, server: Server, pool: &futures_cpupool::CpuPool,
+                   uri_mapper: &Arc<UriMapper>) {
+    send_diagnostics_when_available(server.clone(), pool, uri_mapper.clone(),
+                                    file.ensure_tokens());
+    send_diagnostics_when_available(server, pool, uri_mapper.clone(), file.ensure_chunk());
 }
 
 // in the reality, the "loop" is done via a chain of futures and the function immediately returns
@@ -314,13 +328,14 @@ fn checking_loop(server: Server, workspace: Arc<RwLock<Workspace>>,
         let outputs_fut = workspace.read().ensure_combined_check_outputs();
         if let Ok(fut) = outputs_fut {
             let server_ = server.clone();
+            let uri_mapper = workspace.read().uri_mapper().clone();
             fut.then(move |res| {
                 debug!("background checking has finished ({})",
                        if res.is_ok() { "ok" } else { "err" });
 
                 // send diagnostics for this check
                 if let Ok(ref value_and_diags) = res {
-                    let _ = send_diagnostics(server_, &value_and_diags.1);
+                    let _ = send_diagnostics(server_, &*uri_mapper, &value_and_diags.1);
                 }
                 Ok(())
             }).and_then(move |_| {
@@ -391,7 +406,7 @@ fn main_loop(server: Server, workspace: Arc<RwLock<Workspace>>) {
 
                 let pool = ws.pool().clone();
                 let file = ws.file(&uri).unwrap();
-                on_file_changed(&file, server.clone(), &pool);
+                on_file_changed(&file, server.clone(), &pool, ws.uri_mapper());
             }
 
             Received::Notification(Notification::DidChangeTextDocument(params)) => {
@@ -410,7 +425,7 @@ fn main_loop(server: Server, workspace: Arc<RwLock<Workspace>>) {
                     }
                     try_or_notify!(e);
 
-                    on_file_changed(&file, server.clone(), &pool);
+                    on_file_changed(&file, server.clone(), &pool, ws.uri_mapper());
                 }
                 trace!("workspace: {:#?}", *ws);
             }
@@ -695,9 +710,7 @@ fn signature(server: Server, workspace: Arc<RwLock<Workspace>>, id: protocol::Id
 
 fn definition(server: Server, workspace: Arc<RwLock<Workspace>>, id: protocol::Id,
               file: WorkspaceFile, cancel_token: CancelToken, position: &protocol::Position) {
-    use std::path::Path;
     use futures::{future, stream, Stream};
-    use url::Url;
     use kailua_env::{Span, Source};
     use kailua_syntax::ast::NameRef;
     use ops::definition;
@@ -716,12 +729,13 @@ fn definition(server: Server, workspace: Arc<RwLock<Workspace>>, id: protocol::I
         let class = definition::classify(&tokens.0, &chunk.0, pos);
         debug!("definition: {:?} {:#?}", class, pos);
 
-        fn send_spans(server: Server, id: protocol::Id, spans: &[Span], source: &Source) {
+        fn send_spans(server: Server, id: protocol::Id, spans: &[Span],
+                      source: &Source, uri_mapper: &UriMapper) {
             debug!("definition spans: {:#?}", spans);
             let locs: Vec<_> = spans.iter().filter_map(|&span| {
                 diags::translate_span(span, source).and_then(|(path, range)| {
-                    Url::from_file_path(Path::new(&path)).ok().map(|url| {
-                        Location { uri: url.to_string(), range: range }
+                    uri_mapper.path_to_uri(&path).map(|uri| {
+                        Location { uri: uri, range: range }
                     })
                 })
             }).collect();
@@ -733,9 +747,9 @@ fn definition(server: Server, workspace: Arc<RwLock<Workspace>>, id: protocol::I
         match class {
             Some(definition::Class::Var(_, NameRef::Local(scoped_id))) => {
                 if let Some(span) = definition::local_var_definition(&chunk.0, &scoped_id) {
-                    send_spans(server, id, &[span], &ws.source());
+                    send_spans(server, id, &[span], &ws.source(), &**ws.uri_mapper());
                 } else {
-                    send_spans(server, id, &[], &ws.source());
+                    send_spans(server, id, &[], &ws.source(), &**ws.uri_mapper());
                 }
                 future::ok(()).boxed()
             },
@@ -758,14 +772,14 @@ fn definition(server: Server, workspace: Arc<RwLock<Workspace>>, id: protocol::I
                 spans_fut.map_err(|e| e.as_ref().map(|_| ())).and_then(move |spans| {
                     cancel_token.keep_going()?;
                     let ws = spare_workspace.read();
-                    send_spans(server, id, &spans, &ws.source());
+                    send_spans(server, id, &spans, &ws.source(), &**ws.uri_mapper());
                     Ok(())
                 }).boxed()
             },
 
             // XXX PossiblyRequire depends on package.path/cpath, which can change in runtime
             Some(definition::Class::PossiblyRequire(_, _, _)) | None => {
-                send_spans(server, id, &[], &ws.source());
+                send_spans(server, id, &[], &ws.source(), &**ws.uri_mapper());
                 future::ok(()).boxed()
             },
         }
@@ -778,9 +792,7 @@ fn rename(server: Server, workspace: Arc<RwLock<Workspace>>, id: protocol::Id,
           file: WorkspaceFile, cancel_token: CancelToken,
           position: &protocol::Position, new_name: String) {
     use std::collections::HashMap;
-    use std::path::Path;
     use futures::{future, stream, Stream};
-    use url::Url;
     use kailua_env::{Span, Source};
     use kailua_syntax::ast::NameRef;
     use ops::definition;
@@ -800,7 +812,7 @@ fn rename(server: Server, workspace: Arc<RwLock<Workspace>>, id: protocol::Id,
         debug!("rename: {:?} {:#?}", class, pos);
 
         fn send_spans(server: Server, id: protocol::Id, spans: &[Span],
-                      new_name: String, source: &Source) {
+                      new_name: String, source: &Source, uri_mapper: &UriMapper) {
             debug!("rename spans: {:#?}", spans);
 
             let mut spansmap = HashMap::new();
@@ -813,13 +825,13 @@ fn rename(server: Server, workspace: Arc<RwLock<Workspace>>, id: protocol::Id,
 
             let changes = spansmap.into_iter().flat_map(|(_unit, (file, spans))| {
                 file.and_then(|file| {
-                    Url::from_file_path(Path::new(file.path())).ok().map(|url| {
+                    uri_mapper.path_to_uri(file.path()).map(|uri| {
                         let edits: Vec<_> = spans.into_iter().filter_map(|span| {
                             diags::translate_span_without_path(span, file).map(|range| {
                                 TextEdit { range: range, newText: new_name.clone() }
                             })
                         }).collect();
-                        (url.to_string(), edits)
+                        (uri, edits)
                     })
                 })
             }).collect();
@@ -833,7 +845,7 @@ fn rename(server: Server, workspace: Arc<RwLock<Workspace>>, id: protocol::Id,
         match class {
             Some(definition::Class::Var(_, NameRef::Local(scoped_id))) => {
                 let spans = definition::local_var_uses(&tokens.0, &chunk.0, &scoped_id);
-                send_spans(server, id, &spans, new_name, &ws.source());
+                send_spans(server, id, &spans, new_name, &ws.source(), &**ws.uri_mapper());
                 future::ok(()).boxed()
             },
 
@@ -858,7 +870,7 @@ fn rename(server: Server, workspace: Arc<RwLock<Workspace>>, id: protocol::Id,
                 spans_fut.map_err(|e| e.as_ref().map(|_| ())).and_then(move |spans| {
                     cancel_token.keep_going()?;
                     let ws = spare_workspace.read();
-                    send_spans(server, id, &spans, new_name, &ws.source());
+                    send_spans(server, id, &spans, new_name, &ws.source(), &**ws.uri_mapper());
                     Ok(())
                 }).boxed()
             },
@@ -871,7 +883,7 @@ fn rename(server: Server, workspace: Arc<RwLock<Workspace>>, id: protocol::Id,
                         message: ws.localize(&message::CannotRename {}).to_string(),
                     },
                 );
-                send_spans(server, id, &[], new_name, &ws.source());
+                send_spans(server, id, &[], new_name, &ws.source(), &**ws.uri_mapper());
                 future::ok(()).boxed()
             },
         }
@@ -898,6 +910,11 @@ pub enum Target {
 /// This may return early if it couldn't connect to the target.
 /// Also returns after receiving a shutdown request.
 pub fn main(target: Target) -> io::Result<()> {
+    main_with_uri_mapper(target, Arc::new(FileUriMapper))
+}
+
+/// Same to `main` but URIs from and to the client are mapped with given `UriMapper`.
+pub fn main_with_uri_mapper(target: Target, uri_mapper: Arc<UriMapper>) -> io::Result<()> {
     use std::net::TcpStream;
 
     info!("starting kailua_langsvr {}",
@@ -912,7 +929,7 @@ pub fn main(target: Target) -> io::Result<()> {
     };
     info!("established connection");
 
-    let workspace = Arc::new(RwLock::new(initialize_workspace(&server)));
+    let workspace = Arc::new(RwLock::new(initialize_workspace(&server, uri_mapper)));
     info!("initialized workspace, starting a main loop");
 
     main_loop(server, workspace);
@@ -34,9 +34,13 @@ pub fn help<F>(outputs: &[Arc<Output>], pos: Pos, source: &Source, mut localize:
                 "\u{200c}\u{200d}\u{200d}\u{200c}\u{2060}\u{200c}\u{200b}\u{200d}\
                  \u{200c}\u{200c}\u{200d}\u{200b}\u{2060}\u{200d}\u{2060}\u{2060}";
 
+            // records and unions larger than this are truncated
+            const MAX_WIDTH: usize = 32;
+
             let range = diags::translate_span(slot.span, source).map(|(_, range)| range);
             let types = output.types() as &TypeContext;
-            let value = format!("{}{:0.1}", TYPE_PREFIX, localize(&slot.display(types)));
+            let displayed = slot.display(types).with_max_width(MAX_WIDTH);
+            let value = format!("{}{:0.1}", TYPE_PREFIX, localize(&displayed));
             if seen.insert(value.clone()) {
                 // XXX currently this can differ throughout the outputs
                 hover_range = Some(range);
@@ -0,0 +1,81 @@
+//! Mapping between client URIs and paths used in the workspace.
+
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// A mapping between URIs (as seen by the client) and paths (as seen by the checker).
+///
+/// Not every checked file lives at a real filesystem path;
+/// an embedder can supply its own mapper to handle e.g. archive-internal paths or
+/// unsaved editor buffers. The resulting path is used as a key to the workspace file
+/// and also as a path for `SourceFile`, so it will be given back to `path_to_uri`.
+pub trait UriMapper: Send + Sync {
+    /// Converts a URI from the client to a path, or returns a reason if it cannot be handled.
+    fn uri_to_path(&self, uri: &str) -> Result<PathBuf, &'static str>;
+
+    /// Converts a path from the checker (e.g. `SourceFile::path`) to a URI,
+    /// or returns `None` if it cannot be sent to the client.
+    fn path_to_uri(&self, path: &str) -> Option<String>;
+}
+
+/// The default `UriMapper` which only recognizes `file:` URIs.
+#[derive(Clone, Debug)]
+pub struct FileUriMapper;
+
+impl UriMapper for FileUriMapper {
+    fn uri_to_path(&self, uri: &str) -> Result<PathBuf, &'static str> {
+        file_uri_to_path(uri)
+    }
+
+    fn path_to_uri(&self, path: &str) -> Option<String> {
+        Url::from_file_path(Path::new(path)).ok().map(|url| url.to_string())
+    }
+}
+
+pub fn file_uri_to_path(uri: &str) -> Result<PathBuf, &'static str> {
+    let url = Url::parse(uri).map_err(|_| "invalid URI")?;
+    if url.scheme() != "file" {
+        return Err("non-file URI");
+    }
+    if let Ok(path) = url.to_file_path() {
+        return Ok(path);
+    }
+
+    #[cfg(windows)]
+    {
+        use std::ffi::OsString;
+        use std::path::Component;
+        use url::Host;
+
+        // Url::to_file_path only handles no host or localhost, which is different from vscode-uri
+        // we first try localhost then retry by temporarily setting the authority part on windows
+        let host = match url.host() {
+            Some(Host::Domain(name)) => name.to_string(),
+            Some(Host::Ipv4(addr)) => addr.to_string(),
+            Some(Host::Ipv6(addr)) => {
+                // an "official" hack for UNC
+                // https://msdn.microsoft.com/en-us/library/aa385353.aspx
+                let s = &addr.segments();
+                format!("{:x}-{:x}-{:x}-{:x}-{:x}-{:x}-{:x}-{:x}.ipv6-literal.net",
+                        s[0], s[1], s[2], s[3], s[4], s[5], s[6], s[7])
+            },
+            None => return Err("non-file URI"),
+        };
+
+        // convert file://host/path to file:///z:/path (z: is required for rust-url)
+        let url = Url::parse(&format!("file:///z:{}", url.path())).unwrap();
+        if let Ok(path) = url.to_file_path() {
+            // now path starts with z:\foo\bar, so replace z: by \\host to complete it
+            let mut components = path.components();
+            let _prefix = components.next();
+            assert!(match _prefix { Some(Component::Prefix(..)) => true, _ => false });
+
+            let mut pathstr = OsString::from("\\\\");
+            pathstr.push(&host);
+            pathstr.push(components.as_path());
+            return Ok(PathBuf::from(pathstr));
+        }
+    }
+
+    Err("non-file URI")
+}
@@ -3,84 +3,42 @@ use std::fmt;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::ffi::OsStr;
-use std::collections::{hash_map, HashMap};
+use std::collections::{hash_map, HashMap, HashSet};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use futures::{future, stream, Future, Stream, BoxFuture};
 use futures_cpupool::CpuPool;
-use url::Url;
 use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use walkdir::WalkDir;
 
 use kailua_env::{Unit, Pos, Span, Spanned, Source, SourceFile, SourceSlice};
 use kailua_diag::{self, Stop, Report, Locale, Localize, Localized};
 use kailua_syntax::{Lexer, Nest, NestedToken, Parser, Chunk};
+use kailua_syntax::lang::Language;
 use kailua_check;
 use kailua_check::options::FsSource;
 use kailua_check::env::{Context, Output};
 use kailua_workspace::{self, WorkspaceOptions};
+use kailua_workspace::graph::RecordingOptions;
+use kailua_workspace::incremental::{self, IncrementalState, IncrementalSource};
+use kailua_workspace::incremental::TrackReportedUnits;
+use kailua_workspace::preparse::History;
 
 use fmtutils::Ellipsis;
 use diags::{self, ReportTree};
 use futureutils::{CancelError, CancelToken, CancelFuture};
 use message as m;
 use protocol;
+use uri::UriMapper;
 
 #[derive(Clone, Debug)]
 pub struct WorkspaceError(pub &'static str);
 
 pub type WorkspaceResult<T> = Result<T, WorkspaceError>;
 
-fn uri_to_path(uri: &str) -> WorkspaceResult<PathBuf> {
-    let url = Url::parse(uri).map_err(|_| WorkspaceError("invalid URI"))?;
-    if url.scheme() != "file" {
-        return Err(WorkspaceError("non-file URI"));
-    }
-    if let Ok(path) = url.to_file_path() {
-        return Ok(path);
-    }
-
-    #[cfg(windows)]
-    {
-        use std::ffi::OsString;
-        use std::path::Component;
-        use url::Host;
-
-        // Url::to_file_path only handles no host or localhost, which is different from vscode-uri
-        // we first try localhost then retry by temporarily setting the authority part on windows
-        let host = match url.host() {
-            Some(Host::Domain(name)) => name.to_string(),
-            Some(Host::Ipv4(addr)) => addr.to_string(),
-            Some(Host::Ipv6(addr)) => {
-                // an "official" hack for UNC
-                // https://msdn.microsoft.com/en-us/library/aa385353.aspx
-                let s = &addr.segments();
-                format!("{:x}-{:x}-{:x}-{:x}-{:x}-{:x}-{:x}-{:x}.ipv6-literal.net",
-                        s[0], s[1], s[2], s[3], s[4], s[5], s[6], s[7])
-            },
-            None => return Err(WorkspaceError("non-file URI")),
-        };
-
-        // convert file://host/path to file:///z:/path (z: is required for rust-url)
-        let url = Url::parse(&format!("file:///z:{}", url.path())).unwrap();
-        if let Ok(path) = url.to_file_path() {
-            // now path starts with z:\foo\bar, so replace z: by \\host to complete it
-            let mut components = path.components();
-            let _prefix = components.next();
-            assert!(match _prefix { Some(Component::Prefix(..)) => true, _ => false });
-
-            let mut pathstr = OsString::from("\\\\");
-            pathstr.push(&host);
-            pathstr.push(components.as_path());
-            return Ok(PathBuf::from(pathstr));
-        }
-    }
-
-    Err(WorkspaceError("non-file URI"))
-}
-
 fn position_to_pos(file: &SourceFile, pos: &protocol::Position) -> Pos {
     if let Some(mut span) = file.line_spans().nth(pos.line as usize) {
         let begin = span.begin().to_usize();
@@ -119,10 +77,11 @@ fn position_to_pos(file: &SourceFile, pos: &protocol::Position) -> Pos {
     }
 }
 
-fn collect_tokens(source: &Source, span: Span, report: &Report) -> Vec<NestedToken> {
+fn collect_tokens(source: &Source, span: Span, language: Language,
+                  report: &Report) -> Vec<NestedToken> {
     let mut iter = source.iter_from_span(span).unwrap();
     let tokens = {
-        let mut lexer = Lexer::new(&mut iter, report);
+        let mut lexer = Lexer::with_language(&mut iter, language, report);
         let nest = Nest::new(&mut lexer);
         nest.collect::<Vec<_>>()
     };
@@ -130,9 +89,12 @@ fn collect_tokens(source: &Source, span: Span, report: &Report) -> Vec<NestedTok
     tokens
 }
 
-fn parse_to_chunk(tokens: Vec<NestedToken>, report: &Report) -> kailua_diag::Result<Chunk> {
+fn parse_to_chunk(tokens: Vec<NestedToken>, language: Language, report: &Report,
+                  cancel_token: &CancelToken) -> kailua_diag::Result<Chunk> {
     let mut tokens = tokens.into_iter();
-    let chunk = Parser::new(&mut tokens, report).into_chunk();
+    let mut parser = Parser::with_language(&mut tokens, language, report);
+    parser.set_cancellation_token(cancel_token.cancellation_token());
+    let chunk = parser.into_chunk();
     chunk
 }
 
@@ -168,6 +130,7 @@ struct WorkspaceFileInner {
 
     source: Arc<RwLock<Source>>,
     message_locale: Locale,
+    language: Language,
 
     path: PathBuf,
     unit: Unit,
@@ -200,6 +163,7 @@ impl fmt::Debug for WorkspaceFile {
          .field("cancel_token", &inner.cancel_token)
          .field("source", &Ellipsis)
          .field("message_locale", &inner.message_locale)
+         .field("language", &inner.language)
          .field("path", &inner.path)
          .field("unit", &inner.unit)
          .field("document", &inner.document)
@@ -213,7 +177,8 @@ impl fmt::Debug for WorkspaceFile {
 
 impl WorkspaceFile {
     fn new(shared: &Arc<RwLock<WorkspaceShared>>, pool: &Arc<CpuPool>,
-           source: &Arc<RwLock<Source>>, message_locale: Locale, path: PathBuf) -> WorkspaceFile {
+           source: &Arc<RwLock<Source>>, message_locale: Locale, language: Language,
+           path: PathBuf) -> WorkspaceFile {
         WorkspaceFile {
             inner: Arc::new(RwLock::new(WorkspaceFileInner {
                 workspace: shared.clone(),
@@ -221,6 +186,7 @@ impl WorkspaceFile {
                 cancel_token: CancelToken::new(),
                 source: source.clone(),
                 message_locale: message_locale,
+                language: language,
                 path: path,
                 unit: Unit::dummy(),
                 document: None,
@@ -243,7 +209,8 @@ impl WorkspaceFile {
         inner.chunk = None;
 
         // also signal the workspace to cancel jobs
-        inner.workspace.write().cancel();
+        let path = inner.path.clone();
+        inner.workspace.write().on_file_changed(&path);
     }
 
     #[allow(dead_code)]
@@ -320,7 +287,7 @@ impl WorkspaceFile {
                         let diags = ReportTree::new(inner.message_locale, path);
 
                         let report = diags.report(|span| diags::translate_span(span, &source));
-                        let tokens = collect_tokens(&source, *span, &report);
+                        let tokens = collect_tokens(&source, *span, inner.language, &report);
                         Ok((Arc::new(tokens), diags))
                     },
 
@@ -374,23 +341,31 @@ impl WorkspaceFile {
                 let tokens = (*tokens_ret.0).clone();
                 let parent_diags = tokens_ret.1.clone();
 
-                let mut inner = spare_inner.write();
-                inner.cancel_token.keep_going()?;
+                // the lock is not held while parsing, so that the parsing can be cancelled
+                let (cancel_token, message_locale, source, language) = {
+                    let inner = spare_inner.read();
+                    (inner.cancel_token.clone(), inner.message_locale, inner.source.clone(),
+                     inner.language)
+                };
+                cancel_token.keep_going()?;
 
-                let diags = ReportTree::new(inner.message_locale, None);
+                let diags = ReportTree::new(message_locale, None);
                 diags.add_parent(parent_diags);
 
                 // in this future source access is only needed for reporting
                 let chunk = {
                     let report = diags.report(|span| {
-                        diags::translate_span(span, &inner.source.read())
+                        diags::translate_span(span, &source.read())
                     });
-                    parse_to_chunk(tokens, &report)
+                    parse_to_chunk(tokens, language, &report, &cancel_token)
                 };
+
+                // partial reports from the cancelled parsing should not be used
+                cancel_token.keep_going()?;
                 match chunk {
                     Ok(chunk) => {
                         let chunk = Arc::new(chunk);
-                        inner.last_chunk = Some(chunk.clone());
+                        spare_inner.write().last_chunk = Some(chunk.clone());
                         Ok((chunk, diags))
                     },
                     Err(_) => Err(From::from(diags)),
@@ -485,6 +460,35 @@ impl WorkspaceBase {
     }
 }
 
+/// Latencies of re-checking hot documents, from the first change to fresh diagnostics.
+#[derive(Clone, Debug, Default)]
+pub struct HotCheckMetrics {
+    /// The number of completed re-checks.
+    pub count: u64,
+
+    pub last: Option<Duration>,
+    pub max: Duration,
+    pub total: Duration,
+}
+
+impl HotCheckMetrics {
+    fn record(&mut self, latency: Duration) {
+        self.count += 1;
+        self.last = Some(latency);
+        self.max = self.max.max(latency);
+        self.total += latency;
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count > 0 {
+            // Duration / u64 is not available; the count should be small enough anyway
+            Some(self.total / self.count.min(u32::max_value() as u64) as u32)
+        } else {
+            None
+        }
+    }
+}
+
 // a portion of Workspace that should be shared across WorkspaceFile.
 // this should not be modified in the normal cases (otherwise it can be easily deadlocked),
 // with an exception of cascading cancellation.
@@ -495,9 +499,29 @@ struct WorkspaceShared {
 
     check_outputs: Vec<Option<ReportFuture<Arc<Output>>>>,
     last_check_outputs: Vec<Option<Arc<Output>>>,
+
+    // paths loaded by the last completed check for each start path
+    check_deps: Vec<HashSet<PathBuf>>,
+
+    // signatures and dependencies of modules for each start path, used to skip unchanged modules
+    incremental: Vec<IncrementalState>,
+
+    // how long each file took to parse and check, shared with `kailua check --cache`
+    history: History,
+
+    // documents whose diagnostics should be always fresh (normally open documents).
+    // checks depending on them go first and other checks wait for them.
+    hot_paths: HashSet<PathBuf>,
+
+    // the time of the earliest change to hot documents not yet reflected to diagnostics
+    hot_since: Option<Instant>,
+    hot_metrics: HotCheckMetrics,
 }
 
 type Shared = Arc<RwLock<WorkspaceShared>>;
+
+// the maximum number of incremental checks for a single start path before the full check
+const MAX_INCREMENTAL_ROUNDS: usize = 3;
 type SharedWrite<'a> = RwLockWriteGuard<'a, WorkspaceShared>;
 
 impl fmt::Debug for WorkspaceShared {
@@ -514,6 +538,9 @@ impl fmt::Debug for WorkspaceShared {
          .field("cancel_token", &self.cancel_token)
          .field("check_outputs", &DummyOptionList(&self.check_outputs))
          .field("last_check_outputs", &DummyOptionList(&self.last_check_outputs))
+         .field("hot_paths", &self.hot_paths)
+         .field("hot_since", &self.hot_since)
+         .field("hot_metrics", &self.hot_metrics)
          .finish()
     }
 }
@@ -527,6 +554,27 @@ impl WorkspaceShared {
             *output = None;
         }
     }
+
+    fn on_file_changed(&mut self, path: &Path) {
+        if self.hot_since.is_none() && self.hot_paths.contains(path) {
+            self.hot_since = Some(Instant::now());
+        }
+        for state in &mut self.incremental {
+            state.mark_changed(path);
+        }
+        self.cancel();
+    }
+
+    fn is_hot_check(&self, index: usize, start_path: &Path) -> bool {
+        self.hot_paths.contains(start_path) ||
+            self.check_deps[index].iter().any(|path| self.hot_paths.contains(path))
+    }
+
+    fn finish_hot_check(&mut self) {
+        if let Some(since) = self.hot_since.take() {
+            self.hot_metrics.record(since.elapsed());
+        }
+    }
 }
 
 struct WorkspaceFsSourceInner {
@@ -536,8 +584,11 @@ struct WorkspaceFsSourceInner {
     source: Arc<RwLock<Source>>,
     temp_units: Vec<Unit>, // will be gone after checking
     temp_files: HashMap<PathBuf, Chunk>,
+    loaded_paths: HashSet<PathBuf>, // will be dependencies of the check
+    parse_times: Vec<(PathBuf, Duration)>, // for files read from the disk
 
     message_locale: Locale,
+    language: Language,
     root_report: ReportTree,
 }
 
@@ -552,6 +603,7 @@ impl FsSource for WorkspaceFsSource {
         let mut fssource = self.inner.borrow_mut();
 
         fssource.cancel_token.keep_going::<()>().map_err(|_| Stop)?;
+        fssource.loaded_paths.insert(path.base.to_owned());
 
         // try to use the client-maintained text as a source code
         let files = fssource.files.clone();
@@ -581,6 +633,7 @@ impl FsSource for WorkspaceFsSource {
 
         // try to read the file (and finally raise an error if it can't be read)
 
+        let started = Instant::now();
         let sourcefile = match SourceFile::from_file(path.base) {
             Ok(f) => f,
             Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
@@ -595,11 +648,12 @@ impl FsSource for WorkspaceFsSource {
         let chunk = {
             let source = fssource.source.read();
             let report = diags.report(|span| diags::translate_span(span, &source));
-            let tokens = collect_tokens(&source, span, &report);
-            parse_to_chunk(tokens, &report)
+            let tokens = collect_tokens(&source, span, fssource.language, &report);
+            parse_to_chunk(tokens, fssource.language, &report, &fssource.cancel_token)
         };
         match chunk {
             Ok(chunk) => {
+                fssource.parse_times.push((path.base.to_owned(), started.elapsed()));
                 fssource.temp_files.insert(path.base.to_owned(), chunk.clone());
                 Ok(Some(chunk))
             },
@@ -610,6 +664,12 @@ impl FsSource for WorkspaceFsSource {
 
 pub struct Workspace {
     message_locale: Locale,
+    language: Language, // updated when the configuration is read
+
+    // if false, caches in `.kailua-cache` are neither read nor written,
+    // except for the timing history which is only read (as `kailua check` does)
+    cache: bool,
+    uri_mapper: Arc<UriMapper>,
 
     pool: Arc<CpuPool>,
     files: Arc<RwLock<HashMap<PathBuf, WorkspaceFile>>>,
@@ -625,6 +685,9 @@ impl fmt::Debug for Workspace {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Workspace")
          .field("message_locale", &self.message_locale)
+         .field("language", &self.language)
+         .field("cache", &self.cache)
+         .field("uri_mapper", &Ellipsis)
          .field("pool", &Ellipsis)
          .field("files", &self.files)
          .field("source", &Ellipsis)
@@ -634,9 +697,13 @@ impl fmt::Debug for Workspace {
 }
 
 impl Workspace {
-    pub fn new(base_dir: PathBuf, pool: Arc<CpuPool>, default_locale: Locale) -> Workspace {
+    pub fn new(base_dir: PathBuf, pool: Arc<CpuPool>, default_locale: Locale, cache: bool,
+               uri_mapper: Arc<UriMapper>) -> Workspace {
         Workspace {
             message_locale: default_locale,
+            language: Language::default(),
+            cache: cache,
+            uri_mapper: uri_mapper,
             pool: pool,
             files: Arc::new(RwLock::new(HashMap::new())),
             source: Arc::new(RwLock::new(Source::new())),
@@ -645,6 +712,12 @@ impl Workspace {
                 base: WorkspaceBase::Config(kailua_workspace::Config::from_base_dir(base_dir)),
                 check_outputs: Vec::new(),
                 last_check_outputs: Vec::new(),
+                check_deps: Vec::new(),
+                incremental: Vec::new(),
+                history: History::new(),
+                hot_paths: HashSet::new(),
+                hot_since: None,
+                hot_metrics: HotCheckMetrics::default(),
             })),
         }
     }
@@ -653,6 +726,14 @@ impl Workspace {
         &self.pool
     }
 
+    pub fn uri_mapper(&self) -> &Arc<UriMapper> {
+        &self.uri_mapper
+    }
+
+    fn uri_to_path(&self, uri: &str) -> WorkspaceResult<PathBuf> {
+        self.uri_mapper.uri_to_path(uri).map_err(WorkspaceError)
+    }
+
     pub fn source<'a>(&'a self) -> RwLockReadGuard<'a, Source> {
         self.source.read()
     }
@@ -674,6 +755,9 @@ impl Workspace {
         let mut shared = self.shared.write();
         let ws = if let WorkspaceBase::Config(ref mut config) = shared.base {
             config.use_default_config_paths();
+            for warning in config.config_warnings() {
+                warn!("{}", warning);
+            }
             if let Some(ws) = kailua_workspace::Workspace::new(config, self.message_locale) {
                 Some(ws)
             } else {
@@ -684,9 +768,31 @@ impl Workspace {
         };
         if let Some(ws) = ws {
             let noutputs = ws.start_paths().len();
+            self.language = ws.language();
+
+            // the configuration may affect any signature; caches are kept per configuration
+            let cache = self.cache;
+            shared.incremental = ws.start_paths().iter().map(|path| {
+                if !cache {
+                    return IncrementalState::new();
+                }
+                let cache_path = ws.cache_path(path);
+                let fs = &*ws.sys().fs;
+                IncrementalState::load(fs, &cache_path, ws.fingerprint()).unwrap_or_else(|e| {
+                    warn!("failed to read a cache {}: {}", cache_path.display(), e);
+                    IncrementalState::new()
+                })
+            }).collect();
+            let history_path = ws.history_path();
+            shared.history = History::load(&*ws.sys().fs, &history_path).unwrap_or_else(|e| {
+                warn!("failed to read a history {}: {}", history_path.display(), e);
+                History::new()
+            });
+
             shared.base = WorkspaceBase::Workspace(ws);
             shared.check_outputs.resize(noutputs, None);
             shared.last_check_outputs.resize(noutputs, None);
+            shared.check_deps.resize(noutputs, HashSet::new());
         }
         true
     }
@@ -714,14 +820,15 @@ impl Workspace {
     }
 
     pub fn file<'a>(&'a self, uri: &str) -> Option<WorkspaceFile> {
-        match uri_to_path(uri) {
+        match self.uri_to_path(uri) {
             Ok(path) => self.files.read().get(&path).cloned(),
             Err(_) => None,
         }
     }
 
     fn make_file(&self, path: PathBuf) -> WorkspaceFile {
-        WorkspaceFile::new(&self.shared, &self.pool, &self.source, self.message_locale, path)
+        WorkspaceFile::new(&self.shared, &self.pool, &self.source, self.message_locale,
+                           self.language, path)
     }
 
     fn destroy_file(&self, file: WorkspaceFile) -> bool {
@@ -731,8 +838,29 @@ impl Workspace {
         file.document.is_some() && sourcefile.is_some()
     }
 
+    /// Returns documents whose diagnostics should be kept fresh before others.
+    #[allow(dead_code)]
+    pub fn hot_paths(&self) -> HashSet<PathBuf> {
+        self.shared.read().hot_paths.clone()
+    }
+
+    /// Replaces the set of hot documents. Open documents are hot by default.
+    ///
+    /// Checks depending on hot documents are scheduled before, and never wait for, other checks.
+    #[allow(dead_code)]
+    pub fn set_hot_paths(&self, paths: HashSet<PathBuf>) {
+        self.shared.write().hot_paths = paths;
+    }
+
+    /// Returns the latency metrics of re-checking hot documents so far.
+    #[allow(dead_code)]
+    pub fn hot_check_metrics(&self) -> HotCheckMetrics {
+        self.shared.read().hot_metrics.clone()
+    }
+
     pub fn open_file(&self, item: protocol::TextDocumentItem) -> WorkspaceResult<()> {
-        let path = uri_to_path(&item.uri)?;
+        let path = self.uri_to_path(&item.uri)?;
+        self.shared.write().hot_paths.insert(path.clone());
 
         let mut files = self.files.write();
         let file = files.entry(path.clone()).or_insert_with(|| self.make_file(path));
@@ -752,7 +880,8 @@ impl Workspace {
     }
 
     pub fn close_file(&self, uri: &str) -> WorkspaceResult<()> {
-        let path = uri_to_path(uri)?;
+        let path = self.uri_to_path(uri)?;
+        self.shared.write().hot_paths.remove(&path);
 
         // closing file breaks the synchronization so the file should be re-read from fs
         let mut files = self.files.write();
@@ -772,7 +901,7 @@ impl Workspace {
     }
 
     pub fn on_file_created(&self, uri: &str) -> Option<WorkspaceFile> {
-        if let Ok(path) = uri_to_path(uri) {
+        if let Ok(path) = self.uri_to_path(uri) {
             let file = self.ensure_file(&path);
             let _ = file.ensure_chunk();
             Some(file)
@@ -782,7 +911,7 @@ impl Workspace {
     }
 
     pub fn on_file_changed(&self, uri: &str) -> Option<WorkspaceFile> {
-        if let Ok(path) = uri_to_path(uri) {
+        if let Ok(path) = self.uri_to_path(uri) {
             let file = self.ensure_file(&path);
             file.cancel();
             let _ = file.ensure_chunk();
@@ -793,7 +922,7 @@ impl Workspace {
     }
 
     pub fn on_file_deleted(&self, uri: &str) {
-        if let Ok(path) = uri_to_path(uri) {
+        if let Ok(path) = self.uri_to_path(uri) {
             let mut files = self.files.write();
             if let Some(file) = files.remove(&path) {
                 self.destroy_file(file);
@@ -810,8 +939,10 @@ impl Workspace {
         self.shared.read().cancel_token.future()
     }
 
+    // the check only starts after `after` completes (successfully or not).
     fn build_future_for_check_output(
-        &self, index: usize, start_path: &Path, spare_shared: Shared, shared: &mut SharedWrite
+        &self, index: usize, start_path: &Path, after: BoxFuture<(), ()>,
+        spare_shared: Shared, shared: &mut SharedWrite
     ) -> ReportFuture<Arc<Output>> {
         let start_chunk_fut = self.ensure_file(start_path).ensure_chunk();
 
@@ -820,75 +951,188 @@ impl Workspace {
         let source = self.source.clone();
         let cancel_token = shared.cancel_token.clone();
         let message_locale = self.message_locale;
+        let language = self.language;
+        let use_cache = self.cache;
+        let hot_paths = shared.hot_paths.clone();
 
-        let fut = start_chunk_fut.map_err(|e| (*e).clone()).and_then(move |chunk_ret| {
+        let fut = after.then(move |_| {
+            start_chunk_fut.map_err(|e| (*e).clone())
+        }).and_then(move |chunk_ret| {
             cancel_token.keep_going()?;
 
-            let start_chunk = (*chunk_ret.0).clone();
-            let diags = ReportTree::new(message_locale, None);
-            diags.add_parent(chunk_ret.1.clone());
-
-            // the actual checking process.
-            //
-            // this will routinely lock the shared, so we avoid locking it from the caller
-            // by cloning required values prematurely.
-            let fssource = WorkspaceFsSource {
-                inner: Rc::new(RefCell::new(WorkspaceFsSourceInner {
-                    cancel_token: cancel_token.clone(),
-                    files: files,
-                    source: source.clone(),
-                    temp_units: Vec::new(),
-                    temp_files: HashMap::new(),
-                    message_locale: message_locale,
-                    root_report: diags.clone(),
-                })),
-            };
+            // modules not affected by changes are replaced with their signatures.
+            // open documents are always fully checked, as their outputs are used for hovers.
+            let state = spare_shared.read().incremental[index].clone();
+            let state = Rc::new(RefCell::new(state));
+            for path in &hot_paths {
+                state.borrow_mut().mark_changed(path);
+            }
 
-            let (opts, preload) = match spare_shared.read().base {
-                WorkspaceBase::Config(_) => {
-                    // it should not be the case, but if we ever get to this point,
-                    // we cannot proceed at all because there's no start path.
-                    // we should have been alerted though.
-                    return Err(From::from(diags));
-                },
-                WorkspaceBase::Workspace(ref ws) => {
-                    let opts = WorkspaceOptions::new(fssource.clone(), &start_path, ws);
-                    (Rc::new(RefCell::new(opts)), ws.preload().clone())
-                },
-            };
+            let mut round = 0;
+            loop {
+                round += 1;
 
-            let (ok, output) = {
-                // the translation should NOT lock the source (read or write) indefinitely.
-                // we also want to drop the proxy report as fast as possible.
-                let mut context = Context::new(diags.report(|span| {
-                    diags::translate_span(span, &source.read())
-                }));
-                let ok = kailua_check::check_from_chunk_with_preloading(&mut context, start_chunk,
-                                                                        opts, &preload).is_ok();
-                (ok, context.into_output())
-            };
+                let start_chunk = (*chunk_ret.0).clone();
+                let diags = ReportTree::new(message_locale, None);
+                diags.add_parent(chunk_ret.1.clone());
+
+                // the actual checking process.
+                //
+                // this will routinely lock the shared, so we avoid locking it from the caller
+                // by cloning required values prematurely.
+                let fssource = WorkspaceFsSource {
+                    inner: Rc::new(RefCell::new(WorkspaceFsSourceInner {
+                        cancel_token: cancel_token.clone(),
+                        files: files.clone(),
+                        source: source.clone(),
+                        temp_units: Vec::new(),
+                        temp_files: HashMap::new(),
+                        loaded_paths: HashSet::new(),
+                        parse_times: Vec::new(),
+                        message_locale: message_locale,
+                        language: language,
+                        root_report: diags.clone(),
+                    })),
+                };
 
-            // fssource should be owned only by this function; the following should not fail
-            let fssource = Rc::try_unwrap(fssource.inner).ok().expect("no single owner");
-            let fssource = fssource.into_inner();
+                let (opts, preload, asserts, config_path, cache) = match spare_shared.read().base {
+                    WorkspaceBase::Config(_) => {
+                        // it should not be the case, but if we ever get to this point,
+                        // we cannot proceed at all because there's no start path.
+                        // we should have been alerted though.
+                        return Err(From::from(diags));
+                    },
+                    WorkspaceBase::Workspace(ref ws) => {
+                        let mut incsource = IncrementalSource::new(fssource.clone(),
+                                                                   state.clone());
+                        let hash_source = source.clone();
+                        incsource.set_content_hash(move |unit| {
+                            hash_source.read().file(unit)
+                                       .map(|f| incremental::hash_contents(f.data()))
+                        });
+                        let opts = WorkspaceOptions::new(vec![incsource], &start_path, ws);
+                        let opts = Rc::new(RefCell::new(RecordingOptions::new(opts)));
+                        let config_path = ws.config_path().map(|p| p.display().to_string());
+                        let cache = (ws.cache_path(&start_path), ws.fingerprint(),
+                                     ws.history_path(), ws.sys().clone());
+                        (opts, ws.preload().clone(), ws.asserts().to_owned(), config_path, cache)
+                    },
+                };
 
-            // remove all temporarily added chunks from the source
-            // XXX ideally this should be cached as much as possible though
-            let mut source = source.write();
-            for unit in fssource.temp_units {
-                let sourcefile = source.remove(unit);
-                assert!(sourcefile.is_some());
-            }
+                let (ok, output, recheck) = {
+                    // the translation should NOT lock the source (read or write) indefinitely.
+                    // we also want to drop the proxy report as fast as possible.
+                    let mut context = Context::new(TrackReportedUnits::new(diags.report(|span| {
+                        diags::translate_span(span, &source.read())
+                    })));
+                    context.set_cancellation_token(cancel_token.cancellation_token());
+                    let mut ok = kailua_check::check_from_chunk_with_preloading(
+                        &mut context, start_chunk, opts.clone(), &preload
+                    ).is_ok();
+
+                    // failed workspace assertions are reported to the configuration file
+                    if ok {
+                        match kailua_check::verify_assertions(&mut context, &asserts,
+                                                              opts.clone()) {
+                            Ok(failures) => {
+                                for msg in failures {
+                                    if let Some(ref path) = config_path {
+                                        diags.add_diag(path.clone(), protocol::Diagnostic {
+                                            range: protocol::Range {
+                                                start: protocol::Position {
+                                                    line: 0, character: 0,
+                                                },
+                                                end: protocol::Position {
+                                                    line: 0, character: 0,
+                                                },
+                                            },
+                                            severity: Some(protocol::DiagnosticSeverity::Error),
+                                            code: None,
+                                            source: None,
+                                            message: msg,
+                                        });
+                                    }
+                                }
+                            }
+                            Err(_) => ok = false,
+                        }
+                    }
 
-            // FsSource may have failed from the cancel request, so we should catch it here
-            cancel_token.keep_going()?;
+                    let reported = context.report().units();
+                    let recheck = state.borrow_mut().finish_check(&opts.borrow(), &context,
+                                                                  &reported);
+                    (ok, context.into_output(), recheck)
+                };
+                drop(opts); // holds a clone of fssource
 
-            if ok {
-                let output = Arc::new(output);
-                spare_shared.write().last_check_outputs[index] = Some(output.clone());
-                Ok((output, diags))
-            } else {
-                Err(From::from(diags))
+                // temporary files are removed from the source below, so resolve them now
+                let check_times: Vec<_> = {
+                    let source = source.read();
+                    output.stats().module_durations().into_iter().filter_map(|(unit, elapsed)| {
+                        source.file(unit).map(|file| (PathBuf::from(file.path()), elapsed))
+                    }).collect()
+                };
+
+                // fssource should be owned only by this function; the following should not fail
+                let fssource = Rc::try_unwrap(fssource.inner).ok().expect("no single owner");
+                let fssource = fssource.into_inner();
+
+                // remove all temporarily added chunks from the source
+                // XXX ideally this should be cached as much as possible though
+                {
+                    let mut source = source.write();
+                    for unit in fssource.temp_units {
+                        let sourcefile = source.remove(unit);
+                        assert!(sourcefile.is_some());
+                    }
+                }
+
+                // FsSource may have failed from the cancel request, so we should catch it here
+                cancel_token.keep_going()?;
+
+                // some stubbed modules turned out to be affected; in the worst case
+                // (e.g. a long chain of changed signatures) we fall back to the full check
+                if recheck {
+                    if round >= MAX_INCREMENTAL_ROUNDS {
+                        state.borrow_mut().reset();
+                    }
+                    continue;
+                }
+
+                let (cache_path, fingerprint, history_path, sys) = cache;
+                let history = {
+                    let mut shared = spare_shared.write();
+                    shared.check_deps[index] = fssource.loaded_paths;
+                    shared.incremental[index] = state.borrow().clone();
+
+                    // keyed by canonical paths as `preparse` does
+                    for (path, elapsed) in fssource.parse_times {
+                        shared.history.record(sys.paths.canonical(&*sys.fs, &path), elapsed);
+                    }
+                    for (path, elapsed) in check_times {
+                        shared.history.record_check(sys.paths.canonical(&*sys.fs, &path),
+                                                    elapsed);
+                    }
+                    shared.history.clone()
+                };
+
+                // saved states are used in the next session (or by `kailua check --cache`)
+                if use_cache {
+                    if let Err(e) = state.borrow_mut().save(&*sys.fs, &cache_path, fingerprint) {
+                        warn!("failed to write a cache {}: {}", cache_path.display(), e);
+                    }
+                    if let Err(e) = history.save(&*sys.fs, &history_path) {
+                        warn!("failed to write a history {}: {}", history_path.display(), e);
+                    }
+                }
+
+                return if ok {
+                    let output = Arc::new(output);
+                    spare_shared.write().last_check_outputs[index] = Some(output.clone());
+                    Ok((output, diags))
+                } else {
+                    Err(From::from(diags))
+                };
             }
         });
 
@@ -907,14 +1151,58 @@ impl Workspace {
         };
         assert_eq!(shared.check_outputs.len(), start_paths.len());
 
-        for (i, path) in start_paths.iter().enumerate() {
+        // checks depending on hot documents go first, and others wait for them to finish,
+        // so that hot documents do not compete with the background work for the pool
+        let (hot, cold): (Vec<_>, Vec<_>) = (0..start_paths.len()).partition(|&i| {
+            shared.is_hot_check(i, &start_paths[i])
+        });
+        if hot.is_empty() {
+            shared.hot_since = None;
+        }
+
+        let mut new_hot_futs = Vec::new();
+        for &i in &hot {
+            if shared.check_outputs[i].is_none() {
+                let fut = self.build_future_for_check_output(i, &start_paths[i],
+                                                             future::ok(()).boxed(),
+                                                             spare_shared.clone(), &mut shared);
+                shared.check_outputs[i] = Some(fut.clone());
+                new_hot_futs.push(fut);
+            }
+        }
+
+        for &i in &cold {
             if shared.check_outputs[i].is_none() {
-                let fut = self.build_future_for_check_output(i, path, spare_shared.clone(),
-                                                             &mut shared);
+                let hot_futs: Vec<_> = hot.iter().map(|&j| {
+                    shared.check_outputs[j].clone().unwrap().then(|_| Ok::<(), ()>(()))
+                }).collect();
+                let after = future::join_all(hot_futs).map(|_| ()).boxed();
+                let fut = self.build_future_for_check_output(i, &start_paths[i], after,
+                                                             spare_shared.clone(), &mut shared);
                 shared.check_outputs[i] = Some(fut);
             }
         }
 
+        // record the latency when all hot checks deliver fresh diagnostics (not canceled)
+        if !new_hot_futs.is_empty() && shared.hot_since.is_some() {
+            let fresh_futs: Vec<_> = new_hot_futs.into_iter().map(|fut| {
+                fut.then(|res| {
+                    let canceled = match res {
+                        Ok(_) => false,
+                        Err(e) => if let CancelError::Canceled = *e { true } else { false },
+                    };
+                    Ok::<bool, ()>(!canceled)
+                })
+            }).collect();
+            let spare_shared = spare_shared.clone();
+            let fut = future::join_all(fresh_futs).map(move |fresh| {
+                if fresh.into_iter().all(|fresh| fresh) {
+                    spare_shared.write().finish_hot_check();
+                }
+            });
+            self.pool.spawn(fut).forget();
+        }
+
         Ok(shared.check_outputs.iter().map(|fut| fut.as_ref().unwrap().clone()).collect())
     }
 
@@ -102,12 +102,16 @@ impl fmt::Debug for Attr {
 pub enum AttrValue {
     /// A name, as like `foo` in `[make_class(foo)]`.
     Name(Spanned<Name>),
+
+    /// A string, as like `"use bar"` in `[deprecated("use bar")]`.
+    Str(Spanned<Str>),
 }
 
 impl fmt::Debug for AttrValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             AttrValue::Name(ref name) => write!(f, "{:?}", name),
+            AttrValue::Str(ref s) => write!(f, "{:?}", s),
         }
     }
 }
@@ -259,6 +263,9 @@ pub struct Sig {
     /// A list of attributes.
     pub attrs: Vec<Spanned<Attr>>,
 
+    /// A list of generic type parameters, empty if the function is not generic.
+    pub type_params: Vec<Spanned<Name>>,
+
     /// A list of arguments (resolved to scoped identifiers) and associated types if any.
     pub args: Spanned<Seq<TypeSpec<Spanned<ScopedId>>, Varargs>>,
 
@@ -271,6 +278,14 @@ impl fmt::Debug for Sig {
         for attr in &self.attrs {
             write!(f, "{:?} ", attr)?;
         }
+        if !self.type_params.is_empty() {
+            write!(f, "<")?;
+            let comma = Comma::new();
+            for param in &self.type_params {
+                write!(f, "{}{:?}", comma, param)?;
+            }
+            write!(f, ">")?;
+        }
         write!(f, "[")?;
         let comma = Comma::new();
         for namespec in &self.args.head {
@@ -367,8 +382,13 @@ pub enum Ex {
     True,
 
     /// A number literal.
+    ///
+    /// Since Lua 5.3 this is only used for float literals.
     Num(f64),
 
+    /// An integer literal. Only generated since Lua 5.3.
+    Int(i64),
+
     /// A string literal.
     ///
     /// A difference between `"string"` and `[[string]]` is not recorded.
@@ -423,6 +443,7 @@ impl fmt::Debug for Ex {
             Ex::False => write!(f, "false"),
             Ex::True => write!(f, "true"),
             Ex::Num(v) => write!(f, "{:?}", v),
+            Ex::Int(v) => write!(f, "{}", v),
             Ex::Str(ref s) => write!(f, "{:?}", *s),
             Ex::Varargs => write!(f, "..."),
             Ex::Func(ref p, bs, ref b) => write!(f, "Func({:?}, {:?}{:?})", *p, bs, *b),
@@ -453,6 +474,8 @@ pub enum UnOp {
     Not,
     /// `#`.
     Len,
+    /// `~`. [5.3+]
+    BNot,
 }
 
 impl UnOp {
@@ -461,6 +484,7 @@ impl UnOp {
             UnOp::Neg => "-",
             UnOp::Not => "not",
             UnOp::Len => "#",
+            UnOp::BNot => "~",
         }
     }
 }
@@ -476,12 +500,24 @@ pub enum BinOp {
     Mul,
     /// `/`.
     Div,
+    /// `//`. [5.3+]
+    IDiv,
     /// `^`.
     Pow,
     /// `%`.
     Mod,
     /// `..`.
     Cat,
+    /// `&`. [5.3+]
+    BAnd,
+    /// `|`. [5.3+]
+    BOr,
+    /// `~`. [5.3+]
+    BXor,
+    /// `<<`. [5.3+]
+    Shl,
+    /// `>>`. [5.3+]
+    Shr,
     /// `<`.
     Lt,
     /// `<=`.
@@ -507,9 +543,15 @@ impl BinOp {
             BinOp::Sub => "-",
             BinOp::Mul => "*",
             BinOp::Div => "/",
+            BinOp::IDiv => "//",
             BinOp::Pow => "^",
             BinOp::Mod => "%",
             BinOp::Cat => "..",
+            BinOp::BAnd => "&",
+            BinOp::BOr => "|",
+            BinOp::BXor => "~",
+            BinOp::Shl => "<<",
+            BinOp::Shr => ">>",
             BinOp::Lt  => "<",
             BinOp::Le  => "<=",
             BinOp::Gt  => ">",
@@ -546,6 +588,28 @@ pub enum TypeScope {
     Exported,
 }
 
+/// A required field in the interface (from `--# interface`).
+#[derive(Clone, PartialEq)]
+pub enum InterfaceField {
+    /// `name: [const] type`.
+    Field(Spanned<SlotKind>),
+
+    /// `name: method(...) --> ...`.
+    ///
+    /// The receiver (`self`) is not a part of the function type,
+    /// as its type depends on the implementation.
+    Method(Spanned<FuncKind>),
+}
+
+impl fmt::Debug for InterfaceField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InterfaceField::Field(ref slotkind) => write!(f, "{:?}", slotkind),
+            InterfaceField::Method(ref funckind) => write!(f, "Method({:?})", funckind),
+        }
+    }
+}
+
 /// A statement.
 ///
 /// Many statement nodes have associated scopes.
@@ -615,29 +679,46 @@ pub enum St {
     /// `--# type [scope] name = type`.
     KailuaType(TypeScope, Spanned<Name>, Spanned<Kind>),
 
-    /// `--# assume [global] name: type`.
+    /// `--# assume [scoped] [global] name: type`.
     ///
+    /// The first `bool` is true when `scoped` is present, in which case the prior binding
+    /// of the variable is restored at the end of the current block.
     /// The sibling scope only exists when the statement is redefining a local name.
-    KailuaAssume(Spanned<RenameRef>, M, Spanned<Kind>, Option<Scope>),
+    KailuaAssume(bool /*scoped*/, Spanned<RenameRef>, M, Spanned<Kind>, Option<Scope>),
 
-    /// `--# assume [static] name.field.field: type`.
+    /// `--# assume [scoped] [static] name.field.field: type`.
     ///
-    /// The first `bool` is true when `static` is present.
-    KailuaAssumeField(bool /*static*/, Spanned<(Spanned<NameRef>, Vec<Spanned<Name>>)>,
-                      M, Spanned<Kind>),
+    /// The first `bool` is true when `scoped` is present (see `St::KailuaAssume`),
+    /// and the second `bool` is true when `static` is present.
+    KailuaAssumeField(bool /*scoped*/, bool /*static*/,
+                      Spanned<(Spanned<NameRef>, Vec<Spanned<Name>>)>, M, Spanned<Kind>),
 
-    /// `--# assume name.field.field: method(...) --> ...`.
+    /// `--# assume [scoped] name.field.field: method(...) --> ...`.
     ///
     /// This is distinct from `St::KailuaAssumeField` because it is not possible to
     /// desugar it without knowing the type of `self`.
-    KailuaAssumeMethod(Spanned<(Spanned<NameRef>, Vec<Spanned<Name>>)>, M, Spanned<FuncKind>),
+    KailuaAssumeMethod(bool /*scoped*/, Spanned<(Spanned<NameRef>, Vec<Spanned<Name>>)>,
+                       M, Spanned<FuncKind>),
 
     /// `--# class system ...`.
     KailuaClassSystem(Spanned<Name>),
 
-    /// `--# assume [global] class[(...)] ClassName[: ParentClassName]`.
+    /// `--# assume [global] class[(...)] ClassName[<T, ...>][: ParentClassName]`.
     KailuaAssumeClass(Option<Spanned<Name>> /*system*/, Spanned<RenameRef> /*variable & type name*/,
+                      Vec<Spanned<Name>> /*type parameters*/,
                       Option<Spanned<Name>> /*parent type name*/, Option<Scope>),
+
+    /// `--# interface name = { field: type, field: method(...) --> ..., ... }`.
+    KailuaInterface(Spanned<Name>, Vec<(Spanned<Name>, InterfaceField)>),
+
+    /// `--# assert name implements interface`.
+    KailuaAssertImplements(Spanned<NameRef>, Spanned<Name>),
+
+    /// `--# ignore [function] [: code, ...]`.
+    ///
+    /// The `bool` is true when `function` is present, in which case the enclosing function
+    /// is affected instead of the next statement. Codes are lint names like `shadowing`.
+    KailuaIgnore(bool /*function*/, Vec<Spanned<Name>>),
 }
 
 /// In the debugging output scopes are printed in two ways:
@@ -691,27 +772,57 @@ impl fmt::Debug for St {
             St::KailuaOpen(ref lib) => write!(f, "KailuaOpen({:?})", lib),
             St::KailuaType(scope, ref t, ref k) =>
                 write!(f, "KailuaType({:?}, {:?}, {:?})", scope, t, k),
-            St::KailuaAssume(ref i, m, ref k, is) => {
-                write!(f, "KailuaAssume({:?}, {:?}, {:?})", i, m, k)?;
+            St::KailuaAssume(scoped, ref i, m, ref k, is) => {
+                write!(f, "KailuaAssume(")?;
+                if scoped { write!(f, "scoped, ")?; }
+                write!(f, "{:?}, {:?}, {:?})", i, m, k)?;
                 if let Some(is) = is { write!(f, "{:?}", is)?; }
                 Ok(())
             },
-            St::KailuaAssumeField(static_, Spanned { base: (ref i, ref ii), span }, m, ref k) => {
-                write!(f, "KailuaAssumeField({}, ({:?}", static_, i)?;
+            St::KailuaAssumeField(scoped, static_, Spanned { base: (ref i, ref ii), span },
+                                  m, ref k) => {
+                write!(f, "KailuaAssumeField(")?;
+                if scoped { write!(f, "scoped, ")?; }
+                write!(f, "{}, ({:?}", static_, i)?;
                 for i in ii { write!(f, ".{:?}", i)?; }
                 write!(f, "){:?}, {:?}, {:?})", span, m, k)
             },
-            St::KailuaAssumeMethod(Spanned { base: (ref i, ref ii), span }, m, ref fk) => {
-                write!(f, "KailuaAssumeMethod(({:?}", i)?;
+            St::KailuaAssumeMethod(scoped, Spanned { base: (ref i, ref ii), span }, m, ref fk) => {
+                write!(f, "KailuaAssumeMethod(")?;
+                if scoped { write!(f, "scoped, ")?; }
+                write!(f, "({:?}", i)?;
                 for i in ii { write!(f, ".{:?}", i)?; }
                 write!(f, "){:?}, {:?}, {:?})", span, m, fk)
             },
             St::KailuaClassSystem(ref sys) => write!(f, "KailuaClassSystem({:?})", sys),
-            St::KailuaAssumeClass(ref sys, ref i, ref pi, is) => {
-                write!(f, "KailuaAssumeClass({:?}, {:?}, {:?})", sys, i, pi)?;
+            St::KailuaAssumeClass(ref sys, ref i, ref tp, ref pi, is) => {
+                write!(f, "KailuaAssumeClass({:?}, {:?}", sys, i)?;
+                if !tp.is_empty() {
+                    write!(f, "<")?;
+                    let comma = Comma::new();
+                    for p in tp { write!(f, "{}{:?}", comma, p)?; }
+                    write!(f, ">")?;
+                }
+                write!(f, ", {:?})", pi)?;
                 if let Some(is) = is { write!(f, "{:?}", is)?; }
                 Ok(())
             },
+            St::KailuaInterface(ref i, ref fields) => {
+                write!(f, "KailuaInterface({:?}, [", i)?;
+                let comma = Comma::new();
+                for &(ref name, ref field) in fields {
+                    write!(f, "{}{:?}: {:?}", comma, name, field)?;
+                }
+                write!(f, "])")
+            },
+            St::KailuaAssertImplements(ref i, ref iface) =>
+                write!(f, "KailuaAssertImplements({:?}, {:?})", i, iface),
+            St::KailuaIgnore(func, ref codes) => {
+                write!(f, "KailuaIgnore({}, [", func)?;
+                let comma = Comma::new();
+                for code in codes { write!(f, "{}{:?}", comma, code)?; }
+                write!(f, "])")
+            },
         }
     }
 }
@@ -791,6 +902,12 @@ impl fmt::Debug for SlotKind {
 /// A function type for Kailua.
 #[derive(Clone, PartialEq)]
 pub struct FuncKind {
+    /// A list of generic type parameters, empty if the function is not generic.
+    ///
+    /// They can be used as type names in the arguments and returns,
+    /// and are instantiated to actual types for each call.
+    pub type_params: Vec<Spanned<Name>>,
+
     /// A list of argument types with optional names.
     ///
     /// The name is purely for description and has no effect in the type.
@@ -804,6 +921,14 @@ pub struct FuncKind {
 
 impl fmt::Debug for FuncKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.type_params.is_empty() {
+            write!(f, "<")?;
+            let comma = Comma::new();
+            for param in &self.type_params {
+                write!(f, "{}{:?}", comma, param)?;
+            }
+            write!(f, ">")?;
+        }
         write!(f, "(")?;
         let comma = Comma::new();
         for &(ref name, ref arg) in &self.args.head {
@@ -854,6 +979,9 @@ pub enum K {
     /// An integral literal type.
     IntegerLit(i32),
 
+    /// `integer(lo..hi)`, an integer between two inclusive bounds.
+    IntegerRange(i32, i32),
+
     /// `string`.
     String,
 
@@ -892,12 +1020,18 @@ pub enum K {
     /// `thread`.
     Thread,
 
+    /// `thread<Y, R>`, a coroutine yielding `Y` and resumed with `R`.
+    Coroutine(Spanned<Kind>, Spanned<Kind>),
+
     /// `userdata`.
     UserData,
 
     /// A named type.
     Named(Spanned<Name>),
 
+    /// A named generic type with type arguments, `Name<T, U, ...>`.
+    Generic(Spanned<Name>, Vec<Spanned<Kind>>),
+
     /// `T?`.
     ///
     /// The checker distinguishes a plain type and a "nilable" type,
@@ -913,6 +1047,12 @@ pub enum K {
     /// `T | U | ...`.
     Union(Vec<Spanned<Kind>>),
 
+    /// `T - U`, i.e. `T` excluding values of `U`.
+    ///
+    /// This has a lower precedence than `|`, so `a | b - c | d` is `(a | b) - (c | d)`.
+    /// The checker only accepts primitive types and their unions as `U`.
+    Difference(Spanned<Kind>, Spanned<Kind>),
+
     /// `[attribute] T`.
     Attr(Spanned<Kind>, Spanned<Attr>),
 
@@ -933,6 +1073,7 @@ impl fmt::Debug for K {
             K::Number             => write!(f, "Number"),
             K::Integer            => write!(f, "Integer"),
             K::IntegerLit(v)      => write!(f, "Integer({})", v),
+            K::IntegerRange(lo, hi) => write!(f, "Integer({}..{})", lo, hi),
             K::String             => write!(f, "String"),
             K::StringLit(ref s)   => write!(f, "String({:?})", *s),
             K::Table              => write!(f, "Table"),
@@ -941,9 +1082,16 @@ impl fmt::Debug for K {
             K::Map(ref k, ref v)  => write!(f, "Map({:?}, {:?})", *k, *v),
             K::Function           => write!(f, "Function"),
             K::Named(ref name)    => write!(f, "{:?}", *name),
+            K::Generic(ref name, ref args) => {
+                write!(f, "{:?}<", *name)?;
+                let comma = Comma::new();
+                for arg in args { write!(f, "{}{:?}", comma, arg)?; }
+                write!(f, ">")
+            },
             K::WithNil(ref k)     => write!(f, "{:?}?", *k),
             K::WithoutNil(ref k)  => write!(f, "{:?}!", *k),
             K::Thread             => write!(f, "Thread"),
+            K::Coroutine(ref y, ref r) => write!(f, "Thread({:?}, {:?})", *y, *r),
             K::UserData           => write!(f, "UserData"),
             K::Error(None)        => write!(f, "Error"),
             K::Error(Some(ref s)) => write!(f, "Error({:?})", *s),
@@ -963,6 +1111,7 @@ impl fmt::Debug for K {
             K::Tuple(ref fields) => write!(f, "Tuple({:?})", *fields),
             K::Func(ref func) => write!(f, "Func({:?})", *func),
             K::Union(ref kinds) => write!(f, "Union({:?})", *kinds),
+            K::Difference(ref k, ref ex) => write!(f, "Difference({:?}, {:?})", *k, *ex),
             K::Attr(ref k, ref a) => write!(f, "{:?} {:?}", a, k),
         }
     }
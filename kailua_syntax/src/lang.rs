@@ -1,7 +1,8 @@
 //! Source language description.
 //!
-//! Right now Kailua only supports the entirety of Lua 5.1 and its own extension syntax,
-//! but these types may allow other versions of Lua in the future.
+//! Kailua supports the entirety of Lua 5.1 and its own extension syntax by default.
+//! Later versions of Lua are partially supported; notably operators and integer literals
+//! of Lua 5.3 and later are recognized only when the language is explicitly set.
 
 use std::fmt;
 use kailua_diag::{Locale, Localize, Localized};
@@ -44,6 +45,13 @@ impl Language {
     }
 }
 
+impl Default for Language {
+    /// Lua 5.1 with the current Kailua extension.
+    fn default() -> Language {
+        Language::new(Lua::Lua51, Kailua::Kailua10)
+    }
+}
+
 impl fmt::Debug for Language {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let lua = self.lua();
@@ -61,7 +69,7 @@ impl Localize for Language {
         let lua_name = Localized::new(&lua, locale);
         if let Some(kailua) = self.kailua() {
             let kailua_name = Localized::new(&kailua, locale);
-            match &locale[..] {
+            match &locale.resolve(&["ko"])[..] {
                 "ko" => write!(f, "{} 확장을 사용하는 {}", kailua_name, lua_name),
                 _ => write!(f, "{} with {} extension", lua_name, kailua_name),
             }
@@ -82,6 +90,9 @@ pub enum Lua {
 
     /// Lua 5.3.
     Lua53 = 0x53,
+
+    /// Lua 5.4.
+    Lua54 = 0x54,
 }
 
 impl Lua {
@@ -90,15 +101,34 @@ impl Lua {
             0x51 => Some(Lua::Lua51),
             0x52 => Some(Lua::Lua52),
             0x53 => Some(Lua::Lua53),
+            0x54 => Some(Lua::Lua54),
             _ => None,
         }
     }
 
+    /// Parses a version number like `5.1` (as used in the configuration) to the version.
+    pub fn from_version(v: &str) -> Option<Lua> {
+        match v {
+            "5.1" => Some(Lua::Lua51),
+            "5.2" => Some(Lua::Lua52),
+            "5.3" => Some(Lua::Lua53),
+            "5.4" => Some(Lua::Lua54),
+            _ => None,
+        }
+    }
+
+    /// Returns true if this version has a distinct 64-bit integer subtype of numbers,
+    /// along with the floor division (`//`) and bitwise operators.
+    pub fn has_integers(&self) -> bool {
+        *self >= Lua::Lua53
+    }
+
     pub fn name(&self) -> &'static str {
         match *self {
             Lua::Lua51 => "Lua 5.1",
             Lua::Lua52 => "Lua 5.2",
             Lua::Lua53 => "Lua 5.3",
+            Lua::Lua54 => "Lua 5.4",
         }
     }
 }
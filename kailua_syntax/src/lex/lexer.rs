@@ -6,6 +6,7 @@ use kailua_diag as diag;
 use kailua_env::{SourceData, Pos, Span, Spanned, WithLoc};
 use kailua_env::SourceData::{U8, U16, EOF};
 use kailua_diag::{Report, Reporter, Localize};
+use lang::Language;
 
 use super::{Tok, Punct, Keyword};
 
@@ -35,14 +36,27 @@ pub struct Lexer<'a> {
     meta: bool,
     meta_span: Span,
     shebang: Option<Span>,
+    // a token already read but not yet returned (currently only `..` after an integer)
+    pending: Option<Spanned<Tok>>,
     eof: bool,
+    language: Language,
     report: &'a Report,
 }
 
 impl<'a> Lexer<'a> {
     /// Creates a lexer with given stream of spanned source data and the report receiver.
+    ///
+    /// The lexer assumes the default language (Lua 5.1).
     pub fn new(bytes: &'a mut Iterator<Item=Spanned<SourceData>>,
                report: &'a Report) -> Lexer<'a> {
+        Lexer::with_language(bytes, Language::default(), report)
+    }
+
+    /// Same to `Lexer::new` but generates tokens for given language.
+    ///
+    /// Lua 5.3 and later have additional operators and distinct integer literals (`Tok::Int`).
+    pub fn with_language(bytes: &'a mut Iterator<Item=Spanned<SourceData>>, language: Language,
+                         report: &'a Report) -> Lexer<'a> {
         let mut first = bytes.next().expect("no EOF after the end of input stream");
 
         // if the first letter is `#`, skip the entire line as a comment
@@ -77,11 +91,25 @@ impl<'a> Lexer<'a> {
             meta: false,
             meta_span: Span::dummy(),
             shebang: shebang,
+            pending: None,
             eof: false,
+            language: language,
             report: report,
         }
     }
 
+    /// Same to `Lexer::new` but starts as if the input follows a meta comment (e.g. `--:`).
+    ///
+    /// This allows for parsing Kailua-specific fragments (e.g. types) without any comment.
+    /// Like ordinary meta comments, the meta block ends at the first newline.
+    pub fn new_in_meta(bytes: &'a mut Iterator<Item=Spanned<SourceData>>,
+                       report: &'a Report) -> Lexer<'a> {
+        let mut lexer = Lexer::new(bytes, report);
+        lexer.meta = true;
+        lexer.meta_span = lexer.last_pos.into();
+        lexer
+    }
+
     fn pos(&self) -> Pos {
         if self.lookahead {
             self.last_pos
@@ -344,6 +372,9 @@ impl<'a> Lexer<'a> {
         if let Some(span) = self.shebang.take() {
             return Ok(Some(Tok::Comment.with_loc(span)));
         }
+        if let Some(tok) = self.pending.take() {
+            return Ok(Some(tok));
+        }
 
         loop {
             // skip any whitespace
@@ -363,6 +394,7 @@ impl<'a> Lexer<'a> {
                 (@token Keyword($e:expr)) => (Tok::Keyword($e));
                 (@token Name($e:expr))    => (Tok::Name($e));
                 (@token Num($e:expr))     => (Tok::Num($e));
+                (@token Int($e:expr))     => (Tok::Int($e));
                 (@token Str($e:expr))     => (Tok::Str($e));
                 (@token $i:ident)         => (Tok::Punct(Punct::$i));
 
@@ -397,6 +429,7 @@ impl<'a> Lexer<'a> {
 
                 // numbers
                 U8(c @ b'0'...b'9') => {
+                    let integers = self.language.lua().has_integers();
                     if c == b'0' && self.try(|c| c == U8(b'x')).is_some() {
                         // hexadecimal
                         let mut num = Vec::new();
@@ -411,6 +444,11 @@ impl<'a> Lexer<'a> {
                         if s.is_empty() {
                             self.report.error(begin..self.pos(), m::InvalidNumber {}).done()?;
                             // continue reading other tokens
+                        } else if integers {
+                            // hexadecimal integers wrap around in Lua 5.3+;
+                            // only the last 16 digits (64 bits) are significant
+                            let v = u64::from_str_radix(&s[s.len().saturating_sub(16)..], 16);
+                            return tok!(Int(v.unwrap() as i64));
                         } else if s.len() <= 16 {
                             let v = u64::from_str_radix(s, 16).unwrap();
                             return tok!(Num(v as f64));
@@ -429,7 +467,21 @@ impl<'a> Lexer<'a> {
                     } else {
                         let mut num = vec![c];
                         self.scan_while(is_digit, |c| num.push(c.u8()));
+                        let int_len = num.len();
+                        let dot_begin = self.pos();
                         if let Some(c) = self.try(|c| c == U8(b'.')) {
+                            // `1..10` in meta blocks is an integer range, not `1.` and `.10`
+                            if self.meta && self.try(|c| c == U8(b'.')).is_some() {
+                                let s = str::from_utf8(&num).unwrap();
+                                let v = s.parse::<f64>().unwrap();
+                                let span = Span::new(dot_begin, self.pos());
+                                self.pending = Some(Tok::Punct(Punct::DotDot).with_loc(span));
+                                let tok = match s.parse::<i64>() {
+                                    Ok(v) if integers => Tok::Int(v),
+                                    _ => Tok::Num(v),
+                                };
+                                return Ok(Some(tok.with_loc(Span::new(begin, dot_begin))));
+                            }
                             num.push(c.u8());
                             self.scan_while(is_digit, |c| num.push(c.u8()));
                         }
@@ -442,6 +494,12 @@ impl<'a> Lexer<'a> {
                         }
 
                         if let Ok(s) = str::from_utf8(&num) {
+                            // decimal integers that do not fit in 64 bits are floats in Lua 5.3+
+                            if integers && num.len() == int_len {
+                                if let Ok(v) = s.parse::<i64>() {
+                                    return tok!(Int(v));
+                                }
+                            }
                             if let Ok(v) = s.parse::<f64>() {
                                 return tok!(Num(v));
                             }
@@ -523,7 +581,12 @@ impl<'a> Lexer<'a> {
 
                 U8(b'+') => return tok!(Plus),
                 U8(b'*') => return tok!(Star),
-                U8(b'/') => return tok!(Slash),
+                U8(b'/') => {
+                    if self.language.lua().has_integers() {
+                        if let Some(_) = self.try(|c| c == U8(b'/')) { return tok!(SlashSlash); }
+                    }
+                    return tok!(Slash);
+                },
                 U8(b'%') => return tok!(Percent),
                 U8(b'^') => return tok!(Caret),
                 U8(b'#') => return tok!(Hash),
@@ -533,17 +596,27 @@ impl<'a> Lexer<'a> {
                 },
                 U8(b'~') => {
                     if let Some(_) = self.try(|c| c == U8(b'=')) { return tok!(TildeEq); }
+                    if self.language.lua().has_integers() { return tok!(Tilde); }
                     self.report.error(begin..self.pos(), m::UnexpectedChar {}).done()?;
                     // continue reading other tokens
                 },
+                // shifts are not generated in the meta block, where `>>` may close type arguments
                 U8(b'<') => {
                     if let Some(_) = self.try(|c| c == U8(b'=')) { return tok!(LtEq); }
+                    if !self.meta && self.language.lua().has_integers() {
+                        if let Some(_) = self.try(|c| c == U8(b'<')) { return tok!(LtLt); }
+                    }
                     return tok!(Lt);
                 },
                 U8(b'>') => {
                     if let Some(_) = self.try(|c| c == U8(b'=')) { return tok!(GtEq); }
+                    if !self.meta && self.language.lua().has_integers() {
+                        if let Some(_) = self.try(|c| c == U8(b'>')) { return tok!(GtGt); }
+                    }
                     return tok!(Gt);
                 },
+                U8(b'&') if self.language.lua().has_integers() => return tok!(Amp),
+                U8(b'|') if self.language.lua().has_integers() => return tok!(Pipe),
                 U8(b'(') => return tok!(LParen),
                 U8(b')') => return tok!(RParen),
                 U8(b'{') => return tok!(LBrace),
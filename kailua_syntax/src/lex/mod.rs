@@ -26,8 +26,14 @@ pub enum Tok {
     Keyword(Keyword),
 
     /// A number.
+    ///
+    /// Since Lua 5.3 this is only used for float literals
+    /// and decimal integer literals that do not fit in 64 bits.
     Num(f64),
 
+    /// An integer literal that fits in 64 bits. Only generated since Lua 5.3.
+    Int(i64),
+
     /// A name (either an identifier or a quoted name in the meta block).
     Name(Name),
 
@@ -42,7 +48,7 @@ pub enum Tok {
 
 impl Localize for Tok {
     fn fmt_localized(&self, f: &mut fmt::Formatter, locale: Locale) -> fmt::Result {
-        match (&locale[..], self) {
+        match (&locale.resolve(&["ko"])[..], self) {
             ("ko", &Tok::Error)      => write!(f, "잘못된 문자"),
             (_,    &Tok::Error)      => write!(f, "an invalid character"),
             ("ko", &Tok::Comment)    => write!(f, "주석"),
@@ -51,6 +57,8 @@ impl Localize for Tok {
             (_,    &Tok::Keyword(w)) => write!(f, "{}", Localized::new(&w, locale)),
             ("ko", &Tok::Num(_))     => write!(f, "숫자"),
             (_,    &Tok::Num(_))     => write!(f, "a number"),
+            ("ko", &Tok::Int(_))     => write!(f, "숫자"),
+            (_,    &Tok::Int(_))     => write!(f, "a number"),
             ("ko", &Tok::Name(_))    => write!(f, "이름"),
             (_,    &Tok::Name(_))    => write!(f, "a name"),
             ("ko", &Tok::Str(_))     => write!(f, "문자열 리터럴"),
@@ -74,7 +82,7 @@ macro_rules! define_puncts {
         /// This includes Kailua-specific punctuations,
         /// which are only generated in the meta block (marked as [M] below).
         /// Some of them are also only generated after a particular Lua version
-        /// (marked as [5.x+] below).
+        /// (marked as [5.x+] below) given to the lexer.
         #[derive(Copy, Clone, Debug, PartialEq, Eq)]
         pub enum $ty { $(#[$m] $i,)* }
 
@@ -129,7 +137,7 @@ define_puncts! { Punct |locale|:
     DashDashGt      "`-->`",    /// `-->`. [M]
     Ques            "`?`",      /// `?`. [M]
     Bang            "`!`",      /// `!`. [M]
-    Newline         match &locale[..] { "ko" => "개행문자", _ => "a newline" },
+    Newline         match &locale.resolve(&["ko"])[..] { "ko" => "개행문자", _ => "a newline" },
                     /// A newline. Only generated at the end of the meta block.
 }
 
@@ -219,7 +227,7 @@ impl From<Keyword> for Name {
 impl Localize for Keyword {
     fn fmt_localized(&self, f: &mut fmt::Formatter, locale: Locale) -> fmt::Result {
         let name = str::from_utf8(self.name()).unwrap();
-        match &locale[..] {
+        match &locale.resolve(&["ko"])[..] {
             "ko" => write!(f, "예약어 `{}`", name),
             _ => write!(f, "a keyword `{}`", name),
         }
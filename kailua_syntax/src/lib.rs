@@ -39,13 +39,17 @@
 #[macro_use] extern crate log;
 extern crate kailua_env;
 
-use kailua_env::{Source, Span};
-use kailua_diag::Report;
+use std::fmt;
+use std::ops;
+use std::error::Error;
+use kailua_env::{Source, SourceFile, Span, Spanned};
+use kailua_diag::{Report, CancellationToken, Interrupted};
 
 pub use string::{Str, Name};
 pub use lex::{Lexer, Nest, Tok, NestedToken};
-pub use ast::Chunk;
+pub use ast::{Chunk, Kind};
 pub use parser::Parser;
+use lang::Language;
 
 pub mod lang;
 mod message;
@@ -60,10 +64,16 @@ mod parser;
 /// it can continue in spite of reported errors.
 /// `kailua_diag::report::TrackMaxKind` is useful for this.
 pub fn parse_chunk(source: &Source, span: Span, report: &Report) -> kailua_diag::Result<Chunk> {
+    parse_chunk_with_language(source, span, Language::default(), report)
+}
+
+/// Same to `parse_chunk` but parses given language instead of the default (Lua 5.1).
+pub fn parse_chunk_with_language(source: &Source, span: Span, language: Language,
+                                 report: &Report) -> kailua_diag::Result<Chunk> {
     if let Some(mut iter) = source.iter_from_span(span) {
-        let mut lexer = Lexer::new(&mut iter, &report);
+        let mut lexer = Lexer::with_language(&mut iter, language, &report);
         let mut nest = Nest::new(&mut lexer);
-        let parser = Parser::new(&mut nest, &report);
+        let parser = Parser::with_language(&mut nest, language, &report);
         parser.into_chunk()
     } else {
         use kailua_diag::Reporter;
@@ -71,3 +81,74 @@ pub fn parse_chunk(source: &Source, span: Span, report: &Report) -> kailua_diag:
     }
 }
 
+/// Same to `parse_chunk_with_language` but stops as soon as given token gets cancelled.
+///
+/// Reports generated before the cancellation are partial and should be discarded.
+pub fn parse_chunk_with_cancellation(source: &Source, span: Span, language: Language,
+                                     report: &Report, token: &CancellationToken)
+        -> Result<Chunk, Interrupted> {
+    if let Some(mut iter) = source.iter_from_span(span) {
+        let mut lexer = Lexer::with_language(&mut iter, language, &report);
+        let mut nest = Nest::new(&mut lexer);
+        let mut parser = Parser::with_language(&mut nest, language, &report);
+        parser.set_cancellation_token(token.clone());
+        token.interrupted(parser.into_chunk())
+    } else {
+        use kailua_diag::Reporter;
+        Ok(report.fatal(span, message::NoFileForSpan {}).done()?)
+    }
+}
+
+
+/// An error returned by `parse_kind`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KindError {
+    /// The byte range of the (first) error within the string.
+    pub range: ops::Range<usize>,
+
+    /// The error message in the default locale.
+    pub message: String,
+}
+
+impl fmt::Display for KindError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at {}..{})", self.message, self.range.start, self.range.end)
+    }
+}
+
+impl Error for KindError {
+    fn description(&self) -> &str { &self.message }
+}
+
+/// Parses a standalone type in the Kailua syntax, e.g. `vector<string>`.
+///
+/// The string is parsed as if it follows `--:`, so it should be a single line.
+/// Spans in the resulting type belong to no `Source`, but their positions are
+/// byte offsets within the string (`Pos::to_usize`).
+/// Unlike `parse_chunk` this is not recoverable; the first error is returned.
+pub fn parse_kind(s: &str) -> Result<Spanned<Kind>, KindError> {
+    use kailua_diag::{Locale, CollectedReport, Kind as ReportKind};
+
+    let mut source = Source::new();
+    let span = source.add(SourceFile::from_u8(String::new(), s.as_bytes().to_owned()));
+    let report = CollectedReport::new(Locale::dummy());
+    let kind = {
+        let mut iter = source.iter_from_span(span).unwrap();
+        let mut lexer = Lexer::new_in_meta(&mut iter, &report);
+        let mut nest = Nest::new(&mut lexer);
+        let parser = Parser::new(&mut nest, &report);
+        parser.into_kind()
+    };
+
+    let error = report.into_reports().into_iter().find(|&(kind, _, _)| kind >= ReportKind::Error);
+    match (kind, error) {
+        (Ok(kind), None) => Ok(kind),
+        (_, Some((_, span, message))) => {
+            let range = span.begin().to_usize()..span.end().to_usize();
+            Err(KindError { range: range, message: message })
+        }
+        (Err(_), None) => {
+            Err(KindError { range: 0..s.len(), message: format!("invalid type") })
+        }
+    }
+}
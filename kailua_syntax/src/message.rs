@@ -97,6 +97,11 @@ define_msg! { pub NoNewline<'a> { read: &'a Tok }:
     _    => "Expected a newline, got {read}",
 }
 
+define_msg! { pub NoEndOfType<'a> { read: &'a Tok }:
+    "ko" => "타입이 끝나야 하는데 {read}이(가) 나왔습니다",
+    _    => "Expected the end of the type, got {read}",
+}
+
 define_msg! { pub NoName<'a> { read: &'a Tok }:
     "ko" => "이름이 나와야 하는데 {read}이(가) 나왔습니다",
     _    => "Expected a name, got {read}",
@@ -362,6 +367,16 @@ define_msg! { pub FirstArgNameInFuncKind:
     _    => "The first duplicate appeared here",
 }
 
+define_msg! { pub DuplicateTypeParam<'a> { name: &'a Name }:
+    "ko" => "타입 인자 {name}이 중복됩니다",
+    _    => "Duplicate type parameter {name}",
+}
+
+define_msg! { pub FirstTypeParam:
+    "ko" => "여기서 처음 나왔습니다",
+    _    => "The first duplicate appeared here",
+}
+
 define_msg! { pub PartiallyNamedFieldsInFuncKind:
     "ko" => "타입에서 일부 인자에만 이름이 붙어 있습니다",
     _    => "Not all but only some arguments in the type are named",
@@ -382,6 +397,11 @@ define_msg! { pub NoTypeSeqInUnion:
     _    => "A sequence of types cannot be inside a union",
 }
 
+define_msg! { pub NoTypeSeqInDifference:
+    "ko" => "타입열은 다른 타입에서 제외될 수 없습니다",
+    _    => "A sequence of types cannot be excluded from another type",
+}
+
 define_msg! { pub NoSingleTypeButTypeSeq:
     "ko" => "하나의 타입이 나와야 하는데 타입열이 나왔습니다",
     _    => "Expected a single type, not type sequence",
@@ -412,16 +432,41 @@ define_msg! { pub WrongMapParamsArity:
     _    => "`map` type needs two type parameters",
 }
 
+define_msg! { pub WrongThreadParamsArity:
+    "ko" => "`thread` 타입에는 타입 인자가 없거나 두 개 있어야 합니다",
+    _    => "`thread` type needs either no or two type parameters",
+}
+
 define_msg! { pub WrongMapParamsModf:
     "ko" => "`map` 타입의 첫 타입 인자에는 변수 종류를 사용할 수 없습니다",
     _    => "The first type parameter of `map` type cannot have modifiers",
 }
 
+define_msg! { pub TypeArgsCannotHaveModf:
+    "ko" => "타입 인자에는 변수 종류를 사용할 수 없습니다",
+    _    => "Type arguments cannot have modifiers",
+}
+
 define_msg! { pub NonIntegerType:
     "ko" => "정수가 아닌 숫자나, 너무 작거나 큰 정수는 타입으로 쓸 수 없습니다",
     _    => "Cannot use a non-integer, or too small or too large integer as a type",
 }
 
+define_msg! { pub NoIntegerTypeAfterDash<'a> { read: &'a Tok }:
+    "ko" => "`-` 뒤에 정수 타입이 나와야 하는데 {read}이(가) 나왔습니다",
+    _    => "Expected an integer type after `-`, got {read}",
+}
+
+define_msg! { pub NoIntegerInRange<'a> { read: &'a Tok }:
+    "ko" => "정수 범위 타입에는 정수가 나와야 하는데 {read}이(가) 나왔습니다",
+    _    => "Expected an integer in the integer range type, got {read}",
+}
+
+define_msg! { pub EmptyIntegerRange { lo: i32, hi: i32 }:
+    "ko" => "정수 범위 타입의 하한 {lo}이(가) 상한 {hi}보다 큽니다",
+    _    => "The lower bound {lo} of the integer range type is greater than the upper bound {hi}",
+}
+
 define_msg! { pub ReservedKindName<'a> { name: &'a Name }:
     "ko" => "{name} 타입 이름은 예약되어 있으며 사용할 수 없습니다",
     _    => "The type name {name} is reserved and cannot be used",
@@ -437,6 +482,16 @@ define_msg! { pub ModuleModfInNonAssign:
     _    => "`module` can only be used in top-level types of assignments or `local`s",
 }
 
+define_msg! { pub UnknownLocalAttrib<'a> { name: &'a Name }:
+    "ko" => "지역 변수 속성 {name}은(는) 알 수 없는 속성이며, `const`나 `close`여야 합니다",
+    _    => "Unknown local variable attribute {name}, should be either `const` or `close`",
+}
+
+define_msg! { pub LocalAttribWithModule:
+    "ko" => "`module` 타입 수정자와 지역 변수 속성은 함께 쓸 수 없습니다",
+    _    => "Local variable attributes cannot be used with the `module` modifier",
+}
+
 define_msg! { pub FutureKeyword<'a> { read: &'a Tok, current: Lua, future: Lua }:
     "ko" => "{read}은(는) {current}에서는 이름으로 처리되지만 \
              {future}부터는 예약어가 되었으므로 쓰지 않는 것이 좋습니다",
@@ -482,6 +537,11 @@ define_msg! { pub AssumeClassStatic:
     _    => "`--# assume static` can only be used to set fields in class prototypes",
 }
 
+define_msg! { pub AssumeClassScoped:
+    "ko" => "클래스는 되돌릴 수 없으므로 `--# assume scoped class`는 쓸 수 없습니다",
+    _    => "`--# assume scoped class` is not allowed because classes cannot be undone",
+}
+
 define_msg! { pub TypeGlobalInLocalScope:
     "ko" => "`--# type global`은 최상위 블록에서만 쓸 수 있습니다",
     _    => "`--# type global` should be in the top-level scope",
@@ -7,15 +7,15 @@ use std::fmt;
 use std::result;
 use std::collections::{hash_map, HashMap};
 use kailua_env::{Pos, Span, Spanned, WithLoc, Scope, ScopedId, ScopeMap};
-use kailua_diag::{report, Locale, Report, Reporter, Localize};
+use kailua_diag::{report, Locale, Report, Reporter, Localize, CancellationToken};
 
 use message as m;
-use lang::{Language, Lua, Kailua};
+use lang::{Language, Lua};
 use lex::{Tok, Punct, Keyword, NestedToken, NestingCategory, NestingSerial};
 use string::{Str, Name};
 use ast::{NameRef, RenameRef, Var, Seq, Sig, Attr, AttrValue, Args, Table};
 use ast::{Ex, Exp, UnOp, BinOp, SelfParam, TypeScope, St, Stmt, Block};
-use ast::{M, MM, K, Kind, SlotKind, FuncKind, TypeSpec, Varargs, Returns};
+use ast::{M, MM, K, Kind, SlotKind, FuncKind, InterfaceField, TypeSpec, Varargs, Returns};
 use ast::{LocalName, LocalNameKind, TokenAux, Chunk};
 
 /// The parser.
@@ -55,6 +55,9 @@ pub struct Parser<'a> {
 
     // auxiliary info for each *input* token (i.e. including elided tokens)
     token_aux: Vec<TokenAux>,
+
+    // checked for each statement and table item
+    cancel_token: Option<CancellationToken>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -79,7 +82,7 @@ type Result<T> = result::Result<T, Stop>;
 
 impl Localize for EOF {
     fn fmt_localized(&self, f: &mut fmt::Formatter, locale: Locale) -> fmt::Result {
-        match &locale[..] {
+        match &locale.resolve(&["ko"])[..] {
             "ko" => write!(f, "파일의 끝"),
             _ => write!(f, "end of file"),
         }
@@ -249,6 +252,7 @@ impl Eq for IndexedName {
 #[derive(Clone, PartialEq)]
 struct Presig {
     prefix: Spanned<bool>, // true for `method`, false for `function` (span included)
+    type_params: Vec<Spanned<Name>>,
     args: Spanned<Seq<Spanned<TypeSpec<Spanned<IndexedName>>>, Spanned<Option<Spanned<Kind>>>>>,
     returns: Option<Returns>,
 }
@@ -352,10 +356,21 @@ macro_rules! match_next {
 impl<'a> Parser<'a> {
     /// Creates a new nesting analyzer with given stream of spanned tokens
     /// with nesting informations and the report receiver.
+    ///
+    /// The parser assumes the default language (Lua 5.1).
     pub fn new(iter: &'a mut Iterator<Item=NestedToken>, report: &'a Report) -> Parser<'a> {
+        Parser::with_language(iter, Language::default(), report)
+    }
+
+    /// Same to `Parser::new` but parses given language.
+    ///
+    /// Tokens should have been generated by the lexer with the same language
+    /// (`Lexer::with_language`).
+    pub fn with_language(iter: &'a mut Iterator<Item=NestedToken>, language: Language,
+                         report: &'a Report) -> Parser<'a> {
         let mut parser = Parser {
             iter: iter.fuse(),
-            language: Language::new(Lua::Lua51, Kailua::Kailua10), // XXX should be configurable
+            language: language,
             elided_newline: None,
             lookahead: None,
             lookahead2: None,
@@ -373,6 +388,7 @@ impl<'a> Parser<'a> {
             scope_stack: Vec::new(),
             block_depth: 0,
             token_aux: Vec::new(),
+            cancel_token: None,
         };
 
         // read the first token and fill the last_span
@@ -383,6 +399,21 @@ impl<'a> Parser<'a> {
         parser
     }
 
+    /// Makes the parser stop with `Stop` as soon as given token gets cancelled.
+    ///
+    /// The parser stops without reporting anything in that case.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancel_token = Some(token);
+    }
+
+    // cancellation is not recoverable
+    fn keep_going(&self) -> Result<()> {
+        match self.cancel_token {
+            Some(ref token) if token.is_cancelled() => Err(Stop::Fatal),
+            _ => Ok(()),
+        }
+    }
+
     fn _next(&mut self) -> Option<(usize, NestedToken)> {
         loop {
             let next = self.iter.next();
@@ -950,6 +981,26 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // NAME [`<` NAME `>`], where the latter is a Lua 5.4 attribute.
+    // both `const` and `close` make the variable read-only, which is all Kailua models;
+    // the returned span is that of the attribute if any.
+    fn parse_local_name(&mut self) -> Result<Spanned<(IndexedName, Option<Span>)>> {
+        let name = self.parse_name()?;
+        let mut attrib = None;
+        if self.language.lua() >= Lua::Lua54 && self.may_expect(Punct::Lt) {
+            let attribname = self.try_name_or_keyword()?;
+            if &attribname.base[..] == b"const" || &attribname.base[..] == b"close" {
+                attrib = Some(attribname.span);
+            } else {
+                self.error(&attribname, m::UnknownLocalAttrib { name: &attribname.base })
+                    .done()?;
+            }
+            self.expect(Punct::Gt)?;
+        }
+        let span = name.span;
+        Ok((name.base, attrib).with_loc(span))
+    }
+
     fn try_name_or_keyword(&mut self) -> Result<Spanned<Name>> {
         match_next! { self;
             Tok::Name(name) in span => Ok(name.with_loc(span));
@@ -1027,6 +1078,7 @@ impl<'a> Parser<'a> {
         let mut pastlast = false;
         let mut excessspan = None;
         loop {
+            self.keep_going()?;
             let stmt = match self.try_parse_stmt() {
                 Ok(Some(stmt)) => stmt,
                 Ok(None) => {
@@ -1107,6 +1159,7 @@ impl<'a> Parser<'a> {
 
     fn try_parse_stmt(&mut self) -> Result<Option<Spanned<Stmt>>> {
         trace!("parsing stmt");
+        self.keep_going()?;
         let begin = self.pos();
 
         let funcspec = self.try_parse_kailua_func_spec()?;
@@ -1296,7 +1349,7 @@ impl<'a> Parser<'a> {
 
                         let mut names = Vec::new();
                         let (span, eq) =
-                            self.scan_list_with_spec(Self::parse_name,
+                            self.scan_list_with_spec(Self::parse_local_name,
                                                      |namespec| names.push(namespec))?;
                         let mut names = names.with_loc(span);
 
@@ -1321,8 +1374,19 @@ impl<'a> Parser<'a> {
                         // XXX should also mention all excess arguments
                         let mut namerefs = Vec::new().with_loc(names.span);
                         for namespec in names.base {
-                            let name = self.add_spanned_local_name(sibling_scope, namespec.base)?;
-                            namerefs.push(TypeSpec { base: name, modf: namespec.modf,
+                            let (name, attrib) = namespec.base.base;
+                            let name = name.with_loc(namespec.base.span);
+                            let mut modf = namespec.modf;
+                            if let Some(attrib) = attrib {
+                                match modf {
+                                    MM::None | MM::Const => modf = MM::Const,
+                                    MM::Module => {
+                                        self.error(attrib, m::LocalAttribWithModule {}).done()?;
+                                    }
+                                }
+                            }
+                            let name = self.add_spanned_local_name(sibling_scope, name)?;
+                            namerefs.push(TypeSpec { base: name, modf: modf,
                                                      kind: namespec.kind });
                         }
                         Box::new(St::Local(namerefs, exps, sibling_scope))
@@ -1547,7 +1611,7 @@ impl<'a> Parser<'a> {
         let end = end.unwrap_or_else(|| self.last_pos());
         returns = self.try_parse_kailua_rettype_spec()?;
 
-        let (attrs, args, returns) = match funcspec {
+        let (attrs, type_params, args, returns) = match funcspec {
             Some(Spanned { base: (attrs, Some(presig)), .. }) => {
                 // before any checking, we should ensure that every parameter has a type attached
                 // (`self` is a notable exception, but should have been removed by now)
@@ -1625,15 +1689,16 @@ impl<'a> Parser<'a> {
                 }
 
                 let args = Seq { head: combinedargs, tail: presig.base.args.base.tail };
-                (attrs, Ok(args.with_loc(presig.base.args.span)), presig.base.returns)
+                (attrs, presig.base.type_params,
+                 Ok(args.with_loc(presig.base.args.span)), presig.base.returns)
             },
 
             Some(Spanned { base: (attrs, None), .. }) => {
-                (attrs, Err(args), returns.map(|ret| ret.base))
+                (attrs, Vec::new(), Err(args), returns.map(|ret| ret.base))
             },
 
             None => {
-                (Vec::new(), Err(args), returns.map(|ret| ret.base))
+                (Vec::new(), Vec::new(), Err(args), returns.map(|ret| ret.base))
             },
         };
 
@@ -1695,7 +1760,7 @@ impl<'a> Parser<'a> {
             Ok((selfparam, args))
         })?;
 
-        let sig = Sig { attrs: attrs, args: args, returns: returns };
+        let sig = Sig { attrs: attrs, type_params: type_params, args: args, returns: returns };
         Ok(Some((selfparam, sig, scope, block)))
     }
 
@@ -1713,6 +1778,7 @@ impl<'a> Parser<'a> {
                     parser.expect(Punct::RBrace)?;
                     break;
                 }
+                parser.keep_going()?;
                 let item = scan(parser)?;
                 items.push(item);
 
@@ -1920,6 +1986,7 @@ impl<'a> Parser<'a> {
             Tok::Keyword(Keyword::False) in span => Ok(Some(Box::new(Ex::False).with_loc(span)));
             Tok::Keyword(Keyword::True) in span => Ok(Some(Box::new(Ex::True).with_loc(span)));
             Tok::Num(v) in span => Ok(Some(Box::new(Ex::Num(v)).with_loc(span)));
+            Tok::Int(v) in span => Ok(Some(Box::new(Ex::Int(v)).with_loc(span)));
             Tok::Str(s) in span => Ok(Some(Box::new(Ex::Str(s)).with_loc(span)));
             Tok::Punct(Punct::DotDotDot) in span => Ok(Some(Box::new(Ex::Varargs).with_loc(span)));
 
@@ -1973,6 +2040,7 @@ impl<'a> Parser<'a> {
             Tok::Punct(Punct::Dash) => Some(UnOp::Neg),
             Tok::Keyword(Keyword::Not) => Some(UnOp::Not),
             Tok::Punct(Punct::Hash) => Some(UnOp::Len),
+            Tok::Punct(Punct::Tilde) => Some(UnOp::BNot),
             _ => None,
         };
         op.map(|op| op.with_loc(tok))
@@ -1985,9 +2053,15 @@ impl<'a> Parser<'a> {
             Tok::Punct(Punct::Dash) => Some(BinOp::Sub),
             Tok::Punct(Punct::Star) => Some(BinOp::Mul),
             Tok::Punct(Punct::Slash) => Some(BinOp::Div),
+            Tok::Punct(Punct::SlashSlash) => Some(BinOp::IDiv),
             Tok::Punct(Punct::Caret) => Some(BinOp::Pow),
             Tok::Punct(Punct::Percent) => Some(BinOp::Mod),
             Tok::Punct(Punct::DotDot) => Some(BinOp::Cat),
+            Tok::Punct(Punct::Amp) => Some(BinOp::BAnd),
+            Tok::Punct(Punct::Pipe) => Some(BinOp::BOr),
+            Tok::Punct(Punct::Tilde) => Some(BinOp::BXor),
+            Tok::Punct(Punct::LtLt) => Some(BinOp::Shl),
+            Tok::Punct(Punct::GtGt) => Some(BinOp::Shr),
             Tok::Punct(Punct::Lt) => Some(BinOp::Lt),
             Tok::Punct(Punct::LtEq) => Some(BinOp::Le),
             Tok::Punct(Punct::Gt) => Some(BinOp::Gt),
@@ -2018,7 +2092,7 @@ impl<'a> Parser<'a> {
         fn unary_prec(op: UnOp) -> /*recursion*/ u8 {
             match op {
                 // binary ^ operator here
-                UnOp::Neg | UnOp::Not | UnOp::Len => 8,
+                UnOp::Neg | UnOp::Not | UnOp::Len | UnOp::BNot => 12,
                 // other binary operators here
             }
         }
@@ -2040,11 +2114,15 @@ impl<'a> Parser<'a> {
                                 mut exp: Spanned<Exp>) -> Result<Spanned<Exp>> {
         fn binary_prec(op: BinOp) -> (/*comparison*/ u8, /*recursion*/ u8) {
             match op {
-                BinOp::Pow => (10, 9),
+                BinOp::Pow => (14, 13),
                 // unary operators here
-                BinOp::Mul | BinOp::Div | BinOp::Mod => (7, 7),
-                BinOp::Add | BinOp::Sub => (6, 6),
-                BinOp::Cat => (5, 4),
+                BinOp::Mul | BinOp::Div | BinOp::IDiv | BinOp::Mod => (11, 11),
+                BinOp::Add | BinOp::Sub => (10, 10),
+                BinOp::Cat => (9, 8),
+                BinOp::Shl | BinOp::Shr => (7, 7),
+                BinOp::BAnd => (6, 6),
+                BinOp::BXor => (5, 5),
+                BinOp::BOr => (4, 4),
                 BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge | BinOp::Eq | BinOp::Ne => (3, 3),
                 BinOp::And => (2, 2),
                 BinOp::Or => (1, 1),
@@ -2216,6 +2294,9 @@ impl<'a> Parser<'a> {
             Tok::Name(name) in span => {
                 Ok(Some(AttrValue::Name(Name::from(name).with_loc(span)).with_loc(span)))
             };
+            Tok::Str(s) in span => {
+                Ok(Some(AttrValue::Str(s.with_loc(span)).with_loc(span)))
+            };
             'unread: _ => Ok(None);
         }
     }
@@ -2266,6 +2347,7 @@ impl<'a> Parser<'a> {
     fn parse_kailua_slotkind_after_name(&mut self, begin: Pos,
                                         name: Spanned<IndexedName>) -> Result<Spanned<SlotKind>> {
         let kind = self.parse_kailua_kind_after_name(begin, name)?;
+        let kind = self.parse_kailua_kind_after_kind(begin, kind)?;
         Ok(SlotKind { modf: M::None, kind: kind }.with_loc(begin..self.last_pos()))
     }
 
@@ -2309,6 +2391,7 @@ impl<'a> Parser<'a> {
                         Ok(Some((Some(name), kind)))
                     } else {
                         let kind = parser.parse_kailua_kind_after_name(begin, name)?;
+                        let kind = parser.parse_kailua_kind_after_kind(begin, kind)?;
                         Ok(Some((None, kind)))
                     }
                 };
@@ -2426,9 +2509,32 @@ impl<'a> Parser<'a> {
         Ok(Seq { head: specs, tail: tail }.with_loc(begin..end))
     }
 
+    fn parse_kailua_type_params(&mut self) -> Result<Vec<Spanned<Name>>> {
+        let mut params: Vec<Spanned<Name>> = Vec::new();
+        if self.may_expect(Punct::Lt) {
+            // `<` NAME {`,` NAME} `>`
+            loop {
+                let name = self.parse_name()?.map(|n| n.name);
+                if let Some(prev) = params.iter().find(|param| param.base == name.base) {
+                    self.error(&name, m::DuplicateTypeParam { name: &name.base })
+                        .note(prev.span, m::FirstTypeParam {})
+                        .done()?;
+                } else {
+                    params.push(name);
+                }
+                if !self.may_expect(Punct::Comma) {
+                    break;
+                }
+            }
+            self.expect(Punct::Gt)?;
+        }
+        Ok(params)
+    }
+
     fn parse_kailua_funckind(&mut self) -> Result<Spanned<FuncKind>> {
         let begin = self.pos();
 
+        let type_params = self.parse_kailua_type_params()?;
         self.expect(Punct::LParen)?;
         let args = self.parse_kailua_kindlist(true)?;
         self.expect(Punct::RParen)?;
@@ -2449,7 +2555,7 @@ impl<'a> Parser<'a> {
         };
 
         let span = begin..self.last_pos();
-        Ok(FuncKind { args: args, returns: returns }.with_loc(span))
+        Ok(FuncKind { type_params: type_params, args: args, returns: returns }.with_loc(span))
     }
 
     fn try_parse_kailua_kind_params(&mut self)
@@ -2517,21 +2623,73 @@ impl<'a> Parser<'a> {
             Box::new(K::Error(reason)).with_loc(name.span)
         } else {
             let namespan = name.span;
-            let kind = match self.builtin_kind(&name.base.name) {
-                Some(Some(kind)) => kind,
+            match self.builtin_kind(&name.base.name) {
+                Some(Some(K::Integer)) if self.lookahead(Punct::LParen) => {
+                    // `integer` `(` INT `..` INT `)`
+                    self.expect(Punct::LParen)?;
+                    let lo = self.parse_kailua_integer_bound()?;
+                    self.expect(Punct::DotDot)?;
+                    let hi = self.parse_kailua_integer_bound()?;
+                    self.expect(Punct::RParen)?;
+                    let span = Span::from(begin..self.last_pos());
+                    match (lo, hi) {
+                        (Some(lo), Some(hi)) if lo <= hi => {
+                            Box::new(K::IntegerRange(lo, hi)).with_loc(span)
+                        }
+                        (Some(lo), Some(hi)) => {
+                            self.error(span, m::EmptyIntegerRange { lo: lo, hi: hi }).done()?;
+                            Box::new(K::Oops).with_loc(span)
+                        }
+                        (_, _) => Box::new(K::Oops).with_loc(span),
+                    }
+                },
+                Some(Some(K::Thread)) if self.lookahead(Punct::Lt) => {
+                    // `thread` `<` KIND `,` KIND `>`; type arguments cannot have modifiers
+                    let params = self.parse_kailua_kind_params()?;
+                    let span = namespan | params.span;
+                    if params.len() != 2 {
+                        self.error(&params, m::WrongThreadParamsArity {}).done()?;
+                        Box::new(K::Oops).with_loc(span)
+                    } else {
+                        let mut it = params.base.into_iter();
+                        let (ym, y) = it.next().unwrap();
+                        let (rm, r) = it.next().unwrap();
+                        for modf in &[ym, rm] {
+                            if modf.base != M::None {
+                                self.error(modf, m::TypeArgsCannotHaveModf {}).done()?;
+                            }
+                        }
+                        Box::new(K::Coroutine(y, r)).with_loc(span)
+                    }
+                },
+                Some(Some(kind)) => Box::new(kind).with_loc(namespan),
                 Some(None) => {
                     self.error(&name, m::ReservedKindName { name: &name }).done()?;
-                    K::Oops
+                    Box::new(K::Oops).with_loc(namespan)
                 },
                 None => {
-                    K::Named(name.map(|n| n.name))
+                    let name = name.map(|n| n.name);
+                    if let Some(params) = self.try_parse_kailua_kind_params()? {
+                        // NAME `<` KIND [`,` KIND] `>`; type arguments cannot have modifiers
+                        let span = namespan | params.span;
+                        let mut args = Vec::new();
+                        for (modf, kind) in params.base {
+                            if modf.base != M::None {
+                                self.error(&modf, m::TypeArgsCannotHaveModf {}).done()?;
+                            }
+                            args.push(kind);
+                        }
+                        Box::new(K::Generic(name, args)).with_loc(span)
+                    } else {
+                        Box::new(K::Named(name)).with_loc(namespan)
+                    }
                 },
-            };
-            Box::new(kind).with_loc(namespan)
+            }
         };
 
         let kind = self.parse_kailua_kind_suffix(begin, kind); // handle ? or !
-        let kind = self.parse_kailua_kind_after_kind(begin, kind)?; // handle `| KIND ...`
+        // handle `| KIND ...`; the caller should handle `- KIND` which has a lower precedence
+        let kind = self.parse_kailua_union_kind_after_kind(begin, kind)?;
         Ok(kind)
     }
 
@@ -2542,8 +2700,8 @@ impl<'a> Parser<'a> {
         let kind = match_next! { self;
             Tok::Keyword(Keyword::Function) in span => {
                 // either a "function" type or a function signature
-                if self.lookahead(Punct::LParen) {
-                    // function `(` ... `)` [`-->` ...]
+                if self.lookahead(Punct::LParen) || self.lookahead(Punct::Lt) {
+                    // function [`<` ... `>`] `(` ... `)` [`-->` ...]
                     let func = self.parse_kailua_funckind()?;
                     // cannot be followed by postfix operators
                     let kind = Box::new(K::Func(func)).with_loc(begin..self.last_pos());
@@ -2700,12 +2858,20 @@ impl<'a> Parser<'a> {
                 return Ok(Some(AtomicKind::One(kind)));
             };
 
-            Tok::Num(v) in span => {
-                if i32::MIN as f64 <= v && v <= i32::MAX as f64 && v.floor() == v {
-                    Box::new(K::IntegerLit(v as i32)).with_loc(span)
-                } else {
-                    self.error(span, m::NonIntegerType {}).done()?;
-                    Box::new(K::Oops).with_loc(span)
+            Tok::Num(v) in span => self.kailua_integer_lit(v, span)?;
+            Tok::Int(v) in span => self.kailua_integer_lit(v as f64, span)?;
+
+            // `-` is only used for negative integer literals in types
+            Tok::Punct(Punct::Dash) => {
+                match_next! { self;
+                    Tok::Num(v) => self.kailua_integer_lit(-v, Span::from(begin..self.last_pos()))?;
+                    Tok::Int(v) => {
+                        let span = Span::from(begin..self.last_pos());
+                        self.kailua_integer_lit(-(v as f64), span)?
+                    };
+                    'unread: _, m::NoIntegerTypeAfterDash => {
+                        Box::new(K::Oops).with_loc(begin..self.last_pos())
+                    };
                 }
             };
 
@@ -2718,6 +2884,32 @@ impl<'a> Parser<'a> {
         Ok(Some(AtomicKind::One(kind)))
     }
 
+    fn kailua_integer_lit(&mut self, v: f64, span: Span) -> Result<Spanned<Kind>> {
+        if i32::MIN as f64 <= v && v <= i32::MAX as f64 && v.floor() == v {
+            Ok(Box::new(K::IntegerLit(v as i32)).with_loc(span))
+        } else {
+            self.error(span, m::NonIntegerType {}).done()?;
+            Ok(Box::new(K::Oops).with_loc(span))
+        }
+    }
+
+    // an integer bound in `integer(lo..hi)`, None if it has been reported as invalid
+    fn parse_kailua_integer_bound(&mut self) -> Result<Option<i32>> {
+        let begin = self.pos();
+        let negative = self.may_expect(Punct::Dash);
+        let v = match_next! { self;
+            Tok::Num(v) => if negative { -v } else { v };
+            Tok::Int(v) => if negative { -(v as f64) } else { v as f64 };
+            'unread: _, m::NoIntegerInRange => return Err(Stop::Recover);
+        };
+        if i32::MIN as f64 <= v && v <= i32::MAX as f64 && v.floor() == v {
+            Ok(Some(v as i32))
+        } else {
+            self.error(begin..self.last_pos(), m::NonIntegerType {}).done()?;
+            Ok(None)
+        }
+    }
+
     fn try_parse_kailua_prefixed_kind_seq(&mut self) -> Result<Option<AtomicKind>> {
         let begin = self.pos();
 
@@ -2763,6 +2955,32 @@ impl<'a> Parser<'a> {
 
     fn parse_kailua_kind_after_kind(&mut self, begin: Pos,
                                     kind: Spanned<Kind>) -> Result<Spanned<Kind>> {
+        let mut kind = self.parse_kailua_union_kind_after_kind(begin, kind)?;
+
+        while self.may_expect(Punct::Dash) { // T - U - ...
+            let exbegin = self.pos();
+            let excluded = match self.try_parse_kailua_prefixed_kind_seq()? {
+                Some(AtomicKind::One(excluded)) => {
+                    self.parse_kailua_union_kind_after_kind(exbegin, excluded)?
+                }
+                Some(AtomicKind::Seq(..)) => {
+                    self.error(exbegin..self.last_pos(), m::NoTypeSeqInDifference {})
+                        .done()?;
+                    Recover::recover()
+                }
+                None => {
+                    error_with!(self, m::NoType);
+                    break;
+                }
+            };
+            kind = Box::new(K::Difference(kind, excluded)).with_loc(begin..self.last_pos());
+        }
+
+        Ok(kind)
+    }
+
+    fn parse_kailua_union_kind_after_kind(&mut self, begin: Pos,
+                                          kind: Spanned<Kind>) -> Result<Spanned<Kind>> {
         if self.lookahead(Punct::Pipe) { // A | B | ...
             // TODO the current parser is massively ambiguous about pipes in atomic types
             let mut kinds = vec![kind];
@@ -2992,6 +3210,7 @@ impl<'a> Parser<'a> {
                     }
                 };
                 let sig = if let Some(prefix) = sigprefix {
+                    let type_params = parser.parse_kailua_type_params()?;
                     parser.expect(Punct::LParen)?;
                     let args = parser.parse_kailua_namekindlist()?;
                     parser.expect(Punct::RParen)?;
@@ -3001,7 +3220,12 @@ impl<'a> Parser<'a> {
                         Returns::Seq(Seq::empty())
                     };
                     let end = parser.last_pos();
-                    let presig = Presig { prefix: prefix, args: args, returns: Some(returns) };
+                    let presig = Presig {
+                        prefix: prefix,
+                        type_params: type_params,
+                        args: args,
+                        returns: Some(returns),
+                    };
                     Some(presig.with_loc(begin..end))
                 } else {
                     None
@@ -3016,14 +3240,15 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn resolve_kailua_assume_rename(&mut self, global: bool, scopespan: Span,
+    // scoped global assumptions are allowed in local scopes, as they are undone at the block end
+    fn resolve_kailua_assume_rename(&mut self, scoped: bool, global: bool, scopespan: Span,
                                     name: Spanned<IndexedName>, allow_local_shadowing: bool)
         -> Result<(Spanned<RenameRef>, Option<Scope>)>
     {
         if global {
             let local_shadowing =
                 self.resolve_local_name_without_idx(&name.name).is_some();
-            if self.block_depth > 0 {
+            if self.block_depth > 0 && !scoped {
                 self.error(scopespan, m::AssumeGlobalInLocalScope {}).done()?;
             }
             if local_shadowing {
@@ -3031,7 +3256,7 @@ impl<'a> Parser<'a> {
             }
 
             self.set_token_aux(name.base.idx, TokenAux::GlobalVarName);
-            if self.block_depth == 0 && !local_shadowing {
+            if self.block_depth == 0 && !local_shadowing && !scoped {
                 // only register a new global variable when it didn't error
                 self.global_scope.entry(name.base.name.clone())
                                  .or_insert(name.span);
@@ -3073,10 +3298,10 @@ impl<'a> Parser<'a> {
         }
     }
 
-    // assume [global] NAME ":" MODF KIND
-    // assume [static] NAME {"." NAME} ":" MODF KIND
-    // assume NAME {"." NAME} ":" MODF "method" ...
-    // assume [global] class ["(" NAME ")"] NAME [":" NAME] ["=" MODF]
+    // assume ["scoped"] [global] NAME ":" MODF KIND
+    // assume ["scoped"] [static] NAME {"." NAME} ":" MODF KIND
+    // assume ["scoped"] NAME {"." NAME} ":" MODF "method" ...
+    // assume [global] class ["(" NAME ")"] NAME ["<" NAME {"," NAME} ">"] [":" NAME] ["=" MODF]
     //
     // returns a sibling scope if created.
     fn try_parse_kailua_assume(&mut self) -> Result<(Stmt, Option<Scope>)> {
@@ -3089,6 +3314,19 @@ impl<'a> Parser<'a> {
             Method(Span, Option<Spanned<FuncKind>>),
         }
 
+        // `scoped` is not a keyword, so `--# assume scoped: ...` assumes a variable `scoped`
+        let scoped = if self.lookahead(FixedName("scoped")) {
+            let tok = self.read();
+            let is_name = self.lookahead(Punct::Colon) || self.lookahead(Punct::Dot);
+            if is_name {
+                self.unread(tok);
+            }
+            !is_name
+        } else {
+            false
+        };
+        let scopedspan = if scoped { self.last_span } else { Span::dummy() };
+
         let scopebegin = self.pos();
         let scope = if self.may_expect(Keyword::Global) {
             Scope::Global
@@ -3108,6 +3346,7 @@ impl<'a> Parser<'a> {
                 None
             };
             let classname = self.parse_name()?;
+            let type_params = self.parse_kailua_type_params()?;
             let parenttype = if self.may_expect(Punct::Colon) {
                 self.recover_upto_with(|parser| {
                     parser.parse_name().map(|n| Some(n.map(|n| n.name)))
@@ -3116,15 +3355,19 @@ impl<'a> Parser<'a> {
                 None
             };
 
-            // ignore `static`
+            // ignore `static` and `scoped`
             if scope.base == Scope::Static {
                 self.error(&scope, m::AssumeClassStatic {}).done()?;
             }
+            if scoped {
+                self.error(scopedspan, m::AssumeClassScoped {}).done()?;
+            }
 
             let (renameref, sibling_scope) =
-                self.resolve_kailua_assume_rename(scope.base == Scope::Global,
+                self.resolve_kailua_assume_rename(false, scope.base == Scope::Global,
                                                   scope.span, classname, false)?;
-            Ok((Box::new(St::KailuaAssumeClass(system, renameref, parenttype, sibling_scope)),
+            Ok((Box::new(St::KailuaAssumeClass(system, renameref, type_params,
+                                               parenttype, sibling_scope)),
                 sibling_scope))
         } else {
             let namesbegin = self.pos();
@@ -3170,9 +3413,9 @@ impl<'a> Parser<'a> {
                     self.error(&scope, m::AssumeNameStatic {}).done()?;
                 }
                 let (renameref, sibling_scope) =
-                    self.resolve_kailua_assume_rename(scope.base == Scope::Global,
+                    self.resolve_kailua_assume_rename(scoped, scope.base == Scope::Global,
                                                       scope.span, rootname, true)?;
-                Ok((Box::new(St::KailuaAssume(renameref, modf, kind, sibling_scope)),
+                Ok((Box::new(St::KailuaAssume(scoped, renameref, modf, kind, sibling_scope)),
                     sibling_scope))
             } else {
                 if scope.base == Scope::Global {
@@ -3183,7 +3426,7 @@ impl<'a> Parser<'a> {
                 let rootname0 = rootname.clone();
                 let rootname = rootname.map(|name| self.resolve_name(name));
                 if let NameRef::Global(_) = rootname.base {
-                    if self.block_depth > 0 {
+                    if self.block_depth > 0 && !scoped {
                         self.error(&rootname0,
                                    m::AssumeFieldGlobalInLocalScope { name: &rootname0.name })
                             .done()?;
@@ -3193,15 +3436,17 @@ impl<'a> Parser<'a> {
                 let is_static = scope.base == Scope::Static;
                 let names = (rootname, names).with_loc(namesbegin..namesend);
                 let st = match kind {
-                    Kindlike::Kind(kind) => St::KailuaAssumeField(is_static, names, modf, kind),
+                    Kindlike::Kind(kind) => {
+                        St::KailuaAssumeField(scoped, is_static, names, modf, kind)
+                    }
                     Kindlike::Method(kindspan, funckind) =>{
                         if scope.base != Scope::Implied {
                             self.error(kindspan, m::AssumeMethodToNonInstanceField {}).done()?;
                         }
                         if let Some(funckind) = funckind {
-                            St::KailuaAssumeMethod(names, modf, funckind)
+                            St::KailuaAssumeMethod(scoped, names, modf, funckind)
                         } else {
-                            St::KailuaAssumeField(is_static, names, modf,
+                            St::KailuaAssumeField(scoped, is_static, names, modf,
                                                   Kind::recover().without_loc())
                         }
                     },
@@ -3211,6 +3456,80 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn try_parse_kailua_interface_or_assert(&mut self) -> Result<Option<Box<St>>> {
+        if self.may_expect(FixedName("interface")) {
+            // interface NAME = "{" NAME ":" (MODF KIND | "method" FUNCKIND) {"," ...} "}"
+            let name = self.parse_name()?;
+            self.expect(Punct::Eq)?;
+            self.expect(Punct::LBrace)?;
+
+            let mut seen = HashMap::new(); // value denotes the first span
+            let (_, fields) = self.scan_tabular_body(false, |parser| {
+                let fieldname = parser.parse_name()?;
+                match seen.entry(fieldname.base.name.clone()) {
+                    hash_map::Entry::Occupied(e) => {
+                        parser.error(fieldname.span,
+                                     m::DuplicateFieldNameInRec { name: &fieldname.base })
+                              .note(*e.get(), m::FirstFieldNameInRec {})
+                              .done()?;
+                    }
+                    hash_map::Entry::Vacant(e) => {
+                        e.insert(fieldname.span);
+                    }
+                }
+                parser.expect(Punct::Colon)?;
+                let field = if parser.may_expect(Keyword::Method) {
+                    InterfaceField::Method(parser.parse_kailua_funckind()?)
+                } else {
+                    InterfaceField::Field(parser.parse_kailua_slotkind()?)
+                };
+                Ok((fieldname.map(|n| n.name), field))
+            })?;
+
+            Ok(Some(Box::new(St::KailuaInterface(name.map(|n| n.name), fields))))
+        } else if self.may_expect(FixedName("assert")) {
+            // assert NAME implements NAME
+            let name = self.parse_name()?;
+            let name = name.map(|name| self.resolve_name(name));
+            self.expect(FixedName("implements"))?;
+            let iface = self.parse_name()?;
+            Ok(Some(Box::new(St::KailuaAssertImplements(name, iface.map(|n| n.name)))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn try_parse_kailua_ignore(&mut self) -> Result<Option<Box<St>>> {
+        if self.may_expect(FixedName("ignore")) {
+            // ignore ["function"] [":" CODE {"," CODE}]
+            let func = self.may_expect(Keyword::Function);
+            let mut codes = Vec::new();
+            if self.may_expect(Punct::Colon) {
+                codes.push(self.parse_kailua_lint_code()?);
+                while self.may_expect(Punct::Comma) {
+                    codes.push(self.parse_kailua_lint_code()?);
+                }
+            }
+            Ok(Some(Box::new(St::KailuaIgnore(func, codes))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // NAME {"-" NAME}, where each NAME can be a keyword (e.g. `float-for-step`)
+    fn parse_kailua_lint_code(&mut self) -> Result<Spanned<Name>> {
+        let first = self.try_name_or_keyword()?;
+        let mut span = first.span;
+        let mut code = first.base.into_bytes().into_vec();
+        while self.may_expect(Punct::Dash) {
+            let next = self.try_name_or_keyword()?;
+            span |= next.span;
+            code.push(b'-');
+            code.extend_from_slice(&next.base);
+        }
+        Ok(Name::from(code).with_loc(span))
+    }
+
     fn try_parse_kailua_spec(&mut self) -> Result<Option<Option<Spanned<Stmt>>>> {
         trace!("parsing kailua spec");
         let begin = self.pos();
@@ -3278,6 +3597,12 @@ impl<'a> Parser<'a> {
                         Some(Box::new(St::KailuaType(typescope, name.map(|n| n.name), kind)))
                     };
 
+                    // ignore ..., interface NAME = ... or assert NAME implements NAME
+                    'unread: Tok::Name(_) => match parser.try_parse_kailua_ignore()? {
+                        Some(stmt) => Some(stmt),
+                        None => parser.try_parse_kailua_interface_or_assert()?,
+                    };
+
                     'unread: _ => None; // empty `--#` is valid
                 };
 
@@ -3328,5 +3653,23 @@ impl<'a> Parser<'a> {
             Err(report::Stop)
         }
     }
+
+    /// Parses a single type and returns it (while generating reports).
+    ///
+    /// The tokens should be generated from `Lexer::new_in_meta`, i.e. as if they follow `--:`.
+    /// Any token after the type is an error. Errors in the type itself can be recovered,
+    /// so the caller should also determine if it can continue in spite of reported errors.
+    pub fn into_kind(mut self) -> report::Result<Spanned<Kind>> {
+        let kind = self.parse_kailua_kind().map_err(|_| report::Stop)?;
+
+        // the lexer closes the meta block with a newline, which should be the end of input
+        self.may_expect(Punct::Newline);
+        let next = self.read();
+        if next.1.base != Tok::EOF {
+            let _ = self.error(next.1.span, m::NoEndOfType { read: &next.1.base }).done();
+            return Err(report::Stop);
+        }
+        Ok(kind)
+    }
 }
 
@@ -797,6 +797,33 @@ local c = a
 --!      Local([`b`$1], [`a`_])$1]), \
 --!  Local([`c`$2], [`a`_])$2]
 
+--8<-- assume-scoped
+do
+    --# assume scoped global a: string
+    --# assume scoped b.c: WHATEVER
+    --# assume scoped b.d: method()
+    local e = a
+end
+--! [Do([KailuaAssume(scoped, `a`_ => `a`_, _, String), \
+--!      KailuaAssumeField(scoped, false, (`b`_.`c`), _, Dynamic), \
+--!      KailuaAssumeMethod(scoped, (`b`_.`d`), _, () --> ()), \
+--!      Local([`e`$1], [`a`_])$1])]
+
+--8<-- assume-scoped-local
+local a
+--# assume scoped a: string
+--! [Local([`a`$1], [])$1, KailuaAssume(scoped, `a`$1 => `a`$2, _, String)$2]
+
+--8<-- assume-scoped-name
+--# assume scoped: string
+--# assume scoped.x: string
+--! [KailuaAssume(`scoped`_ => `scoped`$1, _, String)$1, \
+--!  KailuaAssumeField(false, (`scoped`$1.`x`), _, String)]
+
+--8<-- assume-scoped-class
+--# assume scoped global class A --@< Error: `--# assume scoped class` is not allowed because classes cannot be undone
+--! [KailuaAssumeClass(None, `A`_ => `A`_, None)]
+
 --8<-- assume-field-global
 --# assume global a.b: WHATEVER --@< Error: `global` is redundant here because a field gets `--# assume`d in place
 --# assume global a.b.c: WHATEVER --@< Error: `global` is redundant here because a field gets `--# assume`d in place
@@ -895,6 +922,15 @@ x = 42
 --# assume global class A: B
 --! [KailuaAssumeClass(None, `A`_ => `A`_, Some(`B`))]
 
+--8<-- assume-class-generic
+--# assume global class(gideros) Queue<T, U>: B
+--! [KailuaAssumeClass(Some(`gideros`), `Queue`_ => `Queue`_<`T`, `U`>, Some(`B`))]
+
+--8<-- assume-class-generic-duplicate
+--# assume global class Queue<T, T> --@< Error: Duplicate type parameter `T`
+                                    --@^ Note: The first duplicate appeared here
+--! [KailuaAssumeClass(None, `Queue`_ => `Queue`_<`T`>, None)]
+
 --8<-- assume-class-inherit-recover
 --# assume global class A: --@<-v Error: Expected a name, got a newline
 x = 42
@@ -1023,6 +1059,18 @@ local y --: function(a: any...) --@< Error: Variadic arguments cannot have a nam
 --! [Local([`x`$1: _ Func((`a`: Any, Any...) --> ())], [])$1, \
 --!  Local([`y`$2: _ Func((Any...) --> ())], [])$2]
 
+--8<-- kind-func-generic
+local x --: function<T>(T) --> T
+local y --: function<K, V>(m: map<K, V>) --> vector<K>
+--! [Local([`x`$1: _ Func(<`T`>(`T`) --> `T`)], [])$1, \
+--!  Local([`y`$2: _ Func(<`K`, `V`>(`m`: Map(`K`, _ `V`)) --> Array(_ `K`))], [])$2]
+
+--8<-- kind-func-generic-dup
+local x --: function<T, U, T>(T, U) --> T
+--@^ Error: Duplicate type parameter `T`
+--@^^ Note: The first duplicate appeared here
+--! [Local([`x`$1: _ Func(<`T`, `U`>(`T`, `U`) --> `T`)], [])$1]
+
 --8<-- kind-func-named-args-recover-1
 local a --: function(a             --@<-v Error: Expected `)`, got a newline
 local b --: function(a:            --@<-v Error: Expected a single type, got a newline
@@ -1055,6 +1103,16 @@ local y --: function(string, integer) --> !
 local x --: thread
 --! [Local([`x`$1: _ Thread], [])$1]
 
+--8<-- kind-thread-params
+local x --: thread<integer, string?>
+--! [Local([`x`$1: _ Thread(Integer, String?)], [])$1]
+
+--8<-- kind-thread-params-recover
+local x --: thread<integer> --@< Error: `thread` type needs either no or two type parameters
+local y --: thread<const integer, string> --@< Error: Type arguments cannot have modifiers
+--! [Local([`x`$1: _ Oops], [])$1, \
+--!  Local([`y`$2: _ Thread(Integer, String)], [])$2]
+
 --8<-- kind-userdata
 local x --: userdata
 --! [Local([`x`$1: _ UserData], [])$1]
@@ -1308,6 +1366,12 @@ local w --: [builtin(lua51, `damn it`)] string
 --!  Local([`z`$3: _ [`builtin`(`lua51`)] String], [])$3, \
 --!  Local([`w`$4: _ [`builtin`(`lua51`, `damn it`)] String], [])$4]
 
+--8<-- kind-attr-str-value
+local x --: [builtin("use y instead")] string
+local y --: [builtin(lua51, 'damn it')] string
+--! [Local([`x`$1: _ [`builtin`("use y instead")] String], [])$1, \
+--!  Local([`y`$2: _ [`builtin`(`lua51`, "damn it")] String], [])$2]
+
 --8<-- kind-attr-empty
 local x --: [] string --@< Error: Expected a name, got `]`
 --! [Local([`x`$1: _ String], [])$1]
@@ -1362,6 +1426,75 @@ local x --: const | integer --@< Error: Expected a newline, got `|`
 local x --: 123456789012345 --@< Error: Cannot use a non-integer, or too small or too large integer as a type
 --! [Local([`x`$1: _ Oops], [])$1]
 
+--8<-- kind-generic
+local x --: Queue<integer, vector<Queue<T>>>?
+--! [Local([`x`$1: _ `Queue`<Integer, Array(_ `Queue`<`T`>)>?], [])$1]
+
+--8<-- kind-generic-modf
+local x --: Queue<const integer> --@< Error: Type arguments cannot have modifiers
+--! [Local([`x`$1: _ `Queue`<Integer>], [])$1]
+
+--8<-- kind-negative-int
+local x --: -42 | - 3 | -2147483648
+--! [Local([`x`$1: _ Union([Integer(-42), Integer(-3), Integer(-2147483648)])], [])$1]
+
+--8<-- kind-negative-non-int
+local x --: -3.14 --@< Error: Cannot use a non-integer, or too small or too large integer as a type
+--! [Local([`x`$1: _ Oops], [])$1]
+
+--8<-- kind-negative-recover
+local x --: -integer --@< Error: Expected an integer type after `-`, got a name
+                     --@^ Error: Expected a newline, got a name
+--! [Local([`x`$1: _ Oops], [])$1]
+
+--8<-- kind-int-range
+local x --: integer(1..10)
+--! [Local([`x`$1: _ Integer(1..10)], [])$1]
+
+--8<-- kind-int-range-negative
+local x --: int(-5..-1)?
+--! [Local([`x`$1: _ Integer(-5..-1)?], [])$1]
+
+--8<-- kind-int-range-spaces
+local x --: integer ( 0 .. 255 ) | string
+--! [Local([`x`$1: _ Union([Integer(0..255), String])], [])$1]
+
+--8<-- kind-int-range-empty
+local x --: integer(10..1) --@< Error: The lower bound 10 of the integer range type is greater than the upper bound 1
+--! [Local([`x`$1: _ Oops], [])$1]
+
+--8<-- kind-int-range-non-int
+local x --: integer(1..2.5) --@< Error: Cannot use a non-integer, or too small or too large integer as a type
+--! [Local([`x`$1: _ Oops], [])$1]
+
+--8<-- kind-int-range-recover
+local x --: integer(1..) --@< Error: Expected an integer in the integer range type, got `)`
+--! [Local([`x`$1: _ Oops], [])$1]
+
+--8<-- kind-difference
+local x --: any - nil
+--! [Local([`x`$1: _ Difference(Any, Nil)], [])$1]
+
+--8<-- kind-difference-union
+local x --: string | number | table - number | table
+--! [Local([`x`$1: _ Difference(Union([String, Union([Number, Table])]), Union([Number, Table]))], [])$1]
+
+--8<-- kind-difference-chain
+local x --: any - nil - table?
+--! [Local([`x`$1: _ Difference(Difference(Any, Nil), Table?)], [])$1]
+
+--8<-- kind-difference-negative-int
+local x --: integer - -1
+--! [Local([`x`$1: _ Difference(Integer, Integer(-1))], [])$1]
+
+--8<-- kind-difference-seq
+local x --: any - (integer, string) --@< Error: A sequence of types cannot be excluded from another type
+--! [Local([`x`$1: _ Difference(Any, Oops)], [])$1]
+
+--8<-- kind-difference-recover
+local x --: any - --@<-v Error: Expected a type, got a newline
+--! [Local([`x`$1: _ Any], [])$1]
+
 --8<-- kind-non-int
 local x --: 3.14 --@< Error: Cannot use a non-integer, or too small or too large integer as a type
 --! [Local([`x`$1: _ Oops], [])$1]
@@ -1434,6 +1567,11 @@ local function foo(a) end
 local function foo(a) end
 --! [FuncDecl(`foo`$2, [`a`$1: _ Integer] --> [String, Dynamic...], $1[])$2]
 
+--8<-- funcspec-generic
+--v function<T>(a: T) --> T
+local function foo(a) end
+--! [FuncDecl(`foo`$2, <`T`>[`a`$1: _ `T`] --> `T`, $1[])$2]
+
 --8<-- funcspec-inline
 (--v function(a: const integer,
  --v          ...)
@@ -2238,6 +2376,49 @@ do
 end
 --! [Do([KailuaType(Local, `Int`, Integer)])]
 
+--8<-- interface
+--# interface Shape = {
+--#     name: const string,
+--#     area: method() --> number,
+--#     scale: method(factor: number),
+--# }
+--! [KailuaInterface(`Shape`, [`name`: Const String, \
+--!                           `area`: Method(() --> Number), \
+--!                           `scale`: Method((`factor`: Number) --> ())])]
+
+--8<-- interface-duplicate-field
+--# interface Shape = { area: number, area: method() }
+--@^ Error: Duplicate record field `area` in the type specification
+--@^^ Note: The first duplicate appeared here
+--! [KailuaInterface(`Shape`, [`area`: _ Number, `area`: Method(() --> ())])]
+
+--8<-- assert-implements
+local Square = {}
+--# assert Square implements Shape
+--# assert Circle implements Shape
+--! [Local([`Square`$1], [{}])$1, \
+--!  KailuaAssertImplements(`Square`$1, `Shape`), \
+--!  KailuaAssertImplements(`Circle`_, `Shape`)]
+
+--8<-- assert-implements-missing-interface
+--# assert Square implements --@<-v Error: Expected a name, got a newline
+--! [Oops]
+
+--8<-- ignore
+--# ignore
+f()
+--! [KailuaIgnore(false, []), Void(`f`_())]
+
+--8<-- ignore-codes
+--# ignore: shadowing, float-for-step
+--# ignore function: unused-variable
+--! [KailuaIgnore(false, [`shadowing`, `float-for-step`]), \
+--!  KailuaIgnore(true, [`unused-variable`])]
+
+--8<-- ignore-missing-code
+--# ignore: --@<-v Error: Expected a name, got a newline
+--! [Oops]
+
 --8<-- kind-error
 --# type x = error
 --! [KailuaType(Exported, `x`, Error)]
@@ -2266,6 +2447,61 @@ f()
 --# type `goto` = integer
 --! [KailuaOpen(`lua51`), KailuaType(Exported, `goto`, Integer)]
 
+--8<-- lua53-integer-lit -- flag:lua53
+a = 42
+b = 0xff
+c = 9223372036854775807
+d = 0xffffffffffffffff
+e = 0x10000000000000001
+--! [Assign([`a`_], [42]), Assign([`b`_], [255]), Assign([`c`_], [9223372036854775807]), \
+--!  Assign([`d`_], [-1]), Assign([`e`_], [1])]
+
+--8<-- lua53-float-lit -- flag:lua53
+a = 1.5
+--! [Assign([`a`_], [1.5])]
+
+--8<-- lua53-operators -- flag:lua53
+a = 1 // 2 & 3 | 4 ~ 5 << 6 >> 7
+--! [Assign([`a`_], [(((1 // 2) & 3) | (4 ~ ((5 << 6) >> 7)))])]
+
+--8<-- lua53-operators-prec -- flag:lua53
+a = ~1 ~ 2
+b = 1 .. 2 << 3
+c = 1 | 2 == 3
+d = -2 ^ 2 // 3
+--! [Assign([`a`_], [((~ 1) ~ 2)]), Assign([`b`_], [((1 .. 2) << 3)]), \
+--!  Assign([`c`_], [((1 | 2) == 3)]), Assign([`d`_], [((- (2 ^ 2)) // 3)])]
+
+--8<-- lua53-nested-kind-params -- flag:lua53
+local x --: vector<vector<integer>>
+--! [Local([`x`$1: _ Array(_ Array(_ Integer))], [])$1]
+
+--8<-- lua54-operators -- flag:lua54
+a = 1 // 2
+--! [Assign([`a`_], [(1 // 2)])]
+
+--8<-- lua54-local-attribs -- flag:lua54
+local a <const>, b <close> = 1, f()
+local c <const> --: integer
+      = 2
+--! [Local([`a`$1: Const, `b`$1: Const], [1, `f`_()])$1, Local([`c`$2: Const Integer], [2])$2]
+
+--8<-- lua54-local-attribs-unknown -- flag:lua54
+local a <mutable> = 1 --@< Error: Unknown local variable attribute `mutable`, should be either `const` or `close`
+--! [Local([`a`$1], [1])$1]
+
+--8<-- lua54-local-attribs-module -- flag:lua54
+local a <const> --: module
+--@^ Error: Local variable attributes cannot be used with the `module` modifier
+      = {}
+--! [Local([`a`$1: Module], [{}])$1]
+
+--8<-- lua51-no-bitwise-operators
+a = 1 & 2
+--@^ Error: Unexpected character
+--@^^ Error: Only function calls are allowed as statement-level expressions
+--! [Assign([`a`_], [1.0]), Void(2.0)]
+
 --8<-- type-spec-recover-negative-span
 local a = {} --: var { var { } } --@< Error: Expected a newline, got a keyword `var`
 local b --: var { var { } }      --@< Error: Expected a newline, got a keyword `var`
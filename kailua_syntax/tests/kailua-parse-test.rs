@@ -8,21 +8,22 @@ extern crate kailua_syntax;
 
 use std::cell::RefCell;
 use std::rc::Rc;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use clap::{App, Arg, ArgMatches};
 use kailua_env::{Source, Span};
 use kailua_diag::{Report, Reporter, TrackMaxKind};
 use kailua_syntax::{Lexer, Nest, NestedToken, Parser, Chunk};
+use kailua_syntax::lang::{Language, Lua, Kailua};
 use kailua_syntax::ast::TokenAux;
 
-fn lex_and_parse_chunk(source: &Source, span: Span,
+fn lex_and_parse_chunk(source: &Source, span: Span, language: Language,
                        report: &Report) -> kailua_diag::Result<(Vec<NestedToken>, Chunk)> {
     if let Some(mut iter) = source.iter_from_span(span) {
-        let mut lexer = Lexer::new(&mut iter, &report);
+        let mut lexer = Lexer::with_language(&mut iter, language, &report);
         let tokens: Vec<_> = Nest::new(&mut lexer).collect();
         let chunk = {
             let mut tokens_iter = tokens.iter().cloned();
-            let parser = Parser::new(&mut tokens_iter, &report);
+            let parser = Parser::with_language(&mut tokens_iter, language, &report);
             parser.into_chunk()?
         };
         Ok((tokens, chunk))
@@ -76,10 +77,29 @@ impl kailua_test::Testing for Testing {
         self.note_token_aux = matches.is_present("note_token_aux");
     }
 
-    fn run(&self, source: Rc<RefCell<Source>>, span: Span, _filespans: &HashMap<String, Span>,
+    fn run(&self, source: Rc<RefCell<Source>>, span: Span, filespans: &HashMap<String, Span>,
            report: Rc<Report>) -> String {
+        self.run_with_flags(source, span, filespans, report, &HashSet::new())
+    }
+
+    fn run_with_flags(&self, source: Rc<RefCell<Source>>, span: Span,
+                      _filespans: &HashMap<String, Span>, report: Rc<Report>,
+                      flags: &HashSet<String>) -> String {
+        // `flag:lua53` and so on change the Lua version
+        let lua = if flags.contains("lua54") {
+            Lua::Lua54
+        } else if flags.contains("lua53") {
+            Lua::Lua53
+        } else if flags.contains("lua52") {
+            Lua::Lua52
+        } else {
+            Lua::Lua51
+        };
+        let language = Language::new(lua, Kailua::Kailua10);
+
         let report = TrackMaxKind::new(&*report);
-        if let Ok((tokens, chunk)) = lex_and_parse_chunk(&source.borrow(), span, &report) {
+        if let Ok((tokens, chunk)) = lex_and_parse_chunk(&source.borrow(), span, language,
+                                                         &report) {
             assert_eq!(tokens.len(), chunk.token_aux.len());
             let s = format!("{:?}", chunk.block);
             if self.note_scopes {
@@ -105,6 +105,9 @@
 //!
 //! * `feature:!FEATURE` enables the test only when a particular feature is disabled.
 //!
+//! * `flag:FLAG` is passed to `Testing::run_with_flags` as is.
+//!   The implementation can use this to alter its configuration per test.
+//!
 //! # Test invocation
 //!
 //! `cargo test` (or if you have multiple test binaries, `cargo test --test NAME`)
@@ -180,6 +183,14 @@ pub trait Testing {
     fn run(&self, source: Rc<RefCell<Source>>, span: Span, filespans: &HashMap<String, Span>,
            report: Rc<Report>) -> String;
 
+    /// Same to `run`, but also receives a set of `flag:FLAG` options for the test.
+    /// Ignores flags and calls `run` by default.
+    fn run_with_flags(&self, source: Rc<RefCell<Source>>, span: Span,
+                      filespans: &HashMap<String, Span>, report: Rc<Report>,
+                      _flags: &HashSet<String>) -> String {
+        self.run(source, span, filespans, report)
+    }
+
     /// Checks if the actual output and expected output matches.
     /// By default it is a simple string equivalence.
     fn check_output(&self, actual: &str, expected: &str) -> bool { actual == expected }
@@ -405,6 +416,7 @@ struct Test {
     ignored: bool,
     exact: bool,
     features: HashSet<String>,
+    flags: HashSet<String>,
 }
 
 fn extract_tests(path: &Path) -> Result<Vec<Test>, TestError> {
@@ -476,6 +488,9 @@ fn extract_tests(path: &Path) -> Result<Vec<Test>, TestError> {
                     features: options.iter().filter_map(|s| {
                         if s.starts_with("feature:") { Some(s[8..].to_owned()) } else { None }
                     }).collect(),
+                    flags: options.iter().filter_map(|s| {
+                        if s.starts_with("flag:") { Some(s[5..].to_owned()) } else { None }
+                    }).collect(),
                 });
                 current_file = None;
                 current_lines = Vec::new();
@@ -752,8 +767,9 @@ impl<T: Testing> Tester<T> {
             if !self.stop_on_panic {
                 panic::set_hook(Box::new(|_| {})); // suppress the default panicking message
             }
+            let flags = &test.flags;
             let output = panic::catch_unwind(move || {
-                testing.run(source.0, inputspan, &filespans, report.0)
+                testing.run_with_flags(source.0, inputspan, &filespans, report.0, flags)
             });
             panic::take_hook();
             output
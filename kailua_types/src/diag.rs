@@ -46,6 +46,8 @@ pub enum Origin {
     Strings = 0x12,
     /// From an operation with `Functions`.
     Functions = 0x13,
+    /// From an operation with `Coroutine`.
+    Coroutine = 0x14,
 
     /// From an operation with `Union`.
     Union = 0x20,
@@ -3,20 +3,31 @@
 use std::mem;
 use std::str;
 use std::fmt;
+use std::ops;
 use std::collections::{HashMap, HashSet};
 use vec_map::{self, VecMap};
 use atomic::Atomic;
 use atomic::Ordering::Relaxed;
 
 use kailua_diag::Locale;
+use kailua_syntax::{Str, Name};
 use diag::{Origin, TypeReport, TypeResult};
-use ty::{Ty, T, Slot, TVar, RVar, Lattice, Key};
-use ty::{TypeContext, ClassId, ClassSystemId, DisplayState};
+use ty::{Ty, T, Slot, TVar, TParam, RVar, Lattice, Key};
+use ty::{TypeContext, ClassId, ClassSystemId, CustomTagId, TagStrId, DisplayState, DisplayHook};
+use ty::{ComplexityLimit, DEFAULT_LITERAL_UNION_LIMIT};
 use ty::flags::*;
 use self::partitions::{Partition, Partitions};
 
 mod partitions;
 
+/// The log target for constraints asserted to type and row variables.
+///
+/// Each constraint is logged at the debug level with its ID, the number of constraints
+/// asserted so far (see `Types::constraint_count`), and a constraint conflicting with
+/// earlier bounds is logged at the info level with the same ID. This allows solver traces
+/// to be enabled separately from other logs, e.g. `RUST_LOG=kailua_types::constraints=debug`.
+pub const CONSTRAINT_LOG_TARGET: &'static str = "kailua_types::constraints";
+
 #[derive(Debug)]
 struct Bound {
     parent: Atomic<u32>,
@@ -160,6 +171,18 @@ pub trait ClassProvider: Send + Sync {
     /// Should return true if the nominal identifier `lhs` is
     /// a subtype of another nominal identifier `rhs`.
     fn is_subclass_of(&self, lhs: ClassId, rhs: ClassId) -> bool;
+
+    /// Should return the generic nominal identifier and type arguments for given identifier,
+    /// or `None` if it is not generic.
+    ///
+    /// For the generic identifier itself type arguments are its own type parameters.
+    fn class_type_args(&self, cid: ClassId) -> Option<(ClassId, Vec<Ty>)>;
+
+    /// Should return a nominal identifier for given generic identifier and type arguments,
+    /// or `None` if it is not generic or the number of type arguments is incorrect.
+    ///
+    /// The same identifier should be returned for the same type arguments.
+    fn specialize_class(&self, cid: ClassId, args: Vec<Ty>) -> Option<ClassId>;
 }
 
 impl<'a, T: ClassProvider + ?Sized> ClassProvider for &'a T {
@@ -174,6 +197,12 @@ impl<'a, T: ClassProvider + ?Sized> ClassProvider for &'a T {
     fn is_subclass_of(&self, lhs: ClassId, rhs: ClassId) -> bool {
         (**self).is_subclass_of(lhs, rhs)
     }
+    fn class_type_args(&self, cid: ClassId) -> Option<(ClassId, Vec<Ty>)> {
+        (**self).class_type_args(cid)
+    }
+    fn specialize_class(&self, cid: ClassId, args: Vec<Ty>) -> Option<ClassId> {
+        (**self).specialize_class(cid, args)
+    }
 }
 
 impl<T: ClassProvider + ?Sized> ClassProvider for Box<T> {
@@ -188,6 +217,12 @@ impl<T: ClassProvider + ?Sized> ClassProvider for Box<T> {
     fn is_subclass_of(&self, lhs: ClassId, rhs: ClassId) -> bool {
         (**self).is_subclass_of(lhs, rhs)
     }
+    fn class_type_args(&self, cid: ClassId) -> Option<(ClassId, Vec<Ty>)> {
+        (**self).class_type_args(cid)
+    }
+    fn specialize_class(&self, cid: ClassId, args: Vec<Ty>) -> Option<ClassId> {
+        (**self).specialize_class(cid, args)
+    }
 }
 
 /// A dummy `ClassProvider` which allows no nominal types.
@@ -206,6 +241,60 @@ impl ClassProvider for DummyClassProvider {
     fn is_subclass_of(&self, _lhs: ClassId, _rhs: ClassId) -> bool {
         false
     }
+    fn class_type_args(&self, _cid: ClassId) -> Option<(ClassId, Vec<Ty>)> {
+        None
+    }
+    fn specialize_class(&self, _cid: ClassId, _args: Vec<Ty>) -> Option<ClassId> {
+        None
+    }
+}
+
+/// Counters of type inference operations, used to find out what dominates the checking cost.
+///
+/// The counters only grow; the cost of a portion of checking is a difference of two counters.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TypeStats {
+    /// The number of type variables generated, including copies.
+    pub tvars: u64,
+
+    /// The number of implicit unions resulting in a type different from both operands,
+    /// e.g. `1 | 2` widened to `integer`.
+    pub widenings: u64,
+
+    /// The number of failed subtyping or equality assertions,
+    /// including those retried or recovered by the checker.
+    pub failures: u64,
+}
+
+impl TypeStats {
+    /// Returns a single number representing the overall cost.
+    pub fn total(&self) -> u64 {
+        self.tvars + self.widenings + self.failures
+    }
+}
+
+impl ops::Add for TypeStats {
+    type Output = TypeStats;
+
+    fn add(self, rhs: TypeStats) -> TypeStats {
+        TypeStats {
+            tvars: self.tvars + rhs.tvars,
+            widenings: self.widenings + rhs.widenings,
+            failures: self.failures + rhs.failures,
+        }
+    }
+}
+
+impl ops::Sub for TypeStats {
+    type Output = TypeStats;
+
+    fn sub(self, rhs: TypeStats) -> TypeStats {
+        TypeStats {
+            tvars: self.tvars - rhs.tvars,
+            widenings: self.widenings - rhs.widenings,
+            failures: self.failures - rhs.failures,
+        }
+    }
 }
 
 /// The type environment.
@@ -218,12 +307,44 @@ pub struct Types {
     tvar_sup: Constraints, // lower bound
     tvar_eq: Constraints, // tight bound
 
+    // type parameter information (they do not have any constraint)
+    next_tparam: u32,
+
     // row variable information
     next_rvar: RVar,
     row_infos: VecMap<Box<RowInfo>>,
 
+    // metatables set to records (`[setmetatable]`), retained across copies of row variables
+    metatables: HashMap<RVar, Slot>,
+
+    // records sealed by their table constructors, retained across copies of row variables
+    sealed_rvars: HashSet<RVar>,
+
     // classes and class systems are handled in a separate subsystem, encapsulated as ClassProvider
     classes: Box<ClassProvider>,
+
+    // user-defined display hooks, consulted in the order of registration
+    display_hooks: Vec<Box<DisplayHook>>,
+
+    // attribute names of custom tags, indexed by `CustomTagId`
+    custom_tags: Vec<Name>,
+
+    // strings given to tags, indexed by `TagStrId`
+    tag_strs: Vec<Str>,
+
+    // the maximum number of literals in a union, see `TypeContext::literal_union_limit`
+    literal_union_limit: usize,
+
+    // the number of type constraints asserted so far, see `Types::constraint_count`
+    constraints: usize,
+
+    // complexity limits exceeded so far, in the order of the first occurrence
+    exceeded_limits: Vec<ComplexityLimit>,
+
+    // inference statistics; failures are counted when reports are generated,
+    // which only requires a shared reference
+    stats: TypeStats,
+    failures: Atomic<u64>,
 }
 
 impl Types {
@@ -235,9 +356,20 @@ impl Types {
             tvar_sub: Constraints::new("<:"),
             tvar_sup: Constraints::new(":>"),
             tvar_eq: Constraints::new("="),
+            next_tparam: 0,
             next_rvar: RVar::new(1), // RVar::new(0) == RVar::empty()
             row_infos: VecMap::new(),
+            metatables: HashMap::new(),
+            sealed_rvars: HashSet::new(),
             classes: classes,
+            display_hooks: Vec::new(),
+            custom_tags: Vec::new(),
+            tag_strs: Vec::new(),
+            literal_union_limit: DEFAULT_LITERAL_UNION_LIMIT,
+            constraints: 0,
+            exceeded_limits: Vec::new(),
+            stats: TypeStats::default(),
+            failures: Atomic::new(0),
         }
     }
 
@@ -251,6 +383,86 @@ impl Types {
         self.message_locale = locale;
     }
 
+    /// Registers a hook to override how certain types are displayed.
+    ///
+    /// Hooks registered earlier take precedence.
+    pub fn add_display_hook(&mut self, hook: Box<DisplayHook>) {
+        self.display_hooks.push(hook);
+    }
+
+    /// Registers an attribute name for a new custom tag and returns its identifier.
+    ///
+    /// Returns `None` if the name has been already registered.
+    /// Callbacks for the tag are maintained separately by the checker.
+    pub fn add_custom_tag(&mut self, name: Name) -> Option<CustomTagId> {
+        if self.custom_tags.contains(&name) {
+            return None;
+        }
+        let id = CustomTagId(self.custom_tags.len() as u32);
+        self.custom_tags.push(name);
+        Some(id)
+    }
+
+    /// Sets the maximum number of literals in a union of numbers or strings.
+    ///
+    /// See `TypeContext::literal_union_limit` for details.
+    pub fn set_literal_union_limit(&mut self, limit: usize) {
+        self.literal_union_limit = limit;
+    }
+
+    /// Returns the number of type constraints asserted so far.
+    ///
+    /// Every assertion on type variables counts as one, and so does every field
+    /// asserted to row variables. The checker can use this to bound the cost of inference.
+    pub fn constraint_count(&self) -> usize {
+        self.constraints
+    }
+
+    /// Returns complexity limits exceeded so far, in the order of the first occurrence.
+    pub fn exceeded_limits(&self) -> &[ComplexityLimit] {
+        &self.exceeded_limits
+    }
+
+    /// Returns the inference statistics accumulated so far.
+    pub fn stats(&self) -> TypeStats {
+        TypeStats { failures: self.failures.load(Relaxed), ..self.stats }
+    }
+
+    /// Returns the metatable set to a record with given row variable, if any.
+    pub fn get_metatable(&self, rvar: &RVar) -> Option<Slot> {
+        self.metatables.get(rvar).cloned()
+    }
+
+    /// Sets or (when `meta` is `None`) clears the metatable of a record.
+    ///
+    /// Copies of the row variable made afterwards (e.g. when returned from functions)
+    /// share the same metatable.
+    pub fn set_metatable(&mut self, rvar: RVar, meta: Option<Slot>) {
+        if let Some(meta) = meta {
+            self.metatables.insert(rvar, meta);
+        } else {
+            self.metatables.remove(&rvar);
+        }
+    }
+
+    /// Returns true if a record with given row variable is sealed.
+    ///
+    /// New fields cannot be added to sealed records via assignments.
+    pub fn is_sealed(&self, rvar: &RVar) -> bool {
+        self.sealed_rvars.contains(rvar)
+    }
+
+    /// Marks a record as sealed. Copies of the row variable made afterwards are also sealed.
+    pub fn seal(&mut self, rvar: RVar) {
+        self.sealed_rvars.insert(rvar);
+    }
+
+    // counts a new constraint and returns an ID for it
+    fn next_constraint_id(&mut self) -> usize {
+        self.constraints += 1;
+        self.constraints
+    }
+
     fn assert_rvar_rel(&mut self, lhs: RVar, rhs: RVar, is_sub: bool) -> TypeResult<()> {
         trace!("{:?} should be {} {:?}", lhs, if is_sub { "<:" } else { "=" }, rhs);
 
@@ -472,6 +684,8 @@ impl Types {
 
 impl TypeContext for Types {
     fn gen_report(&self) -> TypeReport {
+        // reports are only generated for failed assertions
+        self.failures.fetch_add(1, Relaxed);
         TypeReport::new(self.message_locale)
     }
 
@@ -481,10 +695,30 @@ impl TypeContext for Types {
     }
 
     fn gen_tvar(&mut self) -> TVar {
+        self.stats.tvars += 1;
         self.next_tvar.0 += 1;
         self.next_tvar
     }
 
+    fn record_widening(&mut self) {
+        self.stats.widenings += 1;
+    }
+
+    fn record_limit_exceeded(&mut self, limit: ComplexityLimit) {
+        if !self.exceeded_limits.contains(&limit) {
+            self.exceeded_limits.push(limit);
+        }
+    }
+
+    fn literal_union_limit(&self) -> usize {
+        self.literal_union_limit
+    }
+
+    fn gen_tparam(&mut self, name: Name) -> TParam {
+        self.next_tparam += 1;
+        TParam { id: self.next_tparam, name: name }
+    }
+
     fn copy_tvar(&mut self, tvar: TVar) -> TVar {
         if self.tvar_eq.get_bound(tvar).map_or(false, |b| b.bound.is_some()) {
             // we have an equal bound, so tvar has no chance to be extended
@@ -507,15 +741,18 @@ impl TypeContext for Types {
 
     fn assert_tvar_sub(&mut self, lhs: TVar, rhs0: &Ty) -> TypeResult<()> {
         let rhs = rhs0.clone().coerce();
-        debug!("adding a constraint {:?} <: {:?} (coerced to {:?})", lhs, rhs0, rhs);
+        let id = self.next_constraint_id();
+        debug!(target: CONSTRAINT_LOG_TARGET,
+               "#{}: adding a constraint {:?} <: {:?} (coerced to {:?})", id, lhs, rhs0, rhs);
         if let Some(eb) = self.tvar_eq.get_bound(lhs).and_then(|b| b.bound.clone()) {
             eb.assert_sub(&rhs, self)?;
         } else {
             if let Some(ub) = self.tvar_sub.add_bound(lhs, &rhs).map(|b| b.clone()) {
                 // the original bound is not consistent, bound <: rhs still has to hold
                 if let Err(e) = ub.assert_sub(&rhs, self) {
-                    info!("variable {:?} cannot have multiple possibly disjoint \
-                           bounds (original <: {:?}, later <: {:?}): {:?}", lhs, ub, rhs, e);
+                    info!(target: CONSTRAINT_LOG_TARGET,
+                          "#{}: variable {:?} cannot have multiple possibly disjoint \
+                           bounds (original <: {:?}, later <: {:?}): {:?}", id, lhs, ub, rhs, e);
                     return Err(e);
                 }
             }
@@ -528,15 +765,18 @@ impl TypeContext for Types {
 
     fn assert_tvar_sup(&mut self, lhs: TVar, rhs: &Ty) -> TypeResult<()> {
         // no coercion here, as type coercion will always expand the type
-        debug!("adding a constraint {:?} :> {:?}", lhs, rhs);
+        let id = self.next_constraint_id();
+        debug!(target: CONSTRAINT_LOG_TARGET,
+               "#{}: adding a constraint {:?} :> {:?}", id, lhs, rhs);
         if let Some(eb) = self.tvar_eq.get_bound(lhs).and_then(|b| b.bound.clone()) {
             rhs.assert_sub(&eb, self)?;
         } else {
             if let Some(lb) = self.tvar_sup.add_bound(lhs, rhs).map(|b| b.clone()) {
                 // the original bound is not consistent, bound :> rhs still has to hold
                 if let Err(e) = rhs.assert_sub(&lb, self) {
-                    info!("variable {:?} cannot have multiple possibly disjoint \
-                           bounds (original :> {:?}, later :> {:?}): {:?}", lhs, lb, rhs, e);
+                    info!(target: CONSTRAINT_LOG_TARGET,
+                          "#{}: variable {:?} cannot have multiple possibly disjoint \
+                           bounds (original :> {:?}, later :> {:?}): {:?}", id, lhs, lb, rhs, e);
                     return Err(e);
                 }
             }
@@ -549,12 +789,15 @@ impl TypeContext for Types {
 
     fn assert_tvar_eq(&mut self, lhs: TVar, rhs0: &Ty) -> TypeResult<()> {
         let rhs = rhs0.clone().coerce();
-        debug!("adding a constraint {:?} = {:?} (coerced to {:?})", lhs, rhs0, rhs);
+        let id = self.next_constraint_id();
+        debug!(target: CONSTRAINT_LOG_TARGET,
+               "#{}: adding a constraint {:?} = {:?} (coerced to {:?})", id, lhs, rhs0, rhs);
         if let Some(eb) = self.tvar_eq.add_bound(lhs, &rhs).map(|b| b.clone()) {
             // the original bound is not consistent, bound = rhs still has to hold
             if let Err(e) = eb.assert_eq(&rhs, self) {
-                info!("variable {:?} cannot have multiple possibly disjoint \
-                       bounds (original = {:?}, later = {:?}): {:?}", lhs, eb, rhs, e);
+                info!(target: CONSTRAINT_LOG_TARGET,
+                      "#{}: variable {:?} cannot have multiple possibly disjoint \
+                       bounds (original = {:?}, later = {:?}): {:?}", id, lhs, eb, rhs, e);
                 return Err(e);
             }
         } else {
@@ -569,7 +812,9 @@ impl TypeContext for Types {
     }
 
     fn assert_tvar_sub_tvar(&mut self, lhs: TVar, rhs: TVar) -> TypeResult<()> {
-        debug!("adding a constraint {:?} <: {:?}", lhs, rhs);
+        let id = self.next_constraint_id();
+        debug!(target: CONSTRAINT_LOG_TARGET,
+               "#{}: adding a constraint {:?} <: {:?}", id, lhs, rhs);
         if !self.tvar_eq.is(lhs, rhs) {
             if !self.tvar_sub.add_relation(lhs, rhs) {
                 // TODO
@@ -584,7 +829,9 @@ impl TypeContext for Types {
     }
 
     fn assert_tvar_eq_tvar(&mut self, lhs: TVar, rhs: TVar) -> TypeResult<()> {
-        debug!("adding a constraint {:?} = {:?}", lhs, rhs);
+        let id = self.next_constraint_id();
+        debug!(target: CONSTRAINT_LOG_TARGET,
+               "#{}: adding a constraint {:?} = {:?}", id, lhs, rhs);
         // do not update tvar_sub & tvar_sup, tvar_eq will be consulted first
         if !self.tvar_eq.add_relation(lhs, rhs) {
             // TODO
@@ -657,10 +904,19 @@ impl TypeContext for Types {
         let rvar = self.gen_rvar();
         self.row_infos.insert(rvar.to_usize(),
                               Box::new(RowInfo { fields: Some(fields), next: None }));
+        if let Some(meta) = self.metatables.get(&rvar0).cloned() {
+            self.metatables.insert(rvar.clone(), meta);
+        }
+        if self.sealed_rvars.contains(&rvar0) {
+            self.sealed_rvars.insert(rvar.clone());
+        }
         rvar
     }
 
     fn assert_rvar_sub(&mut self, lhs: RVar, rhs: RVar) -> TypeResult<()> {
+        let id = self.next_constraint_id();
+        debug!(target: CONSTRAINT_LOG_TARGET,
+               "#{}: adding a constraint {:?} <: {:?}", id, lhs, rhs);
         // TODO appropriate labels just in case
         self.assert_rvar_rel(lhs.clone(), rhs.clone(), true).map_err(|r| {
             r.not_sub(Origin::RVar, "<rvar>", "<rvar>", self)
@@ -668,6 +924,9 @@ impl TypeContext for Types {
     }
 
     fn assert_rvar_eq(&mut self, lhs: RVar, rhs: RVar) -> TypeResult<()> {
+        let id = self.next_constraint_id();
+        debug!(target: CONSTRAINT_LOG_TARGET,
+               "#{}: adding a constraint {:?} = {:?}", id, lhs, rhs);
         // TODO appropriate labels just in case
         self.assert_rvar_rel(lhs.clone(), rhs.clone(), false).map_err(|r| {
             r.not_eq(Origin::RVar, "<rvar>", "<rvar>", self)
@@ -675,6 +934,11 @@ impl TypeContext for Types {
     }
 
     fn assert_rvar_includes(&mut self, lhs: RVar, rhs: &[(Key, Slot)]) -> TypeResult<()> {
+        // every field counts as a constraint, logged together with the ID of the last one
+        self.constraints += rhs.len();
+        let id = self.constraints;
+        debug!(target: CONSTRAINT_LOG_TARGET,
+               "#{}: adding a constraint {:?} :> {:?}", id, lhs, rhs);
         self.assert_rvar_includes_(lhs.clone(), rhs, true).map_err(|r| {
             r.record_should_have_keys(rhs.iter().map(|&(ref k, _)| k))
         })
@@ -744,6 +1008,36 @@ impl TypeContext for Types {
         }
     }
 
+    fn fmt_hooked_type(&self, ty: &Ty, f: &mut fmt::Formatter,
+                       st: &DisplayState) -> Option<fmt::Result> {
+        self.display_hooks.iter().filter_map(|hook| hook.fmt_type(ty, f, st)).next()
+    }
+
+    fn intern_tag_str(&mut self, s: Str) -> TagStrId {
+        if let Some(i) = self.tag_strs.iter().position(|t| *t == s) {
+            return TagStrId(i as u32);
+        }
+        self.tag_strs.push(s);
+        TagStrId(self.tag_strs.len() as u32 - 1)
+    }
+
+    fn get_tag_str(&self, id: TagStrId) -> Option<&Str> {
+        self.tag_strs.get(id.0 as usize)
+    }
+
+    fn custom_tag_from_name(&self, name: &[u8]) -> Option<CustomTagId> {
+        self.custom_tags.iter().position(|n| &n[..] == name).map(|i| CustomTagId(i as u32))
+    }
+
+    fn fmt_custom_tag_name(&self, id: CustomTagId, f: &mut fmt::Formatter,
+                           _st: &DisplayState) -> fmt::Result {
+        if let Some(name) = self.custom_tags.get(id.0 as usize) {
+            write!(f, "{:-}", name)
+        } else {
+            write!(f, "<custom tag {:?}>", id)
+        }
+    }
+
     fn fmt_class_name(&self, cid: ClassId, f: &mut fmt::Formatter,
                       st: &DisplayState) -> fmt::Result {
         self.classes.fmt_class_name(cid, f, st)
@@ -757,6 +1051,14 @@ impl TypeContext for Types {
     fn is_subclass_of(&self, lhs: ClassId, rhs: ClassId) -> bool {
         self.classes.is_subclass_of(lhs, rhs)
     }
+
+    fn class_type_args(&self, cid: ClassId) -> Option<(ClassId, Vec<Ty>)> {
+        self.classes.class_type_args(cid)
+    }
+
+    fn specialize_class(&mut self, cid: ClassId, args: Vec<Ty>) -> Option<ClassId> {
+        self.classes.specialize_class(cid, args)
+    }
 }
 
 #[test]
@@ -768,6 +1070,136 @@ fn test_types_is_send_and_sync() {
     _assert_sync(Types::new(Locale::dummy(), Box::new(DummyClassProvider)));
 }
 
+#[test]
+fn test_types_display_hook() {
+    use std::borrow::Cow;
+    use kailua_diag::Localized;
+    use kailua_syntax::Str;
+    use ty::{Display, Tables, Nil, F};
+
+    fn key(s: &str) -> Key { Key::from(Str::from(s.as_bytes().to_owned())) }
+
+    fn record(types: &mut Types, fields: &[(&str, Ty)]) -> Ty {
+        let rvar = types.gen_rvar();
+        let fields: Vec<_> = fields.iter().map(|&(k, ref v)| {
+            (key(k), Slot::new(F::Var, v.clone()))
+        }).collect();
+        types.assert_rvar_includes(rvar.clone(), &fields).unwrap();
+        types.assert_rvar_closed(rvar.clone()).unwrap();
+        Ty::new(T::Tables(Cow::Owned(Tables::Fields(rvar))))
+    }
+
+    fn display(ty: &Ty, types: &Types) -> String {
+        format!("{}", Localized::new(&ty.display(types as &TypeContext), Locale::dummy()))
+    }
+
+    let mut types = Types::new(Locale::dummy(), Box::new(DummyClassProvider));
+    types.add_display_hook(Box::new(|ty: &Ty, f: &mut fmt::Formatter, st: &DisplayState| {
+        if let T::Tables(ref tab) = **ty {
+            if let Tables::Fields(ref rvar) = **tab {
+                let mut keys: Vec<_> =
+                    st.context.get_rvar_fields(rvar.clone()).into_iter().map(|(k, _)| k).collect();
+                keys.sort();
+                if keys == [key("x"), key("y")] {
+                    return Some(write!(f, "Vec2"));
+                }
+            }
+        }
+        None
+    }));
+
+    let num = Ty::new(T::Number);
+    let vec2 = record(&mut types, &[("x", num.clone()), ("y", num.clone())]).or_nil(Nil::Noisy);
+    let other = record(&mut types, &[("x", num)]);
+    let nested = record(&mut types, &[("pos", vec2.clone())]);
+    assert_eq!(display(&vec2, &types), "Vec2?");
+    assert_eq!(display(&other, &types), "{x: number}");
+    assert_eq!(display(&nested, &types), "{pos: Vec2?}");
+}
+
+#[test]
+fn test_types_display_width() {
+    use std::borrow::Cow;
+    use kailua_diag::Localized;
+    use kailua_syntax::Str;
+    use ty::{Display, Tables, Union, Unioned, F};
+
+    fn key(s: &str) -> Key { Key::from(Str::from(s.as_bytes().to_owned())) }
+
+    fn display(ty: &Ty, types: &Types, width: usize) -> String {
+        let ty = ty.display(types as &TypeContext).with_max_width(width);
+        format!("{}", Localized::new(&ty, Locale::dummy()))
+    }
+
+    let mut types = Types::new(Locale::dummy(), Box::new(DummyClassProvider));
+
+    let rvar = types.gen_rvar();
+    let fields: Vec<_> = ["a", "b", "c", "d"].iter().map(|k| {
+        (key(k), Slot::new(F::Var, Ty::new(T::Integer)))
+    }).collect();
+    types.assert_rvar_includes(rvar.clone(), &fields).unwrap();
+    types.assert_rvar_closed(rvar.clone()).unwrap();
+    let rec = Ty::new(T::Tables(Cow::Owned(Tables::Fields(rvar))));
+    assert_eq!(display(&rec, &types, 2), "{a: integer, b: integer, <2 fields omitted>}");
+    assert_eq!(display(&rec, &types, 4), "{a: integer, b: integer, c: integer, d: integer}");
+    assert_eq!(display(&rec, &types, 0), "{<4 fields omitted>}");
+
+    let mut u = Unioned::explicit_int(1);
+    for i in 2..6 {
+        u = u.union(&Unioned::explicit_int(i), true, &mut types).unwrap();
+    }
+    let union = Ty::new(T::Union(Cow::Owned(u)));
+    assert_eq!(display(&union, &types, 3), "(1|2|3|<2 types omitted>)");
+    assert_eq!(display(&union, &types, 5), "(1|2|3|4|5)");
+}
+
+#[test]
+fn test_types_literal_union_limit() {
+    use std::borrow::Cow;
+    use kailua_syntax::Str;
+    use ty::{Numbers, Strings, Tables, Union, Unioned, F};
+
+    let mut types = Types::new(Locale::dummy(), Box::new(DummyClassProvider));
+    types.set_literal_union_limit(3);
+
+    let mut u = Unioned::explicit_int(1);
+    for i in 2..4 {
+        u = u.union(&Unioned::explicit_int(i), true, &mut types).unwrap();
+    }
+    assert_eq!(u.numbers, Some(Numbers::Some([1, 2, 3].iter().cloned().collect())));
+    assert!(types.exceeded_limits().is_empty());
+    u = u.union(&Unioned::explicit_int(4), true, &mut types).unwrap();
+    assert_eq!(u.numbers, Some(Numbers::Int));
+    assert_eq!(types.exceeded_limits(), &[ComplexityLimit::UnionSize]);
+
+    let mut u = Unioned::explicit_str(Str::from(b"a"[..].to_owned()));
+    for s in &["b", "c", "d"] {
+        let s = Unioned::explicit_str(Str::from(s.as_bytes().to_owned()));
+        u = u.union(&s, true, &mut types).unwrap();
+    }
+    assert_eq!(u.strings, Some(Strings::All));
+
+    // structurally equal records are collapsed into a single record
+    let mut record = |kind: &str| {
+        let rvar = types.gen_rvar();
+        let kind = Ty::new(T::Str(Cow::Owned(Str::from(kind.as_bytes().to_owned()))));
+        let fields = [(Key::from(Str::from(b"kind"[..].to_owned())), Slot::new(F::Var, kind))];
+        types.assert_rvar_includes(rvar.clone(), &fields).unwrap();
+        types.assert_rvar_closed(rvar.clone()).unwrap();
+        rvar
+    };
+    let (a1, a2, b) = (record("a"), record("a"), record("b"));
+    let mut tab = |rvar: &RVar| {
+        Unioned::from(&T::Tables(Cow::Owned(Tables::Fields(rvar.clone()))), &mut types).unwrap()
+    };
+    let (ua1, ua2, ub) = (tab(&a1), tab(&a2), tab(&b));
+    let u = ua1.union(&ua2, true, &mut types).unwrap();
+    assert_eq!(u.tables, Some(Tables::Fields(a1.clone())));
+    assert!(u.records.is_empty());
+    let u = ua1.union(&ub, true, &mut types).unwrap().union(&ua2, true, &mut types).unwrap();
+    assert_eq!(u.records, vec![a1, b]);
+}
+
 #[test]
 fn test_types_tvar() {
     let mut types = Types::new(Locale::dummy(), Box::new(DummyClassProvider));
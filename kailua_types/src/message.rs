@@ -4,158 +4,167 @@ use kailua_syntax::Name;
 
 pub type Ty<'a> = Displayed<'a, ty::Ty, &'a TypeContext>;
 
-define_msg! { pub NotSubtype<'a> { sub: &'a str, sup: &'a str }:
+// every message has a stable diagnostic code (`KT0123`) which users can refer to;
+// codes are never reused or renumbered, so a new message should get the next unused code
+
+define_msg! { pub NotSubtype<'a> { sub: &'a str, sup: &'a str } = "KT0001":
     "ko" => "`{sub}`이(가) `{sup}`의 서브타입이 아닙니다",
     _    => "`{sub}` is not a subtype of `{sup}`",
 }
 
-define_msg! { pub NotSubtypeInSelf<'a> { sub: &'a str, sup: &'a str }:
+define_msg! { pub NotSubtypeInSelf<'a> { sub: &'a str, sup: &'a str } = "KT0002":
     "ko" => "`self` 자리에 있는 `{sub}`이(가) `{sup}`의 서브타입이 아닙니다",
     _    => "`{sub}` in the `self` position is not a subtype of `{sup}`",
 }
 
-define_msg! { pub NotSubtypeInFuncArgs<'a> { sub: &'a str, sup: &'a str, index: Ordinal }:
+define_msg! { pub NotSubtypeInFuncArgs<'a> { sub: &'a str, sup: &'a str,
+                                             index: Ordinal } = "KT0003":
     "ko" => "함수의 {index} 인자 `{sub}`이(가) `{sup}`의 서브타입이 아닙니다",
     _    => "{index:+} function argument `{sub}` is not a subtype of `{sup}`",
 }
 
-define_msg! { pub NotSubtypeInMethodArgs<'a> { sub: &'a str, sup: &'a str, index: Ordinal }:
+define_msg! { pub NotSubtypeInMethodArgs<'a> { sub: &'a str, sup: &'a str,
+                                               index: Ordinal } = "KT0004":
     "ko" => "메소드의 {index} 인자 `{sub}`이(가) `{sup}`의 서브타입이 아닙니다",
     _    => "{index:+} method argument `{sub}` is not a subtype of `{sup}`",
 }
 
-define_msg! { pub NotSubtypeInReturns<'a> { sub: &'a str, sup: &'a str, index: Ordinal }:
+define_msg! { pub NotSubtypeInReturns<'a> { sub: &'a str, sup: &'a str, index: Ordinal } = "KT0005":
     "ko" => "함수의 {index} 반환값인 `{sub}`이(가) `{sup}`의 서브타입이 아닙니다",
     _    => "{index:+} return type `{sub}` is not a subtype of `{sup}`",
 }
 
-define_msg! { pub NotEqual<'a> { lhs: &'a str, rhs: &'a str }:
+define_msg! { pub NotEqual<'a> { lhs: &'a str, rhs: &'a str } = "KT0006":
     "ko" => "`{lhs}`와(과) `{rhs}`이(가) 같은 타입이 아닙니다",
     _    => "`{lhs}` does not equal to `{rhs}`",
 }
 
-define_msg! { pub NotEqualInSelf<'a> { lhs: &'a str, rhs: &'a str }:
+define_msg! { pub NotEqualInSelf<'a> { lhs: &'a str, rhs: &'a str } = "KT0007":
     "ko" => "`self` 자리에 있는 `{lhs}`와(과) `{rhs}`이(가) 같은 타입이 아닙니다",
     _    => "`{lhs}` in the `self` position does not equal to `{rhs}`",
 }
 
-define_msg! { pub NotEqualInFuncArgs<'a> { lhs: &'a str, rhs: &'a str, index: Ordinal }:
+define_msg! { pub NotEqualInFuncArgs<'a> { lhs: &'a str, rhs: &'a str, index: Ordinal } = "KT0008":
     "ko" => "함수의 {index} 인자 `{lhs}`와(과) `{rhs}`이(가) 같은 타입이 아닙니다",
     _    => "{index:+} function argument `{lhs}` does not equal to `{rhs}`",
 }
 
-define_msg! { pub NotEqualInMethodArgs<'a> { lhs: &'a str, rhs: &'a str, index: Ordinal }:
+define_msg! { pub NotEqualInMethodArgs<'a> { lhs: &'a str, rhs: &'a str,
+                                             index: Ordinal } = "KT0009":
     "ko" => "메소드의 {index} 인자 `{lhs}`와(과) `{rhs}`이(가) 같은 타입이 아닙니다",
     _    => "{index:+} method argument `{lhs}` does not equal to `{rhs}`",
 }
 
-define_msg! { pub NotEqualInReturns<'a> { lhs: &'a str, rhs: &'a str, index: Ordinal }:
+define_msg! { pub NotEqualInReturns<'a> { lhs: &'a str, rhs: &'a str, index: Ordinal } = "KT0010":
     "ko" => "함수의 {index} 반환값인 `{lhs}`와(과) `{rhs}`이(가) 같은 타입이 아닙니다",
     _    => "{index:+} return type `{lhs}` does not equal to `{rhs}`",
 }
 
-define_msg! { pub CannotUnionType<'a> { ty: &'a str }:
+define_msg! { pub CannotUnionType<'a> { ty: &'a str } = "KT0011":
     "ko" => "`{ty}` 타입을 포함하는 합 타입을 만들 수 없습니다",
     _    => "Cannot create a union type including `{ty}`",
 }
 
-define_msg! { pub InvalidUnionType<'a> { lhs: &'a str, rhs: &'a str }:
+define_msg! { pub InvalidUnionType<'a> { lhs: &'a str, rhs: &'a str } = "KT0012":
     "ko" => "`{lhs}`와(과) `{rhs}`의 합 타입을 만들 수 없습니다",
     _    => "Cannot create a union type of `{lhs}` and `{rhs}`",
 }
 
-define_msg! { pub InvalidUnionTypeInSelf<'a> { lhs: &'a str, rhs: &'a str }:
+define_msg! { pub InvalidUnionTypeInSelf<'a> { lhs: &'a str, rhs: &'a str } = "KT0013":
     "ko" => "`self` 자리에 있는 `{lhs}`와(과) `{rhs}`의 합 타입을 만들 수 없습니다",
     _    => "Cannot create a union type of `{lhs}` and `{rhs}` in the `self` position",
 }
 
-define_msg! { pub InvalidUnionTypeInFuncArgs<'a> { lhs: &'a str, rhs: &'a str, index: Ordinal }:
+define_msg! { pub InvalidUnionTypeInFuncArgs<'a> { lhs: &'a str, rhs: &'a str,
+                                                   index: Ordinal } = "KT0014":
     "ko" => "함수의 {index} 인자에서 `{lhs}`와(과) `{rhs}`의 합 타입을 만들 수 없습니다",
     _    => "Cannot create a union type of `{lhs}` and `{rhs}` in the {index} function argument",
 }
 
-define_msg! { pub InvalidUnionTypeInMethodArgs<'a> { lhs: &'a str, rhs: &'a str, index: Ordinal }:
+define_msg! { pub InvalidUnionTypeInMethodArgs<'a> { lhs: &'a str, rhs: &'a str,
+                                                     index: Ordinal } = "KT0015":
     "ko" => "메소드의 {index} 인자에서 `{lhs}`와(과) `{rhs}`의 합 타입을 만들 수 없습니다",
     _    => "Cannot create a union type of `{lhs}` and `{rhs}` in the {index} method argument",
 }
 
-define_msg! { pub InvalidUnionTypeInReturns<'a> { lhs: &'a str, rhs: &'a str, index: Ordinal }:
+define_msg! { pub InvalidUnionTypeInReturns<'a> { lhs: &'a str, rhs: &'a str,
+                                                  index: Ordinal } = "KT0016":
     "ko" => "함수의 {index} 반환값에서 `{lhs}`와(과) `{rhs}`의 합 타입을 만들 수 없습니다",
     _    => "Cannot create a union type of `{lhs}` and `{rhs}` in the {index} return type",
 }
 
-define_msg! { pub ArityMismatch<'a> { other: &'a str, index: Ordinal }:
+define_msg! { pub ArityMismatch<'a> { other: &'a str, index: Ordinal } = "KT0017":
     "ko" => "반대편 타입이 `{other}`이기 때문에 {index} 타입을 생략할 수 없습니다",
     _    => "{index:+} type cannot be omitted because the other type is `{other}`",
 }
 
-define_msg! { pub LessArityInFuncArgs<'a> { other: &'a str, index: Ordinal }:
+define_msg! { pub LessArityInFuncArgs<'a> { other: &'a str, index: Ordinal } = "KT0018":
     "ko" => "명시된 타입이 `{other}`이기 때문에 함수의 {index} 인자를 생략할 수 없습니다",
     _    => "{index:+} function argument cannot be omitted because its type is `{other}`",
 }
 
-define_msg! { pub LessArityInMethodArgs<'a> { other: &'a str, index: Ordinal }:
+define_msg! { pub LessArityInMethodArgs<'a> { other: &'a str, index: Ordinal } = "KT0019":
     "ko" => "반대편 타입이 `{other}`이기 때문에 메소드의 {index} 인자를 생략할 수 없습니다",
     _    => "{index:+} method argument cannot be omitted because its type is `{other}`",
 }
 
-define_msg! { pub LessArityInReturns<'a> { other: &'a str, index: Ordinal }:
+define_msg! { pub LessArityInReturns<'a> { other: &'a str, index: Ordinal } = "KT0020":
     "ko" => "반대편 타입이 `{other}`이기 때문에 {index} 반환값을 생략할 수 없습니다",
     _    => "{index:+} return value cannot be omitted because its type is `{other}`",
 }
 
-define_msg! { pub MoreArityInFuncArgs { index: usize }:
+define_msg! { pub MoreArityInFuncArgs { index: usize } = "KT0021":
     "ko" => "함수에 {index}개를 넘는 인자를 넣을 수 없습니다",
     _    => "Cannot give more than {index} argument(s) to the function",
 }
 
-define_msg! { pub MoreArityInMethodArgs { index: usize }:
+define_msg! { pub MoreArityInMethodArgs { index: usize } = "KT0022":
     "ko" => "`self`를 포함해 메소드에 {index}개를 넘는 인자를 넣을 수 없습니다",
     _    => "Cannot give more than {index} argument(s) including `self` to the method",
 }
 
-define_msg! { pub MoreArityInReturns { index: usize }:
+define_msg! { pub MoreArityInReturns { index: usize } = "KT0023":
     "ko" => "{index}개를 넘는 값을 반환할 수 없습니다",
     _    => "Cannot return more than {index} value(s)",
 }
 
-define_msg! { pub OtherTypeOrigin:
+define_msg! { pub OtherTypeOrigin = "KT0024":
     "ko" => "다른 타입은 여기에서 만들어졌습니다",
     _    => "The other type originates here",
 }
 
 // TODO should point to the correct span
-define_msg! { pub InextensibleRec:
+define_msg! { pub InextensibleRec = "KT0025":
     "ko" => "레코드 타입에 더 이상 새 필드를 추가할 수 없습니다",
     _    => "No longer possible to add a new field to this record type",
 }
 
 // TODO should point to the correct span
-define_msg! { pub RecursiveRec:
+define_msg! { pub RecursiveRec = "KT0026":
     "ko" => "레코드 타입에서 재귀 참조가 발견되었습니다",
     _    => "Recursive cycles detected in the record type",
 }
 
 // TODO should point to the correct span
-define_msg! { pub RecDuplicateKey<'a> { key: &'a Key }:
+define_msg! { pub RecDuplicateKey<'a> { key: &'a Key } = "KT0027":
     "ko" => "레코드 타입이 `{key}` 필드를 중복으로 가집니다",
     _    => "Duplicate key `{key}` found in the record type",
 }
 
 // TODO should point to the correct span
-define_msg! { pub RecCannotHaveKey<'a> { key: &'a Key }:
+define_msg! { pub RecCannotHaveKey<'a> { key: &'a Key } = "KT0028":
     "ko" => "레코드 타입이 `{key}` 필드를 가질 수 없습니다",
     _    => "The record cannot have a field with the key `{key}`",
 }
 
 // TODO should point to the correct span
-define_msg! { pub RecShouldHaveKeys<'a> { keys: &'a str }:
+define_msg! { pub RecShouldHaveKeys<'a> { keys: &'a str } = "KT0029":
     "ko" => "레코드 타입이 {keys} 필드를 포함하지 않습니다",
     _    => "The record does not have a field with the key(s) {keys}",
 }
 
 // TODO should point to the correct span
-define_msg! { pub RecExtendedWithNonNil<'a> { key: &'a Key, slot: &'a str }:
+define_msg! { pub RecExtendedWithNonNil<'a> { key: &'a Key, slot: &'a str } = "KT0030":
     "ko" => "레코드 타입에 원래 존재하지 않던 `{key}` 필드는 \
              명시적으로 nil을 포함하지 않는 `{slot}` 타입으로 추가될 수 없습니다",
     _    => "The record cannot add a new field with the key `{key}` and \
@@ -163,59 +172,96 @@ define_msg! { pub RecExtendedWithNonNil<'a> { key: &'a Key, slot: &'a str }:
 }
 
 // should be same to kailua_check's version
-define_msg! { pub CannotUpdate<'a> { tab: &'a str }:
+define_msg! { pub CannotUpdate<'a> { tab: &'a str } = "KT0031":
     "ko" => "변경할 수 없는 `{tab}` 타입을 인덱싱해서 갱신할 수 없습니다",
     _    => "Cannot update the immutable type `{tab}` by indexing",
 }
 
 // should be same to kailua_check's version
-define_msg! { pub CannotAssign<'a> { lhs: &'a str, rhs: &'a str }:
+define_msg! { pub CannotAssign<'a> { lhs: &'a str, rhs: &'a str } = "KT0032":
     "ko" => "`{lhs}` 타입에 `{rhs}` 타입을 대입할 수 없습니다",
     _    => "Cannot assign `{rhs}` into `{lhs}`",
 }
 
-define_msg! { pub CannotFilter<'a> { ty: &'a str }:
+define_msg! { pub CannotFilter<'a> { ty: &'a str } = "KT0033":
     "ko" => "`{ty}` 타입을 좁힐 수 없습니다",
     _    => "Cannot narrow `{ty}`",
 }
 
-define_msg! { pub UnknownAttrName<'a> { name: &'a Name }:
+define_msg! { pub UnknownAttrName<'a> { name: &'a Name } = "KT0034":
     "ko" => "{name} 타입 속성을 알 수 없어서 무시합니다",
     _    => "{name} is an unknown type attribute and ignored",
 }
 
-define_msg! { pub AttrCannotHaveAnyValues<'a> { name: &'a Name }:
+define_msg! { pub AttrCannotHaveAnyValues<'a> { name: &'a Name } = "KT0035":
     "ko" => "{name} 타입 속성에는 아무 값도 붙을 수 없습니다",
     _    => "The type attribute {name} cannot have any values",
 }
 
-define_msg! { pub AttrRequiresFixedNumOfValues<'a> { name: &'a Name, count: usize }:
+define_msg! { pub AttrRequiresFixedNumOfValues<'a> { name: &'a Name, count: usize } = "KT0036":
     "ko" => "{name} 타입 속성에는 정확히 {count}개의 값이 붙어야 합니다",
     _    => "The type attribute {name} requires exactly {count} value(s)",
 }
 
-define_msg! { pub DuplicateAttr<'a> { ty: Ty<'a> }:
+define_msg! { pub AttrRequiresOptionalStr<'a> { name: &'a Name } = "KT0037":
+    "ko" => "{name} 타입 속성은 인자가 없거나 문자열 하나만 받을 수 있습니다",
+    _    => "{name} type attribute can only have no values or a single string",
+}
+
+define_msg! { pub DuplicateAttr<'a> { ty: Ty<'a> } = "KT0038":
     "ko" => "이미 속성이 붙어 있는 `{ty}` 타입에 속성을 더 붙일 수 없습니다",
     _    => "Cannot add an attribute to a type `{ty}` with an existing attribute",
 }
 
-define_msg! { pub UnsupportedErrorType:
+define_msg! { pub NonGenericTypeWithArgs<'a> { name: &'a Name } = "KT0039":
+    "ko" => "{name} 타입은 제네릭 클래스가 아니라서 타입 인자를 받을 수 없습니다",
+    _    => "The type {name} is not a generic class and cannot have type arguments",
+}
+
+define_msg! { pub WrongNumOfTypeArgs<'a> { name: &'a Name, expected: usize,
+                                           actual: usize } = "KT0040":
+    "ko" => "{name} 타입에는 {expected}개의 타입 인자가 필요한데 {actual}개가 주어졌습니다",
+    _    => "The type {name} requires {expected} type argument(s) but {actual} were given",
+}
+
+define_msg! { pub UnsupportedErrorType = "KT0041":
     "ko" => "`error \"메시지\"` 타입은 아직 지원되지 않습니다",
     _    => "`error \"message\"` type is not yet supported",
 }
 
-define_msg! { pub DuplicateFieldNameInRec<'a> { name: &'a Name }:
+define_msg! { pub DuplicateFieldNameInRec<'a> { name: &'a Name } = "KT0042":
     "ko" => "타입에서 레코드 이름 {name}이 중복됩니다",
     _    => "Duplicate record field {name} in the type specification",
 }
 
-define_msg! { pub FirstFieldNameInRec:
+define_msg! { pub FirstFieldNameInRec = "KT0043":
     "ko" => "여기서 처음 나왔습니다",
     _    => "The first duplicate appeared here",
 }
 
-define_msg! { pub UnsupportedUnionTypeSpec:
+define_msg! { pub UnsupportedUnionTypeSpec = "KT0044":
     "ko" => "이 합 타입은 타입 명세에서 지원되지 않습니다",
     _    => "This union type is not supported in the specification",
 }
 
+define_msg! { pub UnsupportedExcludedTypeSpec = "KT0045":
+    "ko" => "`T - U` 타입에서 제외되는 타입은 원시 타입이나 그 합 타입이어야 합니다",
+    _    => "The excluded type in `T - U` should be a primitive type or a union of them",
+}
+
+define_msg! { pub UnsupportedDifferenceTypeSpec = "KT0046":
+    "ko" => "이 타입에서 다른 타입을 제외할 수 없습니다",
+    _    => "Cannot exclude other types from this type in the specification",
+}
+
+#[test]
+fn test_codes() {
+    let source = include_str!("message.rs");
+    let mut codes: Vec<&str> = source.split("= \"KT").skip(1).map(|s| &s[..4]).collect();
+    assert_eq!(codes.len(), source.matches("define_msg! {").count() - 1);
+    assert!(codes.iter().all(|code| code.bytes().all(|c| b'0' <= c && c <= b'9')));
+    codes.sort();
+    let ncodes = codes.len();
+    codes.dedup();
+    assert_eq!(codes.len(), ncodes, "duplicate diagnostic codes");
+}
@@ -0,0 +1,69 @@
+use std::fmt;
+
+use diag::{Origin, TypeReport, TypeResult};
+use super::{Display, DisplayState, Ty, TypeContext, Lattice, Substitution};
+
+/// A coroutine type with known yield and resume value types, i.e. `thread<Y, R>`.
+///
+/// `yields` is the type of values passed to the resuming code
+/// (both by `coroutine.yield` and by the final return),
+/// and `resumes` is the type of values passed to the coroutine by `coroutine.resume`.
+/// Only the first value of each is tracked.
+#[derive(Clone)]
+pub struct Coroutine {
+    pub yields: Ty,
+    pub resumes: Ty,
+}
+
+impl Coroutine {
+    pub fn new(yields: Ty, resumes: Ty) -> Coroutine {
+        Coroutine { yields: yields, resumes: resumes }
+    }
+
+    pub fn generalize(self, ctx: &mut TypeContext) -> Coroutine {
+        Coroutine { yields: self.yields.generalize(ctx), resumes: self.resumes.generalize(ctx) }
+    }
+
+    /// Replaces type parameters present in the coroutine to their actual types.
+    pub fn substitute(self, subst: &mut Substitution, ctx: &mut TypeContext) -> Coroutine {
+        Coroutine {
+            yields: self.yields.substitute(subst, ctx),
+            resumes: self.resumes.substitute(subst, ctx),
+        }
+    }
+}
+
+impl Lattice for Coroutine {
+    fn assert_sub(&self, other: &Self, ctx: &mut TypeContext) -> TypeResult<()> {
+        // yielded values flow out of the coroutine and resumed values flow into it
+        (|| {
+            self.yields.assert_sub(&other.yields, ctx)?;
+            other.resumes.assert_sub(&self.resumes, ctx)
+        })().map_err(|r: TypeReport| r.not_sub(Origin::Coroutine, self, other, ctx))
+    }
+
+    fn assert_eq(&self, other: &Self, ctx: &mut TypeContext) -> TypeResult<()> {
+        (|| {
+            self.yields.assert_eq(&other.yields, ctx)?;
+            self.resumes.assert_eq(&other.resumes, ctx)
+        })().map_err(|r: TypeReport| r.not_eq(Origin::Coroutine, self, other, ctx))
+    }
+}
+
+impl PartialEq for Coroutine {
+    fn eq(&self, other: &Coroutine) -> bool {
+        self.yields == other.yields && self.resumes == other.resumes
+    }
+}
+
+impl Display for Coroutine {
+    fn fmt_displayed(&self, f: &mut fmt::Formatter, st: &DisplayState) -> fmt::Result {
+        write!(f, "thread<{}, {}>", self.yields.display(st), self.resumes.display(st))
+    }
+}
+
+impl fmt::Debug for Coroutine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "thread<{:?}, {:?}>", self.yields, self.resumes)
+    }
+}
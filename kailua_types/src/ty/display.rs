@@ -5,7 +5,7 @@ use std::collections::{HashSet, HashMap};
 use kailua_env::{Span, Spanned};
 use kailua_diag::{Locale, Localize, Localized};
 use kailua_syntax::Name;
-use super::{TypeContext, S, TVar, RVar};
+use super::{TypeContext, S, TVar, RVar, Ty};
 
 /// A display state to catch recursive types and handle display hints.
 pub struct DisplayState<'a> {
@@ -22,6 +22,10 @@ pub struct DisplayState<'a> {
     // rvars are one of the biggest source of type display bloats,
     // so they are controlled via {:.<maxlevel>} (0 means that no records are printed)
     max_rvar_level: Option<usize>,
+
+    // records and unions with too many items are truncated via `Displayed::with_max_width`,
+    // in which case the number of omitted items is printed instead
+    max_width: Option<usize>,
 }
 
 impl<'a> DisplayState<'a> {
@@ -34,6 +38,7 @@ impl<'a> DisplayState<'a> {
             tvars_seen: RefCell::new(HashSet::new()),
             rvars_seen: RefCell::new(HashSet::new()),
             max_rvar_level: f.precision(),
+            max_width: None,
         }
     }
 
@@ -45,6 +50,11 @@ impl<'a> DisplayState<'a> {
         self.max_rvar_level.map_or(true, |maxlevel| self.rvars_seen.borrow().len() < maxlevel)
     }
 
+    /// Returns the maximum number of fields in a record or types in a union to be printed.
+    pub fn max_width(&self) -> Option<usize> {
+        self.max_width
+    }
+
     pub fn is_slot_seen(&self, slot: &S) -> bool {
         !self.slots_seen.borrow_mut().insert(slot as *const S)
     }
@@ -156,7 +166,7 @@ impl Display for DisplayName {
                 write!(f, "{:+}{}", name.base, st.disambiguator(name))
             },
             DisplayName::Var(ref name) => {
-                match &st.locale[..] {
+                match &st.locale.resolve(&["ko"])[..] {
                     "ko" => write!(f, "<변수")?,
                     _    => write!(f, "<variable")?,
                 }
@@ -166,14 +176,44 @@ impl Display for DisplayName {
     }
 }
 
+/// A hook to override how certain types are displayed.
+///
+/// Hooks are registered to the type context (`kailua_types::env::Types::add_display_hook`)
+/// and consulted for every type without its own display name,
+/// so that embedders can render domain types (e.g. a `Vec3` record) in a concise form.
+pub trait DisplayHook: Send + Sync {
+    /// Should print given type to the formatter and return `Some` if this hook handles it.
+    /// Returning `None` lets the type (or the next hook) displayed as usual.
+    ///
+    /// The nilability suffix (`?` or `!`) is printed separately after the hook,
+    /// but the tag is not; a hook is free to ignore or print it.
+    /// Displaying `ty` itself from the hook will recurse, while its parts can be displayed.
+    fn fmt_type(&self, ty: &Ty, f: &mut fmt::Formatter, st: &DisplayState) -> Option<fmt::Result>;
+}
+
+impl<F> DisplayHook for F
+    where F: Fn(&Ty, &mut fmt::Formatter, &DisplayState) -> Option<fmt::Result> + Send + Sync
+{
+    fn fmt_type(&self, ty: &Ty, f: &mut fmt::Formatter, st: &DisplayState) -> Option<fmt::Result> {
+        self(ty, f, st)
+    }
+}
+
 /// Human-readable description of various types requiring the type context.
 ///
 /// Expected to implement `std::fmt::Display`.
+/// For types, following format flags are recognized:
+///
+/// * `{:#}` prints the nilability even when it is implicit.
+/// * `{:0}` ignores display names and hooks at the top level.
+/// * `{:.N}` prints nested records only up to `N` levels.
+///
+/// Records and unions can be also truncated with `Displayed::with_max_width`.
 pub trait Display: fmt::Debug + Sized {
     fn fmt_displayed(&self, f: &mut fmt::Formatter, st: &DisplayState) -> fmt::Result;
 
     fn display<'b, C>(&'b self, ctx: C) -> Displayed<'b, Self, C> {
-        Displayed { base: self, ctx: ctx }
+        Displayed { base: self, ctx: ctx, max_width: None }
     }
 }
 
@@ -217,6 +257,7 @@ impl<'a> Display for &'a str {
 pub struct Displayed<'b, T: Display + 'b, C> {
     base: &'b T,
     ctx: C,
+    max_width: Option<usize>,
 }
 
 impl<'b, 'c, 'd, T: Display + 'b> fmt::Display for Displayed<'b, T, &'c DisplayState<'d>> {
@@ -226,6 +267,11 @@ impl<'b, 'c, 'd, T: Display + 'b> fmt::Display for Displayed<'b, T, &'c DisplayS
 }
 
 impl<'b, 'c, T: Display + 'b> Displayed<'b, T, &'c TypeContext> {
+    /// Prints only the first `max_width` fields of records and types of unions.
+    pub fn with_max_width(self, max_width: usize) -> Displayed<'b, T, &'c TypeContext> {
+        Displayed { max_width: Some(max_width), ..self }
+    }
+
     // a shortcut for `Localized::new` (hard to provide for Localize itself due to trait object)
     pub fn localized<'a>(&'a self,
                          locale: Locale) -> Localized<'a, Displayed<'b, T, &'c TypeContext>> {
@@ -235,7 +281,8 @@ impl<'b, 'c, T: Display + 'b> Displayed<'b, T, &'c TypeContext> {
 
 impl<'b, 'c, T: Display + 'b> Localize for Displayed<'b, T, &'c TypeContext> {
     fn fmt_localized(&self, f: &mut fmt::Formatter, locale: Locale) -> fmt::Result {
-        let st = DisplayState::new(f, locale, self.ctx);
+        let mut st = DisplayState::new(f, locale, self.ctx);
+        st.max_width = self.max_width;
         self.base.fmt_displayed(f, &st)
     }
 }
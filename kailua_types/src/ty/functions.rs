@@ -1,15 +1,245 @@
 use std::fmt;
-use kailua_env::Spanned;
-use kailua_diag::Result;
+use kailua_env::{Span, Spanned};
+use kailua_diag::{Result, Locale, Report, Kind, Localize};
 use kailua_syntax::Name;
 use kailua_syntax::ast::{FuncKind, Returns};
 
 use diag::{Origin, TypeReport, TypeResult};
-use super::{Display, DisplayState, Ty, TySeq, TypeContext, TypeResolver, Lattice};
+use super::{Display, DisplayState, T, Ty, TySeq, Tables, TypeContext, TypeResolver};
+use super::{Lattice, Union, RVar, TParam, Class, ClassId, ClassSystemId};
+
+/// A mapping from type parameters to actual types, used to instantiate generic functions.
+pub struct Substitution {
+    bindings: Vec<(TParam, Ty)>,
+    // row variables already substituted, so that recursive records can be handled
+    rvars: Vec<(RVar, RVar)>,
+}
+
+impl Substitution {
+    pub fn new() -> Substitution {
+        Substitution { bindings: Vec::new(), rvars: Vec::new() }
+    }
+
+    /// Binds a type parameter to given type, replacing the prior binding if any.
+    pub fn bind(&mut self, param: TParam, ty: Ty) {
+        if let Some(binding) = self.bindings.iter_mut().find(|&&mut (ref p, _)| *p == param) {
+            binding.1 = ty;
+            return;
+        }
+        self.bindings.push((param, ty));
+    }
+
+    /// Returns a type bound to given type parameter if any.
+    pub fn get(&self, param: &TParam) -> Option<&Ty> {
+        self.bindings.iter().find(|&&(ref p, _)| *p == *param).map(|&(_, ref ty)| ty)
+    }
+
+    /// Replaces a row variable with a fresh one with all fields substituted.
+    pub fn substitute_rvar(&mut self, rvar: RVar, ctx: &mut TypeContext) -> RVar {
+        if rvar == RVar::empty() || rvar == RVar::any() {
+            return rvar;
+        }
+        if let Some(&(_, ref rvar_)) = self.rvars.iter().find(|&&(ref r, _)| *r == rvar) {
+            return rvar_.clone();
+        }
+
+        let mut fields = Vec::new();
+        let last = ctx.list_rvar_fields(rvar.clone(), &mut |k, v| {
+            fields.push((k.clone(), v.clone()));
+            Ok(())
+        }).expect("list_rvar_fields exited early while we haven't break");
+
+        let rvar_ = ctx.gen_rvar();
+        self.rvars.push((rvar, rvar_.clone()));
+        let fields: Vec<_> = fields.into_iter().map(|(k, v)| {
+            (k, v.substitute(self, ctx))
+        }).collect();
+        ctx.assert_rvar_includes(rvar_.clone(), &fields).expect(
+            "cannot insert disjoint fields into a fresh row variable"
+        );
+        if last == RVar::empty() {
+            ctx.assert_rvar_closed(rvar_.clone()).expect(
+                "cannot make a fresh row variable not extensible"
+            );
+        }
+        rvar_
+    }
+
+    /// Replaces type arguments of a specialized generic class (or the generic class itself).
+    pub fn substitute_class(&mut self, cid: ClassId, ctx: &mut TypeContext) -> ClassId {
+        if self.bindings.is_empty() {
+            return cid;
+        }
+        if let Some((generic, args)) = ctx.class_type_args(cid) {
+            let args = args.into_iter().map(|t| t.substitute(self, ctx)).collect();
+            ctx.specialize_class(generic, args).unwrap_or(cid)
+        } else {
+            cid
+        }
+    }
+
+    // collects bindings for `params` by matching the formal type against the actual type.
+    // this is a best-effort approximation; the instantiated type should be checked later.
+    fn infer(&mut self, params: &[TParam], formal: &Ty, actual: &Ty, ctx: &mut TypeContext) {
+        let actual = if let Some(actual) = ctx.resolve_exact_type(actual) {
+            actual
+        } else {
+            return;
+        };
+
+        if let T::Param(ref p) = **formal {
+            if params.contains(p) {
+                let actual = if formal.can_omit() { actual.without_nil() } else { actual };
+                self.infer_param(p, actual.coerce(), ctx);
+            }
+            return;
+        }
+
+        match (formal.get_tables(), actual.get_tables()) {
+            (Some(&Tables::Array(ref fv)), Some(&Tables::Array(ref av))) |
+            (Some(&Tables::Array(ref fv)), Some(&Tables::ArrayN(ref av))) |
+            (Some(&Tables::ArrayN(ref fv)), Some(&Tables::Array(ref av))) |
+            (Some(&Tables::ArrayN(ref fv)), Some(&Tables::ArrayN(ref av))) => {
+                self.infer(params, &fv.unlift(), &av.unlift(), ctx);
+            }
+            (Some(&Tables::Map(ref fk, ref fv)), Some(&Tables::Map(ref ak, ref av))) => {
+                self.infer(params, fk, ak, ctx);
+                self.infer(params, &fv.unlift(), &av.unlift(), ctx);
+            }
+            (Some(&Tables::Map(ref fk, ref fv)), Some(&Tables::Array(ref av))) => {
+                self.infer(params, fk, &Ty::new(T::Integer), ctx);
+                self.infer(params, &fv.unlift(), &av.unlift(), ctx);
+            }
+            (Some(&Tables::Array(ref fv)), Some(&Tables::Fields(ref ar))) => {
+                for (_, av) in ctx.get_rvar_fields(ar.clone()) {
+                    self.infer(params, &fv.unlift(), &av.unlift(), ctx);
+                }
+            }
+            (Some(&Tables::Map(ref fk, ref fv)), Some(&Tables::Fields(ref ar))) => {
+                for (k, av) in ctx.get_rvar_fields(ar.clone()) {
+                    self.infer(params, fk, &Ty::new(k.to_type().into_send()), ctx);
+                    self.infer(params, &fv.unlift(), &av.unlift(), ctx);
+                }
+            }
+            (Some(&Tables::Fields(ref fr)), Some(&Tables::Fields(ref ar))) => {
+                let afields = ctx.get_rvar_fields(ar.clone());
+                for (k, fv) in ctx.get_rvar_fields(fr.clone()) {
+                    if let Some(&(_, ref av)) = afields.iter().find(|&&(ref ak, _)| *ak == k) {
+                        self.infer(params, &fv.unlift(), &av.unlift(), ctx);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if let (&T::Class(Class::Instance(fc)), &T::Class(Class::Instance(ac))) =
+                (&**formal, &*actual) {
+            if let (Some((fg, fargs)), Some((ag, aargs))) = (ctx.class_type_args(fc),
+                                                             ctx.class_type_args(ac)) {
+                if fg == ag {
+                    for (f, a) in fargs.iter().zip(&aargs) {
+                        self.infer(params, f, a, ctx);
+                    }
+                }
+            }
+        }
+
+        if let (&T::Coroutine(ref fc), &T::Coroutine(ref ac)) = (&**formal, &*actual) {
+            self.infer(params, &fc.yields, &ac.yields, ctx);
+            self.infer(params, &fc.resumes, &ac.resumes, ctx);
+        }
+
+        match (formal.get_functions(), actual.get_functions()) {
+            (Some(&Functions::Simple(ref ff)), Some(&Functions::Simple(ref af))) => {
+                self.infer_seq(params, &ff.args, &af.args, ctx);
+                if let (&Some(ref fr), &Some(ref ar)) = (&ff.returns, &af.returns) {
+                    self.infer_seq(params, fr, ar, ctx);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn infer_seq(&mut self, params: &[TParam], formal: &TySeq, actual: &TySeq,
+                 ctx: &mut TypeContext) {
+        let len = formal.head.len().max(actual.head.len());
+        for i in 0..len {
+            let f = formal.head.get(i).or(formal.tail.as_ref());
+            let a = actual.head.get(i).or(actual.tail.as_ref());
+            if let (Some(f), Some(a)) = (f, a) {
+                self.infer(params, f, a, ctx);
+            }
+        }
+        if let (&Some(ref f), &Some(ref a)) = (&formal.tail, &actual.tail) {
+            self.infer(params, f, a, ctx);
+        }
+    }
+
+    fn infer_param(&mut self, param: &TParam, ty: Ty, ctx: &mut TypeContext) {
+        // dynamic types give no information, so they are only used when nothing else is known
+        let ty = match self.get(param) {
+            Some(prev) if ty.is_dynamic() => prev.clone(),
+            Some(prev) if prev.is_dynamic() => ty,
+            Some(prev) => match prev.union(&ty, false, ctx) {
+                Ok(ty) => ty,
+                Err(_) => prev.clone(), // will be reported when the instance gets checked
+            },
+            None => ty,
+        };
+        self.bind(param.clone(), ty);
+    }
+}
+
+// a type resolver that additionally resolves type parameters to the corresponding types
+struct ParamResolver<'a> {
+    parent: &'a mut TypeResolver,
+    params: &'a [TParam],
+}
+
+impl<'a> Report for ParamResolver<'a> {
+    fn message_locale(&self) -> Locale {
+        self.parent.message_locale()
+    }
+
+    fn add_span(&self, k: Kind, s: Span, m: &Localize) -> Result<()> {
+        self.parent.add_span(k, s, m)
+    }
+}
+
+impl<'a> TypeResolver for ParamResolver<'a> {
+    fn context(&self) -> &TypeContext {
+        self.parent.context()
+    }
+
+    fn context_mut(&mut self) -> &mut TypeContext {
+        self.parent.context_mut()
+    }
+
+    fn ty_from_name(&self, name: &Spanned<Name>) -> Result<Ty> {
+        if let Some(param) = self.params.iter().find(|p| p.name == name.base) {
+            Ok(Ty::new(T::Param(param.clone())))
+        } else {
+            self.parent.ty_from_name(name)
+        }
+    }
+
+    fn class_system_from_name(&self, name: &Spanned<Name>) -> Result<Option<ClassSystemId>> {
+        self.parent.class_system_from_name(name)
+    }
+}
+
+fn substitute_tyseq(seq: TySeq, subst: &mut Substitution, ctx: &mut TypeContext) -> TySeq {
+    let head = seq.head.into_iter().map(|t| t.substitute(subst, ctx)).collect();
+    let tail = seq.tail.map(|t| t.substitute(subst, ctx));
+    TySeq { head: head, tail: tail }
+}
 
 /// A function type.
 #[derive(Clone, PartialEq)]
 pub struct Function {
+    /// Type parameters, empty if the function is not generic.
+    pub type_params: Vec<TParam>,
+
     /// Argument types.
     pub args: TySeq,
 
@@ -22,7 +252,21 @@ pub struct Function {
 
 impl Function {
     pub fn from_kind(func: &Spanned<FuncKind>, resolv: &mut TypeResolver) -> Result<Function> {
-        let args = TySeq::from_kind_seq(&func.args, |namekind| &namekind.1, resolv)?;
+        let type_params: Vec<_> = func.type_params.iter().map(|name| {
+            resolv.context_mut().gen_tparam(name.base.clone())
+        }).collect();
+
+        let (args, returns) = {
+            let mut resolv = ParamResolver { parent: resolv, params: &type_params };
+            let args = TySeq::from_kind_seq(&func.args, |namekind| &namekind.1, &mut resolv)?;
+            let returns = match func.returns {
+                Returns::Seq(ref seq) =>
+                    Some(TySeq::from_kind_seq(seq, |kind| kind, &mut resolv)?),
+                Returns::Never(_span) => None,
+            };
+            (args, returns)
+        };
+
         let mut argnames = Vec::new();
         for (i, &(ref name, _)) in func.args.head.iter().enumerate() {
             if let Some(ref name) = *name {
@@ -30,14 +274,78 @@ impl Function {
                 argnames.push(Some(name.clone()));
             }
         }
-        let returns = match func.returns {
-            Returns::Seq(ref seq) => Some(TySeq::from_kind_seq(seq, |kind| kind, resolv)?),
-            Returns::Never(_span) => None,
-        };
-        Ok(Function { args: args, argnames: argnames, returns: returns })
+        Ok(Function { type_params: type_params, args: args, argnames: argnames, returns: returns })
+    }
+
+    pub fn is_generic(&self) -> bool {
+        !self.type_params.is_empty()
+    }
+
+    /// Replaces type parameters present in the function to their actual types.
+    ///
+    /// Type parameters of this function itself are not affected.
+    pub fn substitute(self, subst: &mut Substitution, ctx: &mut TypeContext) -> Function {
+        Function {
+            type_params: self.type_params,
+            args: substitute_tyseq(self.args, subst, ctx),
+            argnames: self.argnames,
+            returns: self.returns.map(|returns| substitute_tyseq(returns, subst, ctx)),
+        }
+    }
+
+    /// Instantiates a generic function with type parameters inferred from given argument types.
+    ///
+    /// Type parameters that cannot be inferred are replaced with `unbound`.
+    /// The resulting function is no longer generic, and should be still checked against
+    /// the arguments as the inference is not guaranteed to be consistent.
+    pub fn instantiate(&self, args: &TySeq, unbound: &Ty, ctx: &mut TypeContext) -> Function {
+        if !self.is_generic() {
+            return self.clone();
+        }
+
+        let mut subst = Substitution::new();
+        subst.infer_seq(&self.type_params, &self.args, args, ctx);
+        for param in &self.type_params {
+            if subst.get(param).is_none() {
+                subst.bind(param.clone(), unbound.clone());
+            }
+        }
+
+        let mut func = self.clone().substitute(&mut subst, ctx);
+        func.type_params.clear();
+        func
+    }
+
+    // makes type parameters of two generic functions identical so that they can be compared
+    fn rename_type_params(&self, other: &Function, ctx: &mut TypeContext) -> Function {
+        let mut subst = Substitution::new();
+        for (param, otherparam) in self.type_params.iter().zip(&other.type_params) {
+            subst.bind(param.clone(), Ty::new(T::Param(otherparam.clone())));
+        }
+        let mut func = self.clone().substitute(&mut subst, ctx);
+        func.type_params = other.type_params.clone();
+        func
     }
 
     fn assert_sub(&self, other: &Self, ctx: &mut TypeContext) -> TypeResult<()> {
+        if self.type_params.len() != other.type_params.len() {
+            if other.is_generic() {
+                // a non-generic function cannot be used as a generic function
+                return Err(ctx.gen_report());
+            }
+
+            // a generic function can be used as any instance of it
+            let inst = self.instantiate(&other.args, &Ty::new(T::All), ctx);
+            return inst.assert_sub(other, ctx);
+        }
+        if self.is_generic() {
+            let renamed = self.rename_type_params(other, ctx);
+            return renamed.assert_sub_instance(other, ctx);
+        }
+        self.assert_sub_instance(other, ctx)
+    }
+
+    fn assert_sub_instance(&self, other: &Self, ctx: &mut TypeContext) -> TypeResult<()> {
         // contravariant
         other.args.assert_sub(&self.args, ctx)?;
 
@@ -52,6 +360,17 @@ impl Function {
     }
 
     fn assert_eq(&self, other: &Self, ctx: &mut TypeContext) -> TypeResult<()> {
+        if self.type_params.len() != other.type_params.len() {
+            return Err(ctx.gen_report());
+        }
+        if self.is_generic() {
+            let renamed = self.rename_type_params(other, ctx);
+            return renamed.assert_eq_instance(other, ctx);
+        }
+        self.assert_eq_instance(other, ctx)
+    }
+
+    fn assert_eq_instance(&self, other: &Self, ctx: &mut TypeContext) -> TypeResult<()> {
         self.args.assert_eq(&other.args, ctx)?;
 
         match (&self.returns, &other.returns) {
@@ -72,7 +391,16 @@ impl Function {
             where WriteTy: FnMut(&Ty, &mut fmt::Formatter, bool) -> fmt::Result,
                   WriteTySeq: FnMut(&TySeq, &mut fmt::Formatter) -> fmt::Result {
         // we cannot directly print self.args as they should be interleaved with self.argnames
-        write!(f, "function(")?;
+        write!(f, "function")?;
+        if !self.type_params.is_empty() {
+            write!(f, "<")?;
+            for (i, param) in self.type_params.iter().enumerate() {
+                if i > 0 { write!(f, ", ")?; }
+                write!(f, "{:+}", param.name)?;
+            }
+            write!(f, ">")?;
+        }
+        write!(f, "(")?;
         let mut first = true;
         let mut names = self.argnames.iter();
         for t in &self.args.head {
@@ -144,6 +472,14 @@ pub enum Functions {
 }
 
 impl Functions {
+    /// Replaces type parameters present in the function to their actual types.
+    pub fn substitute(self, subst: &mut Substitution, ctx: &mut TypeContext) -> Functions {
+        match self {
+            Functions::Simple(func) => Functions::Simple(func.substitute(subst, ctx)),
+            Functions::All => Functions::All,
+        }
+    }
+
     fn fmt_generic<WriteFunc>(&self, f: &mut fmt::Formatter,
                               mut write_func: WriteFunc) -> fmt::Result
             where WriteFunc: FnMut(&Function, &mut fmt::Formatter) -> fmt::Result {
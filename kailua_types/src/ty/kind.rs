@@ -0,0 +1,633 @@
+//! Printing types back to the type syntax.
+//!
+//! The `Display` output is meant to be read by humans: it is localized, follows display hints
+//! and hooks, and abbreviates large or recursive types. Anything that writes types
+//! to be parsed again (e.g. stub generation or the insertion of type annotations)
+//! should instead use `Ty::to_kind_string`, whose output is guaranteed to be parsed back
+//! (`kailua_syntax::parse_kind` followed by `Ty::from_kind`) to an equivalent type,
+//! i.e. a type that is both a subtype and a supertype of the original type.
+//!
+//! Type names and class names are printed as is, so they should be resolvable
+//! in the scope where the output is parsed. Type parameters are also printed by their names.
+
+use std::fmt;
+use std::result;
+use kailua_diag::Locale;
+use kailua_syntax::Name;
+use super::{TypeContext, DisplayState, TVar, RVar, Class, Dyn, Nil, T, Ty, Tag, Key, Tables};
+use super::{Function, Functions, TySeq, F, Slot};
+use super::flags::T_ALL;
+
+/// A reason that a type cannot be printed in the type syntax.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unexpressible {
+    /// The type contains an error type.
+    Error,
+
+    /// The type contains an unresolved type variable or an uninitialized slot.
+    Unknown,
+
+    /// The type contains a class prototype or an unnamed class.
+    Class,
+
+    /// The type contains `vector<T>` with an implied `n` field (from `table.pack` etc.).
+    ArrayN,
+
+    /// The type contains a table with integer keys that cannot be written as a tuple.
+    Table,
+
+    /// The type is recursive.
+    Recursive,
+}
+
+impl fmt::Display for Unexpressible {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match *self {
+            Unexpressible::Error => "an error type",
+            Unexpressible::Unknown => "an unknown type",
+            Unexpressible::Class => "a class prototype or an unnamed class",
+            Unexpressible::ArrayN => "a vector with an implied `n` field",
+            Unexpressible::Table => "a table with integer keys which is not a tuple",
+            Unexpressible::Recursive => "a recursive type",
+        };
+        write!(f, "the type contains {}, which has no representation in the type syntax", reason)
+    }
+}
+
+type Result<T> = result::Result<T, Unexpressible>;
+
+// formats a part of the type that is only available via `DisplayState`
+fn format_with_state<Fmt>(ctx: &TypeContext, fmt: Fmt) -> String
+    where Fmt: Fn(&mut fmt::Formatter, &DisplayState) -> fmt::Result
+{
+    struct Formatted<'a, Fmt> {
+        ctx: &'a TypeContext,
+        fmt: Fmt,
+    }
+
+    impl<'a, Fmt> fmt::Display for Formatted<'a, Fmt>
+        where Fmt: Fn(&mut fmt::Formatter, &DisplayState) -> fmt::Result
+    {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let st = DisplayState::new(f, Locale::dummy(), self.ctx);
+            (self.fmt)(f, &st)
+        }
+    }
+
+    Formatted { ctx: ctx, fmt: fmt }.to_string()
+}
+
+// the printed name should be a single (possibly quoted) name token
+fn is_single_name(s: &str) -> bool {
+    if s.len() >= 2 && s.starts_with('`') && s.ends_with('`') {
+        // the `Display` output may contain escape sequences which the lexer doesn't accept
+        !s[1..s.len()-1].contains(|c| c == '`' || c == '\\')
+    } else {
+        !s.is_empty() && !Name::from(s.as_bytes()).quote_required()
+    }
+}
+
+/// Quotes a byte string with given quote character (`"`, `'` or `` ` ``),
+/// so that it can be read back by the lexer in any Lua version.
+///
+/// `Debug` outputs of `Str` and `Name` are not suitable for this purpose,
+/// as they use `\xNN` and `` \` `` which Lua 5.1 doesn't know.
+pub fn quote(s: &[u8], quote: char) -> String {
+    let mut out = String::new();
+    out.push(quote);
+    for &c in s {
+        match c {
+            b'\t' => out.push_str("\\t"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\\' => out.push_str("\\\\"),
+            c if c as char == quote => { out.push('\\'); out.push(quote); }
+            b'\x20'...b'\x7e' => out.push(c as char),
+            // always use three digits so that the next digit is not absorbed
+            _ => out.push_str(&format!("\\{:03}", c)),
+        }
+    }
+    out.push(quote);
+    out
+}
+
+fn name(s: &[u8]) -> String {
+    if Name::from(s).quote_required() {
+        quote(s, '`')
+    } else {
+        String::from_utf8_lossy(s).into_owned()
+    }
+}
+
+struct Printer<'a> {
+    ctx: &'a TypeContext,
+    // if set, unexpressible parts are replaced with `WHATEVER` instead of failing
+    lossy: bool,
+    tvars_seen: Vec<TVar>,
+    rvars_seen: Vec<RVar>,
+    out: String,
+}
+
+impl<'a> Printer<'a> {
+    fn fail(&mut self, reason: Unexpressible) -> Result<()> {
+        if self.lossy {
+            self.out.push_str("WHATEVER");
+            Ok(())
+        } else {
+            Err(reason)
+        }
+    }
+
+    fn ty(&mut self, ty: &Ty) -> Result<()> {
+        if let Some(tag) = ty.tag() {
+            self.tag(tag);
+        }
+
+        let nil = ty.nil();
+        if let T::None = **ty {
+            // nil-derived types have their own representations
+            self.out.push_str("nil");
+        } else {
+            // unions and function signatures cannot be directly followed by postfix operators
+            let parens = (nil != Nil::Silent || ty.tag().is_some()) && self.needs_parens(ty);
+            if parens { self.out.push_str("("); }
+            self.t(ty)?;
+            if parens { self.out.push_str(")"); }
+        }
+
+        match nil {
+            Nil::Silent => {}
+            Nil::Noisy => self.out.push_str("?"),
+            Nil::Absent => self.out.push_str("!"),
+        }
+        Ok(())
+    }
+
+    fn needs_parens(&self, t: &T) -> bool {
+        match *t {
+            T::Functions(ref func) => match **func {
+                Functions::Simple(_) => true,
+                Functions::All => false,
+            },
+            T::Union(ref u) => {
+                let mut count = 0;
+                let _ = u.visit(T_ALL, |_| -> result::Result<(), ()> {
+                    count += 1;
+                    Ok(())
+                });
+                count != 1
+            },
+            T::TVar(tv) => {
+                if let Some(t) = self.ctx.get_tvar_exact_type(tv) {
+                    !self.tvars_seen.contains(&tv) && self.needs_parens(&t)
+                } else {
+                    false
+                }
+            },
+            _ => false,
+        }
+    }
+
+    fn tag(&mut self, tag: Tag) {
+        self.out.push_str("[");
+        self.out.push_str(&name(tag.name().as_bytes()));
+        if let Tag::MakeClass(csid) = tag {
+            let system = format_with_state(self.ctx, |f, st| {
+                st.context.fmt_class_system_name(csid, f, st)
+            });
+            self.out.push_str(&format!("({})", system));
+        }
+        self.out.push_str("] ");
+    }
+
+    fn t(&mut self, t: &T) -> Result<()> {
+        match *t {
+            T::Dynamic(Dyn::User) => self.out.push_str("WHATEVER"),
+            T::Dynamic(Dyn::Oops) => return self.fail(Unexpressible::Error),
+
+            T::All      => self.out.push_str("any"),
+            T::None     => self.out.push_str("nil"),
+            T::Boolean  => self.out.push_str("boolean"),
+            T::True     => self.out.push_str("true"),
+            T::False    => self.out.push_str("false"),
+            T::Thread   => self.out.push_str("thread"),
+            T::UserData => self.out.push_str("userdata"),
+
+            T::Number     => self.out.push_str("number"),
+            T::Integer    => self.out.push_str("integer"),
+            T::Int(v)     => self.out.push_str(&v.to_string()),
+            T::IntRange(lo, hi) => self.out.push_str(&format!("integer({}..{})", lo, hi)),
+            T::String     => self.out.push_str("string"),
+            T::Str(ref s) => self.out.push_str(&quote(s, '"')),
+
+            T::TVar(tv) => {
+                if self.tvars_seen.contains(&tv) {
+                    return self.fail(Unexpressible::Recursive);
+                }
+                if let Some(t) = self.ctx.get_tvar_exact_type(tv) {
+                    self.tvars_seen.push(tv);
+                    let ret = self.ty(&t);
+                    self.tvars_seen.pop();
+                    return ret;
+                } else {
+                    return self.fail(Unexpressible::Unknown);
+                }
+            },
+
+            T::Param(ref p) => self.out.push_str(&name(&p.name)),
+
+            T::Tables(ref tab) => return self.tables(tab),
+            T::Functions(ref func) => return self.functions(func),
+            T::Coroutine(ref co) => {
+                self.out.push_str("thread<");
+                self.ty(&co.yields)?;
+                self.out.push_str(", ");
+                self.ty(&co.resumes)?;
+                self.out.push_str(">");
+            },
+            T::Class(c) => return self.class(c),
+
+            T::Union(ref u) => {
+                let mut members = Vec::new();
+                let _ = u.visit(T_ALL, |t| -> result::Result<(), ()> {
+                    members.push(t);
+                    Ok(())
+                });
+                if members.is_empty() {
+                    self.out.push_str("nil");
+                }
+                for (i, t) in members.iter().enumerate() {
+                    if i > 0 { self.out.push_str("|"); }
+                    let parens = self.needs_parens(t);
+                    if parens { self.out.push_str("("); }
+                    self.t(t)?;
+                    if parens { self.out.push_str(")"); }
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn slot(&mut self, slot: &Slot) -> Result<()> {
+        match slot.flex() {
+            F::Unknown => return self.fail(Unexpressible::Unknown),
+            F::Dynamic(Dyn::User) => {
+                self.out.push_str("WHATEVER");
+                return Ok(());
+            },
+            F::Dynamic(Dyn::Oops) => return self.fail(Unexpressible::Error),
+            F::Const => self.out.push_str("const "),
+            // `Just` is yet to be assigned and `Module` is yet to be initialized,
+            // both of them being mutable
+            F::Just | F::Var | F::Module => {},
+        }
+        let ty = slot.unlift().clone();
+        self.ty(&ty)
+    }
+
+    fn tables(&mut self, tab: &Tables) -> Result<()> {
+        match *tab {
+            Tables::All => self.out.push_str("table"),
+
+            Tables::Fields(ref rvar) => {
+                if self.rvars_seen.contains(rvar) {
+                    return self.fail(Unexpressible::Recursive);
+                }
+
+                let mut fields = Vec::new();
+                let last = self.ctx.list_rvar_fields(rvar.clone(), &mut |k, v| {
+                    fields.push((k.clone(), v.clone()));
+                    Ok(())
+                }).expect("list_rvar_fields exited early while we haven't break");
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                let extensible = last != RVar::empty();
+
+                // the fields should be either all names or all consecutive integers from 1
+                let names = fields.iter().all(|&(ref k, _)| if let Key::Str(_) = *k {
+                    true
+                } else {
+                    false
+                });
+                let tuple = !extensible && fields.iter().enumerate().all(|(i, &(ref k, _))| {
+                    *k == Key::Int(i as i32 + 1)
+                });
+                if !names && !tuple {
+                    return self.fail(Unexpressible::Table);
+                }
+
+                self.rvars_seen.push(rvar.clone());
+                let ret = (|| {
+                    self.out.push_str("{");
+                    for (i, &(ref k, ref v)) in fields.iter().enumerate() {
+                        if i > 0 { self.out.push_str(", "); }
+                        if let Key::Str(ref k) = *k {
+                            self.out.push_str(&name(k));
+                            self.out.push_str(": ");
+                        }
+                        self.slot(v)?;
+                    }
+                    if extensible {
+                        self.out.push_str(if fields.is_empty() { "..." } else { ", ..." });
+                    }
+                    self.out.push_str("}");
+                    Ok(())
+                })();
+                self.rvars_seen.pop();
+                return ret;
+            },
+
+            Tables::Array(ref v) => {
+                self.out.push_str("vector<");
+                self.slot(v)?;
+                self.out.push_str(">");
+            },
+
+            Tables::ArrayN(_) => return self.fail(Unexpressible::ArrayN),
+
+            Tables::Map(ref k, ref v) => {
+                self.out.push_str("map<");
+                self.ty(k)?;
+                self.out.push_str(", ");
+                self.slot(v)?;
+                self.out.push_str(">");
+            },
+        }
+        Ok(())
+    }
+
+    fn functions(&mut self, func: &Functions) -> Result<()> {
+        match *func {
+            Functions::All => {
+                self.out.push_str("function");
+                Ok(())
+            },
+            Functions::Simple(ref func) => self.function(func),
+        }
+    }
+
+    fn function(&mut self, func: &Function) -> Result<()> {
+        self.out.push_str("function");
+        if !func.type_params.is_empty() {
+            self.out.push_str("<");
+            for (i, param) in func.type_params.iter().enumerate() {
+                if i > 0 { self.out.push_str(", "); }
+                self.out.push_str(&name(&param.name));
+            }
+            self.out.push_str(">");
+        }
+
+        // argument names can be only printed when all arguments are named
+        let named = func.argnames.len() == func.args.head.len() &&
+                    func.argnames.iter().all(|name| name.is_some());
+        self.out.push_str("(");
+        for (i, t) in func.args.head.iter().enumerate() {
+            if i > 0 { self.out.push_str(", "); }
+            if named {
+                let argname = func.argnames[i].as_ref().unwrap();
+                self.out.push_str(&name(&argname.base));
+                self.out.push_str(": ");
+            }
+            self.ty(t)?;
+        }
+        if let Some(ref t) = func.args.tail {
+            if !func.args.head.is_empty() { self.out.push_str(", "); }
+            self.ty(t)?;
+            self.ellipsis();
+        }
+        self.out.push_str(")");
+
+        match func.returns {
+            Some(ref returns) => match (returns.head.len(), returns.tail.is_some()) {
+                (0, false) => Ok(()),
+                (1, false) => {
+                    self.out.push_str(" --> ");
+                    self.ty(&returns.head[0])
+                },
+                (_, _) => {
+                    self.out.push_str(" --> ");
+                    self.tyseq(returns)
+                },
+            },
+            None => {
+                self.out.push_str(" --> !");
+                Ok(())
+            },
+        }
+    }
+
+    fn ellipsis(&mut self) {
+        // `42...` would be read as `42.` followed by `..`
+        if self.out.ends_with(|c: char| c.is_digit(10)) {
+            self.out.push_str(" ");
+        }
+        self.out.push_str("...");
+    }
+
+    fn tyseq(&mut self, seq: &TySeq) -> Result<()> {
+        self.out.push_str("(");
+        for (i, t) in seq.head.iter().enumerate() {
+            if i > 0 { self.out.push_str(", "); }
+            self.ty(t)?;
+        }
+        if let Some(ref t) = seq.tail {
+            if !seq.head.is_empty() { self.out.push_str(", "); }
+            self.ty(t)?;
+            self.ellipsis();
+        }
+        self.out.push_str(")");
+        Ok(())
+    }
+
+    fn class(&mut self, c: Class) -> Result<()> {
+        match c {
+            Class::Prototype(_) => self.fail(Unexpressible::Class),
+            Class::Instance(cid) => {
+                // specialized generic classes are printed as `Name<T, ...>`
+                let (generic, args) = match self.ctx.class_type_args(cid) {
+                    Some((generic, args)) if generic != cid => (generic, args),
+                    _ => (cid, Vec::new()),
+                };
+
+                let name = format_with_state(self.ctx, |f, st| {
+                    st.context.fmt_class_name(generic, f, st)
+                });
+                if !is_single_name(&name) {
+                    return self.fail(Unexpressible::Class);
+                }
+                self.out.push_str(&name);
+                if !args.is_empty() {
+                    self.out.push('<');
+                    for (i, arg) in args.iter().enumerate() {
+                        if i > 0 { self.out.push_str(", "); }
+                        self.ty(arg)?;
+                    }
+                    self.out.push('>');
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl Ty {
+    /// Prints the type in the type syntax, which can be parsed back to an equivalent type.
+    ///
+    /// Unlike `Display` this ignores any display hints and hooks, and does not depend on
+    /// the locale. Fails when the type (or any part of it) has no such representation.
+    pub fn to_kind_string(&self, ctx: &TypeContext) -> Result<String> {
+        let mut printer = Printer {
+            ctx: ctx, lossy: false, tvars_seen: Vec::new(), rvars_seen: Vec::new(),
+            out: String::new(),
+        };
+        printer.ty(self)?;
+        Ok(printer.out)
+    }
+
+    /// Same to `to_kind_string` but any unexpressible part is replaced with `WHATEVER`.
+    ///
+    /// The output is always parsable, but its type may be no longer equivalent to the original.
+    pub fn to_lossy_kind_string(&self, ctx: &TypeContext) -> String {
+        let mut printer = Printer {
+            ctx: ctx, lossy: true, tvars_seen: Vec::new(), rvars_seen: Vec::new(),
+            out: String::new(),
+        };
+        printer.ty(self).expect("lossy printing should not fail");
+        printer.out
+    }
+}
+
+#[test]
+fn test_kind_string_roundtrip() {
+    use std::borrow::Cow;
+    use kailua_env::{Span, Spanned, WithLoc};
+    use kailua_diag::{self, Kind, Localize, NoReport, Report, Stop};
+    use kailua_syntax::parse_kind;
+    use env::{Types, DummyClassProvider};
+    use ty::{TypeResolver, ClassSystemId, Lattice};
+
+    struct Resolver {
+        types: Types,
+    }
+
+    impl Report for Resolver {
+        fn message_locale(&self) -> Locale {
+            Locale::dummy()
+        }
+        fn add_span(&self, kind: Kind, span: Span, msg: &Localize) -> kailua_diag::Result<()> {
+            NoReport.add_span(kind, span, msg)
+        }
+    }
+
+    impl TypeResolver for Resolver {
+        fn context(&self) -> &TypeContext {
+            &self.types
+        }
+        fn context_mut(&mut self) -> &mut TypeContext {
+            &mut self.types
+        }
+        fn ty_from_name(&self, _name: &Spanned<Name>) -> kailua_diag::Result<Ty> {
+            Err(Stop)
+        }
+        fn class_system_from_name(&self, _name: &Spanned<Name>)
+            -> kailua_diag::Result<Option<ClassSystemId>>
+        {
+            Err(Stop)
+        }
+    }
+
+    fn parse(resolv: &mut Resolver, s: &str) -> Option<Ty> {
+        let kind = parse_kind(s).unwrap_or_else(|e| panic!("cannot parse {:?}: {:?}", s, e));
+        Ty::from_kind(&kind, resolv).ok()
+    }
+
+    fn roundtrip(resolv: &mut Resolver, s: &str) -> Option<String> {
+        // some combinations are invalid (e.g. `{} | {}`), they are simply ignored
+        let ty = match parse(resolv, s) {
+            Some(ty) => ty,
+            None => return None,
+        };
+        let printed = ty.to_kind_string(&resolv.types)
+                        .unwrap_or_else(|e| panic!("cannot print {:?}: {}", s, e));
+        let reparsed = parse(resolv, &printed)
+                           .unwrap_or_else(|| panic!("cannot resolve {:?} from {:?}", printed, s));
+        assert!(ty.assert_sub(&reparsed, &mut resolv.types).is_ok() &&
+                reparsed.assert_sub(&ty, &mut resolv.types).is_ok(),
+                "{:?} printed as a non-equivalent type {:?}", s, printed);
+        assert_eq!(reparsed.to_kind_string(&resolv.types).as_ref(), Ok(&printed),
+                   "{:?} printed as a non-canonical type", s);
+        Some(printed)
+    }
+
+    let mut resolv = Resolver { types: Types::new(Locale::dummy(), Box::new(DummyClassProvider)) };
+
+    // canonical forms
+    assert_eq!(roundtrip(&mut resolv, "nil").unwrap(), "nil");
+    assert_eq!(roundtrip(&mut resolv, "nil?").unwrap(), "nil?");
+    assert_eq!(roundtrip(&mut resolv, "-3 | 4 | 2").unwrap(), "-3|2|4");
+    assert_eq!(roundtrip(&mut resolv, "true | boolean").unwrap(), "boolean");
+    assert_eq!(roundtrip(&mut resolv, "'a\\n`b\"\\2001'").unwrap(), "\"a\\n`b\\\"\\2001\"");
+    assert_eq!(roundtrip(&mut resolv, "(string | integer)?").unwrap(), "(integer|string)?");
+    assert_eq!(roundtrip(&mut resolv, "{y: string, x: const integer, `a\\`b`: any}").unwrap(),
+               "{`a\\`b`: any, x: const integer, y: string}");
+    assert_eq!(roundtrip(&mut resolv, "{integer, {...}, {}}").unwrap(), "{integer, {...}, {}}");
+    assert_eq!(roundtrip(&mut resolv, "function() --> ()").unwrap(), "function()");
+    assert_eq!(roundtrip(&mut resolv, "(function(a: integer) --> (string...)) | string").unwrap(),
+               "string|(function(a: integer) --> (string...))");
+    assert_eq!(roundtrip(&mut resolv, "function<T>(T, vector<T>) --> !").unwrap(),
+               "function<T>(T, vector<T>) --> !");
+    assert_eq!(roundtrip(&mut resolv, "[string_meta] (function())?").unwrap(),
+               "[string_meta] (function())?");
+
+    // argument names are only kept when all arguments are named
+    let partial = Function {
+        type_params: Vec::new(),
+        args: TySeq { head: vec![Ty::new(T::Integer), Ty::new(T::String)], tail: None },
+        argnames: vec![Some(Name::from(&b"x"[..]).without_loc())],
+        returns: Some(TySeq::new()),
+    };
+    let partial = Ty::new(T::func(partial));
+    assert_eq!(partial.to_kind_string(&resolv.types).unwrap(), "function(integer, string)");
+
+    // types without any representation
+    let arrayn = Tables::ArrayN(Slot::new(F::Var, Ty::new(T::All)));
+    let arrayn = Slot::just(Ty::new(T::Tables(Cow::Owned(arrayn))));
+    let arrayn = Tables::Map(Ty::new(T::String), arrayn);
+    let arrayn = Ty::new(T::Tables(Cow::Owned(arrayn)));
+    assert_eq!(arrayn.to_kind_string(&resolv.types), Err(Unexpressible::ArrayN));
+    assert_eq!(arrayn.to_lossy_kind_string(&resolv.types), "map<string, WHATEVER>");
+    let tvar = Ty::new(T::TVar(resolv.types.gen_tvar())).or_nil(Nil::Noisy);
+    assert_eq!(tvar.to_kind_string(&resolv.types), Err(Unexpressible::Unknown));
+    assert_eq!(tvar.to_lossy_kind_string(&resolv.types), "WHATEVER?");
+    assert_eq!(Ty::dummy().to_kind_string(&resolv.types), Err(Unexpressible::Error));
+
+    // every combination of simple types should survive the roundtrip
+    let atoms = [
+        "nil", "WHATEVER", "any", "boolean", "true", "integer", "number", "-42", "'x'", "string",
+        "thread", "userdata", "table", "function", "{}", "{...}", "integer?", "string!",
+        "vector<const number>", "map<string, boolean?>", "function(string) --> integer",
+    ];
+    let templates = [
+        "vector<%>", "vector<const %>", "map<%, %>", "{x: %, `y z`: const %}", "{%, %}",
+        "{x: %, ...}", "function(%, % ...) --> %", "function(p: %, q: %) --> (%, %)",
+        "(%) | (%)", "(%)?", "[string_meta] %", "function<T>(T, %) --> (%, T)",
+    ];
+    let mut checked = 0;
+    for template in &templates {
+        for a in &atoms {
+            for b in &atoms {
+                let mut parts = template.split('%');
+                let mut s = parts.next().unwrap().to_owned();
+                let args = [a, b];
+                for (i, part) in parts.enumerate() {
+                    s.push_str(args[i % 2]);
+                    s.push_str(part);
+                }
+                if roundtrip(&mut resolv, &s).is_some() {
+                    checked += 1;
+                }
+            }
+        }
+    }
+    // only a handful of unions should be invalid
+    assert!(checked * 10 > templates.len() * atoms.len() * atoms.len() * 9);
+}
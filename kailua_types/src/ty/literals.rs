@@ -14,6 +14,9 @@ pub enum Numbers {
     /// Multiple integer literals.
     Some(BTreeSet<i32>),
 
+    /// Integers between two inclusive bounds. The lower bound is always less than the upper bound.
+    Range(i32, i32),
+
     /// Any integer.
     Int,
 
@@ -21,6 +24,41 @@ pub enum Numbers {
     All,
 }
 
+impl Numbers {
+    /// Returns a range of integers, or a single integer literal if both bounds are same.
+    pub fn range(lo: i32, hi: i32) -> Numbers {
+        assert!(lo <= hi, "Numbers::range called with an empty range");
+        if lo == hi { Numbers::One(lo) } else { Numbers::Range(lo, hi) }
+    }
+
+    /// Returns the smallest and largest integers if `self` is finite.
+    fn bounds(&self) -> Option<(i32, i32)> {
+        match *self {
+            Numbers::One(v) => Some((v, v)),
+            Numbers::Some(ref set) => {
+                let lo = *set.iter().next()?;
+                let hi = *set.iter().next_back()?;
+                Some((lo, hi))
+            }
+            Numbers::Range(lo, hi) => Some((lo, hi)),
+            Numbers::Int | Numbers::All => None,
+        }
+    }
+
+    /// Returns true if `self` contains every integer in given inclusive range.
+    fn contains_range(&self, lo: i32, hi: i32) -> bool {
+        match *self {
+            Numbers::One(v) => lo == v && hi == v,
+            // a range is never larger than the set if every integer in it is in the set
+            Numbers::Some(ref set) => {
+                (hi as i64 - lo as i64) < set.len() as i64 && (lo..hi+1).all(|v| set.contains(&v))
+            }
+            Numbers::Range(l, h) => l <= lo && hi <= h,
+            Numbers::Int | Numbers::All => true,
+        }
+    }
+}
+
 impl Union for Numbers {
     type Output = Numbers;
 
@@ -33,6 +71,21 @@ impl Union for Numbers {
             (&Numbers::Int, _) => Ok(Numbers::Int),
             (_, &Numbers::Int) => Ok(Numbers::Int),
 
+            // the explicit union involving ranges is approximated to the smallest range
+            // containing both operands, since we cannot represent disjoint ranges
+            (&Numbers::Range(..), _) | (_, &Numbers::Range(..)) => {
+                let (a, b) = (self.bounds().unwrap(), other.bounds().unwrap());
+                if self.contains_range(b.0, b.1) {
+                    Ok(self.clone())
+                } else if other.contains_range(a.0, a.1) {
+                    Ok(other.clone())
+                } else if explicit {
+                    Ok(Numbers::range(a.0.min(b.0), a.1.max(b.1)))
+                } else {
+                    Ok(Numbers::Int)
+                }
+            },
+
             (&Numbers::Some(ref a), &Numbers::Some(ref b)) => {
                 if explicit {
                     let mut ab = a.clone();
@@ -89,6 +142,12 @@ impl Lattice for Numbers {
     fn assert_sub(&self, other: &Self, ctx: &mut TypeContext) -> TypeResult<()> {
         let ok = match (self, other) {
             (&Numbers::One(a), &Numbers::One(b)) => a == b,
+            (&Numbers::One(a), &Numbers::Range(lo, hi)) => lo <= a && a <= hi,
+            (&Numbers::Some(ref a), &Numbers::Range(lo, hi)) => {
+                a.iter().all(|&v| lo <= v && v <= hi)
+            },
+            (&Numbers::Range(lo, hi), _) => other.contains_range(lo, hi),
+            (_, &Numbers::Range(..)) => false,
             (&Numbers::One(a), &Numbers::Some(ref b)) => b.contains(&a),
             (&Numbers::Some(ref a), &Numbers::One(b)) => a.len() == 1 && a.contains(&b),
             (&Numbers::Some(ref a), &Numbers::Some(ref b)) => a.is_subset(b),
@@ -130,6 +189,9 @@ impl PartialEq for Numbers {
             (&Numbers::Some(ref a), &Numbers::One(b)) => a.len() == 1 && a.contains(&b),
             (&Numbers::One(a), &Numbers::Some(ref b)) => b.len() == 1 && b.contains(&a),
             (&Numbers::One(a), &Numbers::One(b)) => a == b,
+            (&Numbers::Range(lo, hi), ref other) | (ref other, &Numbers::Range(lo, hi)) => {
+                other.contains_range(lo, hi) && other.bounds() == Some((lo, hi))
+            },
             (_, _) => false,
         }
     }
@@ -155,6 +217,7 @@ impl fmt::Debug for Numbers {
                 }
                 write!(f, ")")
             }
+            Numbers::Range(lo, hi) => write!(f, "integer({}..{})", lo, hi),
             Numbers::One(v) => write!(f, "{:?}", v),
         }
     }
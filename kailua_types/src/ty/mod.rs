@@ -5,27 +5,51 @@ use std::result;
 use diag::{TypeReport, TypeResult};
 use kailua_env::Spanned;
 use kailua_diag::{Result, Locale, Report};
-use kailua_syntax::Name;
+use kailua_syntax::{Str, Name};
 
-pub use self::display::{Display, Displayed, DisplayState, DisplayName};
+pub use self::display::{Display, Displayed, DisplayState, DisplayName, DisplayHook};
 pub use self::literals::{Numbers, Strings};
 pub use self::tables::{Key, Tables};
-pub use self::functions::{Function, Functions};
+pub use self::functions::{Function, Functions, Substitution};
+pub use self::coroutine::Coroutine;
 pub use self::union::Unioned;
 pub use self::value::{Dyn, Nil, T, Ty};
 pub use self::slot::{F, S, Slot};
 pub use self::seq::{SeqIter, TySeq, SpannedTySeq, SlotSeq, SpannedSlotSeq};
 pub use self::tag::Tag;
+pub use self::kind::{Unexpressible, quote};
 
 mod display;
 mod literals;
 mod tables;
 mod functions;
+mod coroutine;
 mod union;
 mod value;
 mod slot;
 mod seq;
 mod tag;
+mod kind;
+
+/// The default maximum number of literals in a union of numbers or strings.
+///
+/// See `TypeContext::literal_union_limit` for details.
+pub const DEFAULT_LITERAL_UNION_LIMIT: usize = 128;
+
+/// Limits on the complexity of types, exceeding which makes types widened.
+///
+/// Only the union size is enforced by the types themselves; others are enforced by the checker.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ComplexityLimit {
+    /// The number of literals in a union (see `TypeContext::literal_union_limit`).
+    UnionSize,
+
+    /// The depth of nested table constructors.
+    TableDepth,
+
+    /// The number of type constraints accumulated so far.
+    Constraints,
+}
 
 /// Anonymous, unifiable type variables generated by `TypeContext`.
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
@@ -38,6 +62,24 @@ impl fmt::Debug for TVar {
     }
 }
 
+/// Rigid type parameters of generic functions, generated by `TypeContext`.
+///
+/// Unlike type variables, a type parameter never gets unified and is only equal to itself.
+/// It gets replaced with an actual type whenever the generic function is called.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct TParam {
+    pub id: u32,
+    pub name: Name,
+}
+
+/// In the debugging output the type parameter is denoted
+/// <code>&lt;<i>name</i>#<i>id</i>&gt;</code>.
+impl fmt::Debug for TParam {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{:+}#{}>", self.name, self.id)
+    }
+}
+
 /// Row variables generated by `TypeContext`.
 ///
 /// A row variable #0 (`RVar::empty()`) denotes a special, inextensible "empty" row variable.
@@ -87,6 +129,30 @@ impl fmt::Debug for ClassSystemId {
     }
 }
 
+/// Identifiers for type tags registered by the embedder (`Tag::Custom`).
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CustomTagId(pub u32);
+
+/// In the debugging output the custom tag identifier is denoted <code>#<i>id</i></code>.
+impl fmt::Debug for CustomTagId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Identifiers for strings given to tags, e.g. a message in `[deprecated("...")]`.
+///
+/// Tags are copyable and cannot hold strings directly, so strings are kept in the type context.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TagStrId(pub u32);
+
+/// In the debugging output the tag string identifier is denoted <code>$<i>id</i></code>.
+impl fmt::Debug for TagStrId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${}", self.0)
+    }
+}
+
 /// Identifiers for nominal types (currently only used for instantiable classes).
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ClassId(pub ClassSystemId, pub u32);
@@ -119,7 +185,7 @@ impl Class {
 
 impl Display for Class {
     fn fmt_displayed(&self, f: &mut fmt::Formatter, st: &DisplayState) -> fmt::Result {
-        let (pre, cid, post) = match (*self, &st.locale[..]) {
+        let (pre, cid, post) = match (*self, &st.locale.resolve(&["ko"])[..]) {
             (Class::Prototype(cid), "ko") => ("<", cid, " 프로토타입>"),
             (Class::Prototype(cid), _)    => ("<prototype for ", cid, ">"),
             (Class::Instance(cid),  _)    => ("", cid, ""),
@@ -187,6 +253,24 @@ pub trait TypeContext {
     /// Generates a new fresh type variable.
     fn gen_tvar(&mut self) -> TVar;
 
+    /// Records that an implicit union has widened the type, for the inference statistics.
+    fn record_widening(&mut self) {}
+
+    /// Records that a type has been widened due to the complexity limit.
+    fn record_limit_exceeded(&mut self, _limit: ComplexityLimit) {}
+
+    /// Returns the maximum number of literals in a union of numbers or strings.
+    ///
+    /// Unions with more literals are widened to `integer` or `string` respectively,
+    /// as they are costly to handle and display while rarely being useful.
+    /// Returns `DEFAULT_LITERAL_UNION_LIMIT` by default.
+    fn literal_union_limit(&self) -> usize {
+        DEFAULT_LITERAL_UNION_LIMIT
+    }
+
+    /// Generates a new type parameter with given name.
+    fn gen_tparam(&mut self, name: Name) -> TParam;
+
     /// Copies a type variable so that a new variable has the same constraints to the original
     /// but is no longer connected to the original.
     ///
@@ -262,6 +346,14 @@ pub trait TypeContext {
         fields // do not return the last rvar, to which operations are no-ops
     }
 
+    /// Prints given type to the formatter with a registered display hook, if any.
+    ///
+    /// Returns `None` when no hook handles the type, which is the default.
+    fn fmt_hooked_type(&self, _ty: &Ty, _f: &mut fmt::Formatter,
+                       _st: &DisplayState) -> Option<fmt::Result> {
+        None
+    }
+
     /// Prints a type name for given nominal identifier to the formatter.
     fn fmt_class_name(&self, cid: ClassId, f: &mut fmt::Formatter,
                       st: &DisplayState) -> fmt::Result;
@@ -270,9 +362,38 @@ pub trait TypeContext {
     fn fmt_class_system_name(&self, csid: ClassSystemId, f: &mut fmt::Formatter,
                              st: &DisplayState) -> fmt::Result;
 
+    /// Stores a string given to a tag and returns its identifier.
+    fn intern_tag_str(&mut self, s: Str) -> TagStrId;
+
+    /// Returns a string previously stored with `intern_tag_str`.
+    fn get_tag_str(&self, id: TagStrId) -> Option<&Str>;
+
+    /// Resolves an attribute name to a custom tag registered by the embedder, if any.
+    ///
+    /// Returns `None` by default.
+    fn custom_tag_from_name(&self, _name: &[u8]) -> Option<CustomTagId> {
+        None
+    }
+
+    /// Prints the attribute name for given custom tag identifier to the formatter.
+    fn fmt_custom_tag_name(&self, id: CustomTagId, f: &mut fmt::Formatter,
+                           _st: &DisplayState) -> fmt::Result {
+        write!(f, "<custom tag {:?}>", id)
+    }
+
     /// Returns true if given nominal instance type is a subtype of another nominal instance type.
     fn is_subclass_of(&self, lhs: ClassId, rhs: ClassId) -> bool;
 
+    /// Returns the generic nominal identifier and type arguments for given identifier if any.
+    ///
+    /// For the generic identifier itself type arguments are its own type parameters.
+    fn class_type_args(&self, cid: ClassId) -> Option<(ClassId, Vec<Ty>)>;
+
+    /// Returns a nominal identifier for given generic identifier specialized with type arguments.
+    ///
+    /// Returns `None` if the identifier is not generic or the number of arguments is incorrect.
+    fn specialize_class(&mut self, cid: ClassId, args: Vec<Ty>) -> Option<ClassId>;
+
     /// Returns a pair of type flags that is an exact lower and upper bound for that type.
     ///
     /// Used as an approximate type bound testing like arithmetics.
@@ -395,6 +516,9 @@ impl TypeContext for NoTypeContext {
     fn gen_tvar(&mut self) -> TVar {
         panic!("gen_tvar is not supposed to be called here");
     }
+    fn gen_tparam(&mut self, name: Name) -> TParam {
+        panic!("gen_tparam({:+}) is not supposed to be called here", name);
+    }
     fn copy_tvar(&mut self, tvar: TVar) -> TVar {
         panic!("copy_tvar({:?}) is not supposed to be called here", tvar);
     }
@@ -444,6 +568,13 @@ impl TypeContext for NoTypeContext {
         panic!("list_rvar_fields({:?}, ...) is not supposed to be called here", rvar)
     }
 
+    fn intern_tag_str(&mut self, s: Str) -> TagStrId {
+        panic!("intern_tag_str({:?}) is not supposed to be called here", s);
+    }
+    fn get_tag_str(&self, id: TagStrId) -> Option<&Str> {
+        panic!("get_tag_str({:?}) is not supposed to be called here", id);
+    }
+
     fn fmt_class_name(&self, cid: ClassId, _f: &mut fmt::Formatter,
                       _st: &DisplayState) -> fmt::Result {
         panic!("fmt_class_name({:?}, ...) is not supposed to be called here", cid);
@@ -455,6 +586,12 @@ impl TypeContext for NoTypeContext {
     fn is_subclass_of(&self, lhs: ClassId, rhs: ClassId) -> bool {
         panic!("is_subclass_of({:?}, {:?}) is not supposed to be called here", lhs, rhs);
     }
+    fn class_type_args(&self, cid: ClassId) -> Option<(ClassId, Vec<Ty>)> {
+        panic!("class_type_args({:?}) is not supposed to be called here", cid);
+    }
+    fn specialize_class(&mut self, cid: ClassId, args: Vec<Ty>) -> Option<ClassId> {
+        panic!("specialize_class({:?}, {:?}) is not supposed to be called here", cid, args);
+    }
 }
 
 impl Lattice for TVar {
@@ -9,7 +9,7 @@ use parking_lot::{RwLock, RwLockReadGuard};
 use kailua_env::{Span, Spanned};
 use kailua_syntax::ast::{M, MM};
 use diag::Origin;
-use super::{Dyn, Nil, T, Ty, TypeContext, Lattice, Union, Dummy, TVar, Tag};
+use super::{Dyn, Nil, T, Ty, TypeContext, Lattice, Union, Dummy, TVar, Tag, Substitution};
 use super::{TypeReport, TypeResult};
 use super::display::{Display, DisplayState, DisplayName};
 use super::flags::Flags;
@@ -250,6 +250,10 @@ impl S {
         self.map_ty(|t| t.generalize(ctx))
     }
 
+    pub fn substitute(self, subst: &mut Substitution, ctx: &mut TypeContext) -> S {
+        self.map_ty(|t| t.substitute(subst, ctx))
+    }
+
     fn resolve_unknown_flex(&self, other: &S) -> (Bits, Bits) {
         // if one flex is unknown, use the other's flex (this should be atomic)
         let mut lbits = self.bits();
@@ -369,7 +373,7 @@ impl Display for S {
             return write!(f, "<...>");
         }
 
-        let (write_ty, prefix) = match (self.flex(), &st.locale[..]) {
+        let (write_ty, prefix) = match (self.flex(), &st.locale.resolve(&["ko"])[..]) {
             (F::Unknown, "ko") => (false, "<초기화되지 않음>"),
             (F::Unknown, _)    => (false, "<not initialized>"),
 
@@ -541,6 +545,14 @@ impl Slot {
         Ok(())
     }
 
+    /// Replaces the type of the slot in place and returns the previous type.
+    ///
+    /// This is used for the flow-sensitive narrowing,
+    /// which may restore the returned type afterwards, and for marking weak tables.
+    pub fn replace_ty(&self, ty: Ty) -> Ty {
+        mem::replace(&mut *self.0.ty.write(), ty)
+    }
+
     // following methods are direct analogues to value type's ones, whenever applicable
 
     pub fn flex(&self) -> F { self.0.flex() }
@@ -576,6 +588,10 @@ impl Slot {
         Slot::from((*self.0).clone().generalize(ctx))
     }
 
+    pub fn substitute(&self, subst: &mut Substitution, ctx: &mut TypeContext) -> Slot {
+        Slot::from((*self.0).clone().substitute(subst, ctx))
+    }
+
     // should *not* create a new slot! (the resulting slot is not a different type,
     // but a same type with a display hint; the hint *should* be global.)
     pub fn set_display(self, disp: DisplayName) -> Slot {
@@ -5,7 +5,7 @@ use std::collections::BTreeMap;
 
 use kailua_syntax::Str;
 use diag::{Origin, TypeReport, TypeResult};
-use super::{Display, DisplayState, T, Ty, Slot, TypeContext, Union, Lattice, RVar};
+use super::{Display, DisplayState, T, Ty, Slot, TypeContext, Union, Lattice, RVar, Substitution};
 
 /// A key allowed in the row variable.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -116,6 +116,20 @@ impl Tables {
         }
     }
 
+    pub fn substitute(self, subst: &mut Substitution, ctx: &mut TypeContext) -> Tables {
+        match self {
+            Tables::Fields(r) => Tables::Fields(subst.substitute_rvar(r, ctx)),
+            Tables::Array(v) => Tables::Array(v.substitute(subst, ctx)),
+            Tables::ArrayN(v) => Tables::ArrayN(v.substitute(subst, ctx)),
+            Tables::Map(k, v) => {
+                let k = k.substitute(subst, ctx);
+                let v = v.substitute(subst, ctx);
+                Tables::Map(k, v)
+            },
+            Tables::All => Tables::All,
+        }
+    }
+
     fn fmt_generic<WriteTy, WriteSlot>(&self, f: &mut fmt::Formatter,
                                        st: Option<&DisplayState>,
                                        mut write_ty: WriteTy,
@@ -129,7 +143,7 @@ impl Tables {
             Tables::Fields(ref rvar) => {
                 if let Some(st) = st {
                     if !st.can_recurse() {
-                        return match &st.locale[..] {
+                        return match &st.locale.resolve(&["ko"])[..] {
                             "ko" => write!(f, "<생략>"),
                             _    => write!(f, "<omitted>"),
                         };
@@ -165,12 +179,21 @@ impl Tables {
                     write!(f, "{{")?;
                     let mut first = true;
 
+                    // fields past the maximum width (if any) are counted as omitted
+                    let maxwidth = st.and_then(|st| st.max_width());
+                    let mut printed = 0;
+
                     // try consecutive initial integers first
                     let mut nextlen = 1;
                     while let Some(t) = fields.get(&Key::Int(nextlen)) {
+                        nextlen += 1;
+                        if maxwidth.map_or(false, |w| printed >= w) {
+                            morefields += 1;
+                            continue;
+                        }
+                        printed += 1;
                         if first { first = false; } else { write!(f, ", ")?; }
                         write_slot(t, f, false)?;
-                        nextlen += 1;
                     }
 
                     // print other keys
@@ -179,6 +202,11 @@ impl Tables {
                             Key::Int(v) if 1 <= v && v < nextlen => continue, // strip duplicates
                             _ => {}
                         }
+                        if maxwidth.map_or(false, |w| printed >= w) {
+                            morefields += 1;
+                            continue;
+                        }
+                        printed += 1;
                         if first { first = false; } else { write!(f, ", ")?; }
                         write!(f, "{}: ", name)?;
                         write_slot(t, f, false)?;
@@ -2,7 +2,7 @@ use std::fmt;
 use kailua_env::Spanned;
 use kailua_diag::{Result, Reporter};
 use kailua_syntax::ast::{Attr, AttrValue};
-use super::{Display, DisplayState, TypeResolver, ClassSystemId};
+use super::{Display, DisplayState, TypeResolver, ClassSystemId, CustomTagId, TagStrId};
 use message as m;
 
 /// A type tag for giving a type special meanings.
@@ -70,24 +70,29 @@ pub enum Tag {
     /// `table`
     ///
     /// A table mirroring the global environment.
-    //
-    // XXX not yet implemented
+    ///
+    /// Indexing this table with a string literal is same to accessing the global variable
+    /// with that name, so `_G.foo = 42` creates (or assigns to) the global variable `foo`.
+    /// Other keys are not (yet) supported and the table behaves as an ordinary table for them.
     GlobalEnv,
 
     /// `function(...) -> (...)`
     ///
     /// Calling this function will alter the global environment in unspecified way,
     /// so it is no longer assumed to be known after the call.
-    //
-    // XXX not yet implemented
+    ///
+    /// Global variables read after the call are dynamic unless they have been
+    /// assigned again since then or declared with `--# assume`.
     GlobalEval,
 
     /// `function(string, ...) -> (...)`
     ///
     /// Calling this function will enable the "module" mode in Lua 5.1.
-    //
-    // XXX not yet implemented
-    // XXX probably requires ModuleEnv tag
+    ///
+    /// In this mode global variables are read from and written to the module table,
+    /// which is returned from the chunk unless it returns explicitly. Other global variables
+    /// are visible only when `package.seeall` is given as an option.
+    /// Only supported at the top level of the chunk.
     BecomeModule,
 
     /// `var string`
@@ -106,6 +111,76 @@ pub enum Tag {
     /// there is no other valid way to get a table with such a type.
     StringMeta,
 
+    /// `function(table, table?) -> table`
+    ///
+    /// Sets the metatable of a record. The metatable is tracked per record,
+    /// and when the metatable has an `__index` field of a record type,
+    /// fields missing from the record are looked up from that `__index` record
+    /// (and so on, following metatables of `__index` records as well).
+    /// Returns the first argument with its type intact.
+    SetMetatable,
+
+    /// `table`
+    ///
+    /// A table with weak keys or values, i.e. its metatable has a `__mode` field.
+    /// Entries can be collected at any time, so reading a value always results in `T?`.
+    /// `SetMetatable` gives this tag to the table when the metatable has such a field.
+    Weak,
+
+    /// `function(any) -> table`
+    ///
+    /// Returns the metatable previously set to a record via `SetMetatable` if any.
+    GetMetatable,
+
+    /// `function(integer|'#', any...) -> (any...)`
+    ///
+    /// `select('#', ...)` returns an `integer`, and `select(n, ...)` with an integer literal `n`
+    /// returns the types of arguments from the `n`-th variadic argument,
+    /// so that the type of typed varargs (`...: T`) is preserved.
+    /// Other calls are checked against the function signature.
+    Select,
+
+    /// `function(function, any...) -> (boolean, any...)`
+    ///
+    /// Calls the first argument with remaining arguments and catches any error.
+    /// When the function signature is known, the return types are `boolean`
+    /// followed by the function's return types (the first one unioned with `string`,
+    /// the error message), and they are narrowed by checking the boolean:
+    /// `local ok, res = pcall(f); if ok then ... end` has `res` of the function's return type
+    /// inside the branch and `string` in the other branch.
+    Pcall,
+
+    /// `function(function, function, any...) -> (boolean, any...)`
+    ///
+    /// Same to `Pcall`, except that the error message is passed to the second argument
+    /// and its first return type is used in place of `string`.
+    Xpcall,
+
+    /// `function(function) -> thread`
+    ///
+    /// Creates a coroutine from a function. When the values yielded by the function
+    /// (via `CoroutineYield`) or its parameter are known, the result is `thread<Y, R>`
+    /// where `Y` is the first yielded or returned type and `R` is the first parameter type.
+    CoroutineCreate,
+
+    /// `function(function) -> function`
+    ///
+    /// Same to `CoroutineCreate`, but the result is a function `function(R) --> Y`
+    /// which resumes the coroutine on each call.
+    CoroutineWrap,
+
+    /// `function(thread, any...) -> (boolean, any...)`
+    ///
+    /// When the first argument is `thread<Y, R>`, the second argument is checked against `R`
+    /// and the second return type becomes `Y`.
+    CoroutineResume,
+
+    /// `function(any...) -> (any...)`
+    ///
+    /// The type of the first argument is recorded as a yielded type of the enclosing function,
+    /// to be used by `CoroutineCreate` and `CoroutineWrap`.
+    CoroutineYield,
+
     /// `function(<class prototype type>?) -> <class prototype type>`
     ///
     /// A function that makes a class prototype. If the argument is given, it should be
@@ -117,6 +192,45 @@ pub enum Tag {
     /// There may be additional behaviors depending on the class system used.
     MakeClass(ClassSystemId),
 
+    /// `<any class prototype field>`
+    ///
+    /// A prototype field (normally a method) that should be defined by subclasses.
+    /// The field is usable as declared, but the class cannot be instantiated via `new`
+    /// until all abstract fields are overridden in that class or its parents.
+    Abstract,
+
+    /// `<any type>`
+    ///
+    /// A deprecated function, field or module, optionally with a message
+    /// (e.g. `[deprecated("use bar instead")]`) which is given as a string in the context.
+    /// Every use of the value is reported with the severity of the `deprecated` lint.
+    Deprecated(Option<TagStrId>),
+
+    /// `function(...) -> (...)`
+    ///
+    /// A function whose return values should not be ignored (e.g. `io.open`).
+    /// Calling it as a statement is reported with the severity of the `discarded-result` lint.
+    NoDiscard,
+
+    /// `<class instance type>`
+    ///
+    /// The type named `self` inside methods of a class, referring to the class instance.
+    /// When a method is called, the type with this tag in the return type is replaced
+    /// with the actual type of the receiver (e.g. an instance of a subclass),
+    /// so that methods returning `self` keep the receiver type.
+    ///
+    /// This tag cannot be given as an attribute; it is only given by the checker.
+    SelfType,
+
+    /// `<any type>`
+    ///
+    /// A tag registered by the embedder (`kailua_check::env::Context::add_custom_tag`).
+    /// Its behavior is defined by callbacks invoked when a function with the tag is called
+    /// and when a variable or field with the tag is assigned.
+    /// Built-in attribute names take precedence over custom tags.
+    /// As with other tags working via assignment, a slot with this tag accepts untagged values.
+    Custom(CustomTagId),
+
     /// `function() -> any`
     ///
     /// Issues a fresh type variable for each use. The return type is ignored.
@@ -175,6 +289,37 @@ impl Tag {
             b"package_path"  => no_values(resolv, Tag::PackagePath),
             b"package_cpath" => no_values(resolv, Tag::PackageCpath),
             b"string_meta"   => no_values(resolv, Tag::StringMeta),
+            b"setmetatable"  => no_values(resolv, Tag::SetMetatable),
+            b"getmetatable"  => no_values(resolv, Tag::GetMetatable),
+            b"weak"          => no_values(resolv, Tag::Weak),
+            b"select"        => no_values(resolv, Tag::Select),
+            b"pcall"         => no_values(resolv, Tag::Pcall),
+            b"xpcall"        => no_values(resolv, Tag::Xpcall),
+            b"coroutine_create" => no_values(resolv, Tag::CoroutineCreate),
+            b"coroutine_wrap"   => no_values(resolv, Tag::CoroutineWrap),
+            b"coroutine_resume" => no_values(resolv, Tag::CoroutineResume),
+            b"coroutine_yield"  => no_values(resolv, Tag::CoroutineYield),
+            b"abstract"         => no_values(resolv, Tag::Abstract),
+            b"nodiscard"        => no_values(resolv, Tag::NoDiscard),
+
+            b"deprecated" => {
+                let message = if let Some(ref values) = attr.values {
+                    match &values[..] {
+                        &[] => None,
+                        &[Spanned { base: AttrValue::Str(ref s), .. }] => {
+                            Some(resolv.context_mut().intern_tag_str(s.base.clone()))
+                        }
+                        _ => {
+                            resolv.error(values, m::AttrRequiresOptionalStr { name: &attr.name })
+                                  .done()?;
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                Ok(Some(Tag::Deprecated(message)))
+            },
 
             b"make_class" => {
                 let values = values(resolv, 1)?;
@@ -189,7 +334,10 @@ impl Tag {
             b"internal kailua_gen_tvar"    => no_values(resolv, Tag::KailuaGenTvar),
             b"internal kailua_assert_tvar" => no_values(resolv, Tag::KailuaAssertTvar),
 
-            _ => {
+            name => {
+                if let Some(id) = resolv.context().custom_tag_from_name(name) {
+                    return no_values(resolv, Tag::Custom(id));
+                }
                 resolv.warn(&attr.name, m::UnknownAttrName { name: &attr.name.base }).done()?;
                 Ok(None)
             }
@@ -210,7 +358,22 @@ impl Tag {
             Tag::PackagePath  => "package_path",
             Tag::PackageCpath => "package_cpath",
             Tag::StringMeta   => "string_meta",
+            Tag::SetMetatable => "setmetatable",
+            Tag::GetMetatable => "getmetatable",
+            Tag::Weak         => "weak",
+            Tag::Select       => "select",
+            Tag::Pcall        => "pcall",
+            Tag::Xpcall       => "xpcall",
+            Tag::CoroutineCreate => "coroutine_create",
+            Tag::CoroutineWrap   => "coroutine_wrap",
+            Tag::CoroutineResume => "coroutine_resume",
+            Tag::CoroutineYield  => "coroutine_yield",
             Tag::MakeClass(_) => "make_class",
+            Tag::Abstract     => "abstract",
+            Tag::Deprecated(_) => "deprecated",
+            Tag::NoDiscard    => "nodiscard",
+            Tag::SelfType     => "self",
+            Tag::Custom(_)    => "custom",
 
             Tag::_Subtype         => "internal subtype",
             Tag::_NoSubtype       => "internal no_subtype",
@@ -252,7 +415,13 @@ impl Tag {
             Tag::_NoSubtype2 => false,
 
             Tag::PackagePath |
-            Tag::PackageCpath => false,
+            Tag::PackageCpath |
+            Tag::Weak |
+            Tag::Abstract |
+            Tag::Deprecated(_) |
+            Tag::NoDiscard |
+            Tag::SelfType |
+            Tag::Custom(_) => false,
             _ => true,
         }
     }
@@ -266,6 +435,12 @@ impl fmt::Debug for Tag {
             Tag::MakeClass(csid) => {
                 write!(f, "({:?})", csid)?;
             }
+            Tag::Custom(id) => {
+                write!(f, "({:?})", id)?;
+            }
+            Tag::Deprecated(Some(id)) => {
+                write!(f, "({:?})", id)?;
+            }
             _ => {}
         }
 
@@ -275,6 +450,10 @@ impl fmt::Debug for Tag {
 
 impl Display for Tag {
     fn fmt_displayed(&self, f: &mut fmt::Formatter, st: &DisplayState) -> fmt::Result {
+        if let Tag::Custom(id) = *self {
+            return st.context.fmt_custom_tag_name(id, f, st);
+        }
+
         write!(f, "{}", self.name())?;
 
         match *self {
@@ -283,6 +462,11 @@ impl Display for Tag {
                 st.context.fmt_class_system_name(csid, f, st)?;
                 write!(f, ")")?;
             }
+            Tag::Deprecated(Some(id)) => {
+                if let Some(s) = st.context.get_tag_str(id) {
+                    write!(f, "({})", s)?;
+                }
+            }
             _ => {}
         }
 
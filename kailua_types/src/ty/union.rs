@@ -5,8 +5,8 @@ use std::collections::BTreeSet;
 use kailua_syntax::Str;
 use diag::{TypeReport, TypeResult, Origin};
 use super::display::{Display, DisplayState, DisplayName};
-use super::{T, TypeContext, Lattice, Union};
-use super::{Numbers, Strings, Tables, Functions, Class};
+use super::{T, TypeContext, Lattice, Union, RVar, ComplexityLimit};
+use super::{Numbers, Strings, Key, Tables, Functions, Class};
 use super::flags::*;
 
 /// An expanded value type for unions.
@@ -30,6 +30,14 @@ pub struct Unioned {
     /// Table types, if any.
     pub tables: Option<Tables>,
 
+    /// Records in a discriminated union, if any.
+    ///
+    /// Records normally cannot be unioned, but they can when they have a common field
+    /// (a discriminant) with distinct string literal types, e.g. `{kind: "circle", ...}`
+    /// and `{kind: "rect", ...}`. There are at least two records when this is not empty,
+    /// and `tables` is always `None` in that case.
+    pub records: Vec<RVar>,
+
     /// Function types, if any.
     pub functions: Option<Functions>,
 
@@ -54,7 +62,8 @@ impl Unioned {
     pub fn empty() -> Unioned {
         Unioned {
             simple: U_NONE, numbers: None, strings: None, tables: None,
-            functions: None, classes: BTreeSet::new(), display_hints: Vec::new(),
+            records: Vec::new(), functions: None, classes: BTreeSet::new(),
+            display_hints: Vec::new(),
         }
     }
 
@@ -62,21 +71,24 @@ impl Unioned {
         let simple = if b { U_TRUE } else { U_FALSE };
         Unioned {
             simple: simple, numbers: None, strings: None, tables: None,
-            functions: None, classes: BTreeSet::new(), display_hints: Vec::new(),
+            records: Vec::new(), functions: None, classes: BTreeSet::new(),
+            display_hints: Vec::new(),
         }
     }
 
     pub fn explicit_int(v: i32) -> Unioned {
         Unioned {
             simple: U_NONE, numbers: Some(Numbers::One(v)), strings: None, tables: None,
-            functions: None, classes: BTreeSet::new(), display_hints: Vec::new(),
+            records: Vec::new(), functions: None, classes: BTreeSet::new(),
+            display_hints: Vec::new(),
         }
     }
 
     pub fn explicit_str(s: Str) -> Unioned {
         Unioned {
             simple: U_NONE, numbers: None, strings: Some(Strings::One(s)), tables: None,
-            functions: None, classes: BTreeSet::new(), display_hints: Vec::new(),
+            records: Vec::new(), functions: None, classes: BTreeSet::new(),
+            display_hints: Vec::new(),
         }
     }
 
@@ -87,7 +99,7 @@ impl Unioned {
             &T::Dynamic(_) | &T::All => {
                 panic!("Unioned::from called with T::Dynamic or T::All");
             }
-            &T::TVar(_) => {
+            &T::TVar(_) | &T::Param(_) => {
                 return Err(ctx.gen_report().cannot_union_single(ty, ctx));
             }
 
@@ -96,11 +108,14 @@ impl Unioned {
             &T::True     => { u.simple = U_TRUE; }
             &T::False    => { u.simple = U_FALSE; }
             &T::Thread   => { u.simple = U_THREAD; }
+            // coroutine types are erased in unions
+            &T::Coroutine(_) => { u.simple = U_THREAD; }
             &T::UserData => { u.simple = U_USERDATA; }
 
             &T::Number     => { u.numbers = Some(Numbers::All); }
             &T::Integer    => { u.numbers = Some(Numbers::Int); }
             &T::Int(v)     => { u.numbers = Some(Numbers::One(v)); }
+            &T::IntRange(lo, hi) => { u.numbers = Some(Numbers::Range(lo, hi)); }
             &T::String     => { u.strings = Some(Strings::All); }
             &T::Str(ref s) => { u.strings = Some(Strings::One(s.clone().into_owned())); }
 
@@ -114,6 +129,86 @@ impl Unioned {
         Ok(u)
     }
 
+    /// Returns a discriminant of given records, if any.
+    ///
+    /// The discriminant is a common field with distinct string literal types in every record.
+    /// The first such field in the key order is returned.
+    pub fn discriminant(records: &[RVar], ctx: &TypeContext) -> Option<Key> {
+        let mut keys: Vec<Key> = match records.first() {
+            Some(rvar) => ctx.get_rvar_fields(rvar.clone()).into_iter().map(|(k, _)| k).collect(),
+            None => return None,
+        };
+        keys.sort();
+
+        'key: for key in keys {
+            let mut values = Vec::new();
+            for rvar in records {
+                match Unioned::discriminant_value(rvar, &key, ctx) {
+                    Some(ref value) if values.contains(value) => continue 'key,
+                    Some(value) => values.push(value),
+                    None => continue 'key,
+                }
+            }
+            return Some(key);
+        }
+        None
+    }
+
+    /// Returns true if two records have the same fields with equal types.
+    ///
+    /// Structurally equal records can have different row variables,
+    /// e.g. when they have been constructed from separate table constructors.
+    pub fn records_equal(lhs: &RVar, rhs: &RVar, ctx: &TypeContext) -> bool {
+        if lhs == rhs {
+            return true;
+        }
+
+        let fields = |rvar: &RVar| {
+            let mut fields = Vec::new();
+            let last = ctx.list_rvar_fields(rvar.clone(), &mut |k, v| {
+                fields.push((k.clone(), v.clone()));
+                Ok(())
+            });
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            (fields, last.ok() == Some(RVar::empty()))
+        };
+        fields(lhs) == fields(rhs)
+    }
+
+    /// Returns the string literal type of given field in the record, if any.
+    pub fn discriminant_value(rvar: &RVar, key: &Key, ctx: &TypeContext) -> Option<Str> {
+        ctx.get_rvar_fields(rvar.clone()).into_iter()
+           .find(|&(ref k, _)| *k == *key)
+           .and_then(|(_, v)| v.unlift().as_string().cloned())
+    }
+
+    /// Asserts that a record is a subtype of one of records in a discriminated union.
+    ///
+    /// The record should have the same discriminant value as the matching record.
+    pub fn assert_record_sub(rvar: &RVar, records: &[RVar],
+                             ctx: &mut TypeContext) -> TypeResult<()> {
+        let matching = Unioned::discriminant(records, ctx).and_then(|key| {
+            let value = Unioned::discriminant_value(rvar, &key, ctx)?;
+            records.iter().find(|r| {
+                Unioned::discriminant_value(r, &key, ctx).as_ref() == Some(&value)
+            }).cloned()
+        });
+        if let Some(other) = matching {
+            rvar.assert_sub(&other, ctx)
+        } else {
+            Err(ctx.gen_report())
+        }
+    }
+
+    // returns records that would be a part of a discriminated union, if possible
+    fn records_for_union(&self) -> Option<Vec<RVar>> {
+        match self.tables {
+            None => Some(self.records.clone()),
+            Some(Tables::Fields(ref rvar)) => Some(vec![rvar.clone()]),
+            Some(_) => None,
+        }
+    }
+
     pub fn flags(&self) -> Flags {
         let mut flags = Flags::from_bits_truncate(self.simple.bits());
         match self.numbers {
@@ -123,6 +218,7 @@ impl Unioned {
         }
         if self.strings.is_some()   { flags.insert(T_STRING); }
         if self.tables.is_some()    { flags.insert(T_TABLE); }
+        if !self.records.is_empty() { flags.insert(T_TABLE); }
         if self.functions.is_some() { flags.insert(T_FUNCTION); }
         if !self.classes.is_empty() { flags.insert(T_TABLE); }
         flags
@@ -147,6 +243,9 @@ impl Unioned {
                 for &v in vv { f(T::Int(v))?; }
             }
             Some(Numbers::One(v)) if mask.intersects(T_INTEGER) => { f(T::Int(v))?; }
+            Some(Numbers::Range(lo, hi)) if mask.intersects(T_INTEGER) => {
+                f(T::IntRange(lo, hi))?;
+            }
             _ => {}
         }
 
@@ -167,6 +266,9 @@ impl Unioned {
             if let Some(ref tab) = self.tables {
                 f(T::Tables(Cow::Borrowed(tab)))?;
             }
+            for rvar in &self.records {
+                f(T::Tables(Cow::Owned(Tables::Fields(rvar.clone()))))?;
+            }
         }
 
         if mask.contains(T_FUNCTION) {
@@ -201,9 +303,11 @@ impl Unioned {
             let ret = self.visit(T_ALL, |ty| {
                 if single.is_some() { return Err(()); }
 
-                // avoid simplifying explicitly written literal types
+                // avoid simplifying explicitly written literal and range types
                 match ty {
-                    T::Int(_) | T::Str(_) | T::True | T::False => return Err(()),
+                    T::Int(_) | T::IntRange(..) | T::Str(_) | T::True | T::False => {
+                        return Err(());
+                    }
                     _ => {}
                 }
 
@@ -221,6 +325,7 @@ impl Unioned {
 
     fn fmt_generic<WriteTy, WriteName>(&self, f: &mut fmt::Formatter,
                                        hints: &[(Flags, DisplayName)],
+                                       maxwidth: Option<usize>,
                                        mut write_ty: WriteTy,
                                        mut write_name: WriteName) -> fmt::Result
         where WriteTy: FnMut(&T, &mut fmt::Formatter) -> fmt::Result,
@@ -251,7 +356,14 @@ impl Unioned {
             write!(f, "(")?;
         }
         let mut first = true;
+        let mut printed = 0;
+        let mut omitted = 0;
         self.visit(mask, |ty| {
+            if maxwidth.map_or(false, |w| printed >= w) {
+                omitted += 1;
+                return Ok(());
+            }
+            printed += 1;
             if first {
                 first = false;
             } else {
@@ -260,6 +372,11 @@ impl Unioned {
             write_ty(&ty, f)
         })?;
         for &(_, ref name) in hints.iter().filter(keep_hint) {
+            if maxwidth.map_or(false, |w| printed >= w) {
+                omitted += 1;
+                continue;
+            }
+            printed += 1;
             if first {
                 first = false;
             } else {
@@ -267,6 +384,12 @@ impl Unioned {
             }
             write_name(name, f)?;
         }
+        if omitted > 0 {
+            if !first {
+                write!(f, "|")?;
+            }
+            write!(f, "<{} types omitted>", omitted)?;
+        }
         if count != 1 {
             write!(f, ")")?
         }
@@ -315,12 +438,63 @@ impl Union for Unioned {
                 lhs.union(rhs, explicit, ctx)?
             });
 
+            // enormous literal unions (often from generated data) are widened to base types
+            let limit = ctx.literal_union_limit();
+            let numbers = match numbers {
+                Some(Numbers::Some(ref set)) if set.len() > limit => {
+                    ctx.record_limit_exceeded(ComplexityLimit::UnionSize);
+                    Some(Numbers::Int)
+                }
+                numbers => numbers,
+            };
+            let strings = match strings {
+                Some(Strings::Some(ref set)) if set.len() > limit => {
+                    ctx.record_limit_exceeded(ComplexityLimit::UnionSize);
+                    Some(Strings::All)
+                }
+                strings => strings,
+            };
+
+            // records with a discriminant can form a discriminated union.
+            // it should be explicitly constructed, but can be implicitly extended later
+            let extending = !self.records.is_empty() || !other.records.is_empty();
+            let records = match (self.records_for_union(), other.records_for_union()) {
+                (Some(mut records), Some(rhs)) if extending || explicit => {
+                    for rvar in rhs {
+                        if !records.iter().any(|r| Unioned::records_equal(r, &rvar, ctx)) {
+                            records.push(rvar);
+                        }
+                    }
+                    if records.len() == 1 || Unioned::discriminant(&records, ctx).is_some() {
+                        records
+                    } else {
+                        Vec::new()
+                    }
+                }
+                _ => Vec::new(),
+            };
+
+            // structurally equal records have been collapsed to a single record
+            let (records, single) = if records.len() == 1 {
+                (Vec::new(), records.into_iter().next())
+            } else {
+                (records, None)
+            };
+
             // tables cannot be unioned except when one operand is a record and another is
             // a supertype of that record. otherwise (including the case of two records)
             // they should be equal, so records can be seemingly unioned due to row extension
-            let tables = union_options!(&self.tables, &other.tables, |lhs, rhs| {
-                lhs.union(rhs, explicit, ctx)?
-            });
+            let tables = if let Some(rvar) = single {
+                Some(Tables::Fields(rvar))
+            } else if !records.is_empty() {
+                None
+            } else if extending {
+                return Err(ctx.gen_report());
+            } else {
+                union_options!(&self.tables, &other.tables, |lhs, rhs| {
+                    lhs.union(rhs, explicit, ctx)?
+                })
+            };
 
             // functions cannot be unioned at all and unequal function always results in an error
             let functions = union_options!(&self.functions, &other.functions, |lhs, rhs| {
@@ -333,7 +507,7 @@ impl Union for Unioned {
 
             let mut u = Unioned {
                 simple: simple, numbers: numbers, strings: strings,
-                tables: tables, functions: functions, classes: classes,
+                tables: tables, records: records, functions: functions, classes: classes,
                 display_hints: self.display_hints.clone(),
             };
             for &(flags, ref name) in &other.display_hints {
@@ -366,11 +540,38 @@ impl Lattice for Unioned {
 
             assert_sub_options!(&self.numbers, &other.numbers);
             assert_sub_options!(&self.strings, &other.strings);
-            assert_sub_options!(&self.tables, &other.tables);
             assert_sub_options!(&self.functions, &other.functions);
 
-            if !self.classes.is_subset(&other.classes) {
-                return Err(ctx.gen_report());
+            // each record in a discriminated union is checked separately
+            if other.records.is_empty() {
+                assert_sub_options!(&self.tables, &other.tables);
+                for rvar in &self.records {
+                    match other.tables {
+                        Some(ref tab) => Tables::Fields(rvar.clone()).assert_sub(tab, ctx)?,
+                        None => return Err(ctx.gen_report()),
+                    }
+                }
+            } else {
+                match self.tables {
+                    Some(Tables::Fields(ref rvar)) => {
+                        Unioned::assert_record_sub(rvar, &other.records, ctx)?;
+                    }
+                    Some(_) => return Err(ctx.gen_report()),
+                    None => {}
+                }
+                for rvar in &self.records {
+                    Unioned::assert_record_sub(rvar, &other.records, ctx)?;
+                }
+            }
+
+            // classes are also subsumed by `table`
+            match other.tables {
+                Some(Tables::All) => {}
+                _ => {
+                    if !self.classes.is_subset(&other.classes) {
+                        return Err(ctx.gen_report());
+                    }
+                }
             }
 
             Ok(())
@@ -400,6 +601,17 @@ impl Lattice for Unioned {
             assert_eq_options!(&self.tables, &other.tables);
             assert_eq_options!(&self.functions, &other.functions);
 
+            // records are matched by their discriminants, so `<:` in both directions suffices
+            if self.records.len() != other.records.len() {
+                return Err(ctx.gen_report());
+            }
+            for rvar in &self.records {
+                Unioned::assert_record_sub(rvar, &other.records, ctx)?;
+            }
+            for rvar in &other.records {
+                Unioned::assert_record_sub(rvar, &self.records, ctx)?;
+            }
+
             if self.classes != other.classes {
                 return Err(ctx.gen_report());
             }
@@ -412,7 +624,7 @@ impl Lattice for Unioned {
 impl Display for Unioned {
     fn fmt_displayed(&self, f: &mut fmt::Formatter, st: &DisplayState) -> fmt::Result {
         self.fmt_generic(
-            f, &self.display_hints,
+            f, &self.display_hints, st.max_width(),
             |t, f| fmt::Display::fmt(&t.display(st), f),
             |name, f| fmt::Display::fmt(&name.display(st), f),
         )
@@ -422,7 +634,7 @@ impl Display for Unioned {
 impl fmt::Debug for Unioned {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.fmt_generic(
-            f, &[],
+            f, &[], None,
             |t, f| fmt::Debug::fmt(t, f),
             |_, _| panic!("no names allowed"),
         )?;
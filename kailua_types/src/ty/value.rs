@@ -13,7 +13,9 @@ use diag::{Origin, TypeReport, TypeResult, TypeReportHint, TypeReportMore};
 use super::display::{Display, DisplayState, DisplayName};
 use super::{TypeContext, NoTypeContext, TypeResolver};
 use super::{F, Slot, Lattice, Union, Dummy};
-use super::{Numbers, Strings, Key, Tables, Function, Functions, Unioned, TVar, Tag, Class};
+use super::{Numbers, Strings, Key, Tables, Function, Functions, Coroutine, Unioned};
+use super::{TVar, TParam, Tag, Class};
+use super::Substitution;
 use super::flags::*;
 use message as m;
 
@@ -97,6 +99,12 @@ pub enum T<'a> {
     /// An integer literal type generated from an implicit Lua expression.
     Int(i32),
 
+    /// An integer range type (`integer(lo..hi)`) with a lower bound less than an upper bound.
+    ///
+    /// Ranges are always explicit and kept in union types;
+    /// this is only constructed when union types are visited.
+    IntRange(i32, i32),
+
     /// A string literal type generated from an implicit Lua expression.
     Str(Cow<'a, Str>),
 
@@ -106,12 +114,20 @@ pub enum T<'a> {
     /// Function types.
     Functions(Cow<'a, Functions>),
 
+    /// Coroutine types with known yield and resume types, i.e. `thread<Y, R>`.
+    ///
+    /// This is a subtype of `thread`.
+    Coroutine(Cow<'a, Coroutine>),
+
     /// Nominal types.
     Class(Class),
 
     /// A type variable.
     TVar(TVar),
 
+    /// A type parameter of generic functions.
+    Param(TParam),
+
     /// Union types, or explicit literal types.
     Union(Cow<'a, Unioned>),
 }
@@ -131,6 +147,11 @@ impl<'a> T<'a> {
         u.numbers = Some(Numbers::Some(i.into_iter().collect()));
         T::Union(Cow::Owned(u))
     }
+    pub fn int_range(lo: i32, hi: i32) -> T<'a> {
+        let mut u = Unioned::empty();
+        u.numbers = Some(Numbers::range(lo, hi));
+        T::Union(Cow::Owned(u))
+    }
     pub fn strs<I: IntoIterator<Item=Str>>(i: I) -> T<'a> {
         let mut u = Unioned::empty();
         u.strings = Some(Strings::Some(i.into_iter().collect()));
@@ -171,14 +192,18 @@ impl<'a> T<'a> {
             T::Number   => T_NUMBER,
             T::Integer  => T_INTEGER,
             T::Int(_)   => T_INTEGER,
+            T::IntRange(..) => T_INTEGER,
             T::String   => T_STRING,
             T::Str(_)   => T_STRING,
 
             T::Tables(..) => T_TABLE,
             T::Functions(..) => T_FUNCTION,
+            T::Coroutine(..) => T_THREAD,
             T::Class(..) => T_TABLE,
 
             T::TVar(..) => T_NONE,
+            // a type parameter can be anything, so it is as restrictive as `any`
+            T::Param(..) => T_ALL,
             T::Union(ref u) => u.flags(),
         }
     }
@@ -198,13 +223,16 @@ impl<'a> T<'a> {
             T::Number     => T::Number,
             T::Integer    => T::Integer,
             T::Int(v)     => T::Int(v),
+            T::IntRange(lo, hi) => T::IntRange(lo, hi),
             T::String     => T::String,
             T::Str(ref s) => T::Str(Cow::Borrowed(&**s)),
 
             T::Tables(ref tab) => T::Tables(Cow::Borrowed(&**tab)),
             T::Functions(ref func) => T::Functions(Cow::Borrowed(&**func)),
+            T::Coroutine(ref co) => T::Coroutine(Cow::Borrowed(&**co)),
             T::Class(c) => T::Class(c),
             T::TVar(v) => T::TVar(v),
+            T::Param(ref p) => T::Param(p.clone()),
             T::Union(ref u) => T::Union(Cow::Borrowed(&**u)),
         }
     }
@@ -344,6 +372,7 @@ impl<'a> T<'a> {
             T::Number     => T::Number,
             T::Integer    => T::Integer,
             T::Int(v)     => T::Int(v),
+            T::IntRange(lo, hi) => T::IntRange(lo, hi),
             T::String     => T::String,
             T::Str(s)     => T::Str(Cow::Owned(s.into_owned())),
 
@@ -353,15 +382,51 @@ impl<'a> T<'a> {
             // functions are _not_ recursively altered (will be generalized at call site)
             T::Functions(func) => T::Functions(Cow::Owned(func.into_owned())),
 
+            T::Coroutine(co) => T::Coroutine(Cow::Owned(co.into_owned().generalize(ctx))),
+
             T::Class(c) => T::Class(c),
             T::TVar(tv) => T::TVar(ctx.copy_tvar(tv)),
+            T::Param(p) => T::Param(p),
 
             // unions _are_ recursively altered
             T::Union(u) => {
                 let mut u = u.into_owned();
                 u.tables = u.tables.take().map(|tab| tab.generalize(ctx));
+                u.records = u.records.into_iter().map(|r| ctx.copy_rvar(r)).collect();
+                T::Union(Cow::Owned(u))
+            },
+        }
+    }
+
+    /// Replaces type parameters present in given type to their actual types.
+    ///
+    /// Type parameters at the top level are replaced by `Ty::substitute` instead,
+    /// as the actual type may have different nils or tags.
+    pub fn substitute(self, subst: &mut Substitution, ctx: &mut TypeContext) -> T<'static> {
+        match self {
+            T::Tables(tab) => T::Tables(Cow::Owned(tab.into_owned().substitute(subst, ctx))),
+            T::Functions(func) => {
+                T::Functions(Cow::Owned(func.into_owned().substitute(subst, ctx)))
+            },
+            T::Coroutine(co) => T::Coroutine(Cow::Owned(co.into_owned().substitute(subst, ctx))),
+
+            T::Class(Class::Instance(cid)) => {
+                T::Class(Class::Instance(subst.substitute_class(cid, ctx)))
+            },
+
+            T::Union(u) => {
+                let mut u = u.into_owned();
+                u.tables = u.tables.take().map(|tab| tab.substitute(subst, ctx));
+                u.records = u.records.into_iter().map(|r| subst.substitute_rvar(r, ctx)).collect();
+                u.functions = u.functions.take().map(|func| func.substitute(subst, ctx));
+                u.classes = u.classes.into_iter().map(|c| match c {
+                    Class::Instance(cid) => Class::Instance(subst.substitute_class(cid, ctx)),
+                    c => c,
+                }).collect();
                 T::Union(Cow::Owned(u))
             },
+
+            t => t.into_send(),
         }
     }
 
@@ -380,13 +445,16 @@ impl<'a> T<'a> {
             T::Number     => T::Number,
             T::Integer    => T::Integer,
             T::Int(v)     => T::Int(v),
+            T::IntRange(lo, hi) => T::IntRange(lo, hi),
             T::String     => T::String,
             T::Str(s)     => T::Str(Cow::Owned(s.into_owned())),
 
             T::Tables(tab)     => T::Tables(Cow::Owned(tab.into_owned())),
             T::Functions(func) => T::Functions(Cow::Owned(func.into_owned())),
+            T::Coroutine(co)   => T::Coroutine(Cow::Owned(co.into_owned())),
             T::Class(c)        => T::Class(c),
             T::TVar(tv)        => T::TVar(tv),
+            T::Param(p)        => T::Param(p),
 
             T::Union(u) => T::Union(Cow::Owned(u.into_owned())),
         }
@@ -438,6 +506,8 @@ impl<'a> T<'a> {
         match *self {
             T::Dynamic(_) => Ok(Cow::Borrowed(self)),
             T::None => Ok(Cow::Borrowed(self)),
+            // `any - nil` is kept as is, as it is what `any` means without nil
+            T::All if flags.contains(T_ALL & !T_NOISY_NIL) => Ok(Cow::Borrowed(self)),
             T::All => Ok(Cow::Owned(flags_to_ubound(flags))),
             T::Boolean => match flags & T_BOOLEAN {
                 T_BOOLEAN => Ok(Cow::Borrowed(self)),
@@ -453,6 +523,7 @@ impl<'a> T<'a> {
 
             T::Integer  => Ok(flags_or_none(T_INTEGER)),
             T::Int(_)   => Ok(flags_or_none(T_INTEGER)),
+            T::IntRange(..) => Ok(flags_or_none(T_INTEGER)),
             T::True     => Ok(flags_or_none(T_TRUE)),
             T::False    => Ok(flags_or_none(T_FALSE)),
             T::Thread   => Ok(flags_or_none(T_THREAD)),
@@ -462,10 +533,14 @@ impl<'a> T<'a> {
             T::Str(_)       => Ok(flags_or_none(T_STRING)),
             T::Tables(_)    => Ok(flags_or_none(T_TABLE)),
             T::Functions(_) => Ok(flags_or_none(T_FUNCTION)),
+            T::Coroutine(_) => Ok(flags_or_none(T_THREAD)),
             T::Class(_)     => Ok(flags_or_none(T_TABLE)),
 
             T::TVar(tv) => Ok(Cow::Owned(T::TVar(narrow_tvar(tv, flags, ctx)?))),
 
+            // type parameters cannot be narrowed
+            T::Param(_) => Ok(Cow::Borrowed(self)),
+
             T::Union(ref u) => {
                 // compile a list of flags to remove, and only alter if there is any removal
                 let removed = !flags & u.flags();
@@ -481,7 +556,7 @@ impl<'a> T<'a> {
                     u.numbers = narrow_numbers(num, flags).map(|num| num.into_owned());
                 }
                 if removed.contains(T_STRING)   { u.strings   = None; }
-                if removed.contains(T_TABLE)    { u.tables    = None; }
+                if removed.contains(T_TABLE)    { u.tables    = None; u.records.clear(); }
                 if removed.contains(T_FUNCTION) { u.functions = None; }
                 u.filter_display_hints(removed);
                 Ok(Cow::Owned(u.simplify()))
@@ -521,6 +596,7 @@ impl<'a> Lattice<Unioned> for T<'a> {
                 T::True     => other.simple.contains(U_TRUE),
                 T::False    => other.simple.contains(U_FALSE),
                 T::Thread   => other.simple.contains(U_THREAD),
+                T::Coroutine(_) => other.simple.contains(U_THREAD),
                 T::UserData => other.simple.contains(U_USERDATA),
 
                 T::Number => match other.numbers {
@@ -534,9 +610,16 @@ impl<'a> Lattice<Unioned> for T<'a> {
                 T::Int(lhs) => match other.numbers {
                     Some(Numbers::All) | Some(Numbers::Int) => true,
                     Some(Numbers::Some(ref rhs)) => rhs.contains(&lhs),
+                    Some(Numbers::Range(lo, hi)) => lo <= lhs && lhs <= hi,
                     Some(Numbers::One(rhs)) => lhs == rhs,
                     _ => false,
                 },
+                T::IntRange(lo, hi) => {
+                    if let Some(ref rhs) = other.numbers {
+                        return Numbers::Range(lo, hi).assert_sub(rhs, ctx);
+                    }
+                    false
+                },
 
                 T::String => match other.strings {
                     Some(Strings::All) => true,
@@ -553,6 +636,11 @@ impl<'a> Lattice<Unioned> for T<'a> {
                     if let Some(ref num) = other.tables {
                         return lhs.assert_sub(num, ctx);
                     }
+                    if let Tables::Fields(ref rvar) = **lhs {
+                        if !other.records.is_empty() {
+                            return Unioned::assert_record_sub(rvar, &other.records, ctx);
+                        }
+                    }
                     false
                 }
                 T::Functions(ref lhs) => {
@@ -561,7 +649,10 @@ impl<'a> Lattice<Unioned> for T<'a> {
                     }
                     false
                 },
-                T::Class(c) => other.classes.contains(&c),
+                T::Class(c) => match other.tables {
+                    Some(Tables::All) => true,
+                    _ => other.classes.contains(&c),
+                },
 
                 T::TVar(lhs) => {
                     let otherty = &Ty::new(T::Union(Cow::Owned(other.clone())));
@@ -620,6 +711,15 @@ impl<'a> T<'a> {
             (Cow::Borrowed(t), None, None)
         }
 
+        fn discriminated(a: &Tables, b: &Tables, ctx: &TypeContext) -> bool {
+            match (a, b) {
+                (&Tables::Fields(ref ar), &Tables::Fields(ref br)) if ar != br => {
+                    Unioned::discriminant(&[ar.clone(), br.clone()], ctx).is_some()
+                }
+                _ => false,
+            }
+        }
+
         (|| {
             // resolve type variables, which may result in Ty
             let (t1, niltag1, hint1) = resolve(self, ctx);
@@ -661,6 +761,14 @@ impl<'a> T<'a> {
                 (&T::Thread,   &T::Thread)   => (T::Thread, BOTH),
                 (&T::UserData, &T::UserData) => (T::UserData, BOTH),
 
+                // coroutines with different types are erased to `thread`
+                (&T::Coroutine(ref a), &T::Coroutine(ref b)) if a == b => {
+                    (T::Coroutine(Cow::Owned(a.clone().into_owned())), BOTH)
+                },
+                (&T::Coroutine(_), &T::Coroutine(_)) => (T::Thread, NONE),
+                (&T::Coroutine(_), &T::Thread) => (T::Thread, RIGHT),
+                (&T::Thread, &T::Coroutine(_)) => (T::Thread, LEFT),
+
                 (&T::Number,  &T::Number)  => (T::Number, BOTH),
                 (&T::Integer, &T::Number)  => (T::Number, RIGHT),
                 (&T::Int(_),  &T::Number)  => (T::Number, RIGHT),
@@ -686,8 +794,10 @@ impl<'a> T<'a> {
 
                 // tables cannot be unioned except when one operand is a record and another is
                 // a supertype of that record. otherwise (including the case of two records)
-                // they should be equal, so records can be seemingly unioned due to row extension
-                (&T::Tables(ref a), &T::Tables(ref b)) => {
+                // they should be equal, so records can be seemingly unioned due to row extension.
+                // explicitly unioned records with a discriminant are handled by `Unioned` though
+                (&T::Tables(ref a), &T::Tables(ref b))
+                        if !(explicit && discriminated(a, b, ctx)) => {
                     let u = a.union(b, explicit, ctx)?;
                     let keep = (u == **a, u == **b);
                     (T::Tables(Cow::Owned(u)), keep)
@@ -699,6 +809,9 @@ impl<'a> T<'a> {
                     (T::Functions(Cow::Owned(a.clone().into_owned())), BOTH)
                 },
 
+                // type parameters can be only unioned to themselves
+                (&T::Param(ref a), &T::Param(ref b)) if a == b => (T::Param(a.clone()), BOTH),
+
                 // unresolved type variables should be equal to each other to be unioned
                 (&T::TVar(a), &T::TVar(b)) => {
                     ctx.assert_tvar_eq_tvar(a, b)?;
@@ -741,6 +854,10 @@ impl<'a> T<'a> {
                 },
             };
 
+            if !explicit && !keep1 && !keep2 {
+                ctx.record_widening();
+            }
+
             // calculate display hints retained.
             // - selfhint/otherhint shadows hint1/hint2 when given
             // - hints not marked as kept are ignored no matter they come from
@@ -816,12 +933,18 @@ impl<'a, 'b> Lattice<T<'b>> for T<'a> {
                 (&T::Integer,    &T::Integer)    => true,
                 (&T::Int(_),     &T::Integer)    => true,
                 (&T::Int(a),     &T::Int(b))     => a == b,
+                (&T::IntRange(..), &T::Number)   => true,
+                (&T::IntRange(..), &T::Integer)  => true,
+                (&T::IntRange(a, b), &T::IntRange(c, d)) => c <= a && b <= d,
+                (&T::Int(v),     &T::IntRange(lo, hi)) => lo <= v && v <= hi,
                 (&T::String,     &T::String)     => true,
                 (&T::Str(_),     &T::String)     => true,
                 (&T::Str(ref a), &T::Str(ref b)) => *a == *b,
 
                 (&T::Tables(ref a),    &T::Tables(ref b))    => return a.assert_sub(b, ctx),
                 (&T::Functions(ref a), &T::Functions(ref b)) => return a.assert_sub(b, ctx),
+                (&T::Coroutine(ref a), &T::Coroutine(ref b)) => return a.assert_sub(b, ctx),
+                (&T::Coroutine(_),     &T::Thread)           => true,
 
                 (&T::Class(Class::Prototype(a)), &T::Class(Class::Prototype(b))) => {
                     a == b // prototypes are NOT compatible to each other!
@@ -829,6 +952,13 @@ impl<'a, 'b> Lattice<T<'b>> for T<'a> {
                 (&T::Class(Class::Instance(a)), &T::Class(Class::Instance(b))) => {
                     ctx.is_subclass_of(a, b)
                 },
+                // both prototypes and instances are tables at the runtime
+                (&T::Class(_), &T::Tables(ref b)) => match **b {
+                    Tables::All => true,
+                    _ => false,
+                },
+
+                (&T::Param(ref a), &T::Param(ref b)) => a == b,
 
                 (&T::Union(ref a), &T::Union(ref b)) => return a.assert_sub(b, ctx),
                 (&T::Union(_), &T::TVar(b)) => {
@@ -872,12 +1002,15 @@ impl<'a, 'b> Lattice<T<'b>> for T<'a> {
                 (&T::Number,     &T::Number)     => true,
                 (&T::Integer,    &T::Integer)    => true,
                 (&T::Int(a),     &T::Int(b))     => a == b,
+                (&T::IntRange(a, b), &T::IntRange(c, d)) => a == c && b == d,
                 (&T::String,     &T::String)     => true,
                 (&T::Str(ref a), &T::Str(ref b)) => *a == *b,
 
                 (&T::Tables(ref a),    &T::Tables(ref b))    => return a.assert_eq(b, ctx),
                 (&T::Functions(ref a), &T::Functions(ref b)) => return a.assert_eq(b, ctx),
+                (&T::Coroutine(ref a), &T::Coroutine(ref b)) => return a.assert_eq(b, ctx),
                 (&T::Class(a),         &T::Class(b))         => a == b,
+                (&T::Param(ref a),     &T::Param(ref b))     => a == b,
 
                 (&T::TVar(a), &T::TVar(b)) => return a.assert_eq(&b, ctx),
                 (a, &T::TVar(b)) => return ctx.assert_tvar_eq(b, &Ty::new(a.clone().into_send())),
@@ -920,13 +1053,16 @@ impl<'a, 'b> PartialEq<T<'b>> for T<'a> {
             (&T::Number,     &T::Number)     => true,
             (&T::Integer,    &T::Integer)    => true,
             (&T::Int(a),     &T::Int(b))     => a == b,
+            (&T::IntRange(a, b), &T::IntRange(c, d)) => a == c && b == d,
             (&T::String,     &T::String)     => true,
             (&T::Str(ref a), &T::Str(ref b)) => *a == *b,
 
             (&T::Tables(ref a),    &T::Tables(ref b))    => *a == *b,
             (&T::Functions(ref a), &T::Functions(ref b)) => *a == *b,
+            (&T::Coroutine(ref a), &T::Coroutine(ref b)) => *a == *b,
             (&T::Class(a),         &T::Class(b))         => a == b,
             (&T::TVar(a),          &T::TVar(b))          => a == b,
+            (&T::Param(ref a),     &T::Param(ref b))     => a == b,
             (&T::Union(ref a),     &T::Union(ref b))     => a == b,
 
             (_, _) => false,
@@ -939,7 +1075,7 @@ impl<'a> Display for T<'a> {
         match *self {
             T::Dynamic(Dyn::User) => write!(f, "WHATEVER"),
             T::Dynamic(Dyn::Oops) => {
-                match &st.locale[..] {
+                match &st.locale.resolve(&["ko"])[..] {
                     "ko" => write!(f, "<오류>"),
                     _    => write!(f, "<error>"),
                 }
@@ -947,7 +1083,7 @@ impl<'a> Display for T<'a> {
 
             T::All      => write!(f, "any"),
             T::None     => {
-                match &st.locale[..] {
+                match &st.locale.resolve(&["ko"])[..] {
                     "ko" => write!(f, "<불가능한 타입>"),
                     _    => write!(f, "<impossible type>"),
                 }
@@ -961,6 +1097,7 @@ impl<'a> Display for T<'a> {
             T::Number     => write!(f, "number"),
             T::Integer    => write!(f, "integer"),
             T::Int(v)     => write!(f, "{}", v),
+            T::IntRange(lo, hi) => write!(f, "integer({}..{})", lo, hi),
             T::String     => write!(f, "string"),
             T::Str(ref s) => write!(f, "{:?}", s),
 
@@ -971,7 +1108,7 @@ impl<'a> Display for T<'a> {
                     let ret = if let Some(t) = st.context.get_tvar_exact_type(tv) {
                         fmt::Display::fmt(&t.display(st), f)
                     } else {
-                        match &st.locale[..] {
+                        match &st.locale.resolve(&["ko"])[..] {
                             "ko" => write!(f, "<알 수 없는 타입>"),
                             _    => write!(f, "<unknown type>"),
                         }
@@ -981,8 +1118,11 @@ impl<'a> Display for T<'a> {
                 }
             },
 
+            T::Param(ref p) => write!(f, "{:+}", p.name),
+
             T::Tables(ref tab)      => fmt::Display::fmt(&tab.display(st), f),
             T::Functions(ref func)  => fmt::Display::fmt(&func.display(st), f),
+            T::Coroutine(ref co)    => fmt::Display::fmt(&co.display(st), f),
             T::Class(c)             => fmt::Display::fmt(&c.display(st), f),
             T::Union(ref u)         => fmt::Display::fmt(&u.display(st), f),
         }
@@ -1006,13 +1146,16 @@ impl<'a> fmt::Debug for T<'a> {
             T::Number     => write!(f, "number"),
             T::Integer    => write!(f, "integer"),
             T::Int(v)     => write!(f, "{}", v),
+            T::IntRange(lo, hi) => write!(f, "integer({}..{})", lo, hi),
             T::String     => write!(f, "string"),
             T::Str(ref s) => write!(f, "{:?}", s),
 
             T::Tables(ref tab)     => fmt::Debug::fmt(tab, f),
             T::Functions(ref func) => fmt::Debug::fmt(func, f),
+            T::Coroutine(ref co)   => fmt::Debug::fmt(co, f),
             T::Class(ref c)        => fmt::Debug::fmt(c, f),
             T::TVar(ref tv)        => fmt::Debug::fmt(tv, f),
+            T::Param(ref p)        => fmt::Debug::fmt(p, f),
             T::Union(ref u)        => fmt::Debug::fmt(u, f),
         }
     }
@@ -1184,6 +1327,31 @@ impl Ty {
         Ty { inner: Box::new(TyInner::new(ty, Nil::Silent)) }
     }
 
+    // returns flags for the excluded type in `T - U`, or None if it is not made of primitives
+    fn excluded_flags_from_kind(kind: &Spanned<Kind>) -> Option<Flags> {
+        match *kind.base {
+            K::Nil => Some(T_NOISY_NIL),
+            K::Boolean => Some(T_BOOLEAN),
+            K::BooleanLit(true) => Some(T_TRUE),
+            K::BooleanLit(false) => Some(T_FALSE),
+            K::Number => Some(T_NUMBER),
+            K::Integer => Some(T_INTEGER),
+            K::String => Some(T_STRING),
+            K::Table => Some(T_TABLE),
+            K::Function => Some(T_FUNCTION),
+            K::Thread => Some(T_THREAD),
+            K::UserData => Some(T_USERDATA),
+            K::WithNil(ref k) => Ty::excluded_flags_from_kind(k).map(|f| f | T_NOISY_NIL),
+            K::WithoutNil(ref k) => Ty::excluded_flags_from_kind(k),
+            K::Union(ref kinds) => {
+                kinds.iter().fold(Some(T_NONE), |flags, k| {
+                    Some(flags? | Ty::excluded_flags_from_kind(k)?)
+                })
+            }
+            _ => None,
+        }
+    }
+
     pub fn from_kind(kind: &Spanned<Kind>, resolv: &mut TypeResolver) -> Result<Ty> {
         let slot_from_slotkind = |slotkind: &SlotKind, resolv: &mut TypeResolver| -> Result<Slot> {
             let ty = Ty::from_kind(&slotkind.kind, resolv)?;
@@ -1203,8 +1371,51 @@ impl Ty {
             K::Table             => Ty::new(T::Tables(Cow::Owned(Tables::All))),
             K::Function          => Ty::new(T::Functions(Cow::Owned(Functions::All))),
             K::Thread            => Ty::new(T::Thread),
+            K::Coroutine(ref y, ref r) => {
+                let y = Ty::from_kind(y, resolv)?;
+                let r = Ty::from_kind(r, resolv)?;
+                Ty::new(T::Coroutine(Cow::Owned(Coroutine::new(y, r))))
+            },
             K::UserData          => Ty::new(T::UserData),
             K::Named(ref name)   => resolv.ty_from_name(name)?,
+            K::Generic(ref name, ref args) => {
+                let ty = resolv.ty_from_name(name)?;
+                let args = args.iter().map(|k| Ty::from_kind(k, resolv))
+                                      .collect::<Result<Vec<_>>>()?;
+
+                // only the generic class itself (not its specialization) can have arguments
+                let generic = match *ty {
+                    T::Class(Class::Instance(cid)) => {
+                        match resolv.context().class_type_args(cid) {
+                            Some((cid_, params)) if cid == cid_ => Some((cid, params.len())),
+                            _ => None,
+                        }
+                    }
+                    T::Dynamic(Dyn::Oops) => return Ok(ty), // already reported
+                    _ => None,
+                };
+
+                match generic {
+                    Some((cid, nparams)) if nparams == args.len() => {
+                        let cid = resolv.context_mut().specialize_class(cid, args).expect(
+                            "specialize_class failed with the correct number of arguments"
+                        );
+                        Ty::new(T::Class(Class::Instance(cid))).union_nil(ty.nil())
+                    }
+                    Some((_, nparams)) => {
+                        resolv.error(kind, m::WrongNumOfTypeArgs { name: &name.base,
+                                                                   expected: nparams,
+                                                                   actual: args.len() })
+                              .done()?;
+                        Ty::new(T::Dynamic(Dyn::Oops))
+                    }
+                    None => {
+                        resolv.error(kind, m::NonGenericTypeWithArgs { name: &name.base })
+                              .done()?;
+                        Ty::new(T::Dynamic(Dyn::Oops))
+                    }
+                }
+            },
             K::WithNil(ref k)    => Ty::from_kind(k, resolv)?.or_nil(Nil::Noisy),
             K::WithoutNil(ref k) => Ty::from_kind(k, resolv)?.or_nil(Nil::Absent),
             // XXX think about the possibility of nil? and nil! more
@@ -1220,6 +1431,7 @@ impl Ty {
             K::IntegerLit(v) => {
                 Ty::new(T::Union(Cow::Owned(Unioned::explicit_int(v))))
             },
+            K::IntegerRange(lo, hi) => Ty::new(T::int_range(lo, hi)),
             K::StringLit(ref s) => {
                 Ty::new(T::Union(Cow::Owned(Unioned::explicit_str(s.to_owned()))))
             },
@@ -1320,6 +1532,27 @@ impl Ty {
                 ty.base
             }
 
+            K::Difference(ref k, ref excluded) => {
+                let ty = Ty::from_kind(k, resolv)?;
+                let flags = if let Some(flags) = Ty::excluded_flags_from_kind(excluded) {
+                    flags
+                } else {
+                    resolv.error(excluded, m::UnsupportedExcludedTypeSpec {}).done()?;
+                    return Ok(Ty::dummy());
+                };
+                match ty.filter_by_flags(T_ALL & !flags, resolv.context_mut()) {
+                    // excluding nil works like `T!`, so that nilable types can't be assigned
+                    Ok(ty) if flags.contains(T_NOISY_NIL) => ty.or_nil(Nil::Absent),
+                    Ok(ty) => ty,
+                    Err(r) => {
+                        resolv.error(kind, m::UnsupportedDifferenceTypeSpec {})
+                              .report_types(r, TypeReportHint::None)
+                              .done()?;
+                        return Ok(Ty::dummy());
+                    }
+                }
+            }
+
             K::Attr(ref kind, ref attr) => {
                 let mut ty = Ty::from_kind(kind, resolv)?;
                 // None is simply ignored, `Tag::from` has already reported the error
@@ -1436,6 +1669,22 @@ impl Ty {
         self
     }
 
+    /// Replaces type parameters present in given type to their actual types.
+    pub fn substitute(mut self, subst: &mut Substitution, ctx: &mut TypeContext) -> Ty {
+        let actual = if let T::Param(ref p) = *self.inner.ty() {
+            subst.get(p).cloned()
+        } else {
+            None
+        };
+        if let Some(actual) = actual {
+            let tag = self.tag().or(actual.tag());
+            return actual.union_nil(self.nil()).with_tag(tag);
+        }
+
+        self.inner.remap_ty(|t| t.substitute(subst, ctx));
+        self
+    }
+
     pub fn flags(&self) -> Flags {
         let mut flags = self.inner.ty().flags();
         if self.inner.nil() == Nil::Noisy {
@@ -1796,19 +2045,30 @@ impl Display for Ty {
         let (nil, tag, name) = self.display_repr(st, f);
         let nil = if f.alternate() { nil.with_nil() } else { nil };
 
-        if let Some(tag) = tag {
-            write!(f, "[{}] ", tag.display(st))?;
-        }
+        // display hooks are ignored at the top level by `{:0}` just like display hints
+        let hooked = if name.is_none() && !(st.is_top_level() && f.sign_aware_zero_pad()) {
+            st.context.fmt_hooked_type(self, f, st)
+        } else {
+            None
+        };
 
-        if let Some(name) = name {
-            write!(f, "{}", name.display(st))?;
+        if let Some(ret) = hooked {
+            ret?;
         } else {
-            match (ty, nil) {
-                // nil-derived types have their own representations
-                (&T::None, Nil::Silent) => return write!(f, "nil"),
-                (&T::None, Nil::Noisy) => return write!(f, "nil"),
+            if let Some(tag) = tag {
+                write!(f, "[{}] ", tag.display(st))?;
+            }
 
-                (_, _) => ty.fmt_displayed(f, st)?,
+            if let Some(name) = name {
+                write!(f, "{}", name.display(st))?;
+            } else {
+                match (ty, nil) {
+                    // nil-derived types have their own representations
+                    (&T::None, Nil::Silent) => return write!(f, "nil"),
+                    (&T::None, Nil::Noisy) => return write!(f, "nil"),
+
+                    (_, _) => ty.fmt_displayed(f, st)?,
+                }
             }
         }
 
@@ -1995,6 +2255,18 @@ mod tests {
                explicit=T::ints(vec![2, 3, 4, 5]), implicit=T::Integer);
         check!(T::ints(vec![3, 4, 5]), T::ints(vec![3, 4, 5]); T::ints(vec![3, 4, 5]));
 
+        // integer ranges
+        check!(T::int_range(1, 5), T::int_range(1, 5); T::int_range(1, 5));
+        check!(T::int_range(1, 5), T::Int(3); T::int_range(1, 5));
+        check!(T::ints(vec![2, 4]), T::int_range(1, 5); T::int_range(1, 5));
+        check!(T::int_range(1, 5), T::int_range(2, 3); T::int_range(1, 5));
+        check!(T::int_range(1, 3), T::ints(vec![1, 2, 3]); T::int_range(1, 3));
+        check!(T::int_range(1, 5), T::Integer; T::Integer);
+        check!(T::int_range(1, 5), T::Int(7);
+               explicit=T::int_range(1, 7), implicit=T::Integer);
+        check!(T::int_range(1, 3), T::int_range(6, 8);
+               explicit=T::int_range(1, 8), implicit=T::Integer);
+
         // string literals
         check!(T::String, T::Str(os("hello")); T::String);
         check!(T::Str(os("hello")), T::String; T::String);
@@ -2170,6 +2442,21 @@ mod tests {
         assert!(nosubboolorstr.assert_sub(&substr, &mut NoTypeContext).is_err());
         assert!(nosubboolorstr.assert_sub(&nosubboolorstr, &mut NoTypeContext).is_ok());
 
+        // integer range subtyping
+        let range = T::int_range(1, 5);
+        assert!(T::Int(3).assert_sub(&range, &mut NoTypeContext).is_ok());
+        assert!(T::Int(6).assert_sub(&range, &mut NoTypeContext).is_err());
+        assert!(T::ints(vec![1, 5]).assert_sub(&range, &mut NoTypeContext).is_ok());
+        assert!(T::int_range(2, 4).assert_sub(&range, &mut NoTypeContext).is_ok());
+        assert!(T::int_range(0, 4).assert_sub(&range, &mut NoTypeContext).is_err());
+        assert!(range.assert_sub(&T::Integer, &mut NoTypeContext).is_ok());
+        assert!(range.assert_sub(&T::Number, &mut NoTypeContext).is_ok());
+        assert!(range.assert_sub(&T::ints(1..6), &mut NoTypeContext).is_ok());
+        assert!(range.assert_sub(&T::ints(vec![1, 2, 3, 5, 6]), &mut NoTypeContext).is_err());
+        assert!(T::Integer.assert_sub(&range, &mut NoTypeContext).is_err());
+        assert!((T::int_range(1, 3) | T::String).assert_sub(&(range.clone() | T::String),
+                                                            &mut NoTypeContext).is_ok());
+
         let mut types = Types::new(Locale::dummy(), Box::new(DummyClassProvider));
 
         {
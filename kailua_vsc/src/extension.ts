@@ -148,6 +148,7 @@ function initializeLanguageServer(context: vscode.ExtensionContext) {
         },
         initializationOptions: {
             default_locale: vscode.env.language,
+            cache: vscode.workspace.getConfiguration('kailua').get('cache', false),
         },
 	};
 	
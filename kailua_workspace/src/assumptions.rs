@@ -0,0 +1,148 @@
+//! Audit reports of `--# assume` directives.
+//!
+//! `--# assume` gives a type to a variable or a field without any checking,
+//! so a wrong assumption silently propagates to the rest of the code base.
+//! An `AssumptionReport` lists all such directives used during the checking,
+//! so that users can review this unchecked surface.
+
+use std::fmt::Write;
+use std::path::Path;
+use std::collections::BTreeMap;
+use kailua_env::Source;
+use kailua_diag::{Locale, Localized};
+use kailua_types::ty::{TypeContext, Display};
+use kailua_check::env::{Output, AssumptionKind};
+use paths::PathPolicy;
+
+/// An `--# assume` directive with its declared type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssumedItem {
+    /// A path relative to the base directory when possible, with `/` as a separator.
+    pub file: String,
+
+    /// The line (starting from 1) where the directive has been given.
+    pub line: usize,
+
+    pub kind: AssumptionKind,
+
+    /// True if the variable is global.
+    pub global: bool,
+
+    /// The variable name followed by field names if any, separated by `.`.
+    pub name: String,
+
+    /// The declared type, formatted.
+    pub ty: String,
+}
+
+/// `--# assume` directives collected from checker outputs, sorted by files and lines.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AssumptionReport {
+    // (file, line, name) to items; multiple start paths can check the same directive
+    items: BTreeMap<(String, usize, String), AssumedItem>,
+}
+
+impl AssumptionReport {
+    pub fn new() -> AssumptionReport {
+        AssumptionReport { items: BTreeMap::new() }
+    }
+
+    /// Adds an item. Items at the same position with the same name are merged.
+    pub fn add(&mut self, item: AssumedItem) {
+        let key = (item.file.clone(), item.line, item.name.clone());
+        self.items.insert(key, item);
+    }
+
+    /// Adds all `--# assume` directives from the checker output.
+    ///
+    /// Types are formatted in given locale.
+    /// Directives without a source file (e.g. from the built-in library) are ignored.
+    pub fn add_output(&mut self, output: &Output, source: &Source, base_dir: &Path,
+                      paths: &PathPolicy, locale: Locale) {
+        let types = output.types() as &TypeContext;
+        for assumption in output.assumptions() {
+            let file = match source.file(assumption.span.unit()) {
+                Some(file) => file,
+                None => continue,
+            };
+            let line = match file.line_from_pos(assumption.span.begin()) {
+                Some((line, _)) => line + 1,
+                None => continue,
+            };
+            let name: Vec<_> = assumption.path.iter()
+                                             .map(|name| String::from_utf8_lossy(name))
+                                             .collect();
+            let ty = Localized::new(&assumption.ty.display(types), locale).to_string();
+            self.add(AssumedItem {
+                file: paths.relative(Path::new(file.path()), base_dir),
+                line: line,
+                kind: assumption.kind,
+                global: assumption.global,
+                name: name.join("."),
+                ty: ty,
+            });
+        }
+    }
+
+    /// Returns all items sorted by files and lines.
+    pub fn items(&self) -> Vec<AssumedItem> {
+        self.items.values().cloned().collect()
+    }
+
+    /// Formats the report for the console, one directive per line.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for item in self.items.values() {
+            let kind = match (item.kind, item.global) {
+                (AssumptionKind::Variable, true) => "global",
+                (AssumptionKind::Variable, false) => "local",
+                (AssumptionKind::Field, _) => "field",
+                (AssumptionKind::Method, _) => "method",
+                (AssumptionKind::Class, _) => "class",
+            };
+            let _ = write!(text, "{}:{}: {} {}", item.file, item.line, kind, item.name);
+            if item.kind != AssumptionKind::Class {
+                let _ = write!(text, ": {}", item.ty);
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[test]
+fn test_assumption_report() {
+    use testutils::check_test_code;
+
+    let code = "--# open lua51\n\
+                --# assume global foo: { bar: integer }\n\
+                --# assume foo.bar: string\n\
+                local x = {}\n\
+                --# assume x: WHATEVER\n\
+                --# assume global class Point\n\
+                --# assume Point.norm: method() --> number\n";
+    let (context, source) = check_test_code("/base/a.lua", code);
+
+    let mut report = AssumptionReport::new();
+    report.add_output(&context, &source, Path::new("/base"), &PathPolicy::native(),
+                      Locale::dummy());
+    // adding the same output again doesn't duplicate items
+    report.add_output(&context, &source, Path::new("/base"), &PathPolicy::native(),
+                      Locale::dummy());
+
+    // the built-in library also uses `--# assume` but it is not included
+    let items = report.items();
+    assert_eq!(items.iter().map(|item| (&item.name[..], item.line, item.kind, item.global))
+                        .collect::<Vec<_>>(),
+               [("foo", 2, AssumptionKind::Variable, true),
+                ("foo.bar", 3, AssumptionKind::Field, true),
+                ("x", 5, AssumptionKind::Variable, false),
+                ("Point", 6, AssumptionKind::Class, true),
+                ("Point.norm", 7, AssumptionKind::Method, true)]);
+    assert_eq!(items[2].ty, "WHATEVER");
+
+    let text = report.to_text();
+    assert!(text.contains("a.lua:3: field foo.bar: string\n"), "{}", text);
+    assert!(text.contains("a.lua:6: class Point\n"), "{}", text);
+    assert_eq!(text.lines().count(), 5);
+}
@@ -0,0 +1,147 @@
+//! Cheaper checking of large machine-generated data files.
+//!
+//! A data file is a module consisting of a single `return { ... }` with lots of entries,
+//! frequently generated by tools (e.g. configuration dumps). Fully inferring them is costly
+//! and rarely useful, so the table is instead summarized from its sampled entries
+//! into a `vector` or `map` type, and the chunk is replaced with an equivalent declaration.
+
+use kailua_env::{Span, SourceData, WithLoc};
+use kailua_diag::NoReport;
+use kailua_syntax::{Chunk, Lexer, Nest, Parser};
+use kailua_syntax::ast::{St, Ex, Exp, Table};
+
+/// The maximum number of sampled entries in each table.
+const MAX_SAMPLES: usize = 64;
+
+/// Information about a data file checked with a summarized type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataFile {
+    /// The span for the entire file.
+    pub span: Span,
+
+    /// The number of table entries in the file, including nested tables.
+    pub entries: usize,
+
+    /// The summarized type in the Kailua syntax.
+    pub summary: String,
+}
+
+fn count_entries(table: &Table) -> usize {
+    let mut count = table.items.len();
+    for &(_, ref value) in &table.items {
+        if let Ex::Table(ref table) = *value.base {
+            count += count_entries(table);
+        }
+    }
+    count
+}
+
+fn value_type(exp: &Exp) -> Option<&'static str> {
+    match **exp {
+        Ex::Num(v) if v.fract() == 0.0 && v.abs() < 2147483648.0 => Some("integer"),
+        Ex::Num(_) => Some("number"),
+        Ex::Int(_) => Some("integer"),
+        Ex::Str(_) => Some("string"),
+        Ex::True | Ex::False => Some("boolean"),
+        Ex::Exp(ref e) => value_type(&e.base),
+        _ => None,
+    }
+}
+
+fn summarize_table(table: &Table) -> String {
+    let step = (table.items.len() + MAX_SAMPLES - 1) / MAX_SAMPLES;
+    let samples = table.items.iter().step_by(step.max(1));
+
+    let mut positional = true;
+    let mut string_keyed = true;
+    let mut value = None;
+    let mut homogeneous = true;
+    for &(ref key, ref v) in samples {
+        match *key {
+            None => { string_keyed = false; }
+            Some(ref key) => {
+                positional = false;
+                if let Ex::Str(_) = *key.base {} else { string_keyed = false; }
+            }
+        }
+        match (value, value_type(&v.base)) {
+            (_, None) => { homogeneous = false; }
+            (None, Some(t)) => { value = Some(t); }
+            (Some("integer"), Some("number")) | (Some("number"), Some("integer")) => {
+                value = Some("number");
+            }
+            (Some(u), Some(t)) if u != t => { homogeneous = false; }
+            _ => {}
+        }
+    }
+
+    let value = if homogeneous { value.unwrap_or("WHATEVER") } else { "WHATEVER" };
+    if positional {
+        format!("vector<{}>", value)
+    } else if string_keyed {
+        format!("map<string, {}>", value)
+    } else {
+        format!("WHATEVER")
+    }
+}
+
+fn parse_summary(summary: &str, span: Span) -> Chunk {
+    let code = format!("local data = {{}} --: {}\nreturn data\n", summary);
+    let mut iter = code.bytes().map(|c| SourceData::U8(c).with_loc(span))
+                               .chain(Some(SourceData::EOF.with_loc(span)));
+    let mut lexer = Lexer::new(&mut iter, &NoReport);
+    let mut nest = Nest::new(&mut lexer);
+    let parser = Parser::new(&mut nest, &NoReport);
+    parser.into_chunk().expect("failed to parse a summarized data file")
+}
+
+/// Replaces a chunk with the summarized declaration if it is a data file
+/// with at least `min_entries` table entries.
+pub fn summarize_data_chunk(chunk: &Chunk, min_entries: usize) -> Option<(Chunk, DataFile)> {
+    if chunk.block.len() != 1 {
+        return None;
+    }
+    let table = match *chunk.block[0].base {
+        St::Return(ref exps) if exps.len() == 1 => match *exps[0].base {
+            Ex::Table(ref table) => table,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let entries = count_entries(table);
+    if entries < min_entries {
+        return None;
+    }
+
+    let summary = summarize_table(table);
+    // every token points to the beginning of the original file (scopes need a real span)
+    let begin = chunk.block.span.begin();
+    let mut summarized = parse_summary(&summary, Span::new(begin, begin));
+    summarized.block.span = chunk.block.span; // so that the chunk is still associated to the file
+    Some((summarized, DataFile { span: chunk.block.span, entries: entries, summary: summary }))
+}
+
+#[test]
+fn test_summarize_data_chunk() {
+    use kailua_env::{Source, SourceFile};
+    use kailua_syntax::parse_chunk;
+
+    fn summarize(code: &str, min_entries: usize) -> Option<(usize, String)> {
+        let mut source = Source::new();
+        let span = source.add(SourceFile::from_u8(String::new(), code.as_bytes().to_owned()));
+        let chunk = parse_chunk(&source, span, &NoReport).unwrap();
+        summarize_data_chunk(&chunk, min_entries).map(|(_, data)| (data.entries, data.summary))
+    }
+
+    assert_eq!(summarize("return {1, 2, 3}", 3), Some((3, "vector<integer>".to_owned())));
+    assert_eq!(summarize("return {1, 2.5}", 1), Some((2, "vector<number>".to_owned())));
+    assert_eq!(summarize("return {a = 'x', b = 'y'}", 1),
+               Some((2, "map<string, string>".to_owned())));
+    assert_eq!(summarize("return {a = {1, 2}, b = {3}}", 5),
+               Some((5, "map<string, WHATEVER>".to_owned())));
+    assert_eq!(summarize("return {1, a = 2}", 1), Some((2, "WHATEVER".to_owned())));
+    assert_eq!(summarize("return {1, 2, 3}", 4), None);
+    assert_eq!(summarize("local x = 1\nreturn {1, 2, 3}", 1), None);
+    assert_eq!(summarize("return f{1, 2, 3}", 1), None);
+}
@@ -0,0 +1,136 @@
+//! Declaration files generated from checked modules.
+//!
+//! When `require` searches for a module `F`, the checker tries `F.kailua` before `F`
+//! (see `FsOptions::search_chunk`). A `Declaration` is such a file written from the types
+//! inferred for a checked module, so that downstream projects can use precise types
+//! of a library without checking its source every time:
+//!
+//! ```lua
+//! -- Declarations for `foo.bar` generated by Kailua from the inferred types.
+//! --# type Point = {x: number, y: number}
+//! --# assume global counter: integer
+//! --# assume M: {norm: function(p: {x: number, y: number}) --> number, ...}
+//! return M
+//! ```
+//!
+//! Types are written in the type syntax, so any type name appearing in them should be
+//! declared or exported by the declaration itself (or preloaded in the downstream project).
+
+use std::fmt::Write;
+use std::path::PathBuf;
+use kailua_env::Unit;
+use kailua_types::ty::{TypeContext, Ty};
+use kailua_check::env::{Id, Module, Output};
+
+/// A generated `.kailua` file for a module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Declaration {
+    /// The source code of the declaration.
+    pub code: String,
+
+    /// True if some types could not be expressed and have been replaced with `WHATEVER`.
+    pub lossy: bool,
+}
+
+impl Declaration {
+    /// Generates a declaration for the module `name` loaded by `require`.
+    ///
+    /// The declaration assumes the type returned by the module, types exported from it,
+    /// and global variables defined in the module source (given as `unit`).
+    /// Returns `None` if the module never returns.
+    pub fn from_module(name: &[u8], module: &Module, output: &Output,
+                       unit: Unit) -> Option<Declaration> {
+        let returns = module.returns.as_ref()?;
+
+        let types = output.types() as &TypeContext;
+        let mut lossy = false;
+        let mut kind = |ty: &Ty| {
+            ty.to_kind_string(types).unwrap_or_else(|_| {
+                lossy = true;
+                ty.to_lossy_kind_string(types)
+            })
+        };
+
+        let mut exported_types: Vec<_> = module.exported_types.iter().map(|(name, def)| {
+            (format!("{:+}", name), kind(&def.ty))
+        }).collect();
+        exported_types.sort();
+
+        let mut globals = Vec::new();
+        for (id, def) in output.all() {
+            if let Id::Global(ref name) = *id {
+                if def.span.unit() != unit {
+                    continue;
+                }
+                if let Some(slot) = def.slot.slot() {
+                    globals.push((format!("{:+}", name), kind(&slot.unlift())));
+                }
+            }
+        }
+        globals.sort();
+
+        let returns = kind(&returns.unlift());
+
+        let mut code = String::new();
+        let _ = writeln!(code, "-- Declarations for `{}` generated by Kailua \
+                                from the inferred types.", String::from_utf8_lossy(name));
+        if lossy {
+            code.push_str("-- Some types could not be expressed and have been replaced \
+                           with `WHATEVER`.\n");
+        }
+        for &(ref name, ref kind) in &exported_types {
+            let _ = writeln!(code, "--# type {} = {}", name, kind);
+        }
+        for &(ref name, ref kind) in &globals {
+            let _ = writeln!(code, "--# assume global {}: {}", name, kind);
+        }
+        let _ = writeln!(code, "--# assume M: {}", returns);
+        code.push_str("return M\n");
+
+        Some(Declaration { code: code, lossy: lossy })
+    }
+
+    /// Returns a relative path where the declaration for the module `name` should be written.
+    ///
+    /// Dots in the name are replaced with directory separators as `require` does,
+    /// so declarations written to `DIR` are found by adding `DIR` to `definitions` in the config.
+    pub fn relative_path(name: &[u8]) -> PathBuf {
+        let name = String::from_utf8_lossy(name);
+        let mut path: PathBuf = name.split('.').collect();
+        path.set_extension("kailua");
+        path
+    }
+}
+
+#[test]
+fn test_declaration() {
+    use std::path::Path;
+    use testutils::check_test_module;
+
+    let check = |code: &str| {
+        let (context, _, span) = check_test_module("/a.lua", "foo.bar", code);
+        let module = context.get_module(b"foo.bar").unwrap();
+        Declaration::from_module(b"foo.bar", module, &context, span.unit())
+    };
+
+    let code = "--# type Point = { x: number, y: number }\n\
+                local M = {} --: module\n\
+                --v function(p: Point) --> number\n\
+                function M.norm(p) return p.x + p.y end\n\
+                counter = 0 --: integer\n\
+                return M\n";
+    let decl = check(code).unwrap();
+    assert!(!decl.lossy);
+    assert_eq!(decl.code,
+               "-- Declarations for `foo.bar` generated by Kailua from the inferred types.\n\
+                --# type Point = {x: number, y: number}\n\
+                --# assume global counter: integer\n\
+                --# assume M: {norm: function(p: {x: number, y: number}) --> number, ...}\n\
+                return M\n");
+
+    // the declaration itself can be checked as a module, giving the same declaration
+    assert_eq!(check(&decl.code).as_ref(), Some(&decl));
+
+    assert_eq!(check("error('never returns')\n"), None);
+    assert_eq!(Declaration::relative_path(b"foo.bar"), Path::new("foo").join("bar.kailua"));
+}
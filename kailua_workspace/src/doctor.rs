@@ -0,0 +1,286 @@
+//! Diagnosing the workspace setup.
+//!
+//! `Workspace::doctor` checks the workspace without parsing or checking any source,
+//! so that command-line tools and editor setup wizards can point out configuration problems
+//! (missing start files, search paths pointing to nowhere, unknown preloads and so on)
+//! before they show up as confusing type errors.
+
+use std::str;
+use std::path::{Path, PathBuf};
+use serde_json::{Map, Value};
+use kailua_check;
+use {json, Workspace, ResolvedModule, apply_search_paths_template};
+
+/// The severity of a finding.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Severity {
+    /// Something worth knowing but not necessarily a problem.
+    Info,
+
+    /// The checking will work but probably not as intended.
+    Warning,
+
+    /// The checking will fail or is not going to be meaningful.
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// A single problem found by `Workspace::doctor`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+
+    /// A unique identifier of the kind of the finding, in lowercase words separated by hyphens.
+    pub code: &'static str,
+
+    /// A human-readable English description of the problem.
+    pub message: String,
+
+    /// A suggested fix in English, if any.
+    pub fix: Option<String>,
+}
+
+impl Finding {
+    fn new(severity: Severity, code: &'static str, message: String,
+           fix: Option<String>) -> Finding {
+        Finding { severity: severity, code: code, message: message, fix: fix }
+    }
+
+    /// Exports the finding as a JSON object with `severity`, `code`, `message`
+    /// and optionally `fix`.
+    pub fn to_json(&self) -> Value {
+        let mut map = Map::new();
+        map.insert("severity".to_owned(), Value::String(self.severity.name().to_owned()));
+        map.insert("code".to_owned(), Value::String(self.code.to_owned()));
+        map.insert("message".to_owned(), Value::String(self.message.clone()));
+        if let Some(ref fix) = self.fix {
+            map.insert("fix".to_owned(), Value::String(fix.clone()));
+        }
+        Value::Object(map)
+    }
+}
+
+/// Exports findings as a JSON array of objects returned by `Finding::to_json`.
+pub fn to_json(findings: &[Finding]) -> Value {
+    Value::Array(findings.iter().map(|f| f.to_json()).collect())
+}
+
+// returns a directory that given search path template searches, if it can be determined
+fn template_dir(base_dir: &Path, template: &[u8]) -> Option<PathBuf> {
+    let template = str::from_utf8(template).ok()?;
+    let prefix = &template[..template.find('?')?];
+    let dir = if prefix.ends_with('/') || prefix.ends_with('\\') {
+        &prefix[..prefix.len() - 1]
+    } else {
+        Path::new(prefix).parent()?.to_str()?
+    };
+    if dir.is_empty() {
+        None
+    } else {
+        Some(base_dir.join(dir))
+    }
+}
+
+impl Workspace {
+    /// Validates the workspace setup and returns a list of findings, most severe first.
+    ///
+    /// This checks that the configuration file is readable, start paths exist,
+    /// search path templates expand for every start path and point to existing directories
+    /// (`package_cpath` usually points to type stubs), definition bundles exist,
+    /// and preloaded libraries and modules are known.
+    /// An empty list means that no problem has been found.
+    pub fn doctor(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let fs = &*self.sys.fs;
+
+        if let Some(ref path) = self.config_path {
+            let readable = fs.read(path).map_err(|e| e.to_string()).and_then(|data| {
+                let data = String::from_utf8(data).map_err(|e| e.to_string())?;
+                json::parse(&data).map_err(|e| e.to_string())
+            });
+            if let Err(e) = readable {
+                findings.push(Finding::new(
+                    Severity::Error, "unreadable-config",
+                    format!("The configuration file `{}` cannot be read: {}", path.display(), e),
+                    Some(format!("Fix or recreate `{}`.", path.display())),
+                ));
+            }
+        } else {
+            findings.push(Finding::new(
+                Severity::Info, "no-config",
+                "No configuration file is used.".to_owned(),
+                Some(format!("Create `{}` to share the setup with other tools.",
+                             self.base_dir.join("kailua.json").display())),
+            ));
+        }
+
+        for start_path in &self.start_paths {
+            if !fs.is_file(start_path) {
+                findings.push(Finding::new(
+                    Severity::Error, "missing-start-path",
+                    format!("The start path `{}` does not exist.", start_path.display()),
+                    Some("Fix `start_path` in the configuration.".to_owned()),
+                ));
+            }
+        }
+
+        let mut missing_dirs = Vec::new();
+        for start_path in &self.start_paths {
+            let options = self.start_path_options(start_path);
+            let package_path = options.and_then(|o| o.package_path.as_ref())
+                                      .or(self.package_path.as_ref());
+            let package_cpath = options.and_then(|o| o.package_cpath.as_ref())
+                                       .or(self.package_cpath.as_ref());
+
+            for &(key, search_paths) in &[("package_path", package_path),
+                                          ("package_cpath", package_cpath)] {
+                let search_paths = if let Some(paths) = search_paths { paths } else { continue };
+                let expanded = if let Some(paths) =
+                        apply_search_paths_template(search_paths, start_path) {
+                    paths
+                } else {
+                    findings.push(Finding::new(
+                        Severity::Error, "bad-search-path",
+                        format!("`{}` cannot be expanded for the start path `{}`.",
+                                key, start_path.display()),
+                        Some(format!("Check `{{` and `}}` in `{}`.", key)),
+                    ));
+                    continue;
+                };
+
+                for template in expanded.split(|&c| c == b';') {
+                    let dir = if let Some(dir) = template_dir(&self.base_dir, template) {
+                        dir
+                    } else {
+                        continue;
+                    };
+                    if fs.is_dir(&dir) || missing_dirs.contains(&dir) {
+                        continue;
+                    }
+                    let (code, what) = if key == "package_cpath" {
+                        ("missing-stub-dir", "type stubs")
+                    } else {
+                        ("missing-search-dir", "Lua modules")
+                    };
+                    findings.push(Finding::new(
+                        Severity::Warning, code,
+                        format!("The directory `{}` for {} in `{}` does not exist.",
+                                dir.display(), what, key),
+                        Some(format!("Create the directory or remove `{}` from `{}`.",
+                                     String::from_utf8_lossy(template), key)),
+                    ));
+                    missing_dirs.push(dir);
+                }
+            }
+        }
+
+        for dir in &self.definitions {
+            if !fs.is_dir(dir) {
+                findings.push(Finding::new(
+                    Severity::Warning, "missing-definition-dir",
+                    format!("The definition bundle `{}` does not exist.", dir.display()),
+                    Some("Fix or remove the directory from `definitions`.".to_owned()),
+                ));
+            }
+        }
+
+        let libraries = kailua_check::available_libraries();
+        for name in &self.preload.open {
+            let known = str::from_utf8(&name.base).ok().map_or(false, |n| libraries.contains(&n));
+            if !known {
+                findings.push(Finding::new(
+                    Severity::Error, "unknown-library",
+                    format!("`preload.open` has an unknown library `{}`.",
+                            String::from_utf8_lossy(&name.base)),
+                    Some(format!("Use one of {}.", libraries.iter().map(|n| format!("`{}`", n))
+                                                           .collect::<Vec<_>>().join(", "))),
+                ));
+            }
+        }
+
+        for name in &self.preload.require {
+            for start_path in &self.start_paths {
+                let (code, problem) = match self.resolve_require(&name.base, start_path) {
+                    Some(ResolvedModule::Chunk(_)) => continue,
+                    Some(ResolvedModule::NativeLibrary(_)) => {
+                        ("native-preload-without-stub", "resolves to a native module without \
+                                                         a type stub")
+                    }
+                    None => ("unresolved-preload", "cannot be resolved"),
+                };
+                findings.push(Finding::new(
+                    Severity::Warning, code,
+                    format!("The module `{}` in `preload.require` {} from `{}`.",
+                            String::from_utf8_lossy(&name.base), problem, start_path.display()),
+                    Some("Check `package_path` and `package_cpath`, \
+                          or add a `.kailua` stub for the module.".to_owned()),
+                ));
+            }
+        }
+
+        // the sort is stable, so findings of the same severity are kept in order
+        findings.sort_by(|a, b| b.severity.cmp(&a.severity));
+        findings
+    }
+}
+
+#[test]
+fn test_doctor() {
+    use std::sync::Arc;
+    use kailua_diag::Locale;
+    use sys::{Sys, FakeFileSystem};
+    use Config;
+
+    let fs = FakeFileSystem::new();
+    fs.write("/ws/kailua.json", r#"{
+        "start_path": ["main.lua", "other.lua"],
+        "package_path": "src/?.lua;lib/?.lua",
+        "package_cpath": "stubs/?",
+        "definitions": ["defs"],
+        "preload": { "open": ["lua51", "lua99"], "require": ["util", "native"] }
+    }"#);
+    fs.write("/ws/main.lua", "");
+    fs.write("/ws/src/util.lua", "");
+    fs.write("/ws/stubs/native", "");
+    let fs = Arc::new(fs);
+
+    let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+    let mut sys = Sys::real();
+    sys.fs = fs.clone();
+    config.set_sys(sys);
+    assert!(config.set_config_path(PathBuf::from("/ws/kailua.json")).unwrap());
+    let workspace = Workspace::new(&config, Locale::dummy()).unwrap();
+
+    let findings = workspace.doctor();
+    let codes: Vec<_> = findings.iter().map(|f| (f.severity, f.code)).collect();
+    assert_eq!(codes, [
+        (Severity::Error, "missing-start-path"),
+        (Severity::Error, "unknown-library"),
+        (Severity::Warning, "missing-search-dir"),
+        (Severity::Warning, "missing-definition-dir"),
+        (Severity::Warning, "native-preload-without-stub"),
+        (Severity::Warning, "native-preload-without-stub"),
+    ]);
+    assert!(findings[0].message.contains("other.lua"));
+    assert!(findings[1].message.contains("lua99"));
+    assert!(findings[2].message.contains("lib"));
+    assert_eq!(findings[2].to_json()["severity"], "warning");
+
+    fs.write("/ws/other.lua", "");
+    fs.write("/ws/lib/.keep", "");
+    fs.write("/ws/defs/.keep", "");
+    fs.write("/ws/stubs/native.kailua", "");
+    fs.write("/ws/kailua.json", "{ broken");
+    let findings = workspace.doctor();
+    let codes: Vec<_> = findings.iter().map(|f| f.code).collect();
+    assert_eq!(codes, ["unreadable-config", "unknown-library"]);
+}
@@ -0,0 +1,255 @@
+//! Text edits spanning multiple files, applied as a single transaction.
+//!
+//! Tools rewriting the sources (renaming, quick fixes, annotation insertion and so on)
+//! often have to touch several files at once, and a half-applied rename is worse than none.
+//! `WorkspaceEdit` collects such edits and applies them all or nothing:
+//! if writing any file fails, every file written so far is restored to the original contents.
+//! The same edits can be previewed without touching the disk.
+
+use std::io::{self, Read, Write};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use kailua_env::{Span, Source, SourceSlice};
+
+/// A replacement of a byte range in a file.
+///
+/// An empty range denotes an insertion and an empty `new_text` denotes a deletion.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub new_text: String,
+}
+
+/// The result of edits to a single file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilePreview {
+    pub path: PathBuf,
+    pub original: Vec<u8>,
+    pub modified: Vec<u8>,
+}
+
+impl FilePreview {
+    pub fn is_changed(&self) -> bool {
+        self.original != self.modified
+    }
+}
+
+/// A set of text edits across multiple files.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WorkspaceEdit {
+    files: BTreeMap<PathBuf, Vec<TextEdit>>,
+}
+
+impl WorkspaceEdit {
+    pub fn new() -> WorkspaceEdit {
+        WorkspaceEdit { files: BTreeMap::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.values().all(|edits| edits.is_empty())
+    }
+
+    /// Returns the paths and edits in the order of paths.
+    pub fn files<'a>(&'a self) -> Box<Iterator<Item=(&'a Path, &'a [TextEdit])> + 'a> {
+        Box::new(self.files.iter().map(|(path, edits)| (path.as_path(), &edits[..])))
+    }
+
+    /// Replaces bytes from `start` to `end` (exclusive) in given file.
+    pub fn replace<P: Into<PathBuf>, S: Into<String>>(&mut self, path: P,
+                                                      start: usize, end: usize, new_text: S) {
+        assert!(start <= end, "WorkspaceEdit::replace with an inverted range");
+        let edit = TextEdit { start: start, end: end, new_text: new_text.into() };
+        self.files.entry(path.into()).or_insert_with(Vec::new).push(edit);
+    }
+
+    /// Inserts a text at given byte offset in given file.
+    ///
+    /// Multiple insertions at the same offset are applied in the order of calls.
+    pub fn insert<P: Into<PathBuf>, S: Into<String>>(&mut self, path: P,
+                                                     offset: usize, new_text: S) {
+        self.replace(path, offset, offset, new_text);
+    }
+
+    /// Replaces a span from the source with a text.
+    ///
+    /// Returns false if the span does not belong to any file in the source,
+    /// or the file has not been read from the byte stream (thus offsets are not byte offsets).
+    pub fn replace_span<S: Into<String>>(&mut self, source: &Source,
+                                         span: Span, new_text: S) -> bool {
+        if span.is_dummy() {
+            return false;
+        }
+        let file = match source.get_file(span.unit()) {
+            Some(file) => file,
+            None => return false,
+        };
+        if let SourceSlice::U16(_) = file.data() {
+            return false;
+        }
+        self.replace(file.path(), span.begin().to_usize(), span.end().to_usize(), new_text);
+        true
+    }
+
+    /// Merges all edits from another `WorkspaceEdit`.
+    pub fn extend(&mut self, other: WorkspaceEdit) {
+        for (path, edits) in other.files {
+            self.files.entry(path).or_insert_with(Vec::new).extend(edits);
+        }
+    }
+
+    /// Computes the resulting contents of all affected files without writing them (a dry run).
+    ///
+    /// Fails with `io::ErrorKind::InvalidInput` when edits are inverted, overlap
+    /// or go past the end of file.
+    pub fn preview(&self) -> io::Result<Vec<FilePreview>> {
+        let mut previews = Vec::new();
+        for (path, edits) in &self.files {
+            let mut original = Vec::new();
+            File::open(path)?.read_to_end(&mut original)?;
+            let modified = apply_edits(path, &original, edits)?;
+            previews.push(FilePreview { path: path.clone(), original: original,
+                                        modified: modified });
+        }
+        Ok(previews)
+    }
+
+    /// Applies all edits, either completely or not at all.
+    ///
+    /// Every file is first fully written to a temporary sibling file and then renamed over
+    /// the original, so no file is ever left partially written.
+    /// If any step fails, files already replaced are restored and the first error is returned.
+    /// Returns the previews of all affected files on success.
+    pub fn apply(&self) -> io::Result<Vec<FilePreview>> {
+        let previews = self.preview()?;
+
+        let mut written: Vec<&FilePreview> = Vec::new();
+        for preview in &previews {
+            if !preview.is_changed() {
+                continue;
+            }
+            if let Err(e) = replace_file(&preview.path, &preview.modified) {
+                // rollback is best-effort; the original error is more informative
+                for done in written.into_iter().rev() {
+                    let _ = replace_file(&done.path, &done.original);
+                }
+                return Err(e);
+            }
+            written.push(preview);
+        }
+
+        Ok(previews)
+    }
+}
+
+fn apply_edits(path: &Path, data: &[u8], edits: &[TextEdit]) -> io::Result<Vec<u8>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+
+    // stable, so insertions at the same offset keep their order
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    edits.sort_by_key(|edit| edit.start);
+
+    let mut modified = Vec::with_capacity(data.len());
+    let mut last = 0;
+    for edit in edits {
+        if edit.start > edit.end {
+            return Err(invalid(format!("edit {}..{} ends before it starts in {}",
+                                       edit.start, edit.end, path.display())));
+        }
+        if edit.end > data.len() {
+            return Err(invalid(format!("edit {}..{} goes past the end of {}",
+                                       edit.start, edit.end, path.display())));
+        }
+        if edit.start < last {
+            return Err(invalid(format!("edit {}..{} overlaps with a previous edit in {}",
+                                       edit.start, edit.end, path.display())));
+        }
+        modified.extend_from_slice(&data[last..edit.start]);
+        modified.extend_from_slice(edit.new_text.as_bytes());
+        last = edit.end;
+    }
+    modified.extend_from_slice(&data[last..]);
+    Ok(modified)
+}
+
+fn replace_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut tmpname = path.file_name().map_or_else(Default::default, |name| name.to_owned());
+    tmpname.push(".kailua-edit");
+    let tmppath = path.with_file_name(tmpname);
+
+    let result = File::create(&tmppath)
+        .and_then(|mut f| f.write_all(data).and_then(|_| f.sync_all()))
+        .and_then(|_| fs::rename(&tmppath, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmppath);
+    }
+    result
+}
+
+#[test]
+fn test_apply_edits() {
+    let path = Path::new("a.lua");
+    let data = b"local x = 1\nprint(x)\n";
+    let edit = |start, end, text: &str| TextEdit { start: start, end: end,
+                                                    new_text: text.to_owned() };
+
+    let modified = apply_edits(path, data, &[edit(18, 19, "y"), edit(6, 7, "y"),
+                                            edit(0, 0, "-- a\n"), edit(0, 0, "-- b\n")]);
+    assert_eq!(modified.unwrap(), b"-- a\n-- b\nlocal y = 1\nprint(y)\n".to_vec());
+
+    let overlapping = apply_edits(path, data, &[edit(0, 5, ""), edit(3, 7, "")]);
+    assert_eq!(overlapping.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    let past_end = apply_edits(path, data, &[edit(20, 30, "")]);
+    assert_eq!(past_end.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    let reversed = apply_edits(path, data, &[edit(7, 5, "")]);
+    assert_eq!(reversed.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    let reversed_past_end = apply_edits(path, data, &[edit(30, 0, "")]);
+    assert_eq!(reversed_past_end.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_workspace_edit() {
+    use testutils::TempDir;
+
+    let dir = TempDir::new("edit");
+    let read = |name: &str| {
+        let mut data = String::new();
+        File::open(dir.join(name)).unwrap().read_to_string(&mut data).unwrap();
+        data
+    };
+    File::create(dir.join("a.lua")).unwrap().write_all(b"foo = 1").unwrap();
+    File::create(dir.join("b.lua")).unwrap().write_all(b"print(foo)").unwrap();
+
+    let mut edit = WorkspaceEdit::new();
+    assert!(edit.is_empty());
+    edit.replace(dir.join("a.lua"), 0, 3, "bar");
+    edit.replace(dir.join("b.lua"), 6, 9, "bar");
+    assert_eq!(edit.files().count(), 2);
+
+    // dry run
+    let previews = edit.preview().unwrap();
+    assert_eq!(previews.len(), 2);
+    assert_eq!(previews[0].modified, b"bar = 1".to_vec());
+    assert_eq!(previews[1].modified, b"print(bar)".to_vec());
+    assert_eq!(read("a.lua"), "foo = 1");
+
+    // failure in any file should leave every file intact
+    let mut failing = edit.clone();
+    failing.replace(dir.join("c.lua"), 0, 0, "--");
+    assert!(failing.apply().is_err());
+    assert_eq!(read("a.lua"), "foo = 1");
+    assert_eq!(read("b.lua"), "print(foo)");
+
+    // ...even when it fails after some files have been written
+    fs::create_dir(dir.join("b.lua.kailua-edit")).unwrap();
+    assert!(edit.apply().is_err());
+    assert_eq!(read("a.lua"), "foo = 1");
+    assert_eq!(read("b.lua"), "print(foo)");
+    fs::remove_dir(dir.join("b.lua.kailua-edit")).unwrap();
+
+    edit.apply().unwrap();
+    assert_eq!(read("a.lua"), "bar = 1");
+    assert_eq!(read("b.lua"), "print(bar)");
+    assert!(!dir.join("a.lua.kailua-edit").exists());
+}
@@ -0,0 +1,269 @@
+//! An interactive session for applying fixes one by one.
+//!
+//! Command-line tools may offer a cleanup mode asking "apply this fix? (y/n/all)" for
+//! each fix. `FixSession` keeps track of which fixes have been applied or skipped,
+//! and after each application asks the `FixProvider` again for the affected files only,
+//! since any edit may invalidate (or newly make possible) other fixes in the same file.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
+use edit::WorkspaceEdit;
+
+/// A single applicable fix.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fix {
+    /// The name of the rule which has produced this fix, used for grouping.
+    pub rule: String,
+
+    /// The file the fix is reported for.
+    /// The edit itself may touch other files as well.
+    pub path: PathBuf,
+
+    /// A human-readable description of the fix.
+    pub message: String,
+
+    pub edit: WorkspaceEdit,
+}
+
+impl Fix {
+    fn same_as(&self, other: &Fix) -> bool {
+        // edits can shift after other fixes are applied, so they are not compared
+        self.rule == other.rule && self.path == other.path && self.message == other.message
+    }
+}
+
+/// Computes fixes for files.
+pub trait FixProvider {
+    /// Returns all fixes for given files, usually by re-reading and re-checking them.
+    fn fixes(&mut self, paths: &[PathBuf]) -> io::Result<Vec<Fix>>;
+}
+
+impl<F: FnMut(&[PathBuf]) -> io::Result<Vec<Fix>>> FixProvider for F {
+    fn fixes(&mut self, paths: &[PathBuf]) -> io::Result<Vec<Fix>> {
+        self(paths)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FixState {
+    Pending,
+    Applied,
+    Skipped,
+}
+
+/// An identifier of a fix in the session. Never reused within the same session.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FixId(usize);
+
+#[derive(Debug)]
+struct FixEntry {
+    id: FixId,
+    fix: Fix,
+    state: FixState,
+}
+
+/// A sequence of fixes presented one at a time.
+pub struct FixSession<P> {
+    provider: P,
+    entries: Vec<FixEntry>,
+    next_id: usize,
+}
+
+impl<P: FixProvider> FixSession<P> {
+    /// Starts a session by computing fixes for all given files.
+    pub fn new(mut provider: P, paths: &[PathBuf]) -> io::Result<FixSession<P>> {
+        let fixes = provider.fixes(paths)?;
+        let mut session = FixSession { provider: provider, entries: Vec::new(), next_id: 0 };
+        session.add_fixes(fixes);
+        Ok(session)
+    }
+
+    fn add_fixes(&mut self, fixes: Vec<Fix>) {
+        for fix in fixes {
+            // a fix once skipped should not be asked again
+            let skipped = self.entries.iter().any(|e| {
+                e.state == FixState::Skipped && e.fix.same_as(&fix)
+            });
+            if skipped {
+                continue;
+            }
+            let id = FixId(self.next_id);
+            self.next_id += 1;
+            self.entries.push(FixEntry { id: id, fix: fix, state: FixState::Pending });
+        }
+
+        // group fixes by rule while keeping the provider's order otherwise (stable)
+        self.entries.sort_by(|a, b| a.fix.rule.cmp(&b.fix.rule));
+    }
+
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    /// Returns the names of rules with pending fixes and the number of pending fixes.
+    pub fn pending_rules(&self) -> Vec<(&str, usize)> {
+        let mut rules: Vec<(&str, usize)> = Vec::new();
+        for e in self.entries.iter().filter(|e| e.state == FixState::Pending) {
+            match rules.last_mut() {
+                Some(&mut (rule, ref mut count)) if rule == e.fix.rule => {
+                    *count += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            rules.push((&e.fix.rule, 1));
+        }
+        rules
+    }
+
+    /// Returns all fixes known to the session, grouped by rule.
+    pub fn fixes<'a>(&'a self) -> Box<Iterator<Item=(FixId, &'a Fix, FixState)> + 'a> {
+        Box::new(self.entries.iter().map(|e| (e.id, &e.fix, e.state)))
+    }
+
+    pub fn get(&self, id: FixId) -> Option<(&Fix, FixState)> {
+        self.entries.iter().find(|e| e.id == id).map(|e| (&e.fix, e.state))
+    }
+
+    /// Returns the next pending fix to be presented, if any.
+    pub fn next_pending(&self) -> Option<(FixId, &Fix)> {
+        self.entries.iter().find(|e| e.state == FixState::Pending).map(|e| (e.id, &e.fix))
+    }
+
+    /// Marks a pending fix as skipped. Returns false if the fix is not pending.
+    pub fn skip(&mut self, id: FixId) -> bool {
+        match self.entries.iter_mut().find(|e| e.id == id) {
+            Some(ref mut e) if e.state == FixState::Pending => {
+                e.state = FixState::Skipped;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Applies a pending fix and recomputes fixes for files affected by it.
+    ///
+    /// Pending fixes for affected files are discarded and replaced with fresh ones.
+    /// Returns false if the fix is not pending; nothing is changed in that case.
+    /// If the edit fails, no file is changed and the fix remains pending.
+    pub fn apply(&mut self, id: FixId) -> io::Result<bool> {
+        let affected: Vec<PathBuf> = {
+            let e = match self.entries.iter().find(|e| e.id == id) {
+                Some(e) if e.state == FixState::Pending => e,
+                _ => return Ok(false),
+            };
+            e.fix.edit.apply()?;
+            let mut affected: BTreeSet<&Path> = e.fix.edit.files().map(|(p, _)| p).collect();
+            affected.insert(&e.fix.path);
+            affected.into_iter().map(|p| p.to_owned()).collect()
+        };
+
+        for e in &mut self.entries {
+            if e.id == id {
+                e.state = FixState::Applied;
+            }
+        }
+        self.entries.retain(|e| e.state != FixState::Pending || !affected.contains(&e.fix.path));
+
+        let fixes = self.provider.fixes(&affected)?;
+        self.add_fixes(fixes);
+        Ok(true)
+    }
+
+    /// Applies all pending fixes from given rule (or all rules if `None`), one by one.
+    ///
+    /// Fixes newly made available by prior applications are also applied.
+    /// Returns the number of applied fixes.
+    pub fn apply_all(&mut self, rule: Option<&str>) -> io::Result<usize> {
+        let mut count = 0;
+        loop {
+            let next = self.entries.iter().find(|e| {
+                e.state == FixState::Pending && rule.map_or(true, |r| e.fix.rule == r)
+            }).map(|e| e.id);
+            match next {
+                Some(id) => {
+                    if self.apply(id)? {
+                        count += 1;
+                    }
+                }
+                None => return Ok(count),
+            }
+        }
+    }
+
+    /// Returns the numbers of pending, applied and skipped fixes in this order.
+    pub fn summary(&self) -> (usize, usize, usize) {
+        let count = |state| self.entries.iter().filter(|e| e.state == state).count();
+        (count(FixState::Pending), count(FixState::Applied), count(FixState::Skipped))
+    }
+}
+
+#[test]
+fn test_fix_session() {
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use testutils::TempDir;
+
+    let dir = TempDir::new("fix");
+    let read = |path: &Path| {
+        let mut data = String::new();
+        File::open(path).unwrap().read_to_string(&mut data).unwrap();
+        data
+    };
+    let a = dir.join("a.lua");
+    let b = dir.join("b.lua");
+    File::create(&a).unwrap().write_all(b"x = 1;  \ny = 2;\n").unwrap();
+    File::create(&b).unwrap().write_all(b"z = 3;\n").unwrap();
+
+    // two toy rules: redundant semicolons and trailing spaces, one fix per occurrence
+    let provider = |paths: &[PathBuf]| -> io::Result<Vec<Fix>> {
+        let mut fixes = Vec::new();
+        for path in paths {
+            let data = read(path);
+            let mut offset = 0;
+            for line in data.split('\n') {
+                let trimmed = line.trim_right();
+                if trimmed.ends_with(';') {
+                    let mut edit = WorkspaceEdit::new();
+                    let end = offset + trimmed.len();
+                    edit.replace(path.clone(), end - 1, end, "");
+                    fixes.push(Fix { rule: "semicolon".to_owned(), path: path.clone(),
+                                     message: format!("`{}`", trimmed), edit: edit });
+                }
+                if trimmed.len() < line.len() {
+                    let mut edit = WorkspaceEdit::new();
+                    edit.replace(path.clone(), offset + trimmed.len(), offset + line.len(), "");
+                    fixes.push(Fix { rule: "whitespace".to_owned(), path: path.clone(),
+                                     message: format!("`{}`", trimmed), edit: edit });
+                }
+                offset += line.len() + 1;
+            }
+        }
+        Ok(fixes)
+    };
+
+    let mut session = FixSession::new(provider, &[a.clone(), b.clone()]).unwrap();
+    assert_eq!(session.pending_rules(), vec![("semicolon", 3), ("whitespace", 1)]);
+
+    // apply the first semicolon fix; the whitespace fix for a.lua should be recomputed
+    let (id, fix) = session.next_pending().map(|(id, fix)| (id, fix.clone())).unwrap();
+    assert_eq!((&fix.path, &fix.message[..]), (&a, "`x = 1;`"));
+    assert!(session.apply(id).unwrap());
+    assert!(!session.apply(id).unwrap());
+    assert_eq!(read(&a), "x = 1  \ny = 2;\n");
+    assert_eq!(session.pending_rules(), vec![("semicolon", 2), ("whitespace", 1)]);
+
+    // skipped fixes are not presented again even after recomputation
+    let (id, _) = session.next_pending().unwrap();
+    assert!(session.skip(id));
+    assert!(!session.skip(id));
+    assert_eq!(session.apply_all(Some("whitespace")).unwrap(), 1);
+    assert_eq!(read(&a), "x = 1\ny = 2;\n");
+    assert_eq!(session.pending_rules(), vec![("semicolon", 1)]);
+
+    assert_eq!(session.apply_all(None).unwrap(), 1);
+    assert_eq!(read(&a), "x = 1\ny = 2\n");
+    assert_eq!(read(&b), "z = 3;\n");
+    assert_eq!(session.summary(), (0, 3, 1));
+}
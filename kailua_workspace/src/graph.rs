@@ -0,0 +1,328 @@
+//! The module dependency graph discovered by the checker.
+//!
+//! `RecordingOptions` wraps other `Options` to record every `require` made while checking,
+//! and the resulting `RequireGraph` can be exported for build systems:
+//! as a JSON object, or as Starlark rules understood by Bazel and Buck.
+
+use std::fmt::Write;
+use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use serde_json::Value;
+use kailua_env::{Spanned, Source, Unit};
+use kailua_diag::{Report, Stop};
+use kailua_syntax::Chunk;
+use kailua_syntax::lang::Lua;
+use kailua_check::options::{Options, DynamicRequire};
+use kailua_check::lint::Severity;
+use paths::PathPolicy;
+
+/// A mapping from files to files required by them.
+///
+/// Paths are relative to the base directory when possible, and always use `/` as a separator.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequireGraph {
+    deps: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl RequireGraph {
+    pub fn new() -> RequireGraph {
+        RequireGraph { deps: BTreeMap::new() }
+    }
+
+    /// Adds a file without any dependency, if not yet added.
+    pub fn add_file(&mut self, file: String) {
+        self.deps.entry(file).or_insert_with(BTreeSet::new);
+    }
+
+    /// Adds a dependency from `from` to `to`. Both files are added as needed.
+    pub fn add_dependency(&mut self, from: String, to: String) {
+        self.add_file(to.clone());
+        self.deps.entry(from).or_insert_with(BTreeSet::new).insert(to);
+    }
+
+    /// Merges another graph (e.g. from a different start path) into this graph.
+    pub fn merge(&mut self, other: RequireGraph) {
+        for (file, deps) in other.deps {
+            self.deps.entry(file).or_insert_with(BTreeSet::new).extend(deps);
+        }
+    }
+
+    /// Returns all files in the sorted order.
+    pub fn files(&self) -> Vec<&str> {
+        self.deps.keys().map(|s| &s[..]).collect()
+    }
+
+    /// Returns direct dependencies of given file in the sorted order.
+    pub fn dependencies(&self, file: &str) -> Vec<&str> {
+        self.deps.get(file).map_or(Vec::new(), |deps| deps.iter().map(|s| &s[..]).collect())
+    }
+
+    /// Exports the graph as a JSON object from files to arrays of dependencies.
+    pub fn to_json(&self) -> Value {
+        let map = self.deps.iter().map(|(file, deps)| {
+            (file.clone(), Value::Array(deps.iter().map(|d| Value::String(d.clone())).collect()))
+        }).collect();
+        Value::Object(map)
+    }
+
+    /// Exports the graph as Starlark rules, one `rule` per file.
+    ///
+    /// Each rule has `name` derived from the path, `srcs` with that file
+    /// and `deps` referring to other rules in the same package.
+    /// When multiple files map to the same name (e.g. `a-b.lua` and `a_b.lua`),
+    /// files other than the first in the sorted order get a numeric suffix like `a_b_lua_2`.
+    pub fn to_starlark(&self, rule: &str) -> String {
+        fn target_name(file: &str) -> String {
+            file.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect()
+        }
+
+        // Starlark has no `\u{...}` escapes produced by `{:?}`, and reads source files as UTF-8
+        fn quote(s: &str) -> String {
+            let mut quoted = String::from("\"");
+            for c in s.chars() {
+                match c {
+                    '"' => quoted.push_str("\\\""),
+                    '\\' => quoted.push_str("\\\\"),
+                    '\n' => quoted.push_str("\\n"),
+                    '\r' => quoted.push_str("\\r"),
+                    '\t' => quoted.push_str("\\t"),
+                    c if c < ' ' || c == '\x7f' => {
+                        let _ = write!(quoted, "\\{:03o}", c as u32);
+                    }
+                    c => quoted.push(c),
+                }
+            }
+            quoted.push('"');
+            quoted
+        }
+
+        let mut names = HashMap::new();
+        let mut used = BTreeSet::new();
+        for file in self.deps.keys() {
+            let base = target_name(file);
+            let mut name = base.clone();
+            let mut suffix = 1;
+            while used.contains(&name) {
+                suffix += 1;
+                name = format!("{}_{}", base, suffix);
+            }
+            used.insert(name.clone());
+            names.insert(&file[..], name);
+        }
+
+        let mut out = String::new();
+        for (file, deps) in &self.deps {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            let _ = writeln!(out, "{}(", rule);
+            let _ = writeln!(out, "    name = {},", quote(&names[&file[..]]));
+            let _ = writeln!(out, "    srcs = [{}],", quote(file));
+            if !deps.is_empty() {
+                let _ = writeln!(out, "    deps = [");
+                for dep in deps {
+                    let _ = writeln!(out, "        {},", quote(&format!(":{}", names[&dep[..]])));
+                }
+                let _ = writeln!(out, "    ],");
+            }
+            let _ = writeln!(out, ")");
+        }
+        out
+    }
+}
+
+/// `Options` that record the module dependencies while delegating to inner options.
+///
+/// Only units are recorded during the checking, so that `Source` can be freely borrowed;
+/// they get resolved to paths by `RecordingOptions::graph`.
+pub struct RecordingOptions<O> {
+    inner: O,
+    modules: HashMap<Vec<u8>, Unit>,
+    requires: Vec<(Unit, Vec<u8>)>,
+}
+
+impl<O: Options> RecordingOptions<O> {
+    pub fn new(inner: O) -> RecordingOptions<O> {
+        RecordingOptions { inner: inner, modules: HashMap::new(), requires: Vec::new() }
+    }
+
+    /// Returns the inner options.
+    pub fn inner(&self) -> &O {
+        &self.inner
+    }
+
+    /// Returns modules loaded from chunks so far, as pairs of `require` arguments and units.
+    pub fn modules(&self) -> &HashMap<Vec<u8>, Unit> {
+        &self.modules
+    }
+
+    /// Returns `require` arguments recorded so far and units of requiring chunks, in order.
+    pub fn requires(&self) -> &[(Unit, Vec<u8>)] {
+        &self.requires
+    }
+
+    /// Returns a dependency graph from the records so far.
+    ///
+    /// `start_unit` is always included in the graph even when it has no dependency.
+    /// Paths are made relative to `base_dir` according to given path policy.
+    pub fn graph(&self, source: &Source, base_dir: &Path, paths: &PathPolicy,
+                 start_unit: Unit) -> RequireGraph {
+        let path_of = |unit: Unit| {
+            source.file(unit).map(|f| paths.relative(Path::new(f.path()), base_dir))
+        };
+
+        let mut graph = RequireGraph::new();
+        if let Some(path) = path_of(start_unit) {
+            graph.add_file(path);
+        }
+        for &(from, ref name) in &self.requires {
+            let from = path_of(from);
+            let to = self.modules.get(name).and_then(|&unit| path_of(unit));
+            if let (Some(from), Some(to)) = (from, to) {
+                graph.add_dependency(from, to);
+            }
+        }
+        graph
+    }
+}
+
+impl<O: Options> Options for RecordingOptions<O> {
+    fn set_package_path(&mut self, path: Spanned<&[u8]>,
+                        report: &Report) -> Result<(), Option<Stop>> {
+        self.inner.set_package_path(path, report)
+    }
+
+    fn set_package_cpath(&mut self, path: Spanned<&[u8]>,
+                         report: &Report) -> Result<(), Option<Stop>> {
+        self.inner.set_package_cpath(path, report)
+    }
+
+    fn on_require(&mut self, path: Spanned<&[u8]>) {
+        // preloaded modules do not have a requiring file
+        if !path.span.is_dummy() {
+            self.requires.push((path.span.unit(), path.base.to_owned()));
+        }
+        self.inner.on_require(path);
+    }
+
+    fn require_chunk(&mut self, path: Spanned<&[u8]>,
+                     report: &Report) -> Result<Chunk, Option<Stop>> {
+        let chunk = self.inner.require_chunk(path, report)?;
+        if !chunk.block.span.is_dummy() {
+            self.modules.insert(path.base.to_owned(), chunk.block.span.unit());
+        }
+        Ok(chunk)
+    }
+    fn lua_version(&self) -> Lua {
+        self.inner.lua_version()
+    }
+    fn strict_nil(&self) -> bool {
+        self.inner.strict_nil()
+    }
+    fn strict_globals(&self) -> bool {
+        self.inner.strict_globals()
+    }
+
+    fn dynamic_require(&self) -> DynamicRequire {
+        self.inner.dynamic_require()
+    }
+    fn literal_union_limit(&self) -> usize {
+        self.inner.literal_union_limit()
+    }
+    fn table_depth_limit(&self) -> usize {
+        self.inner.table_depth_limit()
+    }
+    fn constraint_limit(&self) -> usize {
+        self.inner.constraint_limit()
+    }
+    fn lint_severity(&self, code: &str) -> Severity {
+        self.inner.lint_severity(code)
+    }
+    fn code_severity(&self, code: &str) -> Option<Severity> {
+        self.inner.code_severity(code)
+    }
+}
+
+#[test]
+fn test_export() {
+    let mut graph = RequireGraph::new();
+    graph.add_dependency("main.lua".to_owned(), "lib/util.lua".to_owned());
+    graph.add_dependency("main.lua".to_owned(), "lib/util.lua".to_owned());
+    graph.add_file("main.lua".to_owned());
+
+    let mut other = RequireGraph::new();
+    other.add_dependency("lib/util.lua".to_owned(), "socket.kailua".to_owned());
+    graph.merge(other);
+
+    assert_eq!(graph.files(), vec!["lib/util.lua", "main.lua", "socket.kailua"]);
+    assert_eq!(graph.dependencies("main.lua"), vec!["lib/util.lua"]);
+
+    assert_eq!(graph.to_json(), json!({
+        "lib/util.lua": ["socket.kailua"],
+        "main.lua": ["lib/util.lua"],
+        "socket.kailua": [],
+    }));
+    assert_eq!(graph.to_starlark("lua_library"), "\
+lua_library(
+    name = \"lib_util_lua\",
+    srcs = [\"lib/util.lua\"],
+    deps = [
+        \":socket_kailua\",
+    ],
+)
+
+lua_library(
+    name = \"main_lua\",
+    srcs = [\"main.lua\"],
+    deps = [
+        \":lib_util_lua\",
+    ],
+)
+
+lua_library(
+    name = \"socket_kailua\",
+    srcs = [\"socket.kailua\"],
+)
+");
+}
+
+#[test]
+fn test_export_starlark_names() {
+    let mut graph = RequireGraph::new();
+    graph.add_dependency("main.lua".to_owned(), "a-b.lua".to_owned());
+    graph.add_dependency("main.lua".to_owned(), "a_b.lua".to_owned());
+    graph.add_dependency("main.lua".to_owned(), "a.b.lua".to_owned());
+    graph.add_file("x\u{7f}\"q\"\\\u{e9}\u{1f334}.lua".to_owned());
+
+    assert_eq!(graph.to_starlark("lua_library"), "\
+lua_library(
+    name = \"a_b_lua\",
+    srcs = [\"a-b.lua\"],
+)
+
+lua_library(
+    name = \"a_b_lua_2\",
+    srcs = [\"a.b.lua\"],
+)
+
+lua_library(
+    name = \"a_b_lua_3\",
+    srcs = [\"a_b.lua\"],
+)
+
+lua_library(
+    name = \"main_lua\",
+    srcs = [\"main.lua\"],
+    deps = [
+        \":a_b_lua\",
+        \":a_b_lua_2\",
+        \":a_b_lua_3\",
+    ],
+)
+
+lua_library(
+    name = \"x__q__\u{e9}__lua\",
+    srcs = [\"x\\177\\\"q\\\"\\\\\u{e9}\u{1f334}.lua\"],
+)
+");
+}
@@ -0,0 +1,154 @@
+//! Reports of modules and functions dominating the type inference cost.
+//!
+//! Large unannotated functions can generate a lot of type variables and union widenings,
+//! which make the checking slow and error messages confusing. A `HotSpotReport` lists
+//! such code with the statistics collected by the checker, so that users can annotate them.
+
+use std::fmt::Write;
+use std::path::Path;
+use std::collections::BTreeMap;
+use kailua_env::Source;
+use kailua_check::env::Output;
+use kailua_check::stats::{StatsScope, TypeStats};
+use paths::PathPolicy;
+
+/// Inference statistics for a module or a function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HotSpot {
+    /// A path relative to the base directory when possible, with `/` as a separator.
+    pub file: String,
+
+    /// The line (starting from 1) where the function has been declared,
+    /// or `None` for the top-level code of the module or the whole module.
+    pub line: Option<usize>,
+
+    pub stats: TypeStats,
+}
+
+/// Inference statistics collected from checker outputs, grouped by files and functions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HotSpotReport {
+    // (file, line) to statistics; line is None for the top-level code
+    spots: BTreeMap<(String, Option<usize>), TypeStats>,
+}
+
+fn sort_desc(spots: &mut Vec<HotSpot>) {
+    // the sort is stable, so spots of the same cost are kept in the order of files and lines
+    spots.sort_by(|a, b| b.stats.total().cmp(&a.stats.total()));
+}
+
+impl HotSpotReport {
+    pub fn new() -> HotSpotReport {
+        HotSpotReport { spots: BTreeMap::new() }
+    }
+
+    /// Adds statistics for a module (`line` is `None`) or a function.
+    /// Statistics for the same module or function are summed.
+    pub fn add(&mut self, file: String, line: Option<usize>, stats: TypeStats) {
+        let spot = self.spots.entry((file, line)).or_insert_with(TypeStats::default);
+        *spot = *spot + stats;
+    }
+
+    /// Adds all statistics from the checker output.
+    ///
+    /// Statistics without a source file (e.g. from the built-in library) are ignored.
+    pub fn add_output(&mut self, output: &Output, source: &Source,
+                      base_dir: &Path, paths: &PathPolicy) {
+        for entry in output.stats().entries() {
+            let file = match source.file(entry.scope.unit()) {
+                Some(file) => file,
+                None => continue,
+            };
+            let line = match entry.scope {
+                StatsScope::Module(_) => None,
+                StatsScope::Function(span) => match file.line_from_pos(span.begin()) {
+                    Some((line, _)) => Some(line + 1),
+                    None => continue,
+                },
+            };
+            self.add(paths.relative(Path::new(file.path()), base_dir), line, entry.stats);
+        }
+    }
+
+    /// Returns statistics for each module including its functions, most costly first.
+    pub fn modules(&self) -> Vec<HotSpot> {
+        let mut modules: BTreeMap<&str, TypeStats> = BTreeMap::new();
+        for (&(ref file, _), &stats) in &self.spots {
+            let module = modules.entry(file).or_insert_with(TypeStats::default);
+            *module = *module + stats;
+        }
+        let mut modules: Vec<_> = modules.into_iter().map(|(file, stats)| {
+            HotSpot { file: file.to_owned(), line: None, stats: stats }
+        }).collect();
+        sort_desc(&mut modules);
+        modules
+    }
+
+    /// Returns statistics for each function and the top-level code of each module,
+    /// most costly first. Statistics of nested functions are not included to outer ones.
+    pub fn functions(&self) -> Vec<HotSpot> {
+        let mut functions: Vec<_> = self.spots.iter().map(|(&(ref file, line), &stats)| {
+            HotSpot { file: file.clone(), line: line, stats: stats }
+        }).collect();
+        sort_desc(&mut functions);
+        functions
+    }
+
+    /// Formats the report for the console, listing at most `limit` functions.
+    pub fn to_text(&self, limit: usize) -> String {
+        fn write_spot(text: &mut String, spot: &HotSpot) {
+            let name = match spot.line {
+                Some(line) => format!("{}:{}", spot.file, line),
+                None => spot.file.clone(),
+            };
+            let _ = writeln!(text, "{:>8} {:>10} {:>9}  {}",
+                             spot.stats.tvars, spot.stats.widenings, spot.stats.failures, name);
+        }
+
+        let mut text = String::new();
+        let _ = writeln!(text, "{:>8} {:>10} {:>9}  {}", "tvars", "widenings", "failures",
+                         "module");
+        for spot in self.modules() {
+            write_spot(&mut text, &spot);
+        }
+        text.push('\n');
+        let _ = writeln!(text, "{:>8} {:>10} {:>9}  {}", "tvars", "widenings", "failures",
+                         "function (or the top level without a line)");
+        for spot in self.functions().iter().take(limit) {
+            write_spot(&mut text, spot);
+        }
+        text
+    }
+}
+
+#[test]
+fn test_hot_spot_report() {
+    use testutils::check_test_code;
+
+    let code = "local x = 1\n\
+                --v function(a: boolean?)\n\
+                local function f(a)\n\
+                \x20   local t = a and 1 or 2\n\
+                end\n";
+    let (context, source) = check_test_code("/base/a.lua", code);
+
+    let mut report = HotSpotReport::new();
+    report.add_output(&context, &source, Path::new("/base"), &PathPolicy::native());
+    report.add("b.lua".to_owned(), None,
+               TypeStats { tvars: 1000, widenings: 0, failures: 0 });
+
+    let modules = report.modules();
+    assert_eq!(modules.iter().map(|m| &m.file[..]).collect::<Vec<_>>(), ["b.lua", "a.lua"]);
+
+    let functions = report.functions();
+    assert_eq!(functions[0].file, "b.lua");
+    // the function declaration includes the preceding function specification
+    let f = functions.iter().find(|f| f.file == "a.lua" && f.line == Some(2)).unwrap();
+    let top = functions.iter().find(|f| f.file == "a.lua" && f.line == None).unwrap();
+    assert_eq!(f.stats.widenings, 2); // `a and 1 or 2` widens literal types to integer
+    assert_eq!(modules[1].stats, f.stats + top.stats);
+
+    let text = report.to_text(2);
+    assert!(text.contains("    1000          0         0  b.lua\n"));
+    assert_eq!(text.lines().count(), 1 + 2 + 1 + 1 + 2);
+}
@@ -0,0 +1,698 @@
+//! Incremental re-checking of changed modules.
+//!
+//! The checker always starts from a start path and fully checks every module it requires,
+//! which is too slow for the interactive feedback in large workspaces. `IncrementalState`
+//! remembers the dependencies and the exported signature of each module from previous checks,
+//! and `IncrementalSource` replaces every module that needs no re-checking with a stub chunk
+//! declaring the remembered signature. Only changed modules, and their dependents whose
+//! imported signatures have changed, are fully checked.
+//!
+//! Since a dependent is checked after its dependencies, a changed signature may be found
+//! only after its dependents have been already stubbed. `IncrementalState::finish_check`
+//! reports such cases, and the driver should check the start path again:
+//!
+//! ```rust,ignore
+//! loop {
+//!     let source = IncrementalSource::new(fssource, state.clone());
+//!     // ...check the start path with `RecordingOptions` and `TrackReportedUnits`...
+//!     if !state.borrow_mut().finish_check(&recording, &output, &reported) {
+//!         break;
+//!     }
+//! }
+//! ```
+//!
+//! Modules that may change the global environment (e.g. by assigning to global variables
+//! or declaring classes), modules with any report and modules with signatures
+//! that cannot be written in the type syntax are never stubbed.
+//!
+//! The state can be saved to and loaded from a file (see `Workspace::cache_path`),
+//! so that unchanged modules (typically third-party code) are not checked again
+//! in the next run. Parsed chunks themselves are not saved as their spans are only
+//! meaningful in a single `Source`; instead stubs are parsed in place of unchanged modules.
+//! Saved modules are stubbed only when the hashes of their contents
+//! (see `IncrementalSource::set_content_hash`) remain same.
+
+use std::io;
+use std::mem;
+use std::fmt::Write as FmtWrite;
+use std::hash::{Hash, Hasher};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use serde_json;
+use kailua_env::{Span, Spanned, SourceData, SourceSlice, Unit, WithLoc};
+use kailua_diag::{self, Kind, Locale, Localize, NoReport, Report, Stop};
+use kailua_syntax::{Chunk, Lexer, Nest, Parser};
+use kailua_syntax::ast::{Block, St, Ex, Exp, Var, Args, NameRef, TypeScope};
+use kailua_types::ty::quote;
+use kailua_check::env::{Module, Output};
+use kailua_check::options::{Options, FsSource};
+use graph::RecordingOptions;
+use sys::FileSystem;
+
+/// The exported signature of a module in the type syntax.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ModuleSignature {
+    /// The type returned by the module.
+    pub returns: String,
+
+    /// Exported type names and definitions, sorted by names.
+    pub exported_types: Vec<(String, String)>,
+}
+
+impl ModuleSignature {
+    /// Returns the signature of a loaded module,
+    /// or `None` if it never returns or has an unexpressible type.
+    pub fn from_module(module: &Module, output: &Output) -> Option<ModuleSignature> {
+        let returns = module.returns.as_ref()?.unlift().to_kind_string(output.types()).ok()?;
+        let mut exported_types = Vec::new();
+        for (name, def) in &module.exported_types {
+            let kind = def.ty.to_kind_string(output.types()).ok()?;
+            exported_types.push((format!("{:+}", name), kind));
+        }
+        exported_types.sort();
+        Some(ModuleSignature { returns: returns, exported_types: exported_types })
+    }
+
+    // a chunk declaring the same signature, after requiring the same modules
+    fn to_stub_code(&self, requires: &[Vec<u8>]) -> String {
+        let mut code = String::new();
+        for name in requires {
+            let _ = writeln!(code, "require {}", quote(name, '"'));
+        }
+        for &(ref name, ref kind) in &self.exported_types {
+            let _ = writeln!(code, "--# type {} = {}", name, kind);
+        }
+        let _ = writeln!(code, "--# assume M: {}", self.returns);
+        code.push_str("return M\n");
+        code
+    }
+}
+
+fn has_global_root(exp: &Exp) -> bool {
+    match **exp {
+        Ex::Var(Spanned { base: NameRef::Global(_), .. }) => true,
+        Ex::Exp(ref e) | Ex::Index(ref e, _) | Ex::IndexName(ref e, _) => has_global_root(&e.base),
+        _ => false,
+    }
+}
+
+fn exp_has_global_effects(exp: &Exp) -> bool {
+    let any = |ee: &[Spanned<Exp>]| ee.iter().any(|e| exp_has_global_effects(&e.base));
+    let args_have_global_effects = |args: &Args| match *args {
+        Args::List(ref ee) => any(ee),
+        Args::Str(_) => false,
+        Args::Table(ref table) => table.items.iter().any(|&(ref k, ref v)| {
+            k.as_ref().map_or(false, |k| exp_has_global_effects(&k.base)) ||
+                exp_has_global_effects(&v.base)
+        }),
+    };
+
+    match **exp {
+        Ex::Func(_, _, ref block) => block_has_global_effects(block),
+        Ex::Table(ref table) => table.items.iter().any(|&(ref k, ref v)| {
+            k.as_ref().map_or(false, |k| exp_has_global_effects(&k.base)) ||
+                exp_has_global_effects(&v.base)
+        }),
+        Ex::FuncCall(ref f, ref args) => {
+            // `module(...)` and `setfenv(...)` replace the global environment
+            if let Ex::Var(Spanned { base: NameRef::Global(ref name), .. }) = *f.base {
+                if &name[..] == b"module" || &name[..] == b"setfenv" {
+                    return true;
+                }
+            }
+            exp_has_global_effects(&f.base) || args_have_global_effects(&args.base)
+        }
+        Ex::MethodCall(ref fm, ref args) => {
+            exp_has_global_effects(&(fm.0).base) || args_have_global_effects(&args.base)
+        }
+        Ex::Exp(ref e) | Ex::IndexName(ref e, _) | Ex::Un(_, ref e) => {
+            exp_has_global_effects(&e.base)
+        }
+        Ex::Index(ref e1, ref e2) | Ex::Bin(ref e1, _, ref e2) => {
+            exp_has_global_effects(&e1.base) || exp_has_global_effects(&e2.base)
+        }
+        Ex::Oops | Ex::Nil | Ex::False | Ex::True | Ex::Num(_) | Ex::Int(_) | Ex::Str(_) |
+        Ex::Varargs | Ex::Var(_) => false,
+    }
+}
+
+/// Returns true if the block may change the global environment shared with other modules.
+///
+/// This is a conservative syntactic approximation: assignments to global variables
+/// and their fields (even in functions), declarations of global names, types, classes
+/// and interfaces, and `--# open` are all considered to change the environment.
+pub fn block_has_global_effects(block: &Block) -> bool {
+    let any = |ee: &[Spanned<Exp>]| ee.iter().any(|e| exp_has_global_effects(&e.base));
+
+    block.iter().any(|stmt| match *stmt.base {
+        St::Oops | St::Break | St::KailuaIgnore(..) => false,
+        St::Void(ref e) => exp_has_global_effects(&e.base),
+        St::Assign(ref vars, ref exps) => {
+            vars.iter().any(|var| match var.base.base {
+                Var::Name(Spanned { base: NameRef::Global(_), .. }) => true,
+                Var::Name(_) => false,
+                Var::Index(ref e1, ref e2) => {
+                    has_global_root(&e1.base) || exp_has_global_effects(&e1.base) ||
+                        exp_has_global_effects(&e2.base)
+                }
+                Var::IndexName(ref e, _) => {
+                    has_global_root(&e.base) || exp_has_global_effects(&e.base)
+                }
+            }) || exps.as_ref().map_or(false, |exps| any(exps))
+        }
+        St::Do(ref block) => block_has_global_effects(block),
+        St::While(ref cond, ref block) | St::Repeat(ref block, ref cond) => {
+            exp_has_global_effects(&cond.base) || block_has_global_effects(block)
+        }
+        St::If(ref conds, ref lastblock) => {
+            conds.iter().any(|cond| {
+                let (ref cond, ref block) = cond.base;
+                exp_has_global_effects(&cond.base) || block_has_global_effects(block)
+            }) || lastblock.as_ref().map_or(false, |block| block_has_global_effects(block))
+        }
+        St::For(_, ref start, ref end, ref step, _, ref block) => {
+            exp_has_global_effects(&start.base) || exp_has_global_effects(&end.base) ||
+                step.as_ref().map_or(false, |step| exp_has_global_effects(&step.base)) ||
+                block_has_global_effects(block)
+        }
+        St::ForIn(_, ref exps, _, ref block) => any(exps) || block_has_global_effects(block),
+        St::FuncDecl(ref name, _, _, ref block, _) => {
+            if let NameRef::Global(_) = name.base { true } else { block_has_global_effects(block) }
+        }
+        St::MethodDecl(ref names, _, _, _, ref block) => {
+            if let NameRef::Global(_) = (names.0).base {
+                true
+            } else {
+                block_has_global_effects(block)
+            }
+        }
+        St::Local(_, ref exps, _) | St::Return(ref exps) => any(exps),
+        St::KailuaType(scope, _, _) => scope == TypeScope::Global,
+        // scoped assumptions are undone at the end of the chunk, except for class fields
+        St::KailuaAssume(scoped, ref rename, _, _, _) => {
+            if let NameRef::Global(_) = rename.after { !scoped } else { false }
+        }
+        St::KailuaAssumeField(_, _, ref names, _, _) |
+        St::KailuaAssumeMethod(_, ref names, _, _) => {
+            if let NameRef::Global(_) = (names.0).base { true } else { false }
+        }
+        St::KailuaOpen(_) | St::KailuaClassSystem(_) | St::KailuaAssumeClass(..) |
+        St::KailuaInterface(..) => true,
+        St::KailuaAssertImplements(..) => false,
+    })
+}
+
+fn parse_stub(code: &str, span: Span) -> Option<Chunk> {
+    let mut iter = code.bytes().map(|c| SourceData::U8(c).with_loc(span))
+                               .chain(Some(SourceData::EOF.with_loc(span)));
+    let mut lexer = Lexer::new(&mut iter, &NoReport);
+    let mut nest = Nest::new(&mut lexer);
+    let parser = Parser::new(&mut nest, &NoReport);
+    parser.into_chunk().ok()
+}
+
+/// Returns a hash of the file contents, for `IncrementalSource::set_content_hash`.
+///
+/// The hash is stable for the same build of Kailua.
+pub fn hash_contents(data: SourceSlice) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+
+    let mut hasher = DefaultHasher::new();
+    match data {
+        SourceSlice::U8(data) => { 0u8.hash(&mut hasher); data.hash(&mut hasher); }
+        SourceSlice::U16(data) => { 1u8.hash(&mut hasher); data.hash(&mut hasher); }
+    }
+    hasher.finish()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct ModuleRecord {
+    // `require` arguments in the module, in order
+    requires: Vec<Vec<u8>>,
+
+    // files loaded by `requires`
+    deps: HashSet<PathBuf>,
+
+    // `None` if the module cannot be stubbed
+    signature: Option<ModuleSignature>,
+
+    // the hash of the checked contents if known; the module is stubbed only when it matches
+    hash: Option<u64>,
+}
+
+// a chunk loaded from `IncrementalSource` in the current check
+#[derive(Clone, Debug)]
+struct LoadedChunk {
+    path: PathBuf,
+    stubbed: bool,
+    global_effects: bool,
+    hash: Option<u64>,
+}
+
+// the format of saved states
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    version: String,
+    fingerprint: u64,
+    modules: Vec<(PathBuf, ModuleRecord)>,
+}
+
+// saved states from other versions are ignored
+const SAVED_STATE_VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+/// The state of incremental checking for a single start path.
+///
+/// The state should be shared by every check from the same start path
+/// with the same configuration, and should be reset when the configuration changes.
+#[derive(Clone, Debug, Default)]
+pub struct IncrementalState {
+    modules: HashMap<PathBuf, ModuleRecord>,
+
+    // modules to be fully checked in the next check
+    dirty: HashSet<PathBuf>,
+
+    // chunks loaded by `IncrementalSource` in the current check
+    loaded: HashMap<Unit, LoadedChunk>,
+
+    // true if records have been changed since the last load or save
+    modified: bool,
+}
+
+impl IncrementalState {
+    pub fn new() -> IncrementalState {
+        IncrementalState {
+            modules: HashMap::new(),
+            dirty: HashSet::new(),
+            loaded: HashMap::new(),
+            modified: false,
+        }
+    }
+
+    /// Marks a file as changed, so that it gets fully checked in the next check.
+    /// Its dependents are also checked if its signature changes.
+    pub fn mark_changed(&mut self, path: &Path) {
+        if self.modules.contains_key(path) {
+            self.dirty.insert(path.to_owned());
+        }
+    }
+
+    /// Forgets everything, so that every module gets fully checked in the next check.
+    pub fn reset(&mut self) {
+        self.modules.clear();
+        self.dirty.clear();
+        self.loaded.clear();
+        self.modified = true;
+    }
+
+    /// Returns paths to files replaced with stubs in the current check.
+    pub fn stubbed_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<_> = self.loaded.values().filter(|c| c.stubbed)
+                                                    .map(|c| c.path.clone()).collect();
+        paths.sort();
+        paths
+    }
+
+    /// Returns true if a given file will be replaced with a stub in the next check.
+    pub fn is_stubbable(&self, path: &Path) -> bool {
+        !self.dirty.contains(path) &&
+            self.modules.get(path).map_or(false, |module| module.signature.is_some())
+    }
+
+    fn stub_code(&self, path: &Path, hash: Option<u64>) -> Option<String> {
+        if self.dirty.contains(path) {
+            return None;
+        }
+        let module = self.modules.get(path)?;
+        if module.hash.is_some() && module.hash != hash {
+            return None;
+        }
+        module.signature.as_ref().map(|sig| sig.to_stub_code(&module.requires))
+    }
+
+    /// Reads the state saved by `IncrementalState::save`.
+    ///
+    /// Returns an empty state if the file is missing, malformed,
+    /// or saved with a different version of Kailua or a different `fingerprint`.
+    pub fn load(fs: &FileSystem, path: &Path, fingerprint: u64) -> io::Result<IncrementalState> {
+        let data = match fs.read(path) {
+            Ok(data) => data,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut state = Self::new();
+        if let Ok(saved) = serde_json::from_slice::<SavedState>(&data) {
+            if saved.version == SAVED_STATE_VERSION && saved.fingerprint == fingerprint {
+                state.modules.extend(saved.modules);
+            }
+        }
+        Ok(state)
+    }
+
+    /// Writes the state to given file, creating parent directories as needed.
+    ///
+    /// Only modules that can be stubbed and whose contents have known hashes are written.
+    /// `fingerprint` should identify the configuration (see `Workspace::fingerprint`).
+    /// Does nothing if the state has not been changed since the last load or save.
+    pub fn save(&mut self, fs: &FileSystem, path: &Path, fingerprint: u64) -> io::Result<()> {
+        if !self.modified {
+            return Ok(());
+        }
+
+        let mut modules: Vec<_> = self.modules.iter().filter(|&(path, module)| {
+            !self.dirty.contains(path) && module.signature.is_some() && module.hash.is_some()
+        }).map(|(path, module)| (path.clone(), module.clone())).collect();
+        modules.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let saved = SavedState {
+            version: SAVED_STATE_VERSION.to_owned(),
+            fingerprint: fingerprint,
+            modules: modules,
+        };
+        let data = serde_json::to_vec(&saved)?;
+        fs.write_file(path, &data)?;
+        self.modified = false;
+        Ok(())
+    }
+
+    /// Updates the state from a completed check and starts a new check.
+    ///
+    /// `recording` should be the options used for the check,
+    /// and `reported` should be units with any report (see `TrackReportedUnits`).
+    /// Returns true if some modules have been stubbed but should have been fully checked,
+    /// in which case the start path should be checked again.
+    pub fn finish_check<O: Options>(&mut self, recording: &RecordingOptions<O>, output: &Output,
+                                    reported: &HashSet<Unit>) -> bool {
+        let loaded = mem::replace(&mut self.loaded, HashMap::new());
+        let path_of = |name: &[u8]| {
+            recording.modules().get(name).and_then(|unit| loaded.get(unit)).map(|c| &c.path)
+        };
+
+        let mut recheck = false;
+        let mut changed = Vec::new();
+        for (name, unit) in recording.modules() {
+            let chunk = if let Some(chunk) = loaded.get(unit) { chunk } else { continue };
+
+            if chunk.stubbed {
+                // the stub itself can fail, e.g. when a required module is gone
+                if reported.contains(unit) {
+                    if let Some(module) = self.modules.get_mut(&chunk.path) {
+                        module.signature = None;
+                    }
+                    self.modified = true;
+                    self.dirty.insert(chunk.path.clone());
+                    recheck = true;
+                }
+                continue;
+            }
+
+            let requires: Vec<Vec<u8>> = recording.requires().iter().filter_map(|&(from, ref n)| {
+                if from == *unit { Some(n.clone()) } else { None }
+            }).collect();
+            let deps = requires.iter().filter_map(|n| path_of(n)).cloned().collect();
+            let signature = if chunk.global_effects || reported.contains(unit) {
+                None
+            } else {
+                output.get_module(name).and_then(|m| ModuleSignature::from_module(m, output))
+            };
+
+            let record = ModuleRecord {
+                requires: requires, deps: deps, signature: signature, hash: chunk.hash,
+            };
+            let prev = self.modules.insert(chunk.path.clone(), record);
+            self.dirty.remove(&chunk.path);
+            if prev.as_ref() != self.modules.get(&chunk.path) {
+                self.modified = true;
+            }
+
+            // an unexpressible signature is always considered to be changed
+            let sig = self.modules[&chunk.path].signature.as_ref();
+            if sig.is_none() || prev.map_or(true, |prev| prev.signature.as_ref() != sig) {
+                changed.push(chunk.path.clone());
+            }
+        }
+
+        // dependents already fully checked in this check have seen the new signature
+        let checked: HashSet<&PathBuf> = loaded.values().filter(|c| !c.stubbed)
+                                               .map(|c| &c.path).collect();
+        let stubbed: HashSet<&PathBuf> = loaded.values().filter(|c| c.stubbed)
+                                               .map(|c| &c.path).collect();
+        for path in changed {
+            let dependents: Vec<PathBuf> = self.modules.iter().filter_map(|(p, module)| {
+                if module.deps.contains(&path) && !checked.contains(p) {
+                    Some(p.clone())
+                } else {
+                    None
+                }
+            }).collect();
+            for dependent in dependents {
+                if stubbed.contains(&dependent) {
+                    recheck = true;
+                }
+                self.dirty.insert(dependent);
+            }
+        }
+
+        recheck
+    }
+}
+
+/// `FsSource` that replaces modules without any change with stubs.
+///
+/// Every loaded chunk is recorded to the state until `IncrementalState::finish_check`.
+pub struct IncrementalSource<S> {
+    inner: S,
+    state: Rc<RefCell<IncrementalState>>,
+    content_hash: Option<Box<Fn(Unit) -> Option<u64>>>,
+}
+
+impl<S: FsSource> IncrementalSource<S> {
+    pub fn new(inner: S, state: Rc<RefCell<IncrementalState>>) -> IncrementalSource<S> {
+        IncrementalSource { inner: inner, state: state, content_hash: None }
+    }
+
+    /// Sets a function returning the hash of the contents of each loaded unit
+    /// (normally with `hash_contents`).
+    ///
+    /// Hashes are recorded to the state, and modules with recorded hashes are stubbed
+    /// only when their hashes are same. This is required for saving the state.
+    pub fn set_content_hash<F: Fn(Unit) -> Option<u64> + 'static>(&mut self, f: F) {
+        self.content_hash = Some(Box::new(f));
+    }
+
+    /// Returns the inner source.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: FsSource> FsSource for IncrementalSource<S> {
+    fn chunk_from_path(&self, resolved_path: Spanned<&Path>,
+                       report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+        // the file is still parsed (which is relatively cheap) so that the stub has a real span
+        let chunk = if let Some(chunk) = self.inner.chunk_from_path(resolved_path, report)? {
+            chunk
+        } else {
+            return Ok(None);
+        };
+        let unit = chunk.block.span.unit();
+        let hash = self.content_hash.as_ref().and_then(|f| f(unit));
+
+        let stub = self.state.borrow().stub_code(&resolved_path, hash).and_then(|code| {
+            // every token points to the beginning of the original file, as in data files
+            let begin = chunk.block.span.begin();
+            parse_stub(&code, Span::new(begin, begin))
+        });
+        let stubbed = stub.is_some();
+        let global_effects = !stubbed && block_has_global_effects(&chunk.block);
+        self.state.borrow_mut().loaded.insert(unit, LoadedChunk {
+            path: resolved_path.base.to_owned(),
+            stubbed: stubbed,
+            global_effects: global_effects,
+            hash: hash,
+        });
+
+        if let Some(mut stub) = stub {
+            stub.block.span = chunk.block.span; // so that the chunk is still associated to the file
+            Ok(Some(stub))
+        } else {
+            Ok(Some(chunk))
+        }
+    }
+
+    fn path_exists(&self, resolved_path: Spanned<&Path>) -> bool {
+        self.inner.path_exists(resolved_path)
+    }
+
+    fn to_path_buf(&self, path: Spanned<&[u8]>,
+                   report: &Report) -> Result<PathBuf, Option<Stop>> {
+        self.inner.to_path_buf(path, report)
+    }
+}
+
+/// A wrapper for `Report` implementations that also tracks units with any report.
+pub struct TrackReportedUnits<R> {
+    report: R,
+    units: RefCell<HashSet<Unit>>,
+}
+
+impl<R: Report> TrackReportedUnits<R> {
+    pub fn new(report: R) -> TrackReportedUnits<R> {
+        TrackReportedUnits { report: report, units: RefCell::new(HashSet::new()) }
+    }
+
+    /// Returns units with any report so far.
+    pub fn units(&self) -> HashSet<Unit> {
+        self.units.borrow().clone()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.report
+    }
+}
+
+impl<R: Report> Report for TrackReportedUnits<R> {
+    fn message_locale(&self) -> Locale {
+        self.report.message_locale()
+    }
+
+    fn add_span(&self, kind: Kind, span: Span, msg: &Localize) -> kailua_diag::Result<()> {
+        self.units.borrow_mut().insert(span.unit());
+        self.report.add_span(kind, span, msg)
+    }
+}
+
+#[test]
+fn test_incremental_check() {
+    use kailua_env::{Source, SourceFile};
+    use kailua_diag::CollectedReport;
+    use kailua_syntax::parse_chunk;
+    use kailua_check::{self, env::Context, options::FsOptions};
+    use sys::FakeFileSystem;
+
+    struct MemoryFsSource {
+        source: Rc<RefCell<Source>>,
+        files: Rc<RefCell<HashMap<PathBuf, String>>>,
+    }
+
+    impl FsSource for MemoryFsSource {
+        fn chunk_from_path(&self, resolved_path: Spanned<&Path>,
+                           report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+            if let Some(code) = self.files.borrow().get(resolved_path.base) {
+                let file = SourceFile::from_u8(resolved_path.display().to_string(),
+                                               code.as_bytes().to_owned());
+                let span = self.source.borrow_mut().add(file);
+                Ok(Some(parse_chunk(&self.source.borrow(), span, report).map_err(Some)?))
+            } else {
+                Ok(None)
+            }
+        }
+    }
+
+    let source = Rc::new(RefCell::new(Source::new()));
+    let files = Rc::new(RefCell::new(HashMap::new()));
+    let state = Rc::new(RefCell::new(IncrementalState::new()));
+    files.borrow_mut().insert(PathBuf::from("b.lua"), "return { x = 42 }".to_owned());
+    files.borrow_mut().insert(PathBuf::from("a.lua"), "local b = require 'b'\n\
+                                                       local M = {}\n\
+                                                       --v function() --> integer\n\
+                                                       function M.f() return b.x end\n\
+                                                       return M".to_owned());
+    files.borrow_mut().insert(PathBuf::from("main.lua"), "--# open lua51\n\
+                                                          local a = require 'a'\n\
+                                                          local _x = a.f() --: integer".to_owned());
+
+    // returns the number of reports and stubbed paths for each check until completion
+    let check = || {
+        let mut rounds = Vec::new();
+        loop {
+            let fssource = MemoryFsSource { source: source.clone(), files: files.clone() };
+            let report = TrackReportedUnits::new(CollectedReport::new(Locale::dummy()));
+            let chunk = fssource.chunk_from_path(Path::new("main.lua").without_loc(), &report)
+                                .unwrap().unwrap();
+            let mut fssource = IncrementalSource::new(fssource, state.clone());
+            let hash_source = source.clone();
+            fssource.set_content_hash(move |unit| {
+                hash_source.borrow().file(unit).map(|f| hash_contents(f.data()))
+            });
+            let opts = RecordingOptions::new(FsOptions::new(fssource, PathBuf::new()));
+            let opts = Rc::new(RefCell::new(opts));
+            let mut context = Context::new(report);
+            let _ = kailua_check::check_from_chunk(&mut context, chunk, opts.clone());
+
+            let reported = context.report().units();
+            let recheck = {
+                let mut state = state.borrow_mut();
+                let stubbed = state.stubbed_paths();
+                rounds.push((reported.len(), stubbed));
+                state.finish_check(&opts.borrow(), &context, &reported)
+            };
+            if !recheck {
+                return rounds;
+            }
+        }
+    };
+    let paths = |paths: &[&str]| paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+    // everything is checked at first, and stubbed later
+    assert_eq!(check(), vec![(0, paths(&[]))]);
+    assert_eq!(check(), vec![(0, paths(&["a.lua", "b.lua"]))]);
+
+    // the signature of `b` doesn't change
+    files.borrow_mut().insert(PathBuf::from("b.lua"),
+                              "local _y = 54\nreturn { x = 42 }".to_owned());
+    state.borrow_mut().mark_changed(Path::new("b.lua"));
+    assert_eq!(check(), vec![(0, paths(&["a.lua"]))]);
+
+    // the signature of `b` changes, which is only known after `a` has been stubbed
+    files.borrow_mut().insert(PathBuf::from("b.lua"), "return { x = 54 }".to_owned());
+    state.borrow_mut().mark_changed(Path::new("b.lua"));
+    assert_eq!(check(), vec![(0, paths(&["a.lua"])), (0, paths(&["b.lua"]))]);
+
+    // the signature of `a` changes, and `main` (which is always checked) sees the change
+    files.borrow_mut().insert(PathBuf::from("a.lua"), "local M = {}\n\
+                                                       --v function() --> string\n\
+                                                       function M.f() return 'x' end\n\
+                                                       return M".to_owned());
+    state.borrow_mut().mark_changed(Path::new("a.lua"));
+    assert_eq!(check(), vec![(1, paths(&[]))]);
+    assert_eq!(check(), vec![(1, paths(&["a.lua"]))]);
+
+    // saved states are only valid for the same fingerprint
+    let fs = FakeFileSystem::new();
+    let cache_path = Path::new("/ws/.kailua-cache/main.json");
+    state.borrow_mut().save(&fs, cache_path, 42).unwrap();
+    let loaded = IncrementalState::load(&fs, cache_path, 42).unwrap();
+    let mismatch = IncrementalState::load(&fs, cache_path, 54).unwrap();
+    assert!(loaded.is_stubbable(Path::new("a.lua")));
+    assert!(!mismatch.is_stubbable(Path::new("a.lua")));
+
+    // changes not reported by `mark_changed` are still detected from hashes
+    *state.borrow_mut() = loaded;
+    files.borrow_mut().insert(PathBuf::from("a.lua"), "return {}".to_owned());
+    assert_eq!(check(), vec![(1, paths(&[]))]);
+}
+
+#[test]
+fn test_global_effects() {
+    use kailua_env::{Source, SourceFile};
+    use kailua_syntax::parse_chunk;
+
+    let has_global_effects = |code: &str| {
+        let mut source = Source::new();
+        let span = source.add(SourceFile::from_u8(String::new(), code.as_bytes().to_owned()));
+        let chunk = parse_chunk(&source, span, &NoReport).unwrap();
+        block_has_global_effects(&chunk.block)
+    };
+
+    assert!(!has_global_effects("local M = {}\nfunction M.f() local x = 1; x = 2 end\nreturn M"));
+    assert!(!has_global_effects("local x = string.format('%d', 42)"));
+    assert!(has_global_effects("x = 1"));
+    assert!(has_global_effects("local function f() string.x = 1 end"));
+    assert!(has_global_effects("function f() end"));
+    assert!(has_global_effects("module('m')"));
+    assert!(has_global_effects("--# type global T = integer"));
+    assert!(has_global_effects("--# assume global x: integer"));
+}
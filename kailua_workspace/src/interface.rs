@@ -0,0 +1,296 @@
+//! Externally visible interfaces of checked modules.
+//!
+//! A module is visible to others through the value returned to `require`
+//! (typically a table of functions), global variables it defines,
+//! and types it exports with `--# type` (without `local` or `global`).
+//! `ModuleInterface` collects all of them with types formatted as strings,
+//! so that IDE features, caches and declaration generators can consume
+//! the result of the checking without retaining the whole type context.
+//!
+//! Fields of the returned table are given the span where they are first defined,
+//! as long as the module returns a table constructor or a variable holding a table
+//! (e.g. `local M = {} ... function M.f() end ... return M`).
+
+use std::collections::HashMap;
+use serde_json::Value;
+use kailua_env::{Span, Spanned, Source};
+use kailua_diag::{Locale, Localized};
+use kailua_syntax::ast::{Chunk, Block, St, Ex, Exp, Var, NameRef, Table};
+use kailua_types::ty::{TypeContext, Display, Key};
+use kailua_check::env::{Output, Id, Module};
+
+/// A named item in the module interface.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InterfaceItem {
+    pub name: String,
+
+    /// The type, formatted.
+    pub ty: String,
+
+    /// The span where the item is defined; dummy if unknown.
+    pub span: Span,
+}
+
+/// An externally visible part of a module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleInterface {
+    /// The module name given to `require`.
+    pub name: String,
+
+    /// The type returned to `require`, formatted. `None` if the module never returns.
+    pub returns: Option<String>,
+
+    /// Fields of the returned value, if it has fields. Sorted by names.
+    pub fields: Vec<InterfaceItem>,
+
+    /// Global variables defined in the module source. Sorted by names.
+    pub globals: Vec<InterfaceItem>,
+
+    /// Types exported with `--# type`. Sorted by names.
+    pub types: Vec<InterfaceItem>,
+}
+
+impl ModuleInterface {
+    /// Extracts an interface of the module `name` loaded by `require` from the checker output.
+    ///
+    /// `chunk` should be the parsed module source, which is used to locate fields and globals.
+    /// Types are formatted in given locale.
+    /// Returns `None` if the module has not been (successfully) loaded.
+    pub fn from_output(output: &Output, name: &[u8], chunk: &Chunk,
+                       locale: Locale) -> Option<ModuleInterface> {
+        output.get_module(name).map(|module| {
+            ModuleInterface::new(output, name, module, &chunk.block, locale)
+        })
+    }
+
+    /// Same to `from_output` but with an explicit module information.
+    pub fn new(output: &Output, name: &[u8], module: &Module, block: &Spanned<Block>,
+               locale: Locale) -> ModuleInterface {
+        let types = output.types() as &TypeContext;
+        let lossy = |s: &[u8]| String::from_utf8_lossy(s).into_owned();
+
+        let mut fields = Vec::new();
+        if let Some(ref returns) = module.returns {
+            if let Some(available) = output.get_available_fields(&returns.unlift()) {
+                let spans = find_field_spans(block);
+                for (key, slot) in available {
+                    let (name, span) = match key {
+                        Key::Str(ref s) => (lossy(s), spans.get(&s[..]).cloned()),
+                        Key::Int(i) => (i.to_string(), None),
+                    };
+                    fields.push(InterfaceItem {
+                        name: name,
+                        ty: format(&slot, types, locale),
+                        span: span.unwrap_or(Span::dummy()),
+                    });
+                }
+            }
+        }
+
+        let unit = block.span.unit();
+        let mut globals = Vec::new();
+        for (id, def) in output.all() {
+            if let Id::Global(ref name) = *id {
+                if def.span.unit() != unit {
+                    continue;
+                }
+                if let Some(slot) = def.slot.slot() {
+                    globals.push(InterfaceItem {
+                        name: lossy(name),
+                        ty: format(slot, types, locale),
+                        span: def.span,
+                    });
+                }
+            }
+        }
+
+        let mut exported = Vec::new();
+        for (name, def) in &module.exported_types {
+            exported.push(InterfaceItem {
+                name: lossy(name),
+                ty: format(&def.ty, types, locale),
+                span: def.span,
+            });
+        }
+
+        fields.sort_by(|a, b| a.name.cmp(&b.name));
+        globals.sort_by(|a, b| a.name.cmp(&b.name));
+        exported.sort_by(|a, b| a.name.cmp(&b.name));
+
+        ModuleInterface {
+            name: lossy(name),
+            returns: module.returns.as_ref().map(|returns| format(returns, types, locale)),
+            fields: fields,
+            globals: globals,
+            types: exported,
+        }
+    }
+
+    /// Exports the interface as a JSON object.
+    ///
+    /// Each item is a `{"name": ..., "type": ..., "line": ..., "column": ...}` object,
+    /// where lines and columns start from 1 and are `null` when the position is unknown.
+    pub fn to_json(&self, source: &Source) -> Value {
+        let items = |items: &[InterfaceItem]| {
+            Value::Array(items.iter().map(|item| {
+                let pos = position(source, item.span);
+                let mut obj = ::serde_json::Map::new();
+                obj.insert("name".to_owned(), Value::String(item.name.clone()));
+                obj.insert("type".to_owned(), Value::String(item.ty.clone()));
+                obj.insert("line".to_owned(), pos.map_or(Value::Null, |(l, _)| Value::from(l)));
+                obj.insert("column".to_owned(), pos.map_or(Value::Null, |(_, c)| Value::from(c)));
+                Value::Object(obj)
+            }).collect())
+        };
+
+        let mut obj = ::serde_json::Map::new();
+        obj.insert("name".to_owned(), Value::String(self.name.clone()));
+        obj.insert("returns".to_owned(),
+                   self.returns.as_ref().map_or(Value::Null, |ty| Value::String(ty.clone())));
+        obj.insert("fields".to_owned(), items(&self.fields));
+        obj.insert("globals".to_owned(), items(&self.globals));
+        obj.insert("types".to_owned(), items(&self.types));
+        Value::Object(obj)
+    }
+}
+
+fn format<T: Display>(ty: &T, types: &TypeContext, locale: Locale) -> String {
+    Localized::new(&ty.display(types), locale).to_string()
+}
+
+// returns a 1-based line and column (in bytes) of the span
+fn position(source: &Source, span: Span) -> Option<(usize, usize)> {
+    let file = source.file(span.unit())?;
+    let (line, linespan) = file.line_from_pos(span.begin())?;
+    Some((line + 1, span.begin().to_usize() - linespan.begin().to_usize() + 1))
+}
+
+// returns spans to the first definitions of fields in the returned table
+fn find_field_spans(block: &Spanned<Block>) -> HashMap<Vec<u8>, Span> {
+    let mut spans = HashMap::new();
+
+    let returned = block.base.iter().rev().filter_map(|stmt| match *stmt.base {
+        St::Return(ref exps) => exps.base.first(),
+        _ => None,
+    }).next();
+    match returned.map(|exp| &*exp.base) {
+        Some(&Ex::Table(ref table)) => add_table_spans(table, &mut spans),
+        Some(&Ex::Var(ref root)) => add_var_field_spans(&root.base, block, &mut spans),
+        _ => {}
+    }
+
+    spans
+}
+
+fn add_table_spans(table: &Table, spans: &mut HashMap<Vec<u8>, Span>) {
+    for &(ref key, _) in &table.items {
+        if let Some(ref key) = *key {
+            if let Ex::Str(ref s) = *key.base {
+                spans.entry(s.to_vec()).or_insert(key.span);
+            }
+        }
+    }
+}
+
+fn is_var(exp: &Spanned<Exp>, root: &NameRef) -> bool {
+    match *exp.base {
+        Ex::Var(ref name) => name.base == *root,
+        _ => false,
+    }
+}
+
+// scans statements outside of functions for the table constructor and fields assigned to `root`
+fn add_var_field_spans(root: &NameRef, block: &Block, spans: &mut HashMap<Vec<u8>, Span>) {
+    for stmt in block {
+        match *stmt.base {
+            St::Local(ref names, ref exps, _) => {
+                for (name, exp) in names.base.iter().zip(exps.base.iter()) {
+                    if let (&Ex::Table(ref table), &NameRef::Local(ref id)) = (&*exp.base, root) {
+                        if name.base.base == *id {
+                            add_table_spans(table, spans);
+                        }
+                    }
+                }
+            }
+
+            St::Assign(ref vars, ref exps) => {
+                for (i, var) in vars.base.iter().enumerate() {
+                    match var.base.base {
+                        Var::Name(ref name) if name.base == *root => {
+                            let exp = exps.as_ref().and_then(|exps| exps.base.get(i));
+                            if let Some(&Ex::Table(ref table)) = exp.map(|exp| &*exp.base) {
+                                add_table_spans(table, spans);
+                            }
+                        }
+                        Var::IndexName(ref e, ref name) if is_var(e, root) => {
+                            spans.entry(name.base.to_vec()).or_insert(name.span);
+                        }
+                        Var::Index(ref e, ref key) if is_var(e, root) => {
+                            if let Ex::Str(ref s) = *key.base {
+                                spans.entry(s.to_vec()).or_insert(key.span);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            St::MethodDecl(ref path, ..) |
+            St::KailuaAssumeField(_, _, ref path, ..) |
+            St::KailuaAssumeMethod(_, ref path, ..) => {
+                let (ref name, ref fields) = path.base;
+                if name.base == *root && fields.len() == 1 {
+                    spans.entry(fields[0].base.to_vec()).or_insert(fields[0].span);
+                }
+            }
+
+            St::Do(ref block) => add_var_field_spans(root, block, spans),
+            St::If(ref conds, ref lastblock) => {
+                for cond in conds {
+                    add_var_field_spans(root, &cond.base.1, spans);
+                }
+                if let Some(ref block) = *lastblock {
+                    add_var_field_spans(root, block, spans);
+                }
+            }
+
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_module_interface() {
+    use kailua_diag::NoReport;
+    use kailua_syntax::parse_chunk;
+    use testutils::check_test_module;
+
+    let code = "--# type Point = { x: number, y: number }\n\
+                local M = {} --: module\n\
+                --v function(p: Point) --> number\n\
+                function M.norm(p) return p.x + p.y end\n\
+                M.origin = { x = 0, y = 0 } --: Point\n\
+                M.version = 'x'\n\
+                counter = 0\n\
+                return M\n";
+    let (context, source, span) = check_test_module("/a.lua", "a", code);
+
+    let chunk = parse_chunk(&source, span, &NoReport).unwrap();
+    assert_eq!(ModuleInterface::from_output(&context, b"b", &chunk, Locale::dummy()), None);
+    let iface = ModuleInterface::from_output(&context, b"a", &chunk, Locale::dummy()).unwrap();
+    assert_eq!(iface.name, "a");
+    assert_eq!(iface.fields.iter().map(|f| &f.name[..]).collect::<Vec<_>>(),
+               ["norm", "origin", "version"]);
+    assert_eq!(iface.fields[0].ty, "function(p: Point) --> number");
+    assert_eq!(iface.globals.iter().map(|f| &f.name[..]).collect::<Vec<_>>(), ["counter"]);
+    assert_eq!(iface.types.iter().map(|f| &f.name[..]).collect::<Vec<_>>(), ["Point"]);
+
+    let json = iface.to_json(&source);
+    assert_eq!(json["fields"][0]["line"], 4);
+    assert_eq!(json["fields"][0]["column"], 12);
+    assert_eq!(json["fields"][1]["line"], 5);
+    assert_eq!(json["fields"][2]["line"], 6);
+    assert_eq!(json["fields"][2]["column"], 3);
+    assert_eq!(json["globals"][0]["line"], 7);
+    assert_eq!(json["types"][0]["line"], 1);
+}
@@ -0,0 +1,375 @@
+//! A lenient JSON parser for configuration files.
+//!
+//! In addition to the standard JSON, this accepts a subset of JSON5 that we really need to:
+//! comments (`// ...` and `/* ... */`), trailing commas in arrays and objects,
+//! unquoted identifier keys and single-quoted strings.
+//! Every error is reported with a byte offset to the input, so that it can be located.
+
+use std::fmt;
+use std::error::Error;
+use std::collections::HashMap;
+use serde_json::{Map, Number, Value};
+
+/// A parsing error with the position in the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonError {
+    /// The byte offset to the input.
+    pub offset: usize,
+
+    /// The 1-based line number.
+    pub line: usize,
+
+    /// The 1-based column number, counted in Unicode scalar values.
+    pub column: usize,
+
+    /// The description of the error.
+    pub message: String,
+}
+
+impl JsonError {
+    pub fn new(input: &str, offset: usize, message: String) -> JsonError {
+        let (line, column) = line_and_column(input, offset);
+        JsonError { offset: offset, line: line, column: column, message: message }
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {} column {}", self.message, self.line, self.column)
+    }
+}
+
+impl Error for JsonError {
+    fn description(&self) -> &str { &self.message }
+}
+
+/// Returns the 1-based line and column numbers for given byte offset.
+pub fn line_and_column(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset.min(input.len())];
+    let line = before.matches('\n').count() + 1;
+    let linestart = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[linestart..].chars().count() + 1;
+    (line, column)
+}
+
+/// The maximum number of arrays and objects a value can be nested in.
+///
+/// Configurations never need this much, and deeper values would overflow the stack.
+pub const MAX_DEPTH: usize = 128;
+
+/// A parsed JSON document.
+#[derive(Clone, Debug)]
+pub struct Document {
+    /// The parsed value.
+    pub value: Value,
+
+    /// Byte offsets to the values of the top-level object, if the value is an object.
+    pub offsets: HashMap<String, usize>,
+}
+
+/// Parses a JSON document.
+pub fn parse(input: &str) -> Result<Document, JsonError> {
+    let mut parser = Parser { input: input, pos: 0, offsets: HashMap::new() };
+    let value = parser.parse_value(0)?;
+    parser.skip_whitespaces()?;
+    if parser.pos < input.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(Document { value: value, offsets: parser.offsets })
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    offsets: HashMap<String, usize>,
+}
+
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c == '$' || c.is_alphabetic()
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c == '_' || c == '$' || c.is_alphanumeric()
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> JsonError {
+        JsonError::new(self.input, self.pos, message.to_owned())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        if let Some(c) = c {
+            self.pos += c.len_utf8();
+        }
+        c
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        if self.peek() == Some(expected) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", expected)))
+        }
+    }
+
+    fn skip_whitespaces(&mut self) -> Result<(), JsonError> {
+        loop {
+            let rest = &self.input[self.pos..];
+            if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if rest.starts_with("/*") {
+                if let Some(i) = rest[2..].find("*/") {
+                    self.pos += i + 4;
+                } else {
+                    return Err(self.error("unterminated comment"));
+                }
+            } else if let Some(c) = self.peek() {
+                if c.is_whitespace() || c == '\u{feff}' {
+                    self.bump();
+                } else {
+                    return Ok(());
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<Value, JsonError> {
+        self.skip_whitespaces()?;
+        if depth > MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+        match self.peek() {
+            Some('{') => self.parse_object(depth),
+            Some('[') => self.parse_array(depth),
+            Some(q @ '"') | Some(q @ '\'') => Ok(Value::String(self.parse_string(q)?)),
+            Some(c) if c == '-' || c == '+' || c.is_digit(10) => self.parse_number(),
+            Some(c) if is_ident_start(c) => {
+                let start = self.pos;
+                match &self.parse_ident()[..] {
+                    "null" => Ok(Value::Null),
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => Err(JsonError::new(self.input, start, "unexpected identifier".to_owned())),
+                }
+            }
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<Value, JsonError> {
+        self.expect('{')?;
+        let mut map = Map::new();
+        loop {
+            self.skip_whitespaces()?;
+            let key = match self.peek() {
+                Some('}') => { self.bump(); break; }
+                Some(q @ '"') | Some(q @ '\'') => self.parse_string(q)?,
+                Some(c) if is_ident_start(c) => self.parse_ident(),
+                _ => return Err(self.error("expected a key or `}`")),
+            };
+
+            self.skip_whitespaces()?;
+            self.expect(':')?;
+            self.skip_whitespaces()?;
+            if depth == 0 {
+                self.offsets.insert(key.clone(), self.pos);
+            }
+            let value = self.parse_value(depth + 1)?;
+            map.insert(key, value);
+
+            self.skip_whitespaces()?;
+            match self.peek() {
+                Some(',') => { self.bump(); } // the trailing comma is handled by the next loop
+                Some('}') => { self.bump(); break; }
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+        Ok(Value::Object(map))
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<Value, JsonError> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespaces()?;
+            if self.peek() == Some(']') {
+                self.bump();
+                break;
+            }
+
+            values.push(self.parse_value(depth + 1)?);
+
+            self.skip_whitespaces()?;
+            match self.peek() {
+                Some(',') => { self.bump(); }
+                Some(']') => { self.bump(); break; }
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn parse_ident(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !is_ident_continue(c) { break; }
+            self.bump();
+        }
+        self.input[start..self.pos].to_owned()
+    }
+
+    fn parse_string(&mut self, quote: char) -> Result<String, JsonError> {
+        self.expect(quote)?;
+        let mut s = String::new();
+        loop {
+            let start = self.pos;
+            match self.bump() {
+                Some(c) if c == quote => return Ok(s),
+                Some('\\') => {
+                    let c = match self.bump() {
+                        Some('"') => '"',
+                        Some('\'') => '\'',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let hi = self.parse_hex4(start)?;
+                            if hi >= 0xd800 && hi < 0xdc00 {
+                                // needs a low surrogate
+                                if !self.input[self.pos..].starts_with("\\u") {
+                                    return Err(JsonError::new(self.input, start,
+                                                              "unpaired surrogate".to_owned()));
+                                }
+                                self.pos += 2;
+                                let lo = self.parse_hex4(start)?;
+                                if lo < 0xdc00 || lo >= 0xe000 {
+                                    return Err(JsonError::new(self.input, start,
+                                                              "unpaired surrogate".to_owned()));
+                                }
+                                let c = 0x10000 + ((hi - 0xd800) << 10) + (lo - 0xdc00);
+                                ::std::char::from_u32(c).unwrap()
+                            } else if let Some(c) = ::std::char::from_u32(hi) {
+                                c
+                            } else {
+                                return Err(JsonError::new(self.input, start,
+                                                          "unpaired surrogate".to_owned()));
+                            }
+                        }
+                        _ => {
+                            return Err(JsonError::new(self.input, start,
+                                                      "invalid escape sequence".to_owned()));
+                        }
+                    };
+                    s.push(c);
+                }
+                Some('\n') | Some('\r') => {
+                    self.pos = start;
+                    return Err(self.error("unterminated string"));
+                }
+                Some(c) => s.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self, escape_start: usize) -> Result<u32, JsonError> {
+        let end = self.pos + 4;
+        let digits = self.input.get(self.pos..end);
+        match digits.and_then(|s| u32::from_str_radix(s, 16).ok()) {
+            Some(v) if !digits.unwrap().starts_with('+') => {
+                self.pos = end;
+                Ok(v)
+            }
+            _ => Err(JsonError::new(self.input, escape_start,
+                                    "invalid escape sequence".to_owned())),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, JsonError> {
+        let start = self.pos;
+        if self.peek() == Some('+') || self.peek() == Some('-') {
+            self.bump();
+        }
+        let mut is_float = false;
+        while let Some(c) = self.peek() {
+            match c {
+                c if c.is_digit(10) => {}
+                '.' | 'e' | 'E' => { is_float = true; }
+                '+' | '-' if is_float => {}
+                _ => break,
+            }
+            self.bump();
+        }
+
+        let text = self.input[start..self.pos].trim_left_matches('+');
+        let number = if is_float {
+            text.parse::<f64>().ok().and_then(Number::from_f64)
+        } else if text.starts_with('-') {
+            text.parse::<i64>().ok().map(Number::from)
+        } else {
+            text.parse::<u64>().ok().map(Number::from)
+        };
+        if let Some(number) = number {
+            Ok(Value::Number(number))
+        } else {
+            Err(JsonError::new(self.input, start, "invalid number".to_owned()))
+        }
+    }
+}
+
+#[test]
+fn test_parse() {
+    fn p(s: &str) -> Value { parse(s).unwrap().value }
+
+    assert_eq!(p("[3, 4/*5*/, 6]"), json!([3, 4, 6]));
+    assert_eq!(p("[3, 4//5, 6]\n, 7]"), json!([3, 4, 7]));
+    assert_eq!(p(r#"[3, "4//5", "/*6*/"]"#), json!([3, "4//5", "/*6*/"]));
+    assert_eq!(p("[3, 4, 5,\n/*wat*/\n// ???\n]"), json!([3, 4, 5]));
+    assert_eq!(p("{a: 1, 'b': '\"', \"c\": [-2.5e1,],}"), json!({"a": 1, "b": "\"", "c": [-25.0]}));
+    assert_eq!(p(r#""é🌴\n""#), json!("\u{e9}\u{1f334}\n"));
+    assert_eq!(p("\u{feff} null "), Value::Null);
+}
+
+#[test]
+fn test_parse_offsets() {
+    let doc = parse("{\n  // comment\n  start_path: \"a.lua\",\n  x: {y: 1}\n}").unwrap();
+    assert_eq!(doc.offsets.get("start_path"), Some(&29));
+    assert_eq!(doc.offsets.get("x"), Some(&43));
+    assert_eq!(doc.offsets.get("y"), None);
+}
+
+#[test]
+fn test_parse_errors() {
+    fn e(s: &str) -> (usize, usize, usize) {
+        let e = parse(s).unwrap_err();
+        (e.offset, e.line, e.column)
+    }
+
+    assert_eq!(e("[1,,2]"), (3, 1, 4));
+    assert_eq!(e("{\n  \"a\": 1\n  \"b\": 2\n}"), (13, 3, 3));
+    assert_eq!(e("/* \u{e9} */ [1 2]"), (12, 1, 12));
+    assert_eq!(e("[1, /* unterminated"), (4, 1, 5));
+    assert_eq!(e("\"abc"), (4, 1, 5));
+    assert_eq!(e("[nul]"), (1, 1, 2));
+    assert_eq!(e("{} {}"), (3, 1, 4));
+
+    let nested = |n: usize| format!("{}{}", "[".repeat(n), "]".repeat(n));
+    assert!(parse(&nested(MAX_DEPTH + 1)).is_ok());
+    assert_eq!(e(&nested(MAX_DEPTH + 2)), (MAX_DEPTH + 1, 1, MAX_DEPTH + 2));
+    assert_eq!(e(&"{a:".repeat(MAX_DEPTH + 1)), (3 * MAX_DEPTH + 3, 1, 3 * MAX_DEPTH + 4));
+    // should not overflow the stack
+    assert_eq!(parse(&"[".repeat(200000)).unwrap_err().message, "too deeply nested");
+}
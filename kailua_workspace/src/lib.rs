@@ -6,26 +6,68 @@
 
 extern crate serde;
 #[macro_use] extern crate serde_derive;
-extern crate serde_json;
+#[cfg_attr(test, macro_use)] extern crate serde_json;
 #[macro_use] extern crate parse_generics_shim;
-extern crate regex;
-#[macro_use] extern crate lazy_static;
+extern crate walkdir;
 extern crate kailua_env;
 #[macro_use] extern crate kailua_diag;
 extern crate kailua_syntax;
+extern crate kailua_types;
 extern crate kailua_check;
 
+use std::str;
+use std::mem;
 use std::error::Error;
-use std::io::{self, Read};
-use std::fs::File;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::collections::{HashMap, BTreeMap};
 use kailua_env::{Spanned, WithLoc};
 use kailua_diag::{Report, NoReport, Reporter, Stop, Locale};
 use kailua_syntax::Chunk;
-use kailua_check::Preload;
-use kailua_check::options::{Options, FsSource, FsOptions};
+use kailua_syntax::lang::{Language, Lua, Kailua};
+use kailua_check::{Preload, Constant, Assertion};
+use kailua_check::options::{Options, FsSource, FsOptions, DynamicRequire};
+use kailua_check::options::{DEFAULT_LITERAL_UNION_LIMIT, DEFAULT_TABLE_DEPTH_LIMIT};
+use kailua_check::options::DEFAULT_CONSTRAINT_LIMIT;
+use kailua_check::lint::{Severity, find_lint, is_diagnostic_code};
+use profile::Profile;
+use sys::{Sys, FileSystem};
 
 mod message;
+mod recording;
+pub mod assumptions;
+pub mod data;
+pub mod declaration;
+pub mod doctor;
+pub mod edit;
+pub mod fix;
+pub mod graph;
+pub mod hotspots;
+pub mod incremental;
+pub mod interface;
+pub mod json;
+pub mod migrate;
+pub mod paths;
+pub mod preparse;
+pub mod profile;
+pub mod repro;
+pub mod scaffold;
+pub mod set;
+pub mod signatures;
+pub mod subset;
+pub mod symbols;
+pub mod sys;
+#[cfg(test)] mod testutils;
+
+/// Search paths specific to a start path, overriding workspace-wide values.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct StartPathOptions {
+    /// The explicit value of `package.path` for this start path, if any.
+    pub package_path: Option<Vec<u8>>,
+
+    /// The explicit value of `package.cpath` for this start path, if any.
+    pub package_cpath: Option<Vec<u8>>,
+}
 
 /// A configuration being built.
 ///
@@ -35,6 +77,13 @@ mod message;
 /// (currently `BASE_DIR/kailua.json` and `BASE_DIR/.vscode/kailua.json`),
 /// so the caller can simply call `Config::use_default_config_paths`
 /// when no configuration file is given.
+///
+/// There may be also a per-user global configuration file
+/// (see `default_global_config_path` for its location) providing defaults for every workspace.
+/// It has the same format except that `start_path` is not allowed.
+/// The workspace configuration overrides the global configuration in the same way
+/// as it overrides included configurations: objects are merged key by key
+/// and other values are replaced. The global configuration should be read first.
 #[derive(Clone, Debug)]
 pub struct Config {
     /// A base dir (the workspace root).
@@ -43,9 +92,24 @@ pub struct Config {
     /// A path to the configuration file, if read. Used for diagnostics.
     config_path: Option<PathBuf>,
 
+    /// A path to the global configuration file and its contents, if read.
+    global_config: Option<(PathBuf, serde_json::Value)>,
+
+    /// All configurations applied so far, merged into a single JSON object.
+    applied: serde_json::Value,
+
+    /// Values given to `set_override` so far, merged into a single JSON object.
+    overrides: serde_json::Value,
+
+    /// Warnings from reading the configuration file, e.g. migrations from older versions.
+    warnings: Vec<String>,
+
     /// Paths to the start file, if any.
     pub start_paths: Vec<PathBuf>,
 
+    /// Per-start-path options, keyed by paths in `start_paths`.
+    pub start_path_options: HashMap<PathBuf, StartPathOptions>,
+
     /// The explicit value of `package.path`, if any.
     ///
     /// If this value is set, assigning to `package.path` does *not* change
@@ -58,11 +122,83 @@ pub struct Config {
     /// the checker's behavior and will rather issue an warning.
     pub package_cpath: Option<Vec<u8>>,
 
+    /// Directories of definition bundles, in the order of preference.
+    ///
+    /// A bundle is a directory of `.kailua` files named after modules
+    /// (`require "a.b"` loads `DIR/a/b.kailua`), which are searched before any search path.
+    /// This is useful for sharing type stubs for third-party libraries across projects.
+    pub definitions: Vec<PathBuf>,
+
+    /// Module name aliases applied to `require` before searching the paths.
+    ///
+    /// This is useful when the module names are rewritten at the build time.
+    pub module_aliases: HashMap<Vec<u8>, Vec<u8>>,
+
+    /// The minimum number of table entries for data files, if they should be summarized.
+    ///
+    /// See the `data` module for details.
+    pub data_file_min_entries: Option<usize>,
+
+    /// The version of Lua being checked (Lua 5.1 by default).
+    ///
+    /// See `Options::lua_version` for the exact behavior.
+    pub lua_version: Lua,
+
+    /// The strictness profile, if any.
+    ///
+    /// The profile has been already applied to `strict_nil`, `strict_globals`
+    /// and `lint_severities`, so this is only informative.
+    pub profile: Option<Profile>,
+
+    /// True if the strict nil-safety mode is enabled.
+    ///
+    /// See `Options::strict_nil` for the exact behavior.
+    pub strict_nil: bool,
+
+    /// True if the strict globals mode is enabled.
+    ///
+    /// See `Options::strict_globals` for the exact behavior.
+    pub strict_globals: bool,
+
+    /// How `require` with a non-literal module name is checked.
+    ///
+    /// See `Options::dynamic_require` for the exact behavior.
+    pub dynamic_require: DynamicRequire,
+
+    /// The maximum number of literals in a union type, if it should differ from the default.
+    ///
+    /// See `Options::literal_union_limit` for the exact behavior.
+    pub literal_union_limit: Option<usize>,
+
+    /// The maximum nesting depth of table constructors, if it should differ from the default.
+    ///
+    /// See `Options::table_depth_limit` for the exact behavior.
+    pub table_depth_limit: Option<usize>,
+
+    /// The maximum number of type constraints, if it should differ from the default.
+    ///
+    /// See `Options::constraint_limit` for the exact behavior.
+    pub constraint_limit: Option<usize>,
+
+    /// Lint severities overriding the defaults, keyed by the lint code.
+    ///
+    /// See `kailua_check::lint` for the list of lints.
+    /// Diagnostic codes like `KC0123` are also accepted (see `Options::code_severity`).
+    pub lint_severities: BTreeMap<String, Severity>,
+
     /// Preloading options.
     pub preload: Preload,
 
+    /// Workspace-level invariants verified after checking.
+    pub asserts: Vec<Assertion>,
+
     /// A preferred message locale, if any.
+    ///
+    /// This may have fallback locales if `message_lang` is given as a list.
     pub message_locale: Option<Locale>,
+
+    /// The clock and the file system, which will be also used by the workspace.
+    sys: Sys,
 }
 
 impl Config {
@@ -71,11 +207,30 @@ impl Config {
         Config {
             base_dir: base_dir,
             config_path: None,
+            global_config: None,
+            applied: serde_json::Value::Object(serde_json::Map::new()),
+            overrides: serde_json::Value::Object(serde_json::Map::new()),
+            warnings: Vec::new(),
             start_paths: vec![start_path],
+            start_path_options: HashMap::new(),
             package_path: None,
             package_cpath: None,
+            definitions: Vec::new(),
+            module_aliases: HashMap::new(),
+            data_file_min_entries: None,
+            lua_version: Lua::Lua51,
+            profile: None,
+            strict_nil: false,
+            strict_globals: false,
+            dynamic_require: DynamicRequire::Warn,
+            literal_union_limit: None,
+            table_depth_limit: None,
+            constraint_limit: None,
+            lint_severities: BTreeMap::new(),
             preload: Preload::default(),
+            asserts: Vec::new(),
             message_locale: None,
+            sys: Sys::real(),
         }
     }
 
@@ -83,11 +238,30 @@ impl Config {
         Config {
             base_dir: base_dir,
             config_path: None,
+            global_config: None,
+            applied: serde_json::Value::Object(serde_json::Map::new()),
+            overrides: serde_json::Value::Object(serde_json::Map::new()),
+            warnings: Vec::new(),
             start_paths: Vec::new(),
+            start_path_options: HashMap::new(),
             package_path: None,
             package_cpath: None,
+            definitions: Vec::new(),
+            module_aliases: HashMap::new(),
+            data_file_min_entries: None,
+            lua_version: Lua::Lua51,
+            profile: None,
+            strict_nil: false,
+            strict_globals: false,
+            dynamic_require: DynamicRequire::Warn,
+            literal_union_limit: None,
+            table_depth_limit: None,
+            constraint_limit: None,
+            lint_severities: BTreeMap::new(),
             preload: Preload::default(),
+            asserts: Vec::new(),
             message_locale: None,
+            sys: Sys::real(),
         }
     }
 
@@ -97,30 +271,230 @@ impl Config {
         self.config_path.as_ref().map(|p| &**p)
     }
 
+    pub fn config_warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn sys(&self) -> &Sys { &self.sys }
+
+    /// Replaces the clock and the file system. Should be called before reading any file.
+    pub fn set_sys(&mut self, sys: Sys) {
+        self.sys = sys;
+    }
+
+    pub fn global_config_path(&self) -> Option<&Path> {
+        self.global_config.as_ref().map(|&(ref p, _)| &**p)
+    }
+
     pub fn set_config_path(&mut self, path: PathBuf) -> io::Result<bool> {
         if self.config_path.is_some() { return Ok(false); }
+        self.read_config(path, false)?;
+        Ok(true)
+    }
+
+    /// Reads the global configuration file, which should be done before `set_config_path`.
+    ///
+    /// Returns `Ok(false)` if any configuration file has been already read.
+    pub fn set_global_config_path(&mut self, path: PathBuf) -> io::Result<bool> {
+        if self.config_path.is_some() || self.global_config.is_some() { return Ok(false); }
+        self.read_config(path, true)?;
+        Ok(true)
+    }
+
+    /// Overrides a single configuration value, as if it were in the configuration file.
+    ///
+    /// The key is a dotted path to the value in the configuration (e.g. `preload.open`),
+    /// and objects in the path are merged with the existing configuration.
+    /// The value is parsed as a JSON value if possible, and used as a string otherwise;
+    /// a single string is also accepted in place of an array for `preload.open`
+    /// and `preload.require`. Overrides are kept even when configuration files are read later,
+    /// so they can be applied in any order. The configuration is not changed on error.
+    ///
+    /// This is intended for command-line options like `--set preload.open=lua51`.
+    pub fn set_override(&mut self, key: &str, value: &str) -> io::Result<()> {
+        use serde_json::{Map, Value};
+
+        const KNOWN_KEYS: &'static [&'static str] = &[
+            "start_path", "package_path", "package_cpath", "definitions", "message_lang",
+            "module_aliases", "data_files", "lua_version", "profile", "strict_nil",
+            "strict_globals", "dynamic_require", "literal_union_limit", "table_depth_limit",
+            "constraint_limit", "severity",
+            "globals", "constants", "preload", "asserts",
+        ];
+
+        let keys: Vec<&str> = key.split('.').collect();
+        if keys.iter().any(|k| k.is_empty()) {
+            return Err(invalid_data(format!("bad configuration key `{}`", key)));
+        }
+        if !KNOWN_KEYS.contains(&keys[0]) {
+            return Err(invalid_data(format!("unknown configuration key `{}`", key)));
+        }
+
+        let value = json::parse(value).map(|doc| doc.value)
+                                      .unwrap_or_else(|_| Value::String(value.to_owned()));
+        let fragment = keys.iter().rev().fold(value, |value, &k| {
+            let mut map = Map::new();
+            map.insert(k.to_owned(), value);
+            Value::Object(map)
+        });
 
+        let mut config = self.clone();
+        merge_json(&mut config.overrides, fragment.clone());
+        merge_json(&mut config.applied, fragment);
+        let applied = config.applied.clone();
+        config.apply_config(applied, &|_, msg| {
+            invalid_data(format!("{} (while overriding `{}`)", msg, key))
+        })?;
+        *self = config;
+        Ok(())
+    }
+
+    fn read_config(&mut self, path: PathBuf, global: bool) -> io::Result<()> {
+        let data = String::from_utf8(self.sys.fs.read(&path)?).map_err(invalid_data)?;
+        let mut doc = json::parse(&data).map_err(invalid_data)?;
+        let warnings = migrate::migrate(&mut doc, migrate::MIGRATIONS,
+                                        migrate::CURRENT_SCHEMA_VERSION);
+        let offsets = doc.offsets;
+
+        // a semantic error is reported with the position of the offending value if possible
+        let invalid_value = |key: &str, msg: &str| {
+            if let Some(&offset) = offsets.get(key) {
+                invalid_data(json::JsonError::new(&data, offset, msg.to_owned()))
+            } else {
+                invalid_data(msg)
+            }
+        };
+
+        let mut warnings = warnings.map_err(|e| invalid_value("schema_version", &e))?;
+        let mut value = doc.value;
+        let fs = &*self.sys.fs;
+        let mut stack = vec![fs.canonicalize(&path).unwrap_or_else(|_| path.clone())];
+        merge_includes(&mut value, &path, fs, &mut stack, &mut warnings).map_err(|e| {
+            invalid_value("include", &e)
+        })?;
+
+        if global {
+            if value.get("start_path").is_some() {
+                let msg = "`start_path` is not allowed in the global configuration";
+                return Err(invalid_value("start_path", msg));
+            }
+        } else {
+            if let Some((_, ref defaults)) = self.global_config {
+                let own = mem::replace(&mut value, defaults.clone());
+                merge_json(&mut value, own);
+            }
+            if value.get("start_path").is_none() && self.overrides.get("start_path").is_none() {
+                return Err(invalid_data("missing field `start_path`"));
+            }
+        }
+        let global_value = if global { Some(value.clone()) } else { None };
+        merge_json(&mut value, self.overrides.clone());
+        self.apply_config(value.clone(), &invalid_value)?;
+
+        if let Some(global_value) = global_value {
+            self.global_config = Some((path, global_value));
+        } else {
+            self.config_path = Some(path);
+        }
+        self.applied = value;
+        self.warnings.extend(warnings);
+        Ok(())
+    }
+
+    // applies a configuration in the JSON value, which has been merged with all other sources
+    fn apply_config(&mut self, value: serde_json::Value,
+                    invalid_value: &Fn(&str, &str) -> io::Error) -> io::Result<()> {
         #[derive(Deserialize, Clone, Debug)]
         struct ConfigData {
-            start_path: StartPath,
+            start_path: Option<StartPath>,
             package_path: Option<String>,
             package_cpath: Option<String>,
-            message_lang: Option<String>,
+            definitions: Option<Vec<PathBuf>>,
+            message_lang: Option<MessageLang>,
+            module_aliases: Option<HashMap<String, String>>,
+            data_files: Option<DataFiles>,
+            lua_version: Option<LuaVersion>,
+            profile: Option<String>,
+            strict_nil: Option<bool>,
+            strict_globals: Option<bool>,
+            dynamic_require: Option<DynamicRequireData>,
+            literal_union_limit: Option<usize>,
+            table_depth_limit: Option<usize>,
+            constraint_limit: Option<usize>,
+            severity: Option<HashMap<String, String>>,
+            globals: Option<HashMap<String, GlobalType>>,
+            constants: Option<HashMap<String, serde_json::Value>>,
             preload: Option<Preload>,
+            asserts: Option<Vec<AssertData>>,
+        }
+
+        #[derive(Deserialize, Clone, Debug)]
+        struct DataFiles {
+            min_entries: usize,
+        }
+
+        // `--set lua_version=5.3` gives a number
+        #[derive(Deserialize, Clone, Debug)]
+        #[serde(untagged)]
+        enum LuaVersion { Str(String), Num(f64) }
+
+        // either `"error"`, `"warn"` or a list of possible module names
+        #[derive(Deserialize, Clone, Debug)]
+        #[serde(untagged)]
+        enum DynamicRequireData { Single(String), Multi(Vec<String>) }
+
+        #[derive(Deserialize, Clone, Debug)]
+        #[serde(untagged)]
+        enum GlobalType { Dynamic(bool), Typed(String) }
+
+        #[derive(Deserialize, Clone, Debug)]
+        struct AssertData {
+            global: Option<String>,
+            module: Option<String>,
+            export: Option<String>,
+            #[serde(rename = "type")] kind: String,
         }
 
         #[derive(Deserialize, Clone, Debug)]
         #[serde(untagged)]
-        enum StartPath { Single(PathBuf), Multi(Vec<PathBuf>) }
+        enum MessageLang { Single(String), Multi(Vec<String>) }
+
+        #[derive(Deserialize, Clone, Debug)]
+        #[serde(untagged)]
+        enum StartPath { Single(StartPathEntry), Multi(Vec<StartPathEntry>) }
+
+        #[derive(Deserialize, Clone, Debug)]
+        #[serde(untagged)]
+        enum StartPathEntry {
+            Path(PathBuf),
+            Options {
+                path: PathBuf,
+                package_path: Option<String>,
+                package_cpath: Option<String>,
+            },
+        }
 
         #[derive(Deserialize, Clone, Debug)]
         struct Preload {
-            #[serde(default)] open: Vec<String>,
-            #[serde(default)] require: Vec<String>,
+            #[serde(default)] open: Strings,
+            #[serde(default)] require: Strings,
         }
 
-        fn invalid_data<E: Into<Box<Error + Send + Sync>>>(e: E) -> io::Error {
-            io::Error::new(io::ErrorKind::InvalidData, e)
+        #[derive(Deserialize, Clone, Debug)]
+        #[serde(untagged)]
+        enum Strings { Single(String), Multi(Vec<String>) }
+
+        impl Default for Strings {
+            fn default() -> Strings { Strings::Multi(Vec::new()) }
+        }
+
+        impl Strings {
+            fn into_vec(self) -> Vec<String> {
+                match self {
+                    Strings::Single(s) => vec![s],
+                    Strings::Multi(ss) => ss,
+                }
+            }
         }
 
         fn verify_search_paths(search_paths: &[u8], start_paths: &[PathBuf]) -> bool {
@@ -136,56 +510,325 @@ impl Config {
             true
         }
 
-        let mut data = String::new();
-        File::open(&path)?.read_to_string(&mut data)?;
-        let data = dehumanize_json(&data);
-        let data: ConfigData = serde_json::de::from_str(&data).map_err(invalid_data)?;
+        fn verify_global_name(name: &str) -> bool {
+            use kailua_env::{Source, SourceFile};
+            use kailua_diag::CollectedReport;
 
-        self.config_path = Some(path);
-        self.start_paths = match data.start_path {
-            StartPath::Single(p) => vec![self.base_dir.join(p)],
-            StartPath::Multi(pp) => pp.into_iter().map(|p| self.base_dir.join(p)).collect(),
-        };
+            // the name should be usable in a single `--# assume global` line
+            if name.contains(|c| c == '\n' || c == '\r') {
+                return false;
+            }
+            let code = format!("--# assume global {}: WHATEVER\n", name);
+            let mut source = Source::new();
+            let span = source.add(SourceFile::from_u8(String::new(), code.into_bytes()));
+            let report = CollectedReport::new(Locale::dummy());
+            kailua_syntax::parse_chunk(&source, span, &report).is_ok() &&
+                report.into_reports().is_empty()
+        }
+
+        let data: ConfigData = serde_json::from_value(value).map_err(invalid_data)?;
+        if let Some(start_paths) = data.start_path {
+            let start_paths = match start_paths {
+                StartPath::Single(p) => vec![p],
+                StartPath::Multi(pp) => pp,
+            };
+            let mut expanded_start_paths = Vec::new();
+            let mut start_path_options = Vec::new();
+            for entry in start_paths {
+                let (p, package_path, package_cpath) = match entry {
+                    StartPathEntry::Path(p) => (p, None, None),
+                    StartPathEntry::Options { path, package_path, package_cpath } => {
+                        (path, package_path, package_cpath)
+                    }
+                };
+                let p = expand_path(&self.base_dir, &p).ok_or_else(|| {
+                    invalid_value("start_path", "cannot expand `~` in `start_path`")
+                })?;
+                if package_path.is_some() || package_cpath.is_some() {
+                    start_path_options.push((p.clone(), package_path, package_cpath));
+                }
+                expanded_start_paths.push(p);
+            }
+            self.start_paths = expanded_start_paths;
+            self.start_path_options = HashMap::new();
+            for (p, package_path, package_cpath) in start_path_options {
+                let verify = |s: Option<String>, key: &str| {
+                    if let Some(s) = s {
+                        let s = expand_search_paths(s.as_bytes()).ok_or_else(|| {
+                            invalid_value("start_path", &format!("cannot expand `~` in `{}`", key))
+                        })?;
+                        if !verify_search_paths(&s, &[p.clone()]) {
+                            return Err(invalid_value("start_path", &format!("bad format for `{}`",
+                                                                            key)));
+                        }
+                        Ok(Some(s))
+                    } else {
+                        Ok(None)
+                    }
+                };
+                let options = StartPathOptions {
+                    package_path: verify(package_path, "package_path")?,
+                    package_cpath: verify(package_cpath, "package_cpath")?,
+                };
+                self.start_path_options.insert(p, options);
+            }
+        }
         self.package_path = if let Some(s) = data.package_path {
-            let s = s.into_bytes();
+            let s = expand_search_paths(s.as_bytes()).ok_or_else(|| {
+                invalid_value("package_path", "cannot expand `~` in `package_path`")
+            })?;
             if !verify_search_paths(&s, &self.start_paths) {
-                return Err(invalid_data("bad format for `package_path`"));
+                return Err(invalid_value("package_path", "bad format for `package_path`"));
             }
             Some(s)
         } else {
             None
         };
         self.package_cpath = if let Some(s) = data.package_cpath {
-            let s = s.into_bytes();
+            let s = expand_search_paths(s.as_bytes()).ok_or_else(|| {
+                invalid_value("package_cpath", "cannot expand `~` in `package_cpath`")
+            })?;
             if !verify_search_paths(&s, &self.start_paths) {
-                return Err(invalid_data("bad format for `package_cpath`"));
+                return Err(invalid_value("package_cpath", "bad format for `package_cpath`"));
             }
             Some(s)
         } else {
             None
         };
+        if let Some(definitions) = data.definitions {
+            self.definitions = Vec::new();
+            for dir in definitions {
+                let dir = expand_path(&self.base_dir, &dir).ok_or_else(|| {
+                    invalid_value("definitions", "cannot expand `~` in `definitions`")
+                })?;
+                if dir.to_str().map_or(true, |dir| dir.contains(|c| c == '?' || c == ';')) {
+                    return Err(invalid_value("definitions",
+                                             &format!("bad directory `{}` in `definitions`",
+                                                      dir.display())));
+                }
+                self.definitions.push(dir);
+            }
+        }
         self.message_locale = if let Some(lang) = data.message_lang {
-            if let Some(locale) = Locale::new(&lang) {
+            let langs = match lang {
+                MessageLang::Single(lang) => vec![lang],
+                MessageLang::Multi(langs) => langs,
+            };
+            let mut chain = Vec::new();
+            for lang in &langs {
+                if let Some(locale) = Locale::new(lang) {
+                    chain.push(locale);
+                } else {
+                    return Err(invalid_value("message_lang", "invalid message language"));
+                }
+            }
+            if let Some(locale) = Locale::with_fallbacks(&chain) {
                 Some(locale)
             } else {
-                return Err(invalid_data("invalid message language"));
+                return Err(invalid_value("message_lang", "too many or no message languages"));
             }
         } else {
             None
         };
+        if let Some(aliases) = data.module_aliases {
+            self.module_aliases = aliases.into_iter().map(|(from, to)| {
+                (from.into_bytes(), to.into_bytes())
+            }).collect();
+        }
+        if let Some(data_files) = data.data_files {
+            self.data_file_min_entries = Some(data_files.min_entries);
+        }
+        if let Some(version) = data.lua_version {
+            let version = match version {
+                LuaVersion::Str(s) => s,
+                LuaVersion::Num(v) => v.to_string(),
+            };
+            if let Some(lua) = Lua::from_version(&version) {
+                self.lua_version = lua;
+            } else {
+                return Err(invalid_value("lua_version",
+                                         "`lua_version` should be one of 5.1, 5.2, 5.3 or 5.4"));
+            }
+        }
+        // the profile is applied first so that individual options can override it
+        let profile = if let Some(name) = data.profile {
+            let profile = Profile::from_name(&name).ok_or_else(|| {
+                invalid_value("profile", "`profile` should be one of `lenient`, `standard` \
+                                          or `strict`")
+            })?;
+            self.strict_nil = profile.strict_nil();
+            self.strict_globals = profile.strict_globals();
+            self.profile = Some(profile);
+            Some(profile)
+        } else {
+            None
+        };
+        if let Some(strict_nil) = data.strict_nil {
+            self.strict_nil = strict_nil;
+        }
+        if let Some(strict_globals) = data.strict_globals {
+            self.strict_globals = strict_globals;
+        }
+        if let Some(dynamic_require) = data.dynamic_require {
+            self.dynamic_require = match dynamic_require {
+                DynamicRequireData::Single(ref s) if s == "error" => DynamicRequire::Error,
+                DynamicRequireData::Single(ref s) if s == "warn" => DynamicRequire::Warn,
+                DynamicRequireData::Single(_) => {
+                    return Err(invalid_value("dynamic_require",
+                                             "`dynamic_require` should be `error`, `warn` \
+                                              or a list of module names"));
+                }
+                DynamicRequireData::Multi(names) => {
+                    DynamicRequire::OneOf(names.into_iter().map(|s| s.into_bytes()).collect())
+                }
+            };
+        }
+        if let Some(limit) = data.literal_union_limit {
+            self.literal_union_limit = Some(limit);
+        }
+        if let Some(limit) = data.table_depth_limit {
+            self.table_depth_limit = Some(limit);
+        }
+        if let Some(limit) = data.constraint_limit {
+            self.constraint_limit = Some(limit);
+        }
+        if profile.is_some() || data.severity.is_some() {
+            self.lint_severities = profile.map_or(BTreeMap::new(), |profile| {
+                profile.lint_severities().into_iter().map(|(code, severity)| {
+                    (code.to_owned(), severity)
+                }).collect()
+            });
+            for (code, severity) in data.severity.unwrap_or_else(HashMap::new) {
+                if find_lint(&code).is_none() && !is_diagnostic_code(&code) {
+                    return Err(invalid_value("severity",
+                                             &format!("unknown lint or code `{}`", code)));
+                }
+                let severity = Severity::from_name(&severity).ok_or_else(|| {
+                    invalid_value("severity", &format!("the severity of `{}` should be \
+                                                        `allow`, `warn` or `error`", code))
+                })?;
+                self.lint_severities.insert(code, severity);
+            }
+        }
+        if let Some(globals) = data.globals {
+            let mut globals: Vec<_> = globals.into_iter().collect();
+            globals.sort_by(|a, b| a.0.cmp(&b.0)); // for the deterministic checking
+            self.preload.globals = Vec::new();
+            for (name, kind) in globals {
+                let kind = match kind {
+                    GlobalType::Dynamic(true) => None,
+                    GlobalType::Dynamic(false) => {
+                        return Err(invalid_value("globals", "a global type cannot be `false`"));
+                    }
+                    GlobalType::Typed(kind) => Some(kind),
+                };
+                if !verify_global_name(&name) {
+                    return Err(invalid_value("globals", &format!("bad global `{}`", name)));
+                }
+                if let Some(ref kind) = kind {
+                    if let Err(e) = kailua_syntax::parse_kind(kind) {
+                        return Err(invalid_value("globals", &format!(
+                            "bad type for the global `{}`: {}", name, e)));
+                    }
+                }
+                self.preload.globals.push((name.into_bytes(), kind.map(|s| s.into_bytes())));
+            }
+        }
+        if let Some(constants) = data.constants {
+            let mut constants: Vec<_> = constants.into_iter().collect();
+            constants.sort_by(|a, b| a.0.cmp(&b.0)); // for the deterministic checking
+            self.preload.constants = Vec::new();
+            for (name, value) in constants {
+                let value = match value {
+                    serde_json::Value::Bool(v) => Constant::Boolean(v),
+                    serde_json::Value::String(s) => Constant::String(s.into_bytes()),
+                    serde_json::Value::Number(ref v) if v.as_i64().map_or(false, |v| {
+                        v as i32 as i64 == v
+                    }) => {
+                        Constant::Integer(v.as_i64().unwrap() as i32)
+                    }
+                    _ => {
+                        return Err(invalid_value("constants", &format!(
+                            "the constant `{}` should be a boolean, a 32-bit integer or a string",
+                            name)));
+                    }
+                };
+                if !verify_global_name(&name) {
+                    return Err(invalid_value("constants", &format!("bad constant `{}`", name)));
+                }
+                self.preload.constants.push((name.into_bytes(), value));
+            }
+        }
+        for &(ref name, _) in &self.preload.constants {
+            if self.preload.globals.iter().any(|&(ref global, _)| global == name) {
+                return Err(invalid_value("constants", &format!(
+                    "the constant `{}` is also declared in `globals`",
+                    String::from_utf8_lossy(name))));
+            }
+        }
+        if let Some(asserts) = data.asserts {
+            self.asserts = Vec::new();
+            for assert in asserts {
+                let assert = match assert {
+                    AssertData { global: Some(name), module: None, export: None, kind } => {
+                        if !verify_global_name(&name) {
+                            return Err(invalid_value("asserts", &format!(
+                                "bad global `{}` in `asserts`", name)));
+                        }
+                        if let Err(e) = kailua_syntax::parse_kind(&kind) {
+                            return Err(invalid_value("asserts", &format!(
+                                "bad type for `{}` in `asserts`: {}", name, e)));
+                        }
+                        Assertion::Global(name.into_bytes(), kind.into_bytes())
+                    }
+                    AssertData { global: None, module: Some(module), export: Some(field),
+                                 kind } => {
+                        if let Err(e) = kailua_syntax::parse_kind(&kind) {
+                            return Err(invalid_value("asserts", &format!(
+                                "bad type for `{}` in `asserts`: {}", field, e)));
+                        }
+                        Assertion::Export(module.into_bytes(), field.into_bytes(),
+                                          kind.into_bytes())
+                    }
+                    _ => {
+                        return Err(invalid_value("asserts", "an assertion should have either \
+                                                             `global` or `module` and `export`"));
+                    }
+                };
+                self.asserts.push(assert);
+            }
+        }
         if let Some(preload) = data.preload {
-            self.preload.open = preload.open.into_iter().map(|s| {
+            self.preload.open = preload.open.into_vec().into_iter().map(|s| {
                 s.into_bytes().without_loc()
             }).collect();
-            self.preload.require = preload.require.into_iter().map(|s| {
+            self.preload.require = preload.require.into_vec().into_iter().map(|s| {
                 s.into_bytes().without_loc()
             }).collect();
         }
 
-        Ok(true)
+        Ok(())
+    }
+
+    /// Reads the global configuration file from `default_global_config_path` if any.
+    ///
+    /// A missing file is silently ignored, and other errors are recorded as warnings.
+    pub fn use_default_global_config_path(&mut self) {
+        if let Some(path) = default_global_config_path() {
+            if let Err(e) = self.set_global_config_path(path.clone()) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    self.warnings.push(format!("ignored the global configuration `{}`: {}",
+                                               path.display(), e));
+                }
+            }
+        }
     }
 
+    /// Reads the global configuration file and then the first configuration file found
+    /// from the known paths. Does nothing for files that cannot be read.
     pub fn use_default_config_paths(&mut self) {
+        self.use_default_global_config_path();
+
         let config_path = self.base_dir.join("kailua.json");
         let _ = self.set_config_path(config_path);
 
@@ -202,10 +845,27 @@ pub struct Workspace {
     base_dir: PathBuf,
     config_path: Option<PathBuf>,
     start_paths: Vec<PathBuf>,
+    start_path_options: HashMap<PathBuf, StartPathOptions>,
     package_path: Option<Vec<u8>>,
     package_cpath: Option<Vec<u8>>,
+    definitions: Vec<PathBuf>,
+    module_aliases: HashMap<Vec<u8>, Vec<u8>>,
+    data_file_min_entries: Option<usize>,
+    lua_version: Lua,
+    strict_nil: bool,
+    strict_globals: bool,
+    dynamic_require: DynamicRequire,
+    literal_union_limit: Option<usize>,
+    table_depth_limit: Option<usize>,
+    constraint_limit: Option<usize>,
+    lint_severities: BTreeMap<String, Severity>,
     preload: Preload,
+    asserts: Vec<Assertion>,
     message_locale: Locale,
+    sys: Sys,
+
+    // the effective configuration as a JSON object, used for exporting reproducers
+    applied: serde_json::Value,
 }
 
 impl Workspace {
@@ -218,10 +878,25 @@ impl Workspace {
             base_dir: config.base_dir.clone(),
             config_path: config.config_path.clone(),
             start_paths: config.start_paths.clone(),
+            start_path_options: config.start_path_options.clone(),
             package_path: config.package_path.clone(),
             package_cpath: config.package_cpath.clone(),
+            definitions: config.definitions.clone(),
+            module_aliases: config.module_aliases.clone(),
+            data_file_min_entries: config.data_file_min_entries,
+            lua_version: config.lua_version,
+            strict_nil: config.strict_nil,
+            strict_globals: config.strict_globals,
+            dynamic_require: config.dynamic_require.clone(),
+            literal_union_limit: config.literal_union_limit,
+            table_depth_limit: config.table_depth_limit,
+            constraint_limit: config.constraint_limit,
+            lint_severities: config.lint_severities.clone(),
             preload: config.preload.clone(),
+            asserts: config.asserts.clone(),
             message_locale: config.message_locale.unwrap_or(default_locale),
+            sys: config.sys.clone(),
+            applied: config.applied.clone(),
         })
     }
 
@@ -237,43 +912,306 @@ impl Workspace {
         &self.start_paths
     }
 
+    /// Returns options specific to given start path, if any.
+    pub fn start_path_options(&self, start_path: &Path) -> Option<&StartPathOptions> {
+        self.start_path_options.get(start_path)
+    }
+
+    /// Returns directories of definition bundles, in the order of preference.
+    pub fn definitions(&self) -> &[PathBuf] {
+        &self.definitions
+    }
+
+    pub fn module_aliases(&self) -> &HashMap<Vec<u8>, Vec<u8>> {
+        &self.module_aliases
+    }
+
+    pub fn data_file_min_entries(&self) -> Option<usize> {
+        self.data_file_min_entries
+    }
+
+    pub fn lua_version(&self) -> Lua {
+        self.lua_version
+    }
+
+    /// Returns the language to parse files in the workspace with.
+    pub fn language(&self) -> Language {
+        Language::new(self.lua_version, Kailua::Kailua10)
+    }
+
+    pub fn strict_nil(&self) -> bool {
+        self.strict_nil
+    }
+
+    pub fn strict_globals(&self) -> bool {
+        self.strict_globals
+    }
+
+    pub fn dynamic_require(&self) -> &DynamicRequire {
+        &self.dynamic_require
+    }
+
+    pub fn literal_union_limit(&self) -> Option<usize> {
+        self.literal_union_limit
+    }
+
+    pub fn table_depth_limit(&self) -> Option<usize> {
+        self.table_depth_limit
+    }
+
+    pub fn constraint_limit(&self) -> Option<usize> {
+        self.constraint_limit
+    }
+
+    /// Returns lint severities overriding the defaults, keyed by the lint code.
+    pub fn lint_severities(&self) -> &BTreeMap<String, Severity> {
+        &self.lint_severities
+    }
+
     pub fn preload(&self) -> &Preload {
         &self.preload
     }
 
+    /// Returns workspace-level invariants to be verified after checking each start path.
+    pub fn asserts(&self) -> &[Assertion] {
+        &self.asserts
+    }
+
+    /// Returns the clock and the file system inherited from the configuration.
+    pub fn sys(&self) -> &Sys {
+        &self.sys
+    }
+
     pub fn message_locale(&self) -> Locale {
         self.message_locale
     }
+
+    /// Returns a hash of all settings that can affect the checking and its reports.
+    ///
+    /// Downstream caches can compare this to invalidate their states
+    /// when the effective configuration changes. The value is stable
+    /// for the same build of Kailua, but should not be persisted across versions.
+    pub fn fingerprint(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        use std::collections::hash_map::DefaultHasher;
+
+        let mut hasher = DefaultHasher::new();
+        self.base_dir.hash(&mut hasher);
+        self.start_paths.hash(&mut hasher);
+        for path in &self.start_paths {
+            self.start_path_options.get(path).hash(&mut hasher);
+        }
+        self.package_path.hash(&mut hasher);
+        self.package_cpath.hash(&mut hasher);
+        self.definitions.hash(&mut hasher);
+
+        // HashMap has no defined order
+        let mut aliases: Vec<_> = self.module_aliases.iter().collect();
+        aliases.sort();
+        aliases.hash(&mut hasher);
+        self.data_file_min_entries.hash(&mut hasher);
+        self.lua_version.hash(&mut hasher);
+        self.strict_nil.hash(&mut hasher);
+        self.strict_globals.hash(&mut hasher);
+        self.dynamic_require.hash(&mut hasher);
+        self.literal_union_limit.hash(&mut hasher);
+        self.table_depth_limit.hash(&mut hasher);
+        self.constraint_limit.hash(&mut hasher);
+        self.lint_severities.hash(&mut hasher);
+
+        for name in &self.preload.open {
+            name.base.hash(&mut hasher);
+        }
+        self.preload.globals.hash(&mut hasher);
+        self.preload.constants.hash(&mut hasher);
+        for name in &self.preload.require {
+            name.base.hash(&mut hasher);
+        }
+        self.asserts.hash(&mut hasher);
+
+        self.message_locale.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns a path to the file caching modules checked from given start path
+    /// (see `incremental::IncrementalState::save`).
+    ///
+    /// Cache files are placed in the `.kailua-cache` directory in the base directory.
+    pub fn cache_path(&self, start_path: &Path) -> PathBuf {
+        use std::hash::{Hash, Hasher};
+        use std::collections::hash_map::DefaultHasher;
+
+        let mut hasher = DefaultHasher::new();
+        start_path.hash(&mut hasher);
+        self.base_dir.join(".kailua-cache").join(format!("{:016x}.json", hasher.finish()))
+    }
+
+    /// Returns a path to the file recording how long each file took to parse and check
+    /// (see `preparse::History::save`), in the same directory as `cache_path`.
+    pub fn history_path(&self) -> PathBuf {
+        self.base_dir.join(".kailua-cache").join("history.txt")
+    }
+
+    /// Returns a file that the checker would load for `require(name)` from given start path,
+    /// without parsing or checking it.
+    ///
+    /// Module aliases and search paths in the workspace are applied, but dynamic changes
+    /// to `package.path` or `package.cpath` in the code are not. Files are looked up in
+    /// the workspace file system; use `WorkspaceOptions::resolve_require` for other sources.
+    pub fn resolve_require(&self, name: &[u8], start_path: &Path) -> Option<ResolvedModule> {
+        // a source only used for checking the existence of files
+        struct ExistingFiles<'a>(&'a FileSystem);
+
+        impl<'a> FsSource for ExistingFiles<'a> {
+            fn chunk_from_path(&self, _resolved_path: Spanned<&Path>,
+                               _report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+                Ok(None)
+            }
+
+            fn path_exists(&self, resolved_path: Spanned<&Path>) -> bool {
+                self.0.is_file(&resolved_path)
+            }
+        }
+
+        let opts = WorkspaceOptions::new(vec![ExistingFiles(&*self.sys.fs)], start_path, self);
+        let resolved = opts.resolve_require(name.without_loc(), &NoReport).ok()??;
+
+        // normalize the path so that it can be compared with discovered paths
+        let paths = &self.sys.paths;
+        Some(match resolved {
+            ResolvedModule::Chunk(path) => ResolvedModule::Chunk(paths.lexical(&path)),
+            ResolvedModule::NativeLibrary(path) => {
+                ResolvedModule::NativeLibrary(paths.lexical(&path))
+            }
+        })
+    }
+}
+
+/// A file that `require` resolves to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResolvedModule {
+    /// A Lua file or a type stub (`.kailua`) that the checker would load.
+    Chunk(PathBuf),
+
+    /// A native library without any type stub. The checker cannot load it.
+    NativeLibrary(PathBuf),
 }
 
 /// An extension to `FsOptions` that is initialized from an workspace.
 pub struct WorkspaceOptions<S> {
-    options: FsOptions<S>,
+    layers: Vec<FsOptions<S>>,
+    module_aliases: HashMap<Vec<u8>, Vec<u8>>,
+    data_file_min_entries: Option<usize>,
+    data_files: Vec<data::DataFile>,
+    lua_version: Lua,
+    strict_nil: bool,
+    strict_globals: bool,
+    dynamic_require: DynamicRequire,
+    literal_union_limit: Option<usize>,
+    table_depth_limit: Option<usize>,
+    constraint_limit: Option<usize>,
+    lint_severities: BTreeMap<String, Severity>,
     can_update_package_path: bool,
     can_update_package_cpath: bool,
 }
 
 impl<S: FsSource> WorkspaceOptions<S> {
-    pub fn new(source: S, start_path: &Path, workspace: &Workspace) -> WorkspaceOptions<S> {
-        let mut options = FsOptions::new(source, workspace.base_dir.clone());
-        if let Some(ref path) = workspace.package_path {
-            let path = apply_search_paths_template(path, start_path).expect(
+    /// Creates options from an ordered list of sources.
+    ///
+    /// `require` searches all search paths in the first source, then in the second source
+    /// and so on, so that sources can be layered (e.g. unsaved files, files in the workspace
+    /// and then bundled stubs). Use `Box<FsSource>` to mix different kinds of sources.
+    pub fn new(sources: Vec<S>, start_path: &Path, workspace: &Workspace) -> WorkspaceOptions<S> {
+        // per-start-path search paths take precedence over workspace-wide ones
+        let options = workspace.start_path_options(start_path);
+        let package_path = options.and_then(|o| o.package_path.as_ref())
+                                  .or(workspace.package_path.as_ref());
+        let package_cpath = options.and_then(|o| o.package_cpath.as_ref())
+                                   .or(workspace.package_cpath.as_ref());
+
+        let package_path = package_path.map(|path| {
+            apply_search_paths_template(path, start_path).expect(
                 "apply_search_paths_template should not fail in this stage"
-            );
-            let _ = options.set_package_path((&path[..]).without_loc(), &NoReport);
-        }
-        if let Some(ref path) = workspace.package_cpath {
-            let path = apply_search_paths_template(path, start_path).expect(
+            )
+        });
+        let package_cpath = package_cpath.map(|path| {
+            apply_search_paths_template(path, start_path).expect(
                 "apply_search_paths_template should not fail in this stage"
-            );
-            let _ = options.set_package_cpath((&path[..]).without_loc(), &NoReport);
-        }
+            )
+        });
+
+        // directories have been verified to be valid UTF-8 without `?` or `;`
+        let definition_paths: Vec<_> = workspace.definitions.iter().map(|dir| {
+            dir.join("?").to_string_lossy().into_owned().into_bytes()
+        }).collect();
+
+        let layers = sources.into_iter().map(|source| {
+            let mut options = FsOptions::new(source, workspace.base_dir.clone());
+            options.set_definition_paths(definition_paths.clone());
+            if let Some(ref path) = package_path {
+                let _ = options.set_package_path((&path[..]).without_loc(), &NoReport);
+            }
+            if let Some(ref path) = package_cpath {
+                let _ = options.set_package_cpath((&path[..]).without_loc(), &NoReport);
+            }
+            options
+        }).collect();
 
         WorkspaceOptions {
-            options: options,
-            can_update_package_path: workspace.package_path.is_none(),
-            can_update_package_cpath: workspace.package_cpath.is_none(),
+            layers: layers,
+            module_aliases: workspace.module_aliases.clone(),
+            data_file_min_entries: workspace.data_file_min_entries,
+            data_files: Vec::new(),
+            lua_version: workspace.lua_version,
+            strict_nil: workspace.strict_nil,
+            strict_globals: workspace.strict_globals,
+            dynamic_require: workspace.dynamic_require.clone(),
+            literal_union_limit: workspace.literal_union_limit,
+            table_depth_limit: workspace.table_depth_limit,
+            constraint_limit: workspace.constraint_limit,
+            lint_severities: workspace.lint_severities.clone(),
+            can_update_package_path: package_path.is_none(),
+            can_update_package_cpath: package_cpath.is_none(),
+        }
+    }
+}
+
+impl<S: FsSource> WorkspaceOptions<S> {
+    fn resolve_alias<'a>(&'a self, path: Spanned<&'a [u8]>) -> Spanned<&'a [u8]> {
+        if let Some(alias) = self.module_aliases.get(path.base) {
+            (&alias[..]).with_loc(path)
+        } else {
+            path
+        }
+    }
+
+    /// Returns a file that `require` would load from current search paths, without loading it.
+    ///
+    /// This follows the same order as `Options::require_chunk` but is not affected by
+    /// data file summarization.
+    pub fn resolve_require(&self, path: Spanned<&[u8]>,
+                           report: &Report) -> Result<Option<ResolvedModule>, Option<Stop>> {
+        let path = self.resolve_alias(path);
+        for options in &self.layers {
+            match options.resolve_chunk_path(path, report) {
+                Ok(None) | Err(None) => {}
+                Ok(Some(resolved)) => return Ok(Some(ResolvedModule::Chunk(resolved))),
+                Err(e) => return Err(e),
+            }
+        }
+        for options in &self.layers {
+            if let Ok(Some(native_path)) = options.search_native_library(path, report) {
+                return Ok(Some(ResolvedModule::NativeLibrary(native_path)));
+            }
         }
+        Ok(None)
+    }
+}
+
+impl<S> WorkspaceOptions<S> {
+    /// Returns data files that have been summarized so far.
+    pub fn data_files(&self) -> &[data::DataFile] {
+        &self.data_files
     }
 }
 
@@ -281,7 +1219,10 @@ impl<S: FsSource> Options for WorkspaceOptions<S> {
     fn set_package_path(&mut self, path: Spanned<&[u8]>,
                         report: &Report) -> Result<(), Option<Stop>> {
         if self.can_update_package_path {
-            self.options.set_package_path(path, report)
+            for options in &mut self.layers {
+                options.set_package_path(path, report)?;
+            }
+            Ok(())
         } else {
             report.warn(path.span, message::PackagePathIsExplicitlySet {}).done()?;
             Ok(())
@@ -291,7 +1232,10 @@ impl<S: FsSource> Options for WorkspaceOptions<S> {
     fn set_package_cpath(&mut self, path: Spanned<&[u8]>,
                          report: &Report) -> Result<(), Option<Stop>> {
         if self.can_update_package_cpath {
-            self.options.set_package_cpath(path, report)
+            for options in &mut self.layers {
+                options.set_package_cpath(path, report)?;
+            }
+            Ok(())
         } else {
             report.warn(path.span, message::PackageCpathIsExplicitlySet {}).done()?;
             Ok(())
@@ -300,58 +1244,799 @@ impl<S: FsSource> Options for WorkspaceOptions<S> {
 
     fn require_chunk(&mut self, path: Spanned<&[u8]>,
                      report: &Report) -> Result<Chunk, Option<Stop>> {
-        self.options.require_chunk(path, report)
-    }
-}
-
-// serde-json does not allow comments that we really need to...
-// this will roughly "tokenize" (seemingly) JSON and remove comments as much as possible.
-// also a stray comma before `]` or `}` will be removed.
-fn dehumanize_json(s: &str) -> String {
-    use regex::Regex;
-
-    lazy_static! {
-        static ref TOKEN_PATTERN: Regex =
-            Regex::new(r#"(?xs)
-                          "(?:\\.|[^"])*" |  # strings should be skipped altogether
-                          //[^\r\n]* |       # single-line comment
-                          /\*.*?\*/ |        # possibly-multi-line comment
-                          .                  # others are simply passed through
-                          "#).unwrap();
-    }
-
-    let mut out = String::new();
-    let mut prev_was_comma = false;
-    for tok in TOKEN_PATTERN.find_iter(s) {
-        let tok = tok.as_str();
-        if tok.starts_with("//") || tok.starts_with("/*") {
-            out.push(' ');
-        } else if tok == " " || tok == "\t" || tok == "\n" || tok == "\r" {
-            out.push_str(tok);
+        let path = self.resolve_alias(path);
+        for options in &self.layers {
+            match options.search_chunk(path, report) {
+                Ok(None) | Err(None) => {} // not found (or unreported error), try the next source
+                Ok(Some(chunk)) => {
+                    if let Some(min_entries) = self.data_file_min_entries {
+                        if let Some((chunk, data)) = data::summarize_data_chunk(&chunk,
+                                                                                 min_entries) {
+                            self.data_files.push(data);
+                            return Ok(chunk);
+                        }
+                    }
+                    return Ok(chunk);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        // no source has a loadable chunk, but a native library may exist in some source
+        for options in &self.layers {
+            if let Ok(Some(native_path)) = options.search_native_library(path, report) {
+                return Err(options.report_native_library(path, &native_path, report));
+            }
+        }
+        Err(None)
+    }
+
+    fn lua_version(&self) -> Lua {
+        self.lua_version
+    }
+
+    fn strict_nil(&self) -> bool {
+        self.strict_nil
+    }
+
+    fn strict_globals(&self) -> bool {
+        self.strict_globals
+    }
+
+    fn dynamic_require(&self) -> DynamicRequire {
+        self.dynamic_require.clone()
+    }
+
+    fn literal_union_limit(&self) -> usize {
+        self.literal_union_limit.unwrap_or(DEFAULT_LITERAL_UNION_LIMIT)
+    }
+
+    fn table_depth_limit(&self) -> usize {
+        self.table_depth_limit.unwrap_or(DEFAULT_TABLE_DEPTH_LIMIT)
+    }
+
+    fn constraint_limit(&self) -> usize {
+        self.constraint_limit.unwrap_or(DEFAULT_CONSTRAINT_LIMIT)
+    }
+
+    fn lint_severity(&self, code: &str) -> Severity {
+        if let Some(&severity) = self.lint_severities.get(code) {
+            severity
         } else {
-            if prev_was_comma && !(tok == "]" || tok == "}") {
-                // ignore `,` before `]` or `}`, ignoring comments or whitespaces
-                out.push(',');
+            find_lint(code).map_or(Severity::Warn, |lint| lint.default_severity)
+        }
+    }
+
+    fn code_severity(&self, code: &str) -> Option<Severity> {
+        self.lint_severities.get(code).cloned()
+    }
+}
+
+// a source without any file, recording every path tried
+#[cfg(test)]
+#[derive(Clone)]
+struct RecordingFsSource(::std::rc::Rc<::std::cell::RefCell<Vec<PathBuf>>>);
+
+#[cfg(test)]
+impl FsSource for RecordingFsSource {
+    fn chunk_from_path(&self, resolved_path: Spanned<&Path>,
+                       _report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+        self.0.borrow_mut().push(resolved_path.base.to_owned());
+        Ok(None)
+    }
+}
+
+#[test]
+fn test_module_aliases() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut config = Config::from_start_path(PathBuf::from("main.lua"));
+    config.module_aliases.insert(b"socket".to_vec(), b"vendor.socket".to_vec());
+    let workspace = Workspace::new(&config, Locale::dummy()).unwrap();
+
+    let paths = Rc::new(RefCell::new(Vec::new()));
+    let source = RecordingFsSource(paths.clone());
+    let mut opts = WorkspaceOptions::new(vec![source], Path::new("main.lua"), &workspace);
+
+    assert!(opts.require_chunk((&b"socket"[..]).without_loc(), &NoReport).is_err());
+    assert!(paths.borrow().contains(&Path::new("vendor").join("socket.lua.kailua")));
+    assert!(!paths.borrow().contains(&PathBuf::from("socket.lua")));
+
+    paths.borrow_mut().clear();
+    assert!(opts.require_chunk((&b"other"[..]).without_loc(), &NoReport).is_err());
+    assert!(paths.borrow().contains(&PathBuf::from("other.lua")));
+}
+
+#[test]
+fn test_definition_bundles() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+    config.set_override("start_path", "main.lua").unwrap();
+    config.set_override("definitions", r#"["defs/socket", "/shared/defs"]"#).unwrap();
+    assert_eq!(config.definitions,
+               [PathBuf::from("/ws/defs/socket"), PathBuf::from("/shared/defs")]);
+    assert!(config.set_override("definitions", r#"["defs/?"]"#).is_err());
+    let workspace = Workspace::new(&config, Locale::dummy()).unwrap();
+
+    let paths = Rc::new(RefCell::new(Vec::new()));
+    let source = RecordingFsSource(paths.clone());
+    let mut opts = WorkspaceOptions::new(vec![source], Path::new("/ws/main.lua"), &workspace);
+
+    // bundles are searched in order before the search paths, only for `.kailua` files
+    assert!(opts.require_chunk((&b"socket.core"[..]).without_loc(), &NoReport).is_err());
+    let paths = paths.borrow();
+    assert_eq!(paths[0], Path::new("/ws/defs/socket").join("socket").join("core.kailua"));
+    assert_eq!(paths[2], Path::new("/shared/defs").join("socket").join("core.kailua"));
+    assert_eq!(paths[4], Path::new("/ws").join("socket").join("core.lua.kailua"));
+    assert!(!paths.contains(&Path::new("/ws/defs/socket").join("socket").join("core")));
+}
+
+#[test]
+fn test_layered_sources() {
+    use kailua_env::{Source, SourceFile};
+    use kailua_syntax::parse_chunk;
+
+    // a source containing given paths, each containing its index as the only statement
+    struct MockFsSource(Vec<PathBuf>);
+
+    impl FsSource for MockFsSource {
+        fn chunk_from_path(&self, resolved_path: Spanned<&Path>,
+                           report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+            if let Some(i) = self.0.iter().position(|p| *p == *resolved_path.base) {
+                let mut source = Source::new();
+                let code = format!("return {}", i).into_bytes();
+                let span = source.add(SourceFile::from_u8(String::new(), code));
+                Ok(Some(parse_chunk(&source, span, report).map_err(Some)?))
+            } else {
+                Ok(None)
             }
-            prev_was_comma = tok == ",";
-            if !prev_was_comma {
-                out.push_str(tok);
+        }
+    }
+
+    let mut config = Config::from_start_path(PathBuf::from("main.lua"));
+    config.package_path = Some(b"?.lua;lib/?.lua".to_vec());
+    let workspace = Workspace::new(&config, Locale::dummy()).unwrap();
+
+    let overlay: Box<FsSource> = Box::new(MockFsSource(vec![PathBuf::from("a.lua")]));
+    let files: Box<FsSource> = Box::new(MockFsSource(vec![
+        PathBuf::from("b.lua"), PathBuf::from("a.lua"), Path::new("lib").join("c.lua"),
+    ]));
+    let mut opts = WorkspaceOptions::new(vec![overlay, files], Path::new("main.lua"), &workspace);
+
+    let mut require = |name: &[u8]| {
+        opts.require_chunk(name.without_loc(), &NoReport).map(|chunk| format!("{:?}", chunk.block))
+    };
+    let a = require(b"a").unwrap();
+    let b = require(b"b").unwrap();
+    assert_eq!(a, b); // both are `return 0`, from the overlay and from files respectively
+    assert!(require(b"c").unwrap() != a);
+    assert!(require(b"d").is_err());
+}
+
+#[test]
+fn test_start_path_options() {
+    use std::sync::Arc;
+    use sys::{FakeClock, FakeFileSystem};
+
+    struct MockFsSource(PathBuf);
+
+    impl FsSource for MockFsSource {
+        fn chunk_from_path(&self, resolved_path: Spanned<&Path>,
+                           report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+            use kailua_env::{Source, SourceFile};
+
+            if *resolved_path.base == self.0 {
+                let mut source = Source::new();
+                let span = source.add(SourceFile::from_u8(String::new(), b"return 0".to_vec()));
+                Ok(Some(kailua_syntax::parse_chunk(&source, span, report).map_err(Some)?))
+            } else {
+                Ok(None)
             }
         }
     }
-    if prev_was_comma {
-        out.push(',');
+
+    let fs = Arc::new(FakeFileSystem::new());
+    fs.write("/ws/kailua.json", "{
+        start_path: ['main.lua', { path: 'tools/gen.lua', package_path: 'tools/?.lua' }],
+        package_path: '?.lua',
+    }");
+    let dir = PathBuf::from("/ws");
+    let mut config = Config::from_base_dir(dir.clone());
+    config.set_sys(Sys { clock: Arc::new(FakeClock::new()), fs: fs,
+                         paths: paths::PathPolicy::native() });
+    config.set_config_path(dir.join("kailua.json")).unwrap();
+    assert_eq!(config.start_paths, vec![dir.join("main.lua"), dir.join("tools/gen.lua")]);
+    assert_eq!(config.start_path_options.len(), 1);
+
+    let workspace = Workspace::new(&config, Locale::dummy()).unwrap();
+    let util = dir.join("tools").join("util.lua");
+    let mut require = |start_path: &Path| {
+        let source = MockFsSource(util.clone());
+        let mut opts = WorkspaceOptions::new(vec![source], start_path, &workspace);
+        opts.require_chunk((&b"util"[..]).without_loc(), &NoReport).is_ok()
+    };
+    assert!(!require(&dir.join("main.lua")));
+    assert!(require(&dir.join("tools/gen.lua")));
+}
+
+#[test]
+fn test_native_library() {
+    use kailua_diag::{CollectedReport, Kind};
+
+    // a source with native libraries only
+    struct MockFsSource(Vec<PathBuf>);
+
+    impl FsSource for MockFsSource {
+        fn chunk_from_path(&self, _resolved_path: Spanned<&Path>,
+                           _report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+            Ok(None)
+        }
+
+        fn path_exists(&self, resolved_path: Spanned<&Path>) -> bool {
+            self.0.iter().any(|p| *p == *resolved_path.base)
+        }
     }
-    out
+
+    let mut config = Config::from_start_path(PathBuf::from("main.lua"));
+    config.package_cpath = Some(b"lib/?.so".to_vec());
+    let workspace = Workspace::new(&config, Locale::dummy()).unwrap();
+
+    let source = MockFsSource(vec![Path::new("lib").join("socket").join("core.so")]);
+    let mut opts = WorkspaceOptions::new(vec![source], Path::new("main.lua"), &workspace);
+
+    let report = CollectedReport::new(Locale::dummy());
+    let mut require = |name: &[u8]| opts.require_chunk(name.without_loc(), &report).err();
+    assert_eq!(require(b"socket.core"), Some(Some(Stop)));
+    assert_eq!(require(b"socket.http"), Some(None));
+    let kinds: Vec<_> = report.into_reports().into_iter().map(|(kind, _, _)| kind).collect();
+    assert_eq!(kinds, vec![Kind::Warning, Kind::Note]);
 }
 
 #[test]
-fn test_dehumanize_json() {
-    // it is expected that commas go after whitespaces.
-    assert_eq!(dehumanize_json("[3, 4/*5*/6]"), "[3 ,4 6]");
-    assert_eq!(dehumanize_json("[3, 4//5, 6]\n7"), "[3 ,4 \n7");
-    assert_eq!(dehumanize_json(r#"[3, "4//5", "/*6*/"]"#), r#"[3 ,"4//5" ,"/*6*/"]"#);
-    assert_eq!(dehumanize_json("[3, 4, 5,\n/*wat*/\n// ???\n]"), "[3 ,4 ,5\n \n \n]");
+fn test_resolve_require() {
+    use std::sync::Arc;
+    use sys::{FakeClock, FakeFileSystem};
+
+    let fs = Arc::new(FakeFileSystem::new());
+    let dir = PathBuf::from("/ws");
+    for path in &["main.lua", "util.lua", "util.lua.kailua", "lib/json.lua", "lib/socket/core.so"] {
+        fs.write(dir.join(path), "");
+    }
+
+    let mut config = Config::from_base_dir(dir.clone());
+    config.set_sys(Sys { clock: Arc::new(FakeClock::new()), fs: fs,
+                         paths: paths::PathPolicy::native() });
+    config.start_paths.push(dir.join("main.lua"));
+    config.package_path = Some(b"?.lua;lib/?.lua".to_vec());
+    config.package_cpath = Some(b"lib/?.so".to_vec());
+    config.module_aliases.insert(b"dkjson".to_vec(), b"json".to_vec());
+    let workspace = Workspace::new(&config, Locale::dummy()).unwrap();
+
+    let resolve = |name: &[u8]| workspace.resolve_require(name, &dir.join("main.lua"));
+    assert_eq!(resolve(b"util"), Some(ResolvedModule::Chunk(dir.join("util.lua.kailua"))));
+    assert_eq!(resolve(b"dkjson"), Some(ResolvedModule::Chunk(dir.join("lib/json.lua"))));
+    assert_eq!(resolve(b"socket.core"),
+               Some(ResolvedModule::NativeLibrary(dir.join("lib/socket/core.so"))));
+    assert_eq!(resolve(b"missing"), None);
+}
+
+#[test]
+fn test_fake_sys() {
+    use std::sync::Arc;
+    use sys::{FakeClock, FakeFileSystem};
+
+    let fs = Arc::new(FakeFileSystem::new());
+    fs.write("/ws/kailua.json", "{start_path: 'main.lua', include: 'common.json'}");
+    fs.write("/ws/common.json", "{package_path: 'lib/?.lua'}");
+    fs.write("/ws/lib/util.lua", "return {}");
+
+    let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+    config.set_sys(Sys { clock: Arc::new(FakeClock::new()), fs: fs,
+                        paths: paths::PathPolicy::native() });
+    assert!(config.set_config_path(PathBuf::from("/ws/kailua.json")).unwrap());
+    assert_eq!(config.package_path, Some(b"lib/?.lua".to_vec()));
+
+    let workspace = Workspace::new(&config, Locale::dummy()).unwrap();
+    let main = Path::new("/ws/main.lua");
+    assert_eq!(workspace.resolve_require(b"util", main),
+               Some(ResolvedModule::Chunk(PathBuf::from("/ws/lib/util.lua"))));
+    assert_eq!(workspace.resolve_require(b"json", main), None);
+}
+
+#[test]
+fn test_global_config() {
+    use std::sync::Arc;
+    use sys::{FakeClock, FakeFileSystem};
+
+    let fs = Arc::new(FakeFileSystem::new());
+    fs.write("/home/kailua.json", "{message_lang: 'ko', package_path: 'global/?.lua', \
+                                    preload: {open: ['lua51']}, include: 'engine.json'}");
+    fs.write("/home/engine.json", "{globals: {engine: 'table'}}");
+    fs.write("/ws/kailua.json", "{start_path: 'main.lua', package_path: 'lib/?.lua', \
+                                  preload: {require: ['util']}}");
+    let sys = Sys { clock: Arc::new(FakeClock::new()), fs: fs.clone(),
+                    paths: paths::PathPolicy::native() };
+
+    // the workspace configuration takes precedence
+    let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+    config.set_sys(sys.clone());
+    assert!(config.set_global_config_path(PathBuf::from("/home/kailua.json")).unwrap());
+    assert_eq!(config.package_path, Some(b"global/?.lua".to_vec()));
+    assert!(config.set_config_path(PathBuf::from("/ws/kailua.json")).unwrap());
+    assert_eq!(config.global_config_path(), Some(Path::new("/home/kailua.json")));
+    assert_eq!(config.package_path, Some(b"lib/?.lua".to_vec()));
+    assert_eq!(config.message_locale.map(|l| l.primary().to_string()), Some("ko".to_owned()));
+    assert_eq!(config.preload.open.len(), 1);
+    assert_eq!(config.preload.require.len(), 1);
+    assert_eq!(config.preload.globals, vec![(b"engine".to_vec(), Some(b"table".to_vec()))]);
+    assert_eq!(config.start_paths, vec![PathBuf::from("/ws/main.lua")]);
+
+    // the global configuration cannot be read later
+    assert!(!config.set_global_config_path(PathBuf::from("/home/kailua.json")).unwrap());
+
+    // the global configuration alone is not enough for the workspace
+    let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+    config.set_sys(sys.clone());
+    assert!(config.set_global_config_path(PathBuf::from("/home/kailua.json")).unwrap());
+    assert_eq!(config.config_path(), None);
+    assert!(config.start_paths.is_empty());
+
+    fs.write("/home/kailua.json", "{start_path: 'main.lua'}");
+    let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+    config.set_sys(sys);
+    let err = config.set_global_config_path(PathBuf::from("/home/kailua.json")).unwrap_err();
+    assert!(err.to_string().contains("not allowed"), "{}", err);
+}
+
+#[test]
+fn test_set_override() {
+    use std::sync::Arc;
+    use sys::{FakeClock, FakeFileSystem};
+
+    let fs = Arc::new(FakeFileSystem::new());
+    fs.write("/ws/kailua.json", "{start_path: 'main.lua', globals: {A: 'integer'}, \
+                                  module_aliases: {x: 'y'}}");
+    let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+    config.set_sys(Sys { clock: Arc::new(FakeClock::new()), fs: fs,
+                        paths: paths::PathPolicy::native() });
+
+    // overrides given before reading the configuration file are kept
+    config.set_override("preload.open", "lua51").unwrap();
+    config.set_override("message_lang", "ko").unwrap();
+    assert!(config.set_config_path(PathBuf::from("/ws/kailua.json")).unwrap());
+    assert_eq!(config.preload.open.len(), 1);
+    assert_eq!(config.message_locale.map(|l| l.primary().to_string()), Some("ko".to_owned()));
+
+    // objects are merged
+    config.set_override("globals.B", "'string'").unwrap();
+    assert_eq!(config.preload.globals, vec![(b"A".to_vec(), Some(b"integer".to_vec())),
+                                            (b"B".to_vec(), Some(b"string".to_vec()))]);
+    config.set_override("module_aliases.z", "w").unwrap();
+    assert_eq!(config.module_aliases.len(), 2);
+    config.set_override("data_files.min_entries", "100").unwrap();
+    assert_eq!(config.data_file_min_entries, Some(100));
+    config.set_override("lua_version", "5.3").unwrap();
+    assert_eq!(config.lua_version, Lua::Lua53);
+    assert!(config.set_override("lua_version", "5.0").is_err());
+    config.set_override("strict_nil", "true").unwrap();
+    assert!(config.strict_nil);
+    config.set_override("strict_globals", "true").unwrap();
+    assert!(config.strict_globals);
+    config.set_override("dynamic_require", "error").unwrap();
+    assert_eq!(config.dynamic_require, DynamicRequire::Error);
+    config.set_override("dynamic_require", "['a', 'b']").unwrap();
+    assert_eq!(config.dynamic_require, DynamicRequire::OneOf(vec![b"a".to_vec(), b"b".to_vec()]));
+    assert!(config.set_override("dynamic_require", "maybe").is_err());
+    config.set_override("literal_union_limit", "16").unwrap();
+    assert_eq!(config.literal_union_limit, Some(16));
+    config.set_override("table_depth_limit", "8").unwrap();
+    assert_eq!(config.table_depth_limit, Some(8));
+    config.set_override("constraint_limit", "10000").unwrap();
+    assert_eq!(config.constraint_limit, Some(10000));
+    assert!(config.set_override("constraint_limit", "-1").is_err());
+    config.set_override("severity.empty-block", "error").unwrap();
+    config.set_override("severity.shadowing", "allow").unwrap();
+    assert_eq!(config.lint_severities.get("empty-block"), Some(&Severity::Error));
+    assert_eq!(config.lint_severities.get("shadowing"), Some(&Severity::Allow));
+    assert!(config.set_override("severity.no-such-lint", "warn").is_err());
+    assert!(config.set_override("severity.empty-block", "loud").is_err());
+    config.set_override("severity.KC0001", "allow").unwrap();
+    assert_eq!(config.lint_severities.get("KC0001"), Some(&Severity::Allow));
+    assert!(config.set_override("severity.KC01", "warn").is_err());
+    assert_eq!(config.lint_severities.len(), 3);
+    config.set_override("preload.require", "['a', 'b']").unwrap();
+    assert_eq!(config.preload.require.len(), 2);
+    assert_eq!(config.preload.open.len(), 1);
+    config.set_override("start_path", "other.lua").unwrap();
+    assert_eq!(config.start_paths, vec![PathBuf::from("/ws/other.lua")]);
+
+    // errors do not change the configuration
+    assert!(config.set_override("lints.unused", "off").is_err());
+    assert!(config.set_override("preload.", "lua51").is_err());
+    assert!(config.set_override("data_files.min_entries", "many").is_err());
+    assert!(config.set_override("globals.C", "not a type").is_err());
+    assert_eq!(config.data_file_min_entries, Some(100));
+    assert_eq!(config.preload.globals.len(), 2);
+}
+
+#[test]
+fn test_profile() {
+    use std::sync::Arc;
+    use sys::{FakeClock, FakeFileSystem};
+
+    let fs = Arc::new(FakeFileSystem::new());
+    fs.write("/ws/kailua.json", "{start_path: 'main.lua', profile: 'strict', \
+                                  strict_globals: false, severity: {'unused-variable': 'warn'}}");
+    let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+    config.set_sys(Sys { clock: Arc::new(FakeClock::new()), fs: fs,
+                        paths: paths::PathPolicy::native() });
+    assert!(config.set_config_path(PathBuf::from("/ws/kailua.json")).unwrap());
+
+    // individual options override the profile regardless of their order
+    assert_eq!(config.profile, Some(Profile::Strict));
+    assert!(config.strict_nil);
+    assert!(!config.strict_globals);
+    assert_eq!(config.lint_severities.get("unused-variable"), Some(&Severity::Warn));
+    assert_eq!(config.lint_severities.get("unused-assignment"), Some(&Severity::Error));
+    assert_eq!(config.lint_severities.get("KC0052"), Some(&Severity::Error));
+
+    // switching profiles replaces every preset value
+    config.set_override("profile", "lenient").unwrap();
+    assert_eq!(config.profile, Some(Profile::Lenient));
+    assert!(!config.strict_nil);
+    assert_eq!(config.lint_severities.get("unused-variable"), Some(&Severity::Warn));
+    assert_eq!(config.lint_severities.get("unused-assignment"), Some(&Severity::Allow));
+    assert_eq!(config.lint_severities.get("KC0052"), Some(&Severity::Allow));
+    assert_eq!(config.lint_severities.len(), 3);
+
+    assert!(config.set_override("profile", "paranoid").is_err());
+    assert_eq!(config.profile, Some(Profile::Lenient));
+    for &profile in Profile::all() {
+        assert_eq!(Profile::from_name(profile.name()), Some(profile));
+    }
+}
+
+#[test]
+fn test_globals() {
+    use std::sync::Arc;
+    use sys::{FakeClock, FakeFileSystem};
+
+    let parse = |globals: &str| {
+        let fs = FakeFileSystem::new();
+        fs.write("/ws/kailua.json", format!("{{ start_path: 'main.lua', globals: {} }}", globals));
+        let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+        config.set_sys(Sys { clock: Arc::new(FakeClock::new()), fs: Arc::new(fs),
+                             paths: paths::PathPolicy::native() });
+        config.set_config_path(PathBuf::from("/ws/kailua.json")).map(|_| config.preload.globals)
+    };
+
+    assert_eq!(parse("{ b: 'string', a: true }").unwrap(),
+               vec![(b"a".to_vec(), None), (b"b".to_vec(), Some(b"string".to_vec()))]);
+    assert!(parse("{ a: false }").is_err());
+    assert!(parse("{ 'not a name': true }").is_err());
+    assert!(parse("{ a: 'string\\nprint(42)' }").is_err());
+    assert!(parse("{ a: 'function(' }").is_err());
+}
+
+#[test]
+fn test_constants() {
+    use std::sync::Arc;
+    use sys::{FakeClock, FakeFileSystem};
+
+    let parse = |rest: &str| {
+        let fs = FakeFileSystem::new();
+        fs.write("/ws/kailua.json", format!("{{ start_path: 'main.lua', {} }}", rest));
+        let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+        config.set_sys(Sys { clock: Arc::new(FakeClock::new()), fs: Arc::new(fs),
+                             paths: paths::PathPolicy::native() });
+        config.set_config_path(PathBuf::from("/ws/kailua.json")).map(|_| config.preload.constants)
+    };
+
+    assert_eq!(parse("constants: { PLATFORM: 'win', DEBUG: false, LEVEL: -3 }").unwrap(),
+               vec![(b"DEBUG".to_vec(), Constant::Boolean(false)),
+                    (b"LEVEL".to_vec(), Constant::Integer(-3)),
+                    (b"PLATFORM".to_vec(), Constant::String(b"win".to_vec()))]);
+    assert!(parse("constants: { RATIO: 0.5 }").is_err());
+    assert!(parse("constants: { HUGE: 10000000000 }").is_err());
+    assert!(parse("constants: { LIST: [] }").is_err());
+    assert!(parse("constants: { 'not a name': true }").is_err());
+    assert!(parse("constants: { DEBUG: true }, globals: { DEBUG: 'boolean' }").is_err());
+}
+
+#[test]
+fn test_asserts() {
+    use std::sync::Arc;
+    use sys::{FakeClock, FakeFileSystem};
+
+    let parse = |asserts: &str| {
+        let fs = FakeFileSystem::new();
+        fs.write("/ws/kailua.json", format!("{{ start_path: 'main.lua', asserts: {} }}", asserts));
+        let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+        config.set_sys(Sys { clock: Arc::new(FakeClock::new()), fs: Arc::new(fs),
+                             paths: paths::PathPolicy::native() });
+        config.set_config_path(PathBuf::from("/ws/kailua.json")).map(|_| config.asserts)
+    };
+
+    assert_eq!(parse("[{ global: 'DEBUG', type: 'boolean' }, \
+                       { module: 'core.init', export: 'boot', type: 'function' }]").unwrap(),
+               vec![Assertion::Global(b"DEBUG".to_vec(), b"boolean".to_vec()),
+                    Assertion::Export(b"core.init".to_vec(), b"boot".to_vec(),
+                                      b"function".to_vec())]);
+    assert!(parse("[{ global: 'DEBUG' }]").is_err());
+    assert!(parse("[{ global: 'DEBUG', type: 'function(' }]").is_err());
+    assert!(parse("[{ global: 'DEBUG', type: 'integer\\nstring' }]").is_err());
+    assert!(parse("[{ global: 'local', type: 'integer' }]").is_err());
+    assert!(parse("[{ module: 'core.init', type: 'function' }]").is_err());
+    assert!(parse("[{ global: 'DEBUG', module: 'core.init', export: 'boot', \
+                      type: 'function' }]").is_err());
+}
+
+#[test]
+fn test_fingerprint() {
+    let mut config = Config::from_start_path(PathBuf::from("main.lua"));
+    let fingerprint = |config: &Config| {
+        Workspace::new(config, Locale::dummy()).unwrap().fingerprint()
+    };
+
+    let initial = fingerprint(&config);
+    assert_eq!(fingerprint(&config.clone()), initial);
+
+    config.package_path = Some(b"?.lua;lib/?.lua".to_vec());
+    let with_path = fingerprint(&config);
+    assert!(with_path != initial);
+
+    config.preload.open.push(b"lua51".to_vec().without_loc());
+    assert!(fingerprint(&config) != with_path);
+
+    config.preload.open.clear();
+    assert_eq!(fingerprint(&config), with_path);
+
+    config.module_aliases.insert(b"a".to_vec(), b"b".to_vec());
+    assert!(fingerprint(&config) != with_path);
+}
+
+/// Merges configuration fragments listed in `include` into given configuration value.
+///
+/// Included paths are relative to the including file. Later fragments override earlier ones,
+/// and the including file overrides all fragments; objects are merged recursively.
+/// `stack` contains canonicalized paths of files being included, for detecting cycles.
+fn merge_includes(value: &mut serde_json::Value, config_path: &Path, fs: &FileSystem,
+                  stack: &mut Vec<PathBuf>, warnings: &mut Vec<String>) -> Result<(), String> {
+    use serde_json::{Map, Value};
+
+    let includes = match value.as_object_mut().and_then(|map| map.remove("include")) {
+        None => return Ok(()),
+        Some(Value::String(s)) => vec![s],
+        Some(Value::Array(values)) => {
+            let mut includes = Vec::new();
+            for v in values {
+                match v {
+                    Value::String(s) => includes.push(s),
+                    _ => return Err(format!("`include` should be an array of strings")),
+                }
+            }
+            includes
+        }
+        Some(_) => return Err(format!("`include` should be a string or an array of strings")),
+    };
+
+    let dir = config_path.parent().unwrap_or(Path::new("."));
+    let mut merged = Value::Object(Map::new());
+    for include in includes {
+        let path = expand_path(dir, Path::new(&include)).ok_or_else(|| {
+            format!("cannot expand `~` in `{}`", include)
+        })?;
+        let key = fs.canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if stack.contains(&key) {
+            return Err(format!("`{}` is included recursively", path.display()));
+        }
+
+        let data = fs.read(&path).and_then(|data| {
+            String::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }).map_err(|e| {
+            format!("{} (while reading an included configuration `{}`)", e, path.display())
+        })?;
+        let mut doc = json::parse(&data).map_err(|e| {
+            format!("{} in an included configuration `{}`", e, path.display())
+        })?;
+        let fragment_warnings = migrate::migrate(&mut doc, migrate::MIGRATIONS,
+                                                 migrate::CURRENT_SCHEMA_VERSION);
+        for warning in fragment_warnings.map_err(|e| format!("{} in `{}`", e, path.display()))? {
+            warnings.push(format!("{} in `{}`", warning, path.display()));
+        }
+
+        stack.push(key);
+        merge_includes(&mut doc.value, &path, fs, stack, warnings)?;
+        stack.pop();
+        merge_json(&mut merged, doc.value);
+    }
+
+    let own = mem::replace(value, merged);
+    merge_json(value, own);
+    Ok(())
+}
+
+/// Merges `src` into `dest`, where values in `src` take precedence.
+/// Objects are recursively merged and other values are replaced.
+fn merge_json(dest: &mut serde_json::Value, src: serde_json::Value) {
+    use serde_json::Value;
+
+    match (dest, src) {
+        (&mut Value::Object(ref mut dest), Value::Object(src)) => {
+            for (k, v) in src {
+                if let Some(d) = dest.get_mut(&k) {
+                    merge_json(d, v);
+                    continue;
+                }
+                dest.insert(k, v);
+            }
+        }
+        (dest, src) => *dest = src,
+    }
+}
+
+#[test]
+fn test_include() {
+    use std::sync::Arc;
+    use sys::{FakeClock, FakeFileSystem};
+
+    let fs = Arc::new(FakeFileSystem::new());
+    let sys = Sys { clock: Arc::new(FakeClock::new()), fs: fs.clone(),
+                    paths: paths::PathPolicy::native() };
+    let dir = PathBuf::from("/ws");
+    let write = |path: &str, data: &str| fs.write(dir.join(path), data);
+
+    write("shared/preload.json", "{ include: 'lang.json', preload: { open: ['lua51'] }, \
+                                    package_path: 'shared/?.lua' }");
+    write("shared/lang.json", "{ message_lang: 'ko', package_path: 'lang/?.lua' }");
+    write("kailua.json", "{ include: ['shared/preload.json'], start_path: 'main.lua', \
+                            preload: { require: ['a'] } }");
+
+    let mut config = Config::from_base_dir(dir.clone());
+    config.set_sys(sys.clone());
+    assert_eq!(config.set_config_path(dir.join("kailua.json")).unwrap(), true);
+    assert_eq!(config.package_path, Some(b"shared/?.lua".to_vec()));
+    assert_eq!(config.message_locale.map(|l| l.primary().to_string()), Some("ko".to_owned()));
+    assert_eq!(config.preload.open.len(), 1);
+    assert_eq!(config.preload.require.len(), 1);
+
+    // cyclic includes
+    write("shared/lang.json", "{ include: ['preload.json'] }");
+    let mut config = Config::from_base_dir(dir.clone());
+    config.set_sys(sys.clone());
+    let err = config.set_config_path(dir.join("kailua.json")).unwrap_err();
+    assert!(err.to_string().contains("recursively"), "{}", err);
+
+    // missing includes
+    write("kailua.json", "{ include: 'missing.json', start_path: 'main.lua' }");
+    let mut config = Config::from_base_dir(dir.clone());
+    config.set_sys(sys);
+    assert!(config.set_config_path(dir.join("kailua.json")).is_err());
+}
+
+/// Returns the path to the per-user global configuration file, if it can be determined.
+///
+/// This is `%APPDATA%\kailua\config.json` in Windows and `$XDG_CONFIG_HOME/kailua/config.json`
+/// (defaults to `~/.config/kailua/config.json`) in other platforms.
+pub fn default_global_config_path() -> Option<PathBuf> {
+    use std::env;
+
+    let var = |name: &str| env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let dir = if cfg!(windows) {
+        var("APPDATA")
+    } else {
+        var("XDG_CONFIG_HOME").or_else(|| home_dir().map(|home| home.join(".config")))
+    };
+    dir.map(|dir| dir.join("kailua").join("config.json"))
+}
+
+fn invalid_data<E: Into<Box<Error + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn home_dir() -> Option<PathBuf> {
+    use std::env;
+    env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))
+                       .filter(|home| !home.is_empty())
+                       .map(PathBuf::from)
+}
+
+fn is_separator(c: u8) -> bool {
+    c == b'/' || (cfg!(windows) && c == b'\\')
+}
+
+/// Returns the remainder if the path is `~` or starts with `~/`.
+fn strip_home_prefix(path: &[u8]) -> Option<&[u8]> {
+    if path == b"~" {
+        Some(b"")
+    } else if path.len() >= 2 && path[0] == b'~' && is_separator(path[1]) {
+        Some(&path[2..])
+    } else {
+        None
+    }
+}
+
+/// Resolves a path in the configuration into a normalized path.
+///
+/// `~` at the beginning refers to the home directory, and relative paths are relative to
+/// `base_dir`. `.` and `..` are lexically resolved when possible. Returns `None` if `~` is used
+/// but the home directory cannot be determined.
+fn expand_path(base_dir: &Path, path: &Path) -> Option<PathBuf> {
+    use std::path::Component;
+
+    let path = if let Some(rest) = path.to_str().and_then(|p| strip_home_prefix(p.as_bytes())) {
+        home_dir()?.join(str::from_utf8(rest).unwrap())
+    } else {
+        base_dir.join(path)
+    };
+
+    let mut normalized = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => { normalized.pop(); }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {} // `/..` is `/`
+                _ => normalized.push(".."),
+            },
+            c => normalized.push(c.as_os_str()),
+        }
+    }
+    if normalized.as_os_str().is_empty() {
+        normalized.push(".");
+    }
+    Some(normalized)
+}
+
+/// Expands `~` at the beginning of each template in search paths.
+///
+/// Other templates are kept as is, as they will be resolved against the base directory later.
+fn expand_search_paths(search_paths: &[u8]) -> Option<Vec<u8>> {
+    let mut ret = Vec::new();
+    for (i, template) in search_paths.split(|&c| c == b';').enumerate() {
+        if i > 0 {
+            ret.push(b';');
+        }
+        if let Some(rest) = strip_home_prefix(template) {
+            let home = home_dir()?;
+            ret.extend_from_slice(home.display().to_string().as_bytes());
+            if template.len() > 1 {
+                ret.push(template[1]); // keep the original separator
+            }
+            ret.extend_from_slice(rest);
+        } else {
+            ret.extend_from_slice(template);
+        }
+    }
+    Some(ret)
+}
+
+#[test]
+fn test_expand_path() {
+    let base = Path::new("/work/game");
+    assert_eq!(expand_path(base, Path::new("main.lua")),
+               Some(PathBuf::from("/work/game/main.lua")));
+    assert_eq!(expand_path(base, Path::new("./src/../main.lua")),
+               Some(PathBuf::from("/work/game/main.lua")));
+    assert_eq!(expand_path(base, Path::new("../shared/a.lua")),
+               Some(PathBuf::from("/work/shared/a.lua")));
+    assert_eq!(expand_path(base, Path::new("/abs/a.lua")), Some(PathBuf::from("/abs/a.lua")));
+    assert_eq!(expand_path(base, Path::new("/../a.lua")), Some(PathBuf::from("/a.lua")));
+    assert_eq!(expand_path(Path::new("."), Path::new("../a.lua")),
+               Some(PathBuf::from("../a.lua")));
+    assert_eq!(expand_path(Path::new("."), Path::new(".")), Some(PathBuf::from(".")));
+    assert_eq!(expand_path(base, Path::new("~foo/a.lua")),
+               Some(PathBuf::from("/work/game/~foo/a.lua")));
+
+    if let Some(home) = home_dir() {
+        assert_eq!(expand_path(base, Path::new("~/a.lua")), Some(home.join("a.lua")));
+        let home = home.display().to_string();
+        assert_eq!(expand_search_paths(b"?.lua;~/lib/?.lua;/abs/?.lua;~"),
+                   Some(format!("?.lua;{}/lib/?.lua;/abs/?.lua;{}", home, home).into_bytes()));
+    }
 }
 
 fn apply_search_paths_template(mut search_paths: &[u8], start_path: &Path) -> Option<Vec<u8>> {
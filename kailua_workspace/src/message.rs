@@ -12,3 +12,20 @@ define_msg! { pub PackageCpathIsExplicitlySet:
              `kailua.json` already has an explicit `package_cpath` value",
 }
 
+#[test]
+fn test_message_locale_fallbacks() {
+    use kailua_diag::{Locale, Localized};
+
+    let msg = PackagePathIsExplicitlySet {};
+    let text = |langs: &[&str]| {
+        let chain: Vec<_> = langs.iter().map(|&l| Locale::from(l)).collect();
+        Localized::new(&msg, Locale::with_fallbacks(&chain).unwrap()).to_string()
+    };
+
+    let ko = text(&["ko"]);
+    let en = text(&["en"]);
+    assert!(ko != en);
+    assert_eq!(text(&["pt-br", "ko"]), ko);
+    assert_eq!(text(&["pt-br", "en", "ko"]), en);
+    assert_eq!(text(&["pt-br", "pt"]), en);
+}
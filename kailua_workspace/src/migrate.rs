@@ -0,0 +1,177 @@
+//! Migration of configuration files written for older schema versions.
+//!
+//! Every configuration has an optional `schema_version` field, which defaults to 1.
+//! Whenever the configuration format changes incompatibly, `CURRENT_SCHEMA_VERSION` is bumped
+//! and a `Migration` from the previous version is added to `MIGRATIONS`.
+//! Older configurations are then upgraded in memory (with warnings),
+//! and can be optionally rewritten with `rewrite_config`.
+
+use std::io::{self, Read, Write};
+use std::fs::File;
+use std::path::Path;
+use serde_json::{self, Map, Value};
+use json::{self, Document};
+
+/// The schema version of configurations that this version of Kailua directly understands.
+pub const CURRENT_SCHEMA_VERSION: u64 = 1;
+
+/// A single step of the migration.
+///
+/// Paths are dot-separated keys into nested objects, e.g. `preload.open`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Moves (or renames) a value to another path. Intermediate objects are created as needed.
+    Move(&'static str, &'static str),
+
+    /// Removes a value which is no longer used.
+    Remove(&'static str),
+}
+
+/// A set of steps upgrading configurations from `from_version` to `from_version + 1`.
+#[derive(Copy, Clone, Debug)]
+pub struct Migration {
+    pub from_version: u64,
+    pub steps: &'static [Step],
+}
+
+/// Known migrations, sorted by `from_version`.
+pub const MIGRATIONS: &'static [Migration] = &[];
+
+fn take_path(value: &mut Value, path: &str) -> Option<Value> {
+    let mut keys: Vec<&str> = path.split('.').collect();
+    let last = keys.pop().unwrap();
+    let mut map = value.as_object_mut()?;
+    for key in keys {
+        map = { map }.get_mut(key)?.as_object_mut()?;
+    }
+    map.remove(last)
+}
+
+fn put_path(value: &mut Value, path: &str, new: Value) -> bool {
+    let mut keys: Vec<&str> = path.split('.').collect();
+    let last = keys.pop().unwrap();
+    let mut map = match value.as_object_mut() {
+        Some(map) => map,
+        None => return false,
+    };
+    for key in keys {
+        let next = { map }.entry(key.to_owned()).or_insert_with(|| Value::Object(Map::new()));
+        map = match next.as_object_mut() {
+            Some(map) => map,
+            None => return false,
+        };
+    }
+    if map.contains_key(last) {
+        return false;
+    }
+    map.insert(last.to_owned(), new);
+    true
+}
+
+/// Upgrades a configuration document to `target_version` with given migrations.
+///
+/// The resulting document has a `schema_version` field set to `target_version`.
+/// Returns a list of warnings for each applied step, or an error message
+/// if the configuration cannot be migrated (e.g. it is from the future).
+pub fn migrate(doc: &mut Document, migrations: &[Migration],
+               target_version: u64) -> Result<Vec<String>, String> {
+    let version = match doc.value.get("schema_version") {
+        None => 1,
+        Some(&Value::Number(ref v)) if v.as_u64().map_or(false, |v| v > 0) => v.as_u64().unwrap(),
+        Some(_) => return Err(format!("`schema_version` should be a positive integer")),
+    };
+    if version > target_version {
+        return Err(format!("`schema_version` {} is not supported by this version of Kailua \
+                            (up to {})", version, target_version));
+    }
+
+    let mut warnings = Vec::new();
+    for v in version..target_version {
+        let migration = migrations.iter().find(|m| m.from_version == v);
+        let migration = migration.ok_or_else(|| {
+            format!("no known migration from `schema_version` {}", v)
+        })?;
+
+        for step in migration.steps {
+            match *step {
+                Step::Move(from, to) => {
+                    if let Some(value) = take_path(&mut doc.value, from) {
+                        if !put_path(&mut doc.value, to, value) {
+                            return Err(format!("cannot move `{}` to `{}` \
+                                                while migrating from `schema_version` {}",
+                                               from, to, v));
+                        }
+                        // keep the position of top-level values for diagnostics
+                        if let Some(offset) = doc.offsets.remove(from) {
+                            doc.offsets.insert(to.to_owned(), offset);
+                        }
+                        warnings.push(format!("`{}` has been moved to `{}` \
+                                               since `schema_version` {}", from, to, v + 1));
+                    }
+                }
+                Step::Remove(path) => {
+                    if take_path(&mut doc.value, path).is_some() {
+                        warnings.push(format!("`{}` is no longer used \
+                                               since `schema_version` {}", path, v + 1));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(map) = doc.value.as_object_mut() {
+        map.insert("schema_version".to_owned(), Value::from(target_version));
+    }
+    Ok(warnings)
+}
+
+/// Rewrites an outdated configuration file in place to the current schema version.
+///
+/// Returns `Ok(false)` if the file is already up to date and has not been rewritten.
+/// Note that the rewritten file is a standard JSON, so comments and formatting are lost.
+pub fn rewrite_config(path: &Path) -> io::Result<bool> {
+    let mut data = String::new();
+    File::open(path)?.read_to_string(&mut data)?;
+    let mut doc = json::parse(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let version = doc.value.get("schema_version").and_then(|v| v.as_u64()).unwrap_or(1);
+    migrate(&mut doc, MIGRATIONS, CURRENT_SCHEMA_VERSION).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    })?;
+    if version >= CURRENT_SCHEMA_VERSION {
+        return Ok(false);
+    }
+    let mut data = serde_json::to_string_pretty(&doc.value).map_err(|e| {
+        io::Error::new(io::ErrorKind::Other, e)
+    })?;
+    data.push('\n');
+    File::create(path)?.write_all(data.as_bytes())?;
+    Ok(true)
+}
+
+#[test]
+fn test_migrate() {
+    const TEST_MIGRATIONS: &'static [Migration] = &[
+        Migration { from_version: 1, steps: &[Step::Move("open", "preload.open"),
+                                              Step::Remove("legacy")] },
+        Migration { from_version: 2, steps: &[Step::Move("message_lang", "lang")] },
+    ];
+
+    let mut doc = json::parse("{start_path: 'a.lua', open: ['lua51'], message_lang: 'ko', \
+                               legacy: 1, preload: {require: ['b']}}").unwrap();
+    let warnings = migrate(&mut doc, TEST_MIGRATIONS, 3).unwrap();
+    assert_eq!(warnings.len(), 3);
+    assert_eq!(doc.value, json!({"start_path": "a.lua", "lang": "ko", "schema_version": 3,
+                                 "preload": {"open": ["lua51"], "require": ["b"]}}));
+    assert!(doc.offsets.contains_key("lang"));
+
+    let mut doc = json::parse("{schema_version: 2, open: 'x', message_lang: 'ko'}").unwrap();
+    assert_eq!(migrate(&mut doc, TEST_MIGRATIONS, 3).unwrap().len(), 1);
+    assert_eq!(doc.value, json!({"open": "x", "lang": "ko", "schema_version": 3}));
+
+    let mut doc = json::parse("{schema_version: 4}").unwrap();
+    assert!(migrate(&mut doc, TEST_MIGRATIONS, 3).is_err());
+    let mut doc = json::parse("{schema_version: 'one'}").unwrap();
+    assert!(migrate(&mut doc, TEST_MIGRATIONS, 3).is_err());
+    let mut doc = json::parse("{open: 1, preload: {open: 2}}").unwrap();
+    assert!(migrate(&mut doc, TEST_MIGRATIONS, 3).is_err());
+}
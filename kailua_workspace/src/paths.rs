@@ -0,0 +1,203 @@
+//! A policy for deciding whether two paths refer to the same file.
+//!
+//! Different layers (file discovery, `require` resolution, caches and indexes) have to agree
+//! on path identities, otherwise the same file may be parsed twice or missed from caches.
+//! `PathPolicy` is configured once per workspace (as a part of `Sys`)
+//! and all such layers go through it.
+
+use std::path::{Component, Path, PathBuf};
+use sys::FileSystem;
+
+/// How paths are canonicalized before being compared.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Canonicalize {
+    /// Paths are compared as given, except for separators, cases and `.` components.
+    None,
+
+    /// `.` and `..` components are removed without consulting the file system.
+    ///
+    /// `a/../b` is considered same to `b` even when `a` is a symbolic link.
+    Lexical,
+
+    /// Paths are canonicalized by the file system, resolving symbolic links.
+    /// Paths which cannot be canonicalized (e.g. missing files) are canonicalized lexically.
+    FileSystem,
+}
+
+/// A path normalization policy.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PathPolicy {
+    /// False if paths only different in ASCII cases refer to the same file.
+    pub case_sensitive: bool,
+
+    /// True if `\` is a separator in addition to `/`.
+    ///
+    /// Always true in Windows. Can be set in other platforms for paths written in Windows.
+    pub backslash_is_separator: bool,
+
+    pub canonicalize: Canonicalize,
+
+    /// True if the file discovery follows symbolic links to directories.
+    pub follow_symlinks: bool,
+}
+
+impl PathPolicy {
+    /// Returns the usual policy for the current platform.
+    ///
+    /// Paths are case-insensitive in Windows and macOS (the default file systems are),
+    /// canonicalized by the file system and symbolic links are followed.
+    pub fn native() -> PathPolicy {
+        PathPolicy {
+            case_sensitive: !cfg!(any(windows, target_os = "macos")),
+            backslash_is_separator: cfg!(windows),
+            canonicalize: Canonicalize::FileSystem,
+            follow_symlinks: true,
+        }
+    }
+
+    fn components(&self, path: &Path) -> Vec<String> {
+        let converted;
+        let path = if self.backslash_is_separator && !cfg!(windows) {
+            match path.to_str() {
+                Some(s) if s.contains('\\') => {
+                    converted = PathBuf::from(s.replace('\\', "/"));
+                    &converted
+                }
+                _ => path,
+            }
+        } else {
+            path
+        };
+
+        // the root directory is represented as an empty component,
+        // so that joining components with `/` results in a leading `/`
+        path.components().map(|c| match c {
+            Component::RootDir => String::new(),
+            c => c.as_os_str().to_string_lossy().into_owned(),
+        }).collect()
+    }
+
+    fn fold_case(&self, s: &str) -> String {
+        if self.case_sensitive { s.to_owned() } else { s.to_ascii_lowercase() }
+    }
+
+    /// Removes `.` and `..` components and unifies separators without accessing files.
+    ///
+    /// `..` components which cannot be removed (e.g. at the beginning) are kept.
+    pub fn lexical(&self, path: &Path) -> PathBuf {
+        let mut out: Vec<Component> = Vec::new();
+        let converted = PathBuf::from(self.components(path).join("/"));
+        for c in converted.components() {
+            match c {
+                Component::CurDir => {}
+                Component::ParentDir => match out.last() {
+                    Some(&Component::Normal(_)) => { out.pop(); }
+                    Some(&Component::RootDir) | Some(&Component::Prefix(_)) => {}
+                    _ => out.push(c),
+                },
+                c => out.push(c),
+            }
+        }
+        if out.is_empty() {
+            PathBuf::from(".")
+        } else {
+            out.iter().map(|c| c.as_os_str()).collect()
+        }
+    }
+
+    /// Returns a canonical path according to the policy.
+    /// The case is preserved even when paths are case-insensitive.
+    pub fn canonical(&self, fs: &FileSystem, path: &Path) -> PathBuf {
+        match self.canonicalize {
+            Canonicalize::None => path.to_owned(),
+            Canonicalize::Lexical => self.lexical(path),
+            Canonicalize::FileSystem => {
+                fs.canonicalize(path).unwrap_or_else(|_| self.lexical(path))
+            }
+        }
+    }
+
+    /// Returns a string which is equal for and only for paths to the same file.
+    ///
+    /// Separators are always `/` in the key.
+    pub fn key(&self, fs: &FileSystem, path: &Path) -> String {
+        let path = self.canonical(fs, path);
+        self.fold_case(&self.components(&path).join("/"))
+    }
+
+    /// Returns true if two paths refer to the same file.
+    pub fn same(&self, fs: &FileSystem, a: &Path, b: &Path) -> bool {
+        self.key(fs, a) == self.key(fs, b)
+    }
+
+    /// Returns true if `path` is inside `base` (or is `base`), comparing paths lexically.
+    pub fn starts_with(&self, path: &Path, base: &Path) -> bool {
+        self.strip_prefix(path, base).is_some()
+    }
+
+    fn strip_prefix(&self, path: &Path, base: &Path) -> Option<Vec<String>> {
+        let path = self.components(&self.lexical(path));
+        let base = self.components(&self.lexical(base));
+        if base == ["."] {
+            return Some(path);
+        }
+        if path.len() < base.len() {
+            return None;
+        }
+        let matches = path.iter().zip(base.iter()).all(|(a, b)| {
+            self.fold_case(a) == self.fold_case(b)
+        });
+        if matches { Some(path[base.len()..].to_owned()) } else { None }
+    }
+
+    /// Returns a path relative to `base_dir` if possible, with `/` as a separator.
+    /// Used for paths in the output, so the case is preserved.
+    pub fn relative(&self, path: &Path, base_dir: &Path) -> String {
+        match self.strip_prefix(path, base_dir) {
+            Some(ref rest) if !rest.is_empty() => rest.join("/"),
+            _ => self.components(&self.lexical(path)).join("/"),
+        }
+    }
+}
+
+impl Default for PathPolicy {
+    fn default() -> PathPolicy {
+        PathPolicy::native()
+    }
+}
+
+#[test]
+fn test_path_policy() {
+    use sys::FakeFileSystem;
+
+    let fs = FakeFileSystem::new();
+    let mut policy = PathPolicy {
+        case_sensitive: true,
+        backslash_is_separator: false,
+        canonicalize: Canonicalize::Lexical,
+        follow_symlinks: true,
+    };
+
+    assert_eq!(policy.lexical(Path::new("a/./b/../c.lua")), PathBuf::from("a/c.lua"));
+    assert_eq!(policy.lexical(Path::new("../a/..")), PathBuf::from(".."));
+    assert_eq!(policy.lexical(Path::new("/../a")), PathBuf::from("/a"));
+    assert_eq!(policy.lexical(Path::new("a/..")), PathBuf::from("."));
+
+    assert!(policy.same(&fs, Path::new("/ws/./lib/../a.lua"), Path::new("/ws/a.lua")));
+    assert!(!policy.same(&fs, Path::new("/ws/A.lua"), Path::new("/ws/a.lua")));
+    assert_eq!(policy.key(&fs, Path::new("/ws//a.lua")), "/ws/a.lua");
+    assert_eq!(policy.relative(Path::new("/ws/lib/a.lua"), Path::new("/ws")), "lib/a.lua");
+    assert_eq!(policy.relative(Path::new("/other/a.lua"), Path::new("/ws")), "/other/a.lua");
+    assert_eq!(policy.relative(Path::new("lib/a.lua"), Path::new(".")), "lib/a.lua");
+    assert!(policy.starts_with(Path::new("/ws/game/a.lua"), Path::new("/ws/game")));
+    assert!(!policy.starts_with(Path::new("/ws/gamelib/a.lua"), Path::new("/ws/game")));
+
+    policy.case_sensitive = false;
+    policy.backslash_is_separator = true;
+    assert!(policy.same(&fs, Path::new("/WS/Lib\\A.lua"), Path::new("/ws/lib/a.lua")));
+    assert!(policy.starts_with(Path::new("/WS/a.lua"), Path::new("/ws")));
+    assert_eq!(policy.relative(Path::new("/WS/Lib\\A.lua"), Path::new("/ws")), "Lib/A.lua");
+
+    policy.canonicalize = Canonicalize::None;
+    assert!(!policy.same(&fs, Path::new("/ws/lib/../a.lua"), Path::new("/ws/a.lua")));
+}
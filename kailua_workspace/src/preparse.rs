@@ -0,0 +1,448 @@
+//! Parallel pre-parsing of workspace files.
+//!
+//! Parsing a file does not depend on any other file, so every file discovered in the workspace
+//! can be parsed ahead of the (inherently sequential) type checking.
+//! The results are kept in a `ChunkCache` which is then consulted by `FsSource` implementations.
+
+use std::mem;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use walkdir::WalkDir;
+
+use kailua_env::{Span, Source, SourceFile};
+use kailua_diag::{self, Kind, Report, CollectedReport, Locale};
+use kailua_syntax::{parse_chunk_with_language, Chunk};
+use kailua_syntax::lang::Language;
+use kailua_check::env::Output;
+use sys::{Sys, FileSystem, RealFileSystem};
+use paths::PathPolicy;
+
+/// A result of parsing a single file, with all reports collected (and not yet reported).
+pub struct ParsedChunk {
+    /// The parsed chunk, or `None` if the parsing has been stopped due to errors.
+    pub chunk: Option<Chunk>,
+
+    /// Reports generated while parsing, in the order.
+    pub reports: Vec<(Kind, Span, String)>,
+}
+
+impl ParsedChunk {
+    /// Sends collected reports to the actual `Report` and returns the chunk if any.
+    ///
+    /// This should be called exactly when the file would have been parsed,
+    /// so that the order of reports is the same to the sequential parsing.
+    pub fn replay(self, report: &Report) -> kailua_diag::Result<Chunk> {
+        for (kind, span, msg) in self.reports {
+            // a fatal report returns `Err` and will be handled by the caller below
+            let _ = report.add_span(kind, span, &msg);
+        }
+        self.chunk.ok_or(kailua_diag::Stop)
+    }
+}
+
+/// A cache of parsed chunks keyed by their path identities as per the path policy.
+pub struct ChunkCache {
+    sys: Sys,
+    chunks: HashMap<String, ParsedChunk>,
+}
+
+impl ChunkCache {
+    pub fn new() -> ChunkCache {
+        ChunkCache { sys: Sys::real(), chunks: HashMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Removes and returns the parsed chunk for given path, if any.
+    ///
+    /// A chunk is given only once; the caller is expected to parse the file by itself
+    /// if it is requested again (which is the case for the sequential parsing as well).
+    pub fn take(&mut self, path: &Path) -> Option<ParsedChunk> {
+        self.chunks.remove(&self.sys.path_key(path))
+    }
+}
+
+/// Returns a list of all Lua and Kailua files in given directory.
+pub fn discover_files(base_dir: &Path) -> Vec<PathBuf> {
+    discover_files_with_policy(base_dir, &PathPolicy::native())
+}
+
+/// Same to `discover_files` but symbolic links are followed only when the policy says so.
+///
+/// Files reachable via multiple paths (e.g. symbolic links) are listed only once.
+pub fn discover_files_with_policy(base_dir: &Path, policy: &PathPolicy) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut seen = HashSet::new();
+    for e in WalkDir::new(base_dir).follow_links(policy.follow_symlinks) {
+        // we don't care about I/O errors and (in Unix) symlink loops
+        let e = if let Ok(e) = e { e } else { continue };
+        if !e.file_type().is_file() { continue; }
+
+        let ext = e.path().extension();
+        if ext == Some("lua".as_ref()) || ext == Some("kailua".as_ref()) {
+            if seen.insert(policy.key(&RealFileSystem, e.path())) {
+                paths.push(e.path().to_owned());
+            }
+        }
+    }
+    paths
+}
+
+// runs `f` for each item in `items` with at most `jobs` threads.
+// each thread takes the next unprocessed item whenever it finishes the previous one,
+// so a large item does not block others queued to the same thread.
+fn run_parallel<T, U, F>(items: Vec<T>, jobs: usize, f: F) -> Vec<U>
+    where T: Send + Sync + 'static, U: Send + 'static, F: Fn(&T) -> U + Send + Sync + 'static
+{
+    let nitems = items.len();
+    let jobs = jobs.max(1).min(nitems);
+    if jobs <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let items = Arc::new(items);
+    let next = Arc::new(AtomicUsize::new(0));
+    let results = Arc::new(Mutex::new((0..nitems).map(|_| None).collect::<Vec<_>>()));
+    let f = Arc::new(f);
+
+    let threads: Vec<_> = (0..jobs).map(|_| {
+        let items = items.clone();
+        let next = next.clone();
+        let results = results.clone();
+        let f = f.clone();
+        thread::spawn(move || {
+            loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= items.len() { break; }
+                let ret = f(&items[i]);
+                results.lock().unwrap()[i] = Some(ret);
+            }
+        })
+    }).collect();
+    for t in threads {
+        t.join().expect("pre-parsing thread has panicked");
+    }
+
+    let results = Arc::try_unwrap(results).ok().expect("pre-parsing threads still alive");
+    results.into_inner().unwrap().into_iter().map(|ret| ret.unwrap()).collect()
+}
+
+/// The order in which files are parsed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// Costly files go first, so that the last file to be parsed is more likely to be cheap.
+    /// This minimizes the total time with multiple threads.
+    CostlyFirst,
+
+    /// Cheap files go first, so that more files are available early.
+    /// This is useful for giving a fast feedback.
+    CheapFirst,
+}
+
+/// Historical parsing and checking durations per file, used to estimate the cost of each file.
+///
+/// The history can be saved to and loaded from a file, so that it persists across runs.
+/// Files without any parsing record are estimated from their sizes.
+#[derive(Clone, Debug)]
+pub struct History {
+    durations: HashMap<PathBuf, Duration>,
+    check_durations: HashMap<PathBuf, Duration>,
+}
+
+// an assumed throughput when there is no history at all
+const DEFAULT_BYTES_PER_MICROSEC: f64 = 10.0;
+
+impl History {
+    pub fn new() -> History {
+        History { durations: HashMap::new(), check_durations: HashMap::new() }
+    }
+
+    /// Reads the history from given file. Returns an empty history if the file is missing.
+    ///
+    /// The format is a list of lines, each with `parse` or `check`, microseconds and a path
+    /// separated by tabs. Malformed lines are silently ignored as the history is merely
+    /// an estimate.
+    pub fn load(fs: &FileSystem, path: &Path) -> io::Result<History> {
+        let mut history = History::new();
+        let data = match fs.read(path) {
+            Ok(data) => data,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(history),
+            Err(e) => return Err(e),
+        };
+
+        for line in String::from_utf8_lossy(&data).lines() {
+            let mut fields = line.splitn(3, '\t');
+            if let (Some(kind), Some(micros), Some(path)) =
+                    (fields.next(), fields.next(), fields.next()) {
+                let durations = match kind {
+                    "parse" => &mut history.durations,
+                    "check" => &mut history.check_durations,
+                    _ => continue,
+                };
+                if let Ok(micros) = micros.parse::<u64>() {
+                    durations.insert(PathBuf::from(path), from_micros(micros));
+                }
+            }
+        }
+        Ok(history)
+    }
+
+    /// Writes the history to given file, creating parent directories as needed.
+    pub fn save(&self, fs: &FileSystem, path: &Path) -> io::Result<()> {
+        let mut data = Vec::new();
+        for &(kind, durations) in &[("parse", &self.durations),
+                                    ("check", &self.check_durations)] {
+            let mut entries: Vec<_> = durations.iter().collect();
+            entries.sort();
+            for (path, duration) in entries {
+                writeln!(data, "{}\t{}\t{}", kind, to_micros(*duration), path.display())?;
+            }
+        }
+        fs.write_file(path, &data)
+    }
+
+    /// Returns the last parsing duration of given file.
+    pub fn get(&self, path: &Path) -> Option<Duration> {
+        self.durations.get(path).cloned()
+    }
+
+    pub fn record(&mut self, path: PathBuf, duration: Duration) {
+        self.durations.insert(path, duration);
+    }
+
+    /// Returns the last checking duration of given file,
+    /// excluding the time spent for other modules required by it.
+    pub fn check_duration(&self, path: &Path) -> Option<Duration> {
+        self.check_durations.get(path).cloned()
+    }
+
+    pub fn record_check(&mut self, path: PathBuf, duration: Duration) {
+        self.check_durations.insert(path, duration);
+    }
+
+    /// Records checking durations of all modules checked in given output,
+    /// keyed by canonical paths as `preparse_with_sys` does.
+    ///
+    /// Modules not checked at all (e.g. replaced with their cached signatures) are left as is.
+    pub fn record_checks(&mut self, output: &Output, source: &Source, sys: &Sys) {
+        for (unit, duration) in output.stats().module_durations() {
+            if let Some(file) = source.file(unit) {
+                let path = sys.paths.canonical(&*sys.fs, Path::new(file.path()));
+                self.record_check(path, duration);
+            }
+        }
+    }
+
+    /// Sorts given paths by their last checking durations.
+    /// Paths without any record are considered cheapest.
+    pub fn sort_by_check_cost(&self, paths: &mut [PathBuf], order: Order, sys: &Sys) {
+        let cost = |path: &PathBuf| {
+            self.check_duration(&sys.paths.canonical(&*sys.fs, path)).unwrap_or_default()
+        };
+        match order {
+            Order::CostlyFirst => paths.sort_by(|a, b| cost(b).cmp(&cost(a))),
+            Order::CheapFirst => paths.sort_by(|a, b| cost(a).cmp(&cost(b))),
+        }
+    }
+
+    // the observed throughput, used for files without a history
+    fn bytes_per_microsec(&self, sizes: &HashMap<PathBuf, usize>) -> f64 {
+        let mut bytes = 0;
+        let mut micros = 0;
+        for (path, duration) in &self.durations {
+            if let Some(&size) = sizes.get(path) {
+                bytes += size as u64;
+                micros += to_micros(*duration);
+            }
+        }
+        if bytes > 0 && micros > 0 {
+            bytes as f64 / micros as f64
+        } else {
+            DEFAULT_BYTES_PER_MICROSEC
+        }
+    }
+}
+
+fn to_micros(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000 + (duration.subsec_nanos() / 1000) as u64
+}
+
+fn from_micros(micros: u64) -> Duration {
+    Duration::new(micros / 1_000_000, (micros % 1_000_000) as u32 * 1000)
+}
+
+/// A receiver of the pre-parsing progress.
+///
+/// This is called from multiple threads, but never concurrently.
+pub trait Progress: Send + Sync {
+    /// Called after each file has been parsed.
+    ///
+    /// `remaining` is the estimated time until all files get parsed,
+    /// based on the history and the number of threads.
+    fn update(&self, done: usize, total: usize, remaining: Duration);
+}
+
+/// Reads and parses all given files in parallel with `jobs` threads.
+///
+/// All files successfully read are added to `source` regardless of parsing errors.
+/// Reports are collected into the cache with given locale and not reported immediately.
+pub fn preparse(paths: Vec<PathBuf>, source: &mut Source,
+                locale: Locale, jobs: usize) -> ChunkCache {
+    preparse_with_history(paths, source, locale, jobs, Order::CostlyFirst,
+                          &mut History::new(), None)
+}
+
+/// Same to `preparse` but files are ordered by the estimated cost from `history`,
+/// which gets updated with durations observed in this run.
+///
+/// The estimated remaining time is sent to `progress` if any.
+pub fn preparse_with_history(paths: Vec<PathBuf>, source: &mut Source, locale: Locale,
+                             jobs: usize, order: Order, history: &mut History,
+                             progress: Option<Arc<Progress>>) -> ChunkCache {
+    preparse_with_sys(paths, source, locale, Language::default(), jobs, order, history, progress,
+                      &Sys::real())
+}
+
+/// Same to `preparse_with_history` but files are parsed with given language, and read from
+/// (and durations are measured with) given file system and clock.
+pub fn preparse_with_sys(paths: Vec<PathBuf>, source: &mut Source, locale: Locale,
+                         language: Language, jobs: usize, order: Order, history: &mut History,
+                         progress: Option<Arc<Progress>>, sys: &Sys) -> ChunkCache {
+    let files: Vec<_> = {
+        let sys = sys.clone();
+        run_parallel(paths, jobs, move |path| {
+            sys.fs.read(path).ok().map(|data| {
+                let file = SourceFile::from_u8(path.display().to_string(), data);
+                (sys.paths.canonical(&*sys.fs, path), file)
+            })
+        }).into_iter().filter_map(|f| f).collect()
+    };
+
+    let sizes: HashMap<_, _> =
+        files.iter().map(|&(ref path, ref file)| (path.clone(), file.data().len())).collect();
+    let bytes_per_microsec = history.bytes_per_microsec(&sizes);
+    let mut files: Vec<_> = files.into_iter().map(|(path, file)| {
+        let cost = history.get(&path).map_or_else(|| {
+            (file.data().len() as f64 / bytes_per_microsec) as u64
+        }, to_micros);
+        (cost, path, file)
+    }).collect();
+    match order {
+        Order::CostlyFirst => files.sort_by(|a, b| b.0.cmp(&a.0)),
+        Order::CheapFirst => files.sort_by(|a, b| a.0.cmp(&b.0)),
+    }
+
+    let mut spans = Vec::new();
+    for (cost, path, file) in files {
+        spans.push((cost, path, source.add(file)));
+    }
+
+    // (number of parsed files, estimated cost of remaining files in microseconds)
+    let total = spans.len();
+    let remaining = spans.iter().map(|&(cost, _, _)| cost).sum::<u64>();
+    let status = Arc::new(Mutex::new((0, remaining)));
+    let nthreads = jobs.max(1).min(total).max(1) as u64;
+
+    // parsing threads need a read-only access to the source, which we temporarily own
+    let shared = Arc::new(mem::replace(source, Source::new()));
+    let parsed = {
+        let shared = shared.clone();
+        let clock = sys.clock.clone();
+        run_parallel(spans, jobs, move |&(cost, ref path, span)| {
+            let start = clock.now();
+            let report = CollectedReport::new(locale);
+            let chunk = parse_chunk_with_language(&shared, span, language, &report).ok();
+            let elapsed = clock.now() - start;
+
+            if let Some(ref progress) = progress {
+                let mut status = status.lock().unwrap();
+                status.0 += 1;
+                status.1 = status.1.saturating_sub(cost);
+                progress.update(status.0, total, from_micros(status.1 / nthreads));
+            }
+
+            (path.clone(), elapsed,
+             ParsedChunk { chunk: chunk, reports: report.into_reports() })
+        })
+    };
+    *source = Arc::try_unwrap(shared).ok().expect("pre-parsing threads still alive");
+
+    let mut chunks = HashMap::new();
+    for (path, elapsed, parsed) in parsed {
+        chunks.insert(sys.path_key(&path), parsed);
+        history.record(path, elapsed);
+    }
+    ChunkCache { sys: sys.clone(), chunks: chunks }
+}
+
+#[test]
+fn test_run_parallel() {
+    let items: Vec<usize> = (0..100).collect();
+    for &jobs in &[0, 1, 4, 200] {
+        let ret = run_parallel(items.clone(), jobs, |&i| i * 2);
+        assert_eq!(ret, (0..100).map(|i| i * 2).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn test_history_roundtrip() {
+    use sys::{FakeClock, FakeFileSystem};
+
+    let mut history = History::new();
+    history.record(PathBuf::from("/a.lua"), Duration::new(1, 500_000));
+    history.record(PathBuf::from("/b c.lua"), Duration::new(0, 42_000));
+    history.record_check(PathBuf::from("/b c.lua"), Duration::new(2, 0));
+
+    let fs = Arc::new(FakeFileSystem::new());
+    let path = Path::new("/cache/history.txt");
+    assert!(History::load(&*fs, path).unwrap().durations.is_empty()); // missing file
+    history.save(&*fs, path).unwrap();
+    let loaded = History::load(&*fs, path).unwrap();
+
+    assert_eq!(loaded.get(Path::new("/a.lua")), Some(Duration::new(1, 500_000)));
+    assert_eq!(loaded.get(Path::new("/b c.lua")), Some(Duration::new(0, 42_000)));
+    assert_eq!(loaded.get(Path::new("/c.lua")), None);
+    assert_eq!(loaded.check_duration(Path::new("/a.lua")), None);
+    assert_eq!(loaded.check_duration(Path::new("/b c.lua")), Some(Duration::new(2, 0)));
+
+    fs.write("/a.lua", "");
+    fs.write("/b c.lua", "");
+    let sys = Sys { clock: Arc::new(FakeClock::new()), fs: fs, paths: PathPolicy::native() };
+    let mut paths = vec![PathBuf::from("/b c.lua"), PathBuf::from("/a.lua")];
+    loaded.sort_by_check_cost(&mut paths, Order::CheapFirst, &sys);
+    assert_eq!(paths, [PathBuf::from("/a.lua"), PathBuf::from("/b c.lua")]);
+    loaded.sort_by_check_cost(&mut paths, Order::CostlyFirst, &sys);
+    assert_eq!(paths, [PathBuf::from("/b c.lua"), PathBuf::from("/a.lua")]);
+}
+
+#[test]
+fn test_preparse_with_fake_sys() {
+    use sys::{FakeClock, FakeFileSystem};
+
+    let fs = Arc::new(FakeFileSystem::new());
+    fs.write("/a.lua", "local x = 1");
+    fs.write("/b.lua", "local y = ");
+    let mut paths = PathPolicy::native();
+    paths.case_sensitive = false;
+    let sys = Sys { clock: Arc::new(FakeClock::new()), fs: fs, paths: paths };
+
+    let mut source = Source::new();
+    let mut history = History::new();
+    let paths = vec![PathBuf::from("/a.lua"), PathBuf::from("/b.lua"), PathBuf::from("/c.lua")];
+    let mut cache = preparse_with_sys(paths, &mut source, Locale::dummy(), Language::default(), 2,
+                                      Order::CostlyFirst, &mut history, None, &sys);
+    assert_eq!(cache.len(), 2);
+    assert!(cache.take(Path::new("/A.lua")).unwrap().chunk.is_some());
+    assert!(!cache.take(Path::new("/./b.lua")).unwrap().reports.is_empty());
+    assert!(cache.take(Path::new("/c.lua")).is_none());
+
+    // the fake clock never advances by itself
+    assert_eq!(history.get(Path::new("/a.lua")), Some(Duration::new(0, 0)));
+}
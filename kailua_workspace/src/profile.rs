@@ -0,0 +1,75 @@
+//! Named strictness profiles.
+//!
+//! A number of options control how strict the checker is, and tuning all of them
+//! by hand is tedious and error-prone. A profile is a preset for these options,
+//! selected with `"profile": NAME` in the configuration. Individual options given
+//! in the same configuration (e.g. `strict_nil` or `severity`) take precedence over the preset.
+
+use kailua_check::lint::Severity;
+
+/// The diagnostic code for adding a new field to a record from a table constructor.
+const SEALED_RECORD_CODE: &'static str = "KC0052";
+
+/// A preset for options controlling the strictness of the checker.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// For gradually adopting Kailua in an existing code base.
+    ///
+    /// Records from table constructors can be extended and unused variables are not reported.
+    Lenient,
+
+    /// The default behavior of the checker.
+    Standard,
+
+    /// For code bases fully annotated with Kailua.
+    ///
+    /// The strict nil-safety and strict globals modes are enabled and
+    /// unused variables and assignments are reported as errors.
+    Strict,
+}
+
+impl Profile {
+    /// Returns all profiles, from the most lenient.
+    pub fn all() -> &'static [Profile] {
+        &[Profile::Lenient, Profile::Standard, Profile::Strict]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Profile::Lenient => "lenient",
+            Profile::Standard => "standard",
+            Profile::Strict => "strict",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Profile> {
+        Profile::all().iter().cloned().find(|profile| profile.name() == name)
+    }
+
+    /// The value of `Options::strict_nil` for this profile.
+    pub fn strict_nil(&self) -> bool {
+        *self == Profile::Strict
+    }
+
+    /// The value of `Options::strict_globals` for this profile.
+    pub fn strict_globals(&self) -> bool {
+        *self == Profile::Strict
+    }
+
+    /// Lint severities for this profile, keyed by the lint or diagnostic code.
+    ///
+    /// Every profile gives the severities for the same set of codes,
+    /// so switching profiles never leaves a severity from the previous one.
+    pub fn lint_severities(&self) -> Vec<(&'static str, Severity)> {
+        let (sealed, unused) = match *self {
+            Profile::Lenient => (Severity::Allow, Severity::Allow),
+            Profile::Standard => (Severity::Error, Severity::Warn),
+            Profile::Strict => (Severity::Error, Severity::Error),
+        };
+        vec![
+            (SEALED_RECORD_CODE, sealed),
+            ("unused-assignment", unused),
+            ("unused-variable", unused),
+        ]
+    }
+}
@@ -0,0 +1,61 @@
+//! A source reading files through the workspace file system while recording loaded files.
+//!
+//! Used by `Workspace::check_files` and `Workspace::repro_check`,
+//! which need to know files loaded by the checker after the checking.
+
+use std::io;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use kailua_env::{Spanned, Source, SourceFile, Unit};
+use kailua_diag::{Report, Stop};
+use kailua_syntax::{parse_chunk_with_language, Chunk};
+use kailua_syntax::lang::Language;
+use kailua_check::options::FsSource;
+use sys::FileSystem;
+
+/// Files loaded by `RecordingFsSource`, each with a unit in the source, in the order of loading.
+///
+/// A file loaded multiple times (e.g. once per start path) is recorded each time.
+pub type LoadedFiles = Rc<RefCell<Vec<(PathBuf, Unit)>>>;
+
+/// Returns paths to loaded files without duplicates, in the order of the first loading.
+pub fn loaded_paths(loaded: &[(PathBuf, Unit)]) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for &(ref path, _) in loaded {
+        if !paths.contains(path) {
+            paths.push(path.clone());
+        }
+    }
+    paths
+}
+
+/// `FsSource` reading files through the workspace file system and recording loaded files.
+pub struct RecordingFsSource {
+    pub source: Rc<RefCell<Source>>,
+    pub fs: Arc<FileSystem>,
+    pub loaded: LoadedFiles,
+    pub language: Language,
+}
+
+impl FsSource for RecordingFsSource {
+    fn chunk_from_path(&self, resolved_path: Spanned<&Path>,
+                       report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+        let data = match self.fs.read(&resolved_path) {
+            Ok(data) => data,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(_) => return Err(None),
+        };
+
+        let file = SourceFile::from_u8(resolved_path.display().to_string(), data);
+        let span = self.source.borrow_mut().add(file);
+        self.loaded.borrow_mut().push((resolved_path.to_path_buf(), span.unit()));
+        let chunk = parse_chunk_with_language(&self.source.borrow(), span, self.language, report);
+        chunk.map(Some).map_err(|_| Some(Stop)) // we have already reported parsing errors
+    }
+
+    fn path_exists(&self, resolved_path: Spanned<&Path>) -> bool {
+        self.fs.is_file(&resolved_path)
+    }
+}
@@ -0,0 +1,408 @@
+//! Self-contained reproducers for diagnostics and crashes.
+//!
+//! `Workspace::export_repro` checks the workspace while recording every file the checker loads,
+//! and writes the effective configuration, those files and the version of Kailua
+//! into a single JSON file that users can attach to bug reports.
+//! `ReproBundle::replay` recreates the workspace on an in-memory file system,
+//! so that maintainers can check it directly in tests with `Workspace::repro_check`.
+
+use std::str;
+use std::io::{self, Read, Write};
+use std::fs::File;
+use std::panic::{self, AssertUnwindSafe};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use serde_json::{self, Map, Value};
+use kailua_env::{Span, Source, SourceFile, WithLoc};
+use kailua_diag::{Report, CollectedReport, Kind, Locale};
+use kailua_syntax::{Lexer, Tok};
+use kailua_check::{check_from_chunk_with_preloading, verify_assertions};
+use kailua_check::env::Context;
+use kailua_check::options::FsSource;
+use sys::{Sys, FakeClock, FakeFileSystem};
+use recording::{self, RecordingFsSource};
+use {Config, Workspace, WorkspaceOptions};
+
+/// The current version of the bundle format. Bundles of other versions are rejected.
+pub const FORMAT_VERSION: u64 = 1;
+
+/// The name of the configuration file in the bundle, relative to the base directory.
+pub const CONFIG_FILE_NAME: &'static str = "kailua.json";
+
+/// Options for exporting a reproducer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReproOptions {
+    /// Blanks out ordinary comments (but not meta comments like `--:`) in exported files.
+    ///
+    /// Comments frequently contain private notes but rarely affect the checking.
+    /// Comment delimiters and line breaks are kept, so diagnostics stay at the same positions.
+    pub strip_comments: bool,
+}
+
+/// A configuration and files required to reproduce the checking of a workspace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReproBundle {
+    /// The version of Kailua which has created the bundle.
+    pub version: String,
+
+    /// The effective configuration as a JSON object, with global configurations,
+    /// included files and overrides already merged.
+    pub config: Value,
+
+    /// Contents of files keyed by paths relative to the base directory, with `/` as a separator.
+    ///
+    /// Files outside of the base directory are keyed by their absolute paths.
+    pub files: BTreeMap<String, Vec<u8>>,
+
+    /// True if comments in files have been blanked out.
+    pub comments_stripped: bool,
+}
+
+/// A result of `Workspace::repro_check`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReproCheck {
+    /// Files loaded by the checker, in the order of loading.
+    pub files: Vec<PathBuf>,
+
+    /// Reports formatted as `path:line:column: [Kind] message`,
+    /// where the path is relative to the base directory when possible.
+    pub reports: Vec<String>,
+
+    /// The panic message if the checker has crashed.
+    pub panic: Option<String>,
+}
+
+fn panic_message(payload: Box<::std::any::Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&'static str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "(unknown panic)".to_owned()
+    }
+}
+
+// blanks out ordinary comments in the Lua source, keeping line breaks and comment delimiters
+fn strip_comments(data: &[u8]) -> Vec<u8> {
+    let mut source = Source::new();
+    let span = source.add(SourceFile::from_u8(String::new(), data.to_owned()));
+    let base = span.begin().to_usize();
+
+    let mut stripped = data.to_owned();
+    if let Some(mut iter) = source.iter_from_span(span) {
+        // lexing errors are not our concern, the original file has the same problem
+        let report = CollectedReport::new(Locale::dummy());
+        let lexer = Lexer::new(&mut iter, &report);
+        for tok in lexer {
+            if tok.base == Tok::Comment {
+                let begin = tok.span.begin().to_usize() - base;
+                let end = tok.span.end().to_usize() - base;
+                blank_comment(&mut stripped[begin..end]);
+            }
+        }
+    }
+    stripped
+}
+
+fn blank_comment(comment: &mut [u8]) {
+    let mut begin = 1; // a shebang line; keeps `#`
+    let mut end = comment.len();
+    if comment.starts_with(b"--") {
+        begin = 2;
+        // keeps `[==[` and `]==]` of long comments
+        let level = comment[2..].iter().skip(1).take_while(|&&c| c == b'=').count();
+        if comment.get(2) == Some(&b'[') && comment.get(3 + level) == Some(&b'[') {
+            begin = 4 + level;
+            let mut closing = vec![b']'];
+            closing.extend((0..level).map(|_| b'='));
+            closing.push(b']');
+            if comment[begin..].ends_with(&closing) {
+                end -= closing.len();
+            }
+        }
+    }
+    if begin < end {
+        for c in &mut comment[begin..end] {
+            if *c != b'\n' && *c != b'\r' {
+                *c = b' ';
+            }
+        }
+    }
+}
+
+impl ReproBundle {
+    /// Exports the bundle as a JSON object.
+    ///
+    /// File contents are strings when they are valid UTF-8, and arrays of bytes otherwise.
+    pub fn to_json(&self) -> Value {
+        let files = self.files.iter().map(|(path, data)| {
+            let data = match str::from_utf8(data) {
+                Ok(s) => Value::String(s.to_owned()),
+                Err(_) => Value::Array(data.iter().map(|&c| Value::from(c)).collect()),
+            };
+            (path.clone(), data)
+        }).collect();
+
+        let mut map = Map::new();
+        map.insert("format".to_owned(), Value::from(FORMAT_VERSION));
+        map.insert("version".to_owned(), Value::String(self.version.clone()));
+        map.insert("comments_stripped".to_owned(), Value::Bool(self.comments_stripped));
+        map.insert("config".to_owned(), self.config.clone());
+        map.insert("files".to_owned(), Value::Object(files));
+        Value::Object(map)
+    }
+
+    /// Reads the bundle from a JSON object returned by `ReproBundle::to_json`.
+    pub fn from_json(value: &Value) -> Result<ReproBundle, String> {
+        match value.get("format").and_then(|v| v.as_u64()) {
+            Some(FORMAT_VERSION) => {}
+            Some(format) => return Err(format!("unsupported reproducer format {}", format)),
+            None => return Err("missing or bad `format` in the reproducer".to_owned()),
+        }
+
+        let version = value.get("version").and_then(|v| v.as_str()).unwrap_or("");
+        let comments_stripped = value.get("comments_stripped").and_then(|v| v.as_bool());
+        let config = match value.get("config") {
+            Some(config @ &Value::Object(_)) => config.clone(),
+            _ => return Err("missing or bad `config` in the reproducer".to_owned()),
+        };
+
+        let mut files = BTreeMap::new();
+        let entries = value.get("files").and_then(|v| v.as_object()).ok_or_else(|| {
+            "missing or bad `files` in the reproducer".to_owned()
+        })?;
+        for (path, data) in entries {
+            let data = match *data {
+                Value::String(ref s) => s.as_bytes().to_owned(),
+                Value::Array(ref bytes) => {
+                    let bytes: Option<Vec<u8>> = bytes.iter().map(|c| {
+                        c.as_u64().and_then(|c| if c < 256 { Some(c as u8) } else { None })
+                    }).collect();
+                    bytes.ok_or_else(|| format!("bad contents for `{}` in the reproducer", path))?
+                }
+                _ => return Err(format!("bad contents for `{}` in the reproducer", path)),
+            };
+            files.insert(path.clone(), data);
+        }
+
+        Ok(ReproBundle {
+            version: version.to_owned(),
+            config: config,
+            files: files,
+            comments_stripped: comments_stripped.unwrap_or(false),
+        })
+    }
+
+    /// Writes the bundle to given path as JSON.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let data = serde_json::to_vec_pretty(&self.to_json())?;
+        File::create(path)?.write_all(&data)
+    }
+
+    /// Reads the bundle written by `ReproBundle::save`.
+    pub fn load(path: &Path) -> io::Result<ReproBundle> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        let value: Value = serde_json::from_slice(&data)?;
+        ReproBundle::from_json(&value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Recreates the workspace at given base directory (which should be absolute)
+    /// on a fresh in-memory file system with a fake clock.
+    ///
+    /// The configuration is written to `CONFIG_FILE_NAME` in the base directory.
+    pub fn replay(&self, base_dir: &Path, default_locale: Locale) -> io::Result<Workspace> {
+        let fs = FakeFileSystem::new();
+        for (path, data) in &self.files {
+            fs.write(base_dir.join(path), data.clone());
+        }
+        let config_path = base_dir.join(CONFIG_FILE_NAME);
+        fs.write(config_path.clone(), serde_json::to_vec_pretty(&self.config)?);
+
+        let mut sys = Sys::real();
+        sys.clock = Arc::new(FakeClock::new());
+        sys.fs = Arc::new(fs);
+        let mut config = Config::from_base_dir(base_dir.to_owned());
+        config.set_sys(sys);
+        config.set_config_path(config_path)?;
+        Workspace::new(&config, default_locale).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "no start path in the reproducer")
+        })
+    }
+}
+
+impl Workspace {
+    /// Checks every start path, reading files through the workspace file system,
+    /// and records loaded files and reports.
+    ///
+    /// Unlike the usual checking, this does not stop at errors and catches a panic
+    /// from the checker, so that files loaded so far are still recorded.
+    pub fn repro_check(&self) -> ReproCheck {
+        let source = Rc::new(RefCell::new(Source::new()));
+        let loaded = Rc::new(RefCell::new(Vec::new()));
+        let report = Rc::new(CollectedReport::new(self.message_locale));
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            for start_path in &self.start_paths {
+                let mut context = Context::new(report.clone());
+                let fssource = RecordingFsSource {
+                    source: source.clone(),
+                    fs: self.sys.fs.clone(),
+                    loaded: loaded.clone(),
+                    language: self.language(),
+                };
+                let chunk = match fssource.chunk_from_path((**start_path).without_loc(), &report) {
+                    Ok(Some(chunk)) => chunk,
+                    _ => continue,
+                };
+
+                let opts = WorkspaceOptions::new(vec![fssource], start_path, self);
+                let opts = Rc::new(RefCell::new(opts));
+                let _ = check_from_chunk_with_preloading(&mut context, chunk, opts.clone(),
+                                                         &self.preload).and_then(|()| {
+                    for msg in verify_assertions(&mut context, &self.asserts, opts.clone())? {
+                        report.add_span(Kind::Error, Span::dummy(), &msg)?;
+                    }
+                    Ok(())
+                });
+            }
+        }));
+
+        let source = source.borrow();
+        let collected = Rc::try_unwrap(report).ok().map_or(Vec::new(), |r| r.into_reports());
+        let reports = collected.into_iter().map(|(kind, span, msg)| {
+            let kind = match kind {
+                Kind::Note => "Note",
+                Kind::Info => "Info",
+                Kind::Cause => "Cause",
+                Kind::Warning => "Warning",
+                Kind::Error => "Error",
+                Kind::Fatal => "Fatal",
+            };
+            let file = source.file(span.unit());
+            let loc = file.and_then(|file| {
+                let (line, linespan) = file.line_from_pos(span.begin())?;
+                let column = span.begin().to_usize() - linespan.begin().to_usize();
+                let path = self.sys.paths.relative(Path::new(file.path()), &self.base_dir);
+                Some(format!("{}:{}:{}", path, line + 1, column + 1))
+            });
+            match loc {
+                Some(loc) => format!("{}: [{}] {}", loc, kind, msg),
+                None => format!("[{}] {}", kind, msg),
+            }
+        }).collect();
+
+        let files = recording::loaded_paths(&loaded.borrow());
+        ReproCheck { files: files, reports: reports, panic: result.err().map(panic_message) }
+    }
+
+    /// Returns a bundle with the effective configuration and all files loaded by the checker.
+    pub fn repro_bundle(&self, options: &ReproOptions) -> io::Result<ReproBundle> {
+        let mut config = self.applied.clone();
+        if !config.is_object() {
+            config = Value::Object(Map::new());
+        }
+        // the configuration may have been given by command-line options
+        if config.get("start_path").is_none() {
+            let start_paths = self.start_paths.iter().map(|path| {
+                Value::String(self.repro_path(path))
+            }).collect();
+            config.as_object_mut().unwrap()
+                  .insert("start_path".to_owned(), Value::Array(start_paths));
+        }
+
+        let mut files = BTreeMap::new();
+        for path in self.repro_check().files {
+            let mut data = self.sys.fs.read(&path)?;
+            if options.strip_comments {
+                data = strip_comments(&data);
+            }
+            files.insert(self.repro_path(&path), data);
+        }
+
+        Ok(ReproBundle {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            config: config,
+            files: files,
+            comments_stripped: options.strip_comments,
+        })
+    }
+
+    /// Writes a reproducer bundle for the workspace to given path and returns it.
+    ///
+    /// The bundle contains the effective configuration, all files loaded by the checker
+    /// (comments can be blanked out with `ReproOptions`) and the version of Kailua.
+    /// The absolute path to the workspace is not recorded.
+    pub fn export_repro(&self, path: &Path, options: &ReproOptions) -> io::Result<ReproBundle> {
+        let bundle = self.repro_bundle(options)?;
+        bundle.save(path)?;
+        Ok(bundle)
+    }
+
+    fn repro_path(&self, path: &Path) -> String {
+        if self.sys.paths.starts_with(path, &self.base_dir) {
+            self.sys.paths.relative(path, &self.base_dir)
+        } else {
+            path.display().to_string()
+        }
+    }
+}
+
+#[test]
+fn test_strip_comments() {
+    let code = b"#!/usr/bin/env lua\n\
+                 local x = 1 -- secret\n\
+                 --[==[ private\n notes ]==] local y --: integer -- typed\n\
+                 --v function(a: string)\n\
+                 local s = '-- not a comment' --[[ unclosed";
+    let stripped = strip_comments(code);
+    assert_eq!(stripped.len(), code.len());
+    assert_eq!(String::from_utf8(stripped).unwrap(),
+               "#                 \n\
+                local x = 1 --       \n\
+                --[==[        \n       ]==] local y --: integer --      \n\
+                --v function(a: string)\n\
+                local s = '-- not a comment' --[[         ");
+}
+
+#[test]
+fn test_repro_bundle() {
+    let fs = FakeFileSystem::new();
+    fs.write("/ws/kailua.json", r#"{
+        "start_path": "main.lua",
+        "package_path": "src/?.lua",
+        "preload": { "open": "lua51" }
+    }"#);
+    fs.write("/ws/main.lua", "local util = require 'util' -- my secret\n\
+                              local x = util.answer + 'string'\n");
+    fs.write("/ws/src/util.lua", "return { answer = 42 }\n");
+    fs.write("/ws/src/unused.lua", "return 'unused'\n");
+
+    let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+    let mut sys = Sys::real();
+    sys.fs = Arc::new(fs);
+    config.set_sys(sys);
+    assert!(config.set_config_path(PathBuf::from("/ws/kailua.json")).unwrap());
+    let workspace = Workspace::new(&config, Locale::dummy()).unwrap();
+
+    let original = workspace.repro_check();
+    assert_eq!(original.files, [PathBuf::from("/ws/main.lua"), PathBuf::from("/ws/src/util.lua")]);
+    assert!(original.reports[0].starts_with("main.lua:2:11: [Error] "));
+    assert_eq!(original.panic, None);
+
+    let options = ReproOptions { strip_comments: true };
+    let bundle = workspace.repro_bundle(&options).unwrap();
+    assert_eq!(bundle.files.keys().collect::<Vec<_>>(), ["main.lua", "src/util.lua"]);
+    assert!(!String::from_utf8_lossy(&bundle.files["main.lua"]).contains("secret"));
+    assert_eq!(bundle.config["package_path"], "src/?.lua");
+    assert_eq!(ReproBundle::from_json(&bundle.to_json()), Ok(bundle.clone()));
+
+    let replayed = bundle.replay(Path::new("/elsewhere"), Locale::dummy()).unwrap();
+    let check = replayed.repro_check();
+    assert_eq!(check.files, [PathBuf::from("/elsewhere/main.lua"),
+                             PathBuf::from("/elsewhere/src/util.lua")]);
+    assert_eq!(check.reports, original.reports);
+}
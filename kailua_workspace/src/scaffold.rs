@@ -0,0 +1,201 @@
+//! Project scaffolding.
+//!
+//! A template is a set of files with relative paths, which gets copied to a new workspace.
+//! Templates can be either built in, or read from a user-provided directory.
+
+use std::io::{self, Read, Write};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf, Component};
+use walkdir::WalkDir;
+
+/// A project template.
+#[derive(Clone, Debug)]
+pub struct Template {
+    files: Vec<(PathBuf, Vec<u8>)>,
+    dirs: Vec<PathBuf>,
+}
+
+const DEFAULT_CONFIG: &'static str = r#"{
+    // The entry point of the checking.
+    "start_path": "src/main.lua",
+
+    // Lua modules are searched from `src`.
+    "package_path": "src/?.lua",
+
+    // Native modules are searched from `stubs`, which contains `.kailua` files
+    // that describe modules not written in Lua.
+    "package_cpath": "stubs/?",
+
+    "preload": {
+        // This indicates that we are using Lua 5.1 and all built-in libraries of it.
+        "open": ["lua51"],
+    },
+}
+"#;
+
+const DEFAULT_MAIN: &'static str = r#"-- This is where the checking starts.
+
+local greeting = require 'greeting'
+
+--v function(name: string) --> string
+local function greet(name)
+    return greeting.hello .. ', ' .. name .. '!'
+end
+
+print(greet('world'))
+"#;
+
+const DEFAULT_GREETING: &'static str = r#"-- A module required by `src/main.lua`.
+
+--# type Greeting = { hello: string }
+
+local M = { hello = 'Hello' } --: Greeting
+
+return M
+"#;
+
+const DEFAULT_STUBS_README: &'static str = r#"Put `.kailua` files for native modules here.
+
+For example, `stubs/foo.kailua` is used for `require 'foo'` when the module `foo` is
+only available as a native library; such files would frequently use `--# assume`.
+"#;
+
+const BUILTIN_TEMPLATES: &'static [&'static str] = &["default", "empty"];
+
+fn is_relative_and_safe(path: &Path) -> bool {
+    path.components().all(|c| if let Component::Normal(_) = c { true } else { false })
+}
+
+impl Template {
+    /// Returns a list of names accepted by `Template::builtin`.
+    pub fn builtin_names() -> &'static [&'static str] {
+        BUILTIN_TEMPLATES
+    }
+
+    /// Returns a built-in template with given name.
+    ///
+    /// * `default` has a configuration, a start file requiring another module
+    ///   and a directory for `.kailua` stubs.
+    /// * `empty` only has a configuration pointing to an empty start file.
+    pub fn builtin(name: &str) -> Option<Template> {
+        match name {
+            "default" => Some(Template {
+                files: vec![
+                    (PathBuf::from("kailua.json"), DEFAULT_CONFIG.into()),
+                    (Path::new("src").join("main.lua"), DEFAULT_MAIN.into()),
+                    (Path::new("src").join("greeting.lua"), DEFAULT_GREETING.into()),
+                    (Path::new("stubs").join("README.md"), DEFAULT_STUBS_README.into()),
+                ],
+                dirs: vec![PathBuf::from("src"), PathBuf::from("stubs")],
+            }),
+
+            "empty" => Some(Template {
+                files: vec![
+                    (PathBuf::from("kailua.json"),
+                     b"{\n    \"start_path\": \"main.lua\",\n}\n".to_vec()),
+                    (PathBuf::from("main.lua"), Vec::new()),
+                ],
+                dirs: vec![],
+            }),
+
+            _ => None,
+        }
+    }
+
+    /// Reads a user-provided template from given directory.
+    ///
+    /// All files and directories in that directory (including hidden ones) are copied as is.
+    pub fn from_dir(dir: &Path) -> io::Result<Template> {
+        let mut template = Template { files: Vec::new(), dirs: Vec::new() };
+        for e in WalkDir::new(dir).min_depth(1) {
+            let e = e.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            let relpath = e.path().strip_prefix(dir).expect("walkdir went outside").to_owned();
+            if e.file_type().is_dir() {
+                template.dirs.push(relpath);
+            } else {
+                let mut data = Vec::new();
+                File::open(e.path())?.read_to_end(&mut data)?;
+                template.files.push((relpath, data));
+            }
+        }
+        Ok(template)
+    }
+
+    /// Adds a file to the template, replacing any existing file with the same path.
+    ///
+    /// The path should be relative and should not contain `.` or `..`.
+    pub fn add_file(&mut self, path: PathBuf, data: Vec<u8>) -> bool {
+        if !is_relative_and_safe(&path) {
+            return false;
+        }
+        self.files.retain(|&(ref p, _)| *p != path);
+        self.files.push((path, data));
+        true
+    }
+
+    /// Returns a list of relative paths to the files in the template.
+    pub fn files(&self) -> Vec<&Path> {
+        self.files.iter().map(|&(ref p, _)| &**p).collect()
+    }
+}
+
+/// Creates a new project in `base_dir` (created if missing) from given template.
+///
+/// This never overwrites existing files; if any file in the template already exists,
+/// an error of the kind `AlreadyExists` is returned and nothing is created.
+/// Returns a list of created files.
+pub fn scaffold_project(base_dir: &Path, template: &Template) -> io::Result<Vec<PathBuf>> {
+    for &(ref path, _) in &template.files {
+        if !is_relative_and_safe(path) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      format!("invalid path `{}` in the template",
+                                              path.display())));
+        }
+        let path = base_dir.join(path);
+        if path.exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                                      format!("`{}` already exists", path.display())));
+        }
+    }
+
+    fs::create_dir_all(base_dir)?;
+    for dir in &template.dirs {
+        fs::create_dir_all(base_dir.join(dir))?;
+    }
+
+    let mut created = Vec::new();
+    for &(ref path, ref data) in &template.files {
+        let path = base_dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        File::create(&path)?.write_all(data)?;
+        created.push(path);
+    }
+    Ok(created)
+}
+
+#[test]
+fn test_scaffold_default() {
+    use kailua_diag::Locale;
+    use super::{Config, Workspace};
+    use testutils::TempDir;
+
+    let dir = TempDir::new("scaffold");
+    let base_dir = dir.join("project");
+
+    let template = Template::builtin("default").unwrap();
+    let created = scaffold_project(&base_dir, &template).unwrap();
+    assert_eq!(created.len(), template.files().len());
+    assert!(base_dir.join("src").join("main.lua").is_file());
+
+    // the configuration should be valid
+    let mut config = Config::from_base_dir(base_dir.clone());
+    config.use_default_config_paths();
+    let workspace = Workspace::new(&config, Locale::dummy()).unwrap();
+    assert_eq!(workspace.start_paths(), &[base_dir.join("src/main.lua")]);
+
+    // and should never overwrite
+    let err = scaffold_project(&base_dir, &template).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+}
@@ -0,0 +1,228 @@
+//! Management of multiple workspaces in a single process.
+//!
+//! An editor window may contain multiple root folders, and a monorepo may have several roots
+//! each with its own configuration. `WorkspaceSet` keeps such workspaces independent,
+//! routes paths to the owning workspace and shares read-only files (mostly stubs) among them.
+
+use std::io;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+use std::path::Path;
+use std::collections::HashMap;
+use kailua_env::{Spanned, Source, SourceFile};
+use kailua_diag::{Report, Stop};
+use kailua_syntax::{parse_chunk, Chunk};
+use kailua_check::options::FsSource;
+use super::Workspace;
+use sys::Sys;
+
+/// A set of independent workspaces.
+///
+/// This can be shared across threads; all methods take `&self`.
+pub struct WorkspaceSet {
+    sys: Sys,
+    workspaces: RwLock<Vec<Arc<Workspace>>>,
+    shared_files: Arc<SharedFiles>,
+}
+
+impl WorkspaceSet {
+    pub fn new() -> WorkspaceSet {
+        WorkspaceSet::with_sys(Sys::real())
+    }
+
+    /// Creates a set whose shared files are read from given file system,
+    /// and base directories are compared with given path policy.
+    pub fn with_sys(sys: Sys) -> WorkspaceSet {
+        WorkspaceSet {
+            sys: sys.clone(),
+            workspaces: RwLock::new(Vec::new()),
+            shared_files: Arc::new(SharedFiles::with_sys(sys)),
+        }
+    }
+
+    fn same_dir(&self, a: &Path, b: &Path) -> bool {
+        self.sys.paths.same(&*self.sys.fs, a, b)
+    }
+
+    /// Adds a workspace, replacing any existing workspace with the same base directory.
+    pub fn add(&self, workspace: Workspace) -> Arc<Workspace> {
+        let workspace = Arc::new(workspace);
+        let mut workspaces = self.workspaces.write().unwrap();
+        workspaces.retain(|ws| !self.same_dir(ws.base_dir(), workspace.base_dir()));
+        workspaces.push(workspace.clone());
+        workspace
+    }
+
+    /// Removes a workspace with given base directory.
+    pub fn remove(&self, base_dir: &Path) -> Option<Arc<Workspace>> {
+        let mut workspaces = self.workspaces.write().unwrap();
+        let index = workspaces.iter().position(|ws| self.same_dir(ws.base_dir(), base_dir))?;
+        Some(workspaces.remove(index))
+    }
+
+    /// Returns all workspaces in the order of addition.
+    pub fn workspaces(&self) -> Vec<Arc<Workspace>> {
+        self.workspaces.read().unwrap().clone()
+    }
+
+    /// Returns a workspace owning given path.
+    ///
+    /// When workspaces are nested, the innermost workspace owns the path.
+    /// The path is compared lexically according to the path policy of the set,
+    /// so it should be in the same form as base directories (e.g. both absolute).
+    pub fn owner(&self, path: &Path) -> Option<Arc<Workspace>> {
+        let workspaces = self.workspaces.read().unwrap();
+        workspaces.iter()
+                  .filter(|ws| self.sys.paths.starts_with(path, ws.base_dir()))
+                  .max_by_key(|ws| ws.base_dir().components().count())
+                  .cloned()
+    }
+
+    /// Returns files shared by all workspaces in this set.
+    pub fn shared_files(&self) -> Arc<SharedFiles> {
+        self.shared_files.clone()
+    }
+}
+
+/// A cache of files read by multiple workspaces.
+///
+/// Each workspace has its own `Source`, so this only caches the file contents;
+/// a cached file is invalidated when its modification time changes.
+pub struct SharedFiles {
+    sys: Sys,
+    // keyed by path identities as per the path policy
+    files: Mutex<HashMap<String, (Option<SystemTime>, Arc<Vec<u8>>)>>,
+}
+
+impl SharedFiles {
+    pub fn new() -> SharedFiles {
+        SharedFiles::with_sys(Sys::real())
+    }
+
+    pub fn with_sys(sys: Sys) -> SharedFiles {
+        SharedFiles { sys: sys, files: Mutex::new(HashMap::new()) }
+    }
+
+    /// Returns the number of cached files.
+    pub fn len(&self) -> usize {
+        self.files.lock().unwrap().len()
+    }
+
+    /// Reads a file, possibly from the cache.
+    pub fn read(&self, path: &Path) -> io::Result<SourceFile> {
+        let mtime = self.sys.fs.modified(path)?;
+        let key = self.sys.path_key(path);
+        let data = {
+            let mut files = self.files.lock().unwrap();
+            match files.get(&key) {
+                Some(&(cached_mtime, ref data)) if mtime.is_some() && cached_mtime == mtime => {
+                    Some(data.clone())
+                }
+                _ => {
+                    files.remove(&key);
+                    None
+                }
+            }
+        };
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            let data = Arc::new(self.sys.fs.read(path)?);
+            self.files.lock().unwrap().insert(key, (mtime, data.clone()));
+            data
+        };
+        Ok(SourceFile::from_u8(path.display().to_string(), (*data).clone()))
+    }
+
+    /// Forgets all cached files.
+    pub fn clear(&self) {
+        self.files.lock().unwrap().clear();
+    }
+}
+
+/// A `FsSource` reading files through `SharedFiles` into a per-workspace `Source`.
+pub struct SharedFsSource {
+    source: Rc<RefCell<Source>>,
+    files: Arc<SharedFiles>,
+}
+
+impl SharedFsSource {
+    pub fn new(source: Rc<RefCell<Source>>, files: Arc<SharedFiles>) -> SharedFsSource {
+        SharedFsSource { source: source, files: files }
+    }
+}
+
+impl FsSource for SharedFsSource {
+    fn chunk_from_path(&self, resolved_path: Spanned<&Path>,
+                       report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+        match self.files.read(&resolved_path) {
+            Ok(file) => {
+                let span = self.source.borrow_mut().add(file);
+                if let Ok(chunk) = parse_chunk(&self.source.borrow(), span, report) {
+                    Ok(Some(chunk))
+                } else {
+                    Err(Some(Stop)) // we have already reported parsing errors
+                }
+            }
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    Ok(None)
+                } else {
+                    Err(None)
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_owner() {
+    use std::path::PathBuf;
+    use kailua_diag::Locale;
+    use super::Config;
+
+    let set = WorkspaceSet::new();
+    for dir in &["/repo", "/repo/game", "/other"] {
+        let mut config = Config::from_base_dir(PathBuf::from(dir));
+        config.start_paths.push(Path::new(dir).join("main.lua"));
+        set.add(Workspace::new(&config, Locale::dummy()).unwrap());
+    }
+
+    let owner = |path: &str| set.owner(Path::new(path)).map(|ws| ws.base_dir().to_owned());
+    assert_eq!(owner("/repo/a.lua"), Some(PathBuf::from("/repo")));
+    assert_eq!(owner("/repo/game/a.lua"), Some(PathBuf::from("/repo/game")));
+    assert_eq!(owner("/repo/gamelib/a.lua"), Some(PathBuf::from("/repo")));
+    assert_eq!(owner("/repo/lib/../game/a.lua"), Some(PathBuf::from("/repo/game")));
+    assert_eq!(owner("/elsewhere/a.lua"), None);
+
+    assert!(set.remove(Path::new("/repo/game")).is_some());
+    assert_eq!(owner("/repo/game/a.lua"), Some(PathBuf::from("/repo")));
+    assert_eq!(set.workspaces().len(), 2);
+}
+
+#[test]
+fn test_shared_files() {
+    use sys::{FakeClock, FakeFileSystem};
+    use paths::PathPolicy;
+
+    let fs = Arc::new(FakeFileSystem::new());
+    let sys = Sys { clock: Arc::new(FakeClock::new()), fs: fs.clone(),
+                    paths: PathPolicy::native() };
+    let files = SharedFiles::with_sys(sys);
+    let path = Path::new("/stub.kailua");
+    assert!(files.read(path).is_err());
+
+    fs.write(path, "--# assume x: integer");
+    assert_eq!(files.read(path).unwrap().data().len(), 21);
+    assert_eq!(files.read(path).unwrap().data().len(), 21);
+    assert_eq!(files.len(), 1);
+    fs.write(path, "--# assume x: string");
+    assert_eq!(files.read(path).unwrap().data().len(), 20);
+    assert_eq!(files.len(), 1);
+
+    fs.remove(path);
+    assert!(files.read(path).is_err());
+}
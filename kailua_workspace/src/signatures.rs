@@ -0,0 +1,456 @@
+//! Structured function signatures for documentation tools.
+//!
+//! A function declaration is annotated either with `--v` or with `--:`/`-->` comments,
+//! and both are parsed into the same `Sig` node in the AST. `Signature` is a self-contained
+//! form of that node (with names and types as they appear in the source) combined with
+//! the documentation comment right before the declaration:
+//!
+//! ```lua
+//! -- Returns a new point.
+//! -- @param x The horizontal coordinate.
+//! -- @param y The vertical coordinate.
+//! -- @return The point.
+//! --v function(x: number, y: number) --> Point
+//! function Point.new(x, y) ... end
+//! ```
+//!
+//! Doc generators, signature help and stub exporters can consume this instead of
+//! re-parsing comments by themselves.
+
+use serde_json::Value;
+use kailua_env::{Span, Spanned, Source, SourceSlice, ScopedId};
+use kailua_syntax::Name;
+use kailua_syntax::ast::{Chunk, Block, St, Ex, Exp, Var, NameRef, Sig, Returns};
+
+/// A declared argument of a function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+
+    /// The type as written in the source, or `None` if not annotated.
+    pub kind: Option<String>,
+
+    /// A description from `@param <name> <description>` in the documentation comment.
+    pub description: Option<String>,
+}
+
+/// Declared variadic arguments of a function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Varargs {
+    /// The type of each variadic argument as written in the source, or `None` if not annotated.
+    pub kind: Option<String>,
+
+    /// A description from `@param ... <description>` in the documentation comment.
+    pub description: Option<String>,
+}
+
+/// Declared return types of a function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SigReturns {
+    /// Return types are not annotated and will be inferred by the checker.
+    Inferred,
+
+    /// `--> !`.
+    Never,
+
+    /// Return types as written in the source; `varargs` is the type of trailing `...`, if any.
+    Seq { kinds: Vec<String>, varargs: Option<String> },
+}
+
+/// A function signature with its documentation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    /// The declared name, e.g. `foo`, `Point.new` or `Point:sum`.
+    /// Anonymous functions are named after the variable or field they are assigned to.
+    pub name: String,
+
+    /// The span of the declaration statement.
+    pub span: Span,
+
+    /// True if the function is declared as a method (`function A:b()`).
+    /// The implicit `self` is not included in `params`.
+    pub method: bool,
+
+    /// Names of attributes, e.g. `NO_CHECK` for `[NO_CHECK]`.
+    pub attrs: Vec<String>,
+
+    pub type_params: Vec<String>,
+    pub params: Vec<Param>,
+    pub varargs: Option<Varargs>,
+    pub returns: SigReturns,
+
+    /// Any other lines in the documentation comment, joined with newlines.
+    pub description: Option<String>,
+
+    /// A description from `@return <description>` in the documentation comment.
+    pub returns_description: Option<String>,
+}
+
+impl Signature {
+    /// Exports the signature as a JSON object.
+    /// Missing types and descriptions are represented as `null`.
+    pub fn to_json(&self) -> Value {
+        let opt = |s: &Option<String>| {
+            s.as_ref().map_or(Value::Null, |s| Value::String(s.clone()))
+        };
+        let strs = |ss: &[String]| {
+            Value::Array(ss.iter().map(|s| Value::String(s.clone())).collect())
+        };
+
+        let mut obj = ::serde_json::Map::new();
+        obj.insert("name".to_owned(), Value::String(self.name.clone()));
+        obj.insert("method".to_owned(), Value::Bool(self.method));
+        obj.insert("attrs".to_owned(), strs(&self.attrs));
+        obj.insert("typeParams".to_owned(), strs(&self.type_params));
+        obj.insert("params".to_owned(), Value::Array(self.params.iter().map(|param| {
+            let mut obj = ::serde_json::Map::new();
+            obj.insert("name".to_owned(), Value::String(param.name.clone()));
+            obj.insert("type".to_owned(), opt(&param.kind));
+            obj.insert("description".to_owned(), opt(&param.description));
+            Value::Object(obj)
+        }).collect()));
+        obj.insert("varargs".to_owned(), self.varargs.as_ref().map_or(Value::Null, |varargs| {
+            let mut obj = ::serde_json::Map::new();
+            obj.insert("type".to_owned(), opt(&varargs.kind));
+            obj.insert("description".to_owned(), opt(&varargs.description));
+            Value::Object(obj)
+        }));
+        obj.insert("returns".to_owned(), match self.returns {
+            SigReturns::Inferred => Value::Null,
+            SigReturns::Never => Value::String("!".to_owned()),
+            SigReturns::Seq { ref kinds, ref varargs } => {
+                let mut kinds = kinds.clone();
+                kinds.extend(varargs.iter().map(|kind| format!("{}...", kind)));
+                strs(&kinds)
+            }
+        });
+        obj.insert("description".to_owned(), opt(&self.description));
+        obj.insert("returnsDescription".to_owned(), opt(&self.returns_description));
+        Value::Object(obj)
+    }
+}
+
+fn text(source: &Source, span: Span) -> String {
+    match source.slice_from_span(span) {
+        Some(SourceSlice::U8(s)) => String::from_utf8_lossy(s).into_owned(),
+        Some(SourceSlice::U16(s)) => String::from_utf16_lossy(s),
+        None => String::new(),
+    }
+}
+
+fn name_to_string(name: &Name) -> String {
+    String::from_utf8_lossy(name).into_owned()
+}
+
+// the documentation comment parsed into descriptions
+#[derive(Default)]
+struct DocComment {
+    description: Vec<String>,
+    params: Vec<(String, String)>,
+    returns: Option<String>,
+}
+
+// collects consecutive `--` comment lines right before the line containing `pos`,
+// skipping Kailua annotations (`--v` etc.) in between
+fn doc_comment(source: &Source, span: Span) -> DocComment {
+    let mut doc = DocComment::default();
+    let file = match source.file(span.unit()) {
+        Some(file) => file,
+        None => return doc,
+    };
+    let line = match file.line_from_pos(span.begin()) {
+        Some((line, _)) => line,
+        None => return doc,
+    };
+
+    let mut lines = Vec::new();
+    for linespan in file.line_spans().take(line).collect::<Vec<_>>().into_iter().rev() {
+        let line = text(source, linespan);
+        let line = line.trim();
+        if !line.starts_with("--") || line.starts_with("--[") {
+            break;
+        }
+        let meta = ["--v", "--#", "--:", "-->"].iter().any(|prefix| line.starts_with(prefix));
+        if meta {
+            if lines.is_empty() {
+                continue; // annotations can be between the comment and the declaration
+            } else {
+                break;
+            }
+        }
+        lines.push(line.trim_left_matches('-').trim().to_owned());
+    }
+    lines.reverse();
+
+    for line in lines {
+        if line.starts_with("@param ") {
+            let rest = line["@param ".len()..].trim();
+            let (name, desc) = match rest.find(char::is_whitespace) {
+                Some(i) => (&rest[..i], rest[i..].trim()),
+                None => (rest, ""),
+            };
+            doc.params.push((name.to_owned(), desc.to_owned()));
+        } else if line.starts_with("@return ") {
+            doc.returns = Some(line["@return ".len()..].trim().to_owned());
+        } else if !line.is_empty() || !doc.description.is_empty() {
+            doc.description.push(line);
+        }
+    }
+    while doc.description.last().map_or(false, |line| line.is_empty()) {
+        doc.description.pop();
+    }
+    doc
+}
+
+struct Extractor<'a> {
+    chunk: &'a Chunk,
+    source: &'a Source,
+    signatures: Vec<Signature>,
+}
+
+impl<'a> Extractor<'a> {
+    fn scoped_name(&self, id: &ScopedId) -> String {
+        name_to_string(id.name(&self.chunk.map))
+    }
+
+    fn nameref(&self, nameref: &NameRef) -> String {
+        match *nameref {
+            NameRef::Local(ref id) => self.scoped_name(id),
+            NameRef::Global(ref name) => name_to_string(name),
+        }
+    }
+
+    fn add(&mut self, name: String, span: Span, method: bool, sig: &Sig) {
+        let doc = doc_comment(self.source, span);
+        let source = self.source;
+        let find_param = |name: &str| {
+            doc.params.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref desc)| desc.clone())
+        };
+
+        let params = sig.args.head.iter().map(|spec| {
+            let name = self.scoped_name(&spec.base.base);
+            Param {
+                description: find_param(&name),
+                kind: spec.kind.as_ref().map(|kind| text(source, kind.span)),
+                name: name,
+            }
+        }).collect();
+        let varargs = sig.args.tail.as_ref().map(|varargs| {
+            Varargs {
+                kind: varargs.kind.as_ref().map(|kind| text(source, kind.span)),
+                description: find_param("..."),
+            }
+        });
+        let returns = match sig.returns {
+            None => SigReturns::Inferred,
+            Some(Returns::Never(_)) => SigReturns::Never,
+            Some(Returns::Seq(ref seq)) => SigReturns::Seq {
+                kinds: seq.head.iter().map(|kind| text(source, kind.span)).collect(),
+                varargs: seq.tail.as_ref().map(|kind| text(source, kind.span)),
+            },
+        };
+
+        let description = if doc.description.is_empty() {
+            None
+        } else {
+            Some(doc.description.join("\n"))
+        };
+        self.signatures.push(Signature {
+            name: name,
+            span: span,
+            method: method,
+            attrs: sig.attrs.iter().map(|attr| name_to_string(&attr.base.name.base)).collect(),
+            type_params: sig.type_params.iter().map(|p| name_to_string(&p.base)).collect(),
+            params: params,
+            varargs: varargs,
+            returns: returns,
+            description: description,
+            returns_description: doc.returns.clone(),
+        });
+    }
+
+    // a name for an anonymous function assigned to given variable, if any
+    fn var_name(&self, var: &Var) -> Option<String> {
+        match *var {
+            Var::Name(ref nameref) => Some(self.nameref(&nameref.base)),
+            Var::IndexName(ref exp, ref name) => self.index_name(exp, name),
+            Var::Index(..) => None,
+        }
+    }
+
+    fn exp_name(&self, exp: &Exp) -> Option<String> {
+        match **exp {
+            Ex::Var(ref nameref) => Some(self.nameref(&nameref.base)),
+            Ex::IndexName(ref exp, ref name) => self.index_name(exp, name),
+            Ex::Exp(ref exp) => self.exp_name(exp),
+            _ => None,
+        }
+    }
+
+    fn index_name(&self, exp: &Exp, name: &Spanned<Name>) -> Option<String> {
+        let prefix = self.exp_name(exp)?;
+        Some(format!("{}.{}", prefix, name_to_string(&name.base)))
+    }
+
+    // visits an expression assigned to a named variable
+    fn assigned(&mut self, name: Option<String>, span: Span, exp: &Spanned<Exp>) {
+        match *exp.base {
+            Ex::Func(ref sig, _, ref block) => {
+                if let Some(name) = name {
+                    self.add(name, span, false, sig);
+                }
+                self.block(block);
+            }
+            _ => {}
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in block {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Spanned<Box<St>>) {
+        match *stmt.base {
+            St::Do(ref block) |
+            St::While(_, ref block) |
+            St::Repeat(ref block, _) |
+            St::For(_, _, _, _, _, ref block) |
+            St::ForIn(_, _, _, ref block) => self.block(block),
+
+            St::If(ref cases, ref else_) => {
+                for case in cases {
+                    self.block(&case.base.1);
+                }
+                if let Some(ref block) = *else_ {
+                    self.block(block);
+                }
+            }
+
+            St::FuncDecl(ref name, ref sig, _, ref block, _) => {
+                let name = self.nameref(&name.base);
+                self.add(name, stmt.span, false, sig);
+                self.block(block);
+            }
+
+            St::MethodDecl(ref names, ref selfparam, ref sig, _, ref block) => {
+                let (ref root, ref fields) = names.base;
+                let mut name = self.nameref(&root.base);
+                for (i, field) in fields.iter().enumerate() {
+                    let sep = if i + 1 == fields.len() && selfparam.is_some() { ':' } else { '.' };
+                    name.push(sep);
+                    name.push_str(&name_to_string(&field.base));
+                }
+                self.add(name, stmt.span, selfparam.is_some(), sig);
+                self.block(block);
+            }
+
+            St::Local(ref names, ref exps, _) => {
+                for (i, exp) in exps.base.iter().enumerate() {
+                    let name = names.base.get(i).map(|spec| self.scoped_name(&spec.base.base));
+                    self.assigned(name, stmt.span, exp);
+                }
+            }
+
+            St::Assign(ref vars, Some(ref exps)) => {
+                for (i, exp) in exps.base.iter().enumerate() {
+                    let name = vars.base.get(i).and_then(|spec| self.var_name(&spec.base.base));
+                    self.assigned(name, stmt.span, exp);
+                }
+            }
+
+            _ => {}
+        }
+    }
+}
+
+/// Returns signatures of all named functions declared in the chunk, in the order of appearance.
+///
+/// Functions declared by `function` statements, and function expressions directly assigned
+/// to local or global variables or named fields are included.
+/// Other anonymous functions (e.g. callbacks passed to other functions) are not.
+pub fn extract_signatures(chunk: &Chunk, source: &Source) -> Vec<Signature> {
+    let mut extractor = Extractor { chunk: chunk, source: source, signatures: Vec::new() };
+    extractor.block(&chunk.block.base);
+    extractor.signatures
+}
+
+#[test]
+fn test_extract_signatures() {
+    use kailua_env::SourceFile;
+    use kailua_diag::NoReport;
+    use kailua_syntax::parse_chunk;
+
+    let mut source = Source::new();
+    let code = "\
+-- Returns a new point.
+--
+-- Both coordinates are required.
+-- @param x The horizontal coordinate.
+-- @param y The vertical coordinate.
+-- @return The point.
+--v function(x: number, y: number) --> {x: number, y: number}
+function new_point(x, y) return {x = x, y = y} end
+
+local Point = {}
+
+-- Not a documentation comment, as a code follows.
+local z = 1
+
+--v method(other: {x: number, y: number}, ...: string) --> (number, integer...)
+function Point:dist(other, ...)
+    local function inner(a) --: integer
+    end
+    return 0
+end
+
+-- Logs messages.
+Point.log = function(fmt, --: string
+                     ...) --> !
+    error(fmt)
+end
+";
+    let span = source.add(SourceFile::from_u8("a.lua".to_owned(), code.as_bytes().to_owned()));
+    let chunk = parse_chunk(&source, span, &NoReport).unwrap();
+    let sigs = extract_signatures(&chunk, &source);
+    assert_eq!(sigs.iter().map(|sig| &sig.name[..]).collect::<Vec<_>>(),
+               ["new_point", "Point:dist", "inner", "Point.log"]);
+
+    let sig = &sigs[0];
+    assert_eq!(sig.description.as_ref().map(|s| &s[..]),
+               Some("Returns a new point.\n\nBoth coordinates are required."));
+    assert_eq!(sig.params, vec![
+        Param { name: "x".to_owned(), kind: Some("number".to_owned()),
+                description: Some("The horizontal coordinate.".to_owned()) },
+        Param { name: "y".to_owned(), kind: Some("number".to_owned()),
+                description: Some("The vertical coordinate.".to_owned()) },
+    ]);
+    assert_eq!(sig.varargs, None);
+    assert_eq!(sig.returns, SigReturns::Seq { kinds: vec!["{x: number, y: number}".to_owned()],
+                                              varargs: None });
+    assert_eq!(sig.returns_description.as_ref().map(|s| &s[..]), Some("The point."));
+
+    let sig = &sigs[1];
+    assert!(sig.method);
+    assert_eq!(sig.description, None);
+    assert_eq!(sig.params.len(), 1);
+    assert_eq!(sig.varargs, Some(Varargs { kind: Some("string".to_owned()), description: None }));
+    assert_eq!(sig.returns, SigReturns::Seq { kinds: vec!["number".to_owned()],
+                                              varargs: Some("integer".to_owned()) });
+
+    let sig = &sigs[2];
+    assert_eq!(sig.params, vec![Param { name: "a".to_owned(), kind: Some("integer".to_owned()),
+                                        description: None }]);
+    assert_eq!(sig.returns, SigReturns::Inferred);
+
+    let sig = &sigs[3];
+    assert_eq!(sig.description.as_ref().map(|s| &s[..]), Some("Logs messages."));
+    assert_eq!(sig.varargs, Some(Varargs { kind: None, description: None }));
+    assert_eq!(sig.returns, SigReturns::Never);
+
+    let json = sigs[0].to_json();
+    assert_eq!(json["params"][1]["name"], "y");
+    assert_eq!(json["returns"][0], "{x: number, y: number}");
+    assert_eq!(sigs[1].to_json()["returns"][1], "integer...");
+}
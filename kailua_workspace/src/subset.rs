@@ -0,0 +1,216 @@
+//! Checking a subset of files in the workspace.
+//!
+//! Checking every start path can be slow for large workspaces, while editor commands like
+//! "check the current file" and pre-commit hooks are only interested in a handful of files.
+//! `Workspace::check_files` checks given files and modules they require transitively
+//! (but nothing else), and returns reports only for given files.
+
+use std::fmt;
+use std::io;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::path::PathBuf;
+use kailua_env::{Source, Unit, WithLoc};
+use kailua_diag::{CollectedReport, Kind};
+use kailua_check::check_from_chunk_with_preloading;
+use kailua_check::env::Context;
+use kailua_check::options::FsSource;
+use recording::{self, RecordingFsSource};
+use {Workspace, WorkspaceOptions};
+
+/// A report from `Workspace::check_files`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubsetReport {
+    pub kind: Kind,
+
+    /// The file where the report has been given, or `None` if the report has no position.
+    pub path: Option<PathBuf>,
+
+    /// The line (starting from 1) of the report, or 0 if `path` is `None`.
+    pub line: usize,
+
+    /// The column (starting from 1) of the report, or 0 if `path` is `None`.
+    pub column: usize,
+
+    pub message: String,
+}
+
+impl fmt::Display for SubsetReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref path) = self.path {
+            write!(f, "{}:{}:{}: ", path.display(), self.line, self.column)?;
+        }
+        write!(f, "[{:?}] {}", self.kind, self.message)
+    }
+}
+
+/// A result of `Workspace::check_files`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubsetCheck {
+    /// Files loaded by the checker, i.e. given files and their transitive requires,
+    /// in the order of loading.
+    pub files: Vec<PathBuf>,
+
+    /// Reports in given files, in the order of reporting.
+    ///
+    /// Notes and causes following a report are kept even when they point to other files.
+    pub reports: Vec<SubsetReport>,
+
+    /// False if the checking has stopped before checking all given files,
+    /// possibly due to errors in required modules which are not included in `reports`.
+    pub complete: bool,
+}
+
+impl Workspace {
+    /// Checks given files (relative to the base directory) and modules they require.
+    ///
+    /// Each file is checked as if it were a start path, with preloaded libraries and modules.
+    /// Search paths for a file are those for the file itself if it is a start path,
+    /// and those for the first start path otherwise. A file already loaded while checking
+    /// preceding files is not checked again. Workspace assertions are not verified.
+    ///
+    /// Returns an error if any of given files does not exist.
+    pub fn check_files(&self, paths: &[PathBuf]) -> io::Result<SubsetCheck> {
+        let fs = &*self.sys.fs;
+        let paths: Vec<PathBuf> = paths.iter().map(|path| self.base_dir.join(path)).collect();
+        for path in &paths {
+            if !fs.is_file(path) {
+                return Err(io::Error::new(io::ErrorKind::NotFound,
+                                          format!("`{}` does not exist", path.display())));
+            }
+        }
+
+        let source = Rc::new(RefCell::new(Source::new()));
+        let loaded = Rc::new(RefCell::new(Vec::<(PathBuf, Unit)>::new()));
+        let report = Rc::new(CollectedReport::new(self.message_locale));
+
+        let mut complete = true;
+        for path in &paths {
+            if loaded.borrow().iter().any(|&(ref p, _)| self.sys.paths.same(fs, p, path)) {
+                continue;
+            }
+
+            let fssource = RecordingFsSource {
+                source: source.clone(),
+                fs: self.sys.fs.clone(),
+                loaded: loaded.clone(),
+                language: self.language(),
+            };
+            let chunk = match fssource.chunk_from_path((&**path).without_loc(), &report) {
+                Ok(Some(chunk)) => chunk,
+                _ => {
+                    complete = false;
+                    continue;
+                }
+            };
+
+            let start_path = self.start_paths.iter().find(|p| self.sys.paths.same(fs, p, path))
+                                                   .or(self.start_paths.first())
+                                                   .unwrap_or(path);
+            let opts = WorkspaceOptions::new(vec![fssource], start_path, self);
+            let opts = Rc::new(RefCell::new(opts));
+            let mut context = Context::new(report.clone());
+            if check_from_chunk_with_preloading(&mut context, chunk, opts,
+                                                &self.preload).is_err() {
+                complete = false;
+            }
+        }
+
+        let loaded = loaded.borrow();
+        let is_selected = |unit: Unit| {
+            loaded.iter().any(|&(ref p, u)| {
+                u == unit && paths.iter().any(|path| self.sys.paths.same(fs, p, path))
+            })
+        };
+
+        let source = source.borrow();
+        let collected = Rc::try_unwrap(report).ok().map_or(Vec::new(), |r| r.into_reports());
+        let mut reports = Vec::new();
+        let mut keep = false;
+        for (kind, span, message) in collected {
+            match kind {
+                Kind::Note | Kind::Cause => {}
+                _ => keep = span.unit() == Unit::dummy() || is_selected(span.unit()),
+            }
+            if !keep {
+                continue;
+            }
+
+            let file = source.file(span.unit());
+            let pos = file.and_then(|file| {
+                let (line, linespan) = file.line_from_pos(span.begin())?;
+                let column = span.begin().to_usize() - linespan.begin().to_usize();
+                Some((PathBuf::from(file.path()), line + 1, column + 1))
+            });
+            let (path, line, column) = match pos {
+                Some((path, line, column)) => (Some(path), line, column),
+                None => (None, 0, 0),
+            };
+            reports.push(SubsetReport {
+                kind: kind,
+                path: path,
+                line: line,
+                column: column,
+                message: message,
+            });
+        }
+
+        let files = recording::loaded_paths(&loaded);
+        Ok(SubsetCheck { files: files, reports: reports, complete: complete })
+    }
+}
+
+#[test]
+fn test_check_files() {
+    use std::sync::Arc;
+    use sys::{Sys, FakeClock, FakeFileSystem};
+    use Config;
+    use kailua_diag::Locale;
+
+    let fs = FakeFileSystem::new();
+    fs.write("/ws/kailua.json", r#"{
+        "start_path": "main.lua",
+        "package_path": "src/?.lua",
+        "preload": { "open": "lua51" }
+    }"#);
+    fs.write("/ws/main.lua", "local a = require 'a'\n\
+                              local c = require 'c'\n\
+                              print(a + c)\n");
+    fs.write("/ws/src/a.lua", "local b = require 'b'\n\
+                               local x = b.answer + {}\n\
+                               return b.answer\n");
+    fs.write("/ws/src/b.lua", "local y = 'string' * {}\n\
+                               return { answer = 42 }\n");
+    fs.write("/ws/src/c.lua", "return 'never loaded' + {}\n");
+
+    let mut config = Config::from_base_dir(PathBuf::from("/ws"));
+    let mut sys = Sys::real();
+    sys.clock = Arc::new(FakeClock::new());
+    sys.fs = Arc::new(fs);
+    config.set_sys(sys);
+    config.set_override("severity.unused-variable", "allow").unwrap();
+    config.set_config_path(PathBuf::from("/ws/kailua.json")).unwrap();
+    let workspace = Workspace::new(&config, Locale::dummy()).unwrap();
+
+    // only `a` and `b` are checked, and errors from `b` are not reported
+    let check = workspace.check_files(&[PathBuf::from("src/a.lua")]).unwrap();
+    assert_eq!(check.files, [PathBuf::from("/ws/src/a.lua"), PathBuf::from("/ws/src/b.lua")]);
+    assert!(check.complete);
+    let reports: Vec<_> = check.reports.iter().filter(|r| r.kind == Kind::Error).collect();
+    assert_eq!(reports.len(), 1, "{:?}", check.reports);
+    assert_eq!(reports[0].kind, Kind::Error);
+    assert_eq!(reports[0].path, Some(PathBuf::from("/ws/src/a.lua")));
+    assert_eq!(reports[0].line, 2);
+    assert!(reports[0].to_string().starts_with("/ws/src/a.lua:2:11: [Error] "),
+            "{}", reports[0]);
+
+    // a file already loaded as a module is not checked again
+    let check = workspace.check_files(&[PathBuf::from("src/a.lua"),
+                                        PathBuf::from("/ws/src/b.lua")]).unwrap();
+    assert_eq!(check.files.len(), 2);
+    let errors = check.reports.iter().filter(|r| r.kind == Kind::Error).count();
+    assert_eq!(errors, 2, "{:?}", check.reports);
+
+    assert_eq!(workspace.check_files(&[PathBuf::from("src/d.lua")]).unwrap_err().kind(),
+               io::ErrorKind::NotFound);
+}
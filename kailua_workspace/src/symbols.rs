@@ -0,0 +1,132 @@
+//! Symbol maps for scripts that will be minified after checking.
+//!
+//! Minifiers and obfuscators rewrite line structures and local names, but global names
+//! have to be kept as is. A `SymbolMap` records where each global name has been defined
+//! in the checked sources, so that runtime error reports from shipped scripts
+//! (which mostly mention function names) can be mapped back to the original positions.
+
+use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use serde_json::Value;
+use kailua_env::Source;
+use kailua_check::env::{Id, Output};
+use paths::PathPolicy;
+
+/// A public symbol and its definition position.
+///
+/// Both lines and columns start from 1. Columns count bytes from the beginning of the line.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Symbol {
+    pub line: usize,
+    pub column: usize,
+    pub name: String,
+}
+
+/// A mapping from files to public symbols defined in them.
+///
+/// Paths are relative to the base directory when possible, and always use `/` as a separator.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolMap {
+    files: BTreeMap<String, BTreeSet<Symbol>>,
+}
+
+impl SymbolMap {
+    pub fn new() -> SymbolMap {
+        SymbolMap { files: BTreeMap::new() }
+    }
+
+    /// Adds a symbol defined in given file.
+    pub fn add_symbol(&mut self, file: String, symbol: Symbol) {
+        self.files.entry(file).or_insert_with(BTreeSet::new).insert(symbol);
+    }
+
+    /// Adds all global names defined in the checker output.
+    ///
+    /// Names without a source position (e.g. from the built-in library) are ignored.
+    pub fn add_output(&mut self, output: &Output, source: &Source,
+                      base_dir: &Path, paths: &PathPolicy) {
+        for (id, def) in output.all() {
+            let name = match *id {
+                Id::Global(ref name) => name,
+                Id::Local(..) => continue,
+            };
+            if def.span.is_dummy() {
+                continue;
+            }
+            let file = match source.file(def.span.unit()) {
+                Some(file) => file,
+                None => continue,
+            };
+            if let Some((line, linespan)) = file.line_from_pos(def.span.begin()) {
+                let column = def.span.begin().to_usize() - linespan.begin().to_usize();
+                let symbol = Symbol {
+                    line: line + 1,
+                    column: column + 1,
+                    name: String::from_utf8_lossy(name).into_owned(),
+                };
+                self.add_symbol(paths.relative(Path::new(file.path()), base_dir), symbol);
+            }
+        }
+    }
+
+    /// Merges another map (e.g. from a different start path) into this map.
+    pub fn merge(&mut self, other: SymbolMap) {
+        for (file, symbols) in other.files {
+            self.files.entry(file).or_insert_with(BTreeSet::new).extend(symbols);
+        }
+    }
+
+    /// Returns all files in the sorted order.
+    pub fn files(&self) -> Vec<&str> {
+        self.files.keys().map(|s| &s[..]).collect()
+    }
+
+    /// Returns symbols defined in given file in the order of positions.
+    pub fn symbols(&self, file: &str) -> Vec<&Symbol> {
+        self.files.get(file).map_or(Vec::new(), |symbols| symbols.iter().collect())
+    }
+
+    /// Exports the map as a JSON object from files to arrays of
+    /// `{"name": ..., "line": ..., "column": ...}` objects.
+    pub fn to_json(&self) -> Value {
+        let map = self.files.iter().map(|(file, symbols)| {
+            let symbols = symbols.iter().map(|sym| {
+                let mut obj = ::serde_json::Map::new();
+                obj.insert("name".to_owned(), Value::String(sym.name.clone()));
+                obj.insert("line".to_owned(), Value::from(sym.line));
+                obj.insert("column".to_owned(), Value::from(sym.column));
+                Value::Object(obj)
+            }).collect();
+            (file.clone(), Value::Array(symbols))
+        }).collect();
+        Value::Object(map)
+    }
+}
+
+#[test]
+fn test_symbol_map() {
+    use testutils::check_test_code;
+
+    let code = "local x = 1\nfunction foo() end\n  bar = 'bar'\nfoo = nil\n";
+    let (context, source) = check_test_code("/base/lib/a.lua", code);
+
+    let mut map = SymbolMap::new();
+    map.add_output(&context, &source, Path::new("/base"), &PathPolicy::native());
+    let mut other = SymbolMap::new();
+    let symbol = |line, column, name: &str| {
+        Symbol { line: line, column: column, name: name.to_owned() }
+    };
+    other.add_symbol("lib/a.lua".to_owned(), symbol(2, 10, "foo"));
+    other.add_symbol("b.lua".to_owned(), symbol(1, 1, "baz"));
+    map.merge(other);
+
+    assert_eq!(map.files(), vec!["b.lua", "lib/a.lua"]);
+    assert_eq!(map.symbols("lib/a.lua").len(), 2);
+    assert_eq!(map.to_json(), json!({
+        "b.lua": [{"name": "baz", "line": 1, "column": 1}],
+        "lib/a.lua": [
+            {"name": "foo", "line": 2, "column": 10},
+            {"name": "bar", "line": 3, "column": 3},
+        ],
+    }));
+}
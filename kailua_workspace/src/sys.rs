@@ -0,0 +1,249 @@
+//! Abstractions over the system clock and the file system.
+//!
+//! Parts of this crate depending on the time (e.g. parsing durations) or on the file system
+//! (e.g. configurations, cached files and module resolution) go through `Sys`,
+//! which defaults to the real system but can be replaced with deterministic fakes.
+//! Downstream integrations can use the same fakes to test their watching and caching logic.
+
+use std::io::{self, Read, Write};
+use std::fmt;
+use std::fs::{self, File};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use paths::PathPolicy;
+
+/// A monotonic clock.
+pub trait Clock: Send + Sync {
+    /// Returns the time elapsed since an arbitrary but fixed origin.
+    fn now(&self) -> Duration;
+}
+
+/// The file system, only including operations this crate needs.
+pub trait FileSystem: Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Returns the modification time of given file, or `None` if not supported.
+    fn modified(&self, path: &Path) -> io::Result<Option<SystemTime>>;
+
+    fn is_file(&self, path: &Path) -> bool;
+
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Should return an absolute path with all symbolic links resolved.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// Creates or overwrites a file, creating parent directories as needed.
+    fn write_file(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+}
+
+/// The real monotonic clock, of which origin is the creation of the clock.
+#[derive(Clone, Debug)]
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock { origin: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// The real file system.
+#[derive(Copy, Clone, Debug)]
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<Option<SystemTime>> {
+        Ok(fs::metadata(path)?.modified().ok())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        File::create(path)?.write_all(data)
+    }
+}
+
+/// A clock which only advances when told so.
+#[derive(Debug)]
+pub struct FakeClock {
+    now: Mutex<Duration>,
+}
+
+impl FakeClock {
+    pub fn new() -> FakeClock {
+        FakeClock { now: Mutex::new(Duration::new(0, 0)) }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// An in-memory file system.
+///
+/// Every write bumps the modification time of the file by one second after the previous write
+/// to any file, so that modification times are deterministic and always distinct.
+/// Paths are never normalized; they are canonical only when they are absolute.
+/// Directories are implicit: a path is a directory when any file is inside it.
+#[derive(Debug)]
+pub struct FakeFileSystem {
+    // (the number of writes so far, files with their modification counts)
+    state: Mutex<(u64, HashMap<PathBuf, (u64, Vec<u8>)>)>,
+}
+
+impl FakeFileSystem {
+    pub fn new() -> FakeFileSystem {
+        FakeFileSystem { state: Mutex::new((0, HashMap::new())) }
+    }
+
+    /// Creates or overwrites a file.
+    pub fn write<P: Into<PathBuf>, D: Into<Vec<u8>>>(&self, path: P, data: D) {
+        let mut state = self.state.lock().unwrap();
+        state.0 += 1;
+        let mtime = state.0;
+        state.1.insert(path.into(), (mtime, data.into()));
+    }
+
+    /// Removes a file. Returns false if the file didn't exist.
+    pub fn remove(&self, path: &Path) -> bool {
+        self.state.lock().unwrap().1.remove(path).is_some()
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path.display()))
+}
+
+impl FileSystem for FakeFileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state.1.get(path).map(|&(_, ref data)| data.clone()).ok_or_else(|| not_found(path))
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<Option<SystemTime>> {
+        let state = self.state.lock().unwrap();
+        let &(mtime, _) = state.1.get(path).ok_or_else(|| not_found(path))?;
+        Ok(Some(UNIX_EPOCH + Duration::from_secs(mtime)))
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.state.lock().unwrap().1.contains_key(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.state.lock().unwrap().1.keys().any(|p| p != path && p.starts_with(path))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        if path.is_absolute() && self.is_file(path) {
+            Ok(path.to_owned())
+        } else {
+            Err(not_found(path))
+        }
+    }
+
+    fn write_file(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.write(path, data);
+        Ok(())
+    }
+}
+
+/// The clock, the file system and the path policy used by the workspace.
+#[derive(Clone)]
+pub struct Sys {
+    pub clock: Arc<Clock>,
+    pub fs: Arc<FileSystem>,
+    pub paths: PathPolicy,
+}
+
+impl Sys {
+    /// Returns the real clock and file system with the native path policy.
+    pub fn real() -> Sys {
+        Sys {
+            clock: Arc::new(SystemClock::new()),
+            fs: Arc::new(RealFileSystem),
+            paths: PathPolicy::native(),
+        }
+    }
+
+    /// Returns a path identity key according to the path policy and the file system.
+    pub fn path_key(&self, path: &Path) -> String {
+        self.paths.key(&*self.fs, path)
+    }
+}
+
+impl Default for Sys {
+    fn default() -> Sys {
+        Sys::real()
+    }
+}
+
+impl fmt::Debug for Sys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sys").field("paths", &self.paths).finish()
+    }
+}
+
+#[test]
+fn test_fakes() {
+    let clock = FakeClock::new();
+    assert_eq!(clock.now(), Duration::new(0, 0));
+    clock.advance(Duration::from_millis(1500));
+    assert_eq!(clock.now(), Duration::new(1, 500_000_000));
+
+    let fs = FakeFileSystem::new();
+    let path = Path::new("/a.lua");
+    assert!(!fs.is_file(path));
+    assert_eq!(fs.read(path).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+    fs.write(path, "x = 1");
+    fs.write("/b.lua", "y = 2");
+    assert!(fs.is_file(path));
+    assert!(!fs.is_dir(path));
+    assert!(fs.is_dir(Path::new("/")));
+    assert_eq!(fs.read(path).unwrap(), b"x = 1".to_vec());
+    let mtime = fs.modified(path).unwrap();
+    assert!(mtime < fs.modified(Path::new("/b.lua")).unwrap());
+    fs.write(path, "x = 2");
+    assert!(mtime < fs.modified(path).unwrap());
+    assert_eq!(fs.canonicalize(path).unwrap(), path);
+    assert!(fs.canonicalize(Path::new("a.lua")).is_err());
+
+    assert!(fs.remove(path));
+    assert!(!fs.remove(path));
+    assert!(!fs.is_file(path));
+}
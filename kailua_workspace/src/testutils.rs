@@ -0,0 +1,100 @@
+//! Helpers for unit tests, mainly for checking small code snippets.
+
+use std::env;
+use std::fs;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use kailua_env::{Span, Spanned, Source, SourceFile, WithLoc};
+use kailua_diag::{Report, NoReport, Stop};
+use kailua_syntax::{Chunk, parse_chunk};
+use kailua_check::{Preload, check_from_chunk, check_from_chunk_with_preloading};
+use kailua_check::env::Context;
+use kailua_check::options::Options;
+use kailua_check::lint::Severity;
+
+struct NoOptions;
+
+impl Options for NoOptions {
+    // unused variables in the test code would stop the checking
+    fn lint_severity(&self, _code: &str) -> Severity { Severity::Allow }
+}
+
+/// Checks given code as a start path at `path`, and returns the checker context and the source.
+///
+/// Panics if the code fails to parse or check.
+pub fn check_test_code(path: &str, code: &str) -> (Context<NoReport>, Source) {
+    let mut source = Source::new();
+    let span = source.add(SourceFile::from_u8(path.to_owned(), code.as_bytes().to_owned()));
+    let chunk = parse_chunk(&source, span, &NoReport).unwrap();
+    let mut context = Context::new(NoReport);
+    let opts = Rc::new(RefCell::new(NoOptions));
+    check_from_chunk(&mut context, chunk, opts).unwrap();
+    (context, source)
+}
+
+// resolves any `require` to the same chunk
+struct ModOptions {
+    source: Rc<RefCell<Source>>,
+    span: Span,
+}
+
+impl Options for ModOptions {
+    fn require_chunk(&mut self, _path: Spanned<&[u8]>,
+                     _report: &Report) -> Result<Chunk, Option<Stop>> {
+        parse_chunk(&self.source.borrow(), self.span, &NoReport).map_err(|_| None)
+    }
+
+    // unused variables in the test code would stop the checking
+    fn lint_severity(&self, _code: &str) -> Severity { Severity::Allow }
+}
+
+/// Checks given code as a module `name` at `path`, preloaded from an empty start path
+/// along with the `lua51` library. Returns the checker context, the source
+/// and the span of the module code.
+///
+/// Panics if the code fails to parse or check.
+pub fn check_test_module(path: &str, name: &str, code: &str) -> (Context<NoReport>, Source, Span) {
+    let source = Rc::new(RefCell::new(Source::new()));
+    let span = source.borrow_mut().add(SourceFile::from_u8(path.to_owned(),
+                                                           code.as_bytes().to_owned()));
+    let start = source.borrow_mut().add(SourceFile::from_u8("/main.lua".to_owned(),
+                                                            b"".to_vec()));
+    let start = parse_chunk(&source.borrow(), start, &NoReport).unwrap();
+
+    let mut context = Context::new(NoReport);
+    let opts = Rc::new(RefCell::new(ModOptions { source: source.clone(), span: span }));
+    let mut preload = Preload::default();
+    preload.open.push(b"lua51".to_vec().without_loc());
+    preload.require.push(name.as_bytes().to_vec().without_loc());
+    check_from_chunk_with_preloading(&mut context, start, opts, &preload).unwrap();
+
+    let source = Rc::try_unwrap(source).ok().expect("the source is still shared");
+    (context, source.into_inner(), span)
+}
+
+/// An empty directory in the system temporary directory, removed when dropped.
+///
+/// Only for tests which need the real file system (e.g. atomic renames);
+/// others should use `sys::FakeFileSystem` instead.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates a directory from given name, which should be unique among tests.
+    pub fn new(name: &str) -> TempDir {
+        let dir = env::temp_dir().join(format!("kailua-{}-test-{}", name, ::std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -2,6 +2,7 @@
 
 extern crate env_logger;
 #[macro_use] extern crate clap;
+extern crate num_cpus;
 extern crate kailua_env;
 extern crate kailua_diag;
 extern crate kailua_syntax;
@@ -9,38 +10,69 @@ extern crate kailua_check;
 extern crate kailua_workspace;
 extern crate kailua_langsvr;
 
-use std::io;
+use std::io::{self, Write};
+use std::fs;
+use std::usize;
 use std::path::Path;
-use clap::{App, Error, ErrorKind};
-use kailua_workspace::Workspace;
-
-fn parse_and_check(workspace: &Workspace, quiet: bool) -> Result<(), String> {
+use std::collections::BTreeMap;
+use clap::{App, ArgMatches, Error, ErrorKind};
+use kailua_workspace::{Config, Workspace};
+use kailua_workspace::graph::RequireGraph;
+use kailua_workspace::symbols::SymbolMap;
+use kailua_workspace::hotspots::HotSpotReport;
+use kailua_workspace::assumptions::AssumptionReport;
+use kailua_workspace::declaration::Declaration;
+
+fn parse_and_check(workspace: &Workspace, quiet: bool, jobs: usize, module_cache: bool,
+                   graph: &mut RequireGraph, symbols: &mut SymbolMap,
+                   hotspots: &mut HotSpotReport,
+                   assumptions: &mut AssumptionReport,
+                   declarations: &mut BTreeMap<Vec<u8>, Declaration>) -> Result<(), String> {
     use std::str;
     use std::io;
     use std::cell::RefCell;
     use std::rc::Rc;
     use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
 
     use kailua_env::{Span, Spanned, Source, SourceFile, WithLoc};
     use kailua_diag::message::{Locale, Localize};
-    use kailua_diag::report::{Stop, Kind, Report, ConsoleReport, TrackMaxKind};
-    use kailua_syntax::{parse_chunk, Chunk};
-    use kailua_check::check_from_chunk_with_preloading;
+    use kailua_diag::report::{Stop, Kind, Report, ConsoleReport, CollectedReport, TrackMaxKind};
+    use kailua_syntax::{parse_chunk_with_language, Chunk};
+    use kailua_syntax::lang::Language;
+    use kailua_check::{check_from_chunk_with_preloading, verify_assertions};
     use kailua_check::env::Context;
     use kailua_check::options::FsSource;
     use kailua_workspace::WorkspaceOptions;
+    use kailua_workspace::graph::RecordingOptions;
+    use kailua_workspace::incremental::{self, IncrementalState, IncrementalSource};
+    use kailua_workspace::incremental::TrackReportedUnits;
+    use kailua_workspace::preparse::{self, ChunkCache};
 
     struct LocalFsSource {
         source: Rc<RefCell<Source>>,
+        cache: Rc<RefCell<ChunkCache>>,
+        language: Language,
     }
 
     impl FsSource for LocalFsSource {
         fn chunk_from_path(&self, resolved_path: Spanned<&Path>,
                            report: &Report) -> Result<Option<Chunk>, Option<Stop>> {
+            // the file may have been already parsed in advance
+            if let Some(parsed) = self.cache.borrow_mut().take(&resolved_path) {
+                return match parsed.replay(report) {
+                    Ok(chunk) => Ok(Some(chunk)),
+                    Err(Stop) => Err(Some(Stop)), // we have already reported parsing errors
+                };
+            }
+
             match SourceFile::from_file(&resolved_path) {
                 Ok(file) => {
                     let span = self.source.borrow_mut().add(file);
-                    if let Ok(chunk) = parse_chunk(&self.source.borrow(), span, report) {
+                    let chunk = parse_chunk_with_language(&self.source.borrow(), span,
+                                                          self.language, report);
+                    if let Ok(chunk) = chunk {
                         Ok(Some(chunk))
                     } else {
                         Err(Some(Stop)) // we have already reported parsing errors
@@ -76,17 +108,77 @@ fn parse_and_check(workspace: &Workspace, quiet: bool) -> Result<(), String> {
         }
     }
 
-    let source = Rc::new(RefCell::new(Source::new()));
+    // shows the pre-parsing progress in a single line of the standard error,
+    // only when the parsing takes long enough to be noticed
+    struct ConsoleProgress {
+        started: Instant,
+        last_shown: Mutex<Option<Instant>>,
+    }
+
+    impl preparse::Progress for ConsoleProgress {
+        fn update(&self, done: usize, total: usize, remaining: Duration) {
+            let now = Instant::now();
+            let mut last_shown = self.last_shown.lock().unwrap();
+            if done < total {
+                if now - self.started < Duration::from_millis(500) {
+                    return;
+                }
+                if last_shown.map_or(false, |t| now - t < Duration::from_millis(100)) {
+                    return;
+                }
+                *last_shown = Some(now);
+                eprint!("\rParsing files... {}/{} (about {}s left)   ",
+                        done, total, remaining.as_secs() + 1);
+            } else if last_shown.is_some() {
+                eprint!("\r{:60}\r", "");
+            }
+        }
+    }
+
+    // parsing is independent to each other, so we can parse all files in parallel
+    // while deferring reports until the checker actually requires them
+    let mut source = Source::new();
+    let sys = workspace.sys();
+    let mut paths = preparse::discover_files_with_policy(workspace.base_dir(), &sys.paths);
+    for start_path in workspace.start_paths() {
+        if !paths.iter().any(|path| sys.paths.same(&*sys.fs, path, start_path)) {
+            paths.push(start_path.to_owned());
+        }
+    }
+
+    // the history from previous runs puts costly files first, so that they don't finish last
+    let history_path = workspace.history_path();
+    let mut history = preparse::History::load(&*sys.fs, &history_path).unwrap_or_else(|e| {
+        if !quiet {
+            eprintln!("warning: couldn't read a history `{}`: {}", history_path.display(), e);
+        }
+        preparse::History::new()
+    });
+    let progress: Option<Arc<preparse::Progress>> = if quiet {
+        None
+    } else {
+        Some(Arc::new(ConsoleProgress { started: Instant::now(), last_shown: Mutex::new(None) }))
+    };
+    let cache = preparse::preparse_with_sys(paths, &mut source, workspace.message_locale(),
+                                            workspace.language(), jobs,
+                                            preparse::Order::CostlyFirst,
+                                            &mut history, progress, sys);
+    let cache = Rc::new(RefCell::new(cache));
+
+    let source = Rc::new(RefCell::new(source));
     let report = Rc::new(TrackMaxKind::new(OptionalConsoleReport {
         quiet: quiet,
         report: ConsoleReport::with_locale(source.clone(), workspace.message_locale()),
     }));
 
-    // TODO multiple outputs should deduplicate warnings if possible
-    for start_path in workspace.start_paths() {
-        let mut context = Context::new(report.clone());
+    // checking is sequential, so cheap start paths go first for a faster feedback
+    let mut start_paths = workspace.start_paths().to_owned();
+    history.sort_by_check_cost(&mut start_paths, preparse::Order::CheapFirst, sys);
 
-        let fssource = LocalFsSource { source: source.clone() };
+    // TODO multiple outputs should deduplicate warnings if possible
+    for start_path in &start_paths {
+        let fssource = LocalFsSource { source: source.clone(), cache: cache.clone(),
+                                       language: workspace.language() };
         let filechunk = match fssource.chunk_from_path((**start_path).without_loc(), &report) {
             Ok(Some(chunk)) => chunk,
             _ => {
@@ -99,10 +191,108 @@ fn parse_and_check(workspace: &Workspace, quiet: bool) -> Result<(), String> {
             return Err(format!("Stopped due to prior errors"));
         }
 
-        let opts = Rc::new(RefCell::new(WorkspaceOptions::new(fssource, start_path, workspace)));
+        // without the module cache the state is initially empty and nothing gets stubbed
+        let cache_path = workspace.cache_path(start_path);
+        let fingerprint = workspace.fingerprint();
+        let state = if module_cache {
+            IncrementalState::load(&*sys.fs, &cache_path, fingerprint).unwrap_or_else(|e| {
+                if !quiet {
+                    eprintln!("warning: couldn't read a cache `{}`: {}", cache_path.display(), e);
+                }
+                IncrementalState::new()
+            })
+        } else {
+            IncrementalState::new()
+        };
+        let state = Rc::new(RefCell::new(state));
+
+        // reports are collected for each round, as stubbed modules may have to be checked again
+        let (mut context, collected, opts, output) = loop {
+            let collected = Rc::new(CollectedReport::new(workspace.message_locale()));
+            let mut context = Context::new(TrackReportedUnits::new(collected.clone()));
+
+            let fssource = LocalFsSource { source: source.clone(), cache: cache.clone(),
+                                           language: workspace.language() };
+            let mut fssource = IncrementalSource::new(fssource, state.clone());
+            let hash_source = source.clone();
+            fssource.set_content_hash(move |unit| {
+                hash_source.borrow().file(unit).map(|f| incremental::hash_contents(f.data()))
+            });
+            let opts = WorkspaceOptions::new(vec![fssource], start_path, workspace);
+            let opts = Rc::new(RefCell::new(RecordingOptions::new(opts)));
+
+            let output = check_from_chunk_with_preloading(&mut context, filechunk.clone(),
+                                                          opts.clone(), workspace.preload());
+
+            let reported = context.report().units();
+            if !state.borrow_mut().finish_check(&opts.borrow(), &context, &reported) {
+                break (context, collected, opts, output);
+            }
+        };
+
+        if module_cache {
+            if let Err(e) = state.borrow_mut().save(&*sys.fs, &cache_path, fingerprint) {
+                if !quiet {
+                    eprintln!("warning: couldn't write a cache `{}`: {}", cache_path.display(), e);
+                }
+            }
+        }
+
+        // only the last round has checked every module not stubbed
+        history.record_checks(&context, &source.borrow(), sys);
+        if module_cache {
+            if let Err(e) = history.save(&*sys.fs, &history_path) {
+                if !quiet {
+                    eprintln!("warning: couldn't write a history `{}`: {}",
+                              history_path.display(), e);
+                }
+            }
+        }
+
+        // workspace assertions are verified only when the checking has been completed
+        let start_unit = filechunk.block.span.unit();
+        let output = output.and_then(|()| {
+            let failures = verify_assertions(&mut context, workspace.asserts(), opts.clone())?;
+            for msg in failures {
+                context.report().add_span(Kind::Error, Span::dummy(), &msg)?;
+            }
+            Ok(())
+        });
+        graph.merge(opts.borrow().graph(&source.borrow(), workspace.base_dir(), &sys.paths,
+                                        start_unit));
+        symbols.add_output(&context, &source.borrow(), workspace.base_dir(), &sys.paths);
+        hotspots.add_output(&context, &source.borrow(), workspace.base_dir(), &sys.paths);
+        assumptions.add_output(&context, &source.borrow(), workspace.base_dir(), &sys.paths,
+                               workspace.message_locale());
+        for (name, &unit) in opts.borrow().modules() {
+            // modules already loaded from `.kailua` files need no declaration
+            let is_declaration = source.borrow().file(unit).map_or(true, |file| {
+                file.path().ends_with(".kailua")
+            });
+            if is_declaration {
+                continue;
+            }
+            if let Some(module) = context.get_module(name) {
+                if let Some(decl) = Declaration::from_module(name, module, &context, unit) {
+                    declarations.insert(name.clone(), decl);
+                }
+            }
+        }
+
+        drop(context);
+        let collected = Rc::try_unwrap(collected).ok().expect("reports still shared");
+        for (kind, span, msg) in collected.into_coded_reports() {
+            let _ = report.add_span(kind, span, &msg);
+        }
 
-        let output = check_from_chunk_with_preloading(&mut context, filechunk, opts,
-                                                      workspace.preload());
+        if !quiet {
+            for data in opts.borrow().inner().data_files() {
+                if let Some(file) = source.borrow().file(data.span.unit()) {
+                    eprintln!("note: `{}` has been checked as a data file with {} entries \
+                               (summarized as `{}`)", file.path(), data.entries, data.summary);
+                }
+            }
+        }
         if !(output.is_ok() && report.can_continue()) {
             return Err(format!("Stopped due to prior errors"));
         }
@@ -135,6 +325,11 @@ fn build_app() -> App<'static, 'static> {
                 "Overrides a default configuration path.\n\
                  Defaults to `BASE_DIR/kailua.json` or `BASE_DIR/.vscode/kailua.json`, \
                  whichever comes first.")
+            (@arg set: --set [KEY_VALUE] +multiple number_of_values(1)
+                "Overrides a configuration value, given as `KEY=VALUE`. \
+                 The key is a dotted path like `preload.open`, and the value is \
+                 a JSON value or a string (e.g. `--set preload.open=lua51`).\n\
+                 Can be given multiple times; later values take precedence.")
             (@arg add_package_path: -p --("add-package-path") [TEMPLATE] +multiple
                 conflicts_with[set_package_path]
                 "Adds a given template (a path with a hole `?`) to `package.path`.\n\
@@ -151,8 +346,50 @@ fn build_app() -> App<'static, 'static> {
             (@arg set_package_cpath: --("set-package-cpath") [TEMPLATES]
                 "Sets `package.cpath` to the exact string given (including separator `;`).\n\
                  Similar to `--add-package-cpath` but will ignore the default values.")
+            (@arg upgrade_config: --("upgrade-config")
+                "Rewrites the configuration file written for older versions of Kailua \
+                 to the current format. Comments in the file will be lost.")
+            (@arg export_deps: --("export-deps") [FORMAT]
+                possible_values(&["json", "starlark"])
+                "Prints the module dependency graph discovered while checking \
+                 to the standard output, even on error.\n\
+                 `json` prints an object from files to their dependencies, \
+                 `starlark` prints a `lua_library` rule per file for Bazel or Buck.")
+            (@arg emit_symbol_map: --("emit-symbol-map") [PATH]
+                "Writes positions of global names defined in the checked sources \
+                 to given path as JSON, even on error.\n\
+                 This can be used to map runtime errors from minified scripts \
+                 back to the checked sources.")
+            (@arg stats: --stats [N]
+                min_values(0)
+                "Prints type inference statistics per module and per function \
+                 (or the top-level code) to the standard output, most costly first, \
+                 even on error.\n\
+                 Only N functions are listed if given. Large numbers of type variables, \
+                 union widenings and failed type assertions indicate \
+                 where type annotations would help.")
+            (@arg assumptions: --assumptions
+                "Prints all `--# assume` directives used during the checking \
+                 with their declared types to the standard output, even on error.\n\
+                 The checker trusts them without any verification, \
+                 so they are worth reviewing from time to time.")
+            (@arg emit_declarations: --("emit-declarations") [DIR]
+                "Writes a declaration (`.kailua` file) with inferred types \
+                 for each module loaded by `require` to given directory, even on error.\n\
+                 A module `a.b` is written to `DIR/a/b.kailua`, \
+                 which other workspaces can use by adding `DIR/?` to `package_path`.")
+            (@arg cache: --cache
+                "Saves signatures of modules without any report to `BASE_DIR/.kailua-cache`, \
+                 and skips checking them in later runs until they or their dependencies change.\n\
+                 Also records how long each file took to parse and check, so that later runs \
+                 can parse costly files first and check cheap start paths first.\n\
+                 Ignored with `--emit-symbol-map`, `--emit-declarations`, `--stats` \
+                 or `--assumptions`, which need every module to be checked.")
             (@arg quiet: -q --quiet
                 "Suppresses all reports.")
+            (@arg jobs: -j --jobs [N]
+                "Sets the number of threads used for parsing files in advance.\n\
+                 Defaults to the number of CPUs.")
             (@arg message_locale: -l --("message-locale") [LOCALE]
                 "Sets the message locales. Defaults to the system language.")
             (@arg path:
@@ -162,6 +399,49 @@ fn build_app() -> App<'static, 'static> {
                  or a path to the Lua file in which case the configuration path should be given. \
                  Defaults to the current directory.")
         )
+        (@subcommand doctor =>
+            (@setting UnifiedHelpMessage)
+            (@setting NextLineHelp)
+            (about:
+                "Validates the workspace setup without checking any source.\n\
+                 \n\
+                 Prints findings (missing start paths, search paths pointing to \
+                 nonexistent directories, unknown preloads and so on) with suggested fixes \
+                 to the standard output, then terminates with an exit code 1 on error.")
+            (@arg config: -c --config [PATH]
+                "Overrides a default configuration path. Same as in `kailua check`.")
+            (@arg set: --set [KEY_VALUE] +multiple number_of_values(1)
+                "Overrides a configuration value, given as `KEY=VALUE`. \
+                 Same as in `kailua check`.")
+            (@arg json: --json
+                "Prints findings as a JSON array of objects with `severity`, `code`, \
+                 `message` and optionally `fix`.")
+            (@arg path:
+                "A path to the base directory or the Lua file to start checking. \
+                 Same as in `kailua check`.")
+        )
+        (@subcommand repro =>
+            (@setting UnifiedHelpMessage)
+            (@setting NextLineHelp)
+            (about:
+                "Exports a self-contained reproducer for bug reports.\n\
+                 \n\
+                 Checks the workspace and writes the effective configuration, all files \
+                 loaded by the checker and the version of Kailua into a single JSON file. \
+                 The absolute path to the workspace is not recorded.")
+            (@arg config: -c --config [PATH]
+                "Overrides a default configuration path. Same as in `kailua check`.")
+            (@arg set: --set [KEY_VALUE] +multiple number_of_values(1)
+                "Overrides a configuration value, given as `KEY=VALUE`. \
+                 Same as in `kailua check`.")
+            (@arg output: -o --output <PATH>
+                "A path to the reproducer to be written.")
+            (@arg strip_comments: --("strip-comments")
+                "Blanks out ordinary comments (but not Kailua meta comments) in the files.")
+            (@arg path:
+                "A path to the base directory or the Lua file to start checking. \
+                 Same as in `kailua check`.")
+        )
         (@subcommand langsvr =>
             (about: "Launches a language server. Not to be used directly.")
             (@group target =>
@@ -185,14 +465,56 @@ fn io_error(s: &str) -> ! {
 }
 
 fn io_error_while(e: io::Error, s: &str) -> ! {
-    let mut e = Error::from(e);
-    e.message = format!("{} (while {})", e.message, s);
-    e.exit();
+    // `Error::from` uses `Error::description`, which loses the position in config errors
+    io_error(&format!("{} (while {})", e, s));
+}
+
+// reads the configuration from `path`, `--config` and `--set` options
+fn read_config(matches: &ArgMatches) -> Config {
+    let path = Path::new(matches.value_of("path").unwrap_or("."));
+
+    let mut config = if path.is_dir() {
+        Config::from_base_dir(path.to_owned())
+    } else {
+        Config::from_start_path(path.to_owned())
+    };
+
+    if let Some(config_path) = matches.value_of("config") {
+        config.use_default_global_config_path();
+        match config.set_config_path(Path::new(config_path).to_owned()) {
+            Ok(true) => {}
+            Ok(false) => {
+                io_error(&format!("Couldn't open a configuration file `{}`", config_path));
+            }
+            Err(e) => {
+                io_error_while(e, &format!("opening a configuration file `{}`", config_path));
+            }
+        }
+    } else {
+        config.use_default_config_paths();
+    }
+
+    if let Some(sets) = matches.values_of("set") {
+        for set in sets {
+            let mut kv = set.splitn(2, '=');
+            if let (Some(key), Some(value)) = (kv.next(), kv.next()) {
+                if let Err(e) = config.set_override(key, value) {
+                    io_error_while(e, &format!("applying `--set {}`", set));
+                }
+            } else {
+                io_error(&format!("`--set` should be given as `KEY=VALUE`, got `{}`", set));
+            }
+        }
+    }
+
+    for warning in config.config_warnings() {
+        eprintln!("warning: {}", warning);
+    }
+    config
 }
 
 pub fn main() {
     use kailua_diag::message::{Locale, get_message_locale};
-    use kailua_workspace::{Config, Workspace};
     use kailua_langsvr::Target;
 
     env_logger::init().unwrap();
@@ -200,26 +522,15 @@ pub fn main() {
     let matches = build_app().get_matches();
 
     if let Some(ref matches) = matches.subcommand_matches("check") {
-        let path = Path::new(matches.value_of("path").unwrap_or("."));
-
-        let mut config = if path.is_dir() {
-            Config::from_base_dir(path.to_owned())
-        } else {
-            Config::from_start_path(path.to_owned())
-        };
+        let mut config = read_config(matches);
 
-        if let Some(config_path) = matches.value_of("config") {
-            match config.set_config_path(Path::new(config_path).to_owned()) {
-                Ok(true) => {}
-                Ok(false) => {
-                    io_error(&format!("Couldn't open a configuration file `{}`", config_path));
-                }
-                Err(e) => {
-                    io_error_while(e, &format!("opening a configuration file `{}`", config_path));
+        if matches.is_present("upgrade_config") {
+            if let Some(config_path) = config.config_path() {
+                if let Err(e) = kailua_workspace::migrate::rewrite_config(config_path) {
+                    io_error_while(e, &format!("upgrading a configuration file `{}`",
+                                               config_path.display()));
                 }
             }
-        } else {
-            config.use_default_config_paths();
         }
 
         let parse_package_paths = |set: &str, add: &str| {
@@ -245,6 +556,28 @@ pub fn main() {
             parse_package_paths("set_package_cpath", "add_package_cpath").or(config.package_cpath);
 
         let quiet = matches.is_present("quiet");
+        let module_cache = matches.is_present("cache") &&
+                           !matches.is_present("emit_symbol_map") &&
+                           !matches.is_present("emit_declarations") &&
+                           !matches.is_present("stats") && !matches.is_present("assumptions");
+
+        let stats_limit = if let Some(limit) = matches.value_of("stats") {
+            match limit.parse() {
+                Ok(limit) => limit,
+                _ => invalid_value(&format!("Invalid number of functions `{}`", limit)),
+            }
+        } else {
+            usize::MAX
+        };
+
+        let jobs = if let Some(jobs) = matches.value_of("jobs") {
+            match jobs.parse() {
+                Ok(jobs) if jobs > 0 => jobs,
+                _ => invalid_value(&format!("Invalid number of jobs `{}`", jobs)),
+            }
+        } else {
+            num_cpus::get()
+        };
 
         let message_locale = if let Some(locale) = matches.value_of("message_locale") {
             if let Some(locale) = Locale::new(locale) {
@@ -257,7 +590,48 @@ pub fn main() {
         };
 
         if let Some(workspace) = Workspace::new(&config, message_locale) {
-            if let Err(e) = parse_and_check(&workspace, quiet) {
+            let mut graph = RequireGraph::new();
+            let mut symbols = SymbolMap::new();
+            let mut hotspots = HotSpotReport::new();
+            let mut assumptions = AssumptionReport::new();
+            let mut declarations = BTreeMap::new();
+            let result = parse_and_check(&workspace, quiet, jobs, module_cache,
+                                         &mut graph, &mut symbols, &mut hotspots,
+                                         &mut assumptions, &mut declarations);
+            match matches.value_of("export_deps") {
+                Some("json") => println!("{}", graph.to_json()),
+                Some("starlark") => print!("{}", graph.to_starlark("lua_library")),
+                _ => {}
+            }
+            if let Some(path) = matches.value_of("emit_symbol_map") {
+                let json = format!("{:#}\n", symbols.to_json());
+                let written = fs::File::create(path).and_then(|mut f| f.write_all(json.as_bytes()));
+                if let Err(e) = written {
+                    io_error_while(e, &format!("writing a symbol map `{}`", path));
+                }
+            }
+            if let Some(dir) = matches.value_of("emit_declarations") {
+                for (name, decl) in &declarations {
+                    let path = Path::new(dir).join(Declaration::relative_path(name));
+                    let written = path.parent().map_or(Ok(()), |dir| fs::create_dir_all(dir))
+                        .and_then(|()| fs::File::create(&path))
+                        .and_then(|mut f| f.write_all(decl.code.as_bytes()));
+                    if let Err(e) = written {
+                        io_error_while(e, &format!("writing a declaration `{}`", path.display()));
+                    }
+                    if decl.lossy && !quiet {
+                        eprintln!("warning: some types in `{}` could not be expressed \
+                                   and have been replaced with `WHATEVER`", path.display());
+                    }
+                }
+            }
+            if matches.is_present("stats") {
+                print!("{}", hotspots.to_text(stats_limit));
+            }
+            if matches.is_present("assumptions") {
+                print!("{}", assumptions.to_text());
+            }
+            if let Err(e) = result {
                 // clap does not have something like ErrorKind::Other :(
                 io_error(&e);
             }
@@ -273,6 +647,68 @@ pub fn main() {
         return;
     }
 
+    if let Some(ref matches) = matches.subcommand_matches("doctor") {
+        use kailua_workspace::doctor::Severity;
+
+        let config = read_config(matches);
+        let workspace = if let Some(workspace) = Workspace::new(&config, Locale::dummy()) {
+            workspace
+        } else {
+            io_error(
+                &format!("Couldn't open a default configuration file at `{}` or `{}`",
+                         config.base_dir().join("kailua.json").display(),
+                         config.base_dir().join(".vscode").join("kailua.json").display())
+            );
+        };
+
+        let findings = workspace.doctor();
+        if matches.is_present("json") {
+            println!("{:#}", kailua_workspace::doctor::to_json(&findings));
+        } else if findings.is_empty() {
+            println!("No problems found.");
+        } else {
+            for finding in &findings {
+                println!("{}[{}]: {}", finding.severity.name(), finding.code, finding.message);
+                if let Some(ref fix) = finding.fix {
+                    println!("    fix: {}", fix);
+                }
+            }
+        }
+
+        let errors = findings.iter().filter(|f| f.severity == Severity::Error).count();
+        if errors > 0 {
+            io_error(&format!("Found {} error(s) in the workspace setup", errors));
+        }
+
+        return;
+    }
+
+    if let Some(ref matches) = matches.subcommand_matches("repro") {
+        use kailua_workspace::repro::ReproOptions;
+
+        let config = read_config(matches);
+        let workspace = if let Some(workspace) = Workspace::new(&config, Locale::dummy()) {
+            workspace
+        } else {
+            io_error(
+                &format!("Couldn't open a default configuration file at `{}` or `{}`",
+                         config.base_dir().join("kailua.json").display(),
+                         config.base_dir().join(".vscode").join("kailua.json").display())
+            );
+        };
+
+        let path = matches.value_of("output").unwrap();
+        let options = ReproOptions { strip_comments: matches.is_present("strip_comments") };
+        match workspace.export_repro(Path::new(path), &options) {
+            Ok(bundle) => {
+                println!("Wrote a reproducer with {} file(s) to `{}`.", bundle.files.len(), path);
+            }
+            Err(e) => io_error_while(e, &format!("writing a reproducer `{}`", path)),
+        }
+
+        return;
+    }
+
     if let Some(ref matches) = matches.subcommand_matches("langsvr") {
         // delegate to kailua_langsvr
         let target = if let Some(addr) = matches.value_of("tcp") {